@@ -0,0 +1,303 @@
+use regex::Regex;
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::transcript::TranscriptCue;
+
+/// A stretch of the transcript that looks like a sponsor read
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct AdSegment {
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+    pub evidence: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sponsor: Option<String>,
+    pub promo_codes: Vec<String>,
+}
+
+/// A SponsorBlock-style skip range for a player or post-processing script
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct SkipListEntry {
+    pub segment: [f64; 2],
+    pub category: String,
+    #[serde(rename = "actionType")]
+    pub action_type: String,
+}
+
+/// Phrases that typically open a sponsor read
+const SPONSOR_PHRASES: &[&str] = &[
+    "brought to you by",
+    "sponsored by",
+    "our sponsor",
+    "today's sponsor",
+    "a word from our sponsor",
+    "support for this podcast comes from",
+    "support for this show comes from",
+];
+
+/// Promo codes are strong enough on their own to open an ad segment
+const PROMO_CODE_PATTERN: &str = r"\b(?:promo|offer|discount|coupon)\s+code\b|\buse\s+code\s+\w+";
+
+/// URLs and discounts only extend an ad segment that is already open
+const PROMO_DETAIL_PATTERN: &str =
+    r"\b[a-z0-9-]+\.(?:com|io|co|net|org|fm)(?:/[a-z0-9/_-]+)?\b|\b\d{1,3}\s*(?:%|percent)\s+off\b";
+
+/// Captures the capitalized name that follows a sponsorship phrase
+const SPONSOR_NAME_PATTERN: &str = r"(?i:brought to you by|sponsored by|today's sponsor is|our sponsor is|support for this (?:podcast|show) comes from)\s+([A-Z0-9][\w&'-]*(?:\s+[A-Z0-9][\w&'-]*)*)";
+
+/// Captures the code itself from "use code X" / "promo code X"
+const PROMO_CODE_VALUE_PATTERN: &str =
+    r"(?i:use|promo|offer|discount|coupon)\s+code\s+([A-Za-z0-9]+)";
+
+/// Longest stretch, measured from the opening cue, that a single ad read may cover
+pub const MAX_AD_SECONDS: f64 = 120.0;
+
+/// Collects the sponsorship cues found in a piece of transcript text
+fn find_ad_evidence(text: &str, code_re: &Regex, detail_re: &Regex) -> Vec<String> {
+    let lower = text.to_lowercase();
+
+    let mut evidence: Vec<String> = SPONSOR_PHRASES
+        .iter()
+        .filter(|phrase| lower.contains(*phrase))
+        .map(|phrase| phrase.to_string())
+        .collect();
+    evidence.extend(code_re.find_iter(&lower).map(|m| m.as_str().to_string()));
+    evidence.extend(detail_re.find_iter(&lower).map(|m| m.as_str().to_string()));
+
+    evidence
+}
+
+/// Marks likely ad segments using sponsorship phrases, promo codes, URLs and speaker changes
+pub fn detect_ad_segments(cues: &[TranscriptCue]) -> Result<Vec<AdSegment>> {
+    let code_re = Regex::new(PROMO_CODE_PATTERN)?;
+    let detail_re = Regex::new(PROMO_DETAIL_PATTERN)?;
+
+    let mut segments: Vec<AdSegment> = Vec::new();
+    let mut i = 0;
+
+    while i < cues.len() {
+        let opening = &cues[i];
+        let lower = opening.text.to_lowercase();
+        let opens_ad =
+            SPONSOR_PHRASES.iter().any(|phrase| lower.contains(phrase)) || code_re.is_match(&lower);
+
+        if !opens_ad {
+            i += 1;
+            continue;
+        }
+
+        let mut evidence = find_ad_evidence(&opening.text, &code_re, &detail_re);
+        let mut last = i;
+
+        // Extend the segment while later cues keep carrying promo details,
+        // stopping when a different speaker takes over
+        for (j, cue) in cues.iter().enumerate().skip(i + 1) {
+            if cue.begin - opening.begin > MAX_AD_SECONDS {
+                break;
+            }
+            if opening.speaker.is_some() && cue.speaker.is_some() && cue.speaker != opening.speaker
+            {
+                break;
+            }
+
+            let found = find_ad_evidence(&cue.text, &code_re, &detail_re);
+            if !found.is_empty() {
+                evidence.extend(found);
+                last = j;
+            }
+        }
+
+        let mut unique = Vec::new();
+        for item in evidence {
+            if !unique.contains(&item) {
+                unique.push(item);
+            }
+        }
+
+        let text = cues[i..=last]
+            .iter()
+            .map(|cue| cue.text.as_str())
+            .collect::<Vec<&str>>()
+            .join(" ");
+
+        segments.push(AdSegment {
+            start_seconds: opening.begin,
+            end_seconds: cues[last].end,
+            evidence: unique,
+            sponsor: extract_sponsor(&text)?,
+            promo_codes: extract_promo_codes(&text)?,
+        });
+
+        i = last + 1;
+    }
+
+    Ok(segments)
+}
+
+/// Extracts the sponsor name announced in an ad read, if any
+pub fn extract_sponsor(text: &str) -> Result<Option<String>> {
+    let re = Regex::new(SPONSOR_NAME_PATTERN)?;
+
+    Ok(re
+        .captures(text)
+        .and_then(|captures| captures.get(1))
+        .map(|m| {
+            m.as_str()
+                .trim_end_matches(['.', ',', '\'', '-'])
+                .to_string()
+        }))
+}
+
+/// Extracts the promo codes mentioned in an ad read, upper-cased and de-duplicated
+pub fn extract_promo_codes(text: &str) -> Result<Vec<String>> {
+    let re = Regex::new(PROMO_CODE_VALUE_PATTERN)?;
+
+    let mut codes = Vec::new();
+    for captures in re.captures_iter(text) {
+        let code = captures[1].to_uppercase();
+        if !codes.contains(&code) {
+            codes.push(code);
+        }
+    }
+
+    Ok(codes)
+}
+
+/// Converts ad segments into SponsorBlock-style skip entries
+pub fn build_skip_list(segments: &[AdSegment]) -> Vec<SkipListEntry> {
+    segments
+        .iter()
+        .map(|segment| SkipListEntry {
+            segment: [segment.start_seconds, segment.end_seconds],
+            category: "sponsor".to_string(),
+            action_type: "skip".to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(begin: f64, end: f64, speaker: &str, text: &str) -> TranscriptCue {
+        TranscriptCue {
+            begin,
+            end,
+            speaker: Some(speaker.to_string()),
+            text: text.to_string(),
+        }
+    }
+
+    /// Unit test - detect_ad_segments marks a sponsor read with its promo details
+    #[test]
+    fn test_detect_ad_segments_marks_sponsor_read() {
+        // Given a conversation interrupted by a sponsor read
+        let cues = vec![
+            cue(0.0, 10.0, "SPEAKER_1", "Welcome back to the show."),
+            cue(
+                10.0,
+                20.0,
+                "SPEAKER_1",
+                "This episode is brought to you by Acme.",
+            ),
+            cue(
+                20.0,
+                30.0,
+                "SPEAKER_1",
+                "Acme makes the best widgets around.",
+            ),
+            cue(
+                30.0,
+                40.0,
+                "SPEAKER_1",
+                "Go to acme.com/podcast and use code SHOW for 20% off.",
+            ),
+            cue(40.0, 50.0, "SPEAKER_2", "So, about that interview."),
+        ];
+
+        // When we detect ads
+        let segments = detect_ad_segments(&cues).unwrap();
+
+        // Then the read is marked from the sponsor phrase to the promo code
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].start_seconds, 10.0);
+        assert_eq!(segments[0].end_seconds, 40.0);
+        assert!(segments[0]
+            .evidence
+            .contains(&"brought to you by".to_string()));
+        assert!(segments[0].evidence.contains(&"use code show".to_string()));
+
+        // And the sponsor and promo code are pulled out of the read
+        assert_eq!(segments[0].sponsor.as_deref(), Some("Acme"));
+        assert_eq!(segments[0].promo_codes, vec!["SHOW".to_string()]);
+    }
+
+    /// Unit test - extract_sponsor reads multi-word sponsor names
+    #[test]
+    fn test_extract_sponsor_reads_multi_word_names() {
+        // Given an ad read for a sponsor with a multi-word name
+        let text = "Support for this podcast comes from Acme Widget Co. They build things.";
+
+        // When we extract the sponsor
+        let sponsor = extract_sponsor(text).unwrap();
+
+        // Then the full capitalized name is returned without trailing punctuation
+        assert_eq!(sponsor.as_deref(), Some("Acme Widget Co"));
+    }
+
+    /// Unit test - extract_promo_codes de-duplicates repeated codes
+    #[test]
+    fn test_extract_promo_codes_deduplicates() {
+        // Given a read that repeats its promo code
+        let text = "Use code pod20 at checkout. Again, that's promo code POD20.";
+
+        // When we extract promo codes
+        let codes = extract_promo_codes(text).unwrap();
+
+        // Then the code appears once, upper-cased
+        assert_eq!(codes, vec!["POD20".to_string()]);
+    }
+
+    /// Unit test - detect_ad_segments ignores regular conversation
+    #[test]
+    fn test_detect_ad_segments_ignores_regular_conversation() {
+        // Given a transcript that only mentions a website in passing
+        let cues = vec![
+            cue(
+                0.0,
+                10.0,
+                "SPEAKER_1",
+                "I read about it on example.com last week.",
+            ),
+            cue(10.0, 20.0, "SPEAKER_2", "That is a great point."),
+        ];
+
+        // When we detect ads
+        let segments = detect_ad_segments(&cues).unwrap();
+
+        // Then nothing is marked
+        assert!(segments.is_empty(), "Passing URL mentions are not ads");
+    }
+
+    /// Unit test - detect_ad_segments ends a segment when the speaker changes
+    #[test]
+    fn test_detect_ad_segments_stops_at_speaker_change() {
+        // Given a sponsor read followed by a guest mentioning a URL
+        let cues = vec![
+            cue(0.0, 10.0, "SPEAKER_1", "Today's sponsor is Acme."),
+            cue(
+                10.0,
+                20.0,
+                "SPEAKER_2",
+                "I found my job on acme.com actually.",
+            ),
+        ];
+
+        // When we detect ads
+        let segments = detect_ad_segments(&cues).unwrap();
+
+        // Then the segment stays with the host's read
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].end_seconds, 10.0);
+    }
+}
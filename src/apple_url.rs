@@ -0,0 +1,523 @@
+use serde::Serialize;
+use url::Url;
+
+use crate::error::{Error, Result};
+
+/// Hosts serving Apple Podcasts pages; the iTunes and Apple Music ones are legacy hosts whose
+/// podcast links redirect to `podcasts.apple.com`
+const APPLE_PODCASTS_HOSTS: &[&str] = &[
+    "podcasts.apple.com",
+    "itunes.apple.com",
+    "geo.itunes.apple.com",
+    "music.apple.com",
+];
+
+/// Schemes of old iTunes Store links, which open the same pages in the iTunes app
+const ITUNES_SCHEMES: &[&str] = &["itms", "itmss", "itms-pcast", "itms-podcasts"];
+
+/// The legacy iTunes media type (`mt=`) of podcasts; other values are music, apps and so on
+const PODCAST_MEDIA_TYPE: &str = "2";
+
+/// Path of the pre-2013 iTunes Store page for a show, which names it with `?id=`
+const LEGACY_SHOW_PATH: &[&str] = &["WebObjects", "MZStore.woa", "wa", "viewPodcast"];
+
+/// What an Apple Podcasts URL points at
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UrlKind {
+    /// `/podcast/<slug>/id<show>?i=<episode>`
+    Episode,
+    /// `/podcast/<slug>/id<show>`
+    Show,
+    /// `/channel/<slug>/id<channel>`
+    Channel,
+}
+
+/// An Apple Podcasts URL broken into its IDs
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct ParsedUrl {
+    pub kind: UrlKind,
+    /// Two-letter storefront code, `us` when the URL has none
+    pub storefront: String,
+    /// Apple show ID, for episode and show URLs
+    pub podcast_id: Option<String>,
+    /// Apple episode ID from `?i=`, for episode URLs
+    pub episode_id: Option<String>,
+    /// Apple channel ID, for channel URLs
+    pub channel_id: Option<String>,
+    /// The URL on `podcasts.apple.com` without tracking parameters or fragment
+    pub url: String,
+}
+
+impl ParsedUrl {
+    /// Parses an Apple Podcasts episode, show or channel URL
+    ///
+    /// Legacy `itunes.apple.com` and `music.apple.com` links, `itms://` app links and
+    /// `viewPodcast?id=` store pages are unwrapped into the same IDs and a current
+    /// `podcasts.apple.com` URL. Anything that is not a URL is [`Error::InvalidUrl`];
+    /// URLs of other sites, and Apple URLs of another shape or media type, are
+    /// [`Error::NotApplePodcasts`].
+    pub fn parse(raw: &str) -> Result<ParsedUrl> {
+        let raw = raw.trim();
+        let parsed = Url::parse(raw).map_err(|_| Error::InvalidUrl(raw.to_string()))?;
+        let not_apple = || Error::NotApplePodcasts(raw.to_string());
+        let query = |name: &str| {
+            parsed
+                .query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+
+        let is_apple = (matches!(parsed.scheme(), "http" | "https")
+            || ITUNES_SCHEMES.contains(&parsed.scheme()))
+            && parsed
+                .host_str()
+                .is_some_and(|host| APPLE_PODCASTS_HOSTS.contains(&host));
+        let is_podcast_media = query("mt").is_none_or(|mt| mt == PODCAST_MEDIA_TYPE);
+        if !is_apple || !is_podcast_media {
+            return Err(not_apple());
+        }
+
+        let segments: Vec<&str> = parsed
+            .path_segments()
+            .map(|segments| segments.filter(|segment| !segment.is_empty()).collect())
+            .unwrap_or_default();
+        let storefront = segments
+            .first()
+            .and_then(|first| parse_storefront(first).ok());
+        let rest = &segments[usize::from(storefront.is_some())..];
+        let legacy_show = rest == LEGACY_SHOW_PATH;
+        let (section, id) = if legacy_show {
+            (Some("podcast"), query("id").filter(|id| is_numeric_id(id)))
+        } else {
+            let id = rest
+                .iter()
+                .skip(1)
+                .filter_map(|segment| segment.strip_prefix("id"))
+                .find(|id| is_numeric_id(id))
+                .map(str::to_string);
+            (rest.first().copied(), id)
+        };
+        let episode_id = match query("i") {
+            Some(value) if is_numeric_id(&value) => Some(value),
+            Some(_) => return Err(not_apple()),
+            None => None,
+        };
+
+        let (kind, podcast_id, channel_id) = match (section, id, &episode_id) {
+            (Some("podcast"), Some(id), Some(_)) => (UrlKind::Episode, Some(id), None),
+            (Some("podcast"), Some(id), None) => (UrlKind::Show, Some(id), None),
+            (Some("channel"), Some(id), None) => (UrlKind::Channel, None, Some(id)),
+            _ => return Err(not_apple()),
+        };
+
+        // Only `?i=` identifies anything; `uo`, `at`, `ct` and friends are tracking, and `mt` is implied
+        let mut url = match (legacy_show, &podcast_id) {
+            (true, Some(id)) => format!("https://podcasts.apple.com/podcast/id{}", id),
+            _ => format!("https://podcasts.apple.com{}", parsed.path()),
+        };
+        if let Some(episode_id) = &episode_id {
+            url.push_str(&format!("?i={}", episode_id));
+        }
+
+        Ok(ParsedUrl {
+            kind,
+            storefront: storefront.unwrap_or_else(|| "us".to_string()),
+            podcast_id,
+            episode_id,
+            channel_id,
+            url,
+        })
+    }
+}
+
+/// Whether an ID is made only of ASCII digits
+fn is_numeric_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Validates that the provided string is a valid URL
+pub fn validate_url(url_str: &str) -> Result<()> {
+    Url::parse(url_str)
+        .map(|_| ())
+        .map_err(|_| Error::InvalidUrl(url_str.to_string()))
+}
+
+/// Extracts the Apple episode ID from the `?i=` query parameter of an episode URL
+pub fn episode_id_from_url(url: &str) -> Option<String> {
+    Url::parse(url)
+        .ok()?
+        .query_pairs()
+        .find(|(key, _)| key == "i")
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Extracts the Apple show ID from the `/id<digits>` path segment of a show or episode URL
+pub fn show_id_from_url(url: &str) -> Option<String> {
+    Url::parse(url)
+        .ok()?
+        .path_segments()?
+        .filter_map(|segment| segment.strip_prefix("id"))
+        .find(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()))
+        .map(str::to_string)
+}
+
+/// Returns true for an Apple Podcasts show URL (a `/podcast/.../id<digits>` path without `?i=`)
+pub fn is_show_url(url: &str) -> bool {
+    ParsedUrl::parse(url).is_ok_and(|parsed| parsed.kind == UrlKind::Show)
+}
+
+/// Extracts the storefront code (`us`, `de`, `jp`, ...) from an Apple Podcasts URL, defaulting to `us`
+pub fn storefront_from_url(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|parsed| {
+            parsed
+                .path_segments()
+                .and_then(|mut segments| segments.next().map(str::to_lowercase))
+        })
+        .filter(|segment| segment.len() == 2 && segment.chars().all(|c| c.is_ascii_alphabetic()))
+        .unwrap_or_else(|| "us".to_string())
+}
+
+/// Validates a two-letter storefront code such as `us` or `fr`
+pub fn parse_storefront(raw: &str) -> std::result::Result<String, String> {
+    let code = raw.trim().to_lowercase();
+
+    if code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+        Ok(code)
+    } else {
+        Err(format!(
+            "Invalid storefront '{}': expected a two-letter code like 'us'",
+            raw
+        ))
+    }
+}
+
+/// Parses a `--show` argument, an Apple show ID or any Apple Podcasts URL naming one, into a show URL
+pub fn parse_show(raw: &str) -> std::result::Result<String, String> {
+    let raw = raw.trim();
+    if !raw.is_empty() && raw.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(format!("https://podcasts.apple.com/us/podcast/id{}", raw));
+    }
+
+    let parsed = ParsedUrl::parse(raw).ok();
+    match parsed.and_then(|parsed| Some((parsed.storefront, parsed.podcast_id?))) {
+        Some((storefront, id)) => Ok(format!(
+            "https://podcasts.apple.com/{}/podcast/id{}",
+            storefront, id
+        )),
+        None => Err(format!(
+            "Invalid show '{}': expected an Apple show ID or Apple Podcasts URL",
+            raw
+        )),
+    }
+}
+
+/// Rewrites (or inserts) the storefront segment of an Apple Podcasts URL
+pub fn rewrite_storefront(url: &str, storefront: &str) -> Result<String> {
+    let mut parsed = Url::parse(url).map_err(|_| Error::InvalidUrl(url.to_string()))?;
+
+    let mut segments: Vec<String> = parsed
+        .path_segments()
+        .map(|segments| segments.map(str::to_string).collect())
+        .unwrap_or_default();
+
+    let has_storefront = segments
+        .first()
+        .is_some_and(|first| parse_storefront(first).is_ok());
+    if has_storefront {
+        segments[0] = storefront.to_string();
+    } else {
+        segments.insert(0, storefront.to_string());
+    }
+
+    parsed.set_path(&segments.join("/"));
+    Ok(parsed.to_string())
+}
+
+/// Points an Apple Podcasts URL at another storefront; other URLs and file paths are returned unchanged
+pub fn with_storefront(url: &str, storefront: &str) -> String {
+    let is_apple =
+        Url::parse(url).is_ok_and(|parsed| parsed.host_str() == Some("podcasts.apple.com"));
+    if !is_apple {
+        return url.to_string();
+    }
+    rewrite_storefront(url, storefront).unwrap_or_else(|_| url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - Valid URL passes validation
+    #[test]
+    fn test_validate_url_accepts_valid_url() {
+        let url = "https://podcasts.apple.com/us/podcast/id840986946?i=1000631244436";
+        assert!(validate_url(url).is_ok());
+    }
+
+    /// Unit test - Invalid URL fails validation
+    #[test]
+    fn test_validate_url_rejects_invalid_url() {
+        let url = "not-a-valid-url";
+        assert!(validate_url(url).is_err());
+    }
+
+    /// Unit test - HTTP URLs are valid
+    #[test]
+    fn test_validate_url_accepts_http() {
+        let url = "http://example.com";
+        assert!(validate_url(url).is_ok());
+    }
+
+    /// Unit test - Empty string fails validation
+    #[test]
+    fn test_validate_url_rejects_empty_string() {
+        let url = "";
+        assert!(validate_url(url).is_err());
+    }
+
+    /// Unit test - Error message includes the invalid URL
+    #[test]
+    fn test_validate_url_error_includes_url() {
+        let url = "not-valid";
+        let result = validate_url(url);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("not-valid"));
+    }
+
+    /// Unit test - episode_id_from_url reads the ?i= parameter
+    #[test]
+    fn test_episode_id_from_url_reads_query_parameter() {
+        // Given episode and show URLs
+        let episode = "https://podcasts.apple.com/us/podcast/id840986946?i=1000631244436";
+        let show = "https://podcasts.apple.com/us/podcast/id840986946";
+
+        // When we extract the episode ID
+        // Then only the episode URL yields one
+        assert_eq!(
+            episode_id_from_url(episode).as_deref(),
+            Some("1000631244436")
+        );
+        assert_eq!(episode_id_from_url(show), None);
+    }
+
+    /// Unit test - storefront_from_url reads the storefront path segment
+    #[test]
+    fn test_storefront_from_url_reads_path_segment() {
+        // Given URLs from different storefronts
+        // When we extract the storefront
+        // Then the two-letter code is returned, defaulting to us
+        assert_eq!(
+            storefront_from_url("https://podcasts.apple.com/de/podcast/id1?i=2"),
+            "de"
+        );
+        assert_eq!(
+            storefront_from_url("https://podcasts.apple.com/JP/podcast/id1"),
+            "jp"
+        );
+        assert_eq!(
+            storefront_from_url("https://podcasts.apple.com/podcast/id1"),
+            "us"
+        );
+    }
+
+    /// Unit test - rewrite_storefront swaps an existing storefront segment
+    #[test]
+    fn test_rewrite_storefront_replaces_existing_segment() {
+        // Given a US episode URL
+        let url = "https://podcasts.apple.com/us/podcast/the-daily/id1200361736?i=1000631244436";
+
+        // When we rewrite it for the French storefront
+        let rewritten = rewrite_storefront(url, "fr").unwrap();
+
+        // Then only the storefront segment changes
+        assert_eq!(
+            rewritten,
+            "https://podcasts.apple.com/fr/podcast/the-daily/id1200361736?i=1000631244436"
+        );
+    }
+
+    /// Unit test - rewrite_storefront inserts a missing storefront segment
+    #[test]
+    fn test_rewrite_storefront_inserts_missing_segment() {
+        // Given a URL without a storefront segment
+        let url = "https://podcasts.apple.com/podcast/id1200361736";
+
+        // When we rewrite it for the Japanese storefront
+        let rewritten = rewrite_storefront(url, "jp").unwrap();
+
+        // Then the storefront is inserted at the front of the path
+        assert_eq!(
+            rewritten,
+            "https://podcasts.apple.com/jp/podcast/id1200361736"
+        );
+    }
+
+    /// Unit test - with_storefront only rewrites Apple Podcasts URLs
+    #[test]
+    fn test_with_storefront_leaves_other_sources_alone() {
+        // Given an Apple URL, another site's URL and a saved page path
+        // When we point them at the British storefront
+        // Then only the Apple URL changes
+        assert_eq!(
+            with_storefront("https://podcasts.apple.com/us/podcast/id1?i=2", "gb"),
+            "https://podcasts.apple.com/gb/podcast/id1?i=2"
+        );
+        assert_eq!(
+            with_storefront("https://example.com/us/feed", "gb"),
+            "https://example.com/us/feed"
+        );
+        assert_eq!(
+            with_storefront("output/episode.html", "gb"),
+            "output/episode.html"
+        );
+    }
+
+    /// Unit test - parse_storefront normalizes and validates codes
+    #[test]
+    fn test_parse_storefront_validates_codes() {
+        // Given storefront arguments
+        // When we parse them
+        // Then two-letter codes are lower-cased and anything else is rejected
+        assert_eq!(parse_storefront("FR"), Ok("fr".to_string()));
+        assert!(parse_storefront("france").is_err());
+        assert!(parse_storefront("f1").is_err());
+    }
+
+    /// Unit test - parse_show accepts show IDs and Apple URLs
+    #[test]
+    fn test_parse_show_builds_show_urls() {
+        // Given a bare ID, an episode URL and a non-Apple URL
+        // When we parse them as shows
+        // Then the first two become show URLs and the last is rejected
+        assert_eq!(
+            parse_show("1200361736"),
+            Ok("https://podcasts.apple.com/us/podcast/id1200361736".to_string())
+        );
+        assert_eq!(
+            parse_show("https://podcasts.apple.com/gb/podcast/the-daily/id1200361736?i=1000"),
+            Ok("https://podcasts.apple.com/gb/podcast/id1200361736".to_string())
+        );
+        assert_eq!(
+            parse_show("https://itunes.apple.com/de/podcast/the-daily/id1200361736?mt=2"),
+            Ok("https://podcasts.apple.com/de/podcast/id1200361736".to_string())
+        );
+        assert!(parse_show("https://example.com/podcast/id1200361736").is_err());
+    }
+
+    /// Unit test - ParsedUrl reads the IDs of each Apple URL shape and drops tracking
+    #[test]
+    fn test_parsed_url_recognizes_apple_shapes() {
+        // Given an episode link with tracking parameters, a legacy show link and a channel link
+        let episode = ParsedUrl::parse(
+            "https://podcasts.apple.com/gb/podcast/the-daily/id1200361736?i=1000631244436&uo=4&at=1l3v#share",
+        )
+        .unwrap();
+        let show =
+            ParsedUrl::parse("http://itunes.apple.com/podcast/the-daily/id1200361736").unwrap();
+        let channel =
+            ParsedUrl::parse("https://podcasts.apple.com/us/channel/nyt/id6442463180").unwrap();
+
+        // When we look at their parts
+        // Then each kind carries its IDs and a clean podcasts.apple.com URL
+        assert_eq!(episode.kind, UrlKind::Episode);
+        assert_eq!(episode.storefront, "gb");
+        assert_eq!(episode.podcast_id.as_deref(), Some("1200361736"));
+        assert_eq!(episode.episode_id.as_deref(), Some("1000631244436"));
+        assert_eq!(
+            episode.url,
+            "https://podcasts.apple.com/gb/podcast/the-daily/id1200361736?i=1000631244436"
+        );
+        assert_eq!(show.kind, UrlKind::Show);
+        assert_eq!(show.storefront, "us");
+        assert_eq!(
+            show.url,
+            "https://podcasts.apple.com/podcast/the-daily/id1200361736"
+        );
+        assert_eq!(channel.kind, UrlKind::Channel);
+        assert_eq!(channel.channel_id.as_deref(), Some("6442463180"));
+        assert_eq!(channel.podcast_id, None);
+    }
+
+    /// Unit test - ParsedUrl unwraps legacy iTunes and Apple Music podcast links
+    #[test]
+    fn test_parsed_url_unwraps_legacy_links() {
+        // Given legacy links to the same show and episode in their old forms
+        let cases = [
+            (
+                "https://itunes.apple.com/us/podcast/the-daily/id1200361736?mt=2&uo=4",
+                "https://podcasts.apple.com/us/podcast/the-daily/id1200361736",
+            ),
+            (
+                "https://music.apple.com/gb/podcast/the-daily/id1200361736?i=1000631244436",
+                "https://podcasts.apple.com/gb/podcast/the-daily/id1200361736?i=1000631244436",
+            ),
+            (
+                "itms-podcasts://itunes.apple.com/us/podcast/id1200361736?mt=2&i=1000631244436",
+                "https://podcasts.apple.com/us/podcast/id1200361736?i=1000631244436",
+            ),
+            (
+                "https://itunes.apple.com/WebObjects/MZStore.woa/wa/viewPodcast?id=1200361736",
+                "https://podcasts.apple.com/podcast/id1200361736",
+            ),
+        ];
+
+        // When we parse them
+        // Then each becomes the current podcasts.apple.com URL with its IDs
+        for (legacy, current) in cases {
+            let parsed = ParsedUrl::parse(legacy).unwrap();
+            assert_eq!(parsed.url, current, "{}", legacy);
+            assert_eq!(parsed.podcast_id.as_deref(), Some("1200361736"));
+        }
+
+        // And links to other iTunes media types are not podcasts
+        assert!(matches!(
+            ParsedUrl::parse("https://itunes.apple.com/us/podcast/id1200361736?mt=1"),
+            Err(Error::NotApplePodcasts(_))
+        ));
+        assert!(ParsedUrl::parse("itms-apps://itunes.apple.com/us/podcast/id1").is_err());
+    }
+
+    /// Unit test - ParsedUrl rejects other sites and other Apple pages with a targeted error
+    #[test]
+    fn test_parsed_url_rejects_non_podcast_urls() {
+        // Given URLs that are not Apple Podcasts episode, show or channel links
+        // When we parse them
+        // Then anything URL-shaped is reported as not an Apple Podcasts URL
+        for url in [
+            "https://example.com",
+            "https://music.apple.com/us/album/id1",
+            "https://podcasts.apple.com/us/browse",
+            "https://podcasts.apple.com/us/podcast/id1?i=latest",
+        ] {
+            assert!(
+                matches!(ParsedUrl::parse(url), Err(Error::NotApplePodcasts(_))),
+                "{}",
+                url
+            );
+        }
+        assert!(matches!(
+            ParsedUrl::parse("not-a-url"),
+            Err(Error::InvalidUrl(_))
+        ));
+    }
+
+    /// Unit test - is_show_url recognizes Apple show URLs only
+    #[test]
+    fn test_is_show_url_recognizes_show_pages() {
+        // Given show, episode and non-Apple URLs
+        // When we classify them
+        // Then only the Apple show URL is a show
+        assert!(is_show_url(
+            "https://podcasts.apple.com/us/podcast/the-daily/id1200361736"
+        ));
+        assert!(!is_show_url(
+            "https://podcasts.apple.com/us/podcast/id1200361736?i=1000631244436"
+        ));
+        assert!(!is_show_url(
+            "https://www.produxlabs.com/product-thinking-blog/episode-252"
+        ));
+    }
+}
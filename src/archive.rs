@@ -0,0 +1,1787 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use serde::Serialize;
+
+use crate::apple_url::{episode_id_from_url, show_id_from_url};
+use crate::error::{Error, Result};
+use crate::metadata::Metadata;
+use crate::show::ShowMetadata;
+use crate::transcript::TranscriptCue;
+
+pub mod bundle;
+pub mod merge;
+
+/// Schema version stored in `PRAGMA user_version`
+const SCHEMA_VERSION: i64 = 8;
+
+/// Tables created in a new archive; timestamps are ISO 8601 UTC set by SQLite
+///
+/// `transcript_cues` is a full-text index of every archived transcript, one row per cue.
+/// `links` gives each episode a short ID that stays the same when its URL changes.
+/// `episodes.removed_upstream` is when `sync` found the episode missing from Apple's listing.
+/// `changes` logs each upstream change found to an archived episode, with the old and new values.
+/// `tags` and `episodes.rating` hold the user's own tags and 1-5 star rating of an episode.
+/// `episodes.played_at` and `progress_seconds` record what the user has listened to.
+/// `smart_lists` holds named conditions, evaluated against the archive each time a list is shown.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS shows (
+    id TEXT PRIMARY KEY,
+    url TEXT NOT NULL,
+    title TEXT NOT NULL,
+    author TEXT NOT NULL,
+    description TEXT NOT NULL,
+    fetched_at TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS episodes (
+    id TEXT PRIMARY KEY,
+    show_id TEXT,
+    url TEXT NOT NULL,
+    title TEXT NOT NULL,
+    show_title TEXT NOT NULL,
+    publish_date TEXT,
+    guid TEXT,
+    metadata TEXT NOT NULL,
+    directory TEXT NOT NULL,
+    transcript_path TEXT,
+    fetched_at TEXT NOT NULL,
+    removed_upstream TEXT,
+    rating INTEGER,
+    played_at TEXT,
+    progress_seconds REAL
+);
+CREATE INDEX IF NOT EXISTS episodes_by_show ON episodes (show_id);
+CREATE TABLE IF NOT EXISTS links (
+    short_id TEXT PRIMARY KEY,
+    episode_id TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL
+);
+CREATE VIRTUAL TABLE IF NOT EXISTS transcript_cues USING fts5 (
+    episode_id UNINDEXED,
+    begin UNINDEXED,
+    speaker UNINDEXED,
+    text
+);
+CREATE TABLE IF NOT EXISTS changes (
+    episode_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    old TEXT,
+    new TEXT,
+    detected_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS changes_by_time ON changes (detected_at);
+CREATE TABLE IF NOT EXISTS tags (
+    episode_id TEXT NOT NULL,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (episode_id, tag)
+);
+CREATE TABLE IF NOT EXISTS smart_lists (
+    name TEXT PRIMARY KEY,
+    condition TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+";
+
+/// SQL expression for the current time as ISO 8601 UTC
+const NOW: &str = "strftime('%Y-%m-%dT%H:%M:%SZ', 'now')";
+
+/// File an archive root directory keeps its database in
+pub const ARCHIVE_FILE: &str = "archive.sqlite";
+
+/// Hex digits in a new short ID; longer prefixes of the same hash are used on collision
+const SHORT_ID_LEN: usize = 6;
+
+/// Columns read into an [`ArchivedEpisode`], in field order
+const EPISODE_COLUMNS: &str = "SELECT episodes.id, show_id, url, title, show_title, publish_date,
+         guid, directory, transcript_path, fetched_at, metadata, links.short_id, removed_upstream,
+         rating,
+         (SELECT group_concat(tag, ',' ORDER BY tag) FROM tags WHERE tags.episode_id = episodes.id),
+         played_at, progress_seconds
+     FROM episodes LEFT JOIN links ON links.episode_id = episodes.id";
+
+/// An episode as stored in the archive
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ArchivedEpisode {
+    /// Apple episode ID, or the URL when it has none
+    pub id: String,
+    /// Stable short ID resolved by `applecast-cli link`
+    pub short_id: String,
+    pub show_id: Option<String>,
+    pub url: String,
+    pub title: String,
+    pub show_title: String,
+    pub publish_date: Option<String>,
+    pub guid: Option<String>,
+    pub directory: String,
+    pub transcript_path: Option<String>,
+    pub fetched_at: String,
+    /// The full `metadata.json` content recorded with the episode
+    pub metadata: serde_json::Value,
+    /// When `sync` found the episode gone from Apple's listing; its saved files are kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removed_upstream: Option<String>,
+    /// The user's star rating, 1 to 5, set by `rate`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rating: Option<u8>,
+    /// The user's own tags, set by `tag add`, in alphabetical order
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// When the user marked the episode played, by `mark played` or `mark import`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub played_at: Option<String>,
+    /// How far into the unplayed episode the user has listened, from `mark import`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress_seconds: Option<f64>,
+}
+
+/// A show as stored in the archive
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ArchivedShow {
+    /// Apple show ID, or the URL when it has none
+    pub id: String,
+    pub url: String,
+    pub title: String,
+    pub author: String,
+    pub description: String,
+    pub fetched_at: String,
+}
+
+/// Which archived episodes to list
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArchiveFilter {
+    /// Only episodes of this Apple show ID
+    pub show_id: Option<String>,
+    /// At most this many episodes, newest first
+    pub limit: Option<usize>,
+    /// Only episodes published on or after this ISO 8601 date
+    pub since: Option<String>,
+    /// Only episodes marked as removed upstream
+    pub removed: bool,
+    /// Only episodes with this personal tag
+    pub tag: Option<String>,
+    /// Only episodes rated at least this many stars
+    pub min_rating: Option<u8>,
+    /// Only played episodes with `Some(true)`, only unplayed ones with `Some(false)`
+    pub played: Option<bool>,
+}
+
+/// What [`Archive::reconcile_listing`] changed, as episode IDs
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct ListingChanges {
+    /// Newly missing from the listing, now marked removed upstream
+    pub removed: Vec<String>,
+    /// Listed again after being marked, now unmarked
+    pub restored: Vec<String>,
+}
+
+/// What changed upstream about an archived episode, as recorded in the `changes` table
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Missing from a complete listing of its show
+    Removed,
+    /// Listed again after being marked removed
+    Restored,
+    /// A different episode title
+    Title,
+    /// A different description in the saved metadata
+    Description,
+    /// A transcript whose cues differ from the indexed ones
+    Transcript,
+}
+
+impl ChangeKind {
+    /// The name stored in the `changes` table
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeKind::Removed => "removed",
+            ChangeKind::Restored => "restored",
+            ChangeKind::Title => "title",
+            ChangeKind::Description => "description",
+            ChangeKind::Transcript => "transcript",
+        }
+    }
+
+    fn parse(name: &str) -> Option<ChangeKind> {
+        [
+            ChangeKind::Removed,
+            ChangeKind::Restored,
+            ChangeKind::Title,
+            ChangeKind::Description,
+            ChangeKind::Transcript,
+        ]
+        .into_iter()
+        .find(|kind| kind.as_str() == name)
+    }
+}
+
+/// An upstream change to an archived episode, with the episode's current details
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct EpisodeChange {
+    pub episode_id: String,
+    pub url: String,
+    pub title: String,
+    pub show_title: String,
+    pub kind: ChangeKind,
+    /// The value before the change, for title and description edits
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    /// The value after the change, for title and description edits
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<String>,
+    pub detected_at: String,
+}
+
+/// A saved query, created by `list create`
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct SmartList {
+    pub name: String,
+    /// The `--where` condition, parsed by [`Condition`](crate::smartlist::Condition)
+    pub condition: String,
+    pub created_at: String,
+}
+
+/// A transcript cue matching a full-text search, with the cues around it
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct TranscriptMatch {
+    pub episode_id: String,
+    pub url: String,
+    pub title: String,
+    pub show_title: String,
+    pub publish_date: Option<String>,
+    /// Seconds from the start of the episode
+    pub begin: f64,
+    pub speaker: Option<String>,
+    pub text: String,
+    /// The cue before the match, if any
+    pub before: Option<String>,
+    /// The cue after the match, if any
+    pub after: Option<String>,
+}
+
+/// A SQLite database recording every show and episode the tool has processed
+///
+/// Episodes are keyed by their Apple episode ID, so an episode reached through
+/// different URLs (storefronts, slugs) is stored once and updated in place.
+///
+/// Episode directories and transcript paths under the database's directory,
+/// the archive root, are stored relative to it and others as absolute paths,
+/// so records resolve the same from any working directory and survive moving
+/// the whole collection.
+pub struct Archive {
+    connection: Connection,
+    /// Absolute path of the database file
+    path: PathBuf,
+}
+
+impl Archive {
+    /// Opens the archive at `path`, creating the file and its tables if needed
+    pub fn open(path: impl AsRef<Path>) -> Result<Archive> {
+        let path = path.as_ref();
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent).map_err(Error::io("Failed to create archive directory"))?;
+        }
+
+        let connection = Connection::open(path)?;
+        let version = schema_version(&connection, path)?;
+        if version > 0 && version < 4 {
+            connection.execute_batch("ALTER TABLE episodes ADD COLUMN removed_upstream TEXT;")?;
+        }
+        if version > 0 && version < 6 {
+            connection.execute_batch("ALTER TABLE episodes ADD COLUMN rating INTEGER;")?;
+        }
+        if version > 0 && version < 7 {
+            connection.execute_batch(
+                "ALTER TABLE episodes ADD COLUMN played_at TEXT;
+                 ALTER TABLE episodes ADD COLUMN progress_seconds REAL;",
+            )?;
+        }
+        connection.execute_batch(SCHEMA)?;
+        connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        let archive = Archive {
+            connection,
+            path: std::path::absolute(path).map_err(Error::io("Failed to resolve archive path"))?,
+        };
+
+        // Episodes archived before short links existed get theirs now
+        if version < 3 {
+            for id in archive.episode_ids()? {
+                archive.short_id(&id)?;
+            }
+        }
+        Ok(archive)
+    }
+
+    /// Opens an existing archive at `path` for reading only, never creating or migrating it
+    ///
+    /// Fails when the file does not exist, or when its schema is older than this
+    /// release's, since it cannot be upgraded in place.
+    pub fn open_read_only(path: impl AsRef<Path>) -> Result<Archive> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(Error::Parse(format!(
+                "Archive {} does not exist",
+                path.display()
+            )));
+        }
+
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        if schema_version(&connection, path)? < SCHEMA_VERSION {
+            return Err(Error::Parse(format!(
+                "Archive {} uses an older schema; open it as the first --db once to upgrade it",
+                path.display()
+            )));
+        }
+        Ok(Archive {
+            connection,
+            path: std::path::absolute(path).map_err(Error::io("Failed to resolve archive path"))?,
+        })
+    }
+
+    /// Absolute path of the database file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The directory holding the database, which relative paths are stored against
+    pub fn root(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new("/"))
+    }
+
+    /// Records a show's details, replacing any earlier record
+    pub fn record_show(&self, url: &str, show: &ShowMetadata) -> Result<()> {
+        let id = show_id_from_url(url).unwrap_or_else(|| url.to_string());
+        self.connection.execute(
+            &format!(
+                "INSERT INTO shows (id, url, title, author, description, fetched_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, {NOW})
+                 ON CONFLICT (id) DO UPDATE SET url = excluded.url, title = excluded.title,
+                     author = excluded.author, description = excluded.description,
+                     fetched_at = excluded.fetched_at"
+            ),
+            params![id, url, show.show_title, show.author, show.description],
+        )?;
+        Ok(())
+    }
+
+    /// Records a processed episode with where its artifacts were saved, replacing any earlier record
+    ///
+    /// A known transcript path is kept when a later run did not save one, so a
+    /// transient transcript failure does not hide the earlier download. Relative
+    /// paths are taken from the working directory and stored as described on [`Archive`].
+    pub fn record_episode(
+        &self,
+        url: &str,
+        metadata: &Metadata,
+        directory: &str,
+        transcript_path: Option<&str>,
+    ) -> Result<()> {
+        let id = episode_id_from_url(url).unwrap_or_else(|| url.to_string());
+        self.record_edits(
+            &id,
+            Some(&metadata.episode_title),
+            Some(&metadata.description),
+        )?;
+        self.connection.execute(
+            &format!(
+                "INSERT INTO episodes (id, show_id, url, title, show_title, publish_date, guid,
+                     metadata, directory, transcript_path, fetched_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, {NOW})
+                 ON CONFLICT (id) DO UPDATE SET show_id = excluded.show_id, url = excluded.url,
+                     title = excluded.title, show_title = excluded.show_title,
+                     publish_date = excluded.publish_date, guid = excluded.guid,
+                     metadata = excluded.metadata, directory = excluded.directory,
+                     transcript_path = COALESCE(excluded.transcript_path, transcript_path),
+                     fetched_at = excluded.fetched_at"
+            ),
+            params![
+                id,
+                show_id_from_url(url),
+                url,
+                metadata.episode_title,
+                metadata.show_title,
+                metadata.publish_date_iso8601,
+                metadata.guid,
+                serde_json::to_string(metadata)?,
+                self.stored_path(directory)?,
+                transcript_path
+                    .map(|path| self.stored_path(path))
+                    .transpose()?,
+            ],
+        )?;
+        self.short_id(&id)?;
+        Ok(())
+    }
+
+    /// Stores an episode taken from another archive as it was recorded there, replacing any earlier record
+    ///
+    /// Unlike [`record_episode`](Archive::record_episode), its fetch time,
+    /// rating, tags and listening state are kept, and its directory and transcript path are
+    /// taken as given, already resolved.
+    pub fn import_episode(&self, episode: &ArchivedEpisode) -> Result<()> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO episodes (id, show_id, url, title, show_title, publish_date,
+                 guid, metadata, directory, transcript_path, fetched_at, removed_upstream, rating,
+                 played_at, progress_seconds)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            params![
+                episode.id,
+                episode.show_id,
+                episode.url,
+                episode.title,
+                episode.show_title,
+                episode.publish_date,
+                episode.guid,
+                episode.metadata.to_string(),
+                self.stored_path(&episode.directory)?,
+                episode
+                    .transcript_path
+                    .as_deref()
+                    .map(|path| self.stored_path(path))
+                    .transpose()?,
+                episode.fetched_at,
+                episode.removed_upstream,
+                episode.rating,
+                episode.played_at,
+                episode.progress_seconds,
+            ],
+        )?;
+        self.connection.execute(
+            "DELETE FROM tags WHERE episode_id = ?1",
+            params![episode.id],
+        )?;
+        self.add_tags(&episode.id, &episode.tags)?;
+        self.short_id(&episode.id)?;
+        Ok(())
+    }
+
+    /// Adds personal tags to an archived episode; tags it already has are left alone
+    pub fn add_tags(&self, id: &str, tags: &[String]) -> Result<()> {
+        for tag in tags {
+            self.connection.execute(
+                "INSERT OR IGNORE INTO tags (episode_id, tag) VALUES (?1, ?2)",
+                params![id, tag],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Removes personal tags from an archived episode
+    pub fn remove_tags(&self, id: &str, tags: &[String]) -> Result<()> {
+        for tag in tags {
+            self.connection.execute(
+                "DELETE FROM tags WHERE episode_id = ?1 AND tag = ?2",
+                params![id, tag],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Sets an archived episode's star rating, or clears it with `None`
+    pub fn set_rating(&self, id: &str, rating: Option<u8>) -> Result<()> {
+        self.connection.execute(
+            "UPDATE episodes SET rating = ?2 WHERE id = ?1",
+            params![id, rating],
+        )?;
+        Ok(())
+    }
+
+    /// Saves a smart list, replacing any list of the same name
+    pub fn save_smart_list(&self, name: &str, condition: &str) -> Result<()> {
+        self.connection.execute(
+            &format!(
+                "INSERT OR REPLACE INTO smart_lists (name, condition, created_at)
+                 VALUES (?1, ?2, {NOW})"
+            ),
+            params![name, condition],
+        )?;
+        Ok(())
+    }
+
+    /// Every saved smart list, by name
+    pub fn smart_lists(&self) -> Result<Vec<SmartList>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT name, condition, created_at FROM smart_lists ORDER BY name")?;
+        let lists = statement
+            .query_map([], |row| {
+                Ok(SmartList {
+                    name: row.get(0)?,
+                    condition: row.get(1)?,
+                    created_at: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(lists)
+    }
+
+    /// The smart list saved as `name`, if any
+    pub fn smart_list(&self, name: &str) -> Result<Option<SmartList>> {
+        Ok(self
+            .smart_lists()?
+            .into_iter()
+            .find(|list| list.name == name))
+    }
+
+    /// Deletes the smart list saved as `name`, returning whether there was one
+    pub fn delete_smart_list(&self, name: &str) -> Result<bool> {
+        let deleted = self
+            .connection
+            .execute("DELETE FROM smart_lists WHERE name = ?1", params![name])?;
+        Ok(deleted > 0)
+    }
+
+    /// Every recorded show, by ID
+    pub fn shows(&self) -> Result<Vec<ArchivedShow>> {
+        let mut statement = self.connection.prepare(
+            "SELECT id, url, title, author, description, fetched_at FROM shows ORDER BY id",
+        )?;
+        let shows = statement
+            .query_map([], |row| {
+                Ok(ArchivedShow {
+                    id: row.get(0)?,
+                    url: row.get(1)?,
+                    title: row.get(2)?,
+                    author: row.get(3)?,
+                    description: row.get(4)?,
+                    fetched_at: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(shows)
+    }
+
+    /// Stores a show taken from another archive, unless the record here was fetched later
+    pub fn import_show(&self, show: &ArchivedShow) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO shows (id, url, title, author, description, fetched_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT (id) DO UPDATE SET url = excluded.url, title = excluded.title,
+                 author = excluded.author, description = excluded.description,
+                 fetched_at = excluded.fetched_at
+             WHERE excluded.fetched_at > shows.fetched_at",
+            params![
+                show.id,
+                show.url,
+                show.title,
+                show.author,
+                show.description,
+                show.fetched_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The short ID of an episode, assigning one the first time it is asked for
+    ///
+    /// Short IDs are derived from the episode ID, not its URL, so they survive
+    /// storefront and slug changes.
+    pub fn short_id(&self, episode_id: &str) -> Result<String> {
+        let existing = self
+            .connection
+            .query_row(
+                "SELECT short_id FROM links WHERE episode_id = ?1",
+                params![episode_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(short_id) = existing {
+            return Ok(short_id);
+        }
+
+        let hash = format!("{:016x}", fnv1a(episode_id));
+        for len in SHORT_ID_LEN..=hash.len() {
+            let inserted = self.connection.execute(
+                &format!(
+                    "INSERT OR IGNORE INTO links (short_id, episode_id, created_at)
+                     VALUES (?1, ?2, {NOW})"
+                ),
+                params![&hash[..len], episode_id],
+            )?;
+            if inserted == 1 {
+                return Ok(hash[..len].to_string());
+            }
+        }
+        Err(Error::Parse(format!(
+            "No free short ID for episode {}",
+            episode_id
+        )))
+    }
+
+    /// The archived episode a short ID points to
+    pub fn resolve_short_id(&self, short_id: &str) -> Result<Option<ArchivedEpisode>> {
+        let mut statement = self
+            .connection
+            .prepare(&format!("{EPISODE_COLUMNS} WHERE links.short_id = ?1"))?;
+        let episodes = read_episodes(
+            &mut statement,
+            params![short_id.trim().to_lowercase()],
+            self.root(),
+        )?;
+        Ok(episodes.into_iter().next())
+    }
+
+    /// The archived episode with this ID, if any
+    pub fn episode(&self, id: &str) -> Result<Option<ArchivedEpisode>> {
+        let mut statement = self
+            .connection
+            .prepare(&format!("{EPISODE_COLUMNS} WHERE episodes.id = ?1"))?;
+        let episodes = read_episodes(&mut statement, params![id.trim()], self.root())?;
+        Ok(episodes.into_iter().next())
+    }
+
+    /// Replaces an archived episode's recorded metadata, keeping its listed details in step
+    pub fn update_metadata(&self, id: &str, metadata: &serde_json::Value) -> Result<()> {
+        self.record_edits(
+            id,
+            metadata["episode_title"].as_str(),
+            metadata["description"].as_str(),
+        )?;
+        self.connection.execute(
+            &format!(
+                "UPDATE episodes SET metadata = ?2, title = COALESCE(?3, title),
+                     show_title = COALESCE(?4, show_title),
+                     publish_date = COALESCE(?5, publish_date), guid = COALESCE(?6, guid),
+                     fetched_at = {NOW}
+                 WHERE id = ?1"
+            ),
+            params![
+                id,
+                metadata.to_string(),
+                metadata["episode_title"].as_str(),
+                metadata["show_title"].as_str(),
+                metadata["publish_date_iso8601"].as_str(),
+                metadata["guid"].as_str(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Replaces the full-text index of an episode's transcript with `cues`
+    pub fn index_transcript(&self, url: &str, cues: &[TranscriptCue]) -> Result<()> {
+        self.index_transcript_stream(url, cues.iter().cloned().map(Ok))
+    }
+
+    /// Replaces the full-text index of an episode's transcript with cues read one at a time
+    ///
+    /// Only one cue is held in memory, so large transcripts can be indexed from
+    /// a [`MappedTtml`](crate::transcript::stream::MappedTtml). If reading a cue
+    /// fails, the previous index is kept. Replacing an index with different cue
+    /// text is recorded as a [`ChangeKind::Transcript`] change.
+    pub fn index_transcript_stream(
+        &self,
+        url: &str,
+        cues: impl IntoIterator<Item = Result<TranscriptCue>>,
+    ) -> Result<()> {
+        let id = episode_id_from_url(url).unwrap_or_else(|| url.to_string());
+        let transaction = self.connection.unchecked_transaction()?;
+        let previous = {
+            let mut statement = transaction
+                .prepare("SELECT text FROM transcript_cues WHERE episode_id = ?1 ORDER BY rowid")?;
+            let texts = statement.query_map(params![id], |row| row.get::<_, String>(0))?;
+            let mut hash = None;
+            for text in texts {
+                hash = Some(fnv1a_continue(hash.unwrap_or(FNV_OFFSET), &text?));
+            }
+            hash
+        };
+        transaction.execute(
+            "DELETE FROM transcript_cues WHERE episode_id = ?1",
+            params![id],
+        )?;
+        let mut hash = FNV_OFFSET;
+        {
+            let mut insert = transaction.prepare(
+                "INSERT INTO transcript_cues (episode_id, begin, speaker, text) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for cue in cues {
+                let cue = cue?;
+                hash = fnv1a_continue(hash, &cue.text);
+                insert.execute(params![id, cue.begin, cue.speaker, cue.text])?;
+            }
+        }
+        if previous.is_some_and(|previous| previous != hash) {
+            insert_change(&transaction, &id, ChangeKind::Transcript, None, None)?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// URLs and resolved transcript paths of archived episodes whose transcripts are not indexed yet
+    ///
+    /// Covers archives created before transcripts were indexed and transcripts
+    /// that could not be read when their episode was recorded.
+    pub fn unindexed_transcripts(&self) -> Result<Vec<(String, String)>> {
+        let mut statement = self.connection.prepare(
+            "SELECT url, transcript_path FROM episodes
+             WHERE transcript_path IS NOT NULL
+                 AND id NOT IN (SELECT episode_id FROM transcript_cues)",
+        )?;
+        let transcripts = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get::<_, String>(1)?)))?
+            .map(|row| row.map(|(url, path)| (url, resolve(self.root(), &path))))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(transcripts)
+    }
+
+    /// Transcript cues containing `phrase`, newest episode first, then in spoken order
+    pub fn search_transcripts(
+        &self,
+        phrase: &str,
+        filter: &ArchiveFilter,
+    ) -> Result<Vec<TranscriptMatch>> {
+        let mut statement = self.connection.prepare(
+            "SELECT transcript_cues.rowid, episode_id, begin, speaker, text,
+                 episodes.url, episodes.title, episodes.show_title, episodes.publish_date
+             FROM transcript_cues JOIN episodes ON episodes.id = transcript_cues.episode_id
+             WHERE transcript_cues MATCH ?1 AND (?2 IS NULL OR episodes.show_id = ?2)
+                 AND (?4 IS NULL OR episodes.publish_date >= ?4)
+             ORDER BY episodes.publish_date IS NULL, episodes.publish_date DESC,
+                 episodes.id DESC, transcript_cues.rowid
+             LIMIT ?3",
+        )?;
+        let limit = filter
+            .limit
+            .and_then(|limit| i64::try_from(limit).ok())
+            .unwrap_or(-1);
+
+        let rows = statement.query_map(
+            params![fts_phrase(phrase), filter.show_id, limit, filter.since],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    TranscriptMatch {
+                        episode_id: row.get(1)?,
+                        begin: row.get(2)?,
+                        speaker: row.get(3)?,
+                        text: row.get(4)?,
+                        url: row.get(5)?,
+                        title: row.get(6)?,
+                        show_title: row.get(7)?,
+                        publish_date: row.get(8)?,
+                        before: None,
+                        after: None,
+                    },
+                ))
+            },
+        )?;
+
+        let mut matches = Vec::new();
+        for row in rows {
+            let (rowid, mut found) = row?;
+            // An episode's cues are inserted together, so its neighbours have adjacent rowids
+            found.before = self.cue_text(rowid - 1, &found.episode_id)?;
+            found.after = self.cue_text(rowid + 1, &found.episode_id)?;
+            matches.push(found);
+        }
+        Ok(matches)
+    }
+
+    /// Text of the indexed cue at `rowid` if it belongs to `episode_id`
+    fn cue_text(&self, rowid: i64, episode_id: &str) -> Result<Option<String>> {
+        Ok(self
+            .connection
+            .query_row(
+                "SELECT text FROM transcript_cues WHERE rowid = ?1 AND episode_id = ?2",
+                params![rowid, episode_id],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// IDs of every archived episode
+    pub fn episode_ids(&self) -> Result<HashSet<String>> {
+        let mut statement = self.connection.prepare("SELECT id FROM episodes")?;
+        let ids = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<String>>>()?;
+        Ok(ids)
+    }
+
+    /// Archived episodes matching `filter`, newest release first, ties broken by ID so listings are stable
+    pub fn episodes(&self, filter: &ArchiveFilter) -> Result<Vec<ArchivedEpisode>> {
+        let mut statement = self.connection.prepare(&format!(
+            "{EPISODE_COLUMNS}
+             WHERE (?1 IS NULL OR show_id = ?1) AND (?3 IS NULL OR publish_date >= ?3)
+                 AND (NOT ?4 OR removed_upstream IS NOT NULL)
+                 AND (?5 IS NULL OR episodes.id IN (SELECT episode_id FROM tags WHERE tag = ?5))
+                 AND (?6 IS NULL OR rating >= ?6)
+                 AND (?7 IS NULL OR (played_at IS NOT NULL) = ?7)
+             ORDER BY publish_date IS NULL, publish_date DESC, id DESC
+             LIMIT ?2"
+        ))?;
+        let limit = filter
+            .limit
+            .and_then(|limit| i64::try_from(limit).ok())
+            .unwrap_or(-1);
+        read_episodes(
+            &mut statement,
+            params![
+                filter.show_id,
+                limit,
+                filter.since,
+                filter.removed,
+                filter.tag,
+                filter.min_rating,
+                filter.played
+            ],
+            self.root(),
+        )
+    }
+
+    /// The `limit` most recently archived episodes, latest fetch first
+    pub fn recently_archived(&self, limit: usize) -> Result<Vec<ArchivedEpisode>> {
+        let mut statement = self.connection.prepare(&format!(
+            "{EPISODE_COLUMNS} ORDER BY fetched_at DESC, episodes.id DESC LIMIT ?1"
+        ))?;
+        read_episodes(
+            &mut statement,
+            params![i64::try_from(limit).unwrap_or(-1)],
+            self.root(),
+        )
+    }
+
+    /// Compares a show's archived episodes with the IDs in a complete, fresh listing of the show
+    ///
+    /// Episodes missing from `listed` are marked removed upstream, keeping the
+    /// time they were first found missing; marked episodes listed again are
+    /// unmarked. The files saved for either are left alone.
+    pub fn reconcile_listing(
+        &self,
+        show_id: &str,
+        listed: &HashSet<String>,
+    ) -> Result<ListingChanges> {
+        let mut statement = self.connection.prepare(
+            "SELECT id, removed_upstream IS NOT NULL FROM episodes WHERE show_id = ?1 ORDER BY id",
+        )?;
+        let archived = statement
+            .query_map(params![show_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<(String, bool)>>>()?;
+
+        let mut changes = ListingChanges::default();
+        for (id, marked) in archived {
+            match (listed.contains(&id), marked) {
+                (false, false) => {
+                    self.connection.execute(
+                        &format!("UPDATE episodes SET removed_upstream = {NOW} WHERE id = ?1"),
+                        params![id],
+                    )?;
+                    insert_change(&self.connection, &id, ChangeKind::Removed, None, None)?;
+                    changes.removed.push(id);
+                }
+                (true, true) => {
+                    self.connection.execute(
+                        "UPDATE episodes SET removed_upstream = NULL WHERE id = ?1",
+                        params![id],
+                    )?;
+                    insert_change(&self.connection, &id, ChangeKind::Restored, None, None)?;
+                    changes.restored.push(id);
+                }
+                _ => {}
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Marks an archived episode played, keeping the time it was first marked, or unplayed with `false`
+    ///
+    /// Either way its listening position is cleared.
+    pub fn set_played(&self, id: &str, played: bool) -> Result<()> {
+        let played_at = if played {
+            format!("COALESCE(played_at, {NOW})")
+        } else {
+            "NULL".to_string()
+        };
+        self.connection.execute(
+            &format!(
+                "UPDATE episodes SET played_at = {played_at}, progress_seconds = NULL WHERE id = ?1"
+            ),
+            params![id],
+        )?;
+        Ok(())
+    }
+
+    /// Records how far into an unplayed archived episode the user has listened
+    pub fn set_progress(&self, id: &str, seconds: f64) -> Result<()> {
+        self.connection.execute(
+            "UPDATE episodes SET progress_seconds = ?2 WHERE id = ?1 AND played_at IS NULL",
+            params![id, seconds],
+        )?;
+        Ok(())
+    }
+
+    /// Upstream changes detected on or after `since` (an ISO 8601 date or time), latest first
+    pub fn changes(&self, since: &str) -> Result<Vec<EpisodeChange>> {
+        let mut statement = self.connection.prepare(
+            "SELECT changes.episode_id, episodes.url, episodes.title, episodes.show_title,
+                 changes.kind, changes.old, changes.new, changes.detected_at
+             FROM changes JOIN episodes ON episodes.id = changes.episode_id
+             WHERE changes.detected_at >= ?1
+             ORDER BY changes.detected_at DESC, changes.rowid DESC",
+        )?;
+        let rows = statement.query_map(params![since], |row| {
+            Ok((
+                row.get::<_, String>(4)?,
+                EpisodeChange {
+                    episode_id: row.get(0)?,
+                    url: row.get(1)?,
+                    title: row.get(2)?,
+                    show_title: row.get(3)?,
+                    kind: ChangeKind::Removed,
+                    old: row.get(5)?,
+                    new: row.get(6)?,
+                    detected_at: row.get(7)?,
+                },
+            ))
+        })?;
+        let mut changes = Vec::new();
+        for row in rows {
+            let (kind, mut change) = row?;
+            // Kinds written by a newer release are left out rather than failing the report
+            let Some(kind) = ChangeKind::parse(&kind) else {
+                continue;
+            };
+            change.kind = kind;
+            changes.push(change);
+        }
+        Ok(changes)
+    }
+
+    /// Records title and description edits of an archived episode about to be updated
+    ///
+    /// An episode not archived yet, or a field the update leaves out, has nothing to compare.
+    fn record_edits(&self, id: &str, title: Option<&str>, description: Option<&str>) -> Result<()> {
+        let previous = self
+            .connection
+            .query_row(
+                "SELECT title, metadata FROM episodes WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?;
+        let Some((old_title, old_metadata)) = previous else {
+            return Ok(());
+        };
+        let old_description = serde_json::from_str::<serde_json::Value>(&old_metadata)?
+            ["description"]
+            .as_str()
+            .map(str::to_string);
+
+        if let Some(title) = title.filter(|title| *title != old_title) {
+            insert_change(
+                &self.connection,
+                id,
+                ChangeKind::Title,
+                Some(&old_title),
+                Some(title),
+            )?;
+        }
+        if let (Some(old), Some(new)) = (old_description.as_deref(), description) {
+            if old != new {
+                insert_change(
+                    &self.connection,
+                    id,
+                    ChangeKind::Description,
+                    Some(old),
+                    Some(new),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// `path` as it is stored: relative to the archive root when under it, else absolute
+    fn stored_path(&self, path: &str) -> Result<String> {
+        let path = std::path::absolute(path).map_err(Error::io("Failed to resolve path"))?;
+        Ok(path
+            .strip_prefix(self.root())
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .into_owned())
+    }
+}
+
+/// Adds a row to the `changes` table, detected now
+fn insert_change(
+    connection: &Connection,
+    episode_id: &str,
+    kind: ChangeKind,
+    old: Option<&str>,
+    new: Option<&str>,
+) -> Result<()> {
+    connection.execute(
+        &format!(
+            "INSERT INTO changes (episode_id, kind, old, new, detected_at)
+             VALUES (?1, ?2, ?3, ?4, {NOW})"
+        ),
+        params![episode_id, kind.as_str(), old, new],
+    )?;
+    Ok(())
+}
+
+/// Parses a personal tag as it is stored: trimmed and lower-cased
+pub fn parse_tag(raw: &str) -> std::result::Result<String, String> {
+    let tag = raw.trim().to_lowercase();
+    if tag.is_empty() {
+        return Err(format!(
+            "Invalid tag '{}': expected a word such as rust",
+            raw
+        ));
+    }
+    Ok(tag)
+}
+
+/// Fails on an archive created by a newer release, returning its schema version otherwise
+fn schema_version(connection: &Connection, path: &Path) -> Result<i64> {
+    let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > SCHEMA_VERSION {
+        return Err(Error::Parse(format!(
+            "Archive {} was created by a newer version of applecast-cli (schema {})",
+            path.display(),
+            version
+        )));
+    }
+    Ok(version)
+}
+
+/// The database an `--db` path names: the path itself, or [`ARCHIVE_FILE`] inside a directory
+pub fn archive_path(path: &str) -> PathBuf {
+    let path = Path::new(path);
+    if path.is_dir() {
+        path.join(ARCHIVE_FILE)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Runs SQLite's `PRAGMA integrity_check` on an existing archive, opened read-only
+pub fn check_integrity(path: &Path) -> Result<()> {
+    if !path.is_file() {
+        return Err(Error::Parse(format!(
+            "Archive {} does not exist",
+            path.display()
+        )));
+    }
+
+    let check = || {
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut statement = connection.prepare("PRAGMA integrity_check")?;
+        let problems = statement.query_map([], |row| row.get::<_, String>(0))?;
+        problems.collect::<rusqlite::Result<Vec<_>>>()
+    };
+    let problems = check().map_err(|e| {
+        Error::Parse(format!(
+            "Archive {} could not be checked: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    if problems == ["ok"] {
+        Ok(())
+    } else {
+        Err(Error::Parse(format!(
+            "Archive {} failed its integrity check: {}",
+            path.display(),
+            problems.join("; ")
+        )))
+    }
+}
+
+/// Several archives read as one, for collections split across local and network storage
+///
+/// Listings and searches merge every archive's results in the single-archive
+/// order. An episode recorded in more than one archive is taken from the first
+/// that has it, so archives are listed in priority order. New records go to
+/// the first, the [`primary`](ArchiveSet::primary) archive. The others are
+/// opened read-only and must already exist, so a mistyped path fails instead
+/// of leaving an empty database behind.
+pub struct ArchiveSet {
+    archives: Vec<Archive>,
+}
+
+impl ArchiveSet {
+    /// Opens every archive in `paths`, in priority order, creating only the primary one if needed
+    pub fn open(paths: &[String]) -> Result<ArchiveSet> {
+        let Some((primary, others)) = paths.split_first() else {
+            return Err(Error::Parse("No archive given".to_string()));
+        };
+        let mut archives = vec![Archive::open(archive_path(primary))?];
+        for path in others {
+            archives.push(Archive::open_read_only(archive_path(path))?);
+        }
+        Ok(ArchiveSet { archives })
+    }
+
+    /// The archive new shows and episodes are recorded in
+    pub fn primary(&self) -> &Archive {
+        &self.archives[0]
+    }
+
+    /// Every archive, in priority order
+    pub fn archives(&self) -> &[Archive] {
+        &self.archives
+    }
+
+    /// Episodes matching `filter` across every archive, newest release first
+    pub fn episodes(&self, filter: &ArchiveFilter) -> Result<Vec<ArchivedEpisode>> {
+        let mut seen = HashSet::new();
+        let mut episodes = Vec::new();
+        for archive in &self.archives {
+            for episode in archive.episodes(filter)? {
+                if seen.insert(episode.id.clone()) {
+                    episodes.push(episode);
+                }
+            }
+        }
+        episodes.sort_by(|a, b| newest_first((&a.publish_date, &a.id), (&b.publish_date, &b.id)));
+        if let Some(limit) = filter.limit {
+            episodes.truncate(limit);
+        }
+        Ok(episodes)
+    }
+
+    /// The `limit` most recently archived episodes across every archive, latest fetch first
+    pub fn recently_archived(&self, limit: usize) -> Result<Vec<ArchivedEpisode>> {
+        let mut seen = HashSet::new();
+        let mut episodes = Vec::new();
+        for archive in &self.archives {
+            for episode in archive.recently_archived(limit)? {
+                if seen.insert(episode.id.clone()) {
+                    episodes.push(episode);
+                }
+            }
+        }
+        episodes.sort_by(|a, b| (&b.fetched_at, &b.id).cmp(&(&a.fetched_at, &a.id)));
+        episodes.truncate(limit);
+        Ok(episodes)
+    }
+
+    /// Transcript cues containing `phrase` across every archive, ordered as in one archive
+    pub fn search_transcripts(
+        &self,
+        phrase: &str,
+        filter: &ArchiveFilter,
+    ) -> Result<Vec<TranscriptMatch>> {
+        let mut owners: HashMap<String, usize> = HashMap::new();
+        let mut matches = Vec::new();
+        for (index, archive) in self.archives.iter().enumerate() {
+            for found in archive.search_transcripts(phrase, filter)? {
+                if *owners.entry(found.episode_id.clone()).or_insert(index) == index {
+                    matches.push(found);
+                }
+            }
+        }
+        // The sort is stable, so each episode's matches stay in spoken order
+        matches.sort_by(|a, b| {
+            newest_first(
+                (&a.publish_date, &a.episode_id),
+                (&b.publish_date, &b.episode_id),
+            )
+        });
+        if let Some(limit) = filter.limit {
+            matches.truncate(limit);
+        }
+        Ok(matches)
+    }
+
+    /// The archived episode with this ID, from the first archive that has it
+    pub fn episode(&self, id: &str) -> Result<Option<ArchivedEpisode>> {
+        for archive in &self.archives {
+            if let Some(episode) = archive.episode(id)? {
+                return Ok(Some(episode));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The archived episode a short ID points to, from the first archive that has it
+    pub fn resolve_short_id(&self, short_id: &str) -> Result<Option<ArchivedEpisode>> {
+        for archive in &self.archives {
+            if let Some(episode) = archive.resolve_short_id(short_id)? {
+                return Ok(Some(episode));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The primary archive's transcripts that are not indexed yet, as URL and resolved path
+    ///
+    /// The other archives are read-only, so only what they already indexed is searchable.
+    pub fn unindexed_transcripts(&self) -> Result<Vec<(&Archive, String, String)>> {
+        let primary = self.primary();
+        Ok(primary
+            .unindexed_transcripts()?
+            .into_iter()
+            .map(|(url, path)| (primary, url, path))
+            .collect())
+    }
+
+    /// Upstream changes detected on or after `since` across every archive, latest first
+    pub fn changes(&self, since: &str) -> Result<Vec<EpisodeChange>> {
+        let mut changes = Vec::new();
+        for archive in &self.archives {
+            changes.extend(archive.changes(since)?);
+        }
+        // The sort is stable, so changes detected together keep their order
+        changes.sort_by(|a, b| b.detected_at.cmp(&a.detected_at));
+        Ok(changes)
+    }
+
+    /// An archived episode's short ID, from the first archive that has the episode
+    pub fn short_id(&self, episode_id: &str) -> Result<Option<String>> {
+        for archive in &self.archives {
+            if archive.episode_ids()?.contains(episode_id) {
+                return archive.short_id(episode_id).map(Some);
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// A stored `path` resolved against the archive root: joined to it when relative
+fn resolve(root: &Path, path: &str) -> String {
+    root.join(path).to_string_lossy().into_owned()
+}
+
+/// Orders `(publish_date, id)` pairs as archive listings are: newest first, undated last, then by ID
+fn newest_first(a: (&Option<String>, &String), b: (&Option<String>, &String)) -> Ordering {
+    a.0.is_none()
+        .cmp(&b.0.is_none())
+        .then_with(|| b.0.cmp(a.0))
+        .then_with(|| b.1.cmp(a.1))
+}
+
+/// Runs a query selecting [`EPISODE_COLUMNS`] and decodes its rows, resolving their paths against `root`
+fn read_episodes(
+    statement: &mut rusqlite::Statement,
+    params: impl rusqlite::Params,
+    root: &Path,
+) -> Result<Vec<ArchivedEpisode>> {
+    let rows = statement.query_map(params, |row| {
+        Ok((
+            ArchivedEpisode {
+                id: row.get(0)?,
+                short_id: row.get::<_, Option<String>>(11)?.unwrap_or_default(),
+                show_id: row.get(1)?,
+                url: row.get(2)?,
+                title: row.get(3)?,
+                show_title: row.get(4)?,
+                publish_date: row.get(5)?,
+                guid: row.get(6)?,
+                directory: row.get(7)?,
+                transcript_path: row.get(8)?,
+                fetched_at: row.get(9)?,
+                metadata: serde_json::Value::Null,
+                removed_upstream: row.get(12)?,
+                rating: row.get(13)?,
+                tags: row
+                    .get::<_, Option<String>>(14)?
+                    .map(|tags| tags.split(',').map(str::to_string).collect())
+                    .unwrap_or_default(),
+                played_at: row.get(15)?,
+                progress_seconds: row.get(16)?,
+            },
+            row.get::<_, String>(10)?,
+        ))
+    })?;
+
+    let mut episodes = Vec::new();
+    for row in rows {
+        let (mut episode, metadata) = row?;
+        episode.metadata = serde_json::from_str(&metadata)?;
+        episode.directory = resolve(root, &episode.directory);
+        episode.transcript_path = episode.transcript_path.map(|path| resolve(root, &path));
+        episodes.push(episode);
+    }
+    Ok(episodes)
+}
+
+/// 64-bit FNV-1a, a hash that stays the same across platforms and releases
+fn fnv1a(text: &str) -> u64 {
+    fnv1a_continue(FNV_OFFSET, text)
+}
+
+/// FNV-1a's starting hash
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Continues an FNV-1a hash over `text`, so texts read one at a time hash like their concatenation
+fn fnv1a_continue(hash: u64, text: &str) -> u64 {
+    text.bytes().fold(hash, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Quotes search text as one FTS5 phrase, so punctuation and operators are matched literally
+fn fts_phrase(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - recorded episodes are listed by show, newest first, and updated in place
+    #[test]
+    fn test_record_and_list_episodes() {
+        // Given an archive with two episodes of one show and one of another
+        let dir = tempfile::tempdir().unwrap();
+        let archive = Archive::open(dir.path().join("archive.sqlite")).unwrap();
+        let episode = |title: &str, date: &str| Metadata {
+            episode_title: title.to_string(),
+            show_title: "Show".to_string(),
+            publish_date_iso8601: Some(date.to_string()),
+            ..Metadata::default()
+        };
+        let older = "https://podcasts.apple.com/us/podcast/show/id1?i=10";
+        let newer = "https://podcasts.apple.com/us/podcast/show/id1?i=11";
+        let other = "https://podcasts.apple.com/us/podcast/other/id2?i=20";
+        archive
+            .record_episode(older, &episode("Old", "2023-01-01"), "out/10", None)
+            .unwrap();
+        archive
+            .record_episode(newer, &episode("New", "2023-02-01"), "out/11", None)
+            .unwrap();
+        archive
+            .record_episode(other, &episode("Other", "2023-03-01"), "out/20", None)
+            .unwrap();
+
+        // When the older episode is processed again from another storefront, saved under the archive root
+        let refetched = "https://podcasts.apple.com/gb/podcast/show/id1?i=10";
+        let transcript = dir.path().join("out/10/transcript.ttml");
+        archive
+            .record_episode(
+                refetched,
+                &episode("Old (remastered)", "2023-01-01"),
+                "out/10",
+                transcript.to_str(),
+            )
+            .unwrap();
+
+        // Then it is updated rather than duplicated, and listing by show is newest first,
+        // with the transcript stored relative to the root and other paths made absolute
+        let listed = archive
+            .episodes(&ArchiveFilter {
+                show_id: Some("1".to_string()),
+                ..ArchiveFilter::default()
+            })
+            .unwrap();
+        let titles: Vec<&str> = listed.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(titles, vec!["New", "Old (remastered)"]);
+        assert_eq!(listed[1].url, refetched);
+        assert_eq!(listed[1].transcript_path.as_deref(), transcript.to_str());
+        let stored: (String, String) = archive
+            .connection
+            .query_row(
+                "SELECT directory, transcript_path FROM episodes WHERE id = '10'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(
+            stored,
+            (
+                cwd.join("out/10").to_string_lossy().into_owned(),
+                "out/10/transcript.ttml".to_string()
+            )
+        );
+        assert_eq!(listed[1].metadata["episode_title"], "Old (remastered)");
+        assert_eq!(
+            archive.episode_ids().unwrap(),
+            HashSet::from(["10".to_string(), "11".to_string(), "20".to_string()])
+        );
+
+        // And updating its metadata changes the listed title too
+        let mut metadata = listed[1].metadata.clone();
+        metadata["episode_title"] = "Old (fixed)".into();
+        archive.update_metadata("10", &metadata).unwrap();
+        let updated = archive.episodes(&ArchiveFilter::default()).unwrap();
+        assert_eq!(updated[2].title, "Old (fixed)");
+        assert_eq!(updated[2].metadata["episode_title"], "Old (fixed)");
+    }
+
+    /// Unit test - short IDs stay with their episode across URL changes
+    #[test]
+    fn test_short_ids_resolve_to_episodes() {
+        // Given an archived episode
+        let dir = tempfile::tempdir().unwrap();
+        let archive = Archive::open(dir.path().join("archive.sqlite")).unwrap();
+        let metadata = Metadata {
+            episode_title: "Weather".to_string(),
+            ..Metadata::default()
+        };
+        archive
+            .record_episode(
+                "https://podcasts.apple.com/us/podcast/show/id1?i=10",
+                &metadata,
+                "out/10",
+                None,
+            )
+            .unwrap();
+        let short_id = archive.short_id("10").unwrap();
+
+        // When it is archived again from another storefront
+        let moved = "https://podcasts.apple.com/gb/podcast/renamed-show/id1?i=10";
+        archive
+            .record_episode(moved, &metadata, "out/10", None)
+            .unwrap();
+
+        // Then its short ID is unchanged and resolves to the latest URL
+        assert_eq!(short_id.len(), SHORT_ID_LEN);
+        assert_eq!(archive.short_id("10").unwrap(), short_id);
+        let resolved = archive.resolve_short_id(&short_id).unwrap().unwrap();
+        assert_eq!(resolved.url, moved);
+        assert_eq!(resolved.short_id, short_id);
+        assert!(archive.resolve_short_id("000000").unwrap().is_none());
+    }
+
+    /// Unit test - transcript search finds phrases with their surrounding cues
+    #[test]
+    fn test_search_transcripts_returns_context() {
+        // Given two archived episodes with indexed transcripts
+        let dir = tempfile::tempdir().unwrap();
+        let archive = Archive::open(dir.path().join("archive.sqlite")).unwrap();
+        let cue = |begin: f64, text: &str| TranscriptCue {
+            begin,
+            end: begin + 4.0,
+            speaker: Some("SPEAKER_1".to_string()),
+            text: text.to_string(),
+        };
+        let first = "https://podcasts.apple.com/us/podcast/show/id1?i=10";
+        let second = "https://podcasts.apple.com/us/podcast/other/id2?i=20";
+        for (url, title) in [(first, "Weather"), (second, "Oceans")] {
+            let metadata = Metadata {
+                episode_title: title.to_string(),
+                ..Metadata::default()
+            };
+            archive
+                .record_episode(url, &metadata, "out", Some("out/transcript.ttml"))
+                .unwrap();
+        }
+        archive
+            .index_transcript(
+                first,
+                &[
+                    cue(0.0, "Welcome back."),
+                    cue(4.0, "Today: climate change, again."),
+                    cue(8.0, "Let's dig in."),
+                ],
+            )
+            .unwrap();
+        let transcript = std::env::current_dir().unwrap().join("out/transcript.ttml");
+        assert_eq!(
+            archive.unindexed_transcripts().unwrap(),
+            vec![(
+                second.to_string(),
+                transcript.to_string_lossy().into_owned()
+            )]
+        );
+        archive
+            .index_transcript(second, &[cue(60.0, "Climate change reaches the deep sea")])
+            .unwrap();
+
+        // When we search for a phrase, then only within one show
+        let all = archive
+            .search_transcripts("climate change", &ArchiveFilter::default())
+            .unwrap();
+        let shown = archive
+            .search_transcripts(
+                "climate change",
+                &ArchiveFilter {
+                    show_id: Some("1".to_string()),
+                    ..ArchiveFilter::default()
+                },
+            )
+            .unwrap();
+
+        // Then both episodes match, each cue with its neighbours from the same episode
+        assert_eq!(all.len(), 2);
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0].title, "Weather");
+        assert_eq!(shown[0].begin, 4.0);
+        assert_eq!(shown[0].before.as_deref(), Some("Welcome back."));
+        assert_eq!(shown[0].after.as_deref(), Some("Let's dig in."));
+        let oceans = all.iter().find(|m| m.title == "Oceans").unwrap();
+        assert_eq!((oceans.before.clone(), oceans.after.clone()), (None, None));
+        assert!(archive
+            .search_transcripts("\"quoted", &ArchiveFilter::default())
+            .unwrap()
+            .is_empty());
+    }
+
+    /// Unit test - an archive set merges its archives' episodes and searches in listing order
+    #[test]
+    fn test_archive_set_merges_archives() {
+        // Given a local archive and a NAS archive root sharing one episode
+        let local_dir = tempfile::tempdir().unwrap();
+        let nas_dir = tempfile::tempdir().unwrap();
+        let local = local_dir.path().join("local.sqlite");
+        let record = |archive: &Archive, id: u32, date: &str, root: &Path, dir: &str| {
+            let url = format!("https://podcasts.apple.com/us/podcast/show/id1?i={}", id);
+            let metadata = Metadata {
+                episode_title: format!("Episode {} in {}", id, dir),
+                publish_date_iso8601: Some(date.to_string()),
+                ..Metadata::default()
+            };
+            let directory = root.join(dir).join(id.to_string());
+            let transcript = directory.join("transcript.ttml");
+            archive
+                .record_episode(
+                    &url,
+                    &metadata,
+                    directory.to_str().unwrap(),
+                    transcript.to_str(),
+                )
+                .unwrap();
+            let cue = TranscriptCue {
+                begin: 1.0,
+                end: 2.0,
+                speaker: None,
+                text: format!("Talking about budgets in episode {}", id),
+            };
+            archive.index_transcript(&url, &[cue]).unwrap();
+        };
+        {
+            let archive = Archive::open(&local).unwrap();
+            record(&archive, 10, "2023-01-01", local_dir.path(), "local");
+            record(&archive, 11, "2023-03-01", local_dir.path(), "local");
+            let nas = Archive::open(nas_dir.path().join(ARCHIVE_FILE)).unwrap();
+            record(&nas, 11, "2023-03-01", nas_dir.path(), "nas");
+            record(&nas, 12, "2023-02-01", nas_dir.path(), "nas");
+        }
+
+        // When they are opened together, the NAS through its root directory
+        let paths = [
+            local.to_string_lossy().into_owned(),
+            nas_dir.path().to_string_lossy().into_owned(),
+        ];
+        let archives = ArchiveSet::open(&paths).unwrap();
+        let episodes = archives.episodes(&ArchiveFilter::default()).unwrap();
+        let matches = archives
+            .search_transcripts("budgets", &ArchiveFilter::default())
+            .unwrap();
+
+        // Then each episode appears once, newest first, the shared one from the first archive,
+        // and each archive's paths are resolved against its own root
+        let ids: Vec<&str> = episodes.iter().map(|episode| episode.id.as_str()).collect();
+        assert_eq!(ids, vec!["11", "12", "10"]);
+        assert_eq!(
+            episodes[0].directory,
+            local_dir.path().join("local/11").to_str().unwrap()
+        );
+        assert_eq!(
+            episodes[1].transcript_path.as_deref(),
+            Some(
+                nas_dir
+                    .path()
+                    .join("nas/12/transcript.ttml")
+                    .to_str()
+                    .unwrap()
+            )
+        );
+        let matched: Vec<&str> = matches.iter().map(|m| m.episode_id.as_str()).collect();
+        assert_eq!(matched, vec!["11", "12", "10"]);
+        let limited = ArchiveFilter {
+            limit: Some(2),
+            ..ArchiveFilter::default()
+        };
+        assert_eq!(archives.episodes(&limited).unwrap().len(), 2);
+        assert_eq!(
+            archives.episode("12").unwrap().unwrap().title,
+            "Episode 12 in nas"
+        );
+        assert!(archives.short_id("12").unwrap().is_some());
+        assert!(archives.short_id("99").unwrap().is_none());
+
+        // And a secondary archive that does not exist fails without being created
+        let missing = nas_dir.path().join("missing.sqlite");
+        let paths = [paths[0].clone(), missing.to_string_lossy().into_owned()];
+        assert!(ArchiveSet::open(&paths).is_err());
+        assert!(!missing.exists());
+    }
+
+    /// Unit test - the integrity check passes a sound archive and fails a damaged or missing one
+    #[test]
+    fn test_check_integrity() {
+        // Given a new archive, a file that is not a database, and a path with nothing there
+        let dir = tempfile::tempdir().unwrap();
+        let sound = dir.path().join("archive.sqlite");
+        Archive::open(&sound).unwrap();
+        let damaged = dir.path().join("damaged.sqlite");
+        fs::write(&damaged, "not a database, just text long enough to be read").unwrap();
+        let missing = dir.path().join("missing.sqlite");
+
+        // When each is checked
+        // Then only the archive passes, and the missing one is not created
+        assert!(check_integrity(&sound).is_ok());
+        assert!(check_integrity(&damaged).is_err());
+        let error = check_integrity(&missing).unwrap_err().to_string();
+        assert!(error.contains("does not exist"), "{}", error);
+        assert!(!missing.exists());
+    }
+
+    /// Unit test - reconcile_listing marks episodes missing from a listing and unmarks returning ones
+    #[test]
+    fn test_reconcile_listing_marks_removed_episodes() {
+        // Given an archive with two episodes of one show and one of another
+        let dir = tempfile::tempdir().unwrap();
+        let archive = Archive::open(dir.path().join("archive.sqlite")).unwrap();
+        for url in [
+            "https://podcasts.apple.com/us/podcast/show/id1?i=10",
+            "https://podcasts.apple.com/us/podcast/show/id1?i=11",
+            "https://podcasts.apple.com/us/podcast/other/id2?i=20",
+        ] {
+            archive
+                .record_episode(url, &Metadata::default(), "out", None)
+                .unwrap();
+        }
+        let removed = || {
+            archive
+                .episodes(&ArchiveFilter {
+                    removed: true,
+                    ..ArchiveFilter::default()
+                })
+                .unwrap()
+                .into_iter()
+                .map(|episode| episode.id)
+                .collect::<Vec<_>>()
+        };
+
+        // When the show's listing drops episode 10, then lists it again
+        let listed = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect();
+        let dropped = archive.reconcile_listing("1", &listed(&["11"])).unwrap();
+        let marked = removed();
+        let first_seen = archive.episode("10").unwrap().unwrap().removed_upstream;
+        archive.reconcile_listing("1", &listed(&["11"])).unwrap();
+        let still = archive.episode("10").unwrap().unwrap().removed_upstream;
+        let restored = archive
+            .reconcile_listing("1", &listed(&["10", "11"]))
+            .unwrap();
+
+        // Then only that episode is marked, keeping when it was first missed, and is unmarked on return
+        assert_eq!(dropped.removed, ["10"]);
+        assert_eq!(marked, ["10"]);
+        assert!(first_seen.is_some());
+        assert_eq!(still, first_seen);
+        assert_eq!(restored.restored, ["10"]);
+        assert!(removed().is_empty());
+    }
+
+    /// Unit test - the archive records edits, replaced transcripts and removals as changes
+    #[test]
+    fn test_changes_record_upstream_edits() {
+        // Given an archived episode with an indexed transcript
+        let dir = tempfile::tempdir().unwrap();
+        let archive = Archive::open(dir.path().join("archive.sqlite")).unwrap();
+        let url = "https://podcasts.apple.com/us/podcast/show/id1?i=10";
+        let metadata = |title: &str, description: &str| Metadata {
+            episode_title: title.to_string(),
+            description: description.to_string(),
+            ..Metadata::default()
+        };
+        let cue = |text: &str| TranscriptCue {
+            begin: 0.0,
+            end: 4.0,
+            speaker: None,
+            text: text.to_string(),
+        };
+        archive
+            .record_episode(url, &metadata("Launch", "About launches."), "out", None)
+            .unwrap();
+        archive.index_transcript(url, &[cue("Hello")]).unwrap();
+
+        // When it is recorded unchanged, then with a new title, description and transcript, then dropped
+        archive
+            .record_episode(url, &metadata("Launch", "About launches."), "out", None)
+            .unwrap();
+        archive.index_transcript(url, &[cue("Hello")]).unwrap();
+        archive
+            .record_episode(
+                url,
+                &metadata("Launch Day", "About launch day."),
+                "out",
+                None,
+            )
+            .unwrap();
+        archive
+            .index_transcript(url, &[cue("Hello again")])
+            .unwrap();
+        archive.reconcile_listing("1", &HashSet::new()).unwrap();
+
+        // Then each change is listed once, latest first, with edits keeping both values
+        let changes = archive.changes("2000-01-01").unwrap();
+        let kinds: Vec<_> = changes.iter().map(|change| change.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                ChangeKind::Removed,
+                ChangeKind::Transcript,
+                ChangeKind::Description,
+                ChangeKind::Title
+            ]
+        );
+        assert_eq!(changes[3].old.as_deref(), Some("Launch"));
+        assert_eq!(changes[3].new.as_deref(), Some("Launch Day"));
+        assert_eq!(changes[0].title, "Launch Day");
+        assert!(archive.changes("2999-01-01").unwrap().is_empty());
+    }
+
+    /// Unit test - personal tags and ratings survive re-recording and filter listings
+    #[test]
+    fn test_tags_and_ratings_filter_episodes() {
+        // Given two archived episodes, one tagged and rated
+        let dir = tempfile::tempdir().unwrap();
+        let archive = Archive::open(dir.path().join("archive.sqlite")).unwrap();
+        let url = |id: u32| format!("https://podcasts.apple.com/us/podcast/show/id1?i={}", id);
+        for id in [10, 11] {
+            archive
+                .record_episode(&url(id), &Metadata::default(), "out", None)
+                .unwrap();
+        }
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.to_string()).collect::<Vec<_>>();
+        archive
+            .add_tags("10", &tags(&["rust", "interview"]))
+            .unwrap();
+        archive.add_tags("10", &tags(&["rust"])).unwrap();
+        archive.add_tags("11", &tags(&["interview"])).unwrap();
+        archive.set_rating("10", Some(5)).unwrap();
+
+        // When the tagged episode is recorded again and one tag comes off the other
+        archive
+            .record_episode(&url(10), &Metadata::default(), "out", None)
+            .unwrap();
+        archive.remove_tags("11", &tags(&["interview"])).unwrap();
+        let listed = |filter: ArchiveFilter| {
+            archive
+                .episodes(&filter)
+                .unwrap()
+                .into_iter()
+                .map(|episode| episode.id)
+                .collect::<Vec<_>>()
+        };
+
+        // Then its tags are kept once each, sorted, with its rating, and both filter the listing
+        let episode = archive.episode("10").unwrap().unwrap();
+        assert_eq!(episode.tags, ["interview", "rust"]);
+        assert_eq!(episode.rating, Some(5));
+        assert!(archive.episode("11").unwrap().unwrap().tags.is_empty());
+        let tagged = ArchiveFilter {
+            tag: Some("interview".to_string()),
+            ..ArchiveFilter::default()
+        };
+        assert_eq!(listed(tagged), ["10"]);
+        let rated = ArchiveFilter {
+            min_rating: Some(4),
+            ..ArchiveFilter::default()
+        };
+        assert_eq!(listed(rated), ["10"]);
+        archive.set_rating("10", None).unwrap();
+        assert_eq!(archive.episode("10").unwrap().unwrap().rating, None);
+    }
+}
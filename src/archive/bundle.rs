@@ -0,0 +1,173 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::Serialize;
+
+use super::merge::{episode_files, merge, moved, MergeReport};
+use super::{Archive, ArchiveFilter, ARCHIVE_FILE};
+use crate::error::{Error, Result};
+
+/// What [`export`] put in a bundle
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct ExportReport {
+    /// IDs of the episodes bundled, newest first
+    pub episodes: Vec<String>,
+    /// Saved files bundled with them, not counting the database
+    pub files: usize,
+}
+
+/// Writes the episodes `filter` selects, with their shows and saved files, to a `.tar.zst` bundle
+///
+/// The bundle holds its own [`ARCHIVE_FILE`] at the top, and every file an
+/// episode saved under the archive root at the same relative path, so it
+/// unpacks into a working archive. Episodes saved outside the root are
+/// recorded with their absolute paths and their files are not bundled. The
+/// bundle is written beside `out` and renamed into place once complete.
+pub fn export(archive: &Archive, filter: &ArchiveFilter, out: &Path) -> Result<ExportReport> {
+    let staging = staging_dir("export")?;
+    let result = write_bundle(archive, filter, out, &staging);
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+fn write_bundle(
+    archive: &Archive,
+    filter: &ArchiveFilter,
+    out: &Path,
+    staging: &Path,
+) -> Result<ExportReport> {
+    let episodes = archive.episodes(filter)?;
+    let bundled = Archive::open(staging.join(ARCHIVE_FILE))?;
+    let show_ids: HashSet<_> = episodes.iter().filter_map(|e| e.show_id.clone()).collect();
+    for show in archive.shows()? {
+        if show_ids.contains(&show.id) {
+            bundled.import_show(&show)?;
+        }
+    }
+
+    let partial = PathBuf::from(format!("{}.part", out.display()));
+    let file = File::create(&partial).map_err(Error::io("Failed to create archive bundle"))?;
+    let encoder = zstd::Encoder::new(file, 0).map_err(Error::io("Failed to compress bundle"))?;
+    let mut tar = tar::Builder::new(encoder);
+    let mut report = ExportReport::default();
+    for episode in &episodes {
+        for (path, relative) in episode_files(episode, archive.root())? {
+            tar.append_path_with_name(&path, &relative)
+                .map_err(Error::io("Failed to write archive bundle"))?;
+            report.files += 1;
+        }
+        bundled.import_episode(&moved(episode, archive.root(), bundled.root()))?;
+        report.episodes.push(episode.id.clone());
+    }
+    // Closing the connection flushes the database before it is bundled
+    drop(bundled);
+    tar.append_path_with_name(staging.join(ARCHIVE_FILE), ARCHIVE_FILE)
+        .map_err(Error::io("Failed to write archive bundle"))?;
+    tar.into_inner()
+        .and_then(|encoder| encoder.finish())
+        .map_err(Error::io("Failed to write archive bundle"))?;
+    fs::rename(&partial, out).map_err(Error::io("Failed to write archive bundle"))?;
+    Ok(report)
+}
+
+/// Unpacks an [`export`] bundle and merges it into `archive`, as `archive merge` would
+///
+/// A bundle from an older release is migrated while unpacked.
+pub fn import(archive: &Archive, bundle: &Path) -> Result<MergeReport> {
+    let staging = staging_dir("import")?;
+    let result = read_bundle(archive, bundle, &staging);
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+fn read_bundle(archive: &Archive, bundle: &Path, staging: &Path) -> Result<MergeReport> {
+    let file = File::open(bundle).map_err(Error::io("Failed to open archive bundle"))?;
+    let decoder = zstd::Decoder::new(file).map_err(Error::io("Failed to decompress bundle"))?;
+    tar::Archive::new(decoder)
+        .unpack(staging)
+        .map_err(Error::io("Failed to unpack archive bundle"))?;
+    if !staging.join(ARCHIVE_FILE).is_file() {
+        return Err(Error::Parse(format!(
+            "{} is not an archive bundle: it has no {}",
+            bundle.display(),
+            ARCHIVE_FILE
+        )));
+    }
+    let other = Archive::open(staging.join(ARCHIVE_FILE))?;
+    merge(archive, &other)
+}
+
+/// An empty scratch directory under the system temp directory, unique to this call
+fn staging_dir(purpose: &str) -> Result<PathBuf> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "applecast-{}-{}-{}",
+        purpose,
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).map_err(Error::io("Failed to create staging directory"))?;
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Metadata;
+
+    /// Unit test - an exported bundle imports into another archive with its files
+    #[test]
+    fn test_export_and_import_round_trip() {
+        // Given an archive with two episodes of one show and one of another
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let source = Archive::open(source_dir.path().join(ARCHIVE_FILE)).unwrap();
+        let save = |id: u32, show: u32, date: &str| {
+            let dir = source_dir.path().join(format!("episodes/{}", id));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("metadata.json"), "{}").unwrap();
+            fs::write(dir.join("audio.mp3.part"), "partial").unwrap();
+            let metadata = Metadata {
+                episode_title: format!("Episode {}", id),
+                publish_date_iso8601: Some(date.to_string()),
+                ..Metadata::default()
+            };
+            let url = format!(
+                "https://podcasts.apple.com/us/podcast/s/id{}?i={}",
+                show, id
+            );
+            source
+                .record_episode(&url, &metadata, dir.to_str().unwrap(), None)
+                .unwrap();
+        };
+        save(10, 1, "2024-01-05T00:00:00Z");
+        save(20, 1, "2024-03-05T00:00:00Z");
+        save(30, 2, "2024-03-06T00:00:00Z");
+
+        // When the first show's episodes since February are exported and imported elsewhere
+        let out = source_dir.path().join("snapshot.tar.zst");
+        let filter = ArchiveFilter {
+            show_id: Some("1".to_string()),
+            since: Some("2024-02-01".to_string()),
+            ..ArchiveFilter::default()
+        };
+        let exported = export(&source, &filter, &out).unwrap();
+        let target = Archive::open(target_dir.path().join(ARCHIVE_FILE)).unwrap();
+        let imported = import(&target, &out).unwrap();
+
+        // Then only that episode travels, with its saved files but not partial downloads
+        assert_eq!(exported.episodes, vec!["20"]);
+        assert_eq!(exported.files, 1);
+        assert!(!Path::new(&format!("{}.part", out.display())).exists());
+        assert_eq!(imported.added, vec!["20"]);
+        let episode = target.episode("20").unwrap().unwrap();
+        let copied = target_dir.path().join("episodes/20");
+        assert_eq!(episode.directory, copied.to_str().unwrap());
+        assert!(copied.join("metadata.json").is_file());
+        assert!(!copied.join("audio.mp3.part").exists());
+        assert_eq!(target.episodes(&ArchiveFilter::default()).unwrap().len(), 1);
+    }
+}
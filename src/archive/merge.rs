@@ -0,0 +1,283 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rusqlite::params;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use super::{Archive, ArchiveFilter, ArchivedEpisode};
+use crate::compress::{read_artifact, stored_path};
+use crate::error::{Error, Result};
+use crate::transcript::stream::MappedTtml;
+
+/// What [`merge`] did with the other archive's episodes
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct MergeReport {
+    /// Episodes only the other archive had
+    pub added: Vec<String>,
+    /// Episodes whose copy there differed and was fetched later, so it replaced the one here
+    pub updated: Vec<String>,
+    /// Episodes whose copy here differed and was fetched later, so it was kept
+    pub kept: Vec<String>,
+    /// Episodes recorded with the same metadata and transcript in both
+    pub unchanged: usize,
+    /// Files copied into this archive's root
+    pub files_copied: usize,
+}
+
+/// Imports another archive's shows, episodes and saved files into `archive`
+///
+/// Episodes are matched by ID. One missing here is added. One recorded in
+/// both is compared by a hash of its metadata and transcript, and when they
+/// differ the copy fetched later wins. An imported episode's files under the
+/// other archive's root are copied to the same place under this archive's
+/// root; files anywhere else are referenced where they are. Personal tags from
+/// both archives are kept, and so are this archive's rating and listening
+/// state when it has them.
+pub fn merge(archive: &Archive, other: &Archive) -> Result<MergeReport> {
+    for show in other.shows()? {
+        archive.import_show(&show)?;
+    }
+
+    let mut report = MergeReport::default();
+    for theirs in other.episodes(&ArchiveFilter::default())? {
+        let ours = archive.episode(&theirs.id)?;
+        match &ours {
+            Some(ours) if content_hash(ours) == content_hash(&theirs) => {
+                keep_personal(archive, ours, &theirs)?;
+                report.unchanged += 1;
+                continue;
+            }
+            Some(ours) if ours.fetched_at >= theirs.fetched_at => {
+                keep_personal(archive, ours, &theirs)?;
+                report.kept.push(theirs.id);
+                continue;
+            }
+            _ => {}
+        }
+
+        let mut imported = theirs.clone();
+        if other.root() != archive.root() {
+            for (path, relative) in episode_files(&theirs, other.root())? {
+                report.files_copied += copy_file(&path, &archive.root().join(relative))?;
+            }
+            imported = moved(&theirs, other.root(), archive.root());
+        }
+        archive.import_episode(&imported)?;
+        reindex(archive, &imported)?;
+        match ours {
+            Some(ours) => {
+                keep_personal(archive, &ours, &theirs)?;
+                report.updated.push(theirs.id)
+            }
+            None => report.added.push(theirs.id),
+        }
+    }
+    Ok(report)
+}
+
+/// Combines both copies' personal tags into `archive`, preferring our rating and listening state to theirs
+fn keep_personal(
+    archive: &Archive,
+    ours: &ArchivedEpisode,
+    theirs: &ArchivedEpisode,
+) -> Result<()> {
+    archive.add_tags(&ours.id, &ours.tags)?;
+    archive.add_tags(&ours.id, &theirs.tags)?;
+    archive.set_rating(&ours.id, ours.rating.or(theirs.rating))?;
+    let played_at = ours.played_at.as_ref().or(theirs.played_at.as_ref());
+    let progress = match played_at {
+        Some(_) => None,
+        None => ours.progress_seconds.or(theirs.progress_seconds),
+    };
+    archive.connection.execute(
+        "UPDATE episodes SET played_at = ?2, progress_seconds = ?3 WHERE id = ?1",
+        params![ours.id, played_at, progress],
+    )?;
+    Ok(())
+}
+
+/// SHA-256 of an episode's recorded metadata and the transcript it points to
+fn content_hash(episode: &ArchivedEpisode) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(episode.metadata.to_string());
+    if let Some(ttml) = episode
+        .transcript_path
+        .as_deref()
+        .and_then(|path| read_artifact(path).ok())
+    {
+        hasher.update(ttml);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// The episode with its paths under `from` moved to the same place under `to`
+pub(super) fn moved(episode: &ArchivedEpisode, from: &Path, to: &Path) -> ArchivedEpisode {
+    let rebase = |path: &str| match Path::new(path).strip_prefix(from) {
+        Ok(relative) => to.join(relative).to_string_lossy().into_owned(),
+        Err(_) => path.to_string(),
+    };
+    ArchivedEpisode {
+        directory: rebase(&episode.directory),
+        transcript_path: episode.transcript_path.as_deref().map(rebase),
+        ..episode.clone()
+    }
+}
+
+/// The files an episode saved under `root`, each with its path relative to `root`
+///
+/// That is everything in its directory but partial downloads, and its
+/// transcript when it was saved elsewhere under `root`.
+pub(super) fn episode_files(
+    episode: &ArchivedEpisode,
+    root: &Path,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut files = Vec::new();
+    let directory = Path::new(&episode.directory);
+    if directory.starts_with(root) {
+        walk(directory, &mut files)?;
+    }
+    if let Some(stored) = episode
+        .transcript_path
+        .as_deref()
+        .map(Path::new)
+        .filter(|path| path.starts_with(root) && !path.starts_with(directory))
+        .and_then(stored_path)
+    {
+        files.push(stored);
+    }
+    Ok(files
+        .into_iter()
+        .filter_map(|path| {
+            let relative = path.strip_prefix(root).ok()?.to_path_buf();
+            Some((path, relative))
+        })
+        .collect())
+}
+
+/// Adds every file under `dir` to `files`; a directory that does not exist adds nothing
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+    for entry in entries {
+        let entry = entry.map_err(Error::io("Failed to read episode directory"))?;
+        let path = entry.path();
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else if !entry.file_name().to_string_lossy().ends_with(".part") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn copy_file(from: &Path, to: &Path) -> Result<usize> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent).map_err(Error::io("Failed to create episode directory"))?;
+    }
+    fs::copy(from, to).map_err(Error::io("Failed to copy artifact"))?;
+    Ok(1)
+}
+
+/// Indexes an imported episode's transcript for search
+///
+/// A transcript that cannot be read leaves the episode unindexed, rather than
+/// with the index of the copy it replaced, so `grep` tries it again later.
+fn reindex(archive: &Archive, episode: &ArchivedEpisode) -> Result<()> {
+    let ttml = episode
+        .transcript_path
+        .as_deref()
+        .and_then(|path| MappedTtml::open(path).ok());
+    if let Some(Ok(cues)) = ttml.as_ref().map(MappedTtml::cues) {
+        if archive.index_transcript_stream(&episode.url, cues).is_ok() {
+            return Ok(());
+        }
+    }
+    archive.index_transcript_stream(&episode.url, std::iter::empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::ARCHIVE_FILE;
+    use crate::metadata::Metadata;
+
+    /// Unit test - merge adds missing episodes with their files and keeps the later of two differing copies
+    #[test]
+    fn test_merge_imports_and_resolves_conflicts() {
+        // Given a laptop archive and a desktop archive, each with its own episode,
+        // one episode saved identically in both, and one saved differently in each
+        let laptop_dir = tempfile::tempdir().unwrap();
+        let desktop_dir = tempfile::tempdir().unwrap();
+        let laptop = Archive::open(laptop_dir.path().join(ARCHIVE_FILE)).unwrap();
+        let desktop = Archive::open(desktop_dir.path().join(ARCHIVE_FILE)).unwrap();
+        let save = |archive: &Archive, id: u32, title: &str, text: &str| {
+            let dir = archive.root().join(format!("episodes/{}", id));
+            fs::create_dir_all(&dir).unwrap();
+            let transcript = dir.join("transcript.ttml");
+            fs::write(
+                &transcript,
+                format!(
+                    r#"<tt><body><div><p begin="0s" end="1s">{}</p></div></body></tt>"#,
+                    text
+                ),
+            )
+            .unwrap();
+            let metadata = Metadata {
+                episode_title: title.to_string(),
+                ..Metadata::default()
+            };
+            let url = format!("https://podcasts.apple.com/us/podcast/show/id1?i={}", id);
+            archive
+                .record_episode(&url, &metadata, dir.to_str().unwrap(), transcript.to_str())
+                .unwrap();
+        };
+        save(&laptop, 10, "Only on the laptop", "laptop notes");
+        save(&desktop, 20, "Only on the desktop", "desktop notes");
+        save(&laptop, 30, "Same everywhere", "shared");
+        save(&desktop, 30, "Same everywhere", "shared");
+        save(&laptop, 40, "Old title", "first cut");
+        save(&desktop, 40, "New title", "final cut");
+        desktop
+            .connection
+            .execute(
+                "UPDATE episodes SET fetched_at = '2099-01-01T00:00:00Z' WHERE id = '40'",
+                [],
+            )
+            .unwrap();
+
+        // When the desktop archive is merged into the laptop's
+        let report = merge(&laptop, &desktop).unwrap();
+
+        // Then the desktop-only episode and its files are imported under the laptop root,
+        // the identical one is left alone and the later edit wins
+        assert_eq!(report.added, vec!["20"]);
+        assert_eq!(report.updated, vec!["40"]);
+        assert!(report.kept.is_empty());
+        assert_eq!(report.unchanged, 1);
+        assert_eq!(report.files_copied, 2);
+        let imported = laptop.episode("20").unwrap().unwrap();
+        let copied = laptop_dir.path().join("episodes/20");
+        assert_eq!(imported.directory, copied.to_str().unwrap());
+        assert!(copied.join("transcript.ttml").is_file());
+        assert_eq!(laptop.episode("40").unwrap().unwrap().title, "New title");
+        assert_eq!(
+            laptop.episode("10").unwrap().unwrap().title,
+            "Only on the laptop"
+        );
+        let found = laptop
+            .search_transcripts("final cut", &ArchiveFilter::default())
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(laptop
+            .search_transcripts("first cut", &ArchiveFilter::default())
+            .unwrap()
+            .is_empty());
+
+        // And merging the other way keeps the desktop's later copy
+        let back = merge(&desktop, &laptop).unwrap();
+        assert_eq!(back.added, vec!["10"]);
+        assert_eq!(back.unchanged, 3);
+    }
+}
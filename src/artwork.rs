@@ -0,0 +1,152 @@
+use regex::Regex;
+
+use crate::error::{Error, Result};
+use crate::fetch::{send_with_retry, HttpOptions};
+use crate::metadata::extract_metadata;
+use crate::server_data::{find_field, serialized_server_data};
+
+/// Dimensions requested from Apple's artwork image service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArtworkSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ArtworkSize {
+    /// The largest square Apple serves for podcast artwork
+    pub const DEFAULT: ArtworkSize = ArtworkSize {
+        width: 3000,
+        height: 3000,
+    };
+
+    /// File name for artwork saved at this size, e.g. `artwork-600x600.jpg`
+    pub fn file_name(&self) -> String {
+        format!("artwork-{}x{}.jpg", self.width, self.height)
+    }
+}
+
+/// Parses an `--download-artwork` size: `600` for a square or `600x400`
+pub fn parse_artwork_size(raw: &str) -> std::result::Result<ArtworkSize, String> {
+    let invalid = || {
+        format!(
+            "Invalid artwork size '{}': expected WIDTHxHEIGHT, e.g. 600x600",
+            raw
+        )
+    };
+    let (width, height) = match raw.trim().split_once(['x', 'X']) {
+        Some((width, height)) => (width, height),
+        None => (raw.trim(), raw.trim()),
+    };
+
+    match (width.parse::<u32>(), height.parse::<u32>()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok(ArtworkSize { width, height }),
+        _ => Err(invalid()),
+    }
+}
+
+/// Replaces the `{w}`, `{h}`, `{c}` and `{f}` placeholders in an Apple artwork URL template
+pub fn fill_artwork_template(template: &str, size: ArtworkSize) -> String {
+    template
+        .replace("{w}", &size.width.to_string())
+        .replace("{h}", &size.height.to_string())
+        .replace("{c}", "bb")
+        .replace("{f}", "jpg")
+}
+
+/// Searches the episode HTML for its artwork URL template
+///
+/// The serialized data carries the template directly; otherwise the sized
+/// image URL from the JSON-LD schema is turned back into a template.
+pub fn find_artwork_template(html: &str) -> Result<Option<String>> {
+    if let Some(template) = serialized_server_data(html)?.and_then(|data| artwork_template(&data)) {
+        return Ok(Some(template));
+    }
+
+    let Some(url) = extract_metadata(html)?.artwork_url else {
+        return Ok(None);
+    };
+    Ok(Some(artwork_template_from_url(&url)?))
+}
+
+/// Reads the first artwork URL template in serialized data
+pub fn artwork_template(data: &serde_json::Value) -> Option<String> {
+    let artwork = find_field(data, "artwork")?;
+    artwork["template"]
+        .as_str()
+        .or_else(|| artwork["url"].as_str())
+        .or_else(|| artwork.as_str())
+        .map(str::to_string)
+}
+
+/// Turns a sized artwork URL (`.../1200x1200bb.jpg`) into a template; other URLs are kept as-is
+pub(crate) fn artwork_template_from_url(url: &str) -> Result<String> {
+    let re = Regex::new(r"/\d+x\d+[a-z]*\.(?:jpg|jpeg|png|webp)$")?;
+    Ok(re.replace(url, "/{w}x{h}{c}.{f}").into_owned())
+}
+
+/// Downloads an artwork image and returns its bytes
+pub async fn download_artwork(url: &str, options: &HttpOptions) -> Result<Vec<u8>> {
+    let response = send_with_retry(options, |client| client.get(url)).await?;
+    if !response.status().is_success() {
+        return Err(Error::Status {
+            status: response.status(),
+            retry_after: None,
+        });
+    }
+
+    let bytes = response.bytes().await.map_err(Error::Body)?;
+    Ok(bytes.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - parse_artwork_size accepts squares and rectangles
+    #[test]
+    fn test_parse_artwork_size() {
+        // Given artwork size arguments
+        // When we parse them
+        // Then a single number is a square, WxH is taken as given, and the rest are rejected
+        assert_eq!(
+            parse_artwork_size("600"),
+            Ok(ArtworkSize {
+                width: 600,
+                height: 600
+            })
+        );
+        assert_eq!(
+            parse_artwork_size("1400x1000"),
+            Ok(ArtworkSize {
+                width: 1400,
+                height: 1000
+            })
+        );
+        assert!(parse_artwork_size("0x600").is_err());
+        assert!(parse_artwork_size("large").is_err());
+    }
+
+    /// Unit test - find_artwork_template prefers the serialized template and falls back to JSON-LD
+    #[test]
+    fn test_find_artwork_template() {
+        // Given a page with a serialized template and one with only a sized JSON-LD image
+        let serialized = r#"<script type="application/json" id="serialized-server-data">[{"data":{"artwork":{"template":"https://is1.example.com/a/{w}x{h}{c}.{f}"}}}]</script>"#;
+        let json_ld = r#"<html><head><script id="schema:episode" type="application/ld+json">
+            {"name": "Episode 1", "image": "https://is1.example.com/a/1200x1200bb.jpg"}
+            </script></head></html>"#;
+
+        // When we find their templates and fill them at 600x600
+        let size = parse_artwork_size("600").unwrap();
+        let filled = |html: &str| {
+            find_artwork_template(html)
+                .unwrap()
+                .map(|template| fill_artwork_template(&template, size))
+        };
+
+        // Then both resolve to the requested size
+        let expected = Some("https://is1.example.com/a/600x600bb.jpg".to_string());
+        assert_eq!(filled(serialized), expected);
+        assert_eq!(filled(json_ld), expected);
+        assert_eq!(filled("<html></html>"), None);
+    }
+}
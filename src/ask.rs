@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::pack::PackChunk;
+use crate::transcript::format_offset;
+
+/// Passages `ask` retrieves when no `--top` is given
+pub const DEFAULT_TOP_PASSAGES: usize = 5;
+
+/// BM25 term-frequency saturation
+const BM25_K1: f64 = 1.2;
+
+/// BM25 length normalization
+const BM25_B: f64 = 0.75;
+
+/// Words that phrase a question rather than say what it is about
+const QUESTION_WORDS: &[&str] = &[
+    "a",
+    "about",
+    "an",
+    "and",
+    "are",
+    "did",
+    "do",
+    "does",
+    "for",
+    "he",
+    "how",
+    "in",
+    "is",
+    "it",
+    "mention",
+    "mentioned",
+    "of",
+    "on",
+    "said",
+    "say",
+    "says",
+    "she",
+    "talk",
+    "talked",
+    "the",
+    "they",
+    "to",
+    "was",
+    "what",
+    "when",
+    "where",
+    "which",
+    "who",
+    "why",
+];
+
+/// How `ask` retrieves passages
+#[derive(Debug, Clone, PartialEq)]
+pub struct AskOptions {
+    /// Most passages retrieved
+    pub top: usize,
+    /// Length of the transcript chunks searched, in seconds
+    pub passage_seconds: f64,
+}
+
+/// A transcript passage retrieved for a question
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Passage {
+    /// Seconds from the start of the episode
+    pub start: f64,
+    pub end: f64,
+    /// BM25 relevance to the question; higher is more relevant
+    pub score: f64,
+    /// Speaker-attributed paragraphs, one per line
+    pub text: String,
+}
+
+impl Passage {
+    /// The passage's time range, as `M:SS–M:SS`
+    pub fn timestamp(&self) -> String {
+        format!("{}–{}", format_offset(self.start), format_offset(self.end))
+    }
+}
+
+/// Lower-cased words of `text`
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// The words of a question that say what it is about
+pub fn query_terms(question: &str) -> Vec<String> {
+    let mut terms: Vec<String> = words(question)
+        .into_iter()
+        .filter(|word| !QUESTION_WORDS.contains(&word.as_str()))
+        .collect();
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+/// The `top` chunks most relevant to `question` by Okapi BM25, best first
+///
+/// Each chunk is a document and the episode's chunks are the collection, so a
+/// term weighs more the fewer chunks mention it. Chunks matching no term are
+/// left out.
+pub fn rank_passages(chunks: &[PackChunk], question: &str, top: usize) -> Vec<Passage> {
+    let terms = query_terms(question);
+    let documents: Vec<Vec<String>> = chunks
+        .iter()
+        .map(|chunk| words(&chunk.paragraphs.join(" ")))
+        .collect();
+    if documents.is_empty() {
+        return Vec::new();
+    }
+    let count = documents.len() as f64;
+    let average_length = documents.iter().map(Vec::len).sum::<usize>() as f64 / count;
+
+    let idf: HashMap<&str, f64> = terms
+        .iter()
+        .map(|term| {
+            let frequency = documents
+                .iter()
+                .filter(|document| document.contains(term))
+                .count() as f64;
+            let idf = ((count - frequency + 0.5) / (frequency + 0.5) + 1.0).ln();
+            (term.as_str(), idf)
+        })
+        .collect();
+
+    let mut passages: Vec<Passage> = chunks
+        .iter()
+        .zip(&documents)
+        .filter_map(|(chunk, document)| {
+            let length = document.len() as f64 / average_length.max(1.0);
+            let score: f64 = terms
+                .iter()
+                .map(|term| {
+                    let tf = document.iter().filter(|word| *word == term).count() as f64;
+                    idf[term.as_str()] * tf * (BM25_K1 + 1.0)
+                        / (tf + BM25_K1 * (1.0 - BM25_B + BM25_B * length))
+                })
+                .sum();
+            (score > 0.0).then(|| Passage {
+                start: chunk.start,
+                end: chunk.end,
+                score,
+                text: chunk.paragraphs.join("\n"),
+            })
+        })
+        .collect();
+    passages.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(a.start.total_cmp(&b.start))
+    });
+    passages.truncate(top);
+    passages
+}
+
+/// A prompt asking an LLM to answer `question` from the numbered passages alone
+pub fn answer_prompt(
+    title: &str,
+    show_title: &str,
+    question: &str,
+    passages: &[Passage],
+) -> String {
+    let mut prompt = format!(
+        "Answer the question about the podcast episode \"{}\" from {} using only the transcript \
+         excerpts below. Cite the excerpts you rely on by number, like [1]. If they do not \
+         answer the question, say so.\n\n",
+        title, show_title
+    );
+    for (index, passage) in passages.iter().enumerate() {
+        prompt.push_str(&format!(
+            "[{}] {}\n{}\n\n",
+            index + 1,
+            passage.timestamp(),
+            passage.text
+        ));
+    }
+    prompt.push_str(&format!("Question: {}\n", question));
+    prompt
+}
+
+/// Runs a shell command with `prompt` on stdin and returns what it prints as the answer
+pub fn run_llm(command: &str, prompt: &str) -> Result<String> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut child = Command::new(shell)
+        .args([flag, command])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(Error::io("Failed to start LLM command"))?;
+
+    // Written from a thread so a command that answers while still reading cannot deadlock
+    let mut stdin = child.stdin.take();
+    let prompt = prompt.to_string();
+    let writer = std::thread::spawn(move || match stdin.as_mut() {
+        Some(stdin) => stdin.write_all(prompt.as_bytes()),
+        None => Ok(()),
+    });
+    let output = child
+        .wait_with_output()
+        .map_err(Error::io("Failed to wait for LLM command"))?;
+    let written = writer.join().unwrap_or(Ok(()));
+    if !output.status.success() {
+        return Err(Error::Llm(format!(
+            "'{}' exited with {}",
+            command, output.status
+        )));
+    }
+    written.map_err(Error::io("Failed to send prompt to LLM command"))?;
+
+    let answer = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if answer.is_empty() {
+        return Err(Error::Llm(format!("'{}' printed no answer", command)));
+    }
+    Ok(answer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(start: f64, paragraphs: &[&str]) -> PackChunk {
+        PackChunk {
+            start,
+            end: start + 60.0,
+            paragraphs: paragraphs.iter().map(|text| text.to_string()).collect(),
+        }
+    }
+
+    /// Unit test - rank_passages puts the chunk about the question's subject first
+    #[test]
+    fn test_rank_passages_bm25() {
+        // Given chunks where one dwells on pricing and others mention it in passing or not at all
+        let chunks = vec![
+            chunk(
+                0.0,
+                &["Host: Welcome to the show, what did you say about the weather?"],
+            ),
+            chunk(
+                60.0,
+                &[
+                    "Guest: Pricing is hard.",
+                    "Host: Why is pricing so hard for startups?",
+                ],
+            ),
+            chunk(
+                120.0,
+                &["Guest: We changed our pricing once and then moved on to hiring."],
+            ),
+            chunk(180.0, &["Host: Thanks for coming on."]),
+        ];
+
+        // When we ask about pricing
+        let passages = rank_passages(&chunks, "What did they say about pricing?", 5);
+
+        // Then question words are ignored, and the chunks mentioning pricing come back, best first
+        assert_eq!(
+            query_terms("What did they say about pricing?"),
+            vec!["pricing"]
+        );
+        let starts: Vec<f64> = passages.iter().map(|passage| passage.start).collect();
+        assert_eq!(starts, vec![60.0, 120.0]);
+        assert!(passages[0].score > passages[1].score);
+        assert_eq!(passages[0].timestamp(), "1:00–2:00");
+        assert!(passages[0].text.contains("\nHost: Why is pricing"));
+        assert!(rank_passages(&chunks, "what about quantum?", 5).is_empty());
+    }
+
+    /// Unit test - run_llm sends the prompt on stdin and returns the command's answer
+    #[cfg(unix)]
+    #[test]
+    fn test_run_llm() {
+        // Given a prompt built from a passage
+        let passage = Passage {
+            start: 60.0,
+            end: 120.0,
+            score: 1.0,
+            text: "Guest: Pricing is hard.".to_string(),
+        };
+        let prompt = answer_prompt("Episode 12", "The Show", "What about pricing?", &[passage]);
+        assert!(prompt.contains("[1] 1:00–2:00\nGuest: Pricing is hard.\n"));
+        assert!(prompt.ends_with("Question: What about pricing?\n"));
+
+        // When commands answer, fail or stay silent
+        // Then the answer is what the command printed, and failures are errors
+        assert_eq!(run_llm("grep -c 'Pricing is hard'", &prompt).unwrap(), "1");
+        assert!(matches!(
+            run_llm("cat >/dev/null; exit 3", &prompt),
+            Err(Error::Llm(_))
+        ));
+        assert!(run_llm("cat >/dev/null", &prompt)
+            .unwrap_err()
+            .to_string()
+            .contains("printed no answer"));
+    }
+}
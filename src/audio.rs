@@ -0,0 +1,297 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use reqwest::header::{CONTENT_LENGTH, CONTENT_RANGE, RANGE};
+use reqwest::StatusCode;
+
+use crate::error::{Error, Result};
+use crate::fetch::{send_with_retry, HttpOptions};
+use crate::metadata::Metadata;
+use crate::output::sanitize_file_name;
+use crate::server_data::{find_string_field, serialized_server_data};
+
+/// Keys that hold the episode's audio enclosure in Apple's serialized data, in order of preference
+pub const AUDIO_URL_KEYS: &[&str] = &["streamUrl", "assetUrl"];
+
+/// Suffix of the partial file kept while a download is in progress
+const PARTIAL_SUFFIX: &str = ".part";
+
+/// Outcome of an audio download
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioDownload {
+    /// Total size of the finished file
+    pub bytes: u64,
+    /// Bytes that were already on disk from an interrupted download
+    pub resumed_from: u64,
+}
+
+/// Searches for the episode's audio URL (`streamUrl`, then `assetUrl`) in the episode HTML
+pub fn find_audio_url(html: &str) -> Result<Option<String>> {
+    Ok(serialized_server_data(html)?.and_then(|data| audio_url_in(&data)))
+}
+
+/// The audio URL in a page's parsed serialized data
+pub fn audio_url_in(data: &serde_json::Value) -> Option<String> {
+    AUDIO_URL_KEYS
+        .iter()
+        .find_map(|key| find_string_field(data, key))
+}
+
+/// Builds a file name like `2023-10-13 - Episode Title.mp3` from the metadata and audio URL
+pub fn audio_file_name(metadata: &Metadata, audio_url: &str) -> String {
+    let extension = url::Url::parse(audio_url)
+        .ok()
+        .and_then(|url| {
+            Path::new(url.path())
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+        })
+        .filter(|ext| ext.len() <= 4 && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or_else(|| "mp3".to_string());
+
+    let stem = match &metadata.publish_date_iso8601 {
+        Some(date) => format!("{} - {}", date, metadata.episode_title),
+        None => metadata.episode_title.clone(),
+    };
+
+    format!("{}.{}", sanitize_file_name(&stem), extension)
+}
+
+/// Downloads audio to `output_path`, resuming a previous `.part` file with an HTTP range request
+///
+/// `progress` is called with the bytes written so far and the total size, when known.
+pub async fn download_audio(
+    url: &str,
+    output_path: &str,
+    options: &HttpOptions,
+    progress: &mut dyn FnMut(u64, Option<u64>),
+) -> Result<AudioDownload> {
+    if let Some(parent) = Path::new(output_path).parent() {
+        fs::create_dir_all(parent).map_err(Error::io("Failed to create output directory"))?;
+    }
+
+    let partial_path = format!("{}{}", output_path, PARTIAL_SUFFIX);
+    let existing = fs::metadata(&partial_path).map(|m| m.len()).unwrap_or(0);
+
+    let mut response = send_with_retry(options, |client| {
+        let request = client.get(url);
+        if existing > 0 {
+            request.header(RANGE, format!("bytes={}-", existing))
+        } else {
+            request
+        }
+    })
+    .await?;
+
+    // 416 means the partial file already holds the whole enclosure
+    if existing > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        fs::rename(&partial_path, output_path).map_err(Error::io("Failed to finish audio file"))?;
+        progress(existing, Some(existing));
+        return Ok(AudioDownload {
+            bytes: existing,
+            resumed_from: existing,
+        });
+    }
+
+    if !response.status().is_success() {
+        return Err(Error::Status {
+            status: response.status(),
+            retry_after: None,
+        });
+    }
+
+    // Servers that ignore the range answer 200 with the whole file, so start over
+    let resumed_from = if response.status() == StatusCode::PARTIAL_CONTENT {
+        existing
+    } else {
+        0
+    };
+    let total = content_total(&response).map(|total| {
+        if response.status() == StatusCode::PARTIAL_CONTENT && !has_content_range(&response) {
+            total + resumed_from
+        } else {
+            total
+        }
+    });
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed_from > 0)
+        .truncate(resumed_from == 0)
+        .open(&partial_path)
+        .map_err(Error::io("Failed to open audio file"))?;
+
+    let mut written = resumed_from;
+    progress(written, total);
+    while let Some(chunk) = response.chunk().await.map_err(Error::Body)? {
+        file.write_all(&chunk)
+            .map_err(Error::io("Failed to write audio file"))?;
+        written += chunk.len() as u64;
+        progress(written, total);
+    }
+
+    fs::rename(&partial_path, output_path).map_err(Error::io("Failed to finish audio file"))?;
+
+    Ok(AudioDownload {
+        bytes: written,
+        resumed_from,
+    })
+}
+
+/// Total enclosure size from `Content-Range: bytes a-b/total`, falling back to `Content-Length`
+fn content_total(response: &reqwest::Response) -> Option<u64> {
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+    };
+
+    header(CONTENT_RANGE)
+        .and_then(|range| range.rsplit_once('/'))
+        .and_then(|(_, total)| total.parse().ok())
+        .or_else(|| header(CONTENT_LENGTH).and_then(|length| length.parse().ok()))
+}
+
+/// Returns true if the response carries a `Content-Range` total
+fn has_content_range(response: &reqwest::Response) -> bool {
+    response.headers().contains_key(CONTENT_RANGE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serves `body` once, honoring a `Range: bytes=N-` request header
+    fn serve_once(body: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+            let mut start = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some(range) = line.to_lowercase().strip_prefix("range: bytes=") {
+                    start = range.trim().trim_end_matches('-').parse().unwrap();
+                }
+            }
+
+            let mut stream = stream;
+            let head = if start > 0 {
+                format!(
+                    "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+                    body.len() - start,
+                    start,
+                    body.len() - 1,
+                    body.len()
+                )
+            } else {
+                format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len())
+            };
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(&body[start..]).unwrap();
+        });
+
+        format!("http://{}/episode.mp3", addr)
+    }
+
+    /// Unit test - find_audio_url prefers streamUrl over assetUrl
+    #[test]
+    fn test_find_audio_url_prefers_stream_url() {
+        // Given serialized data with both audio keys
+        let html = r#"<script type="application/json" id="serialized-server-data">[{"data":{"episode":{"assetUrl":"https://cdn.example.com/asset.mp3","playAction":{"streamUrl":"https://cdn.example.com/stream.m4a"}}}}]</script>"#;
+
+        // When we look for the audio URL
+        let url = find_audio_url(html).unwrap();
+
+        // Then the stream URL wins
+        assert_eq!(url.as_deref(), Some("https://cdn.example.com/stream.m4a"));
+    }
+
+    /// Unit test - audio_file_name uses date, title and URL extension
+    #[test]
+    fn test_audio_file_name_uses_date_title_and_extension() {
+        // Given metadata with a title that has illegal path characters
+        let metadata = Metadata {
+            episode_title: "Q&A: What's next?".to_string(),
+            description: String::new(),
+            show_title: String::new(),
+            publish_date: "Oct 13, 2023".to_string(),
+            publish_date_iso8601: Some("2023-10-13".to_string()),
+            ..Metadata::default()
+        };
+
+        // When we name the audio file
+        let name = audio_file_name(&metadata, "https://cdn.example.com/a/b.M4A?key=1");
+
+        // Then the name is filesystem-safe and keeps the extension
+        assert_eq!(name, "2023-10-13 - Q&A_ What's next_.m4a");
+    }
+
+    /// Unit test - download_audio resumes a partial file with a range request
+    #[tokio::test]
+    async fn test_download_audio_resumes_partial_file() {
+        use tempfile::TempDir;
+
+        // Given a partial download holding the first bytes of the enclosure
+        let body: &'static [u8] = b"0123456789abcdefghij";
+        let temp_dir = TempDir::new().unwrap();
+        let output_path = temp_dir.path().join("episode.mp3");
+        let output_path = output_path.to_str().unwrap();
+        fs::write(format!("{}.part", output_path), &body[..8]).unwrap();
+
+        // When we download again
+        let url = serve_once(body);
+        let mut last = (0, None);
+        let result = download_audio(
+            &url,
+            output_path,
+            &HttpOptions::default(),
+            &mut |written, total| last = (written, total),
+        )
+        .await
+        .unwrap();
+
+        // Then only the remainder is fetched and appended
+        assert_eq!(result.resumed_from, 8);
+        assert_eq!(result.bytes, 20);
+        assert_eq!(fs::read(output_path).unwrap(), body);
+        assert_eq!(last, (20, Some(20)));
+
+        // And the partial file is gone
+        assert!(!Path::new(&format!("{}.part", output_path)).exists());
+    }
+
+    /// Unit test - download_audio streams a fresh download to disk
+    #[tokio::test]
+    async fn test_download_audio_fresh_download() {
+        use tempfile::TempDir;
+
+        // Given no previous download
+        let body: &'static [u8] = b"complete audio bytes";
+        let temp_dir = TempDir::new().unwrap();
+        let output_path = temp_dir.path().join("nested").join("episode.mp3");
+        let output_path = output_path.to_str().unwrap();
+
+        // When we download
+        let url = serve_once(body);
+        let result = download_audio(&url, output_path, &HttpOptions::default(), &mut |_, _| {})
+            .await
+            .unwrap();
+
+        // Then the whole file is written
+        assert_eq!(result.resumed_from, 0);
+        assert_eq!(fs::read(output_path).unwrap(), body);
+    }
+}
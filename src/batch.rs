@@ -0,0 +1,188 @@
+use std::future::Future;
+
+use futures_util::stream::{self, StreamExt};
+use serde::Serialize;
+
+/// Default number of episodes processed at once by `batch`
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Default number of one episode's transcript, audio and artwork downloads run at once
+pub const DEFAULT_ASSET_CONCURRENCY: usize = 3;
+
+/// Outcome of one URL in a batch run
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct BatchEntry {
+    pub url: String,
+    pub directory: Option<String>,
+    pub transcript: bool,
+    /// Already saved and unchanged, so not fetched again
+    pub skipped: bool,
+    pub error: Option<String>,
+}
+
+/// Summary written after a batch run
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct BatchReport {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub entries: Vec<BatchEntry>,
+}
+
+impl BatchReport {
+    /// Builds a report, counting successes, failures and skipped episodes
+    pub fn new(entries: Vec<BatchEntry>) -> BatchReport {
+        let failed = entries.iter().filter(|entry| entry.error.is_some()).count();
+        let skipped = entries.iter().filter(|entry| entry.skipped).count();
+        BatchReport {
+            succeeded: entries.len() - failed - skipped,
+            failed,
+            skipped,
+            entries,
+        }
+    }
+
+    /// Renders the entries as CSV with a header row
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("url,status,directory,transcript,error\n");
+        for entry in &self.entries {
+            let status = if entry.error.is_some() {
+                "failed"
+            } else if entry.skipped {
+                "skipped"
+            } else {
+                "ok"
+            };
+            let row = [
+                csv_field(&entry.url),
+                status.to_string(),
+                csv_field(entry.directory.as_deref().unwrap_or("")),
+                entry.transcript.to_string(),
+                csv_field(entry.error.as_deref().unwrap_or("")),
+            ];
+            out.push_str(&row.join(","));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Quotes a CSV field when it contains a delimiter, quote or line break
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Reads one URL per line, skipping blank lines and `#` comments
+pub fn parse_url_list(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parses a `--concurrency` value
+pub fn parse_concurrency(raw: &str) -> std::result::Result<usize, String> {
+    match raw.trim().parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!(
+            "Invalid concurrency '{}': expected a positive number",
+            raw
+        )),
+    }
+}
+
+/// Runs `task` over `items` with up to `concurrency` in flight, returning results in input order
+pub async fn run_concurrently<T, R, F, Fut>(items: Vec<T>, concurrency: usize, task: F) -> Vec<R>
+where
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = R>,
+{
+    stream::iter(items)
+        .map(task)
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - parse_url_list skips blank lines and comments
+    #[test]
+    fn test_parse_url_list_skips_blanks_and_comments() {
+        // Given a URL file with comments and blank lines
+        let text = "# episodes to archive\nhttps://a.example/1\n\n   https://a.example/2  \n#https://a.example/3\n";
+
+        // When we parse it
+        // Then only the URLs remain, trimmed and in order
+        assert_eq!(
+            parse_url_list(text),
+            vec!["https://a.example/1", "https://a.example/2"]
+        );
+    }
+
+    /// Unit test - run_concurrently keeps input order
+    #[tokio::test]
+    async fn test_run_concurrently_keeps_input_order() {
+        // Given more items than can run at once
+        let items: Vec<u64> = (0..20).collect();
+
+        // When we process them concurrently with uneven durations
+        let results = run_concurrently(items, 4, |n| async move {
+            tokio::time::sleep(std::time::Duration::from_millis((20 - n) % 5)).await;
+            n * 2
+        })
+        .await;
+
+        // Then results come back in input order
+        assert_eq!(results, (0..20).map(|n| n * 2).collect::<Vec<_>>());
+    }
+
+    /// Unit test - BatchReport counts outcomes and renders quoted CSV
+    #[test]
+    fn test_batch_report_to_csv() {
+        // Given one success, one skipped episode and one failure whose message has a comma
+        let report = BatchReport::new(vec![
+            BatchEntry {
+                url: "https://a.example/1".to_string(),
+                directory: Some("output/episodes/1".to_string()),
+                transcript: true,
+                skipped: false,
+                error: None,
+            },
+            BatchEntry {
+                url: "https://a.example/3".to_string(),
+                directory: Some("output/episodes/3".to_string()),
+                transcript: false,
+                skipped: true,
+                error: None,
+            },
+            BatchEntry {
+                url: "https://a.example/2".to_string(),
+                directory: None,
+                transcript: false,
+                skipped: false,
+                error: Some("HTTP request failed, status \"404\"".to_string()),
+            },
+        ]);
+
+        // When we render it as CSV
+        let csv = report.to_csv();
+
+        // Then counts are right and the error field is quoted
+        assert_eq!((report.succeeded, report.skipped, report.failed), (1, 1, 1));
+        assert_eq!(
+            csv,
+            "url,status,directory,transcript,error\n\
+             https://a.example/1,ok,output/episodes/1,true,\n\
+             https://a.example/3,skipped,output/episodes/3,false,\n\
+             https://a.example/2,failed,,false,\"HTTP request failed, status \"\"404\"\"\"\n"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::{HashMap, HashSet};
+
+/// Fewest descriptions boilerplate is looked for in; with fewer, nothing counts as repeated
+const MIN_DESCRIPTIONS: usize = 3;
+
+/// Paragraphs a show repeats in most of its episode descriptions, such as a promo or sponsor blurb
+///
+/// Descriptions are compared line by line, with whitespace collapsed and case
+/// ignored. A line is boilerplate when more than half of the descriptions
+/// contain it, which suits the `plain` and `markdown` description formats.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Boilerplate {
+    lines: HashSet<String>,
+}
+
+impl Boilerplate {
+    /// Finds the lines more than half of `descriptions` share
+    pub fn detect<'a>(descriptions: impl IntoIterator<Item = &'a str>) -> Boilerplate {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        let mut total = 0;
+        for description in descriptions {
+            total += 1;
+            let lines: HashSet<String> = description.lines().filter_map(normalize).collect();
+            for line in lines {
+                *counts.entry(line).or_default() += 1;
+            }
+        }
+        if total < MIN_DESCRIPTIONS {
+            return Boilerplate::default();
+        }
+        Boilerplate {
+            lines: counts
+                .into_iter()
+                .filter(|(_, count)| *count * 2 > total)
+                .map(|(line, _)| line)
+                .collect(),
+        }
+    }
+
+    /// Whether no boilerplate was found
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// `description` without its boilerplate lines, keeping one blank line between paragraphs
+    pub fn strip(&self, description: &str) -> String {
+        let mut kept: Vec<&str> = Vec::new();
+        for line in description.lines() {
+            if normalize(line).is_some_and(|line| self.lines.contains(&line)) {
+                continue;
+            }
+            // Removed paragraphs would otherwise leave runs of blank lines behind
+            if line.trim().is_empty() && kept.last().is_none_or(|last| last.trim().is_empty()) {
+                continue;
+            }
+            kept.push(line);
+        }
+        kept.join("\n").trim().to_string()
+    }
+}
+
+/// A line as it is compared: whitespace collapsed and lower-cased, or `None` when blank
+fn normalize(line: &str) -> Option<String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    (!words.is_empty()).then(|| words.join(" ").to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - Boilerplate finds lines most descriptions share and strips only those
+    #[test]
+    fn test_boilerplate_detect_and_strip() {
+        // Given four descriptions, three ending with the same promo and sign-off
+        let descriptions = [
+            "We talk shop.\n\nSupport the show at example.com\nSee you next week!",
+            "Guest Ann Smith joins.\n\nSupport the show at  example.com\nSee you next week!",
+            "A solo episode.\n\nsupport the show at example.com\n\nSee you next week!",
+            "A special without the promo.\n\nSee you next week!",
+        ];
+
+        // When we detect the show's boilerplate
+        let boilerplate = Boilerplate::detect(descriptions);
+
+        // Then repeated lines are stripped despite spacing and case, and episode text is kept
+        assert_eq!(boilerplate.strip(descriptions[0]), "We talk shop.");
+        assert_eq!(boilerplate.strip(descriptions[2]), "A solo episode.");
+        assert_eq!(
+            boilerplate.strip("Intro.\n\nMiddle.\n\nSee you next week!\n\nOutro."),
+            "Intro.\n\nMiddle.\n\nOutro."
+        );
+        assert!(Boilerplate::detect(descriptions[..2].iter().copied()).is_empty());
+    }
+}
@@ -0,0 +1,182 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+
+/// A stage of processing one episode, each with its own time budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Downloading the episode page
+    Fetch,
+    /// Extracting metadata from the page
+    Parse,
+    /// Downloading the transcript
+    Transcript,
+    /// Downloading the audio file
+    Audio,
+}
+
+impl Stage {
+    /// Name used in timeout errors and flags
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Fetch => "fetch",
+            Stage::Parse => "parse",
+            Stage::Transcript => "transcript",
+            Stage::Audio => "audio",
+        }
+    }
+}
+
+/// Time limits for each stage of an episode and for the whole run; `None` means no limit
+///
+/// A stage that runs past its budget fails with [`Error::Timeout`]. Once the
+/// deadline passes, stages in progress are cut off and new ones fail right
+/// away with [`Error::Deadline`], so batch and show runs can report the rest
+/// as failed and finish.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Budgets {
+    pub fetch: Option<Duration>,
+    pub parse: Option<Duration>,
+    pub transcript: Option<Duration>,
+    pub audio: Option<Duration>,
+    /// When the whole run must stop
+    pub deadline: Option<Instant>,
+}
+
+impl Budgets {
+    /// The budget for one stage
+    pub fn limit(&self, stage: Stage) -> Option<Duration> {
+        match stage {
+            Stage::Fetch => self.fetch,
+            Stage::Parse => self.parse,
+            Stage::Transcript => self.transcript,
+            Stage::Audio => self.audio,
+        }
+    }
+
+    /// Time left before the run deadline, zero once it has passed
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Runs a stage, failing it if it outlasts its budget or the run deadline
+    pub async fn run<T>(&self, stage: Stage, work: impl Future<Output = Result<T>>) -> Result<T> {
+        let budget = self.limit(stage);
+        let remaining = self.remaining();
+        if remaining == Some(Duration::ZERO) {
+            return Err(Error::Deadline);
+        }
+
+        // Whichever limit is nearer applies, and decides the error
+        let (limit, error) = match (budget, remaining) {
+            (Some(budget), Some(remaining)) if remaining < budget => (remaining, Error::Deadline),
+            (Some(budget), _) => (
+                budget,
+                Error::Timeout {
+                    stage: stage.name(),
+                    limit: budget,
+                },
+            ),
+            (None, Some(remaining)) => (remaining, Error::Deadline),
+            (None, None) => return work.await,
+        };
+        tokio::time::timeout(limit, work).await.map_err(|_| error)?
+    }
+
+    /// Runs blocking work such as parsing on a worker thread, under the stage's budget
+    ///
+    /// Work that times out is abandoned; the thread finishes it in the background.
+    pub async fn run_blocking<T: Send + 'static>(
+        &self,
+        stage: Stage,
+        work: impl FnOnce() -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        self.run(stage, async {
+            tokio::task::spawn_blocking(work)
+                .await
+                .map_err(|e| Error::Parse(format!("{} stage failed: {}", stage.name(), e)))?
+        })
+        .await
+    }
+}
+
+/// Parses a duration: seconds, or a number with an `s`, `m`, `h` or `d` suffix
+pub fn parse_duration(raw: &str) -> std::result::Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "Invalid duration '{}': expected a positive duration such as 90s, 15m, 1h or 1d",
+            raw
+        )
+    };
+
+    let trimmed = raw.trim().to_lowercase();
+    let (number, unit) = match trimmed.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => trimmed.split_at(index),
+        None => (trimmed.as_str(), "s"),
+    };
+    let scale = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        _ => return Err(invalid()),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(scale))
+        .filter(|seconds| *seconds > 0)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - run fails a stage that outlasts its budget or the run deadline
+    #[tokio::test]
+    async fn test_run_enforces_budgets_and_deadline() {
+        // Given a short fetch budget, and a deadline that has already passed
+        let budgets = Budgets {
+            fetch: Some(Duration::from_millis(20)),
+            ..Budgets::default()
+        };
+        let expired = Budgets {
+            deadline: Some(Instant::now()),
+            ..Budgets::default()
+        };
+        let slow = || async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        };
+
+        // When stages run under them
+        let timed_out = budgets.run(Stage::Fetch, slow()).await;
+        let unlimited = budgets.run(Stage::Parse, async { Ok(1) }).await;
+        let late = expired.run(Stage::Parse, async { Ok(1) }).await;
+
+        // Then the slow fetch times out, the unlimited stage finishes and the late one never starts
+        assert!(matches!(
+            timed_out,
+            Err(Error::Timeout { stage: "fetch", .. })
+        ));
+        assert_eq!(unlimited.unwrap(), 1);
+        assert!(matches!(late, Err(Error::Deadline)));
+    }
+
+    /// Unit test - parse_duration accepts seconds and unit suffixes
+    #[test]
+    fn test_parse_duration() {
+        // Given plain seconds, suffixed values and invalid input
+        // When we parse them
+        // Then suffixes scale the number and zero or unknown units are rejected
+        assert_eq!(parse_duration("45"), Ok(Duration::from_secs(45)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(1800)));
+        assert_eq!(parse_duration("2H"), Ok(Duration::from_secs(7200)));
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("5w").is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// Directory name used under the user's cache directory
+const CACHE_DIR_NAME: &str = "applecast-cli";
+
+/// A cached page body with the validators needed to revalidate it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CacheEntry {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub body: String,
+}
+
+/// On-disk cache of fetched pages, revalidated with conditional requests
+///
+/// Entries are only reused after the server confirms with a 304 that the page
+/// is unchanged, so a stale copy is never returned in place of a fresh one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageCache {
+    pub dir: PathBuf,
+    /// Skip revalidation and refetch every page, still updating the cache
+    pub refresh: bool,
+}
+
+impl PageCache {
+    /// A cache stored in `dir`
+    pub fn new(dir: impl Into<PathBuf>) -> PageCache {
+        PageCache {
+            dir: dir.into(),
+            refresh: false,
+        }
+    }
+
+    /// Reads the entry for `url`; a missing or unreadable entry is a miss
+    pub fn load(&self, url: &str) -> Option<CacheEntry> {
+        let text = fs::read_to_string(self.path(url)).ok()?;
+        serde_json::from_str::<CacheEntry>(&text)
+            .ok()
+            .filter(|entry| entry.url == url)
+    }
+
+    /// Writes the entry, replacing any earlier one for the same URL
+    pub fn store(&self, entry: &CacheEntry) -> Result<()> {
+        fs::create_dir_all(&self.dir).map_err(Error::io("Failed to create cache directory"))?;
+        fs::write(self.path(&entry.url), serde_json::to_string(entry)?)
+            .map_err(Error::io("Failed to write cache entry"))
+    }
+
+    /// File holding the entry for `url`
+    fn path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", fnv1a(url)))
+    }
+}
+
+/// The per-user cache directory: `$XDG_CACHE_HOME/applecast-cli`, else `~/.cache/applecast-cli`
+pub fn default_cache_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(base.join(CACHE_DIR_NAME))
+}
+
+/// FNV-1a hash of a URL; stable across builds, unlike the std hashers
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - stored entries load back by URL
+    #[test]
+    fn test_store_and_load_round_trip() {
+        // Given a cache in a temporary directory
+        let dir = tempfile::tempdir().unwrap();
+        let cache = PageCache::new(dir.path());
+        let entry = CacheEntry {
+            url: "https://podcasts.apple.com/us/podcast/id1?i=2".to_string(),
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+            body: "<html></html>".to_string(),
+        };
+
+        // When we store an entry and load it back
+        cache.store(&entry).unwrap();
+
+        // Then the same entry is returned, and other URLs miss
+        assert_eq!(cache.load(&entry.url), Some(entry));
+        assert_eq!(cache.load("https://podcasts.apple.com/other"), None);
+    }
+}
@@ -0,0 +1,122 @@
+use plotters::prelude::*;
+
+use crate::error::{Error, Result};
+use crate::output::write_file;
+
+/// Size of a chart, in pixels
+const CHART_SIZE: (u32, u32) = (960, 480);
+
+/// Most dates labelled along the x axis; the rest are left unlabelled
+const MAX_X_LABELS: usize = 12;
+
+/// One point of a timeline chart
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelinePoint {
+    /// The x axis label, usually a date
+    pub label: String,
+    pub value: f64,
+    /// Drawn in red, e.g. for an outlier
+    pub flagged: bool,
+}
+
+/// Validates a `--chart` path: an SVG file
+pub fn parse_chart_path(raw: &str) -> std::result::Result<String, String> {
+    if raw.to_lowercase().ends_with(".svg") {
+        Ok(raw.to_string())
+    } else {
+        Err(format!(
+            "Invalid chart path '{}': charts are written as SVG, e.g. out.svg",
+            raw
+        ))
+    }
+}
+
+/// Draws `points` in order as a line chart and writes it to `path` as SVG
+pub fn write_timeline_chart(
+    path: &str,
+    title: &str,
+    y_label: &str,
+    points: &[TimelinePoint],
+) -> Result<()> {
+    let mut svg = String::new();
+    draw_timeline(&mut svg, title, y_label, points).map_err(|e| Error::Chart(e.to_string()))?;
+    write_file(path, svg)
+}
+
+fn draw_timeline(
+    svg: &mut String,
+    title: &str,
+    y_label: &str,
+    points: &[TimelinePoint],
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let root = SVGBackend::with_string(svg, CHART_SIZE).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let highest = points.iter().map(|point| point.value).fold(0.0, f64::max);
+    let last = points.len().saturating_sub(1).max(1);
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, ("sans-serif", 20))
+        .margin(16)
+        .x_label_area_size(40)
+        .y_label_area_size(64)
+        .build_cartesian_2d(0..last, 0.0..(highest * 1.1).max(1.0))?;
+    let label = |index: &usize| {
+        points
+            .get(*index)
+            .map(|point| point.label.clone())
+            .unwrap_or_default()
+    };
+    chart
+        .configure_mesh()
+        .x_labels(points.len().clamp(1, MAX_X_LABELS))
+        .x_label_formatter(&label)
+        .y_desc(y_label)
+        .draw()?;
+
+    let values = points.iter().enumerate().map(|(x, point)| (x, point.value));
+    chart.draw_series(LineSeries::new(values, &BLUE))?;
+    chart.draw_series(points.iter().enumerate().map(|(x, point)| {
+        let color = if point.flagged { RED } else { BLUE };
+        Circle::new((x, point.value), 3, color.filled())
+    }))?;
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - write_timeline_chart draws an SVG with flagged points in red
+    #[test]
+    fn test_write_timeline_chart() {
+        // Given three points, one flagged
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("charts/durations.svg");
+        let point = |label: &str, value: f64, flagged: bool| TimelinePoint {
+            label: label.to_string(),
+            value,
+            flagged,
+        };
+        let points = [
+            point("2024-01-01", 58.0, false),
+            point("2024-02-01", 7.5, true),
+            point("2024-03-01", 61.0, false),
+        ];
+
+        // When we write the chart
+        write_timeline_chart(path.to_str().unwrap(), "Durations", "minutes", &points).unwrap();
+
+        // Then an SVG is written with the title, a date label and a red point
+        let svg = std::fs::read_to_string(&path).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("Durations"));
+        assert!(svg.contains("2024-02-01"));
+        assert!(svg.contains("#FF0000"));
+        assert!(parse_chart_path("out.png").is_err());
+
+        // And a chart with nothing to plot still draws its axes
+        let empty = temp_dir.path().join("empty.svg");
+        write_timeline_chart(empty.to_str().unwrap(), "Nothing", "changes", &[]).unwrap();
+    }
+}
@@ -0,0 +1,222 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+use crate::error::{Error, Result};
+use crate::output::write_file;
+
+/// zstd level: close to gzip's speed with a noticeably better ratio on HTML and TTML
+const ZSTD_LEVEL: i32 = 9;
+
+/// First bytes of a gzip stream
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// First bytes of a zstd frame
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// How stored HTML snapshots and transcripts are compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Every compression, in the order readers look for their files
+    pub const ALL: [Compression; 2] = [Compression::Zstd, Compression::Gzip];
+
+    /// Extension appended to the file name, after the file's own
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+        }
+    }
+
+    /// The compression a file's first bytes announce, if any
+    pub fn detect(bytes: &[u8]) -> Option<Compression> {
+        if bytes.starts_with(ZSTD_MAGIC) {
+            Some(Compression::Zstd)
+        } else if bytes.starts_with(GZIP_MAGIC) {
+            Some(Compression::Gzip)
+        } else {
+            None
+        }
+    }
+
+    /// `path` with this compression's extension appended
+    pub fn path(self, path: &str) -> String {
+        format!("{}.{}", path, self.extension())
+    }
+
+    /// Compresses `content`
+    pub fn compress(self, content: &[u8]) -> Result<Vec<u8>> {
+        let failed = Error::io("Failed to compress file");
+        match self {
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(content).map_err(failed)?;
+                encoder
+                    .finish()
+                    .map_err(Error::io("Failed to compress file"))
+            }
+            Compression::Zstd => zstd::encode_all(content, ZSTD_LEVEL).map_err(failed),
+        }
+    }
+
+    /// Decompresses `content`
+    pub fn decompress(self, content: &[u8]) -> Result<Vec<u8>> {
+        let failed = Error::io("Failed to decompress file");
+        match self {
+            Compression::Gzip => {
+                let mut inflated = Vec::new();
+                GzDecoder::new(content)
+                    .read_to_end(&mut inflated)
+                    .map_err(failed)?;
+                Ok(inflated)
+            }
+            Compression::Zstd => zstd::decode_all(content).map_err(failed),
+        }
+    }
+}
+
+/// Parses a `--compress` value
+pub fn parse_compression(raw: &str) -> std::result::Result<Compression, String> {
+    match raw.trim().to_lowercase().as_str() {
+        "gzip" | "gz" => Ok(Compression::Gzip),
+        "zstd" | "zst" => Ok(Compression::Zstd),
+        _ => Err(format!(
+            "Invalid compression '{}': expected zstd or gzip",
+            raw
+        )),
+    }
+}
+
+/// Writes a text artifact at `path`, compressed when asked, and returns the path written
+///
+/// Other stored variants of the same artifact are removed, so readers never
+/// pick up a stale copy after the compression setting changes.
+pub fn write_artifact(
+    path: &str,
+    content: impl AsRef<[u8]>,
+    compression: Option<Compression>,
+) -> Result<String> {
+    let written = match compression {
+        Some(compression) => {
+            let path = compression.path(path);
+            write_file(&path, compression.compress(content.as_ref())?)?;
+            path
+        }
+        None => {
+            write_file(path, content)?;
+            path.to_string()
+        }
+    };
+    for stale in variants(path).filter(|variant| *variant != written) {
+        match fs::remove_file(&stale) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(Error::io("Failed to remove stale artifact")(e))
+            }
+            _ => {}
+        }
+    }
+    Ok(written)
+}
+
+/// `path` itself, then `path` with each compression's extension
+fn variants(path: &str) -> impl Iterator<Item = String> + '_ {
+    std::iter::once(path.to_string()).chain(
+        Compression::ALL
+            .into_iter()
+            .map(move |compression| compression.path(path)),
+    )
+}
+
+/// The file an artifact is stored in: `path` itself, else a compressed variant of it
+pub fn stored_path(path: impl AsRef<Path>) -> Option<PathBuf> {
+    let path = path.as_ref();
+    variants(&path.to_string_lossy())
+        .map(PathBuf::from)
+        .find(|variant| variant.is_file())
+}
+
+/// Reads an artifact, whether stored plain or compressed
+///
+/// `path` may name the artifact (`transcript.ttml`) or its compressed file
+/// (`transcript.ttml.zst`); the content decides whether it is decompressed.
+pub fn read_artifact(path: impl AsRef<Path>) -> std::io::Result<String> {
+    let path = path.as_ref();
+    let stored = stored_path(path).unwrap_or_else(|| path.to_path_buf());
+    let bytes = fs::read(stored)?;
+    let bytes = match Compression::detect(&bytes) {
+        Some(compression) => compression
+            .decompress(&bytes)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+        None => bytes,
+    };
+    String::from_utf8(bytes).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// The artifact a stored file holds: its name without a compression extension
+pub fn artifact_name(path: &Path) -> PathBuf {
+    Compression::ALL
+        .into_iter()
+        .find(|compression| {
+            path.extension()
+                .is_some_and(|ext| ext == compression.extension())
+        })
+        .map_or_else(|| path.to_path_buf(), |_| path.with_extension(""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Unit test - compressed artifacts round-trip and replace their other variants
+    #[test]
+    fn test_write_and_read_compressed_artifacts() {
+        // Given an episode page written plain
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("episode.html");
+        let path = path.to_str().unwrap();
+        let html = "<html>".to_string() + &"<p>Episode notes</p>".repeat(200) + "</html>";
+        write_artifact(path, &html, None).unwrap();
+
+        // When it is rewritten with each compression
+        for compression in Compression::ALL {
+            let written = write_artifact(path, &html, Some(compression)).unwrap();
+
+            // Then only the compressed file remains, it is smaller, and reads back by either name
+            assert_eq!(stored_path(path), Some(PathBuf::from(&written)));
+            assert!(fs::metadata(&written).unwrap().len() < html.len() as u64 / 4);
+            assert_eq!(
+                Compression::detect(&fs::read(&written).unwrap()),
+                Some(compression)
+            );
+            assert_eq!(read_artifact(path).unwrap(), html);
+            assert_eq!(read_artifact(&written).unwrap(), html);
+            assert!(!Path::new(path).exists());
+        }
+        assert_eq!(
+            artifact_name(Path::new("out/transcript.ttml.zst")),
+            PathBuf::from("out/transcript.ttml")
+        );
+    }
+
+    /// Unit test - parse_compression accepts zstd and gzip
+    #[test]
+    fn test_parse_compression() {
+        // Given compression names and their extensions, and an unknown one
+        // When we parse them
+        // Then the known ones map to their compression
+        assert_eq!(parse_compression("ZSTD"), Ok(Compression::Zstd));
+        assert_eq!(parse_compression("gz"), Ok(Compression::Gzip));
+        assert!(parse_compression("brotli")
+            .unwrap_err()
+            .contains("zstd or gzip"));
+    }
+}
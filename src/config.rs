@@ -0,0 +1,390 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::parser::ValueSource;
+use clap::Command;
+
+use crate::error::{Error, Result};
+use crate::transform::Transforms;
+use crate::webhook::Webhooks;
+
+/// Directory name used under the user's config directory
+const CONFIG_DIR_NAME: &str = "applecast-cli";
+
+/// Table holding the named profiles in a config file
+const PROFILES_TABLE: &str = "profiles";
+
+/// Table holding per-show webhooks in a config file
+const WEBHOOKS_TABLE: &str = "webhooks";
+
+/// Table holding the extracted-field transforms in a config file
+const TRANSFORMS_TABLE: &str = "transforms";
+
+/// Defaults for command-line flags, read from a TOML config file
+///
+/// Top-level keys apply to every run and `[profiles.<name>]` tables override
+/// them when that profile is selected. Keys are long flag names without the
+/// leading dashes, e.g. `output-dir = "~/podcasts"` or `concurrency = 8`.
+/// `[webhooks.<show>]` tables configure per-show notifications instead, and
+/// `[[transforms.<show>]]` entries rewrite extracted fields.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Config {
+    pub settings: toml::Table,
+    pub profiles: BTreeMap<String, toml::Table>,
+    pub webhooks: Webhooks,
+    pub transforms: Transforms,
+}
+
+impl Config {
+    /// Parses config file contents
+    pub fn parse(text: &str) -> Result<Config> {
+        let mut settings: toml::Table = text
+            .parse()
+            .map_err(|e: toml::de::Error| Error::Config(e.message().to_string()))?;
+
+        let profiles = match settings.remove(PROFILES_TABLE) {
+            None => BTreeMap::new(),
+            Some(toml::Value::Table(profiles)) => profiles
+                .into_iter()
+                .map(|(name, profile)| match profile {
+                    toml::Value::Table(profile) => Ok((name, normalize_keys(profile))),
+                    _ => Err(Error::Config(format!("profile '{}' is not a table", name))),
+                })
+                .collect::<Result<_>>()?,
+            Some(_) => {
+                return Err(Error::Config(format!(
+                    "'{}' must be a table of profiles",
+                    PROFILES_TABLE
+                )))
+            }
+        };
+
+        let webhooks = match settings.remove(WEBHOOKS_TABLE) {
+            None => Webhooks::default(),
+            Some(toml::Value::Table(webhooks)) => Webhooks::parse(&webhooks)?,
+            Some(_) => {
+                return Err(Error::Config(format!(
+                    "'{}' must be a table of shows",
+                    WEBHOOKS_TABLE
+                )))
+            }
+        };
+
+        let transforms = match settings.remove(TRANSFORMS_TABLE) {
+            None => Transforms::default(),
+            Some(toml::Value::Table(transforms)) => Transforms::parse(&transforms)?,
+            Some(_) => {
+                return Err(Error::Config(format!(
+                    "'{}' must be a table of shows",
+                    TRANSFORMS_TABLE
+                )))
+            }
+        };
+
+        Ok(Config {
+            settings: normalize_keys(settings),
+            profiles,
+            webhooks,
+            transforms,
+        })
+    }
+
+    /// Reads and parses the config file at `path`
+    pub fn load(path: &Path) -> Result<Config> {
+        let text = fs::read_to_string(path).map_err(Error::io("Failed to read config file"))?;
+        Config::parse(&text).map_err(|e| match e {
+            Error::Config(message) => Error::Config(format!("{}: {}", path.display(), message)),
+            e => e,
+        })
+    }
+
+    /// The top-level settings with the named profile's settings laid over them
+    pub fn resolve(&self, profile: Option<&str>) -> Result<toml::Table> {
+        let mut settings = self.settings.clone();
+        if let Some(name) = profile {
+            let overrides = self.profiles.get(name).ok_or_else(|| {
+                let known: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+                Error::Config(format!(
+                    "unknown profile '{}' (available: {})",
+                    name,
+                    if known.is_empty() {
+                        "none".to_string()
+                    } else {
+                        known.join(", ")
+                    }
+                ))
+            })?;
+            settings.extend(overrides.clone());
+        }
+        Ok(settings)
+    }
+}
+
+/// Default config file location: `$XDG_CONFIG_HOME/applecast-cli/config.toml`,
+/// falling back to `~/.config/applecast-cli/config.toml`
+pub fn default_config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join(CONFIG_DIR_NAME).join("config.toml"))
+}
+
+/// Adds config settings to a command line as flags, for flags it leaves unset
+///
+/// Precedence is command-line flags, then environment variables, then the
+/// config file, then built-in defaults: a setting is only added when the flag
+/// was neither given nor read from its environment variable. Settings for
+/// flags of other subcommands are ignored; unknown flag names are errors.
+pub fn apply_settings(
+    command: &Command,
+    argv: Vec<OsString>,
+    settings: &toml::Table,
+) -> Result<Vec<OsString>> {
+    let mut command = command.clone();
+    command.build();
+    let matches = command
+        .clone()
+        .try_get_matches_from(&argv)
+        .map_err(|e| Error::Config(e.to_string()))?;
+
+    // The innermost invoked subcommand; building copied global flags into it
+    let (mut invoked, mut invoked_matches) = (&command, &matches);
+    while let Some((name, sub_matches)) = invoked_matches.subcommand() {
+        match invoked.find_subcommand(name) {
+            Some(subcommand) => (invoked, invoked_matches) = (subcommand, sub_matches),
+            None => break,
+        }
+    }
+
+    let mut extra = Vec::new();
+    for (key, value) in settings {
+        let found = invoked
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(key.as_str()));
+        let Some(arg) = found else {
+            if !has_long_flag(&command, key) {
+                return Err(Error::Config(format!("unknown setting '{}'", key)));
+            }
+            continue;
+        };
+
+        let source = invoked_matches.value_source(arg.get_id().as_str());
+        if matches!(
+            source,
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        ) {
+            continue;
+        }
+        extra.extend(setting_args(key, value, arg.get_action().takes_values())?);
+    }
+
+    // Flags must come before a `--` that ends option parsing
+    let mut argv = argv;
+    let end = argv
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(argv.len());
+    argv.splice(end..end, extra);
+    Ok(argv)
+}
+
+/// The command-line arguments for one setting
+fn setting_args(key: &str, value: &toml::Value, takes_value: bool) -> Result<Vec<OsString>> {
+    let invalid = |expected: &str| {
+        Error::Config(format!(
+            "setting '{}' = {} is invalid: expected {}",
+            key, value, expected
+        ))
+    };
+
+    if !takes_value {
+        return match value {
+            toml::Value::Boolean(true) => Ok(vec![format!("--{}", key).into()]),
+            toml::Value::Boolean(false) => Ok(Vec::new()),
+            _ => Err(invalid("true or false")),
+        };
+    }
+
+    let values = match value {
+        toml::Value::Array(values) => values.iter().collect(),
+        value => vec![value],
+    };
+    values
+        .into_iter()
+        .map(|value| {
+            let text = match value {
+                toml::Value::String(text) => expand_home(text),
+                toml::Value::Integer(number) => number.to_string(),
+                toml::Value::Float(number) => number.to_string(),
+                toml::Value::Boolean(flag) => flag.to_string(),
+                _ => return Err(invalid("a string, number or list of them")),
+            };
+            Ok(format!("--{}={}", key, text).into())
+        })
+        .collect()
+}
+
+/// Whether any command in the tree has a flag with this long name
+fn has_long_flag(command: &Command, long: &str) -> bool {
+    command
+        .get_arguments()
+        .any(|arg| arg.get_long() == Some(long))
+        || command
+            .get_subcommands()
+            .any(|subcommand| has_long_flag(subcommand, long))
+}
+
+/// Accepts `snake_case` keys as well as the flags' own `kebab-case`
+fn normalize_keys(table: toml::Table) -> toml::Table {
+    table
+        .into_iter()
+        .map(|(key, value)| (key.replace('_', "-"), value))
+        .collect()
+}
+
+/// Replaces a leading `~/` with the home directory, as a shell would
+pub(crate) fn expand_home(text: &str) -> String {
+    match (text.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest).display().to_string(),
+        _ => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction};
+
+    fn test_command() -> Command {
+        Command::new("app")
+            .arg(
+                Arg::new("output_dir")
+                    .long("output-dir")
+                    .default_value("output")
+                    .global(true),
+            )
+            .arg(
+                Arg::new("proxy")
+                    .long("proxy")
+                    .env("APPLECAST_CONFIG_TEST_PROXY")
+                    .global(true),
+            )
+            .arg(
+                Arg::new("json")
+                    .long("json")
+                    .action(ArgAction::SetTrue)
+                    .global(true),
+            )
+            .arg(
+                Arg::new("detect_ads")
+                    .long("detect-ads")
+                    .action(ArgAction::SetTrue),
+            )
+            .subcommand(
+                Command::new("batch")
+                    .arg(Arg::new("concurrency").long("concurrency"))
+                    .arg(Arg::new("list")),
+            )
+    }
+
+    fn strings(argv: &[OsString]) -> Vec<String> {
+        argv.iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// Unit test - profiles override top-level settings and unknown profiles are errors
+    #[test]
+    fn test_resolve_profile_overrides_defaults() {
+        // Given a config with defaults and two profiles
+        let config = Config::parse(
+            r#"
+            output_dir = "podcasts"
+            concurrency = 4
+
+            [profiles.fast]
+            concurrency = 16
+
+            [profiles.archive]
+            db = "archive.sqlite"
+            "#,
+        )
+        .unwrap();
+
+        // When we resolve no profile, one profile and a missing one
+        let defaults = config.resolve(None).unwrap();
+        let fast = config.resolve(Some("fast")).unwrap();
+        let missing = config.resolve(Some("slow"));
+
+        // Then the profile wins where it sets a key and keys are kebab-case
+        assert_eq!(defaults["output-dir"].as_str(), Some("podcasts"));
+        assert_eq!(defaults["concurrency"].as_integer(), Some(4));
+        assert_eq!(fast["concurrency"].as_integer(), Some(16));
+        assert_eq!(fast["output-dir"].as_str(), Some("podcasts"));
+        assert!(!fast.contains_key("db"));
+        assert!(missing
+            .unwrap_err()
+            .to_string()
+            .contains("available: archive, fast"));
+    }
+
+    /// Unit test - apply_settings gives flags and environment variables precedence over the config
+    #[test]
+    fn test_apply_settings_precedence() {
+        // Given settings for a flag set on the command line, one set in the
+        // environment, a switch, a subcommand flag and another command's flag
+        let settings = Config::parse(
+            r#"
+            output-dir = "from-config"
+            proxy = "http://config:8080"
+            json = true
+            concurrency = 8
+            detect-ads = true
+            "#,
+        )
+        .unwrap()
+        .settings;
+        std::env::set_var("APPLECAST_CONFIG_TEST_PROXY", "http://env:8080");
+        let argv: Vec<OsString> = ["app", "batch", "--output-dir", "cli", "--", "list.txt"]
+            .iter()
+            .map(OsString::from)
+            .collect();
+
+        // When we apply them
+        let applied = apply_settings(&test_command(), argv, &settings).unwrap();
+
+        // Then only the unset flags of the invoked command are added, before `--`
+        assert_eq!(
+            strings(&applied),
+            vec![
+                "app",
+                "batch",
+                "--output-dir",
+                "cli",
+                "--concurrency=8",
+                "--json",
+                "--",
+                "list.txt"
+            ]
+        );
+    }
+
+    /// Unit test - apply_settings rejects unknown settings and values of the wrong type
+    #[test]
+    fn test_apply_settings_rejects_invalid_settings() {
+        // Given a misspelled setting and a switch set to a string
+        let unknown = Config::parse("outptu-dir = \"x\"").unwrap().settings;
+        let mistyped = Config::parse("json = \"yes\"").unwrap().settings;
+        let argv = vec![OsString::from("app")];
+
+        // When we apply them
+        // Then both are errors naming the setting
+        let unknown = apply_settings(&test_command(), argv.clone(), &unknown).unwrap_err();
+        let mistyped = apply_settings(&test_command(), argv, &mistyped).unwrap_err();
+        assert!(unknown.to_string().contains("unknown setting 'outptu-dir'"));
+        assert!(mistyped.to_string().contains("expected true or false"));
+    }
+}
@@ -0,0 +1,375 @@
+use scraper::{Html, Selector};
+use serde::Serialize;
+
+use crate::audio::{find_audio_url, AUDIO_URL_KEYS};
+use crate::metadata::{
+    canonical_url, extract_from_json_ld, extract_from_meta_tags, extract_metadata, Metadata,
+};
+use crate::server_data::{find_episode_guid, find_string_field, server_data_for};
+use crate::transcript::{find_closed_captions_url, find_transcript_url};
+use crate::variant::{detect_variant, VARIANTS};
+
+/// Meta and link selectors the fallback extractors depend on
+const FALLBACK_SELECTORS: &[&str] = &[
+    "meta[property='og:title']",
+    "meta[property='og:description']",
+    "meta[property='og:site_name']",
+    "meta[name='apple:title']",
+    "meta[name='description']",
+    "meta[itemprop='datePublished']",
+    "link[rel='canonical']",
+];
+
+/// What the extractors find in one page snapshot
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Extraction {
+    pub variant: Option<&'static str>,
+    /// Extracted field values by name, in a fixed order
+    pub fields: Vec<(&'static str, Option<String>)>,
+    /// Every selector the extractors use, with how many elements it matched
+    pub selectors: Vec<(String, usize)>,
+}
+
+/// A difference between two snapshots
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Change<T> {
+    pub name: String,
+    pub old: T,
+    pub new: T,
+}
+
+/// Field and selector differences between two page snapshots
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ExtractionDiff {
+    pub variant: Change<Option<&'static str>>,
+    pub fields: Vec<Change<Option<String>>>,
+    pub selectors: Vec<Change<usize>>,
+    /// Fields that were extracted identically
+    pub unchanged: Vec<&'static str>,
+}
+
+/// How often one extraction strategy produced a field across a corpus
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct StrategyCoverage {
+    pub field: &'static str,
+    pub strategy: String,
+    pub hits: usize,
+}
+
+/// Per-field and per-strategy success counts over a corpus of page snapshots
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Coverage {
+    pub pages: usize,
+    /// Pages where the normal extraction produced each field
+    pub fields: Vec<(&'static str, usize)>,
+    pub strategies: Vec<StrategyCoverage>,
+}
+
+/// Runs every extractor over `html`, recording values and selector matches
+pub fn extract_all(html: &str) -> Extraction {
+    let metadata = extract_metadata(html).ok();
+    let field = |value: Option<&String>| value.filter(|v| !v.is_empty()).cloned();
+
+    let fields = vec![
+        (
+            "episode_title",
+            field(metadata.as_ref().map(|m| &m.episode_title)),
+        ),
+        (
+            "description",
+            field(metadata.as_ref().map(|m| &m.description)),
+        ),
+        (
+            "show_title",
+            field(metadata.as_ref().map(|m| &m.show_title)),
+        ),
+        (
+            "publish_date",
+            field(metadata.as_ref().map(|m| &m.publish_date)),
+        ),
+        ("transcript_url", find_transcript_url(html).ok().flatten()),
+        ("audio_url", find_audio_url(html).ok().flatten()),
+        ("guid", find_episode_guid(html).ok().flatten()),
+        ("canonical_url", canonical_url(html)),
+    ];
+
+    let document = Html::parse_document(html);
+    let selectors = selector_list()
+        .into_iter()
+        .map(|selector| {
+            let count = Selector::parse(&selector)
+                .map(|parsed| document.select(&parsed).count())
+                .unwrap_or(0);
+            (selector, count)
+        })
+        .collect();
+
+    Extraction {
+        variant: detect_variant(html).map(|variant| variant.id),
+        fields,
+        selectors,
+    }
+}
+
+/// Selectors from every known page variant followed by the fallback selectors
+fn selector_list() -> Vec<String> {
+    let mut selectors = Vec::new();
+    for variant in VARIANTS {
+        selectors.push(variant.episode_schema.to_string());
+        selectors.push(variant.show_schema.to_string());
+        selectors.push(format!("script[id='{}']", variant.server_data_id));
+    }
+    selectors.extend(FALLBACK_SELECTORS.iter().map(|s| s.to_string()));
+    selectors
+}
+
+/// Runs each extraction strategy on its own, recording which fields it produced
+///
+/// Unlike [`extract_all`], a strategy runs even when an earlier one already
+/// succeeded, so fallbacks that never fire can be told apart from ones that do.
+pub fn attempt_strategies(html: &str) -> Vec<(&'static str, String, bool)> {
+    let document = Html::parse_document(html);
+    let mut attempts = Vec::new();
+    let mut metadata_fields = |strategy: String, metadata: Option<Metadata>| {
+        let found = |value: Option<&String>| value.is_some_and(|v| !v.is_empty());
+        let metadata = metadata.as_ref();
+        attempts.push((
+            "episode_title",
+            strategy.clone(),
+            found(metadata.map(|m| &m.episode_title)),
+        ));
+        attempts.push((
+            "description",
+            strategy.clone(),
+            found(metadata.map(|m| &m.description)),
+        ));
+        attempts.push((
+            "show_title",
+            strategy.clone(),
+            found(metadata.map(|m| &m.show_title)),
+        ));
+        attempts.push((
+            "publish_date",
+            strategy,
+            found(metadata.map(|m| &m.publish_date)),
+        ));
+    };
+
+    for variant in VARIANTS {
+        metadata_fields(
+            format!("json-ld:{}", variant.id),
+            extract_from_json_ld(&document, variant.episode_schema).ok(),
+        );
+    }
+    metadata_fields(
+        "meta-tags".to_string(),
+        extract_from_meta_tags(&document).ok(),
+    );
+
+    for variant in VARIANTS {
+        let data = server_data_for(html, variant).ok().flatten();
+        let strategy = format!("server-data:{}", variant.id);
+        attempts.push((
+            "transcript_url",
+            strategy.clone(),
+            data.as_ref().and_then(find_closed_captions_url).is_some(),
+        ));
+        for key in AUDIO_URL_KEYS {
+            attempts.push((
+                "audio_url",
+                format!("{}:{}", strategy, key),
+                data.as_ref()
+                    .and_then(|data| find_string_field(data, key))
+                    .is_some(),
+            ));
+        }
+        attempts.push((
+            "guid",
+            strategy,
+            data.as_ref()
+                .and_then(|data| find_string_field(data, "guid"))
+                .is_some(),
+        ));
+    }
+
+    attempts.push((
+        "canonical_url",
+        "canonical-link".to_string(),
+        canonical_url(html).is_some(),
+    ));
+    attempts
+}
+
+/// Counts how often each field and each strategy succeeds over `pages`
+pub fn coverage(pages: &[String]) -> Coverage {
+    let mut fields: Vec<(&'static str, usize)> = Vec::new();
+    let mut strategies: Vec<StrategyCoverage> = Vec::new();
+
+    for html in pages {
+        for (name, value) in extract_all(html).fields {
+            let hit = usize::from(value.is_some());
+            match fields.iter_mut().find(|(field, _)| *field == name) {
+                Some((_, hits)) => *hits += hit,
+                None => fields.push((name, hit)),
+            }
+        }
+
+        for (field, strategy, found) in attempt_strategies(html) {
+            let hit = usize::from(found);
+            match strategies
+                .iter_mut()
+                .find(|entry| entry.field == field && entry.strategy == strategy)
+            {
+                Some(entry) => entry.hits += hit,
+                None => strategies.push(StrategyCoverage {
+                    field,
+                    strategy,
+                    hits: hit,
+                }),
+            }
+        }
+    }
+
+    // List strategies grouped by the field they produce, in extraction order
+    strategies.sort_by_key(|entry| fields.iter().position(|(field, _)| *field == entry.field));
+
+    Coverage {
+        pages: pages.len(),
+        fields,
+        strategies,
+    }
+}
+
+/// Compares the extraction results of two snapshots of the same page
+pub fn diff_extractions(old: &Extraction, new: &Extraction) -> ExtractionDiff {
+    let mut fields = Vec::new();
+    let mut unchanged = Vec::new();
+    for ((name, old_value), (_, new_value)) in old.fields.iter().zip(&new.fields) {
+        if old_value == new_value {
+            unchanged.push(*name);
+        } else {
+            fields.push(Change {
+                name: name.to_string(),
+                old: old_value.clone(),
+                new: new_value.clone(),
+            });
+        }
+    }
+
+    // A selector changed behavior when it starts or stops matching, or matches a different count
+    let selectors = old
+        .selectors
+        .iter()
+        .zip(&new.selectors)
+        .filter(|((_, old_count), (_, new_count))| old_count != new_count)
+        .map(|((selector, old_count), (_, new_count))| Change {
+            name: selector.clone(),
+            old: *old_count,
+            new: *new_count,
+        })
+        .collect();
+
+    ExtractionDiff {
+        variant: Change {
+            name: "variant".to_string(),
+            old: old.variant,
+            new: new.variant,
+        },
+        fields,
+        selectors,
+        unchanged,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - diff_extractions reports changed fields and selectors
+    #[test]
+    fn test_diff_extractions_reports_changes() {
+        // Given an old snapshot with JSON-LD and a new one that only has meta tags
+        let old = r#"<html><head>
+            <script id="schema:episode" type="application/ld+json">
+            {"name": "Episode 1", "datePublished": "2023-10-13", "partOfSeries": {"name": "Show"}}
+            </script>
+            <meta property="og:title" content="Episode 1">
+            </head></html>"#;
+        let new = r#"<html><head>
+            <meta property="og:title" content="Episode 1 (Remastered)">
+            <meta property="og:site_name" content="Show">
+            </head></html>"#;
+
+        // When we diff their extractions
+        let diff = diff_extractions(&extract_all(old), &extract_all(new));
+
+        // Then the variant, the changed fields and the selectors that changed behavior are reported
+        assert_eq!(diff.variant.old, Some("serialized-server-data"));
+        assert_eq!(diff.variant.new, None);
+
+        let changed: Vec<&str> = diff.fields.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(changed, vec!["episode_title", "publish_date"]);
+        assert_eq!(
+            diff.fields[0].new.as_deref(),
+            Some("Episode 1 (Remastered)")
+        );
+        assert!(diff.unchanged.contains(&"show_title"));
+
+        let selectors: Vec<(&str, usize, usize)> = diff
+            .selectors
+            .iter()
+            .map(|c| (c.name.as_str(), c.old, c.new))
+            .collect();
+        assert_eq!(
+            selectors,
+            vec![
+                ("script[id='schema:episode']", 1, 0),
+                ("meta[property='og:site_name']", 0, 1),
+            ]
+        );
+    }
+
+    /// Unit test - coverage counts fields and strategies over the fixture corpus
+    #[test]
+    fn test_coverage_over_fixture_corpus() {
+        // Given the saved page fixtures, one per known layout plus a meta-tags-only page
+        let pages = vec![
+            include_str!("../tests/fixtures/pages/serialized-server-data.html").to_string(),
+            include_str!("../tests/fixtures/pages/fastboot-shoebox.html").to_string(),
+            include_str!("../tests/fixtures/pages/meta-tags-only.html").to_string(),
+        ];
+
+        // When we measure coverage
+        let coverage = coverage(&pages);
+        let hits = |field: &str, strategy: &str| {
+            coverage
+                .strategies
+                .iter()
+                .find(|entry| entry.field == field && entry.strategy == strategy)
+                .map(|entry| entry.hits)
+        };
+
+        // Then every page yields a title, and each strategy is credited only where it applies
+        assert_eq!(coverage.pages, 3);
+        assert!(coverage.fields.contains(&("episode_title", 3)));
+        assert!(coverage.fields.contains(&("transcript_url", 1)));
+        assert_eq!(
+            hits("episode_title", "json-ld:serialized-server-data"),
+            Some(1)
+        );
+        assert_eq!(hits("episode_title", "json-ld:fastboot-shoebox"), Some(1));
+        assert_eq!(hits("episode_title", "meta-tags"), Some(3));
+        assert_eq!(
+            hits("audio_url", "server-data:serialized-server-data:streamUrl"),
+            Some(1)
+        );
+        assert_eq!(
+            hits("audio_url", "server-data:fastboot-shoebox:assetUrl"),
+            Some(1)
+        );
+        assert_eq!(
+            hits("transcript_url", "server-data:fastboot-shoebox"),
+            Some(0)
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use std::fs;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+use crate::fetch::{send_with_retry, HttpOptions, USER_AGENT};
+
+/// An endpoint checked by `doctor network`
+pub struct Endpoint {
+    pub name: &'static str,
+    pub url: &'static str,
+}
+
+/// The Apple endpoints the tool depends on, in the order a run uses them
+pub const NETWORK_ENDPOINTS: &[Endpoint] = &[
+    Endpoint {
+        name: "Apple Podcasts web",
+        url: "https://podcasts.apple.com/",
+    },
+    Endpoint {
+        name: "Apple Media (AMP) API",
+        url: "https://amp-api.podcasts.apple.com/",
+    },
+    Endpoint {
+        name: "Transcript/asset CDN",
+        url: "https://is1-ssl.mzstatic.com/",
+    },
+];
+
+/// How long each network diagnostic leg may take
+pub const DIAGNOSTIC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the healthcheck's connectivity probe may take
+pub const HEALTHCHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The outcome of one leg (DNS, TCP, TLS/HTTP) of an endpoint diagnosis
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagnosticStep {
+    pub leg: &'static str,
+    pub ok: bool,
+    pub detail: String,
+}
+
+/// Resolves a host name, reporting every address found
+pub fn check_dns(host: &str, port: u16) -> Result<Vec<SocketAddr>, String> {
+    let addrs: Vec<SocketAddr> = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("could not resolve {}: {}", host, e))?
+        .collect();
+
+    if addrs.is_empty() {
+        return Err(format!("{} resolved to no addresses", host));
+    }
+
+    Ok(addrs)
+}
+
+/// Opens a TCP connection to the first reachable address
+pub fn check_tcp(addrs: &[SocketAddr], timeout: Duration) -> Result<SocketAddr, String> {
+    let mut last_error = String::from("no addresses to try");
+
+    for addr in addrs {
+        match TcpStream::connect_timeout(addr, timeout) {
+            Ok(_) => return Ok(*addr),
+            Err(e) => last_error = format!("{}: {}", addr, e),
+        }
+    }
+
+    Err(last_error)
+}
+
+/// Diagnoses one endpoint leg by leg, stopping at the first failing leg
+pub async fn diagnose_endpoint(url: &str, timeout: Duration) -> Vec<DiagnosticStep> {
+    let mut steps = Vec::new();
+
+    let parsed = match Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            steps.push(DiagnosticStep {
+                leg: "URL",
+                ok: false,
+                detail: e.to_string(),
+            });
+            return steps;
+        }
+    };
+    let host = parsed.host_str().unwrap_or_default();
+    let port = parsed.port_or_known_default().unwrap_or(443);
+
+    let addrs = match check_dns(host, port) {
+        Ok(addrs) => {
+            steps.push(DiagnosticStep {
+                leg: "DNS",
+                ok: true,
+                detail: format!("{} address(es), first {}", addrs.len(), addrs[0].ip()),
+            });
+            addrs
+        }
+        Err(e) => {
+            steps.push(DiagnosticStep {
+                leg: "DNS",
+                ok: false,
+                detail: e,
+            });
+            return steps;
+        }
+    };
+
+    let started = Instant::now();
+    match check_tcp(&addrs, timeout) {
+        Ok(addr) => steps.push(DiagnosticStep {
+            leg: "TCP",
+            ok: true,
+            detail: format!(
+                "connected to {} in {}ms",
+                addr,
+                started.elapsed().as_millis()
+            ),
+        }),
+        Err(e) => {
+            steps.push(DiagnosticStep {
+                leg: "TCP",
+                ok: false,
+                detail: e,
+            });
+            return steps;
+        }
+    }
+
+    // TCP already succeeded, so a connect error here means the TLS handshake
+    // failed (typically a filtering proxy presenting its own certificate)
+    let started = Instant::now();
+    let result = match reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(timeout)
+        .build()
+    {
+        Ok(client) => client.get(url).send().await,
+        Err(e) => Err(e),
+    };
+    steps.push(match result {
+        Ok(response) => DiagnosticStep {
+            leg: "TLS/HTTP",
+            ok: true,
+            detail: format!(
+                "HTTP {} in {}ms",
+                response.status(),
+                started.elapsed().as_millis()
+            ),
+        },
+        Err(e) if e.is_connect() => DiagnosticStep {
+            leg: "TLS",
+            ok: false,
+            detail: format!("handshake failed: {}", e),
+        },
+        Err(e) => DiagnosticStep {
+            leg: "HTTP",
+            ok: false,
+            detail: e.to_string(),
+        },
+    });
+
+    steps
+}
+
+/// Verifies a directory can be created and written to by writing and removing a probe file
+pub fn check_writable(dir: &str) -> Result<(), String> {
+    let probe = Path::new(dir).join(".applecast-healthcheck");
+
+    fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir, e))?;
+    fs::write(&probe, b"ok").map_err(|e| format!("cannot write to {}: {}", dir, e))?;
+    fs::remove_file(&probe).map_err(|e| format!("cannot clean up {}: {}", probe.display(), e))
+}
+
+/// Sends one GET to `url` the way a run would, through the proxy and origin overrides in `options`
+///
+/// Unlike [`diagnose_endpoint`] this goes through the run's HTTP client, so it
+/// also catches a proxy that cannot reach the endpoint. The request is not
+/// retried and may take up to [`HEALTHCHECK_TIMEOUT`].
+pub async fn check_reachable(url: &str, options: &HttpOptions) -> Result<(), String> {
+    let mut options = options.clone();
+    options.retries = Some(0);
+    let response = send_with_retry(&options, |client| {
+        client.get(url).timeout(HEALTHCHECK_TIMEOUT)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let status = response.status();
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(format!("HTTP {}", status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - check_dns resolves localhost
+    #[test]
+    fn test_check_dns_resolves_localhost() {
+        // Given a host that resolves without network access
+        // When we check DNS
+        let addrs = check_dns("localhost", 443);
+
+        // Then at least one address is found
+        assert!(addrs.is_ok(), "localhost should resolve");
+        assert!(!addrs.unwrap().is_empty());
+    }
+
+    /// Unit test - check_tcp connects to a listening socket
+    #[test]
+    fn test_check_tcp_connects_to_listener() {
+        use std::net::TcpListener;
+
+        // Given a local listener
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // When we check TCP reachability
+        let result = check_tcp(&[addr], Duration::from_secs(1));
+
+        // Then the connection succeeds
+        assert_eq!(result, Ok(addr));
+    }
+
+    /// Unit test - diagnose_endpoint stops at the first failing leg
+    #[tokio::test]
+    async fn test_diagnose_endpoint_stops_at_failing_leg() {
+        use std::net::TcpListener;
+
+        // Given a local port with nothing listening on it
+        let port = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        let url = format!("https://127.0.0.1:{}/", port);
+
+        // When we diagnose the endpoint
+        let steps = diagnose_endpoint(&url, Duration::from_secs(1)).await;
+
+        // Then DNS passes, TCP fails, and TLS/HTTP is never attempted
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].leg, "DNS");
+        assert!(steps[0].ok);
+        assert_eq!(steps[1].leg, "TCP");
+        assert!(!steps[1].ok);
+    }
+
+    /// Unit test - check_writable creates the directory and leaves no probe behind
+    #[test]
+    fn test_check_writable_creates_directory_without_leftovers() {
+        use tempfile::TempDir;
+
+        // Given a directory that does not exist yet
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("output");
+
+        // When we check that it is writable
+        let result = check_writable(dir.to_str().unwrap());
+
+        // Then it is created, writable and empty
+        assert_eq!(result, Ok(()));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    }
+}
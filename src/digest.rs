@@ -0,0 +1,343 @@
+use std::fs;
+
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::{Deserialize, Serialize};
+use tera::{Context, Tera};
+
+use crate::config::expand_home;
+use crate::error::{Error, Result};
+use crate::metadata::{format_description, DescriptionFormat};
+use crate::watch::NewEpisode;
+
+/// File `--digest html` writes in the output directory
+pub const DIGEST_FILE: &str = "digest.html";
+
+/// The digest page: one section per show, one entry per new episode
+const DIGEST_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>{{ subject }}</title></head>
+<body>
+<h1>{{ subject }}</h1>
+{% for show in shows %}<h2>{{ show.title }}</h2>
+{% for episode in show.episodes %}<h3><a href="{{ episode.url }}">{{ episode.title }}</a></h3>
+{% if episode.date %}<p><small>{{ episode.date }}</small></p>
+{% endif %}{% for paragraph in episode.paragraphs %}<p>{{ paragraph }}</p>
+{% endfor %}{% endfor %}{% endfor %}</body>
+</html>
+"#;
+
+/// Where a run's digest of new episodes goes
+#[derive(Debug, Clone, PartialEq)]
+pub enum Digest {
+    /// Written to [`DIGEST_FILE`], for an external mailer to pick up
+    Html,
+    /// Sent by email through an SMTP server
+    Email(SmtpConfig),
+}
+
+/// How the connection to the SMTP server is secured
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Upgrade a plain connection with STARTTLS (port 587 by default)
+    #[default]
+    Starttls,
+    /// TLS from the start (port 465 by default)
+    Tls,
+    /// No encryption, for a relay on the same machine (port 25 by default)
+    None,
+}
+
+/// An `--smtp-config` file: the server, its credentials and who the digest goes to
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Environment variable holding the password, to keep it out of the file
+    pub password_env: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+impl SmtpConfig {
+    /// Reads and checks an `--smtp-config` TOML file; a leading `~` is the home directory
+    ///
+    /// Addresses are parsed here, so a typo is reported before a long run
+    /// rather than when its digest is sent.
+    pub fn load(path: &str) -> Result<SmtpConfig> {
+        let path = expand_home(path);
+        let text = fs::read_to_string(&path).map_err(Error::io("Failed to read SMTP config"))?;
+        let invalid = |message: String| Error::Config(format!("{}: {}", path, message));
+        let config: SmtpConfig =
+            toml::from_str(&text).map_err(|e: toml::de::Error| invalid(e.message().to_string()))?;
+        if config.to.is_empty() {
+            return Err(invalid("'to' lists no recipients".to_string()));
+        }
+        for address in config.to.iter().chain([&config.from]) {
+            address
+                .parse::<Mailbox>()
+                .map_err(|e| invalid(format!("invalid address '{}': {}", address, e)))?;
+        }
+        Ok(config)
+    }
+
+    /// The username and password to log in with, if the server needs them
+    fn credentials(&self) -> Result<Option<Credentials>> {
+        let Some(username) = &self.username else {
+            return Ok(None);
+        };
+        let password = match (&self.password, &self.password_env) {
+            (Some(password), _) => password.clone(),
+            (None, Some(var)) => std::env::var(var)
+                .map_err(|_| Error::Config(format!("SMTP password variable {} is not set", var)))?,
+            (None, None) => String::new(),
+        };
+        Ok(Some(Credentials::new(username.clone(), password)))
+    }
+}
+
+#[derive(Serialize)]
+struct DigestShow<'a> {
+    title: &'a str,
+    episodes: Vec<DigestEpisode<'a>>,
+}
+
+#[derive(Serialize)]
+struct DigestEpisode<'a> {
+    title: &'a str,
+    url: &'a str,
+    date: &'a str,
+    paragraphs: Vec<String>,
+}
+
+/// The digest's subject line: how many new episodes, and of which show when there is only one
+pub fn digest_subject(episodes: &[NewEpisode]) -> String {
+    let mut shows: Vec<&str> = episodes.iter().map(|e| e.show_title.as_str()).collect();
+    shows.dedup();
+    match shows.as_slice() {
+        [show] => format!("{}: {} new episode(s)", show, episodes.len()),
+        _ => format!("{} new episode(s)", episodes.len()),
+    }
+}
+
+/// Renders the HTML digest of `episodes`, grouped by show in the order they were processed
+///
+/// Descriptions are reduced to plain paragraphs and every value is escaped,
+/// so markup from a show's notes cannot reach the mail client.
+pub fn render_digest(episodes: &[NewEpisode]) -> Result<String> {
+    let mut shows: Vec<DigestShow> = Vec::new();
+    for new in episodes {
+        let episode = DigestEpisode {
+            title: &new.episode.title,
+            url: &new.episode.url,
+            date: &new.metadata.publish_date,
+            paragraphs: format_description(&new.metadata.description, DescriptionFormat::Plain)
+                .split("\n\n")
+                .map(|paragraph| paragraph.trim().to_string())
+                .filter(|paragraph| !paragraph.is_empty())
+                .collect(),
+        };
+        match shows.last_mut() {
+            Some(show) if show.title == new.show_title => show.episodes.push(episode),
+            _ => shows.push(DigestShow {
+                title: &new.show_title,
+                episodes: vec![episode],
+            }),
+        }
+    }
+
+    let mut context = Context::new();
+    context.insert("subject", &digest_subject(episodes));
+    context.insert("shows", &shows);
+    Tera::one_off(DIGEST_TEMPLATE, &context, true)
+        .map_err(|e| Error::Parse(format!("Failed to render digest: {}", e)))
+}
+
+/// Emails the rendered digest to the config's recipients, blocking until the server accepts it
+pub fn send_digest(config: &SmtpConfig, subject: &str, html: String) -> Result<()> {
+    let failed = |e: &dyn std::fmt::Display| Error::Email(e.to_string());
+    let mut message = Message::builder()
+        .from(config.from.parse().map_err(|e| failed(&e))?)
+        .subject(subject)
+        .header(ContentType::TEXT_HTML);
+    for to in &config.to {
+        message = message.to(to.parse().map_err(|e| failed(&e))?);
+    }
+    let message = message.body(html).map_err(|e| failed(&e))?;
+
+    let mut transport = match config.security {
+        SmtpSecurity::Starttls => SmtpTransport::starttls_relay(&config.host),
+        SmtpSecurity::Tls => SmtpTransport::relay(&config.host),
+        SmtpSecurity::None => Ok(SmtpTransport::builder_dangerous(&config.host)),
+    }
+    .map_err(|e| failed(&e))?;
+    if let Some(port) = config.port {
+        transport = transport.port(port);
+    }
+    if let Some(credentials) = config.credentials()? {
+        transport = transport.credentials(credentials);
+    }
+    transport.build().send(&message).map_err(|e| failed(&e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Metadata;
+    use crate::show::EpisodeSummary;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    fn new_episode(id: &str, title: &str, description: &str) -> NewEpisode {
+        NewEpisode {
+            show_url: "https://podcasts.apple.com/us/podcast/show/id1".to_string(),
+            show_title: "The Show".to_string(),
+            episode: EpisodeSummary {
+                id: id.to_string(),
+                title: title.to_string(),
+                url: format!("https://podcasts.apple.com/us/podcast/show/id1?i={}", id),
+                release_date: None,
+            },
+            directory: format!("output/{}", id),
+            transcript: true,
+            metadata: Metadata {
+                description: description.to_string(),
+                publish_date: "Mar 5, 2024".to_string(),
+                ..Metadata::default()
+            },
+        }
+    }
+
+    /// Unit test - render_digest lists each new episode with its link and escaped description
+    #[test]
+    fn test_render_digest() {
+        // Given two new episodes of one show, one with markup in its description
+        let episodes = vec![
+            new_episode("1", "Pricing", "<p>How to <b>price</b></p><p>Part two</p>"),
+            new_episode("2", "Hiring <Part 1>", ""),
+        ];
+
+        // When the digest is rendered
+        let html = render_digest(&episodes).unwrap();
+
+        // Then it names the show and count, links every episode and escapes its text
+        assert_eq!(digest_subject(&episodes), "The Show: 2 new episode(s)");
+        assert!(html.contains("<title>The Show: 2 new episode(s)</title>"));
+        assert_eq!(html.matches("<h2>").count(), 1);
+        assert!(html.contains("?i=1\">Pricing</a>"));
+        assert!(html.contains("<p>How to price</p>\n<p>Part two</p>"));
+        assert!(html.contains("Hiring &lt;Part 1&gt;"));
+        assert!(html.contains("<small>Mar 5, 2024</small>"));
+    }
+
+    /// Unit test - SmtpConfig::load rejects configs that could never send
+    #[test]
+    fn test_smtp_config_load() {
+        // Given a valid config, one without recipients and one with a bad address
+        let temp_dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, text: &str| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, text).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        let valid = write(
+            "valid.toml",
+            "host = \"smtp.example.com\"\nfrom = \"Podcasts <me@example.com>\"\nto = [\"me@example.com\"]\n",
+        );
+        let empty = write(
+            "empty.toml",
+            "host = \"smtp.example.com\"\nfrom = \"me@example.com\"\nto = []\n",
+        );
+        let bad = write(
+            "bad.toml",
+            "host = \"smtp.example.com\"\nfrom = \"me\"\nto = [\"me@example.com\"]\n",
+        );
+
+        // When they are loaded
+        // Then only the valid one is accepted, with STARTTLS by default
+        let config = SmtpConfig::load(&valid).unwrap();
+        assert_eq!(config.security, SmtpSecurity::Starttls);
+        assert!(SmtpConfig::load(&empty)
+            .unwrap_err()
+            .to_string()
+            .contains("no recipients"));
+        assert!(SmtpConfig::load(&bad)
+            .unwrap_err()
+            .to_string()
+            .contains("invalid address 'me'"));
+    }
+
+    /// Unit test - send_digest delivers the digest as an HTML email
+    #[test]
+    fn test_send_digest() {
+        // Given a plain SMTP server on localhost that records what it receives
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut received = String::new();
+            writer.write_all(b"220 localhost ready\r\n").unwrap();
+            let mut in_data = false;
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 {
+                received.push_str(&line);
+                let reply: &[u8] = if in_data {
+                    if line == ".\r\n" {
+                        in_data = false;
+                        b"250 queued\r\n"
+                    } else {
+                        b""
+                    }
+                } else if line.starts_with("DATA") {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                } else if line.starts_with("QUIT") {
+                    writer.write_all(b"221 bye\r\n").unwrap();
+                    break;
+                } else {
+                    b"250 ok\r\n"
+                };
+                writer.write_all(reply).unwrap();
+                line.clear();
+            }
+            received
+        });
+        let config = SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            security: SmtpSecurity::None,
+            username: None,
+            password: None,
+            password_env: None,
+            from: "Podcasts <me@example.com>".to_string(),
+            to: vec!["you@example.com".to_string()],
+        };
+
+        // When a digest is sent through it
+        let episodes = vec![new_episode("1", "Pricing", "Notes")];
+        send_digest(
+            &config,
+            &digest_subject(&episodes),
+            render_digest(&episodes).unwrap(),
+        )
+        .unwrap();
+
+        // Then the server got one HTML message for the recipient
+        let received = server.join().unwrap();
+        assert!(received.contains("MAIL FROM:<me@example.com>"));
+        assert!(received.contains("RCPT TO:<you@example.com>"));
+        assert!(received.contains("Subject: The Show: 1 new episode(s)"));
+        assert!(received.contains("Content-Type: text/html"));
+    }
+}
@@ -0,0 +1,210 @@
+use serde::Serialize;
+
+/// Fewest measured episodes before any is called an outlier
+const MIN_EPISODES: usize = 4;
+
+/// How many interquartile ranges past the quartiles an outlier lies (Tukey's fences)
+const FENCE: f64 = 1.5;
+
+/// Width of the longest bar in the text chart
+pub const CHART_WIDTH: usize = 30;
+
+/// Why an episode stands out from the rest of its show
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Outlier {
+    Short,
+    Long,
+    FewWords,
+    ManyWords,
+}
+
+impl Outlier {
+    pub fn label(self) -> &'static str {
+        match self {
+            Outlier::Short => "unusually short",
+            Outlier::Long => "unusually long",
+            Outlier::FewWords => "few transcript words",
+            Outlier::ManyWords => "many transcript words",
+        }
+    }
+}
+
+/// One episode's length, by the clock and by its transcript
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct EpisodeLength {
+    pub id: String,
+    pub title: String,
+    pub publish_date: Option<String>,
+    pub duration_seconds: Option<u64>,
+    /// Words in the archived transcript, if there is one
+    pub transcript_words: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub outliers: Vec<Outlier>,
+}
+
+/// The result of `report durations` for one show
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct DurationReport {
+    pub show_id: String,
+    pub median_duration_seconds: Option<f64>,
+    pub median_transcript_words: Option<f64>,
+    /// Oldest first
+    pub episodes: Vec<EpisodeLength>,
+}
+
+/// Orders a show's episodes oldest first and flags those far outside the rest
+///
+/// An episode is an outlier when its duration or transcript word count lies
+/// more than 1.5 interquartile ranges beyond the quartiles. Episodes without a
+/// value are left out of that measure, and nothing is flagged until there are
+/// enough values to compare.
+pub fn duration_report(show_id: &str, mut episodes: Vec<EpisodeLength>) -> DurationReport {
+    // Undated episodes sort first, as `None` orders before any date
+    episodes.sort_by(|a, b| a.publish_date.cmp(&b.publish_date));
+
+    let durations: Vec<f64> = episodes
+        .iter()
+        .filter_map(|episode| episode.duration_seconds.map(|seconds| seconds as f64))
+        .collect();
+    let words: Vec<f64> = episodes
+        .iter()
+        .filter_map(|episode| episode.transcript_words.map(|words| words as f64))
+        .collect();
+    let duration_fences = fences(&durations);
+    let word_fences = fences(&words);
+
+    for episode in &mut episodes {
+        let duration = episode.duration_seconds.map(|seconds| seconds as f64);
+        if let (Some(duration), Some((low, high))) = (duration, duration_fences) {
+            if duration < low {
+                episode.outliers.push(Outlier::Short);
+            } else if duration > high {
+                episode.outliers.push(Outlier::Long);
+            }
+        }
+        let words = episode.transcript_words.map(|words| words as f64);
+        if let (Some(words), Some((low, high))) = (words, word_fences) {
+            if words < low {
+                episode.outliers.push(Outlier::FewWords);
+            } else if words > high {
+                episode.outliers.push(Outlier::ManyWords);
+            }
+        }
+    }
+
+    DurationReport {
+        show_id: show_id.to_string(),
+        median_duration_seconds: quantile(&durations, 0.5),
+        median_transcript_words: quantile(&words, 0.5),
+        episodes,
+    }
+}
+
+/// A bar for the text chart, `CHART_WIDTH` long at `max`
+pub fn bar(value: u64, max: u64) -> String {
+    let filled = if max == 0 {
+        0
+    } else {
+        ((value as f64 / max as f64) * CHART_WIDTH as f64).round() as usize
+    };
+    format!(
+        "{}{}",
+        "█".repeat(filled),
+        "░".repeat(CHART_WIDTH.saturating_sub(filled))
+    )
+}
+
+/// The values below and above which an outlier lies, if there are enough values
+fn fences(values: &[f64]) -> Option<(f64, f64)> {
+    if values.len() < MIN_EPISODES {
+        return None;
+    }
+    let q1 = quantile(values, 0.25)?;
+    let q3 = quantile(values, 0.75)?;
+    let spread = FENCE * (q3 - q1);
+    Some((q1 - spread, q3 + spread))
+}
+
+/// The `q` quantile of `values`, interpolating between the nearest two
+fn quantile(values: &[f64], q: f64) -> Option<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let position = (sorted.len().checked_sub(1)? as f64) * q;
+    let below = sorted[position.floor() as usize];
+    let above = sorted[position.ceil() as usize];
+    Some(below + (above - below) * position.fract())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn episode(date: &str, duration_seconds: Option<u64>, words: Option<usize>) -> EpisodeLength {
+        EpisodeLength {
+            id: date.to_string(),
+            title: format!("Episode of {}", date),
+            publish_date: Some(date.to_string()),
+            duration_seconds,
+            transcript_words: words,
+            outliers: Vec::new(),
+        }
+    }
+
+    /// Unit test - duration_report orders episodes by date and flags those far from the rest
+    #[test]
+    fn test_duration_report_flags_outliers() {
+        // Given five hour-long episodes, a short trailer and one with a long transcript
+        let episodes = vec![
+            episode("2024-05-01", Some(3660), Some(9_800)),
+            episode("2024-01-01", Some(3600), Some(9_500)),
+            episode("2024-02-01", Some(3540), Some(9_200)),
+            episode("2024-03-01", Some(240), None),
+            episode("2024-04-01", Some(3720), Some(30_000)),
+            episode("2024-06-01", Some(3500), Some(9_000)),
+            episode("2024-07-01", None, Some(9_600)),
+        ];
+
+        // When we build the report
+        let report = duration_report("840986946", episodes);
+
+        // Then episodes run oldest first, with the trailer and the wordy episode flagged
+        let dates: Vec<_> = report
+            .episodes
+            .iter()
+            .map(|episode| episode.publish_date.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            dates,
+            [
+                "2024-01-01",
+                "2024-02-01",
+                "2024-03-01",
+                "2024-04-01",
+                "2024-05-01",
+                "2024-06-01",
+                "2024-07-01"
+            ]
+        );
+        let flagged: Vec<_> = report
+            .episodes
+            .iter()
+            .filter(|episode| !episode.outliers.is_empty())
+            .map(|episode| (episode.id.as_str(), episode.outliers.clone()))
+            .collect();
+        assert_eq!(
+            flagged,
+            [
+                ("2024-03-01", vec![Outlier::Short]),
+                ("2024-04-01", vec![Outlier::ManyWords])
+            ]
+        );
+        assert_eq!(report.median_duration_seconds, Some(3570.0));
+        assert_eq!(report.median_transcript_words, Some(9_550.0));
+
+        // And too few episodes are never flagged
+        let few = duration_report("1", vec![episode("2024-01-01", Some(60), None)]);
+        assert!(few.episodes[0].outliers.is_empty());
+        assert_eq!(bar(15, 30).chars().filter(|c| *c == '█').count(), 15);
+    }
+}
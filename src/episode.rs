@@ -0,0 +1,217 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::apple_url::{episode_id_from_url, storefront_from_url, ParsedUrl};
+use crate::compress::{artifact_name, read_artifact};
+use crate::error::{Error, Result};
+use crate::fetch::{fetch_page, HttpOptions};
+use crate::locale::{detect_language, parse_localized_date};
+use crate::metadata::{canonical_url, extract_metadata_with, Extraction, Metadata};
+use crate::server_data::find_episode_guid;
+use crate::transcript::{
+    download_transcript_parts, find_transcript_parts, find_transcript_url, Transcript,
+    TranscriptPart,
+};
+use crate::variant::{detect_variant, PageVariant};
+
+/// An episode page together with the metadata extracted from it
+#[derive(Debug, Clone)]
+pub struct Episode {
+    pub url: String,
+    pub html: String,
+    pub metadata: Metadata,
+    pub options: HttpOptions,
+    /// The page layout the episode was extracted with, or `None` if it was not recognized
+    pub variant: Option<&'static PageVariant>,
+}
+
+impl Episode {
+    /// Builds an episode from HTML that has already been fetched
+    ///
+    /// The publish date is normalized using the storefront in `url`.
+    pub fn from_html(url: &str, html: String, options: &HttpOptions) -> Result<Episode> {
+        Episode::from_html_with(url, html, options, Extraction::Full)
+    }
+
+    /// Builds an episode from fetched HTML, extracting its metadata the given way
+    pub fn from_html_with(
+        url: &str,
+        html: String,
+        options: &HttpOptions,
+        extraction: Extraction,
+    ) -> Result<Episode> {
+        let mut metadata = extract_metadata_with(&html, extraction)?;
+        metadata.publish_date_iso8601 =
+            parse_localized_date(&metadata.publish_date, &storefront_from_url(url));
+        metadata.description_language = detect_language(&metadata.description).map(str::to_string);
+
+        Ok(Episode {
+            url: url.to_string(),
+            variant: detect_variant(&html),
+            html,
+            metadata,
+            options: options.clone(),
+        })
+    }
+
+    /// Builds an episode from a page read from `reader`, such as a file or stdin
+    pub fn from_reader(url: &str, mut reader: impl Read, options: &HttpOptions) -> Result<Episode> {
+        let mut html = String::new();
+        reader
+            .read_to_string(&mut html)
+            .map_err(Error::io("Failed to read HTML"))?;
+        Episode::from_html(url, html, options)
+    }
+
+    /// Builds an episode from a saved page without touching the network
+    ///
+    /// The page's canonical link supplies its URL, and so its storefront; a page
+    /// without one is identified by its path.
+    pub fn from_file(path: impl AsRef<Path>, options: &HttpOptions) -> Result<Episode> {
+        let path = path.as_ref();
+        let html = read_artifact(path).map_err(Error::io("Failed to read HTML file"))?;
+        let url = canonical_url(&html).unwrap_or_else(|| path.display().to_string());
+        Episode::from_html(&url, html, options)
+    }
+
+    /// The storefront code the episode was fetched from
+    pub fn storefront(&self) -> String {
+        storefront_from_url(&self.url)
+    }
+
+    /// The transcript URL embedded in the page, if the episode has one
+    pub fn transcript_url(&self) -> Result<Option<String>> {
+        find_transcript_url(&self.html)
+    }
+
+    /// The TTML files the transcript is served in; more than one for a segmented transcript
+    pub fn transcript_parts(&self) -> Result<Vec<TranscriptPart>> {
+        find_transcript_parts(&self.html)
+    }
+
+    /// Downloads the episode's transcript, stitching a segmented one together
+    pub async fn transcript(&self) -> Result<Option<Transcript>> {
+        let parts = self.transcript_parts()?;
+        let Some(first) = parts.first() else {
+            return Ok(None);
+        };
+
+        let ttml = download_transcript_parts(&parts, &self.options).await?;
+        Ok(Some(Transcript {
+            url: first.url.clone(),
+            ttml,
+        }))
+    }
+
+    /// The episode's RSS GUID, if the page exposes one
+    pub fn guid(&self) -> Result<Option<String>> {
+        find_episode_guid(&self.html)
+    }
+
+    /// A stable identifier for the episode: RSS GUID, then Apple episode ID, then the URL
+    pub fn key(&self) -> String {
+        self.guid()
+            .ok()
+            .flatten()
+            .or_else(|| episode_id_from_url(&self.url))
+            .unwrap_or_else(|| self.url.clone())
+    }
+}
+
+/// Every `.html` page under `dir`, compressed or not, searched recursively and sorted by path
+pub fn saved_pages(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let mut pages = Vec::new();
+    let mut pending = vec![dir.as_ref().to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir).map_err(Error::io("Failed to read directory"))? {
+            let path = entry.map_err(Error::io("Failed to read directory"))?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if artifact_name(&path)
+                .extension()
+                .is_some_and(|ext| ext == "html")
+            {
+                pages.push(path);
+            }
+        }
+    }
+    pages.sort();
+    Ok(pages)
+}
+
+/// Validates, fetches and parses an episode page
+pub async fn fetch_episode(url: &str, options: &HttpOptions) -> Result<Episode> {
+    ParsedUrl::parse(url)?;
+    let html = fetch_page(url, options).await?;
+    Episode::from_html(url, html, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - Episode::from_html extracts metadata and identifiers offline
+    #[test]
+    fn test_episode_from_html_extracts_metadata_and_key() {
+        // Given a German storefront page with JSON-LD and serialized data
+        let html = r#"<html><head>
+            <script id="schema:episode" type="application/ld+json">
+            {"name": "Folge 1", "description": "Beschreibung", "datePublished": "13. Okt. 2023", "partOfSeries": {"name": "Show"}}
+            </script>
+            </head><body>
+            <script type="application/json" id="serialized-server-data">[{"data":{"episode":{"guid":"abc-123"}}}]</script>
+            </body></html>"#;
+        let url = "https://podcasts.apple.com/de/podcast/id1?i=2";
+
+        // When we build the episode
+        let episode = Episode::from_html(url, html.to_string(), &HttpOptions::default()).unwrap();
+
+        // Then metadata is extracted with a storefront-aware ISO date
+        assert_eq!(episode.metadata.episode_title, "Folge 1");
+        assert_eq!(
+            episode.metadata.publish_date_iso8601.as_deref(),
+            Some("2023-10-13")
+        );
+
+        // And the GUID is preferred as the episode key
+        assert_eq!(episode.key(), "abc-123");
+        assert_eq!(
+            episode.variant.map(|v| v.id),
+            Some("serialized-server-data")
+        );
+        assert_eq!(episode.transcript_url().unwrap(), None);
+    }
+
+    /// Unit test - saved pages are found recursively and read with their canonical URL
+    #[test]
+    fn test_episode_from_saved_pages() {
+        // Given an output tree with a saved page, a transcript and a nested page
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("show/episode-2");
+        fs::create_dir_all(&nested).unwrap();
+        let page = r#"<html><head>
+            <link rel="canonical" href="https://podcasts.apple.com/de/podcast/id1?i=2">
+            <script id="schema:episode" type="application/ld+json">
+            {"name": "Folge 2", "datePublished": "13. Okt. 2023", "partOfSeries": {"name": "Show"}}
+            </script></head></html>"#;
+        fs::write(nested.join("episode.html"), page).unwrap();
+        fs::write(nested.join("transcript.ttml"), "<tt/>").unwrap();
+        fs::write(dir.path().join("notes.html"), "<html></html>").unwrap();
+
+        // When we list the saved pages and read the nested one
+        let pages = saved_pages(dir.path()).unwrap();
+        let episode = Episode::from_file(&pages[1], &HttpOptions::default()).unwrap();
+
+        // Then both pages are found and the canonical URL sets the storefront
+        assert_eq!(
+            pages,
+            vec![dir.path().join("notes.html"), nested.join("episode.html")]
+        );
+        assert_eq!(episode.url, "https://podcasts.apple.com/de/podcast/id1?i=2");
+        assert_eq!(
+            episode.metadata.publish_date_iso8601.as_deref(),
+            Some("2023-10-13")
+        );
+    }
+}
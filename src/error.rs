@@ -0,0 +1,206 @@
+use std::time::Duration;
+
+/// Errors returned by the applecast library
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The input is not a URL at all
+    #[error("Invalid URL format: '{0}'")]
+    InvalidUrl(String),
+
+    /// The input is a URL, but not an Apple Podcasts episode, show or channel link
+    #[error("Not an Apple Podcasts URL: '{0}' (expected a podcasts.apple.com episode, show or channel link)")]
+    NotApplePodcasts(String),
+
+    /// A custom request header could not be used
+    #[error("Invalid header: {0}")]
+    InvalidHeader(String),
+
+    /// The HTTP client could not be constructed
+    #[error("Failed to create HTTP client: {0}")]
+    Client(#[source] reqwest::Error),
+
+    /// The request never produced a response (DNS, connect, TLS, timeout)
+    #[error("Failed to fetch URL: {0}")]
+    Request(#[source] reqwest::Error),
+
+    /// The server answered with a non-success status
+    #[error("HTTP request failed with status: {status}{}", rate_limit_note(.status, .retry_after))]
+    Status {
+        status: reqwest::StatusCode,
+        retry_after: Option<Duration>,
+    },
+
+    /// A request kept failing after every retry
+    #[error("{source} (gave up after {attempts} attempts)")]
+    Retried {
+        attempts: u32,
+        #[source]
+        source: Box<Error>,
+    },
+
+    /// The response body could not be read
+    #[error("Failed to read response body: {0}")]
+    Body(#[source] reqwest::Error),
+
+    /// Reading or writing a local file failed
+    #[error("{context}: {source}")]
+    Io {
+        context: &'static str,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// JSON could not be parsed or serialized
+    #[error("Failed to process JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// A transcript was not valid TTML
+    #[error("Failed to parse TTML: {0}")]
+    Ttml(#[from] roxmltree::Error),
+
+    /// A built-in pattern failed to compile
+    #[error("Failed to compile regex: {0}")]
+    Regex(#[from] regex::Error),
+
+    /// The archive database could not be read or written
+    #[error("Archive database error: {0}")]
+    Archive(#[from] rusqlite::Error),
+
+    /// Headless Chromium could not render a page
+    #[error("Failed to render page: {0}")]
+    Render(String),
+
+    /// The text-to-speech command could not produce audio
+    #[error("Failed to render speech: {0}")]
+    Speech(String),
+
+    /// A `--chart` could not be drawn
+    #[error("Failed to draw chart: {0}")]
+    Chart(String),
+
+    /// A `watch --exec` hook command failed
+    #[error("Hook command failed: {0}")]
+    Hook(String),
+
+    /// An `ask --llm-command` command failed or gave no answer
+    #[error("LLM command failed: {0}")]
+    Llm(String),
+
+    /// A `--digest email` message could not be built or sent
+    #[error("Failed to send email: {0}")]
+    Email(String),
+
+    /// A `--git-archive` git command failed
+    #[error("Git command failed: {0}")]
+    Git(String),
+
+    /// A processing stage ran past its `--<stage>-timeout` budget
+    #[error("{stage} stage timed out after {}s", .limit.as_secs())]
+    Timeout {
+        stage: &'static str,
+        limit: Duration,
+    },
+
+    /// The run's `--deadline` passed before this work finished
+    #[error("Run deadline reached")]
+    Deadline,
+
+    /// The config file could not be parsed or names an unknown profile or setting
+    #[error("Invalid config file: {0}")]
+    Config(String),
+
+    /// The page did not contain the data being extracted
+    #[error("{0}")]
+    Parse(String),
+
+    /// Saved files were edited since a run recorded them, and a re-run would overwrite them
+    #[error("{} changed since it was saved; pass --force to overwrite", .0.join(", "))]
+    Changed(Vec<String>),
+}
+
+impl Error {
+    /// Wraps an I/O error with a short description of what was being attempted
+    pub fn io(context: &'static str) -> impl FnOnce(std::io::Error) -> Error {
+        move |source| Error::Io { context, source }
+    }
+
+    /// Whether the error came from talking to the server (connection, status or body)
+    pub fn is_network(&self) -> bool {
+        matches!(
+            self,
+            Error::Client(_)
+                | Error::Request(_)
+                | Error::Status { .. }
+                | Error::Body(_)
+                | Error::Retried { .. }
+        )
+    }
+
+    /// Whether the error came from content that could not be parsed
+    pub fn is_parse(&self) -> bool {
+        matches!(self, Error::Json(_) | Error::Ttml(_) | Error::Parse(_))
+    }
+}
+
+/// Adds the rate-limit explanation to 429 status errors
+fn rate_limit_note(status: &reqwest::StatusCode, retry_after: &Option<Duration>) -> String {
+    if *status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return String::new();
+    }
+
+    match retry_after {
+        Some(delay) => format!(" (rate limited; retry after {}s)", delay.as_secs()),
+        None => " (rate limited)".to_string(),
+    }
+}
+
+/// Result type used throughout the applecast library
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - Status errors explain rate limiting
+    #[test]
+    fn test_status_error_describes_rate_limit() {
+        // Given a 429 response with a Retry-After window and a plain 404
+        let limited = Error::Status {
+            status: reqwest::StatusCode::TOO_MANY_REQUESTS,
+            retry_after: Some(Duration::from_secs(900)),
+        };
+        let missing = Error::Status {
+            status: reqwest::StatusCode::NOT_FOUND,
+            retry_after: None,
+        };
+
+        // When we format them
+        // Then only the 429 mentions rate limiting
+        assert_eq!(
+            limited.to_string(),
+            "HTTP request failed with status: 429 Too Many Requests (rate limited; retry after 900s)"
+        );
+        assert_eq!(
+            missing.to_string(),
+            "HTTP request failed with status: 404 Not Found"
+        );
+    }
+
+    /// Unit test - errors are categorized for exit codes
+    #[test]
+    fn test_error_categories() {
+        // Given a status error, a parse error and an I/O error
+        let status = Error::Status {
+            status: reqwest::StatusCode::NOT_FOUND,
+            retry_after: None,
+        };
+        let parse = Error::Parse("JSON-LD schema not found".to_string());
+        let io = Error::io("Failed to write file")(std::io::Error::other("disk full"));
+
+        // When we categorize them
+        // Then each falls in exactly its own category
+        assert!(status.is_network() && !status.is_parse());
+        assert!(parse.is_parse() && !parse.is_network());
+        assert!(!io.is_network() && !io.is_parse());
+    }
+}
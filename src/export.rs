@@ -0,0 +1,311 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::archive::ArchivedEpisode;
+use crate::error::{Error, Result};
+use crate::output::write_file;
+
+/// Longest slug (in characters) used for a page's file or bundle name
+const MAX_SLUG_CHARS: usize = 60;
+
+/// File name of the cover image copied next to an exported page
+const COVER_FILE: &str = "cover.jpg";
+
+/// Static site generators `list --export` writes pages for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SiteFormat {
+    /// A page bundle per episode: `content/<section>/<name>/index.md` with its cover beside it
+    Hugo,
+    /// A collection document per episode: `_<section>/<name>.md`, cover under `assets/`
+    Jekyll,
+}
+
+/// Parses an `--export` value
+pub fn parse_site_format(raw: &str) -> std::result::Result<SiteFormat, String> {
+    match raw.trim().to_lowercase().as_str() {
+        "hugo" => Ok(SiteFormat::Hugo),
+        "jekyll" => Ok(SiteFormat::Jekyll),
+        _ => Err(format!(
+            "Invalid export format '{}': expected hugo or jekyll",
+            raw
+        )),
+    }
+}
+
+/// Parses a `--section` name into the slug used for its directory
+pub fn parse_section(raw: &str) -> std::result::Result<String, String> {
+    let section = slugify(raw);
+    if section.is_empty() {
+        Err(format!(
+            "Invalid section '{}': expected a name like 'podcasts'",
+            raw
+        ))
+    } else {
+        Ok(section)
+    }
+}
+
+/// Lower-cased ASCII words joined by `-`, as used in site URLs
+pub fn slugify(text: &str) -> String {
+    let words: Vec<String> = text
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    let mut slug = String::new();
+    for word in words {
+        if !slug.is_empty() && slug.len() + 1 + word.len() > MAX_SLUG_CHARS {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word);
+    }
+    slug.chars().take(MAX_SLUG_CHARS).collect()
+}
+
+/// Where and how archived episodes are written as static-site pages
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteExport {
+    pub format: SiteFormat,
+    /// Hugo section or Jekyll collection
+    pub section: String,
+    /// Root of the site the pages are written into
+    pub dir: String,
+    /// Leave out the paragraphs a show repeats in most descriptions; see [`Boilerplate`](crate::boilerplate::Boilerplate)
+    pub strip_boilerplate: bool,
+}
+
+impl SiteExport {
+    /// Writes one episode's page, and its saved artwork as the cover, returning the page path
+    pub fn write(&self, episode: &ArchivedEpisode) -> Result<String> {
+        let name = page_name(episode);
+        let cover = largest_artwork(Path::new(&episode.directory));
+        let root = Path::new(&self.dir);
+
+        let (page, cover_path, cover_ref) = match self.format {
+            SiteFormat::Hugo => {
+                let bundle = root.join("content").join(&self.section).join(&name);
+                (
+                    bundle.join("index.md"),
+                    bundle.join(COVER_FILE),
+                    COVER_FILE.to_string(),
+                )
+            }
+            SiteFormat::Jekyll => {
+                let assets = Path::new("assets").join(&self.section).join(&name);
+                (
+                    root.join(format!("_{}", self.section))
+                        .join(format!("{}.md", name)),
+                    root.join(&assets).join(COVER_FILE),
+                    format!("/{}/{}", assets.display(), COVER_FILE),
+                )
+            }
+        };
+
+        // Without saved artwork the page points at Apple's copy
+        let image = match &cover {
+            Some(source) => {
+                copy_file(source, &cover_path)?;
+                Some(cover_ref)
+            }
+            None => episode.metadata["artwork_url"].as_str().map(str::to_string),
+        };
+
+        let page = page.to_string_lossy().into_owned();
+        write_file(&page, render_page(episode, image.as_deref()))?;
+        Ok(page)
+    }
+}
+
+/// File or bundle name for an episode's page: `<date>-<title slug>`, or its ID without a title
+fn page_name(episode: &ArchivedEpisode) -> String {
+    let slug = match slugify(&episode.title) {
+        slug if slug.is_empty() => slugify(&episode.id),
+        slug => slug,
+    };
+    match &episode.publish_date {
+        Some(date) => format!("{}-{}", date, slug),
+        None => slug,
+    }
+}
+
+/// The largest `artwork-<w>x<h>.jpg` saved in an episode directory
+fn largest_artwork(dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?;
+            let (width, height) = name
+                .strip_prefix("artwork-")?
+                .strip_suffix(".jpg")?
+                .split_once('x')?;
+            let area = width.parse::<u64>().ok()? * height.parse::<u64>().ok()?;
+            Some((area, path))
+        })
+        // Ties in area go to the later path, not to whichever the directory listed last
+        .max()
+        .map(|(_, path)| path)
+}
+
+/// Copies a file, creating the destination's directory if needed
+fn copy_file(source: &Path, destination: &Path) -> Result<()> {
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent).map_err(Error::io("Failed to create output directory"))?;
+    }
+    fs::copy(source, destination).map_err(Error::io("Failed to copy artwork"))?;
+    Ok(())
+}
+
+/// Renders an episode as Markdown with YAML front matter understood by both Hugo and Jekyll
+///
+/// Values are written as JSON, which YAML reads as quoted strings, numbers and
+/// lists, so titles with colons or quotes need no special escaping.
+pub fn render_page(episode: &ArchivedEpisode, image: Option<&str>) -> String {
+    let metadata = &episode.metadata;
+    let quote = |text: &str| serde_json::Value::from(text).to_string();
+
+    let mut front = vec![format!("title: {}", quote(&episode.title))];
+    if let Some(date) = &episode.publish_date {
+        front.push(format!("date: {}", date));
+    }
+    front.push(format!("show: {}", quote(&episode.show_title)));
+    let fields = [
+        ("episode", "episode_number"),
+        ("season", "season_number"),
+        ("duration", "duration_seconds"),
+        ("explicit", "explicit"),
+        ("audio", "audio_url"),
+        ("guid", "guid"),
+        ("tags", "genres"),
+    ];
+    for (key, field) in fields {
+        if !metadata[field].is_null() {
+            front.push(format!("{}: {}", key, metadata[field]));
+        }
+    }
+    if let Some(rating) = episode.rating {
+        front.push(format!("rating: {}", rating));
+    }
+    if !episode.tags.is_empty() {
+        front.push(format!(
+            "personal_tags: {}",
+            serde_json::Value::from(episode.tags.clone())
+        ));
+    }
+    front.push(format!("apple_podcasts_url: {}", quote(&episode.url)));
+    front.push(format!("short_id: {}", quote(&episode.short_id)));
+    if let Some(image) = image {
+        front.push(format!("image: {}", quote(image)));
+    }
+
+    let mut page = format!("---\n{}\n---\n\n", front.join("\n"));
+    if let Some(description) = metadata["description"]
+        .as_str()
+        .filter(|text| !text.trim().is_empty())
+    {
+        page.push_str(description.trim());
+        page.push_str("\n\n");
+    }
+    page.push_str(&format!("[Listen on Apple Podcasts]({})\n", episode.url));
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn episode(directory: &str) -> ArchivedEpisode {
+        ArchivedEpisode {
+            id: "1000631244436".to_string(),
+            short_id: "e1a2b3".to_string(),
+            show_id: Some("840986946".to_string()),
+            url: "https://podcasts.apple.com/us/podcast/id840986946?i=1000631244436".to_string(),
+            title: "Episode 12: \"Quotes\" & Colons: A Test".to_string(),
+            show_title: "Back to the Board".to_string(),
+            publish_date: Some("2023-10-13".to_string()),
+            guid: None,
+            directory: directory.to_string(),
+            transcript_path: None,
+            fetched_at: "2023-10-14T08:00:00Z".to_string(),
+            metadata: json!({
+                "description": "We talk shop.",
+                "episode_number": 12,
+                "genres": ["Business", "Careers"],
+                "artwork_url": "https://is1.example.com/a/3000x3000bb.jpg",
+                "guid": null
+            }),
+            removed_upstream: None,
+            rating: Some(4),
+            tags: vec!["careers".to_string(), "interview".to_string()],
+            played_at: None,
+            progress_seconds: None,
+        }
+    }
+
+    /// Unit test - render_page writes YAML-safe front matter and the description as the body
+    #[test]
+    fn test_render_page_front_matter() {
+        // Given an archived episode whose title has quotes and colons
+        // When we render its page
+        let page = render_page(&episode("out"), Some("cover.jpg"));
+
+        // Then the front matter quotes text, keeps numbers and lists, and skips nulls
+        assert!(page.starts_with(
+            "---\ntitle: \"Episode 12: \\\"Quotes\\\" & Colons: A Test\"\ndate: 2023-10-13\n"
+        ));
+        assert!(page.contains("\nepisode: 12\n"));
+        assert!(page.contains("\ntags: [\"Business\",\"Careers\"]\n"));
+        assert!(page.contains("\nrating: 4\npersonal_tags: [\"careers\",\"interview\"]\n"));
+        assert!(page.contains("\nimage: \"cover.jpg\"\n---\n\nWe talk shop.\n"));
+        assert!(!page.contains("guid:"));
+    }
+
+    /// Unit test - SiteExport lays out Hugo bundles and Jekyll collections with the saved cover
+    #[test]
+    fn test_site_export_layouts() {
+        // Given an episode directory with two artwork sizes
+        let dir = tempfile::tempdir().unwrap();
+        let episode_dir = dir.path().join("episode");
+        fs::create_dir_all(&episode_dir).unwrap();
+        fs::write(episode_dir.join("artwork-600x600.jpg"), "small").unwrap();
+        fs::write(episode_dir.join("artwork-3000x3000.jpg"), "large").unwrap();
+        let episode = episode(episode_dir.to_str().unwrap());
+        let site = dir.path().join("site").to_string_lossy().into_owned();
+
+        // When we export it for Hugo and for Jekyll
+        let hugo = SiteExport {
+            format: SiteFormat::Hugo,
+            section: "podcasts".to_string(),
+            dir: site.clone(),
+            strip_boilerplate: false,
+        };
+        let jekyll = SiteExport {
+            format: SiteFormat::Jekyll,
+            ..hugo.clone()
+        };
+        let hugo_page = hugo.write(&episode).unwrap();
+        let jekyll_page = jekyll.write(&episode).unwrap();
+
+        // Then each page lands where its generator looks, with the largest artwork as cover
+        let name = "2023-10-13-episode-12-quotes-colons-a-test";
+        let bundle = Path::new(&site).join("content/podcasts").join(name);
+        assert_eq!(Path::new(&hugo_page), bundle.join("index.md"));
+        assert_eq!(
+            fs::read_to_string(bundle.join("cover.jpg")).unwrap(),
+            "large"
+        );
+        assert_eq!(
+            Path::new(&jekyll_page),
+            Path::new(&site).join(format!("_podcasts/{}.md", name))
+        );
+        assert!(fs::read_to_string(&jekyll_page)
+            .unwrap()
+            .contains(&format!("image: \"/assets/podcasts/{}/cover.jpg\"", name)));
+    }
+}
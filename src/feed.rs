@@ -0,0 +1,442 @@
+use scraper::{Html, Selector};
+use serde::Serialize;
+
+use crate::apple_url::{storefront_from_url, ParsedUrl};
+use crate::archive::ArchivedEpisode;
+use crate::error::{Error, Result};
+use crate::fetch::{fetch_page, get_text, HttpOptions};
+use crate::locale::parse_localized_date;
+use crate::lookup::lookup;
+use crate::server_data::{find_string_field, serialized_server_data};
+use crate::show::extract_show_metadata;
+use crate::stats::day_number;
+
+/// A show resolved to its original RSS feed
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ShowFeed {
+    /// The Apple Podcasts show URL
+    pub url: String,
+    pub title: String,
+    pub feed_url: String,
+}
+
+/// Finds the RSS feed URL referenced by a show or episode page
+///
+/// The serialized data carries `feedUrl`; an RSS `<link rel="alternate">` is
+/// used when it does not.
+pub fn find_feed_url(html: &str) -> Result<Option<String>> {
+    if let Some(feed_url) = serialized_server_data(html)?
+        .and_then(|data| find_string_field(&data, "feedUrl"))
+        .filter(|url| !url.is_empty())
+    {
+        return Ok(Some(feed_url));
+    }
+
+    let document = Html::parse_document(html);
+    let selector = Selector::parse("link[rel='alternate'][type='application/rss+xml']")
+        .map_err(|e| Error::Parse(format!("Invalid selector: {}", e)))?;
+    Ok(document
+        .select(&selector)
+        .find_map(|link| link.value().attr("href"))
+        .map(str::to_string))
+}
+
+/// Resolves an Apple Podcasts show URL to its RSS feed
+///
+/// The iTunes Lookup API is asked first; the show page is scraped when the API
+/// has no feed for the show or cannot be reached.
+pub async fn resolve_feed(url: &str, options: &HttpOptions) -> Result<ShowFeed> {
+    let show_id = ParsedUrl::parse(url)?
+        .podcast_id
+        .ok_or_else(|| Error::NotApplePodcasts(url.to_string()))?;
+
+    if let Ok(result) = lookup(&show_id, &storefront_from_url(url), options).await {
+        if let Some(show) = result.show {
+            if let Some(feed_url) = show.feed_url.filter(|feed| !feed.is_empty()) {
+                return Ok(ShowFeed {
+                    url: url.to_string(),
+                    title: show.collection_name,
+                    feed_url,
+                });
+            }
+        }
+    }
+
+    let html = fetch_page(url, options).await?;
+    let feed_url = find_feed_url(&html)?
+        .ok_or_else(|| Error::Parse(format!("No RSS feed found for {}", url)))?;
+    let title = extract_show_metadata(&html)
+        .map(|show| show.show_title)
+        .unwrap_or_default();
+    Ok(ShowFeed {
+        url: url.to_string(),
+        title,
+        feed_url,
+    })
+}
+
+/// Downloads a feed and returns it pretty-printed
+pub async fn download_feed(feed_url: &str, options: &HttpOptions) -> Result<String> {
+    pretty_print_xml(&get_text(feed_url, options).await?)
+}
+
+/// Re-indents an XML document two spaces per level
+///
+/// Tags, entities and CDATA sections are copied from the source as written, so
+/// namespace prefixes and escaping survive; only whitespace between elements
+/// changes. Elements holding just text stay on one line.
+pub fn pretty_print_xml(xml: &str) -> Result<String> {
+    let document = roxmltree::Document::parse(xml)
+        .map_err(|e| Error::Parse(format!("Invalid feed XML: {}", e)))?;
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    for node in document.root().children() {
+        write_node(xml, node, 0, &mut out);
+    }
+    Ok(out)
+}
+
+/// Appends one node and its children at `depth`
+fn write_node(xml: &str, node: roxmltree::Node, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    let source = &xml[node.range()];
+
+    if node.is_text() {
+        if !source.trim().is_empty() {
+            out.push_str(&format!("{}{}\n", indent, source.trim()));
+        }
+        return;
+    }
+    if !node.is_element() {
+        out.push_str(&format!("{}{}\n", indent, source));
+        return;
+    }
+
+    let start_tag = &source[..start_tag_len(source)];
+    if start_tag.ends_with("/>") {
+        out.push_str(&format!("{}{}\n", indent, start_tag));
+        return;
+    }
+    let name = start_tag[1..]
+        .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .next()
+        .unwrap_or_default();
+    let end_tag = format!("</{}>", name);
+
+    // Text-only elements stay inline, e.g. `<title>Episode 1</title>`
+    if node.children().all(|child| child.is_text()) {
+        let inner = &source[start_tag.len()..source.len() - end_tag.len()];
+        out.push_str(&format!(
+            "{}{}{}{}\n",
+            indent,
+            start_tag,
+            inner.trim(),
+            end_tag
+        ));
+        return;
+    }
+
+    out.push_str(&format!("{}{}\n", indent, start_tag));
+    for child in node.children() {
+        write_node(xml, child, depth + 1, out);
+    }
+    out.push_str(&format!("{}{}\n", indent, end_tag));
+}
+
+/// Length of the start tag at the beginning of `source`, skipping `>` inside quoted attributes
+fn start_tag_len(source: &str) -> usize {
+    let mut quote = None;
+    for (index, c) in source.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '>') => return index + 1,
+            _ => {}
+        }
+    }
+    source.len()
+}
+
+/// Namespace of the `itunes:` podcast tags
+const ITUNES_NAMESPACE: &str = "http://www.itunes.com/dtds/podcast-1.0.dtd";
+
+/// An episode `<item>` in a show's RSS feed
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct FeedItem {
+    pub title: String,
+    pub guid: Option<String>,
+    /// The `pubDate` as `YYYY-MM-DD`
+    pub publish_date: Option<String>,
+    pub audio_url: Option<String>,
+}
+
+/// Lists the episode items of an RSS feed, in feed order
+///
+/// Items marked `<itunes:block>Yes</itunes:block>` are left out, since the
+/// podcaster asked Apple not to list them.
+pub fn parse_feed_items(xml: &str) -> Result<Vec<FeedItem>> {
+    let document = roxmltree::Document::parse(xml)
+        .map_err(|e| Error::Parse(format!("Invalid feed XML: {}", e)))?;
+    // `<title>` and `<itunes:title>` can both be present; only the RSS one is wanted
+    let child_text = |item: roxmltree::Node, namespace: Option<&str>, name: &str| {
+        item.children()
+            .find(|child| {
+                child.tag_name().namespace() == namespace && child.tag_name().name() == name
+            })
+            .and_then(|child| child.text())
+            .map(|text| text.trim().to_string())
+            .filter(|text| !text.is_empty())
+    };
+
+    Ok(document
+        .descendants()
+        .filter(|node| node.has_tag_name("item"))
+        .filter(|item| {
+            !child_text(*item, Some(ITUNES_NAMESPACE), "block")
+                .is_some_and(|block| block.eq_ignore_ascii_case("yes"))
+        })
+        .map(|item| FeedItem {
+            title: child_text(item, None, "title").unwrap_or_default(),
+            guid: child_text(item, None, "guid"),
+            publish_date: child_text(item, None, "pubDate")
+                .and_then(|date| parse_localized_date(&date, "us")),
+            audio_url: item
+                .children()
+                .find(|child| child.has_tag_name("enclosure"))
+                .and_then(|enclosure| enclosure.attribute("url"))
+                .map(str::to_string),
+        })
+        .collect())
+}
+
+/// Renders feeds as an OPML subscription list for importing into podcast clients
+pub fn to_opml(feeds: &[ShowFeed]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <opml version=\"2.0\">\n  \
+         <head>\n    <title>applecast-cli subscriptions</title>\n  </head>\n  \
+         <body>\n",
+    );
+    for feed in feeds {
+        out.push_str(&format!(
+            "    <outline type=\"rss\" text=\"{title}\" title=\"{title}\" xmlUrl=\"{}\" htmlUrl=\"{}\"/>\n",
+            escape_xml(&feed.feed_url),
+            escape_xml(&feed.url),
+            title = escape_xml(&feed.title),
+        ));
+    }
+    out.push_str("  </body>\n</opml>\n");
+    out
+}
+
+/// Escapes text for an XML attribute or element
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// An archive `fetched_at` time (`YYYY-MM-DDTHH:MM:SSZ`) as an RSS `pubDate`
+fn rss_date(timestamp: &str) -> Option<String> {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let (date, time) = timestamp.trim_end_matches('Z').split_once('T')?;
+    let days = day_number(date)?;
+    let month: usize = date.get(5..7)?.parse().ok()?;
+    Some(format!(
+        "{}, {} {} {} {} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        date.get(8..10)?,
+        MONTHS.get(month.checked_sub(1)?)?,
+        date.get(..4)?,
+        time
+    ))
+}
+
+/// Renders archived episodes as an RSS feed, one item per episode dated by when it was archived
+///
+/// Subscribing a feed reader to it follows what the archiver saves; the item
+/// links point at the episodes' Apple Podcasts pages.
+pub fn to_rss(episodes: &[ArchivedEpisode]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rss version=\"2.0\">\n  \
+         <channel>\n    \
+         <title>applecast-cli: newly archived episodes</title>\n    \
+         <link>https://podcasts.apple.com/</link>\n    \
+         <description>Episodes most recently saved to the archive</description>\n",
+    );
+    if let Some(date) = episodes
+        .first()
+        .and_then(|episode| rss_date(&episode.fetched_at))
+    {
+        out.push_str(&format!("    <lastBuildDate>{}</lastBuildDate>\n", date));
+    }
+    for episode in episodes {
+        out.push_str("    <item>\n");
+        let title = if episode.show_title.is_empty() {
+            episode.title.clone()
+        } else {
+            format!("{} — {}", episode.show_title, episode.title)
+        };
+        out.push_str(&format!("      <title>{}</title>\n", escape_xml(&title)));
+        out.push_str(&format!(
+            "      <link>{}</link>\n",
+            escape_xml(&episode.url)
+        ));
+        out.push_str(&format!(
+            "      <guid isPermaLink=\"false\">{}</guid>\n",
+            escape_xml(&episode.id)
+        ));
+        if let Some(date) = rss_date(&episode.fetched_at) {
+            out.push_str(&format!("      <pubDate>{}</pubDate>\n", date));
+        }
+        if let Some(description) = episode.metadata["description"]
+            .as_str()
+            .filter(|description| !description.is_empty())
+        {
+            out.push_str(&format!(
+                "      <description>{}</description>\n",
+                escape_xml(description)
+            ));
+        }
+        out.push_str("    </item>\n");
+    }
+    out.push_str("  </channel>\n</rss>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - find_feed_url reads the serialized feedUrl, then the RSS link
+    #[test]
+    fn test_find_feed_url() {
+        // Given a page with a serialized feed URL and one with only an RSS link
+        let serialized = r#"<script type="application/json" id="serialized-server-data">[{"data":{"show":{"feedUrl":"https://feeds.example.com/show.xml"}}}]</script>"#;
+        let linked = r#"<html><head><link rel="alternate" type="application/rss+xml" href="https://feeds.example.com/linked.xml"></head></html>"#;
+
+        // When we look for their feeds
+        // Then each is found, and a page without one has none
+        assert_eq!(
+            find_feed_url(serialized).unwrap().as_deref(),
+            Some("https://feeds.example.com/show.xml")
+        );
+        assert_eq!(
+            find_feed_url(linked).unwrap().as_deref(),
+            Some("https://feeds.example.com/linked.xml")
+        );
+        assert_eq!(find_feed_url("<html></html>").unwrap(), None);
+    }
+
+    /// Unit test - parse_feed_items reads items and skips blocked ones
+    #[test]
+    fn test_parse_feed_items() {
+        // Given a feed with a regular item and one blocked from Apple's directory
+        let xml = r#"<rss xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd"><channel><title>Show</title>
+            <item><title>Episode 2</title><itunes:title>Two</itunes:title><guid isPermaLink="false">ep-2</guid><pubDate>Tue, 10 Oct 2023 09:00:00 +0000</pubDate><enclosure url="https://cdn.example.com/2.mp3" type="audio/mpeg"/></item>
+            <item><title>Members only</title><itunes:block>Yes</itunes:block></item>
+        </channel></rss>"#;
+
+        // When we parse its items
+        let items = parse_feed_items(xml).unwrap();
+
+        // Then only the listed item comes back, with its RSS title and ISO date
+        assert_eq!(
+            items,
+            vec![FeedItem {
+                title: "Episode 2".to_string(),
+                guid: Some("ep-2".to_string()),
+                publish_date: Some("2023-10-10".to_string()),
+                audio_url: Some("https://cdn.example.com/2.mp3".to_string()),
+            }]
+        );
+    }
+
+    /// Unit test - pretty_print_xml re-indents while keeping prefixes, entities and CDATA
+    #[test]
+    fn test_pretty_print_xml() {
+        // Given a compact feed with a namespaced element, an entity and a CDATA description
+        let xml = r#"<?xml version="1.0"?><rss xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd"><channel><title>R&amp;D</title><itunes:image href="a.jpg"/><item><description><![CDATA[<p>Hi</p>]]></description></item></channel></rss>"#;
+
+        // When we pretty-print it
+        let pretty = pretty_print_xml(xml).unwrap();
+
+        // Then each element sits on its own indented line, written as in the source
+        assert_eq!(
+            pretty,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <rss xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\">\n\
+             \x20 <channel>\n\
+             \x20   <title>R&amp;D</title>\n\
+             \x20   <itunes:image href=\"a.jpg\"/>\n\
+             \x20   <item>\n\
+             \x20     <description><![CDATA[<p>Hi</p>]]></description>\n\
+             \x20   </item>\n\
+             \x20 </channel>\n\
+             </rss>\n"
+        );
+    }
+
+    /// Unit test - to_opml lists each feed as an escaped outline
+    #[test]
+    fn test_to_opml() {
+        // Given a resolved feed whose title needs escaping
+        let feeds = vec![ShowFeed {
+            url: "https://podcasts.apple.com/us/podcast/id1".to_string(),
+            title: "Tom & Jerry".to_string(),
+            feed_url: "https://feeds.example.com/a.xml".to_string(),
+        }];
+
+        // When we render OPML
+        let opml = to_opml(&feeds);
+
+        // Then the outline carries the escaped title and both URLs
+        assert!(opml.contains(
+            r#"<outline type="rss" text="Tom &amp; Jerry" title="Tom &amp; Jerry" xmlUrl="https://feeds.example.com/a.xml" htmlUrl="https://podcasts.apple.com/us/podcast/id1"/>"#
+        ));
+        assert!(roxmltree::Document::parse(&opml).is_ok());
+    }
+
+    /// Unit test - to_rss dates items by their archive time and escapes their text
+    #[test]
+    fn test_to_rss() {
+        // Given an archived episode whose title needs escaping
+        let episodes = vec![ArchivedEpisode {
+            id: "11".to_string(),
+            short_id: "abc".to_string(),
+            show_id: Some("1".to_string()),
+            url: "https://podcasts.apple.com/us/podcast/show/id1?i=11".to_string(),
+            title: "Q&A".to_string(),
+            show_title: "Show".to_string(),
+            publish_date: Some("2023-01-01".to_string()),
+            guid: None,
+            directory: "out/11".to_string(),
+            transcript_path: None,
+            fetched_at: "2026-10-16T09:30:00Z".to_string(),
+            metadata: serde_json::json!({ "description": "<p>Hi</p>" }),
+            removed_upstream: None,
+            rating: None,
+            tags: Vec::new(),
+            played_at: None,
+            progress_seconds: None,
+        }];
+
+        // When we render RSS
+        let rss = to_rss(&episodes);
+
+        // Then the item is dated by its fetch time, with escaped text, and the feed parses
+        assert!(rss.contains("<title>Show — Q&amp;A</title>"));
+        assert!(rss.contains("<pubDate>Fri, 16 Oct 2026 09:30:00 GMT</pubDate>"));
+        assert!(rss.contains("<lastBuildDate>Fri, 16 Oct 2026 09:30:00 GMT</lastBuildDate>"));
+        assert!(rss.contains("<description>&lt;p&gt;Hi&lt;/p&gt;</description>"));
+        assert!(roxmltree::Document::parse(&rss).is_ok());
+        assert_eq!(
+            parse_feed_items(&rss).unwrap()[0].guid.as_deref(),
+            Some("11")
+        );
+    }
+}
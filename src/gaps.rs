@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::apple_url::{show_id_from_url, storefront_from_url};
+use crate::error::{Error, Result};
+use crate::feed::{parse_feed_items, resolve_feed, FeedItem};
+use crate::fetch::{get_text, HttpOptions};
+use crate::lookup::lookup;
+use crate::show::{
+    enumerate_episodes, EpisodeFilter, EpisodeSummary, ShowCursor, DEFAULT_PAGE_CONCURRENCY,
+};
+
+/// Episodes the RSS feed and Apple's catalog disagree on
+#[derive(Debug, Serialize, Clone, PartialEq, Default)]
+pub struct Gaps {
+    /// Episodes found on both sides
+    pub matched: usize,
+    /// Feed items Apple does not list
+    pub missing_from_apple: Vec<FeedItem>,
+    /// Episodes Apple lists that the feed no longer carries
+    pub missing_from_feed: Vec<EpisodeSummary>,
+}
+
+impl Gaps {
+    /// Returns true when both sides list the same episodes
+    pub fn is_empty(&self) -> bool {
+        self.missing_from_apple.is_empty() && self.missing_from_feed.is_empty()
+    }
+}
+
+/// The result of `report gaps` for one show
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct GapReport {
+    pub show_url: String,
+    pub title: String,
+    pub feed_url: String,
+    pub feed_episodes: usize,
+    pub apple_episodes: usize,
+    /// False when Apple's listing was cut off before its oldest episode
+    pub apple_listing_complete: bool,
+    #[serde(flatten)]
+    pub gaps: Gaps,
+}
+
+/// Title used for matching: lower-cased words with punctuation dropped
+pub(crate) fn title_key(title: &str) -> String {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Pairs feed items with Apple's episodes and returns what is left on each side
+///
+/// Episodes are matched by GUID where `guids` (Apple episode ID to GUID) knows
+/// it, then by title, since podcasters edit titles after Apple has indexed them.
+/// Repeated titles such as "Trailer" pair up in listing order.
+pub fn find_gaps(
+    feed: &[FeedItem],
+    apple: &[EpisodeSummary],
+    guids: &HashMap<String, String>,
+) -> Gaps {
+    let mut apple_left: Vec<Option<(&EpisodeSummary, String)>> = apple
+        .iter()
+        .map(|episode| Some((episode, title_key(&episode.title))))
+        .collect();
+    let mut matched = 0;
+
+    let mut unmatched = Vec::new();
+    for item in feed {
+        let by_guid = item.guid.as_ref().and_then(|guid| {
+            apple_left.iter().position(|slot| {
+                slot.as_ref()
+                    .is_some_and(|(episode, _)| guids.get(&episode.id) == Some(guid))
+            })
+        });
+        match by_guid {
+            Some(index) => {
+                apple_left[index] = None;
+                matched += 1;
+            }
+            None => unmatched.push(item),
+        }
+    }
+
+    let mut missing_from_apple = Vec::new();
+    for item in unmatched {
+        let key = title_key(&item.title);
+        let by_title = apple_left
+            .iter()
+            .position(|slot| slot.as_ref().is_some_and(|(_, title)| *title == key));
+        match by_title {
+            Some(index) => {
+                apple_left[index] = None;
+                matched += 1;
+            }
+            None => missing_from_apple.push(item.clone()),
+        }
+    }
+
+    Gaps {
+        matched,
+        missing_from_apple,
+        missing_from_feed: apple_left
+            .into_iter()
+            .flatten()
+            .map(|(episode, _)| episode.clone())
+            .collect(),
+    }
+}
+
+/// Compares a show's RSS feed against the episodes Apple lists for it
+///
+/// GUIDs come from the iTunes Lookup API, which covers only the latest
+/// episodes; older ones are matched by title. When Apple's listing is cut off,
+/// feed items older than its last episode are not reported as missing.
+pub async fn check_gaps(show_url: &str, options: &HttpOptions) -> Result<GapReport> {
+    let show_id = show_id_from_url(show_url).ok_or_else(|| Error::InvalidUrl(show_url.into()))?;
+    let feed = resolve_feed(show_url, options).await?;
+    let items = parse_feed_items(&get_text(&feed.feed_url, options).await?)?;
+
+    let mut cursor = ShowCursor::new(show_url);
+    let (_, apple) = enumerate_episodes(
+        show_url,
+        options,
+        &EpisodeFilter::default(),
+        DEFAULT_PAGE_CONCURRENCY,
+        &mut cursor,
+        &mut |_| {},
+    )
+    .await?;
+    let complete = cursor.next_page.is_none();
+
+    // GUIDs make matching robust to renamed episodes, but titles work without them
+    let guids: HashMap<String, String> = lookup(&show_id, &storefront_from_url(show_url), options)
+        .await
+        .map(|result| {
+            result
+                .episodes
+                .into_iter()
+                .filter_map(|episode| Some((episode.track_id.to_string(), episode.episode_guid?)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut gaps = find_gaps(&items, &apple, &guids);
+    if !complete {
+        let oldest = apple
+            .iter()
+            .filter_map(|episode| episode.release_date.as_deref())
+            .min();
+        gaps.missing_from_apple
+            .retain(|item| match (item.publish_date.as_deref(), oldest) {
+                (Some(published), Some(oldest)) => published >= oldest,
+                _ => false,
+            });
+    }
+
+    Ok(GapReport {
+        show_url: show_url.to_string(),
+        title: feed.title,
+        feed_url: feed.feed_url,
+        feed_episodes: items.len(),
+        apple_episodes: apple.len(),
+        apple_listing_complete: complete,
+        gaps,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(title: &str, guid: Option<&str>) -> FeedItem {
+        FeedItem {
+            title: title.to_string(),
+            guid: guid.map(str::to_string),
+            publish_date: None,
+            audio_url: None,
+        }
+    }
+
+    fn episode(id: &str, title: &str) -> EpisodeSummary {
+        EpisodeSummary {
+            id: id.to_string(),
+            title: title.to_string(),
+            url: format!("https://podcasts.apple.com/us/podcast/id1?i={}", id),
+            release_date: None,
+        }
+    }
+
+    /// Unit test - find_gaps matches by GUID, then title, and reports the rest
+    #[test]
+    fn test_find_gaps_reports_unmatched_episodes() {
+        // Given a feed with a renamed episode, a matching one and one Apple never indexed,
+        // and an Apple listing that still has an episode removed from the feed
+        let feed = vec![
+            item("Episode 3: The Sequel (Remastered)", Some("guid-3")),
+            item("Episode 2 - Hello, World!", None),
+            item("Episode 2.5: Bonus", Some("guid-bonus")),
+        ];
+        let apple = vec![
+            episode("103", "Episode 3: The Sequel"),
+            episode("102", "Episode 2: Hello World"),
+            episode("101", "Episode 1"),
+        ];
+        let guids = HashMap::from([("103".to_string(), "guid-3".to_string())]);
+
+        // When we look for gaps
+        let gaps = find_gaps(&feed, &apple, &guids);
+
+        // Then the GUID and title matches pair up and the others are reported
+        assert_eq!(gaps.matched, 2);
+        assert_eq!(gaps.missing_from_apple, vec![feed[2].clone()]);
+        assert_eq!(gaps.missing_from_feed, vec![apple[2].clone()]);
+        assert!(!gaps.is_empty());
+    }
+
+    /// Unit test - find_gaps pairs repeated titles one to one
+    #[test]
+    fn test_find_gaps_pairs_repeated_titles() {
+        // Given two trailers in the feed and one on Apple
+        let feed = vec![item("Trailer", None), item("Trailer", None)];
+        let apple = vec![episode("1", "Trailer")];
+
+        // When we look for gaps
+        let gaps = find_gaps(&feed, &apple, &HashMap::new());
+
+        // Then the second trailer is missing from Apple
+        assert_eq!(gaps.matched, 1);
+        assert_eq!(gaps.missing_from_apple.len(), 1);
+        assert!(gaps.missing_from_feed.is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{Error, Result};
+
+/// Files a git archive never commits: downloads and rewrites still in progress,
+/// a `--db` index with its journal files, whose binary pages would churn on every commit,
+/// and the `--backup-friendly` store, which would only duplicate history git already keeps
+pub const GITIGNORE: &str = "*.part\n*.tagging\narchive.sqlite*\n/backup/\n";
+
+/// Committer used when git has no identity configured, e.g. on a fresh server
+const FALLBACK_NAME: &str = "applecast-cli";
+const FALLBACK_EMAIL: &str = "applecast-cli@localhost";
+
+/// An output directory kept as a git repository, committed after each run
+///
+/// Artifacts are written with sorted keys and rows, so a commit holds only
+/// what actually changed and `git log -p` reads as the archive's history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitArchive {
+    dir: PathBuf,
+}
+
+impl GitArchive {
+    /// Opens `dir` as a git archive, running `git init` and adding a `.gitignore` the first time
+    pub fn init(dir: impl AsRef<Path>) -> Result<GitArchive> {
+        let archive = GitArchive {
+            dir: dir.as_ref().to_path_buf(),
+        };
+        fs::create_dir_all(&archive.dir)
+            .map_err(Error::io("Failed to create archive directory"))?;
+        if !archive.dir.join(".git").exists() {
+            archive.git(&["init", "--quiet"])?;
+        }
+        let gitignore = archive.dir.join(".gitignore");
+        if !gitignore.exists() {
+            fs::write(gitignore, GITIGNORE).map_err(Error::io("Failed to write .gitignore"))?;
+        }
+        Ok(archive)
+    }
+
+    /// Commits everything in the directory, returning the short commit hash, or `None` when nothing changed
+    pub fn commit(&self, message: &str) -> Result<Option<String>> {
+        self.git(&["add", "--all"])?;
+        if self.git(&["status", "--porcelain"])?.is_empty() {
+            return Ok(None);
+        }
+
+        let name = format!("user.name={}", FALLBACK_NAME);
+        let email = format!("user.email={}", FALLBACK_EMAIL);
+        let mut args = Vec::new();
+        if self.git(&["config", "user.email"]).is_err() {
+            args.extend(["-c", name.as_str(), "-c", email.as_str()]);
+        }
+        args.extend(["commit", "--quiet", "--message", message]);
+        self.git(&args)?;
+        Ok(Some(self.git(&["rev-parse", "--short", "HEAD"])?))
+    }
+
+    /// Runs git in the archive directory, returning its trimmed stdout
+    fn git(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.dir)
+            .args(args)
+            .output()
+            .map_err(|e| Error::Git(format!("could not run git: {}", e)))?;
+        if !output.status.success() {
+            return Err(Error::Git(format!(
+                "'git {}' exited with {}: {}",
+                args.join(" "),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+/// A commit message: the summary line, then one line per episode title
+pub fn commit_message(summary: &str, titles: &[String]) -> String {
+    if titles.is_empty() {
+        return summary.to_string();
+    }
+    let lines: Vec<String> = titles.iter().map(|title| format!("- {}", title)).collect();
+    format!("{}\n\n{}", summary, lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Unit test - GitArchive commits changes and nothing when the directory is unchanged
+    #[test]
+    fn test_git_archive_commits_only_changes() {
+        // Given a new archive directory with one saved file, a partial download and the SQLite index
+        let temp_dir = TempDir::new().unwrap();
+        let archive = GitArchive::init(temp_dir.path()).unwrap();
+        fs::write(temp_dir.path().join("metadata.json"), "{}").unwrap();
+        fs::write(temp_dir.path().join("audio.mp3.part"), "partial").unwrap();
+        fs::write(temp_dir.path().join("archive.sqlite"), "index").unwrap();
+        fs::write(temp_dir.path().join("archive.sqlite-wal"), "journal").unwrap();
+
+        // When we commit twice, then again after the file changes
+        let first = archive.commit("Sync Show: 1 episode(s) saved").unwrap();
+        let unchanged = archive.commit("Sync Show: 0 episode(s) saved").unwrap();
+        fs::write(temp_dir.path().join("metadata.json"), "[]").unwrap();
+        let second = archive.commit("Sync Show: 1 episode(s) saved").unwrap();
+
+        // Then only runs that changed something are committed, without the partial download or the index
+        assert!(first.is_some());
+        assert_eq!(unchanged, None);
+        assert!(second.is_some() && second != first);
+        let tracked = archive.git(&["ls-files"]).unwrap();
+        assert_eq!(tracked, ".gitignore\nmetadata.json");
+
+        // And opening it again keeps the history
+        let reopened = GitArchive::init(temp_dir.path()).unwrap();
+        assert_eq!(reopened.git(&["rev-list", "--count", "HEAD"]).unwrap(), "2");
+    }
+
+    /// Unit test - commit_message lists episode titles under the summary
+    #[test]
+    fn test_commit_message() {
+        // Given a summary with and without titles
+        // When we build commit messages
+        // Then titles become a bulleted body
+        assert_eq!(
+            commit_message("Batch: 0 succeeded", &[]),
+            "Batch: 0 succeeded"
+        );
+        assert_eq!(
+            commit_message("Sync Show: 2 episode(s) saved", &["A".into(), "B".into()]),
+            "Sync Show: 2 episode(s) saved\n\n- A\n- B"
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use std::io::{BufRead, Write};
+
+use crate::apple_url::parse_storefront;
+use crate::archive::ARCHIVE_FILE;
+use crate::error::{Error, Result};
+use crate::transcript::convert::parse_format;
+
+/// Output directory offered when none is entered, matching `--output-dir`'s default
+const DEFAULT_OUTPUT_DIR: &str = "output";
+
+/// What the `init` wizard asked for, as config settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitAnswers {
+    pub output_dir: String,
+    /// Storefront code for `country`; `None` keeps each URL's own
+    pub country: Option<String>,
+    /// Archive path for `db`; `None` leaves the archive off
+    pub db: Option<String>,
+    /// Values for `transcript-format`, as entered
+    pub transcript_formats: Vec<String>,
+    /// Webhook `watch` posts new episodes to
+    pub notify_url: Option<String>,
+    /// Command `ask` pipes its prompt to
+    pub llm_command: Option<String>,
+}
+
+impl Default for InitAnswers {
+    fn default() -> Self {
+        InitAnswers {
+            output_dir: DEFAULT_OUTPUT_DIR.to_string(),
+            country: None,
+            db: None,
+            transcript_formats: Vec::new(),
+            notify_url: None,
+            llm_command: None,
+        }
+    }
+}
+
+/// Asks questions on `output` and reads the answers from `input`, a line each
+///
+/// A blank line takes the default. An invalid answer is explained and asked
+/// again; once `input` runs out every remaining question takes its default.
+pub struct Prompter<R, W> {
+    input: R,
+    output: W,
+    ended: bool,
+}
+
+impl<R: BufRead, W: Write> Prompter<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Prompter {
+            input,
+            output,
+            ended: false,
+        }
+    }
+
+    /// The trimmed answer to `question`, or `None` for a blank line or no more input
+    fn line(&mut self, question: &str) -> Result<Option<String>> {
+        if self.ended {
+            return Ok(None);
+        }
+        write!(self.output, "{}: ", question).map_err(Error::io("Failed to write prompt"))?;
+        self.output
+            .flush()
+            .map_err(Error::io("Failed to write prompt"))?;
+        let mut answer = String::new();
+        let read = self
+            .input
+            .read_line(&mut answer)
+            .map_err(Error::io("Failed to read answer"))?;
+        if read == 0 {
+            self.ended = true;
+            writeln!(self.output).map_err(Error::io("Failed to write prompt"))?;
+        }
+        let answer = answer.trim();
+        Ok((!answer.is_empty()).then(|| answer.to_string()))
+    }
+
+    /// Asks until `parse` accepts the answer; blank answers give `None`
+    fn ask<T>(
+        &mut self,
+        question: &str,
+        parse: impl Fn(&str) -> std::result::Result<T, String>,
+    ) -> Result<Option<T>> {
+        loop {
+            let Some(answer) = self.line(question)? else {
+                return Ok(None);
+            };
+            match parse(&answer) {
+                Ok(value) => return Ok(Some(value)),
+                Err(message) => {
+                    writeln!(self.output, "  {}", message)
+                        .map_err(Error::io("Failed to write prompt"))?;
+                }
+            }
+        }
+    }
+
+    /// Asks a free-text question, offering `default` for a blank answer
+    fn text(&mut self, question: &str, default: &str) -> Result<String> {
+        let question = format!("{} [{}]", question, default);
+        Ok(self.line(&question)?.unwrap_or_else(|| default.to_string()))
+    }
+
+    /// Asks a yes/no question, defaulting to no
+    fn confirm(&mut self, question: &str) -> Result<bool> {
+        let answer = self.ask(&format!("{} [y/N]", question), |answer| {
+            match answer.to_lowercase().as_str() {
+                "y" | "yes" => Ok(true),
+                "n" | "no" => Ok(false),
+                _ => Err(format!("Invalid answer '{}': expected y or n", answer)),
+            }
+        })?;
+        Ok(answer.unwrap_or(false))
+    }
+}
+
+/// Walks through the settings most people change first
+pub fn run_wizard<R: BufRead, W: Write>(prompter: &mut Prompter<R, W>) -> Result<InitAnswers> {
+    let output_dir = prompter.text("Where should episodes be saved?", DEFAULT_OUTPUT_DIR)?;
+    let country = prompter.ask(
+        "Default country storefront, e.g. 'gb' (blank keeps each link's own)",
+        parse_storefront,
+    )?;
+
+    let db = if prompter.confirm("Keep an archive database for sync, list, grep and ask?")? {
+        let default = format!("{}/{}", output_dir.trim_end_matches('/'), ARCHIVE_FILE);
+        Some(prompter.text("Archive path", &default)?)
+    } else {
+        None
+    };
+
+    let transcript_formats = prompter
+        .ask(
+            "Transcript formats besides TTML, comma-separated: srt, vtt, txt, md, json, \
+             podcast-json, html, a11y (blank for none)",
+            |answer| {
+                answer
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|format| !format.is_empty())
+                    .map(|format| parse_format(format).map(|_| format.to_lowercase()))
+                    .collect::<std::result::Result<Vec<_>, _>>()
+            },
+        )?
+        .unwrap_or_default();
+
+    let (notify_url, llm_command) = if prompter.confirm("Set up integrations (webhook, LLM)?")? {
+        let notify_url = prompter.ask(
+            "Webhook URL for new episodes found by watch (blank for none)",
+            |answer| {
+                if answer.starts_with("http://") || answer.starts_with("https://") {
+                    Ok(answer.to_string())
+                } else {
+                    Err(format!(
+                        "Invalid webhook URL '{}': expected an http:// or https:// URL",
+                        answer
+                    ))
+                }
+            },
+        )?;
+        let llm_command = prompter.ask(
+            "Command ask pipes its prompt to, e.g. 'llm -m gpt-4o-mini' (blank for none)",
+            |answer| Ok(answer.to_string()),
+        )?;
+        (notify_url, llm_command)
+    } else {
+        (None, None)
+    };
+
+    Ok(InitAnswers {
+        output_dir,
+        country,
+        db,
+        transcript_formats,
+        notify_url,
+        llm_command,
+    })
+}
+
+/// A TOML string literal
+fn quoted(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+/// A commented setting: active when `value` is set, otherwise `example` commented out
+fn setting(comment: &str, key: &str, value: Option<String>, example: &str) -> String {
+    match value {
+        Some(value) => format!("# {}\n{} = {}\n", comment, key, value),
+        None => format!("# {}\n# {} = {}\n", comment, key, example),
+    }
+}
+
+/// The config file for `answers`, with a comment on every setting
+///
+/// Settings left unanswered are written commented out with an example, so the
+/// file also shows what else can be set.
+pub fn render_config(answers: &InitAnswers) -> String {
+    let formats = (!answers.transcript_formats.is_empty()).then(|| {
+        let formats: Vec<String> = answers
+            .transcript_formats
+            .iter()
+            .map(|format| quoted(format))
+            .collect();
+        format!("[{}]", formats.join(", "))
+    });
+    let settings = [
+        setting(
+            "Directory all artifacts are written under",
+            "output-dir",
+            Some(quoted(&answers.output_dir)),
+            "\"~/podcasts\"",
+        ),
+        setting(
+            "Storefront Apple Podcasts links are rewritten to",
+            "country",
+            answers.country.as_deref().map(quoted),
+            "\"us\"",
+        ),
+        setting(
+            "SQLite archive that sync records into and list, grep, pack and ask read",
+            "db",
+            answers.db.as_deref().map(quoted),
+            "\"~/podcasts/archive.sqlite\"",
+        ),
+        setting(
+            "Transcript formats saved next to the TTML",
+            "transcript-format",
+            formats,
+            "[\"srt\", \"md\"]",
+        ),
+        setting(
+            "Webhook watch posts each new episode's JSON to",
+            "notify-url",
+            answers.notify_url.as_deref().map(quoted),
+            "\"https://hooks.example.com/podcasts\"",
+        ),
+        setting(
+            "Command ask pipes its prompt to for an answer",
+            "llm-command",
+            answers.llm_command.as_deref().map(quoted),
+            "\"llm -m gpt-4o-mini\"",
+        ),
+    ];
+
+    format!(
+        "# applecast-cli config, written by `applecast-cli init`\n\
+         #\n\
+         # Keys are long flag names without the dashes. Flags given on the command\n\
+         # line or through environment variables take precedence over this file.\n\
+         # Add [profiles.<name>] tables for settings picked with --profile <name>,\n\
+         # [webhooks.<show id>] tables for per-show webhooks, and\n\
+         # [[transforms.<show id>]] entries to clean up extracted fields.\n\
+         \n\
+         {}",
+        settings.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    /// Unit test - the wizard re-asks invalid answers and its config parses back
+    #[test]
+    fn test_wizard_writes_parsable_config() {
+        // Given answers with a mistyped country and transcript format that are corrected
+        let input = "~/podcasts\nuk-ish\ngb\ny\n\nsrt, docx\nsrt, MD\ny\nhooks.example.com\nhttps://hooks.example.com/new\nllm \"-m\" mini\n";
+        let mut output = Vec::new();
+
+        // When the wizard runs and its answers are rendered
+        let answers = run_wizard(&mut Prompter::new(input.as_bytes(), &mut output)).unwrap();
+        let text = render_config(&answers);
+
+        // Then invalid answers were explained, and every answer is a setting of the config
+        let prompts = String::from_utf8(output).unwrap();
+        assert!(prompts.contains("Invalid storefront 'uk-ish'"));
+        assert!(prompts.contains("Invalid transcript format 'docx'"));
+        assert!(prompts.contains("Invalid webhook URL 'hooks.example.com'"));
+        let settings = Config::parse(&text).unwrap().settings;
+        assert_eq!(settings["output-dir"].as_str(), Some("~/podcasts"));
+        assert_eq!(settings["country"].as_str(), Some("gb"));
+        assert_eq!(settings["db"].as_str(), Some("~/podcasts/archive.sqlite"));
+        assert_eq!(
+            settings["transcript-format"],
+            toml::Value::Array(vec!["srt".into(), "md".into()])
+        );
+        assert_eq!(
+            settings["notify-url"].as_str(),
+            Some("https://hooks.example.com/new")
+        );
+        assert_eq!(settings["llm-command"].as_str(), Some("llm \"-m\" mini"));
+    }
+
+    /// Unit test - without answers the wizard keeps the defaults and comments out the rest
+    #[test]
+    fn test_wizard_defaults() {
+        // Given no input at all
+        let mut output = Vec::new();
+
+        // When the wizard runs
+        let answers = run_wizard(&mut Prompter::new(&b""[..], &mut output)).unwrap();
+        let text = render_config(&answers);
+
+        // Then only the output directory is set, and the other settings are commented examples
+        assert_eq!(answers, InitAnswers::default());
+        let settings = Config::parse(&text).unwrap().settings;
+        assert_eq!(settings.len(), 1);
+        assert_eq!(settings["output-dir"].as_str(), Some("output"));
+        assert!(text.contains("# country = \"us\"\n"));
+        assert!(text.contains("# db = \"~/podcasts/archive.sqlite\"\n"));
+    }
+}
@@ -0,0 +1,90 @@
+//! Fetch and process Apple Podcasts episodes.
+//!
+//! The `applecast-cli` binary is a thin wrapper around this library. Most
+//! modules work on in-memory data and leave writing artifacts to the caller
+//! (see [`output`] for the helpers the CLI uses); [`pipeline`] holds the
+//! commands themselves, which save what they fetch into the output directory.
+//!
+//! ```no_run
+//! use applecast::{fetch_episode, HttpOptions};
+//!
+//! # async fn run() -> applecast::Result<()> {
+//! let episode = fetch_episode(
+//!     "https://podcasts.apple.com/us/podcast/id840986946?i=1000631244436",
+//!     &HttpOptions::default(),
+//! )
+//! .await?;
+//! println!("{}", episode.metadata.episode_title);
+//!
+//! if let Some(transcript) = episode.transcript().await? {
+//!     for cue in transcript.cues()? {
+//!         println!("[{:.1}] {}", cue.begin, cue.text);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Network functions are async and run on any Tokio runtime.
+
+pub mod ads;
+pub mod apple_url;
+pub mod archive;
+pub mod artwork;
+pub mod ask;
+pub mod audio;
+pub mod batch;
+pub mod boilerplate;
+pub mod budget;
+pub mod cache;
+pub mod chart;
+pub mod compress;
+pub mod config;
+pub mod devtools;
+pub mod diagnostics;
+pub mod digest;
+pub mod durations;
+pub mod episode;
+pub mod error;
+pub mod export;
+pub mod feed;
+pub mod fetch;
+pub mod gaps;
+pub mod git;
+pub mod init;
+pub mod listening;
+pub mod locale;
+pub mod lookup;
+pub mod manifest;
+pub mod metadata;
+pub mod output;
+pub mod overrides;
+pub mod pack;
+pub mod pipeline;
+pub mod quote;
+pub mod refresh;
+#[cfg(feature = "render")]
+pub mod render;
+pub mod schema;
+pub mod server_data;
+pub mod show;
+pub mod smartlist;
+pub mod speech;
+pub mod sponsors;
+pub mod stats;
+pub mod strip;
+pub mod tag;
+pub mod transcript;
+pub mod transform;
+pub mod variant;
+pub mod watch;
+pub mod webhook;
+pub mod youtube;
+
+pub use apple_url::{ParsedUrl, UrlKind};
+pub use episode::{fetch_episode, Episode};
+pub use error::{Error, Result};
+pub use fetch::HttpOptions;
+pub use metadata::{LocalizedMetadata, Metadata};
+pub use show::ShowMetadata;
+pub use transcript::{Transcript, TranscriptCue};
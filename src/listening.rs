@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+
+use crate::archive::ArchivedEpisode;
+use crate::error::{Error, Result};
+use crate::gaps::title_key;
+
+/// The first bytes of every SQLite database file
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// Episodes in an Apple Podcasts library, with their show's title
+///
+/// The Podcasts app keeps its library in `MTLibrary.sqlite`, a Core Data store
+/// whose `ZMTEPISODE` rows record play counts and the playhead in seconds.
+const APPLE_LIBRARY_EPISODES: &str = "SELECT ZMTEPISODE.ZGUID, ZMTEPISODE.ZENCLOSUREURL,
+         ZMTEPISODE.ZTITLE, ZMTPODCAST.ZTITLE, ZMTEPISODE.ZPLAYCOUNT, ZMTEPISODE.ZPLAYHEAD
+     FROM ZMTEPISODE LEFT JOIN ZMTPODCAST ON ZMTPODCAST.Z_PK = ZMTEPISODE.ZPODCAST";
+
+/// One episode's listening state, as a podcast app exported it
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ListeningRecord {
+    pub guid: Option<String>,
+    /// The enclosure (audio) URL
+    pub audio_url: Option<String>,
+    pub title: String,
+    pub show_title: Option<String>,
+    pub played: bool,
+    /// Seconds listened so far, for an episode started but not finished
+    pub progress_seconds: Option<f64>,
+}
+
+/// What `mark import` did with an export
+#[derive(Debug, Serialize, Clone, PartialEq, Default)]
+pub struct ProgressImport {
+    /// Archived episodes marked played
+    pub played: Vec<String>,
+    /// Archived episodes given a listening position
+    pub in_progress: Vec<String>,
+    /// Records with listening state that matched no archived episode
+    pub unmatched: usize,
+}
+
+/// Reads a listening export: an Overcast OPML export or an Apple Podcasts `MTLibrary.sqlite`
+///
+/// The format is recognized from the file's content. Only episodes played or
+/// started are returned.
+pub fn read_listening_export(path: &Path) -> Result<Vec<ListeningRecord>> {
+    let bytes = fs::read(path).map_err(Error::io("Failed to read listening export"))?;
+    let records = if bytes.starts_with(SQLITE_HEADER) {
+        read_apple_library(path)?
+    } else {
+        let xml = String::from_utf8(bytes).map_err(|_| {
+            Error::Parse(
+                "Expected an Overcast OPML export or an Apple Podcasts library".to_string(),
+            )
+        })?;
+        parse_overcast_opml(&xml)?
+    };
+    Ok(records
+        .into_iter()
+        .filter(|record| record.played || record.progress_seconds.is_some())
+        .collect())
+}
+
+/// Reads the episodes of an Overcast OPML export, "All data" variant, with their shows' titles
+///
+/// Overcast nests `podcast-episode` outlines, carrying `played` and `progress`
+/// attributes, inside each subscribed feed's outline.
+pub fn parse_overcast_opml(xml: &str) -> Result<Vec<ListeningRecord>> {
+    let document = roxmltree::Document::parse(xml)
+        .map_err(|e| Error::Parse(format!("Invalid OPML: {}", e)))?;
+    if !document.root_element().has_tag_name("opml") {
+        return Err(Error::Parse(
+            "Expected an Overcast OPML export or an Apple Podcasts library".to_string(),
+        ));
+    }
+
+    Ok(document
+        .descendants()
+        .filter(|node| node.attribute("type") == Some("podcast-episode"))
+        .map(|episode| {
+            let show_title = episode
+                .parent_element()
+                .filter(|feed| feed.attribute("type") == Some("rss"))
+                .and_then(|feed| feed.attribute("title").or(feed.attribute("text")))
+                .map(str::to_string);
+            ListeningRecord {
+                guid: None,
+                audio_url: episode.attribute("enclosureUrl").map(str::to_string),
+                title: episode.attribute("title").unwrap_or_default().to_string(),
+                show_title,
+                played: episode.attribute("played") == Some("1"),
+                progress_seconds: episode
+                    .attribute("progress")
+                    .and_then(|progress| progress.parse::<f64>().ok())
+                    .filter(|seconds| *seconds > 0.0),
+            }
+        })
+        .collect())
+}
+
+/// Reads the episodes of an Apple Podcasts library database, opened read-only
+fn read_apple_library(path: &Path) -> Result<Vec<ListeningRecord>> {
+    let read = || {
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut statement = connection.prepare(APPLE_LIBRARY_EPISODES)?;
+        let records = statement.query_map([], |row| {
+            Ok(ListeningRecord {
+                guid: row.get(0)?,
+                audio_url: row.get(1)?,
+                title: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                show_title: row.get(3)?,
+                played: row.get::<_, Option<i64>>(4)?.unwrap_or_default() > 0,
+                progress_seconds: row
+                    .get::<_, Option<f64>>(5)?
+                    .filter(|seconds| *seconds > 0.0),
+            })
+        })?;
+        records.collect::<rusqlite::Result<Vec<_>>>()
+    };
+    read().map_err(|e| {
+        Error::Parse(format!(
+            "{} is not an Apple Podcasts library: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+/// Finds the archived episode a listening record is about
+///
+/// Records are matched by GUID, then by audio URL, then by title within the
+/// same show, since apps rewrite enclosure URLs through their own redirects.
+pub struct EpisodeMatcher<'a> {
+    by_guid: HashMap<&'a str, &'a str>,
+    by_audio_url: HashMap<&'a str, &'a str>,
+    by_title: HashMap<(String, String), &'a str>,
+}
+
+impl<'a> EpisodeMatcher<'a> {
+    pub fn new(episodes: &'a [ArchivedEpisode]) -> EpisodeMatcher<'a> {
+        let mut matcher = EpisodeMatcher {
+            by_guid: HashMap::new(),
+            by_audio_url: HashMap::new(),
+            by_title: HashMap::new(),
+        };
+        for episode in episodes {
+            let id = episode.id.as_str();
+            if let Some(guid) = &episode.guid {
+                matcher.by_guid.insert(guid, id);
+            }
+            if let Some(audio_url) = episode.metadata["audio_url"].as_str() {
+                matcher.by_audio_url.insert(audio_url, id);
+            }
+            matcher.by_title.insert(
+                (title_key(&episode.show_title), title_key(&episode.title)),
+                id,
+            );
+        }
+        matcher
+    }
+
+    /// The ID of the archived episode `record` is about, if any
+    pub fn find(&self, record: &ListeningRecord) -> Option<&'a str> {
+        let by_guid = record
+            .guid
+            .as_deref()
+            .and_then(|guid| self.by_guid.get(guid));
+        let by_audio_url = || {
+            record
+                .audio_url
+                .as_deref()
+                .and_then(|url| self.by_audio_url.get(url))
+        };
+        let by_title = || {
+            let show = title_key(record.show_title.as_deref()?);
+            self.by_title.get(&(show, title_key(&record.title)))
+        };
+        by_guid.or_else(by_audio_url).or_else(by_title).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Unit test - Overcast OPML exports are read per episode and matched to archived episodes
+    #[test]
+    fn test_overcast_export_matches_archived_episodes() {
+        // Given an export with a played episode, a started one and an untouched one
+        let opml = r#"<?xml version="1.0" encoding="utf-8"?>
+            <opml version="1.0">
+              <head><title>Overcast Podcast Subscriptions</title></head>
+              <body>
+                <outline text="feeds">
+                  <outline type="rss" text="Back to the Board" title="Back to the Board" xmlUrl="https://example.com/feed">
+                    <outline type="podcast-episode" title="Launch Day" played="1" enclosureUrl="https://cdn.example.com/launch.mp3"/>
+                    <outline type="podcast-episode" title="Pricing, Part 2" played="0" progress="754" enclosureUrl="https://overcast.example/redirect.mp3"/>
+                    <outline type="podcast-episode" title="Trailer" played="0" progress="0"/>
+                  </outline>
+                </outline>
+              </body>
+            </opml>"#;
+        let archived = |id: &str, title: &str, audio_url: &str| ArchivedEpisode {
+            id: id.to_string(),
+            short_id: String::new(),
+            show_id: None,
+            url: String::new(),
+            title: title.to_string(),
+            show_title: "Back to the Board".to_string(),
+            publish_date: None,
+            guid: None,
+            directory: String::new(),
+            transcript_path: None,
+            fetched_at: String::new(),
+            metadata: json!({ "audio_url": audio_url }),
+            removed_upstream: None,
+            rating: None,
+            tags: Vec::new(),
+            played_at: None,
+            progress_seconds: None,
+        };
+        let episodes = [
+            archived("10", "Launch Day!", "https://cdn.example.com/launch.mp3"),
+            archived(
+                "11",
+                "Pricing, part 2",
+                "https://cdn.example.com/pricing.mp3",
+            ),
+        ];
+
+        // When we read it and match each record
+        let records = parse_overcast_opml(opml).unwrap();
+        let matcher = EpisodeMatcher::new(&episodes);
+
+        // Then played state and progress are read, and records match by audio URL, then by title
+        assert_eq!(records.len(), 3);
+        assert!(records[0].played);
+        assert_eq!(records[1].progress_seconds, Some(754.0));
+        assert_eq!(records[1].show_title.as_deref(), Some("Back to the Board"));
+        assert_eq!(records[2].progress_seconds, None);
+        assert_eq!(matcher.find(&records[0]), Some("10"));
+        assert_eq!(matcher.find(&records[1]), Some("11"));
+        assert_eq!(matcher.find(&records[2]), None);
+        assert!(parse_overcast_opml("<rss/>").is_err());
+    }
+}
@@ -0,0 +1,559 @@
+use regex::Regex;
+
+/// Month names per language, used for both full names and prefix abbreviations
+const MONTH_NAMES: &[(&str, [&str; 12])] = &[
+    (
+        "en",
+        [
+            "january",
+            "february",
+            "march",
+            "april",
+            "may",
+            "june",
+            "july",
+            "august",
+            "september",
+            "october",
+            "november",
+            "december",
+        ],
+    ),
+    (
+        "de",
+        [
+            "januar",
+            "februar",
+            "märz",
+            "april",
+            "mai",
+            "juni",
+            "juli",
+            "august",
+            "september",
+            "oktober",
+            "november",
+            "dezember",
+        ],
+    ),
+    (
+        "fr",
+        [
+            "janvier",
+            "février",
+            "mars",
+            "avril",
+            "mai",
+            "juin",
+            "juillet",
+            "août",
+            "septembre",
+            "octobre",
+            "novembre",
+            "décembre",
+        ],
+    ),
+    (
+        "es",
+        [
+            "enero",
+            "febrero",
+            "marzo",
+            "abril",
+            "mayo",
+            "junio",
+            "julio",
+            "agosto",
+            "septiembre",
+            "octubre",
+            "noviembre",
+            "diciembre",
+        ],
+    ),
+    (
+        "it",
+        [
+            "gennaio",
+            "febbraio",
+            "marzo",
+            "aprile",
+            "maggio",
+            "giugno",
+            "luglio",
+            "agosto",
+            "settembre",
+            "ottobre",
+            "novembre",
+            "dicembre",
+        ],
+    ),
+    (
+        "nl",
+        [
+            "januari",
+            "februari",
+            "maart",
+            "april",
+            "mei",
+            "juni",
+            "juli",
+            "augustus",
+            "september",
+            "oktober",
+            "november",
+            "december",
+        ],
+    ),
+    (
+        "pt",
+        [
+            "janeiro",
+            "fevereiro",
+            "março",
+            "abril",
+            "maio",
+            "junho",
+            "julho",
+            "agosto",
+            "setembro",
+            "outubro",
+            "novembro",
+            "dezembro",
+        ],
+    ),
+    (
+        "sv",
+        [
+            "januari",
+            "februari",
+            "mars",
+            "april",
+            "maj",
+            "juni",
+            "juli",
+            "augusti",
+            "september",
+            "oktober",
+            "november",
+            "december",
+        ],
+    ),
+];
+
+/// Abbreviations that are not simple prefixes of the full month name
+const MONTH_ABBREVIATIONS: &[(&str, u32)] =
+    &[("mrz", 3), ("mrt", 3), ("sept", 9), ("set", 9), ("dic", 12)];
+
+/// Storefronts whose numeric dates put the month first
+const MONTH_FIRST_STOREFRONTS: &[&str] = &["us"];
+
+/// Common short words per language, used to guess the language of free text
+const STOPWORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "is", "of", "to", "that", "it", "you", "with", "for", "this", "was",
+            "are", "we",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "und", "das", "ist", "nicht", "ich", "mit", "sie", "ein", "eine", "zu",
+            "auf", "wir",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "et", "est", "des", "une", "que", "pas", "je", "nous", "dans",
+            "pour", "avec",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "la", "los", "las", "y", "es", "que", "una", "por", "con", "para", "pero", "del",
+            "como",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "la", "che", "di", "e", "è", "una", "per", "non", "con", "sono", "gli", "della",
+            "anche",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "van", "is", "dat", "niet", "ik", "je", "met", "zijn", "op",
+            "voor",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "os", "as", "e", "que", "não", "uma", "um", "com", "para", "do", "da", "em",
+            "mais",
+        ],
+    ),
+    (
+        "sv",
+        &[
+            "och", "att", "det", "som", "en", "är", "på", "för", "med", "inte", "jag", "av",
+            "till", "har",
+        ],
+    ),
+];
+
+/// Fewest stopwords a text must contain before its language is guessed
+const MIN_LANGUAGE_HITS: usize = 3;
+
+/// Maps an Apple storefront code to the language its dates are written in
+pub fn storefront_language(storefront: &str) -> &'static str {
+    match storefront {
+        "de" | "at" | "ch" | "li" | "lu" => "de",
+        "fr" | "be" | "mc" | "sn" | "ci" => "fr",
+        "es" | "mx" | "ar" | "cl" | "co" | "pe" | "ve" | "uy" => "es",
+        "it" | "sm" => "it",
+        "nl" => "nl",
+        "br" | "pt" | "ao" | "mz" => "pt",
+        "se" => "sv",
+        "jp" => "ja",
+        "kr" => "ko",
+        _ => "en",
+    }
+}
+
+/// Validates a `--lang` language tag such as `de` or `pt-BR`, normalizing its case
+pub fn parse_language(raw: &str) -> std::result::Result<String, String> {
+    let invalid = || {
+        format!(
+            "Invalid language '{}': expected a language code like 'de' or 'pt-BR'",
+            raw
+        )
+    };
+
+    let tag = raw.trim().replace('_', "-");
+    let (language, region) = match tag.split_once('-') {
+        Some((language, region)) => (language, Some(region)),
+        None => (tag.as_str(), None),
+    };
+    if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(invalid());
+    }
+    match region {
+        None => Ok(language.to_lowercase()),
+        Some(region) if region.len() == 2 && region.chars().all(|c| c.is_ascii_alphabetic()) => Ok(
+            format!("{}-{}", language.to_lowercase(), region.to_uppercase()),
+        ),
+        Some(_) => Err(invalid()),
+    }
+}
+
+/// The `Accept-Language` value for a `--country` storefront and `--lang` language
+///
+/// The storefront's own language is used when no language is given, so
+/// `at` asks for `de-AT, de;q=0.9`.
+pub fn accept_language_for(storefront: Option<&str>, language: Option<&str>) -> Option<String> {
+    let language = language.or(storefront.map(storefront_language))?;
+    let primary = language.split('-').next().unwrap_or(language);
+    let tag = match storefront {
+        Some(storefront) if !language.contains('-') => {
+            format!("{}-{}", language, storefront.to_uppercase())
+        }
+        _ => language.to_string(),
+    };
+
+    if tag == primary {
+        Some(tag)
+    } else {
+        Some(format!("{}, {};q=0.9", tag, primary))
+    }
+}
+
+/// Guesses the language of free text, returning an ISO 639-1 code
+///
+/// Japanese, Korean, Chinese and Russian are recognized by their script; the
+/// Latin-script languages with date support by counting common short words.
+/// Returns `None` for text too short or too mixed to call.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+    let share = |in_script: fn(&char) -> bool| {
+        letters.iter().filter(|c| in_script(c)).count() * 2 > letters.len()
+    };
+    if letters.is_empty() {
+        return None;
+    }
+    if letters.iter().any(|c| matches!(c, '\u{3040}'..='\u{30ff}'))
+        && share(|c| matches!(c, '\u{3040}'..='\u{30ff}' | '\u{4e00}'..='\u{9fff}'))
+    {
+        return Some("ja");
+    }
+    if share(|c| matches!(c, '\u{ac00}'..='\u{d7af}' | '\u{1100}'..='\u{11ff}')) {
+        return Some("ko");
+    }
+    if share(|c| matches!(c, '\u{4e00}'..='\u{9fff}')) {
+        return Some("zh");
+    }
+    if share(|c| matches!(c, '\u{0400}'..='\u{04ff}')) {
+        return Some("ru");
+    }
+
+    let lowercase = text.to_lowercase();
+    let words: Vec<&str> = lowercase
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .collect();
+    let mut scores: Vec<(usize, &'static str)> = STOPWORDS
+        .iter()
+        .map(|(language, stopwords)| {
+            let hits = words.iter().filter(|word| stopwords.contains(word)).count();
+            (hits, *language)
+        })
+        .collect();
+    scores.sort_by_key(|(hits, _)| std::cmp::Reverse(*hits));
+
+    match scores.as_slice() {
+        [(best, language), (second, _), ..] if *best >= MIN_LANGUAGE_HITS && best > second => {
+            Some(language)
+        }
+        _ => None,
+    }
+}
+
+/// Looks up a month number from a (possibly abbreviated) month name, preferring `language`
+fn month_from_name(token: &str, language: &str) -> Option<u32> {
+    let token = token.trim_end_matches('.').to_lowercase();
+
+    if let Some((_, month)) = MONTH_ABBREVIATIONS.iter().find(|(abbr, _)| *abbr == token) {
+        return Some(*month);
+    }
+    if token.chars().count() < 3 {
+        return None;
+    }
+
+    // The storefront's own language first, then every other language
+    let preferred = MONTH_NAMES.iter().filter(|(lang, _)| *lang == language);
+    let others = MONTH_NAMES.iter().filter(|(lang, _)| *lang != language);
+
+    preferred.chain(others).find_map(|(_, names)| {
+        names
+            .iter()
+            .position(|name| name.starts_with(token.as_str()))
+            .map(|index| index as u32 + 1)
+    })
+}
+
+/// Formats and range-checks a date as `YYYY-MM-DD`
+fn format_iso_date(year: u32, month: u32, day: u32) -> Option<String> {
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        _ => return None,
+    };
+
+    if day == 0 || day > days_in_month {
+        return None;
+    }
+
+    Some(format!("{:04}-{:02}-{:02}", year, month, day))
+}
+
+/// Parses a publish date as rendered by the given storefront into ISO 8601 (`YYYY-MM-DD`)
+pub fn parse_localized_date(raw: &str, storefront: &str) -> Option<String> {
+    let raw = raw.trim();
+    let language = storefront_language(storefront);
+
+    // Already ISO 8601 (JSON-LD `datePublished`), possibly with a time part
+    let iso = Regex::new(r"^(\d{4})-(\d{2})-(\d{2})").ok()?;
+    if let Some(captures) = iso.captures(raw) {
+        return format_iso_date(
+            captures[1].parse().ok()?,
+            captures[2].parse().ok()?,
+            captures[3].parse().ok()?,
+        );
+    }
+
+    // Year-first forms: 2023年10月13日 (ja) and 2023. 10. 13. (ko)
+    let year_first = Regex::new(r"(\d{4})\s*[年.]\s*(\d{1,2})\s*[月.]\s*(\d{1,2})").ok()?;
+    if let Some(captures) = year_first.captures(raw) {
+        return format_iso_date(
+            captures[1].parse().ok()?,
+            captures[2].parse().ok()?,
+            captures[3].parse().ok()?,
+        );
+    }
+
+    // Numeric forms: 10/13/2023 (us) or 13.10.2023 / 13/10/2023 (everywhere else)
+    let numeric = Regex::new(r"^(\d{1,2})[./-](\d{1,2})[./-](\d{4})$").ok()?;
+    if let Some(captures) = numeric.captures(raw) {
+        let first: u32 = captures[1].parse().ok()?;
+        let second: u32 = captures[2].parse().ok()?;
+        let year: u32 = captures[3].parse().ok()?;
+        return if MONTH_FIRST_STOREFRONTS.contains(&storefront) {
+            format_iso_date(year, first, second)
+        } else {
+            format_iso_date(year, second, first)
+        };
+    }
+
+    // Textual forms: "Oct 13, 2023", "13. Okt. 2023", "13 de octubre de 2023"
+    let mut day = None;
+    let mut month = None;
+    let mut year = None;
+    for token in raw
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| !token.is_empty())
+    {
+        let digits = token.trim_end_matches('.');
+        if let Ok(number) = digits.parse::<u32>() {
+            if digits.len() == 4 {
+                year = Some(number);
+            } else if day.is_none() {
+                day = Some(number);
+            }
+        } else if month.is_none() {
+            month = month_from_name(token, language);
+        }
+    }
+
+    format_iso_date(year?, month?, day?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - parse_localized_date handles the major storefront formats
+    #[test]
+    fn test_parse_localized_date_across_locales() {
+        // Given publish dates as rendered by each storefront
+        let cases = [
+            ("us", "2023-10-13T07:01:52-0400"),
+            ("us", "Oct 13, 2023"),
+            ("us", "October 13, 2023"),
+            ("us", "10/13/2023"),
+            ("gb", "13 Oct 2023"),
+            ("gb", "13/10/2023"),
+            ("de", "13. Okt. 2023"),
+            ("de", "13.10.2023"),
+            ("de", "13. Oktober 2023"),
+            ("fr", "13 oct. 2023"),
+            ("fr", "13 octobre 2023"),
+            ("es", "13 de octubre de 2023"),
+            ("it", "13 ott 2023"),
+            ("nl", "13 okt. 2023"),
+            ("br", "13 de out. de 2023"),
+            ("se", "13 okt. 2023"),
+            ("jp", "2023年10月13日"),
+            ("kr", "2023. 10. 13."),
+        ];
+
+        // When we parse them
+        // Then every one normalizes to the same ISO date
+        for (storefront, raw) in cases {
+            assert_eq!(
+                parse_localized_date(raw, storefront).as_deref(),
+                Some("2023-10-13"),
+                "{} date '{}' should parse",
+                storefront,
+                raw
+            );
+        }
+    }
+
+    /// Unit test - parse_localized_date disambiguates months by language
+    #[test]
+    fn test_parse_localized_date_uses_storefront_language() {
+        // Given month names that differ or collide between languages
+        // When we parse them for their storefront
+        // Then the storefront's language decides
+        assert_eq!(
+            parse_localized_date("3 mrt. 2024", "nl").as_deref(),
+            Some("2024-03-03")
+        );
+        assert_eq!(
+            parse_localized_date("5 juil. 2024", "fr").as_deref(),
+            Some("2024-07-05")
+        );
+        assert_eq!(
+            parse_localized_date("5 juin 2024", "fr").as_deref(),
+            Some("2024-06-05")
+        );
+        assert_eq!(
+            parse_localized_date("14. März 2024", "de").as_deref(),
+            Some("2024-03-14")
+        );
+    }
+
+    /// Unit test - parse_localized_date rejects impossible dates
+    #[test]
+    fn test_parse_localized_date_rejects_invalid_dates() {
+        // Given unparseable or out-of-range dates
+        // When we parse them
+        // Then no ISO date is produced
+        assert_eq!(parse_localized_date("", "us"), None);
+        assert_eq!(parse_localized_date("sometime soon", "us"), None);
+        assert_eq!(parse_localized_date("31/02/2023", "gb"), None);
+    }
+
+    /// Unit test - parse_language and accept_language_for build Accept-Language from --country and --lang
+    #[test]
+    fn test_accept_language_for_country_and_lang() {
+        // Given storefronts and languages alone and together
+        // When we build Accept-Language values
+        // Then the storefront supplies the region and, without --lang, the language
+        assert_eq!(parse_language("pt_br"), Ok("pt-BR".to_string()));
+        assert_eq!(parse_language("DE"), Ok("de".to_string()));
+        assert!(parse_language("german").is_err());
+        assert_eq!(
+            accept_language_for(Some("at"), None).as_deref(),
+            Some("de-AT, de;q=0.9")
+        );
+        assert_eq!(
+            accept_language_for(Some("ch"), Some("fr")).as_deref(),
+            Some("fr-CH, fr;q=0.9")
+        );
+        assert_eq!(accept_language_for(None, Some("fr")).as_deref(), Some("fr"));
+        assert_eq!(accept_language_for(None, None), None);
+    }
+
+    /// Unit test - detect_language recognizes scripts and common words, and declines to guess short text
+    #[test]
+    fn test_detect_language() {
+        // Given text in several languages and a fragment too short to call
+        // When we detect their languages
+        // Then each is recognized and the fragment is not
+        assert_eq!(
+            detect_language("This is the story of how we built a company that lasts."),
+            Some("en")
+        );
+        assert_eq!(
+            detect_language("Wir sprechen über die Zukunft und das ist nicht einfach."),
+            Some("de")
+        );
+        assert_eq!(
+            detect_language("Dans cet épisode, nous parlons de la musique et des artistes."),
+            Some("fr")
+        );
+        assert_eq!(
+            detect_language("En este episodio hablamos con los fundadores de la empresa."),
+            Some("es")
+        );
+        assert_eq!(
+            detect_language("今日はポッドキャストについて話します。"),
+            Some("ja")
+        );
+        assert_eq!(
+            detect_language("오늘은 팟캐스트에 대해 이야기합니다."),
+            Some("ko")
+        );
+        assert_eq!(detect_language("Episode 12"), None);
+    }
+}
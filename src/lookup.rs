@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::artwork::{artwork_template_from_url, fill_artwork_template, ArtworkSize};
+use crate::error::{Error, Result};
+use crate::fetch::{get_text, HttpOptions};
+use crate::locale::detect_language;
+use crate::metadata::{as_genres, Metadata};
+
+/// iTunes Lookup API endpoint
+pub const LOOKUP_URL: &str = "https://itunes.apple.com/lookup";
+
+/// iTunes Search API endpoint
+pub const SEARCH_URL: &str = "https://itunes.apple.com/search";
+
+/// Most episodes the Lookup API returns for a show; older episodes are only found by scraping
+pub const LOOKUP_EPISODE_LIMIT: u32 = 200;
+
+/// Where episode metadata comes from, selected with `--source`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MetadataSource {
+    /// Only the episode page
+    #[default]
+    Scrape,
+    /// The iTunes Lookup API, with the page filling fields the API lacks
+    Api,
+    /// The episode page, with the iTunes Lookup API filling fields the page lacks
+    Auto,
+}
+
+/// Parses a `--source` value
+pub fn parse_source(raw: &str) -> std::result::Result<MetadataSource, String> {
+    match raw.trim().to_lowercase().as_str() {
+        "scrape" => Ok(MetadataSource::Scrape),
+        "api" => Ok(MetadataSource::Api),
+        "auto" => Ok(MetadataSource::Auto),
+        _ => Err(format!(
+            "Invalid source '{}': expected scrape, api or auto",
+            raw
+        )),
+    }
+}
+
+/// A show as returned by the iTunes Lookup and Search APIs
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ApiShow {
+    pub collection_id: u64,
+    pub collection_name: String,
+    pub artist_name: String,
+    pub collection_view_url: Option<String>,
+    pub feed_url: Option<String>,
+    pub artwork_url600: Option<String>,
+    pub track_count: Option<u32>,
+    pub release_date: Option<String>,
+    pub genres: Vec<String>,
+}
+
+/// An episode as returned by the iTunes Lookup API
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ApiEpisode {
+    pub track_id: u64,
+    pub track_name: String,
+    pub collection_id: u64,
+    pub collection_name: String,
+    pub description: Option<String>,
+    pub short_description: Option<String>,
+    pub release_date: Option<String>,
+    pub track_time_millis: Option<u64>,
+    pub episode_url: Option<String>,
+    pub episode_guid: Option<String>,
+    pub feed_url: Option<String>,
+    pub track_view_url: Option<String>,
+    pub artwork_url600: Option<String>,
+    pub content_advisory_rating: Option<String>,
+    pub genres: Vec<ApiGenre>,
+}
+
+/// A genre attached to an API episode
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct ApiGenre {
+    pub name: String,
+}
+
+/// The show and episodes found by one lookup
+#[derive(Debug, Serialize, Clone, PartialEq, Default)]
+pub struct LookupResult {
+    pub show: Option<ApiShow>,
+    pub episodes: Vec<ApiEpisode>,
+}
+
+impl ApiEpisode {
+    /// Converts the API fields into episode metadata
+    pub fn to_metadata(&self) -> Metadata {
+        let release_date = self.release_date.clone().unwrap_or_default();
+        let genres = self
+            .genres
+            .iter()
+            .map(|genre| serde_json::Value::from(genre.name.as_str()))
+            .collect();
+
+        let mut metadata = Metadata {
+            episode_title: self.track_name.trim().to_string(),
+            description: self
+                .description
+                .as_deref()
+                .or(self.short_description.as_deref())
+                .unwrap_or("")
+                .trim()
+                .to_string(),
+            show_title: self.collection_name.trim().to_string(),
+            // The API gives an ISO timestamp; its date part is the release day in UTC
+            publish_date_iso8601: release_date.get(..10).map(str::to_string),
+            publish_date: release_date,
+            duration_seconds: self.track_time_millis.map(|ms| ms / 1000),
+            guid: self.episode_guid.clone(),
+            audio_url: self.episode_url.clone(),
+            feed_url: self.feed_url.clone(),
+            artwork_url: self.artwork_url600.as_deref().and_then(|url| {
+                let template = artwork_template_from_url(url).ok()?;
+                Some(fill_artwork_template(&template, ArtworkSize::DEFAULT))
+            }),
+            explicit: match self.content_advisory_rating.as_deref() {
+                Some("Explicit") => Some(true),
+                Some("Clean") => Some(false),
+                _ => None,
+            },
+            genres: as_genres(&serde_json::Value::Array(genres)),
+            ..Metadata::default()
+        };
+        metadata.description_language = detect_language(&metadata.description).map(str::to_string);
+        metadata
+    }
+}
+
+/// Splits an iTunes API response into its show and episode results, skipping other kinds
+pub fn parse_api_results(json: &str) -> Result<(Vec<ApiShow>, Vec<ApiEpisode>)> {
+    let response: serde_json::Value = serde_json::from_str(json)?;
+    let results = response["results"]
+        .as_array()
+        .ok_or_else(|| Error::Parse("iTunes API response has no results".to_string()))?;
+
+    let (mut shows, mut episodes) = (Vec::new(), Vec::new());
+    for result in results {
+        match (result["wrapperType"].as_str(), result["kind"].as_str()) {
+            (Some("podcastEpisode"), _) => episodes.push(serde_json::from_value(result.clone())?),
+            (_, Some("podcast")) => shows.push(serde_json::from_value(result.clone())?),
+            _ => {}
+        }
+    }
+    Ok((shows, episodes))
+}
+
+/// Looks up a show or episode by Apple ID, including the show's most recent episodes
+pub async fn lookup(id: &str, storefront: &str, options: &HttpOptions) -> Result<LookupResult> {
+    let limit = LOOKUP_EPISODE_LIMIT.to_string();
+    let url = Url::parse_with_params(
+        LOOKUP_URL,
+        [
+            ("id", id),
+            ("country", storefront),
+            ("entity", "podcastEpisode"),
+            ("limit", limit.as_str()),
+        ],
+    )
+    .map_err(|_| Error::InvalidUrl(LOOKUP_URL.to_string()))?;
+
+    let (shows, episodes) = parse_api_results(&get_text(url.as_str(), options).await?)?;
+    Ok(LookupResult {
+        show: shows.into_iter().next(),
+        episodes,
+    })
+}
+
+/// Looks up one episode of a show, if it is among the show's recent episodes
+pub async fn lookup_episode(
+    show_id: &str,
+    episode_id: &str,
+    storefront: &str,
+    options: &HttpOptions,
+) -> Result<Option<ApiEpisode>> {
+    let result = lookup(show_id, storefront, options).await?;
+    Ok(result
+        .episodes
+        .into_iter()
+        .find(|episode| episode.track_id.to_string() == episode_id))
+}
+
+/// Searches the iTunes catalog for shows matching `term`
+pub async fn search_shows(
+    term: &str,
+    storefront: &str,
+    limit: usize,
+    options: &HttpOptions,
+) -> Result<Vec<ApiShow>> {
+    let limit = limit.to_string();
+    let url = Url::parse_with_params(
+        SEARCH_URL,
+        [
+            ("term", term),
+            ("country", storefront),
+            ("media", "podcast"),
+            ("entity", "podcast"),
+            ("limit", limit.as_str()),
+        ],
+    )
+    .map_err(|_| Error::InvalidUrl(SEARCH_URL.to_string()))?;
+
+    let (shows, _) = parse_api_results(&get_text(url.as_str(), options).await?)?;
+    Ok(shows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - parse_api_results separates shows and episodes and maps episodes to metadata
+    #[test]
+    fn test_parse_api_results_maps_episode_metadata() {
+        // Given a Lookup API response with a show and one of its episodes
+        let json = r#"{"resultCount": 2, "results": [
+            {"wrapperType": "track", "kind": "podcast", "collectionId": 840986946,
+             "collectionName": "Back to the Board", "artistName": "ESPN", "trackCount": 310,
+             "genres": ["Sports", "Podcasts"]},
+            {"wrapperType": "podcastEpisode", "kind": "podcast-episode", "trackId": 1000631244436,
+             "trackName": " Kaepernick, Dak ", "collectionId": 840986946,
+             "collectionName": "Back to the Board", "description": "The latest NBA news",
+             "releaseDate": "2023-10-13T10:00:00Z", "trackTimeMillis": 3723000,
+             "episodeUrl": "https://cdn.example.com/ep.mp3", "episodeGuid": "abc-123",
+             "artworkUrl600": "https://is1.example.com/a/600x600bb.jpg",
+             "contentAdvisoryRating": "Clean",
+             "genres": [{"name": "Podcasts", "id": "26"}, {"name": "Sports", "id": "1545"}]}
+        ]}"#;
+
+        // When we parse it and convert the episode
+        let (shows, episodes) = parse_api_results(json).unwrap();
+        let metadata = episodes[0].to_metadata();
+
+        // Then the show and episode are typed and the metadata fields are filled
+        assert_eq!(shows[0].collection_id, 840986946);
+        assert_eq!(shows[0].track_count, Some(310));
+        assert_eq!(episodes[0].track_id, 1000631244436);
+        assert_eq!(metadata.episode_title, "Kaepernick, Dak");
+        assert_eq!(metadata.publish_date_iso8601.as_deref(), Some("2023-10-13"));
+        assert_eq!(metadata.duration_seconds, Some(3723));
+        assert_eq!(metadata.guid.as_deref(), Some("abc-123"));
+        assert_eq!(
+            metadata.artwork_url.as_deref(),
+            Some("https://is1.example.com/a/3000x3000bb.jpg")
+        );
+        assert_eq!(metadata.explicit, Some(false));
+        assert_eq!(metadata.genres, Some(vec!["Sports".to_string()]));
+    }
+}
@@ -1,865 +1,2033 @@
-use anyhow::{Context, Result};
-use clap::Parser;
-use regex::Regex;
-use scraper::{Html, Selector};
-use serde::Serialize;
+use std::ffi::OsString;
 use std::fs;
-use std::path::Path;
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
 use std::process;
-use url::Url;
-
-/// Represents episode metadata extracted from Apple Podcasts HTML
-#[derive(Debug, Serialize, Clone, PartialEq)]
-struct Metadata {
-    episode_title: String,
-    description: String,
-    show_title: String,
-    publish_date: String,
-}
+use std::time::{Duration, Instant};
+
+use applecast::apple_url::{parse_show, parse_storefront, show_id_from_url, with_storefront};
+use applecast::archive::{parse_tag, ArchiveFilter, ArchiveSet};
+use applecast::artwork::{parse_artwork_size, ArtworkSize};
+use applecast::ask::{AskOptions, DEFAULT_TOP_PASSAGES};
+use applecast::batch::{
+    parse_concurrency, parse_url_list, DEFAULT_ASSET_CONCURRENCY, DEFAULT_CONCURRENCY,
+};
+use applecast::budget::{parse_duration, Budgets};
+use applecast::cache::{default_cache_dir, PageCache};
+use applecast::chart::parse_chart_path;
+use applecast::compress::{parse_compression, Compression};
+use applecast::config::{apply_settings, default_config_path, Config};
+use applecast::digest::{Digest, SmtpConfig};
+use applecast::export::{parse_section, parse_site_format, SiteExport, SiteFormat};
+#[cfg(any(debug_assertions, feature = "test-hooks"))]
+use applecast::fetch::parse_origin_override;
+use applecast::fetch::{parse_header, parse_proxy, parse_proxy_rule, parse_seconds};
+use applecast::init::{render_config, run_wizard, InitAnswers, Prompter};
+use applecast::locale::{accept_language_for, parse_language};
+use applecast::lookup::{parse_source, MetadataSource};
+use applecast::manifest::BACKUP_DIR;
+use applecast::metadata::format::{parse_metadata_format, MetadataFormat};
+use applecast::metadata::sanitize::{parse_sanitize_mode, SanitizeMode, SanitizePolicy};
+use applecast::metadata::{parse_description_format, DescriptionFormat, Extraction};
+use applecast::output::{parse_name_template, to_sorted_json, OutputLayout};
+use applecast::overrides::DEFAULT_OVERRIDES_DIR;
+use applecast::pack::{
+    parse_max_tokens, parse_pack_format, PackFormat, PackOptions, DEFAULT_MAX_TOKENS,
+};
+use applecast::pipeline::archived::{
+    run_ask, run_changes, run_durations, run_grep, run_link, run_list, run_list_create,
+    run_list_delete, run_list_export, run_list_show, run_mark, run_mark_import, run_new_items,
+    run_pack, run_quote, run_rate, run_refresh, run_sponsors, run_tag,
+};
+use applecast::pipeline::batch::run_batch;
+use applecast::pipeline::catalog::{run_feed, run_gaps, run_lookup, run_search};
+use applecast::pipeline::collection::{run_export, run_import, run_merge};
+use applecast::pipeline::devtools::{run_coverage, run_diff_extract, run_strategy_stats};
+use applecast::pipeline::episode::{
+    run_all, run_audio, run_compare, run_fetch, run_metadata, run_offline_metadata, run_transcript,
+    RunOptions,
+};
+use applecast::pipeline::health::{run_healthcheck, run_network_doctor};
+use applecast::pipeline::show::{run_show, run_sync, CrawlOptions};
+use applecast::pipeline::watch::{run_watch, WatchOptions};
+use applecast::pipeline::{Console, Context, Failure, SITE_DIR};
+use applecast::refresh::{parse_refresh_field, parse_strategy, MergeStrategy};
+use applecast::schema::{parse_schema_kind, schema, SchemaKind};
+use applecast::show::{parse_since, parse_since_or_age, EpisodeFilter, DEFAULT_PAGE_CONCURRENCY};
+use applecast::smartlist::{parse_condition, parse_list_name};
+use applecast::speech::{parse_speech_command, SpeechCommand, DEFAULT_SPEECH_COMMAND};
+use applecast::stats::default_stats_path;
+use applecast::transcript::convert::{
+    parse_format, parse_line_length, TranscriptFormat, DEFAULT_A11Y_LINE_LENGTH,
+};
+use applecast::transform::Transforms;
+use applecast::watch::parse_interval;
+use applecast::webhook::Webhooks;
+use applecast::youtube::parse_video_id;
+use applecast::{HttpOptions, ParsedUrl};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::{generate, Shell};
+use tracing::field::{Field, Visit};
+use tracing::{info, warn, Event, Level, Subscriber};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt::format::{FmtSpan, Writer};
+use tracing_subscriber::fmt::writer::{BoxMakeWriter, MakeWriterExt};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
 
 /// A CLI tool for fetching and processing Apple Podcasts content
 #[derive(Parser, Debug)]
 #[command(name = "applecast-cli")]
 #[command(about = "Fetch and process Apple Podcasts episodes and shows", long_about = None)]
+#[command(subcommand_negates_reqs = true)]
 struct Args {
-    /// Apple Podcasts episode or show URL
-    #[arg(value_name = "URL")]
-    url: String,
-}
+    #[command(subcommand)]
+    command: Option<Command>,
 
-fn main() {
-    let args = Args::parse();
+    #[command(flatten)]
+    run: RunArgs,
 
-    // Validate URL format
-    if let Err(e) = validate_url(&args.url) {
-        eprintln!("Error: {}", e);
-        process::exit(1);
-    }
+    #[command(flatten)]
+    http: HttpArgs,
 
-    // Print the received URL
-    println!("📥 Received URL: {}", args.url);
+    #[command(flatten)]
+    output: OutputArgs,
 
-    // Fetch HTML content
-    let html_path = "output/episode.html";
-    if let Err(e) = fetch_html(&args.url, html_path) {
-        eprintln!("Error: {}", e);
-        process::exit(1);
-    }
+    #[command(flatten)]
+    budgets: BudgetArgs,
 
-    println!("✅ Fetched HTML content.");
+    #[command(flatten)]
+    config: ConfigArgs,
 
-    // Extract metadata from HTML
-    let metadata = match extract_metadata(html_path) {
-        Ok(meta) => meta,
-        Err(e) => {
-            eprintln!("Error extracting metadata: {}", e);
-            process::exit(1);
-        }
-    };
+    /// Low-power extraction: read episode metadata from the page's script blocks without
+    /// parsing its HTML, for scheduled syncs on small machines such as a Raspberry Pi
+    #[arg(long, global = true)]
+    profile_lite: bool,
 
-    // Save metadata to JSON
-    let json_path = "output/metadata.json";
-    if let Err(e) = save_metadata_json(&metadata, json_path) {
-        eprintln!("Error saving metadata: {}", e);
-        process::exit(1);
-    }
+    /// Print the JSON Schema of an output file and exit: metadata (default), transcript or segments
+    #[arg(long, value_name = "KIND", num_args = 0..=1, require_equals = true, default_missing_value = "metadata", value_parser = parse_schema_kind)]
+    print_schema: Option<SchemaKind>,
+}
 
-    println!("✅ Metadata extracted and saved to {}", json_path);
+/// Options for the default all-in-one run (page, metadata and transcript)
+#[derive(clap::Args, Debug)]
+struct RunArgs {
+    /// Apple Podcasts episode or show URL
+    #[arg(value_name = "URL", required_unless_present = "print_schema")]
+    url: Option<String>,
 
-    // Search for transcript URL
-    match find_transcript_url(html_path) {
-        Ok(Some(transcript_url)) => {
-            // Transcript found, try to download it
-            let transcript_path = "output/transcript.ttml";
-            match download_transcript(&transcript_url, transcript_path) {
-                Ok(_) => println!("✅ Transcript downloaded and saved to {}", transcript_path),
-                Err(e) => eprintln!("⚠️ Failed to download transcript: {}", e),
-            }
-        }
-        Ok(None) => {
-            println!("⚠️ No transcript found for this episode.");
-        }
-        Err(e) => {
-            eprintln!("⚠️ Error searching for transcript: {}", e);
-        }
-    }
-}
+    /// Detect likely ad segments in the transcript and record them in metadata
+    #[arg(long)]
+    detect_ads: bool,
 
-/// Validates that the provided string is a valid URL
-fn validate_url(url_str: &str) -> Result<(), String> {
-    Url::parse(url_str)
-        .map(|_| ())
-        .map_err(|_| format!("Invalid URL format: '{}'", url_str))
-}
+    /// Export detected ad segments as a JSON skip-list keyed by episode GUID
+    #[arg(long, requires = "detect_ads")]
+    skip_list: bool,
 
-/// Fetches HTML content from a URL and saves it to a file
-fn fetch_html(url: &str, output_path: &str) -> Result<(), String> {
-    // Create output directory if it doesn't exist
-    if let Some(parent) = Path::new(output_path).parent() {
-        fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create output directory: {}", e))?;
-    }
+    /// Also save a copy of the downloaded MP3 audio with the detected ad segments cut out
+    #[arg(long, requires_all = ["detect_ads", "download_audio"])]
+    strip_ads: bool,
 
-    // Create a client that follows redirects with a proper User-Agent
-    let client = reqwest::blocking::Client::builder()
-        .redirect(reqwest::redirect::Policy::limited(10))
-        .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36")
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-
-    // Fetch HTML content
-    let response = client
-        .get(url)
-        .send()
-        .map_err(|e| format!("Failed to fetch URL: {}", e))?;
-
-    // Check if response is successful
-    if !response.status().is_success() {
-        return Err(format!(
-            "HTTP request failed with status: {}",
-            response.status()
-        ));
-    }
+    /// Also capture titles/descriptions from these storefronts, e.g. 'us,fr,jp'
+    #[arg(long, value_name = "CODES", value_delimiter = ',', value_parser = parse_storefront)]
+    locales: Vec<String>,
 
-    // Get response body
-    let html = response
-        .text()
-        .map_err(|e| format!("Failed to read response body: {}", e))?;
+    /// Also download the episode's audio file
+    #[arg(long)]
+    download_audio: bool,
 
-    // Save to file
-    fs::write(output_path, html).map_err(|e| format!("Failed to write to file: {}", e))?;
+    /// Write the episode's metadata, artwork and show-note chapters into the downloaded audio (ID3 for MP3, atoms for M4A)
+    #[arg(long, requires = "download_audio")]
+    tag_audio: bool,
 
-    Ok(())
-}
+    /// Skip the transcript download
+    #[arg(long)]
+    no_transcript: bool,
 
-/// Extracts episode metadata from the saved HTML file
-fn extract_metadata(html_path: &str) -> Result<Metadata> {
-    // Read the HTML file
-    let html_content = fs::read_to_string(html_path).context("Failed to read HTML file")?;
+    /// How many of the episode's transcript, audio and artwork downloads to run at once
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_ASSET_CONCURRENCY, value_parser = parse_concurrency)]
+    concurrency: usize,
 
-    let document = Html::parse_document(&html_content);
+    #[command(flatten)]
+    source: SourceArgs,
 
-    // Try to extract from JSON-LD schema first (most reliable)
-    if let Ok(metadata) = extract_from_json_ld(&document) {
-        return Ok(metadata);
-    }
+    /// Also download the episode artwork at these sizes, e.g. '600x600,3000x3000' (default 3000x3000)
+    #[arg(long, value_name = "SIZES", num_args = 0..=1, require_equals = true, default_missing_value = "3000x3000", value_delimiter = ',', value_parser = parse_artwork_size)]
+    download_artwork: Vec<ArtworkSize>,
 
-    // Fallback to meta tags
-    extract_from_meta_tags(&document)
-}
-
-/// Extracts metadata from JSON-LD schema in the HTML
-fn extract_from_json_ld(document: &Html) -> Result<Metadata> {
-    let script_selector = Selector::parse("script[id='schema:episode']")
-        .map_err(|e| anyhow::anyhow!("Invalid selector: {}", e))?;
-
-    let script = document
-        .select(&script_selector)
-        .next()
-        .context("JSON-LD schema not found")?;
-
-    let json_text = script.text().collect::<String>();
-    let json_value: serde_json::Value =
-        serde_json::from_str(&json_text).context("Failed to parse JSON-LD")?;
-
-    let episode_title = json_value["name"].as_str().unwrap_or("").trim().to_string();
-
-    let description = json_value["description"]
-        .as_str()
-        .unwrap_or("")
-        .trim()
-        .to_string();
-
-    let show_title = json_value["partOfSeries"]["name"]
-        .as_str()
-        .unwrap_or("")
-        .trim()
-        .to_string();
-
-    let publish_date = json_value["datePublished"]
-        .as_str()
-        .unwrap_or("")
-        .trim()
-        .to_string();
-
-    Ok(Metadata {
-        episode_title,
-        description,
-        show_title,
-        publish_date,
-    })
-}
+    /// Also read the show notes aloud into show-notes.wav with a text-to-speech command
+    #[arg(long)]
+    tts_notes: bool,
 
-/// Extracts metadata from HTML meta tags as fallback
-fn extract_from_meta_tags(document: &Html) -> Result<Metadata> {
-    let meta_selector =
-        Selector::parse("meta").map_err(|e| anyhow::anyhow!("Invalid selector: {}", e))?;
-
-    let mut episode_title = String::new();
-    let mut description = String::new();
-    let mut show_title = String::new();
-    let mut publish_date = String::new();
-
-    for element in document.select(&meta_selector) {
-        if let Some(property) = element.value().attr("property") {
-            match property {
-                "og:title" => {
-                    if episode_title.is_empty() {
-                        if let Some(content) = element.value().attr("content") {
-                            episode_title = clean_text(content);
-                        }
-                    }
-                }
-                "og:description" => {
-                    if description.is_empty() {
-                        if let Some(content) = element.value().attr("content") {
-                            description = clean_text(content);
-                        }
-                    }
-                }
-                "og:site_name" => {
-                    if show_title.is_empty() {
-                        if let Some(content) = element.value().attr("content") {
-                            show_title = clean_text(content);
-                        }
-                    }
-                }
-                _ => {}
-            }
-        } else if let Some(name) = element.value().attr("name") {
-            match name {
-                "apple:title" => {
-                    if let Some(content) = element.value().attr("content") {
-                        if episode_title.is_empty() {
-                            episode_title = clean_text(content);
-                        }
-                    }
-                }
-                "description" | "apple:description" => {
-                    if let Some(content) = element.value().attr("content") {
-                        if description.is_empty() {
-                            description = clean_text(content);
-                        }
-                    }
-                }
-                _ => {}
-            }
-        } else if let Some(itemprop) = element.value().attr("itemprop") {
-            match itemprop {
-                "name" | "headline" => {
-                    if let Some(content) = element.value().attr("content") {
-                        if episode_title.is_empty() {
-                            episode_title = clean_text(content);
-                        }
-                    }
-                }
-                "description" => {
-                    if let Some(content) = element.value().attr("content") {
-                        if description.is_empty() {
-                            description = clean_text(content);
-                        }
-                    }
-                }
-                "publisher" => {
-                    if let Some(content) = element.value().attr("content") {
-                        if show_title.is_empty() {
-                            show_title = clean_text(content);
-                        }
-                    }
-                }
-                "datePublished" => {
-                    if let Some(content) = element.value().attr("content") {
-                        if publish_date.is_empty() {
-                            publish_date = clean_text(content);
-                        }
-                    }
-                }
-                _ => {}
-            }
-        }
-    }
+    /// Speech command for --tts-notes: reads text on stdin, writes WAV to {output}
+    #[arg(long, value_name = "COMMAND", requires = "tts_notes", default_value = DEFAULT_SPEECH_COMMAND, value_parser = parse_speech_command)]
+    tts_command: SpeechCommand,
 
-    // Try to extract show title from og:description
-    if show_title.is_empty() {
-        let og_desc_selector = Selector::parse("meta[property='og:description']")
-            .map_err(|e| anyhow::anyhow!("Invalid selector: {}", e))?;
-
-        if let Some(element) = document.select(&og_desc_selector).next() {
-            if let Some(content) = element.value().attr("content") {
-                // og:description often contains "Podcast Episode · Show Name · Date"
-                let parts: Vec<&str> = content.split(" · ").collect();
-                if parts.len() >= 2 {
-                    show_title = parts[1].trim().to_string();
-                }
-            }
-        }
-    }
+    #[command(flatten)]
+    transcript: TranscriptArgs,
 
-    Ok(Metadata {
-        episode_title,
-        description,
-        show_title,
-        publish_date,
-    })
+    #[command(flatten)]
+    crawl: CrawlArgs,
 }
 
-/// Cleans text by trimming whitespace and removing HTML tags
-fn clean_text(text: &str) -> String {
-    // Remove HTML tags using a simple regex-like approach
-    let mut cleaned = text.to_string();
-
-    // Remove HTML tags
-    while let Some(start) = cleaned.find('<') {
-        if let Some(end) = cleaned[start..].find('>') {
-            cleaned.replace_range(start..start + end + 1, "");
-        } else {
-            break;
+impl RunArgs {
+    /// Converts the arguments into library run options
+    fn options(&self) -> RunOptions {
+        RunOptions {
+            detect_ads: self.detect_ads,
+            skip_list: self.skip_list,
+            strip_ads: self.strip_ads,
+            locales: self.locales.clone(),
+            download_audio: self.download_audio,
+            tag_audio: self.tag_audio,
+            no_transcript: self.no_transcript,
+            concurrency: self.concurrency,
+            source: self.source.metadata_source,
+            download_artwork: self.download_artwork.clone(),
+            tts_notes: self.tts_notes,
+            tts_command: self.tts_command.clone(),
+            formats: self.transcript.formats(),
+            crawl: self.crawl.options(),
         }
     }
+}
 
-    // Trim and normalize whitespace
-    cleaned.split_whitespace().collect::<Vec<&str>>().join(" ")
+/// Where episode metadata comes from
+#[derive(clap::Args, Debug)]
+struct SourceArgs {
+    /// Metadata source: scrape (the episode page), api (iTunes Lookup API first) or auto (page first, API fills gaps)
+    #[arg(long = "source", value_name = "SOURCE", default_value = "scrape", value_parser = parse_source)]
+    metadata_source: MetadataSource,
 }
 
-/// Saves metadata to a JSON file
-fn save_metadata_json(metadata: &Metadata, output_path: &str) -> Result<()> {
-    // Create output directory if it doesn't exist
-    if let Some(parent) = Path::new(output_path).parent() {
-        fs::create_dir_all(parent).context("Failed to create output directory")?;
-    }
+/// Transcript conversion options
+#[derive(clap::Args, Debug)]
+struct TranscriptArgs {
+    /// Also convert the transcript to these formats: srt, vtt, txt, md, json, podcast-json, html, a11y
+    #[arg(long = "transcript-format", value_name = "FORMATS", value_delimiter = ',', value_parser = parse_format)]
+    requested: Vec<TranscriptFormat>,
 
-    // Serialize to pretty JSON
-    let json =
-        serde_json::to_string_pretty(metadata).context("Failed to serialize metadata to JSON")?;
+    /// Also save an accessible plain-text transcript (transcript.a11y.txt) for publishing
+    #[arg(long)]
+    a11y: bool,
 
-    // Write to file
-    fs::write(output_path, json).context("Failed to write JSON file")?;
+    /// Longest line of the accessible transcript, in characters
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_A11Y_LINE_LENGTH, value_parser = parse_line_length)]
+    a11y_line_length: usize,
+}
 
-    Ok(())
+impl TranscriptArgs {
+    /// The formats to convert to, with `--a11y` added and the accessible line length applied
+    fn formats(&self) -> Vec<TranscriptFormat> {
+        let accessible = TranscriptFormat::A11y {
+            max_line: self.a11y_line_length,
+        };
+        let mut formats: Vec<TranscriptFormat> = self
+            .requested
+            .iter()
+            .map(|format| match format {
+                TranscriptFormat::A11y { .. } => accessible,
+                format => *format,
+            })
+            .collect();
+        if self.a11y && !formats.contains(&accessible) {
+            formats.push(accessible);
+        }
+        formats
+    }
 }
 
-/// Searches for a transcript URL in the episode HTML file
-fn find_transcript_url(html_path: &str) -> Result<Option<String>> {
-    // Read the HTML file
-    let html_content = fs::read_to_string(html_path).context("Failed to read HTML file")?;
+/// Which episodes to process when given a show URL, and how re-runs treat saved ones
+#[derive(clap::Args, Debug)]
+struct CrawlArgs {
+    /// Process at most this many episodes of a show
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
 
-    // Extract the serialized-server-data JSON
-    let re =
-        Regex::new(r#"<script type="application/json" id="serialized-server-data">(.*?)</script>"#)
-            .context("Failed to compile regex")?;
+    /// Only process show episodes released on or after this date (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE", value_parser = parse_since)]
+    since: Option<String>,
 
-    let json_text = match re.captures(&html_content) {
-        Some(captures) => captures.get(1).map(|m| m.as_str()).unwrap_or(""),
-        None => return Ok(None), // No serialized data found
-    };
+    /// How many show listing pages to fetch at once when their offsets are known
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_PAGE_CONCURRENCY, value_parser = parse_concurrency)]
+    page_concurrency: usize,
 
-    // Parse the JSON
-    let json_value: serde_json::Value = match serde_json::from_str(json_text) {
-        Ok(val) => val,
-        Err(_) => return Ok(None), // Invalid JSON, no transcript
-    };
+    /// Skip episodes whose saved files are all unchanged since they were saved
+    #[arg(long)]
+    skip_existing: bool,
 
-    // Search for closedCaptions URL recursively in the JSON structure
-    fn find_closed_captions_url(value: &serde_json::Value) -> Option<String> {
-        match value {
-            serde_json::Value::Object(map) => {
-                // Check if this object has closedCaptions.url
-                if let Some(cc) = map.get("closedCaptions") {
-                    if let Some(url) = cc.get("url") {
-                        if let Some(url_str) = url.as_str() {
-                            return Some(url_str.to_string());
-                        }
-                    }
-                }
-                // Recursively search in all values
-                for val in map.values() {
-                    if let Some(url) = find_closed_captions_url(val) {
-                        return Some(url);
-                    }
-                }
-                None
-            }
-            serde_json::Value::Array(arr) => {
-                // Search in array elements
-                for val in arr {
-                    if let Some(url) = find_closed_captions_url(val) {
-                        return Some(url);
-                    }
-                }
-                None
-            }
-            _ => None,
+    /// Fetch every episode again, overwriting files edited since they were saved
+    #[arg(long)]
+    force: bool,
+
+    /// Print which episodes would be fetched, skipped or refused, without touching the network or disk
+    #[arg(long)]
+    dry_run: bool,
+}
+
+impl CrawlArgs {
+    /// Converts the arguments into library crawl options
+    fn options(&self) -> CrawlOptions {
+        CrawlOptions {
+            filter: EpisodeFilter {
+                limit: self.limit,
+                since: self.since.clone(),
+            },
+            page_concurrency: self.page_concurrency,
+            skip_existing: self.skip_existing,
+            force: self.force,
+            dry_run: self.dry_run,
         }
     }
+}
 
-    Ok(find_closed_captions_url(&json_value))
+/// Request options shared by every command that talks to Apple
+#[derive(clap::Args, Debug)]
+struct HttpArgs {
+    /// Extra request header as 'Name: value' (repeatable; APPLECAST_HEADERS takes one per line)
+    #[arg(long = "header", value_name = "HEADER", value_parser = parse_header, env = "APPLECAST_HEADERS", value_delimiter = '\n', global = true)]
+    headers: Vec<(String, String)>,
+
+    /// Proxy for every request: http://, https://, socks5:// or socks5h:// URL (default: HTTPS_PROXY/ALL_PROXY, unless --proxy-rule is given)
+    #[arg(long, value_name = "URL", value_parser = parse_proxy, env = "APPLECAST_PROXY", global = true)]
+    proxy: Option<String>,
+
+    /// Proxy for one host and its subdomains, as HOST=URL or HOST=direct, tried before --proxy (repeatable)
+    #[arg(long = "proxy-rule", value_name = "HOST=URL", value_parser = parse_proxy_rule, global = true)]
+    proxy_rules: Vec<(String, Option<String>)>,
+
+    /// User-Agent sent with every request (default: a desktop Safari string)
+    #[arg(long, value_name = "UA", env = "APPLECAST_USER_AGENT", global = true)]
+    user_agent: Option<String>,
+
+    /// Accept-Language sent to Apple, e.g. 'de-DE' to match a German storefront (default: from --country/--lang)
+    #[arg(long, value_name = "LANG", global = true)]
+    accept_language: Option<String>,
+
+    /// Storefront to use, rewriting the country segment of Apple Podcasts URLs, e.g. 'gb' or 'de'
+    #[arg(long, value_name = "CODE", value_parser = parse_storefront, global = true)]
+    country: Option<String>,
+
+    /// Language to ask Apple for, e.g. 'fr' or 'pt-BR' (default: the --country storefront's language)
+    #[arg(long, value_name = "LANG", value_parser = parse_language, global = true)]
+    lang: Option<String>,
+
+    /// Seconds a request may take before it is abandoned (default 30)
+    #[arg(long, value_name = "SECS", value_parser = parse_seconds, global = true)]
+    timeout: Option<Duration>,
+
+    /// Seconds to wait for a connection to be established (default 10)
+    #[arg(long, value_name = "SECS", value_parser = parse_seconds, global = true)]
+    connect_timeout: Option<Duration>,
+
+    /// Times to retry a request after a 5xx, 429, timeout or connection failure (default 3)
+    #[arg(long, value_name = "N", global = true)]
+    retries: Option<u32>,
+
+    /// Seconds to wait before the first retry, doubled for each later one (default 1)
+    #[arg(long, value_name = "SECS", value_parser = parse_seconds, global = true)]
+    retry_delay: Option<Duration>,
+
+    /// Test hook sending requests for one origin to another, as FROM=TO; set through
+    /// APPLECAST_ORIGIN_OVERRIDES by the CLI tests' mock server, and hidden from --help.
+    /// Only debug builds and builds with the `test-hooks` feature accept it.
+    #[cfg(any(debug_assertions, feature = "test-hooks"))]
+    #[arg(long = "origin-override", value_name = "FROM=TO", value_parser = parse_origin_override, env = "APPLECAST_ORIGIN_OVERRIDES", value_delimiter = ',', global = true, hide = true)]
+    origin_overrides: Vec<(String, String)>,
+
+    /// Directory episode pages are cached in (default ~/.cache/applecast-cli)
+    #[arg(long, value_name = "DIR", global = true)]
+    cache_dir: Option<String>,
+
+    /// Always fetch pages over the network and don't cache them
+    #[arg(long, global = true, conflicts_with_all = ["cache_dir", "refresh"])]
+    no_cache: bool,
+
+    /// Refetch cached pages in full instead of revalidating them, then update the cache
+    #[arg(long, global = true)]
+    refresh: bool,
+
+    /// Render pages in headless Chromium when their static HTML lacks episode data
+    #[cfg(feature = "render")]
+    #[arg(long, global = true)]
+    render: bool,
 }
 
-/// Downloads a transcript file from a URL and saves it to disk
-fn download_transcript(url: &str, output_path: &str) -> Result<()> {
-    // Create output directory if it doesn't exist
-    if let Some(parent) = Path::new(output_path).parent() {
-        fs::create_dir_all(parent).context("Failed to create output directory")?;
+impl HttpArgs {
+    /// Converts the arguments into library request options
+    fn options(&self) -> HttpOptions {
+        let mut options = HttpOptions::default();
+        options.headers = self.headers.clone();
+        options.accept_language = self
+            .accept_language
+            .clone()
+            .or_else(|| accept_language_for(self.country.as_deref(), self.lang.as_deref()));
+        options.proxy = self.proxy.clone();
+        options.proxy_rules = self.proxy_rules.clone();
+        options.user_agent = self.user_agent.clone();
+        options.rate_limit_notice = Some(print_rate_limit_notice);
+        options.timeout = self.timeout;
+        options.connect_timeout = self.connect_timeout;
+        options.retries = self.retries;
+        options.retry_delay = self.retry_delay;
+        options.retry_notice = Some(print_retry_notice);
+        options.cache = self.cache();
+        #[cfg(any(debug_assertions, feature = "test-hooks"))]
+        {
+            options.origin_overrides = self.origin_overrides.clone();
+        }
+        #[cfg(feature = "render")]
+        {
+            options.render = self.render;
+        }
+        options
     }
 
-    // Create a client that follows redirects with a proper User-Agent
-    let client = reqwest::blocking::Client::builder()
-        .redirect(reqwest::redirect::Policy::limited(10))
-        .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36")
-        .build()
-        .context("Failed to create HTTP client")?;
-
-    // Fetch transcript content
-    let response = client
-        .get(url)
-        .send()
-        .context("Failed to fetch transcript URL")?;
-
-    // Check if response is successful
-    if !response.status().is_success() {
-        return Err(anyhow::anyhow!(
-            "HTTP request failed with status: {}",
-            response.status()
-        ));
-    }
+    /// The page cache to use, if caching is enabled and a cache directory is known
+    fn cache(&self) -> Option<PageCache> {
+        if self.no_cache {
+            return None;
+        }
 
-    // Get response body
-    let content = response
-        .text()
-        .context("Failed to read transcript response body")?;
+        let dir = self
+            .cache_dir
+            .as_ref()
+            .map(PathBuf::from)
+            .or_else(default_cache_dir)?;
+        Some(PageCache {
+            dir,
+            refresh: self.refresh,
+        })
+    }
+}
 
-    // Save to file
-    fs::write(output_path, content).context("Failed to write transcript file")?;
+/// Where artifacts are written, shared by every command
+#[derive(clap::Args, Debug)]
+struct OutputArgs {
+    /// Directory all artifacts are written under
+    #[arg(long, value_name = "DIR", default_value = "output", global = true)]
+    output_dir: String,
+
+    /// Per-episode directory under --output-dir, e.g. '{show}/{date}-{title}'
+    /// (placeholders: {show}, {title}, {date}, {id}, {storefront})
+    #[arg(long, value_name = "TEMPLATE", value_parser = parse_name_template, global = true)]
+    name_template: Option<String>,
+
+    /// Directory of '<episode-id>.toml' files whose fields replace extracted metadata
+    #[arg(long, value_name = "DIR", default_value = DEFAULT_OVERRIDES_DIR, global = true)]
+    overrides_dir: String,
+
+    /// How descriptions are stored in metadata.json: plain, markdown (keeps links) or html (as given)
+    #[arg(long, value_name = "FORMAT", default_value = "plain", value_parser = parse_description_format, global = true)]
+    description_format: DescriptionFormat,
+
+    /// Clean description HTML before it is stored: none, strip (tracking pixels, repeated
+    /// images and tracking parameters) or archive (also save images under <output-dir>/images)
+    #[arg(long, value_name = "POLICY", default_value = "none", value_parser = parse_sanitize_mode, global = true)]
+    sanitize_description: SanitizeMode,
+
+    /// Extra hosts whose description images are removed as trackers, e.g. 'stats.example.com'
+    #[arg(long, value_name = "HOSTS", value_delimiter = ',', global = true)]
+    tracker_host: Vec<String>,
+
+    /// Also save metadata in these formats: csv (rows appended to <output-dir>/metadata.csv),
+    /// yaml, toml or frontmatter-md; metadata.json is always written
+    #[arg(long = "metadata-format", value_name = "FORMATS", value_delimiter = ',', value_parser = parse_metadata_format, global = true)]
+    metadata_formats: Vec<MetadataFormat>,
+
+    /// Compress stored HTML snapshots and TTML transcripts: zstd (.zst) or gzip (.gz);
+    /// every command that reads them back decompresses them transparently
+    #[arg(long, value_name = "FORMAT", value_parser = parse_compression, global = true)]
+    compress: Option<Compression>,
+
+    /// Also keep every file show and batch runs save in an append-only store under
+    /// <output-dir>/backup, so incremental backups of it only ever upload new files
+    #[arg(long, global = true)]
+    backup_friendly: bool,
+
+    /// Record processed shows and episodes in this SQLite archive (created if missing);
+    /// a directory means its archive.sqlite. Repeat to query several archives as one:
+    /// list, grep, quote, link, pack and ask merge them, and new records go to the first
+    #[arg(long, visible_alias = "archive", value_name = "PATH", global = true)]
+    db: Vec<String>,
+
+    /// Keep --output-dir as a git repository and commit what each run saved, for history and diffs
+    #[arg(long, global = true)]
+    git_archive: bool,
+
+    /// Count which extraction strategies work on fetched pages in this file
+    /// [default: ~/.local/state/applecast-cli/strategy-stats.json]
+    #[arg(long, value_name = "PATH", env = "APPLECAST_STATS_FILE", global = true)]
+    stats_file: Option<String>,
+
+    /// Do not count extraction strategies in the local stats file
+    #[arg(long, env = "APPLECAST_NO_STATS", global = true)]
+    no_stats: bool,
+
+    /// Print the result as one JSON document on stdout; progress messages go to stderr
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Suppress progress messages; warnings and errors on stderr are still printed
+    #[arg(long, short, global = true)]
+    quiet: bool,
+
+    /// Log more detail: -v for HTTP statuses, extraction fallbacks and stage timings, -vv for trace
+    #[arg(long, short, action = clap::ArgAction::Count, global = true, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// Log format: human, or json for one JSON object per event on stderr
+    #[arg(long, value_name = "FORMAT", default_value = "human", value_parser = parse_log_format, global = true)]
+    log_format: LogFormat,
+}
 
-    Ok(())
+impl OutputArgs {
+    /// Converts the arguments into a library output layout
+    fn layout(&self) -> OutputLayout {
+        OutputLayout {
+            dir: self.output_dir.clone(),
+            template: self.name_template.clone(),
+            overrides_dir: self.overrides_dir.clone(),
+            description_format: self.description_format,
+            sanitize: SanitizePolicy {
+                mode: self.sanitize_description,
+                tracker_hosts: self.tracker_host.clone(),
+            },
+            metadata_formats: self.metadata_formats.clone(),
+            compression: self.compress,
+            backup_dir: self
+                .backup_friendly
+                .then(|| format!("{}/{}", self.output_dir, BACKUP_DIR)),
+        }
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Where `sync` and `watch` summarize the new episodes a run found
+#[derive(clap::Args, Debug)]
+struct DigestArgs {
+    /// Summarize new episodes: html writes <output-dir>/digest.html, email sends it using --smtp-config
+    #[arg(long, value_name = "KIND", value_parser = ["html", "email"])]
+    digest: Option<String>,
 
-    /// Unit test - Valid URL passes validation
-    #[test]
-    fn test_validate_url_accepts_valid_url() {
-        let url = "https://podcasts.apple.com/us/podcast/id840986946?i=1000631244436";
-        assert!(validate_url(url).is_ok());
-    }
+    /// TOML file with the SMTP server, credentials and recipients for --digest email
+    #[arg(long, value_name = "FILE", required_if_eq("digest", "email"))]
+    smtp_config: Option<String>,
+}
 
-    /// Unit test - Invalid URL fails validation
-    #[test]
-    fn test_validate_url_rejects_invalid_url() {
-        let url = "not-a-valid-url";
-        assert!(validate_url(url).is_err());
+impl DigestArgs {
+    /// The digest to produce, exiting with status 2 when the SMTP config is unusable
+    fn digest(&self) -> Option<Digest> {
+        match (self.digest.as_deref()?, &self.smtp_config) {
+            ("email", Some(path)) => match SmtpConfig::load(path) {
+                Ok(config) => Some(Digest::Email(config)),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(2);
+                }
+            },
+            _ => Some(Digest::Html),
+        }
     }
+}
 
-    /// Unit test - HTTP URLs are valid
-    #[test]
-    fn test_validate_url_accepts_http() {
-        let url = "http://example.com";
-        assert!(validate_url(url).is_ok());
-    }
+/// Time limits that stop one slow episode from stalling a run
+#[derive(clap::Args, Debug)]
+struct BudgetArgs {
+    /// Fail an episode whose page takes longer than this to fetch, e.g. '2m'
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, global = true)]
+    fetch_timeout: Option<Duration>,
 
-    /// Unit test - Empty string fails validation
-    #[test]
-    fn test_validate_url_rejects_empty_string() {
-        let url = "";
-        assert!(validate_url(url).is_err());
-    }
+    /// Fail an episode whose metadata takes longer than this to extract
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, global = true)]
+    parse_timeout: Option<Duration>,
 
-    /// Unit test - Error message includes the invalid URL
-    #[test]
-    fn test_validate_url_error_includes_url() {
-        let url = "not-valid";
-        let result = validate_url(url);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("not-valid"));
-    }
+    /// Give up on a transcript that takes longer than this to download
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, global = true)]
+    transcript_timeout: Option<Duration>,
 
-    /// Unit test - Fetch HTML creates output file with content
-    #[test]
-    fn test_fetch_html_creates_file_with_content() {
-        use std::fs;
-        use tempfile::TempDir;
+    /// Give up on an audio file that takes longer than this to download
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, global = true)]
+    audio_timeout: Option<Duration>,
 
-        // Given a temporary directory
-        let temp_dir = TempDir::new().unwrap();
-        let output_path = temp_dir.path().join("test_output.html");
-        let output_path_str = output_path.to_str().unwrap();
+    /// Stop the whole run after this long, e.g. '30m'; unfinished episodes are reported as failed
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, global = true)]
+    deadline: Option<Duration>,
+}
 
-        // When we fetch from a test URL (httpbin.org returns HTML)
-        let result = fetch_html("https://httpbin.org/html", output_path_str);
+impl BudgetArgs {
+    /// Converts the arguments into library budgets, starting the deadline clock now
+    fn budgets(&self) -> Budgets {
+        Budgets {
+            fetch: self.fetch_timeout,
+            parse: self.parse_timeout,
+            transcript: self.transcript_timeout,
+            audio: self.audio_timeout,
+            deadline: self.deadline.map(|deadline| Instant::now() + deadline),
+        }
+    }
+}
 
-        // Then the fetch succeeds
-        assert!(result.is_ok(), "fetch_html should succeed");
+/// Where flag defaults are read from; flags and environment variables override them
+#[derive(clap::Args, Debug)]
+struct ConfigArgs {
+    /// Config file with flag defaults [default: ~/.config/applecast-cli/config.toml]
+    #[arg(long, value_name = "PATH", env = "APPLECAST_CONFIG", global = true)]
+    config: Option<PathBuf>,
 
-        // And the file exists
-        assert!(output_path.exists(), "Output file should exist");
+    /// Profile in the config file whose settings override its top-level ones
+    #[arg(long, value_name = "NAME", env = "APPLECAST_PROFILE", global = true)]
+    profile: Option<String>,
+}
 
-        // And the file contains HTML content
-        let content = fs::read_to_string(&output_path).unwrap();
-        assert!(content.contains("html"), "File should contain HTML content");
-        assert!(!content.is_empty(), "File should not be empty");
+impl ConfigArgs {
+    /// The config file to load: `--config`, else the default one when it exists
+    fn file(&self) -> Option<PathBuf> {
+        match &self.config {
+            Some(path) => Some(path.clone()),
+            None => default_config_path().filter(|path| path.exists()),
+        }
     }
+}
 
-    /// Unit test - Fetch HTML creates output directory if missing
-    #[test]
-    fn test_fetch_html_creates_directory() {
-        use tempfile::TempDir;
+/// Subcommands that run a single step instead of the all-in-one pipeline
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Fetch an episode page and save its HTML
+    Fetch {
+        /// Apple Podcasts episode URL
+        url: String,
+    },
+    /// Extract metadata from an episode URL or an already-downloaded HTML file
+    Metadata {
+        /// Apple Podcasts episode URL or path to a saved episode page
+        #[arg(
+            value_name = "URL|FILE",
+            required_unless_present = "from_html",
+            conflicts_with = "from_html"
+        )]
+        source: Option<String>,
+
+        /// Also capture titles/descriptions from these storefronts, e.g. 'us,fr,jp'
+        #[arg(long, value_name = "CODES", value_delimiter = ',', value_parser = parse_storefront)]
+        locales: Vec<String>,
+
+        #[command(flatten)]
+        from: SourceArgs,
+
+        /// Extract offline from a saved episode page, or every .html page under a directory
+        #[arg(long, value_name = "FILE|DIR", conflicts_with_all = ["locales", "metadata_source"])]
+        from_html: Option<String>,
+
+        /// With --from-html: the page's transcript (default: transcript.ttml beside each page)
+        #[arg(long, value_name = "FILE", requires = "from_html")]
+        transcript_file: Option<String>,
+
+        /// With --from-html: don't read transcripts
+        #[arg(long, requires = "from_html", conflicts_with = "transcript_file")]
+        no_transcript: bool,
+
+        #[command(flatten)]
+        transcript: TranscriptArgs,
+    },
+    /// Download an episode's transcript
+    Transcript {
+        /// Apple Podcasts episode URL
+        url: String,
+
+        #[command(flatten)]
+        transcript: TranscriptArgs,
+    },
+    /// Download an episode's audio file, resuming an interrupted download
+    Audio {
+        /// Apple Podcasts episode URL
+        url: String,
+
+        /// Write the episode's metadata, artwork and show-note chapters into the audio file
+        #[arg(long)]
+        tag_audio: bool,
+    },
+    /// Align an episode's transcript against the auto-captions of its YouTube version and save the offset
+    Compare {
+        /// Apple Podcasts episode URL
+        url: String,
+
+        /// The same episode on YouTube
+        #[arg(long, value_name = "URL", value_parser = parse_video_id)]
+        youtube: String,
+    },
+    /// Process every episode of a podcast show
+    Show {
+        /// Apple Podcasts show URL
+        url: String,
+
+        #[command(flatten)]
+        transcript: TranscriptArgs,
+
+        #[command(flatten)]
+        crawl: CrawlArgs,
+    },
+    /// Process only the episodes of a show that the archive does not have yet (needs --db)
+    Sync {
+        /// Apple Podcasts show URL
+        url: String,
+
+        /// After syncing, print only the newly archived episodes on stdout, one JSON object per line (ndjson)
+        #[arg(long, value_name = "FORMAT", value_parser = parse_print_format, conflicts_with = "json")]
+        print_new: Option<PrintFormat>,
+
+        #[command(flatten)]
+        digest: DigestArgs,
+
+        #[command(flatten)]
+        transcript: TranscriptArgs,
+
+        #[command(flatten)]
+        crawl: CrawlArgs,
+    },
+    /// Re-check a show periodically and process each newly published episode
+    Watch {
+        /// Apple Podcasts show URL
+        url: String,
+
+        /// Time between checks: seconds, or a number with s, m, h or d, e.g. '1h'
+        #[arg(long, value_name = "DURATION", default_value = "1h", value_parser = parse_interval)]
+        interval: Duration,
+
+        /// POST each new episode's JSON to this URL
+        #[arg(long, value_name = "URL")]
+        notify_url: Option<String>,
+
+        /// Run this shell command for each new episode, with its JSON on stdin
+        #[arg(long, value_name = "COMMAND")]
+        exec: Option<String>,
+
+        /// Check once and exit instead of watching, e.g. when run from cron
+        #[arg(long)]
+        once: bool,
+
+        #[command(flatten)]
+        digest: DigestArgs,
+
+        #[command(flatten)]
+        transcript: TranscriptArgs,
+    },
+    /// List the episodes stored in the archive, newest first, or manage saved smart lists (needs --db)
+    #[command(alias = "query", args_conflicts_with_subcommands = true)]
+    List {
+        #[command(subcommand)]
+        action: Option<ListAction>,
+
+        /// Only episodes of this show (Apple show ID or URL)
+        #[arg(long, value_name = "ID|URL")]
+        show: Option<String>,
+
+        /// List at most this many episodes
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+
+        /// Also write each listed episode as a static-site page: hugo or jekyll
+        #[arg(long, value_name = "FORMAT", value_parser = parse_site_format)]
+        export: Option<SiteFormat>,
+
+        /// Hugo section or Jekyll collection the pages are written to
+        #[arg(long, value_name = "NAME", default_value = "podcasts", value_parser = parse_section, requires = "export")]
+        section: String,
+
+        /// Site directory the pages are written under [default: <output-dir>/site]
+        #[arg(long, value_name = "DIR", requires = "export")]
+        export_dir: Option<String>,
+
+        /// Leave out of exported pages the lines a show repeats in most of its descriptions
+        #[arg(long, requires = "export")]
+        strip_boilerplate: bool,
+
+        /// Only episodes `sync` found removed from Apple's listing
+        #[arg(long)]
+        removed: bool,
+
+        /// Only episodes you tagged with this tag
+        #[arg(long, value_name = "TAG", value_parser = parse_tag)]
+        tag: Option<String>,
+
+        /// Only episodes you rated at least this many stars
+        #[arg(long, value_name = "STARS", value_parser = clap::value_parser!(u8).range(1..=5))]
+        min_rating: Option<u8>,
+
+        /// Only episodes you marked played
+        #[arg(long, conflicts_with = "unplayed")]
+        played: bool,
+
+        /// Only episodes you have not marked played
+        #[arg(long)]
+        unplayed: bool,
+    },
+    /// Add or remove your own tags on an archived episode (needs --db)
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
+    },
+    /// Record what you have listened to: mark archived episodes played or unplayed, or import an app's history (needs --db)
+    Mark {
+        #[command(subcommand)]
+        action: MarkAction,
+    },
+    /// Rate an archived episode from 1 to 5 stars, or 0 to clear its rating (needs --db)
+    Rate {
+        /// An Apple episode ID, a short ID such as e1a2b3, or an archived episode's URL
+        #[arg(value_name = "ID|SHORT_ID|URL")]
+        episode: String,
+
+        /// Stars, 1 to 5, or 0 to clear the rating
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=5))]
+        stars: u8,
+    },
+    /// Resolve an archived episode's short ID to its URL, or print the short ID of an episode URL (needs --db)
+    Link {
+        /// A short ID such as e1a2b3, or an archived episode's URL
+        #[arg(value_name = "SHORT_ID|URL")]
+        target: String,
+    },
+    /// Re-fetch selected metadata fields of archived episodes and merge them into the saved metadata (needs --db)
+    Refresh {
+        /// Fields to re-fetch, comma-separated, e.g. description,artwork_url
+        #[arg(long, value_name = "FIELDS", required = true, value_delimiter = ',', value_parser = parse_refresh_field)]
+        fields: Vec<String>,
+
+        /// Which value wins when a field already has one: prefer-newer or prefer-existing
+        #[arg(long, value_name = "STRATEGY", default_value = "prefer-newer", value_parser = parse_strategy)]
+        strategy: MergeStrategy,
+
+        /// Only refresh episodes of this show (Apple show ID or URL)
+        #[arg(long, value_name = "ID|URL")]
+        show: Option<String>,
+
+        /// Refresh at most this many episodes, newest first
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+    },
+    /// Search archived transcripts for a phrase, printing matches with timestamps and context (needs --db)
+    Grep {
+        /// Words to find, matched as a phrase ignoring case and punctuation
+        phrase: String,
+
+        /// Only search episodes of this show (Apple show ID or URL)
+        #[arg(long, value_name = "ID|URL")]
+        show: Option<String>,
+
+        /// Print at most this many matches
+        #[arg(long, value_name = "N", default_value_t = DEFAULT_GREP_LIMIT)]
+        limit: usize,
+    },
+    /// Print a quotable sentence from a random archived transcript, with its citation (needs --db)
+    Quote {
+        /// Only quote episodes of this show (Apple show ID or URL)
+        #[arg(long, value_name = "ID|URL")]
+        show: Option<String>,
+    },
+    /// Assemble an archived episode's metadata, chapters and transcript into one file sized for an LLM (needs --db)
+    Pack {
+        /// An Apple episode ID, a short ID such as e1a2b3, or an archived episode's URL
+        #[arg(value_name = "ID|SHORT_ID|URL")]
+        episode: String,
+
+        /// Token budget the pack must fit, e.g. 80000 or 80k; the transcript is cut to fit
+        #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_TOKENS, value_parser = parse_max_tokens)]
+        max_tokens: usize,
+
+        /// Pack format: markdown or json
+        #[arg(long, value_name = "FORMAT", default_value = "markdown", value_parser = parse_pack_format)]
+        format: PackFormat,
+
+        /// Split the transcript into chunks of this length, each headed by its time range, e.g. '5m'
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        chunk: Option<Duration>,
+
+        /// Write the pack here, or '-' for stdout [default: context-pack.md or .json in the episode's directory]
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<String>,
+    },
+    /// Answer a question about an archived episode from its most relevant transcript passages (needs --db)
+    Ask {
+        /// An Apple episode ID, a short ID such as e1a2b3, or an archived episode's URL
+        #[arg(value_name = "ID|SHORT_ID|URL")]
+        episode: String,
+
+        /// The question, e.g. "what did they say about pricing?"
+        question: String,
+
+        /// Retrieve at most this many passages
+        #[arg(long, value_name = "N", default_value_t = DEFAULT_TOP_PASSAGES)]
+        top: usize,
+
+        /// Length of the transcript passages searched, e.g. '90s'
+        #[arg(long, value_name = "DURATION", default_value = "1m", value_parser = parse_duration)]
+        passage: Duration,
+
+        /// Have this shell command answer from the passages, with the prompt on stdin, e.g. 'llm' or 'ollama run llama3'
+        #[arg(long, value_name = "COMMAND", env = "APPLECAST_LLM_COMMAND")]
+        llm_command: Option<String>,
+    },
+    /// Maintain the archive as a whole: merge another archive into it, or export and import bundles (needs --db)
+    Archive {
+        #[command(subcommand)]
+        action: ArchiveAction,
+    },
+    /// Process many episode or show URLs listed in a file, several at a time
+    Batch {
+        /// File with one URL per line ('#' starts a comment), or '-' for stdin
+        #[arg(long, short, value_name = "FILE")]
+        input: String,
+
+        /// How many episodes to process at once
+        #[arg(long, value_name = "N", default_value_t = DEFAULT_CONCURRENCY, value_parser = parse_concurrency)]
+        concurrency: usize,
+
+        /// Format of the final report saved in the output directory
+        #[arg(long, value_name = "FORMAT", default_value = "json", value_parser = ["json", "csv"])]
+        report_format: String,
+
+        #[command(flatten)]
+        transcript: TranscriptArgs,
+
+        #[command(flatten)]
+        crawl: CrawlArgs,
+    },
+    /// Resolve show URLs to their original RSS feeds
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Feed {
+        #[command(subcommand)]
+        action: Option<FeedAction>,
+
+        /// Apple Podcasts show URLs
+        #[arg(value_name = "URL", required = true)]
+        urls: Vec<String>,
+
+        /// Also download each feed, pretty-printed, into <output-dir>/feeds/
+        #[arg(long)]
+        download: bool,
+
+        /// Write the resolved feeds to an OPML file for importing into podcast clients
+        #[arg(long, value_name = "FILE")]
+        opml: Option<String>,
+    },
+    /// Look up a show or episode in the iTunes Lookup API by Apple ID or URL
+    Lookup {
+        /// Apple show or episode ID, or an Apple Podcasts show or episode URL
+        #[arg(value_name = "ID|URL")]
+        id: String,
+
+        /// Storefront to look up in (default: --country, else the URL's storefront, else 'us')
+        #[arg(long, value_name = "CODE", value_parser = parse_storefront)]
+        storefront: Option<String>,
+    },
+    /// Search the iTunes catalog for shows by name
+    Search {
+        /// Show name or keywords
+        term: String,
+
+        /// Storefront to search (default: --country, else 'us')
+        #[arg(long, value_name = "CODE", value_parser = parse_storefront)]
+        storefront: Option<String>,
+
+        /// Maximum number of shows to list
+        #[arg(long, value_name = "N", default_value_t = DEFAULT_SEARCH_LIMIT)]
+        limit: usize,
+    },
+    /// Reports on a show's catalog or on what the archive recorded
+    Report {
+        #[command(subcommand)]
+        kind: ReportKind,
+    },
+    /// Quick single-shot health probe for container HEALTHCHECKs (exit status 0 when healthy)
+    Healthcheck,
+    /// Diagnose common setup problems
+    Doctor {
+        #[command(subcommand)]
+        check: DoctorCheck,
+    },
+    /// Print a shell completion script: bash, zsh, fish, elvish or powershell
+    Completions {
+        /// Shell to complete for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Write a commented config file, asking for the settings most people change first
+    Init {
+        /// Take every default without asking
+        #[arg(long, short = 'y')]
+        yes: bool,
+
+        /// Overwrite an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Tools for maintaining the page extractors
+    Devtools {
+        #[command(subcommand)]
+        tool: DevTool,
+    },
+}
 
-        // Given a temporary directory with a nested path that doesn't exist
-        let temp_dir = TempDir::new().unwrap();
-        let output_path = temp_dir.path().join("nested").join("output.html");
-        let output_path_str = output_path.to_str().unwrap();
+/// Tools available under `applecast-cli devtools`
+#[derive(Subcommand, Debug)]
+enum DevTool {
+    /// Run extraction on two saved page snapshots and show what changed
+    DiffExtract {
+        /// The older snapshot of the page
+        old: String,
+        /// The newer snapshot of the page
+        new: String,
+    },
+    /// Report how often each extraction strategy succeeds over a corpus of saved pages
+    Coverage {
+        /// Directory of saved episode pages (`*.html`)
+        #[arg(default_value = FIXTURE_PAGES_DIR)]
+        dir: String,
+    },
+    /// Show how often each extraction strategy worked on pages this machine fetched, recently and before
+    StrategyStats,
+}
 
-        // When we fetch HTML
-        let result = fetch_html("https://httpbin.org/html", output_path_str);
+/// Actions available under `applecast-cli feed`
+#[derive(Subcommand, Debug)]
+enum FeedAction {
+    /// Write the most recently archived episodes as an RSS feed, to follow the archiver in a feed reader
+    NewItems {
+        /// Where to write the feed, e.g. new.xml
+        #[arg(long, value_name = "FILE")]
+        out: String,
+
+        /// How many of the latest archived episodes to include
+        #[arg(long, value_name = "N", default_value_t = DEFAULT_NEW_ITEMS)]
+        limit: usize,
+    },
+}
 
-        // Then the fetch succeeds
-        assert!(
-            result.is_ok(),
-            "fetch_html should create nested directories"
-        );
+/// Actions available under `applecast-cli list`
+#[derive(Subcommand, Debug)]
+enum ListAction {
+    /// Save a named condition, re-evaluated against the archive each time the list is shown
+    Create {
+        /// The list's name: letters, digits, - and _
+        #[arg(value_name = "NAME", value_parser = parse_list_name)]
+        name: String,
+
+        /// Which episodes belong, e.g. 'duration_seconds>3600 && !played'
+        #[arg(long = "where", value_name = "EXPR", value_parser = parse_condition)]
+        condition: String,
+    },
+    /// Print the episodes a smart list matches now, or every saved list when no name is given
+    Show {
+        #[arg(value_name = "NAME")]
+        name: Option<String>,
+    },
+    /// Write the episodes a smart list matches now as an M3U playlist
+    Export {
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Where to write the playlist, e.g. long-unplayed.m3u
+        #[arg(long, value_name = "FILE")]
+        out: String,
+    },
+    /// Delete a saved smart list
+    Delete {
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+}
 
-        // And the nested directory was created
-        assert!(
-            output_path.parent().unwrap().exists(),
-            "Parent directory should be created"
-        );
+/// Actions available under `applecast-cli tag`
+#[derive(Subcommand, Debug)]
+enum TagAction {
+    /// Add comma-separated tags to an episode, e.g. rust,interview
+    Add {
+        /// An Apple episode ID, a short ID such as e1a2b3, or an archived episode's URL
+        #[arg(value_name = "ID|SHORT_ID|URL")]
+        episode: String,
+
+        /// Tags to add, comma-separated
+        #[arg(value_name = "TAGS", required = true, value_delimiter = ',', value_parser = parse_tag)]
+        tags: Vec<String>,
+    },
+    /// Remove comma-separated tags from an episode
+    Remove {
+        /// An Apple episode ID, a short ID such as e1a2b3, or an archived episode's URL
+        #[arg(value_name = "ID|SHORT_ID|URL")]
+        episode: String,
+
+        /// Tags to remove, comma-separated
+        #[arg(value_name = "TAGS", required = true, value_delimiter = ',', value_parser = parse_tag)]
+        tags: Vec<String>,
+    },
+}
 
-        // And the file exists
-        assert!(
-            output_path.exists(),
-            "Output file should exist in nested directory"
-        );
-    }
+/// Actions available under `applecast-cli mark`
+#[derive(Subcommand, Debug)]
+enum MarkAction {
+    /// Mark episodes played
+    Played {
+        /// Apple episode IDs, short IDs such as e1a2b3, or archived episodes' URLs
+        #[arg(value_name = "ID|SHORT_ID|URL", required = true)]
+        episodes: Vec<String>,
+    },
+    /// Mark episodes unplayed, clearing any listening position
+    Unplayed {
+        /// Apple episode IDs, short IDs such as e1a2b3, or archived episodes' URLs
+        #[arg(value_name = "ID|SHORT_ID|URL", required = true)]
+        episodes: Vec<String>,
+    },
+    /// Mark episodes played or in progress from an Overcast OPML export or an Apple Podcasts MTLibrary.sqlite
+    Import {
+        /// The export file
+        #[arg(value_name = "FILE")]
+        file: String,
+    },
+}
 
-    /// Unit test - Fetch HTML handles invalid URL gracefully
-    #[test]
-    fn test_fetch_html_handles_request_error() {
-        use tempfile::TempDir;
+/// Actions available under `applecast-cli archive`
+#[derive(Subcommand, Debug)]
+enum ArchiveAction {
+    /// Import another archive's shows, episodes and files, keeping the later copy of an episode saved in both
+    Merge {
+        /// The other archive: its database file, or the directory holding its archive.sqlite
+        #[arg(value_name = "PATH")]
+        other: String,
+    },
+    /// Bundle the database and saved files into a portable .tar.zst snapshot
+    Export {
+        /// Where to write the bundle, e.g. snapshot.tar.zst
+        #[arg(long, value_name = "FILE")]
+        out: String,
+
+        /// Only export episodes of this show (Apple show ID or URL)
+        #[arg(long, value_name = "ID|URL")]
+        show: Option<String>,
+
+        /// Only export episodes published on or after this date (YYYY-MM-DD)
+        #[arg(long, value_name = "DATE", value_parser = parse_since)]
+        since: Option<String>,
+    },
+    /// Unpack an `archive export` bundle and merge it in, as `archive merge` would
+    Import {
+        /// The .tar.zst bundle written by `archive export`
+        #[arg(value_name = "FILE")]
+        bundle: String,
+    },
+}
 
-        // Given a temporary directory and an invalid URL
-        let temp_dir = TempDir::new().unwrap();
-        let output_path = temp_dir.path().join("output.html");
-        let output_path_str = output_path.to_str().unwrap();
+/// Reports available under `applecast-cli report`
+#[derive(Subcommand, Debug)]
+enum ReportKind {
+    /// List episodes in the RSS feed that Apple does not list, and the other way round (exit status 1 when any differ)
+    Gaps {
+        /// Apple show ID or Apple Podcasts URL
+        #[arg(long, value_name = "ID|URL", value_parser = parse_show)]
+        show: String,
+    },
+    /// Summarize upstream changes found to archived episodes: removals, edited titles and descriptions, replaced transcripts (needs --db)
+    Changes {
+        /// Only changes found on or after this date (YYYY-MM-DD) or this many days ago, e.g. 30d
+        #[arg(long, value_name = "DATE|DAYS", default_value = "30d", value_parser = parse_since_or_age)]
+        since: String,
+
+        /// Also draw the number of changes found per day as an SVG chart, e.g. changes.svg
+        #[arg(long, value_name = "FILE", value_parser = parse_chart_path)]
+        chart: Option<String>,
+    },
+    /// Chart a show's archived episode durations and transcript word counts over time, flagging outliers (needs --db)
+    Durations {
+        /// Apple show ID or Apple Podcasts URL
+        #[arg(long, value_name = "ID|URL", value_parser = parse_show)]
+        show: String,
+
+        /// Also draw the durations over time as an SVG chart, e.g. durations.svg
+        #[arg(long, value_name = "FILE", value_parser = parse_chart_path)]
+        chart: Option<String>,
+    },
+    /// List the sponsors and promo codes read out in a show's archived episodes, over time (needs --db)
+    Sponsors {
+        /// Apple show ID or Apple Podcasts URL
+        #[arg(long, value_name = "ID|URL", value_parser = parse_show)]
+        show: String,
+    },
+}
 
-        // When we try to fetch from an invalid domain
-        let result = fetch_html(
-            "https://thisisnotavaliddomainforsurehopefully123456789.com",
-            output_path_str,
-        );
+/// Checks available under `applecast-cli doctor`
+#[derive(Subcommand, Debug)]
+enum DoctorCheck {
+    /// Check DNS, TCP, TLS and HTTP reachability of the Apple endpoints the tool uses
+    Network,
+}
 
-        // Then the fetch fails
-        assert!(
-            result.is_err(),
-            "fetch_html should fail for invalid domains"
-        );
+/// Shows listed by `search` unless `--limit` is given
+const DEFAULT_SEARCH_LIMIT: usize = 10;
 
-        // And the error message is descriptive
-        let error = result.unwrap_err();
-        assert!(
-            error.contains("Failed to fetch URL"),
-            "Error should mention fetch failure"
-        );
-    }
+/// Page snapshots `devtools coverage` reads by default
+const FIXTURE_PAGES_DIR: &str = "tests/fixtures/pages";
 
-    /// Unit test - Fetch HTML handles HTTP error status codes
-    #[test]
-    fn test_fetch_html_handles_http_error_status() {
-        use tempfile::TempDir;
+/// Matches printed by `grep` unless `--limit` is given
+const DEFAULT_GREP_LIMIT: usize = 50;
 
-        // Given a temporary directory
-        let temp_dir = TempDir::new().unwrap();
-        let output_path = temp_dir.path().join("output.html");
-        let output_path_str = output_path.to_str().unwrap();
+/// Episodes `feed new-items` includes unless `--limit` is given
+const DEFAULT_NEW_ITEMS: usize = 20;
 
-        // When we try to fetch a URL that returns 404
-        let result = fetch_html("https://httpbin.org/status/404", output_path_str);
+/// Exit statuses for scripts; clap uses 2 for usage errors
+const EXIT_FAILURE: i32 = 1;
+const EXIT_NETWORK: i32 = 3;
+const EXIT_PARSE: i32 = 4;
+const EXIT_NO_TRANSCRIPT: i32 = 5;
 
-        // Then the fetch fails
-        assert!(
-            result.is_err(),
-            "fetch_html should fail for HTTP error codes"
-        );
+/// Line formats `sync --print-new` can print episodes in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PrintFormat {
+    /// One JSON object per line
+    Ndjson,
+}
 
-        // And the error message mentions the status
-        let error = result.unwrap_err();
-        assert!(
-            error.contains("404"),
-            "Error should mention the HTTP status code"
-        );
+/// Parses a `--print-new` value
+fn parse_print_format(raw: &str) -> Result<PrintFormat, String> {
+    match raw.trim().to_lowercase().as_str() {
+        "ndjson" | "jsonl" => Ok(PrintFormat::Ndjson),
+        _ => Err(format!("Invalid print format '{}': expected ndjson", raw)),
     }
+}
 
-    /// Unit test - Metadata struct serializes to JSON correctly
-    #[test]
-    fn test_metadata_serialization() {
-        // Given a Metadata struct with sample data
-        let metadata = Metadata {
-            episode_title: "Test Episode".to_string(),
-            description: "This is a test description".to_string(),
-            show_title: "Test Show".to_string(),
-            publish_date: "2023-10-13".to_string(),
-        };
+/// How log events are written to the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogFormat {
+    /// Progress lines as before; warnings and errors marked with an emoji
+    Human,
+    /// One JSON object per event on stderr
+    Json,
+}
 
-        // When we serialize it to JSON
-        let json = serde_json::to_string(&metadata).unwrap();
-
-        // Then it contains all the expected fields
-        assert!(json.contains("episode_title"));
-        assert!(json.contains("Test Episode"));
-        assert!(json.contains("description"));
-        assert!(json.contains("This is a test description"));
-        assert!(json.contains("show_title"));
-        assert!(json.contains("Test Show"));
-        assert!(json.contains("publish_date"));
-        assert!(json.contains("2023-10-13"));
+/// Parses a `--log-format` value
+fn parse_log_format(raw: &str) -> Result<LogFormat, String> {
+    match raw.trim().to_lowercase().as_str() {
+        "human" => Ok(LogFormat::Human),
+        "json" => Ok(LogFormat::Json),
+        _ => Err(format!(
+            "Invalid log format '{}': expected human or json",
+            raw
+        )),
     }
+}
 
-    /// Unit test - clean_text removes HTML tags and trims whitespace
-    #[test]
-    fn test_clean_text_removes_html_tags() {
-        // Given text with HTML tags
-        let text = "<p>Hello <strong>World</strong></p>";
-
-        // When we clean it
-        let cleaned = clean_text(text);
+/// The human log format: info events print just their message, warnings and
+/// errors get an emoji, and `-v` detail uses the standard timestamped format
+///
+/// Structured fields on those events, such as a `rate_limited` event's host
+/// and resume time, are left to `--log-format json`.
+#[derive(Default)]
+struct HumanFormat {
+    detail: tracing_subscriber::fmt::format::Format,
+}
 
-        // Then HTML tags are removed
-        assert_eq!(cleaned, "Hello World");
+impl<S, N> FormatEvent<S, N> for HumanFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        let marker = match *event.metadata().level() {
+            Level::ERROR => "❌ ",
+            Level::WARN => "⚠️ ",
+            Level::INFO => "",
+            _ => return self.detail.format_event(ctx, writer, event),
+        };
+        let mut message = MessageVisitor::default();
+        event.record(&mut message);
+        writeln!(writer, "{}{}", marker, message.0)
     }
+}
 
-    /// Unit test - clean_text normalizes whitespace
-    #[test]
-    fn test_clean_text_normalizes_whitespace() {
-        // Given text with extra whitespace
-        let text = "  Hello    World  \n  Test  ";
-
-        // When we clean it
-        let cleaned = clean_text(text);
+/// Collects an event's message, ignoring its other fields
+#[derive(Default)]
+struct MessageVisitor(String);
 
-        // Then whitespace is normalized
-        assert_eq!(cleaned, "Hello World Test");
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+        }
     }
+}
 
-    /// Unit test - save_metadata_json creates valid JSON file
-    #[test]
-    fn test_save_metadata_json_creates_file() {
-        use tempfile::TempDir;
-
-        // Given a metadata struct and a temporary directory
-        let metadata = Metadata {
-            episode_title: "Test Episode".to_string(),
-            description: "Test description".to_string(),
-            show_title: "Test Show".to_string(),
-            publish_date: "2023-10-13".to_string(),
-        };
-
-        let temp_dir = TempDir::new().unwrap();
-        let json_path = temp_dir.path().join("metadata.json");
-        let json_path_str = json_path.to_str().unwrap();
-
-        // When we save it
-        let result = save_metadata_json(&metadata, json_path_str);
-
-        // Then it succeeds
-        assert!(result.is_ok(), "save_metadata_json should succeed");
-
-        // And the file exists
-        assert!(json_path.exists(), "JSON file should exist");
-
-        // And the file contains valid JSON
-        let content = fs::read_to_string(&json_path).unwrap();
-        let parsed: serde_json::Value = serde_json::from_str(&content).unwrap();
-
-        // And it has all the expected fields
-        assert_eq!(parsed["episode_title"], "Test Episode");
-        assert_eq!(parsed["description"], "Test description");
-        assert_eq!(parsed["show_title"], "Test Show");
-        assert_eq!(parsed["publish_date"], "2023-10-13");
+/// Installs the log subscriber for `-v`, `--quiet` and `--log-format`
+///
+/// Progress goes to stdout in the human format, or to stderr when
+/// `stdout_reserved` (e.g. with `--json`) so stdout carries only the result;
+/// everything else goes to stderr. Each pipeline stage logs its duration at
+/// debug level.
+fn init_logging(output: &OutputArgs, stdout_reserved: bool) {
+    let level = match (output.quiet, output.verbose) {
+        (true, _) => LevelFilter::WARN,
+        (false, 0) => LevelFilter::INFO,
+        (false, 1) => LevelFilter::DEBUG,
+        (false, _) => LevelFilter::TRACE,
+    };
+    // Dependencies only report their own warnings, however verbose the tool is
+    let filter = Targets::new()
+        .with_target("applecast", level)
+        .with_target("applecast_cli", level)
+        .with_default(LevelFilter::WARN);
+    let registry = tracing_subscriber::registry().with(filter);
+
+    match output.log_format {
+        LogFormat::Json => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_span_events(FmtSpan::CLOSE)
+                    .with_writer(io::stderr),
+            )
+            .init(),
+        LogFormat::Human => {
+            let writer = if stdout_reserved {
+                BoxMakeWriter::new(io::stderr)
+            } else {
+                BoxMakeWriter::new(
+                    io::stderr
+                        .with_max_level(Level::WARN)
+                        .or_else(io::stdout.with_max_level(Level::INFO).or_else(io::stderr)),
+                )
+            };
+            registry
+                .with(
+                    tracing_subscriber::fmt::layer()
+                        .with_span_events(FmtSpan::CLOSE)
+                        .event_format(HumanFormat::default())
+                        .with_ansi(io::stderr().is_terminal())
+                        .with_writer(writer),
+                )
+                .init()
+        }
     }
+}
 
-    /// Unit test - extract_metadata extracts from real Apple Podcasts HTML
-    #[test]
-    fn test_extract_metadata_from_real_html() {
-        // Given the actual episode.html file exists
-        let html_path = "output/episode.html";
+#[tokio::main]
+async fn main() {
+    let (mut args, webhooks, transforms) = parse_args();
+    normalize_urls(&mut args);
+    // Progress must not mix with the NDJSON lines of `sync --print-new`
+    let stdout_reserved = args.output.json
+        || matches!(
+            args.command,
+            Some(Command::Sync {
+                print_new: Some(_),
+                ..
+            })
+        );
+    init_logging(&args.output, stdout_reserved);
 
-        // Skip test if file doesn't exist (for CI/CD environments)
-        if !Path::new(html_path).exists() {
-            return;
+    let mut context = Context::new(args.http.options(), args.output.layout());
+    context.console = Console {
+        json: args.output.json,
+        quiet: args.output.quiet,
+    };
+    context.budgets = args.budgets.budgets();
+    context.git_archive = args.output.git_archive;
+    context.stats_file = if args.output.no_stats {
+        None
+    } else {
+        args.output
+            .stats_file
+            .clone()
+            .or_else(|| default_stats_path().map(|path| path.to_string_lossy().into_owned()))
+    };
+    context.extraction = if args.profile_lite {
+        Extraction::Lite
+    } else {
+        Extraction::Full
+    };
+    context.webhooks = webhooks;
+    context.transforms = transforms;
+    let context = &context;
+
+    // `healthcheck` checks the archives itself rather than failing to open them
+    let healthcheck = matches!(args.command, Some(Command::Healthcheck));
+    let archives = (!args.output.db.is_empty() && !healthcheck)
+        .then(|| or_exit(ArchiveSet::open(&args.output.db), "Error opening archive"));
+    let archives = archives.as_ref();
+    let archive = archives.map(ArchiveSet::primary);
+
+    let outcome = match args.command {
+        Some(Command::Fetch { url }) => run_fetch(context, &url).await,
+        Some(Command::Metadata {
+            source,
+            locales,
+            from,
+            from_html,
+            transcript_file,
+            no_transcript,
+            transcript,
+        }) => match from_html {
+            Some(path) => {
+                if transcript_file.is_some() && Path::new(&path).is_dir() {
+                    eprintln!(
+                        "Error: --transcript-file needs a single saved page, not a directory"
+                    );
+                    process::exit(2);
+                }
+                let local = (!no_transcript).then_some(transcript_file.as_deref());
+                run_offline_metadata(context, &path, local, &transcript.formats())
+            }
+            // clap requires the source whenever --from-html is absent
+            None => {
+                let source = source.unwrap_or_default();
+                run_metadata(context, &source, &locales, from.metadata_source).await
+            }
+        },
+        Some(Command::Transcript { url, transcript }) => {
+            run_transcript(context, &url, &transcript.formats()).await
         }
+        Some(Command::Audio { url, tag_audio }) => run_audio(context, &url, tag_audio).await,
+        Some(Command::Compare { url, youtube }) => run_compare(context, &url, &youtube).await,
+        Some(Command::Show {
+            url,
+            transcript,
+            crawl,
+        }) => run_show(
+            context,
+            &url,
+            &crawl.options(),
+            &transcript.formats(),
+            archive,
+            false,
+        )
+        .await
+        .map(drop),
+        Some(Command::Sync {
+            url,
+            print_new,
+            digest,
+            transcript,
+            crawl,
+        }) => {
+            let Some(archive) = archive else {
+                eprintln!("Error: sync needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            run_sync(
+                context,
+                &url,
+                &crawl.options(),
+                &transcript.formats(),
+                archive,
+                print_new == Some(PrintFormat::Ndjson),
+                digest.digest().as_ref(),
+            )
+            .await
+        }
+        Some(Command::Watch {
+            url,
+            interval,
+            notify_url,
+            exec,
+            once,
+            digest,
+            transcript,
+        }) => {
+            let digest = digest.digest();
+            let watch = WatchOptions {
+                interval,
+                once,
+                notify_url: notify_url.as_deref(),
+                exec: exec.as_deref(),
+                digest: digest.as_ref(),
+            };
+            run_watch(context, &url, &watch, &transcript.formats(), archive).await
+        }
+        Some(Command::List {
+            action: Some(action),
+            ..
+        }) => {
+            let Some(archives) = archives else {
+                eprintln!("Error: list needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            match action {
+                ListAction::Create { name, condition } => {
+                    run_list_create(context, archives, &name, &condition)
+                }
+                ListAction::Show { name } => run_list_show(context, archives, name.as_deref()),
+                ListAction::Export { name, out } => run_list_export(context, archives, &name, &out),
+                ListAction::Delete { name } => run_list_delete(context, archives, &name),
+            }
+        }
+        Some(Command::List {
+            action: None,
+            show,
+            limit,
+            export,
+            section,
+            export_dir,
+            strip_boilerplate,
+            removed,
+            tag,
+            min_rating,
+            played,
+            unplayed,
+        }) => {
+            let Some(archives) = archives else {
+                eprintln!("Error: list needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            let export = export.map(|format| SiteExport {
+                format,
+                section,
+                dir: export_dir.unwrap_or_else(|| format!("{}/{}", context.layout.dir, SITE_DIR)),
+                strip_boilerplate,
+            });
+            let filter = ArchiveFilter {
+                show_id: show
+                    .as_deref()
+                    .map(|show| show_id_from_url(show).unwrap_or_else(|| show.to_string())),
+                limit,
+                since: None,
+                removed,
+                tag,
+                min_rating,
+                played: (played || unplayed).then_some(played),
+            };
+            run_list(context, archives, &filter, export.as_ref())
+        }
+        Some(Command::Tag { action }) => {
+            let Some(archives) = archives else {
+                eprintln!("Error: tag needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            match action {
+                TagAction::Add { episode, tags } => {
+                    run_tag(context, archives, &episode, &tags, true)
+                }
+                TagAction::Remove { episode, tags } => {
+                    run_tag(context, archives, &episode, &tags, false)
+                }
+            }
+        }
+        Some(Command::Mark { action }) => {
+            let Some(archives) = archives else {
+                eprintln!("Error: mark needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            match action {
+                MarkAction::Played { episodes } => run_mark(context, archives, &episodes, true),
+                MarkAction::Unplayed { episodes } => run_mark(context, archives, &episodes, false),
+                MarkAction::Import { file } => run_mark_import(context, archives, &file),
+            }
+        }
+        Some(Command::Rate { episode, stars }) => {
+            let Some(archives) = archives else {
+                eprintln!("Error: rate needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            run_rate(context, archives, &episode, (stars > 0).then_some(stars))
+        }
+        Some(Command::Link { target }) => {
+            let Some(archives) = archives else {
+                eprintln!("Error: link needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            run_link(context, archives, &target)
+        }
+        Some(Command::Refresh {
+            fields,
+            strategy,
+            show,
+            limit,
+        }) => {
+            let Some(archive) = archive else {
+                eprintln!("Error: refresh needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            let filter = ArchiveFilter {
+                show_id: show
+                    .as_deref()
+                    .map(|show| show_id_from_url(show).unwrap_or_else(|| show.to_string())),
+                limit,
+                since: None,
+                removed: false,
+                tag: None,
+                min_rating: None,
+                played: None,
+            };
+            run_refresh(context, archive, &filter, &fields, strategy).await
+        }
+        Some(Command::Grep {
+            phrase,
+            show,
+            limit,
+        }) => {
+            let Some(archives) = archives else {
+                eprintln!("Error: grep needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            run_grep(context, archives, &phrase, show.as_deref(), limit)
+        }
+        Some(Command::Quote { show }) => {
+            let Some(archives) = archives else {
+                eprintln!("Error: quote needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            run_quote(context, archives, show.as_deref())
+        }
+        Some(Command::Ask {
+            episode,
+            question,
+            top,
+            passage,
+            llm_command,
+        }) => {
+            let Some(archives) = archives else {
+                eprintln!("Error: ask needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            let options = AskOptions {
+                top,
+                passage_seconds: passage.as_secs_f64(),
+            };
+            run_ask(
+                context,
+                archives,
+                &episode,
+                &question,
+                &options,
+                llm_command.as_deref(),
+            )
+        }
+        Some(Command::Pack {
+            episode,
+            max_tokens,
+            format,
+            chunk,
+            output,
+        }) => {
+            let Some(archives) = archives else {
+                eprintln!("Error: pack needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            let options = PackOptions {
+                max_tokens,
+                chunk_seconds: chunk.map(|chunk| chunk.as_secs_f64()),
+                format,
+            };
+            run_pack(context, archives, &episode, &options, output.as_deref())
+        }
+        Some(Command::Archive {
+            action: ArchiveAction::Merge { other },
+        }) => {
+            let Some(archive) = archive else {
+                eprintln!("Error: archive merge needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            run_merge(context, archive, &other)
+        }
+        Some(Command::Archive {
+            action: ArchiveAction::Export { out, show, since },
+        }) => {
+            let Some(archive) = archive else {
+                eprintln!("Error: archive export needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            let filter = ArchiveFilter {
+                show_id: show
+                    .as_deref()
+                    .map(|show| show_id_from_url(show).unwrap_or_else(|| show.to_string())),
+                limit: None,
+                since,
+                removed: false,
+                tag: None,
+                min_rating: None,
+                played: None,
+            };
+            run_export(context, archive, &filter, &out)
+        }
+        Some(Command::Archive {
+            action: ArchiveAction::Import { bundle },
+        }) => {
+            let Some(archive) = archive else {
+                eprintln!("Error: archive import needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            run_import(context, archive, &bundle)
+        }
+        Some(Command::Batch {
+            input,
+            concurrency,
+            report_format,
+            transcript,
+            crawl,
+        }) => {
+            let mut urls = read_url_list(&input);
+            for url in &mut urls {
+                localize_url(url, args.http.country.as_deref());
+            }
+            run_batch(
+                context,
+                urls,
+                concurrency,
+                &report_format,
+                &transcript.formats(),
+                &crawl.options(),
+            )
+            .await
+        }
+        Some(Command::Feed {
+            action: Some(FeedAction::NewItems { out, limit }),
+            ..
+        }) => {
+            let Some(archives) = archives else {
+                eprintln!("Error: feed new-items needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            run_new_items(context, archives, &out, limit)
+        }
+        Some(Command::Feed {
+            action: None,
+            urls,
+            download,
+            opml,
+        }) => run_feed(context, &urls, download, opml.as_deref()).await,
+        Some(Command::Lookup { id, storefront }) => {
+            run_lookup(context, &id, storefront.as_deref()).await
+        }
+        Some(Command::Search {
+            term,
+            storefront,
+            limit,
+        }) => run_search(context, &term, storefront.as_deref().unwrap_or("us"), limit).await,
+        Some(Command::Report {
+            kind: ReportKind::Gaps { show },
+        }) => run_gaps(context, &show).await,
+        Some(Command::Report {
+            kind: ReportKind::Changes { since, chart },
+        }) => {
+            let Some(archives) = archives else {
+                eprintln!("Error: report changes needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            run_changes(context, archives, &since, chart.as_deref())
+        }
+        Some(Command::Report {
+            kind: ReportKind::Durations { show, chart },
+        }) => {
+            let Some(archives) = archives else {
+                eprintln!("Error: report durations needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            run_durations(context, archives, &show, chart.as_deref())
+        }
+        Some(Command::Report {
+            kind: ReportKind::Sponsors { show },
+        }) => {
+            let Some(archives) = archives else {
+                eprintln!("Error: report sponsors needs an archive; pass --db <PATH>");
+                process::exit(2);
+            };
+            run_sponsors(context, archives, &show)
+        }
+        Some(Command::Healthcheck) => {
+            run_healthcheck(context, args.config.file(), &args.output.db).await
+        }
+        Some(Command::Doctor {
+            check: DoctorCheck::Network,
+        }) => run_network_doctor().await,
+        Some(Command::Devtools {
+            tool: DevTool::DiffExtract { old, new },
+        }) => run_diff_extract(context, &old, &new),
+        Some(Command::Devtools {
+            tool: DevTool::Coverage { dir },
+        }) => run_coverage(context, &dir),
+        Some(Command::Devtools {
+            tool: DevTool::StrategyStats,
+        }) => {
+            let Some(path) = context.stats_file.as_deref() else {
+                eprintln!(
+                    "Error: strategy stats are off; drop --no-stats or pass --stats-file <PATH>"
+                );
+                process::exit(2);
+            };
+            run_strategy_stats(context, path)
+        }
+        Some(Command::Completions { shell }) => {
+            print_completions(shell);
+            Ok(())
+        }
+        Some(Command::Init { yes, force }) => {
+            run_init(args.config.config.clone(), yes, force);
+            Ok(())
+        }
+        None => match args.print_schema {
+            Some(kind) => {
+                print_schema(kind);
+                Ok(())
+            }
+            None => {
+                let Some(url) = args.run.url.as_deref() else {
+                    eprintln!("Error: a URL is required");
+                    process::exit(2);
+                };
+                run_all(context, url, &args.run.options(), archive).await
+            }
+        },
+    };
+    if let Err(failure) = outcome {
+        exit_with(failure);
+    }
+}
 
-        // When we extract metadata
-        let result = extract_metadata(html_path);
-
-        // Then it succeeds
-        assert!(result.is_ok(), "extract_metadata should succeed");
-
-        let metadata = result.unwrap();
+/// Writes a completion script for `shell` to stdout
+fn print_completions(shell: Shell) {
+    let mut command = Args::command();
+    let name = command.get_name().to_string();
+    generate(shell, &mut command, name, &mut io::stdout());
+}
 
-        // And all fields are non-empty
-        assert!(
-            !metadata.episode_title.is_empty(),
-            "Episode title should not be empty"
-        );
-        assert!(
-            !metadata.description.is_empty(),
-            "Description should not be empty"
-        );
-        assert!(
-            !metadata.show_title.is_empty(),
-            "Show title should not be empty"
-        );
-        assert!(
-            !metadata.publish_date.is_empty(),
-            "Publish date should not be empty"
+/// Asks for the common settings on the terminal and writes them as a config file
+///
+/// Writes `--config` when given, else the default config path. An existing
+/// file is only replaced with `--force`.
+fn run_init(path: Option<PathBuf>, yes: bool, force: bool) {
+    let Some(path) = path.or_else(default_config_path) else {
+        eprintln!("Error: init needs a config path; pass --config <PATH>");
+        process::exit(2);
+    };
+    if path.exists() && !force {
+        eprintln!(
+            "Error: {} already exists; pass --force to replace it",
+            path.display()
         );
+        process::exit(2);
+    }
 
-        // And the values have reasonable content (any episode will do)
-        assert!(
-            metadata.episode_title.len() > 5,
-            "Episode title should have substantial content"
-        );
-        assert!(
-            metadata.show_title.len() > 3,
-            "Show title should have substantial content"
+    let answers = if yes {
+        InitAnswers::default()
+    } else {
+        eprintln!(
+            "Setting up {}. Press Enter to take the default.",
+            path.display()
         );
-        assert!(
-            metadata.publish_date.contains("-"),
-            "Publish date should be in date format"
+        let mut prompter = Prompter::new(io::stdin().lock(), io::stderr());
+        or_exit(run_wizard(&mut prompter), "Error")
+    };
+
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        or_exit(
+            fs::create_dir_all(parent)
+                .map_err(applecast::Error::io("Failed to create config directory")),
+            "Error",
         );
     }
+    or_exit(
+        fs::write(&path, render_config(&answers))
+            .map_err(applecast::Error::io("Failed to write config file")),
+        "Error",
+    );
+    info!("✅ Config written to {}", path.display());
+}
 
-    /// Unit test - extract_from_json_ld parses JSON-LD schema correctly
-    #[test]
-    fn test_extract_from_json_ld() {
-        // Given HTML with a JSON-LD schema
-        let html = r#"
-            <!DOCTYPE html>
-            <html>
-            <head>
-                <script id="schema:episode" type="application/ld+json">
-                {
-                    "name": "Test Episode Title",
-                    "description": "Test episode description",
-                    "datePublished": "2023-01-15",
-                    "partOfSeries": {
-                        "name": "Test Podcast Show"
-                    }
-                }
-                </script>
-            </head>
-            <body></body>
-            </html>
-        "#;
-
-        // When we parse it
-        let document = Html::parse_document(html);
-        let result = extract_from_json_ld(&document);
-
-        // Then it succeeds
-        assert!(result.is_ok(), "extract_from_json_ld should succeed");
-
-        let metadata = result.unwrap();
-
-        // And all fields are extracted correctly
-        assert_eq!(metadata.episode_title, "Test Episode Title");
-        assert_eq!(metadata.description, "Test episode description");
-        assert_eq!(metadata.show_title, "Test Podcast Show");
-        assert_eq!(metadata.publish_date, "2023-01-15");
+/// Prints an output file's JSON Schema on stdout
+fn print_schema(kind: SchemaKind) {
+    match to_sorted_json(&schema(kind)) {
+        Ok(json) => println!("{}", json),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(EXIT_FAILURE);
+        }
     }
+}
 
-    /// Unit test - find_transcript_url returns None when transcript not available
-    #[test]
-    fn test_find_transcript_url_returns_none_when_not_available() {
-        use tempfile::TempDir;
-
-        // Given HTML without transcript data
-        let html = r#"
-            <script type="application/json" id="serialized-server-data">
-            [{"data": {"episode": {"title": "Test"}}}]
-            </script>
-        "#;
-
-        // When we write it to a file and search for transcript URL
-        let temp_dir = TempDir::new().unwrap();
-        let html_path = temp_dir.path().join("episode.html");
-        fs::write(&html_path, html).unwrap();
-
-        let result = find_transcript_url(html_path.to_str().unwrap());
-
-        // Then it should return None
-        assert!(result.is_ok(), "Should not error when no transcript found");
-        assert!(
-            result.unwrap().is_none(),
-            "Should return None when no transcript"
+/// Reads a batch URL list from a file, or from stdin for `-`
+fn read_url_list(input: &str) -> Vec<String> {
+    let text = if input == "-" {
+        let mut text = String::new();
+        or_exit(
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .map_err(applecast::Error::io("Failed to read URLs from stdin")),
+            "Error",
         );
-    }
-
-    /// Unit test - find_transcript_url extracts valid ttml URL
-    #[test]
-    fn test_find_transcript_url_extracts_valid_url() {
-        use tempfile::TempDir;
-
-        // Given HTML with transcript URL in serialized data (nested structure)
-        let html = r#"<html><body><script type="application/json" id="serialized-server-data">[{"data":{"shelves":[{"items":[{"contextAction":{"episodeOffer":{"closedCaptions":{"url":"https://example.com/transcript.ttml"}}}}]}]}}]</script></body></html>"#;
-
-        // When we extract the transcript URL
-        let temp_dir = TempDir::new().unwrap();
-        let html_path = temp_dir.path().join("episode.html");
-        fs::write(&html_path, html).unwrap();
+        text
+    } else {
+        or_exit(
+            fs::read_to_string(input).map_err(applecast::Error::io("Failed to read URL list")),
+            "Error",
+        )
+    };
+    parse_url_list(&text)
+}
 
-        let result = find_transcript_url(html_path.to_str().unwrap());
+/// Unwraps a result, or prints the error with `context` and exits with its category's status
+fn or_exit<T>(result: applecast::Result<T>, context: &str) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{}: {}", context, e);
+        process::exit(exit_code(&e));
+    })
+}
 
-        // Then it should return the URL
-        assert!(result.is_ok(), "Should successfully extract URL");
-        let url = result.unwrap();
-        assert!(url.is_some(), "Should find transcript URL");
-        assert_eq!(url.unwrap(), "https://example.com/transcript.ttml");
+/// Prints why a command failed, unless it already has, and exits with the matching status
+fn exit_with(failure: Failure) -> ! {
+    match failure {
+        Failure::Error { context, error } => {
+            eprintln!("{}: {}", context, error);
+            process::exit(exit_code(&error));
+        }
+        Failure::Message(message) => {
+            eprintln!("Error: {}", message);
+            process::exit(EXIT_FAILURE);
+        }
+        Failure::Reported(Some(error)) => process::exit(exit_code(&error)),
+        Failure::Reported(None) => process::exit(EXIT_FAILURE),
+        Failure::NoTranscript => process::exit(EXIT_NO_TRANSCRIPT),
     }
+}
 
-    /// Unit test - download_transcript creates file with content
-    #[test]
-    fn test_download_transcript_creates_file() {
-        use tempfile::TempDir;
+/// Exit status for an error: network, parse or general failure
+fn exit_code(error: &applecast::Error) -> i32 {
+    if error.is_network() {
+        EXIT_NETWORK
+    } else if error.is_parse() {
+        EXIT_PARSE
+    } else {
+        EXIT_FAILURE
+    }
+}
 
-        // Given a valid URL and output path
-        let temp_dir = TempDir::new().unwrap();
-        let output_path = temp_dir.path().join("transcript.ttml");
+/// Normalizes Apple Podcasts URL arguments and points them, and storefront defaults, at the `--country` storefront
+fn normalize_urls(args: &mut Args) {
+    let country = args.http.country.clone();
+    let localize = |url: &mut String| localize_url(url, country.as_deref());
 
-        // When we download from a test URL
-        let result = download_transcript("https://httpbin.org/html", output_path.to_str().unwrap());
+    if let Some(url) = &mut args.run.url {
+        localize(url);
+    }
+    match &mut args.command {
+        Some(
+            Command::Fetch { url }
+            | Command::Transcript { url, .. }
+            | Command::Audio { url, .. }
+            | Command::Compare { url, .. }
+            | Command::Show { url, .. }
+            | Command::Sync { url, .. }
+            | Command::Watch { url, .. },
+        ) => localize(url),
+        Some(Command::Metadata {
+            source: Some(source),
+            ..
+        }) => localize(source),
+        Some(Command::Report {
+            kind: ReportKind::Gaps { show },
+        }) => localize(show),
+        Some(Command::Feed { urls, .. }) => urls.iter_mut().for_each(localize),
+        Some(Command::Lookup { storefront, .. } | Command::Search { storefront, .. }) => {
+            if let Some(country) = &country {
+                storefront.get_or_insert(country.clone());
+            }
+        }
+        _ => {}
+    }
+}
 
-        // Then it succeeds
-        assert!(result.is_ok(), "download_transcript should succeed");
+/// Drops tracking parameters from an Apple Podcasts URL and moves it to `country`'s storefront
+///
+/// Other URLs and file paths are left for the command to accept or reject.
+fn localize_url(url: &mut String, country: Option<&str>) {
+    if let Ok(parsed) = ParsedUrl::parse(url) {
+        *url = parsed.url;
+    }
+    if let Some(country) = country {
+        *url = with_storefront(url, country);
+    }
+}
 
-        // And the file exists with content
-        assert!(output_path.exists(), "Transcript file should exist");
-        let content = fs::read_to_string(&output_path).unwrap();
-        assert!(!content.is_empty(), "Transcript file should not be empty");
+/// Parses the command line, taking flags it leaves unset from the config file
+///
+/// A missing default config file is fine; a missing `--config` file is not.
+/// Also returns the config file's per-show webhooks and field transforms.
+fn parse_args() -> (Args, Webhooks, Transforms) {
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    let args = Args::parse_from(&argv);
+    // `init` writes the config, so a broken one must not stop it
+    if matches!(args.command, Some(Command::Init { .. })) {
+        return (args, Webhooks::default(), Transforms::default());
     }
 
-    /// Unit test - download_transcript handles HTTP errors
-    #[test]
-    fn test_download_transcript_handles_http_errors() {
-        use tempfile::TempDir;
+    // `healthcheck` reports a broken config as unhealthy instead of a usage error
+    let healthcheck = matches!(args.command, Some(Command::Healthcheck));
+    let invalid = |message: String| -> ! {
+        if healthcheck {
+            println!("❌ config: {}", message);
+            process::exit(1);
+        }
+        eprintln!("Error: {}", message);
+        process::exit(2);
+    };
 
-        // Given an invalid URL
-        let temp_dir = TempDir::new().unwrap();
-        let output_path = temp_dir.path().join("transcript.ttml");
+    let config = args.config.file().map(|path| Config::load(&path));
+    let (webhooks, transforms) = match &config {
+        Some(Ok(config)) => (config.webhooks.clone(), config.transforms.clone()),
+        _ => (Webhooks::default(), Transforms::default()),
+    };
+    let settings = match (config, args.config.profile.as_deref()) {
+        (None, None) => return (args, webhooks, transforms),
+        (None, Some(_)) => Err(applecast::Error::Config(
+            "--profile needs a config file; none was found".to_string(),
+        )),
+        (Some(config), profile) => config.and_then(|config| config.resolve(profile)),
+    };
+    let argv = settings.and_then(|settings| apply_settings(&Args::command(), argv, &settings));
+    match argv.map(Args::try_parse_from) {
+        Ok(Ok(args)) => (args, webhooks, transforms),
+        // A setting clap rejects, e.g. an invalid storefront
+        Ok(Err(e)) if healthcheck => invalid(
+            e.to_string()
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim_start_matches("error: ")
+                .to_string(),
+        ),
+        Ok(Err(e)) => e.exit(),
+        Err(e) => invalid(e.to_string()),
+    }
+}
 
-        // When we try to download from a 404 URL
-        let result = download_transcript(
-            "https://httpbin.org/status/404",
-            output_path.to_str().unwrap(),
-        );
+/// Tells the user why the tool is pausing before it waits out a 429
+///
+/// Logged as a `rate_limited` event carrying the host and the time requests
+/// to it resume, so `--log-format json` consumers can track throttling.
+fn print_rate_limit_notice(host: &str, delay: Duration) {
+    let resume_at = httpdate::fmt_http_date(std::time::SystemTime::now() + delay);
+    warn!(
+        event = "rate_limited",
+        host,
+        resume_at,
+        wait_secs = delay.as_secs(),
+        "Rate limited by {}; resuming in {}s",
+        host,
+        delay.as_secs()
+    );
+}
 
-        // Then it should fail with an error
-        assert!(result.is_err(), "Should fail for HTTP error codes");
-        let error = result.unwrap_err().to_string();
-        assert!(
-            error.contains("404") || error.contains("failed"),
-            "Error should mention HTTP failure"
-        );
-    }
+/// Tells the user a request failed and when it will be retried
+fn print_retry_notice(host: &str, attempt: u32, delay: Duration, reason: &str) {
+    warn!(
+        "{}: {}; retrying in {:.1}s (attempt {})",
+        host,
+        reason,
+        delay.as_secs_f64(),
+        attempt
+    );
 }
@@ -0,0 +1,379 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::output::write_json;
+
+/// File in each episode directory recording what a show or batch run saved there
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Directory under the output directory holding the `--backup-friendly` store
+pub const BACKUP_DIR: &str = "backup";
+
+/// Size and SHA-256 of one saved file
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// The files an episode directory held when it was last saved
+///
+/// Re-runs compare the directory against it: unchanged artifacts can be
+/// skipped, and artifacts edited since are not overwritten without `--force`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub url: String,
+    pub transcript: bool,
+    pub artifacts: BTreeMap<String, Artifact>,
+}
+
+/// A manifest as kept in the backup store, with the episode directory it describes
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub directory: String,
+    #[serde(flatten)]
+    pub manifest: Manifest,
+}
+
+/// What a re-run does with an episode directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// Nothing was saved yet, the run does not skip existing episodes, or `--force` was given
+    Fetch,
+    /// Every artifact is on disk as it was saved
+    Skip { transcript: bool },
+    /// These artifacts are gone; the others are on disk as they were saved
+    Repair(Vec<String>),
+    /// These artifacts were edited or replaced since they were saved
+    Changed(Vec<String>),
+}
+
+impl Decision {
+    /// Name used in dry-run plans
+    pub fn action(&self) -> &'static str {
+        match self {
+            Decision::Fetch => "fetch",
+            Decision::Skip { .. } => "skip",
+            Decision::Repair(_) => "repair",
+            Decision::Changed(_) => "refuse",
+        }
+    }
+}
+
+/// One episode in a `--dry-run` plan
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct PlannedEpisode {
+    pub url: String,
+    /// `None` when the directory depends on metadata only the page has
+    pub directory: Option<String>,
+    /// `fetch`, `skip`, `repair` (missing artifacts), `refuse` (changed artifacts) or `enumerate` (a show URL)
+    pub action: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changed: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+}
+
+impl Manifest {
+    /// Fingerprints every file saved in `dir` and writes the manifest beside them
+    pub fn record(dir: &str, url: &str, transcript: bool) -> Result<Manifest> {
+        let entries = fs::read_dir(dir).map_err(Error::io("Failed to read episode directory"))?;
+        let mut artifacts = BTreeMap::new();
+        for entry in entries {
+            let entry = entry.map_err(Error::io("Failed to read episode directory"))?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let is_file = entry.file_type().is_ok_and(|kind| kind.is_file());
+            // Partial downloads are not artifacts until they are renamed into place
+            if !is_file || name == MANIFEST_FILE || name.ends_with(".part") {
+                continue;
+            }
+            artifacts.insert(name, fingerprint(&entry.path())?);
+        }
+
+        let manifest = Manifest {
+            url: url.to_string(),
+            transcript,
+            artifacts,
+        };
+        write_json(&manifest, &manifest_path(dir))?;
+        Ok(manifest)
+    }
+
+    /// Reads the manifest in `dir`, if a run saved one
+    pub fn load(dir: &str) -> Result<Option<Manifest>> {
+        match fs::read_to_string(manifest_path(dir)) {
+            Ok(text) => Ok(Some(serde_json::from_str(&text)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::io("Failed to read manifest")(e)),
+        }
+    }
+
+    /// Re-fingerprints one artifact after the tool itself rewrote it, if `dir` has a manifest
+    pub fn update(dir: &str, name: &str) -> Result<Option<Manifest>> {
+        let Some(mut manifest) = Manifest::load(dir)? else {
+            return Ok(None);
+        };
+        let artifact = fingerprint(&Path::new(dir).join(name))?;
+        manifest.artifacts.insert(name.to_string(), artifact);
+        write_json(&manifest, &manifest_path(dir))?;
+        Ok(Some(manifest))
+    }
+
+    /// Adds the artifacts saved in `dir` to the append-only backup store in `store`
+    ///
+    /// Each artifact is stored once as `objects/<sha256>`, a name its content
+    /// fixes, and the manifest is added as the next numbered snapshot under
+    /// `manifests/`, unless it equals the last one. Nothing in the store is ever
+    /// rewritten or removed, so an incremental backup of it only uploads new
+    /// files. Returns how many files were added.
+    pub fn back_up(&self, dir: &str, store: &str) -> Result<usize> {
+        let mut added = 0;
+        for (name, artifact) in &self.artifacts {
+            let object = Path::new(store).join("objects").join(&artifact.sha256);
+            if object.is_file() {
+                continue;
+            }
+            // Copied under a temporary name and checked, so an object never holds other content
+            let partial = object.with_extension("part");
+            fs::create_dir_all(Path::new(store).join("objects"))
+                .map_err(Error::io("Failed to create backup store"))?;
+            fs::copy(Path::new(dir).join(name), &partial)
+                .map_err(Error::io("Failed to back up artifact"))?;
+            if fingerprint(&partial)? != *artifact {
+                let _ = fs::remove_file(&partial);
+                return Err(Error::Parse(format!(
+                    "{} changed while it was backed up",
+                    name
+                )));
+            }
+            fs::rename(&partial, &object).map_err(Error::io("Failed to back up artifact"))?;
+            added += 1;
+        }
+
+        let snapshots = Path::new(store)
+            .join("manifests")
+            .join(snapshot_key(&self.url));
+        let (last, latest) = latest_snapshot(&snapshots);
+        let snapshot = Snapshot {
+            directory: dir.to_string(),
+            manifest: self.clone(),
+        };
+        if latest.as_ref() != Some(&snapshot) {
+            let path = snapshots.join(format!("{:06}.json", last + 1));
+            write_json(&snapshot, &path.to_string_lossy())?;
+            added += 1;
+        }
+        Ok(added)
+    }
+
+    /// Artifacts no longer in `dir`
+    pub fn missing(&self, dir: &str) -> Vec<String> {
+        self.artifacts
+            .keys()
+            .filter(|name| !Path::new(dir).join(name).is_file())
+            .cloned()
+            .collect()
+    }
+
+    /// Artifacts in `dir` whose size or content differs from when they were saved
+    pub fn changed(&self, dir: &str) -> Result<Vec<String>> {
+        let mut changed = Vec::new();
+        for (name, saved) in &self.artifacts {
+            let path = Path::new(dir).join(name);
+            let Ok(file) = fs::metadata(&path) else {
+                continue;
+            };
+            // A size change is caught without hashing the file
+            if file.len() != saved.size || fingerprint(&path)? != *saved {
+                changed.push(name.clone());
+            }
+        }
+        Ok(changed)
+    }
+}
+
+/// Decides whether a re-run fetches, skips, repairs or refuses to overwrite an episode directory
+///
+/// The decision is made per artifact: with `skip_existing`, a directory whose
+/// saved files are intact except for some deleted ones is repaired, fetching
+/// only what is gone. Directories without a manifest are fetched, as they were
+/// before manifests existed. An unreadable manifest counts as missing.
+pub fn decide(dir: &str, skip_existing: bool, force: bool) -> Result<Decision> {
+    if force {
+        return Ok(Decision::Fetch);
+    }
+    let Some(manifest) = Manifest::load(dir).unwrap_or(None) else {
+        return Ok(Decision::Fetch);
+    };
+
+    let changed = manifest.changed(dir)?;
+    if !changed.is_empty() {
+        return Ok(Decision::Changed(changed));
+    }
+    if !skip_existing {
+        return Ok(Decision::Fetch);
+    }
+    let missing = manifest.missing(dir);
+    if missing.is_empty() {
+        return Ok(Decision::Skip {
+            transcript: manifest.transcript,
+        });
+    }
+    Ok(Decision::Repair(missing))
+}
+
+/// The store directory an episode's snapshots go in: a short hash of its URL
+fn snapshot_key(url: &str) -> String {
+    format!("{:x}", Sha256::digest(url.as_bytes()))[..16].to_string()
+}
+
+/// The highest snapshot number in `dir` (0 when there is none), and that snapshot if it is readable
+fn latest_snapshot(dir: &Path) -> (u64, Option<Snapshot>) {
+    let last = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.strip_suffix(".json")?.parse::<u64>().ok()
+        })
+        .max()
+        .unwrap_or(0);
+    let snapshot = fs::read_to_string(dir.join(format!("{:06}.json", last)))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok());
+    (last, snapshot)
+}
+
+fn manifest_path(dir: &str) -> String {
+    format!("{}/{}", dir, MANIFEST_FILE)
+}
+
+/// Size and SHA-256 of a file, streamed rather than read into memory
+fn fingerprint(path: &Path) -> Result<Artifact> {
+    let mut file = File::open(path).map_err(Error::io("Failed to read artifact"))?;
+    let mut hasher = Sha256::new();
+    let size = io::copy(&mut file, &mut hasher).map_err(Error::io("Failed to read artifact"))?;
+    Ok(Artifact {
+        size,
+        sha256: format!("{:x}", hasher.finalize()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Unit test - decide skips intact directories, repairs partial ones and refuses edited ones
+    #[test]
+    fn test_decide_skips_intact_and_refuses_changed() {
+        // Given an episode directory recorded in a manifest
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        fs::write(temp_dir.path().join("metadata.json"), "{}").unwrap();
+        fs::write(temp_dir.path().join("transcript.ttml"), "<tt/>").unwrap();
+        fs::write(temp_dir.path().join("audio.mp3.part"), "partial").unwrap();
+        let manifest = Manifest::record(dir, "https://a", true).unwrap();
+        assert_eq!(
+            manifest.artifacts.keys().collect::<Vec<_>>(),
+            vec!["metadata.json", "transcript.ttml"]
+        );
+
+        // When nothing has changed
+        // Then a skip-existing run skips it, a plain run fetches it, and --force always fetches
+        assert_eq!(
+            decide(dir, true, false).unwrap(),
+            Decision::Skip { transcript: true }
+        );
+        assert_eq!(decide(dir, false, false).unwrap(), Decision::Fetch);
+        assert_eq!(decide(dir, true, true).unwrap(), Decision::Fetch);
+
+        // When the metadata is edited by hand
+        fs::write(temp_dir.path().join("metadata.json"), "[]").unwrap();
+
+        // Then re-runs refuse to overwrite it unless forced
+        assert_eq!(
+            decide(dir, true, false).unwrap(),
+            Decision::Changed(vec!["metadata.json".to_string()])
+        );
+        assert_eq!(decide(dir, false, true).unwrap(), Decision::Fetch);
+
+        // When the tool rewrites it and records the change, and the transcript is deleted
+        Manifest::update(dir, "metadata.json").unwrap();
+        fs::remove_file(temp_dir.path().join("transcript.ttml")).unwrap();
+
+        // Then only the missing transcript is fetched again, unless the run fetches everything
+        assert_eq!(
+            decide(dir, true, false).unwrap(),
+            Decision::Repair(vec!["transcript.ttml".to_string()])
+        );
+        assert_eq!(decide(dir, false, false).unwrap(), Decision::Fetch);
+    }
+
+    /// Unit test - back_up only ever adds files to the store
+    #[test]
+    fn test_back_up_is_append_only() {
+        // Given a saved episode directory backed up once
+        let temp_dir = TempDir::new().unwrap();
+        let store_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        let store = store_dir.path().to_str().unwrap();
+        fs::write(temp_dir.path().join("metadata.json"), "{}").unwrap();
+        fs::write(temp_dir.path().join("transcript.ttml"), "<tt/>").unwrap();
+        let first = Manifest::record(dir, "https://a", true).unwrap();
+        assert_eq!(first.back_up(dir, store).unwrap(), 3);
+        let transcript = store_dir
+            .path()
+            .join("objects")
+            .join(&first.artifacts["transcript.ttml"].sha256);
+        let modified = fs::metadata(&transcript).unwrap().modified().unwrap();
+
+        // When nothing changed, and then when the metadata is rewritten
+        let unchanged = first.back_up(dir, store).unwrap();
+        fs::write(temp_dir.path().join("metadata.json"), "[]").unwrap();
+        let second = Manifest::update(dir, "metadata.json").unwrap().unwrap();
+        let added = second.back_up(dir, store).unwrap();
+
+        // Then an unchanged directory adds nothing, and an edit adds only its new
+        // object and a second snapshot, leaving the earlier files as they were
+        assert_eq!(unchanged, 0);
+        assert_eq!(added, 2);
+        assert_eq!(fs::read_to_string(&transcript).unwrap(), "<tt/>");
+        assert_eq!(
+            fs::metadata(&transcript).unwrap().modified().unwrap(),
+            modified
+        );
+        let snapshots = store_dir
+            .path()
+            .join("manifests")
+            .join(snapshot_key("https://a"));
+        let (last, latest) = latest_snapshot(&snapshots);
+        assert_eq!(last, 2);
+        assert_eq!(latest.unwrap().manifest, second);
+        assert!(snapshots.join("000001.json").is_file());
+    }
+
+    /// Unit test - decide fetches directories without a manifest
+    #[test]
+    fn test_decide_fetches_without_manifest() {
+        // Given a directory saved before manifests existed, and one never saved
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("metadata.json"), "{}").unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+
+        // When we decide what to do with them
+        // Then both are fetched
+        assert_eq!(decide(dir, true, false).unwrap(), Decision::Fetch);
+        assert_eq!(
+            decide(&format!("{}/missing", dir), true, false).unwrap(),
+            Decision::Fetch
+        );
+    }
+}
@@ -0,0 +1,981 @@
+pub mod format;
+pub mod sanitize;
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::{Serialize, Serializer};
+use tracing::debug;
+
+use crate::ads::AdSegment;
+use crate::apple_url::rewrite_storefront;
+use crate::artwork::{artwork_template, fill_artwork_template, ArtworkSize};
+use crate::audio::{audio_url_in, find_audio_url};
+use crate::error::{Error, Result};
+use crate::feed::find_feed_url;
+use crate::fetch::{fetch_page, HttpOptions};
+use crate::locale::parse_localized_date;
+use crate::metadata::sanitize::Sanitization;
+use crate::server_data::{
+    find_field, find_string_field, script_content, serialized_server_data, server_data_for,
+};
+use crate::variant::variant_for;
+
+/// Represents episode metadata extracted from Apple Podcasts HTML
+///
+/// Fields the page does not provide are serialized as `null`.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct Metadata {
+    #[serde(serialize_with = "empty_as_null")]
+    pub episode_title: String,
+    /// As the source provides it, which may include HTML; see [`format_description`]
+    #[serde(serialize_with = "empty_as_null")]
+    pub description: String,
+    #[serde(serialize_with = "empty_as_null")]
+    pub show_title: String,
+    #[serde(serialize_with = "empty_as_null")]
+    pub publish_date: String,
+    pub publish_date_iso8601: Option<String>,
+    pub duration_seconds: Option<u64>,
+    pub episode_number: Option<u32>,
+    pub season_number: Option<u32>,
+    pub guid: Option<String>,
+    pub audio_url: Option<String>,
+    /// The show's original RSS feed
+    pub feed_url: Option<String>,
+    pub artwork_url: Option<String>,
+    pub explicit: Option<bool>,
+    /// Show genres, most specific first
+    pub genres: Option<Vec<String>>,
+    /// ISO 639-1 code of the language the description is written in, when it can be told
+    pub description_language: Option<String>,
+    /// Language of the transcript, as declared in the TTML or detected from its text
+    pub transcript_language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub localized: Option<BTreeMap<String, LocalizedMetadata>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ad_segments: Option<Vec<AdSegment>>,
+    /// Extracted values of the fields an override file replaced, keyed by field name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overridden: Option<BTreeMap<String, serde_json::Value>>,
+    /// Trackers, repeated images and tracking parameters `--sanitize-description` removed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sanitized: Option<Sanitization>,
+}
+
+impl Metadata {
+    /// Fills every field this metadata lacks from `other`, keeping the values already present
+    pub fn fill_missing(&mut self, other: Metadata) {
+        fn text(field: &mut String, other: String) {
+            if field.is_empty() {
+                *field = other;
+            }
+        }
+
+        text(&mut self.episode_title, other.episode_title);
+        text(&mut self.description, other.description);
+        text(&mut self.show_title, other.show_title);
+        text(&mut self.publish_date, other.publish_date);
+        self.publish_date_iso8601 = self
+            .publish_date_iso8601
+            .take()
+            .or(other.publish_date_iso8601);
+        self.duration_seconds = self.duration_seconds.or(other.duration_seconds);
+        self.episode_number = self.episode_number.or(other.episode_number);
+        self.season_number = self.season_number.or(other.season_number);
+        self.guid = self.guid.take().or(other.guid);
+        self.audio_url = self.audio_url.take().or(other.audio_url);
+        self.feed_url = self.feed_url.take().or(other.feed_url);
+        self.artwork_url = self.artwork_url.take().or(other.artwork_url);
+        self.explicit = self.explicit.or(other.explicit);
+        self.genres = self.genres.take().or(other.genres);
+        self.description_language = self
+            .description_language
+            .take()
+            .or(other.description_language);
+        self.transcript_language = self
+            .transcript_language
+            .take()
+            .or(other.transcript_language);
+        self.localized = self.localized.take().or(other.localized);
+        self.ad_segments = self.ad_segments.take().or(other.ad_segments);
+        self.overridden = self.overridden.take().or(other.overridden);
+        self.sanitized = self.sanitized.take().or(other.sanitized);
+    }
+}
+
+/// Episode text as shown on another storefront
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct LocalizedMetadata {
+    #[serde(serialize_with = "empty_as_null")]
+    pub episode_title: String,
+    #[serde(serialize_with = "empty_as_null")]
+    pub description: String,
+    #[serde(serialize_with = "empty_as_null")]
+    pub show_title: String,
+    #[serde(serialize_with = "empty_as_null")]
+    pub publish_date: String,
+    pub publish_date_iso8601: Option<String>,
+}
+
+/// Writes a text field that was not found as `null` rather than `""`
+fn empty_as_null<S: Serializer>(
+    value: &str,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    if value.is_empty() {
+        serializer.serialize_none()
+    } else {
+        serializer.serialize_str(value)
+    }
+}
+
+/// How much of an episode page is parsed to extract its metadata
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Extraction {
+    /// Parse the whole page, with every fallback
+    #[default]
+    Full,
+    /// Pull out just the JSON-LD and serialized-data scripts, without building a DOM
+    Lite,
+}
+
+/// Extracts episode metadata from an episode page's HTML the given way
+pub fn extract_metadata_with(html: &str, extraction: Extraction) -> Result<Metadata> {
+    match extraction {
+        Extraction::Full => extract_metadata(html),
+        Extraction::Lite => extract_metadata_lite(html),
+    }
+}
+
+/// Extracts metadata from the page's script blocks alone, parsing its serialized data once
+///
+/// Apple pages run to megabytes of markup, and building their DOM dominates
+/// extraction time and memory on small machines. Pages without a usable
+/// JSON-LD block fall back to [`extract_metadata`]. The only other difference
+/// is that the feed URL is not looked for in `<link>` tags.
+fn extract_metadata_lite(html: &str) -> Result<Metadata> {
+    let variant = variant_for(html);
+    let schema = script_content(html, variant.episode_schema_attr)?
+        .and_then(|json| serde_json::from_str::<serde_json::Value>(json).ok());
+    let Some(schema) = schema else {
+        debug!(
+            variant = variant.id,
+            "No JSON-LD script; falling back to the full parser"
+        );
+        return extract_metadata(html);
+    };
+
+    let mut metadata = metadata_from_json_ld(&schema);
+    if let Some(data) = server_data_for(html, variant)? {
+        metadata.audio_url = audio_url_in(&data);
+        metadata.feed_url = find_string_field(&data, "feedUrl").filter(|url| !url.is_empty());
+        fill_from_server_data(&mut metadata, &data);
+    }
+    Ok(metadata)
+}
+
+/// Extracts episode metadata from an episode page's HTML
+pub fn extract_metadata(html: &str) -> Result<Metadata> {
+    let document = Html::parse_document(html);
+
+    // Try to extract from JSON-LD schema first (most reliable), then fall back to meta tags
+    let variant = variant_for(html);
+    let mut metadata = match extract_from_json_ld(&document, variant.episode_schema) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            debug!(variant = variant.id, selector = variant.episode_schema, error = %e, "Falling back to meta tags");
+            extract_from_meta_tags(&document)?
+        }
+    };
+
+    // The serialized data fills in details the schema lacks
+    add_server_data_details(&mut metadata, html)?;
+    Ok(metadata)
+}
+
+/// Extracts metadata from the JSON-LD schema block matched by `selector`
+pub(crate) fn extract_from_json_ld(document: &Html, selector: &str) -> Result<Metadata> {
+    let script_selector =
+        Selector::parse(selector).map_err(|e| Error::Parse(format!("Invalid selector: {}", e)))?;
+
+    let script = document
+        .select(&script_selector)
+        .next()
+        .ok_or_else(|| Error::Parse("JSON-LD schema not found".to_string()))?;
+
+    let json_text = script.text().collect::<String>();
+    Ok(metadata_from_json_ld(&serde_json::from_str(&json_text)?))
+}
+
+/// Reads the schema's fields into metadata
+fn metadata_from_json_ld(json_value: &serde_json::Value) -> Metadata {
+    let episode_title = json_value["name"].as_str().unwrap_or("").trim().to_string();
+
+    let description = json_value["description"]
+        .as_str()
+        .unwrap_or("")
+        .trim()
+        .to_string();
+
+    let show_title = json_value["partOfSeries"]["name"]
+        .as_str()
+        .unwrap_or("")
+        .trim()
+        .to_string();
+
+    let publish_date = json_value["datePublished"]
+        .as_str()
+        .unwrap_or("")
+        .trim()
+        .to_string();
+
+    let duration_seconds = ["timeRequired", "duration"]
+        .iter()
+        .find_map(|key| json_value[*key].as_str().and_then(parse_iso8601_duration));
+
+    let artwork_url = ["image", "thumbnailUrl"].iter().find_map(|key| {
+        let image = &json_value[*key];
+        image
+            .as_str()
+            .or_else(|| image["url"].as_str())
+            .map(str::to_string)
+    });
+
+    Metadata {
+        episode_title,
+        description,
+        show_title,
+        publish_date,
+        duration_seconds,
+        episode_number: as_number(&json_value["episodeNumber"]),
+        season_number: as_number(&json_value["partOfSeason"]["seasonNumber"]),
+        artwork_url,
+        explicit: json_value["isFamilyFriendly"]
+            .as_bool()
+            .map(|family| !family),
+        genres: as_genres(&json_value["genre"]),
+        ..Metadata::default()
+    }
+}
+
+/// Fills details missing from `metadata` with values from the page's serialized data
+fn add_server_data_details(metadata: &mut Metadata, html: &str) -> Result<()> {
+    metadata.audio_url = find_audio_url(html)?;
+    metadata.feed_url = find_feed_url(html)?;
+    let Some(data) = serialized_server_data(html)? else {
+        debug!("No serialized server data; keeping schema details only");
+        return Ok(());
+    };
+    fill_from_server_data(metadata, &data);
+    Ok(())
+}
+
+/// Fills details missing from `metadata` with values from parsed serialized data
+fn fill_from_server_data(metadata: &mut Metadata, data: &serde_json::Value) {
+    let field = |key: &str| find_field(data, key);
+
+    metadata.guid = field("guid").and_then(|v| v.as_str()).map(str::to_string);
+    metadata.duration_seconds = metadata.duration_seconds.or_else(|| {
+        field("duration").and_then(|v| v.as_u64()).or_else(|| {
+            field("durationInMilliseconds")
+                .and_then(|v| v.as_u64())
+                .map(|ms| ms / 1000)
+        })
+    });
+    metadata.episode_number = metadata
+        .episode_number
+        .or_else(|| field("episodeNumber").and_then(as_number));
+    metadata.season_number = metadata
+        .season_number
+        .or_else(|| field("seasonNumber").and_then(as_number));
+    metadata.artwork_url = metadata.artwork_url.take().or_else(|| {
+        artwork_template(data)
+            .map(|template| fill_artwork_template(&template, ArtworkSize::DEFAULT))
+    });
+    metadata.explicit = metadata
+        .explicit
+        .or_else(|| match field("contentRating")?.as_str()? {
+            "explicit" => Some(true),
+            "clean" => Some(false),
+            _ => None,
+        });
+    metadata.genres = metadata
+        .genres
+        .take()
+        .or_else(|| field("genreNames").and_then(as_genres));
+}
+
+/// Reads a number stored either as a JSON number or a numeric string
+fn as_number(value: &serde_json::Value) -> Option<u32> {
+    value
+        .as_u64()
+        .and_then(|n| u32::try_from(n).ok())
+        .or_else(|| value.as_str().and_then(|s| s.trim().parse().ok()))
+}
+
+/// Reads a genre given as a single name or a list of names
+pub(crate) fn as_genres(value: &serde_json::Value) -> Option<Vec<String>> {
+    let genres: Vec<String> = match value {
+        serde_json::Value::String(genre) => vec![genre.trim().to_string()],
+        serde_json::Value::Array(genres) => genres
+            .iter()
+            .filter_map(|genre| genre.as_str())
+            .map(|genre| genre.trim().to_string())
+            .collect(),
+        _ => Vec::new(),
+    };
+    let genres: Vec<String> = genres
+        .into_iter()
+        // "Podcasts" is the umbrella genre Apple lists for every show
+        .filter(|genre| !genre.is_empty() && genre != "Podcasts")
+        .collect();
+    (!genres.is_empty()).then_some(genres)
+}
+
+fn iso8601_duration() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"^P(?:(\d+)D)?(?:T(?:(\d+)H)?(?:(\d+)M)?(?:(\d+(?:\.\d+)?)S)?)?$").unwrap()
+    })
+}
+
+/// Parses an ISO 8601 duration such as `PT1H2M3S` into whole seconds
+pub fn parse_iso8601_duration(value: &str) -> Option<u64> {
+    let captures = iso8601_duration().captures(value.trim())?;
+    let part = |index: usize| {
+        captures
+            .get(index)
+            .and_then(|m| m.as_str().parse::<f64>().ok())
+            .unwrap_or(0.0)
+    };
+
+    let seconds = part(1) * 86_400.0 + part(2) * 3600.0 + part(3) * 60.0 + part(4);
+    Some(seconds as u64)
+}
+
+/// Extracts metadata from HTML meta tags as fallback
+pub(crate) fn extract_from_meta_tags(document: &Html) -> Result<Metadata> {
+    let meta_selector =
+        Selector::parse("meta").map_err(|e| Error::Parse(format!("Invalid selector: {}", e)))?;
+
+    let mut episode_title = String::new();
+    let mut description = String::new();
+    let mut show_title = String::new();
+    let mut publish_date = String::new();
+
+    for element in document.select(&meta_selector) {
+        if let Some(property) = element.value().attr("property") {
+            match property {
+                "og:title" if episode_title.is_empty() => {
+                    if let Some(content) = element.value().attr("content") {
+                        episode_title = clean_text(content);
+                    }
+                }
+                "og:description" if description.is_empty() => {
+                    if let Some(content) = element.value().attr("content") {
+                        description = content.trim().to_string();
+                    }
+                }
+                "og:site_name" if show_title.is_empty() => {
+                    if let Some(content) = element.value().attr("content") {
+                        show_title = clean_text(content);
+                    }
+                }
+                _ => {}
+            }
+        } else if let Some(name) = element.value().attr("name") {
+            match name {
+                "apple:title" => {
+                    if let Some(content) = element.value().attr("content") {
+                        if episode_title.is_empty() {
+                            episode_title = clean_text(content);
+                        }
+                    }
+                }
+                "description" | "apple:description" => {
+                    if let Some(content) = element.value().attr("content") {
+                        if description.is_empty() {
+                            description = content.trim().to_string();
+                        }
+                    }
+                }
+                _ => {}
+            }
+        } else if let Some(itemprop) = element.value().attr("itemprop") {
+            match itemprop {
+                "name" | "headline" => {
+                    if let Some(content) = element.value().attr("content") {
+                        if episode_title.is_empty() {
+                            episode_title = clean_text(content);
+                        }
+                    }
+                }
+                "description" => {
+                    if let Some(content) = element.value().attr("content") {
+                        if description.is_empty() {
+                            description = content.trim().to_string();
+                        }
+                    }
+                }
+                "publisher" => {
+                    if let Some(content) = element.value().attr("content") {
+                        if show_title.is_empty() {
+                            show_title = clean_text(content);
+                        }
+                    }
+                }
+                "datePublished" => {
+                    if let Some(content) = element.value().attr("content") {
+                        if publish_date.is_empty() {
+                            publish_date = clean_text(content);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Try to extract show title from og:description
+    if show_title.is_empty() {
+        let og_desc_selector = Selector::parse("meta[property='og:description']")
+            .map_err(|e| Error::Parse(format!("Invalid selector: {}", e)))?;
+
+        if let Some(element) = document.select(&og_desc_selector).next() {
+            if let Some(content) = element.value().attr("content") {
+                // og:description often contains "Podcast Episode · Show Name · Date"
+                let parts: Vec<&str> = content.split(" · ").collect();
+                if parts.len() >= 2 {
+                    show_title = parts[1].trim().to_string();
+                }
+            }
+        }
+    }
+
+    Ok(Metadata {
+        episode_title,
+        description,
+        show_title,
+        publish_date,
+        ..Metadata::default()
+    })
+}
+
+/// Reads the page's canonical URL (`<link rel="canonical">`), if present
+pub fn canonical_url(html: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse("link[rel='canonical']").ok()?;
+
+    document
+        .select(&selector)
+        .next()
+        .and_then(|element| element.value().attr("href"))
+        .map(str::to_string)
+}
+
+/// Cleans text into a single line: HTML tags removed, entities decoded and whitespace normalized
+pub fn clean_text(text: &str) -> String {
+    let fragment = Html::parse_fragment(text);
+    let text: String = fragment.root_element().text().collect();
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// How descriptions are stored in `metadata.json`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DescriptionFormat {
+    /// Text only, with paragraphs and line breaks kept as newlines
+    #[default]
+    Plain,
+    /// Like plain, with links kept as `[text](url)` and list items as `- item`
+    Markdown,
+    /// The description exactly as the source provides it
+    Html,
+}
+
+/// Parses a `--description-format` value
+pub fn parse_description_format(raw: &str) -> std::result::Result<DescriptionFormat, String> {
+    match raw.trim().to_lowercase().as_str() {
+        "plain" | "text" => Ok(DescriptionFormat::Plain),
+        "markdown" | "md" => Ok(DescriptionFormat::Markdown),
+        "html" => Ok(DescriptionFormat::Html),
+        _ => Err(format!(
+            "Invalid description format '{}': expected plain, markdown or html",
+            raw
+        )),
+    }
+}
+
+/// Elements rendered as separate paragraphs
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p",
+    "div",
+    "ul",
+    "ol",
+    "blockquote",
+    "pre",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "table",
+    "tr",
+];
+
+/// Converts a description, HTML or plain text, into `format`
+///
+/// Entities are decoded, `<br>` becomes a line break and block elements
+/// become paragraphs. A description without any markup keeps its own line
+/// breaks; in HTML they are just whitespace.
+pub fn format_description(text: &str, format: DescriptionFormat) -> String {
+    if format == DescriptionFormat::Html {
+        return text.trim().to_string();
+    }
+
+    let fragment = Html::parse_fragment(text);
+    let root = fragment.root_element();
+    let has_markup = root
+        .descendants()
+        .skip(1)
+        .any(|node| node.value().is_element());
+
+    let mut rendered = String::new();
+    render_description(
+        root,
+        format == DescriptionFormat::Markdown,
+        !has_markup,
+        &mut rendered,
+    );
+
+    // Normalize spacing within lines and keep at most one blank line between paragraphs
+    let mut lines: Vec<String> = Vec::new();
+    for line in rendered.lines() {
+        let line = line.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if !line.is_empty() || lines.last().is_some_and(|last| !last.is_empty()) {
+            lines.push(line);
+        }
+    }
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// Appends the text of `element`'s children to `out`, marking breaks with newlines
+fn render_description(element: ElementRef, markdown: bool, keep_newlines: bool, out: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) if keep_newlines => out.push_str(text),
+            Node::Text(text) => out.push_str(&text.replace('\n', " ")),
+            Node::Element(_) => {
+                let Some(child) = ElementRef::wrap(child) else {
+                    continue;
+                };
+                match child.value().name() {
+                    "br" => out.push('\n'),
+                    "script" | "style" => {}
+                    "a" if markdown => {
+                        let mut label = String::new();
+                        render_description(child, markdown, keep_newlines, &mut label);
+                        let label = label.split_whitespace().collect::<Vec<&str>>().join(" ");
+                        match child.value().attr("href") {
+                            Some(href) if label.is_empty() => out.push_str(href),
+                            Some(href) => out.push_str(&format!("[{}]({})", label, href)),
+                            None => out.push_str(&label),
+                        }
+                    }
+                    "li" => {
+                        out.push_str(if markdown { "\n- " } else { "\n" });
+                        render_description(child, markdown, keep_newlines, out);
+                        out.push('\n');
+                    }
+                    name if BLOCK_ELEMENTS.contains(&name) => {
+                        out.push_str("\n\n");
+                        render_description(child, markdown, keep_newlines, out);
+                        out.push_str("\n\n");
+                    }
+                    _ => render_description(child, markdown, keep_newlines, out),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Fetches the episode page from another storefront and extracts its localized text
+///
+/// Returns the storefront's HTML alongside the metadata so callers can keep a copy.
+pub async fn fetch_localized_metadata(
+    url: &str,
+    storefront: &str,
+    options: &HttpOptions,
+) -> Result<(String, LocalizedMetadata)> {
+    let localized_url = rewrite_storefront(url, storefront)?;
+    let html = fetch_page(&localized_url, options).await?;
+    let metadata = extract_metadata(&html)?;
+    let publish_date_iso8601 = parse_localized_date(&metadata.publish_date, storefront);
+
+    Ok((
+        html,
+        LocalizedMetadata {
+            episode_title: metadata.episode_title,
+            description: metadata.description,
+            show_title: metadata.show_title,
+            publish_date: metadata.publish_date,
+            publish_date_iso8601,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - Metadata struct serializes to JSON correctly
+    #[test]
+    fn test_metadata_serialization() {
+        // Given a Metadata struct with sample data
+        let metadata = Metadata {
+            episode_title: "Test Episode".to_string(),
+            description: "This is a test description".to_string(),
+            show_title: "Test Show".to_string(),
+            publish_date: "2023-10-13".to_string(),
+            publish_date_iso8601: Some("2023-10-13".to_string()),
+            ..Metadata::default()
+        };
+
+        // When we serialize it to JSON
+        let json = serde_json::to_string(&metadata).unwrap();
+
+        // Then it contains all the expected fields
+        assert!(json.contains("episode_title"));
+        assert!(json.contains("Test Episode"));
+        assert!(json.contains("description"));
+        assert!(json.contains("This is a test description"));
+        assert!(json.contains("show_title"));
+        assert!(json.contains("Test Show"));
+        assert!(json.contains("publish_date"));
+        assert!(json.contains("2023-10-13"));
+    }
+
+    /// Unit test - clean_text removes HTML tags and trims whitespace
+    #[test]
+    fn test_clean_text_removes_html_tags() {
+        // Given text with HTML tags
+        let text = "<p>Hello <strong>World</strong></p>";
+
+        // When we clean it
+        let cleaned = clean_text(text);
+
+        // Then HTML tags are removed
+        assert_eq!(cleaned, "Hello World");
+    }
+
+    /// Unit test - clean_text normalizes whitespace
+    #[test]
+    fn test_clean_text_normalizes_whitespace() {
+        // Given text with extra whitespace
+        let text = "  Hello    World  \n  Test  ";
+
+        // When we clean it
+        let cleaned = clean_text(text);
+
+        // Then whitespace is normalized
+        assert_eq!(cleaned, "Hello World Test");
+    }
+
+    /// Unit test - clean_text decodes entities and keeps a bare '<' in text
+    #[test]
+    fn test_clean_text_decodes_entities() {
+        // Given text with entities and a comparison
+        let text = "Q&amp;A: it&#39;s 3 < 5 &mdash; <em>really</em>";
+
+        // When we clean it
+        let cleaned = clean_text(text);
+
+        // Then entities are decoded and nothing after '<' is dropped
+        assert_eq!(cleaned, "Q&A: it's 3 < 5 — really");
+    }
+
+    /// Unit test - format_description renders HTML as plain text, Markdown or as given
+    #[test]
+    fn test_format_description_formats() {
+        // Given an HTML description with paragraphs, a line break, a link and a list
+        let html = "<p>Guests &amp; topics:<br>AI</p>\n<p>Links:</p><ul><li><a href=\"https://example.com\">Site</a></li></ul>";
+
+        // When we format it each way
+        let plain = format_description(html, DescriptionFormat::Plain);
+        let markdown = format_description(html, DescriptionFormat::Markdown);
+        let raw = format_description(html, DescriptionFormat::Html);
+
+        // Then breaks become newlines, links survive only in Markdown and HTML is kept as is
+        assert_eq!(plain, "Guests & topics:\nAI\n\nLinks:\n\nSite");
+        assert_eq!(
+            markdown,
+            "Guests & topics:\nAI\n\nLinks:\n\n- [Site](https://example.com)"
+        );
+        assert_eq!(raw, html);
+    }
+
+    /// Unit test - format_description keeps the line breaks of text without markup
+    #[test]
+    fn test_format_description_plain_text() {
+        // Given a plain-text description with entities and paragraphs
+        let text = "Line one &amp; more\n\n\n\nLine   two\n";
+
+        // When we format it as plain text
+        // Then entities are decoded and paragraphs kept with one blank line between
+        assert_eq!(
+            format_description(text, DescriptionFormat::Plain),
+            "Line one & more\n\nLine two"
+        );
+    }
+
+    /// Unit test - extract_metadata extracts from real Apple Podcasts HTML
+    #[test]
+    fn test_extract_metadata_from_real_html() {
+        // Given the actual episode.html file exists
+        let html_path = "output/episode.html";
+
+        // Skip test if file doesn't exist (for CI/CD environments)
+        if !std::path::Path::new(html_path).exists() {
+            return;
+        }
+
+        // When we extract metadata
+        let html = std::fs::read_to_string(html_path).unwrap();
+        let result = extract_metadata(&html);
+
+        // Then it succeeds
+        assert!(result.is_ok(), "extract_metadata should succeed");
+
+        let metadata = result.unwrap();
+
+        // And all fields are non-empty
+        assert!(
+            !metadata.episode_title.is_empty(),
+            "Episode title should not be empty"
+        );
+        assert!(
+            !metadata.description.is_empty(),
+            "Description should not be empty"
+        );
+        assert!(
+            !metadata.show_title.is_empty(),
+            "Show title should not be empty"
+        );
+        assert!(
+            !metadata.publish_date.is_empty(),
+            "Publish date should not be empty"
+        );
+
+        // And the values have reasonable content (any episode will do)
+        assert!(
+            metadata.episode_title.len() > 5,
+            "Episode title should have substantial content"
+        );
+        assert!(
+            metadata.show_title.len() > 3,
+            "Show title should have substantial content"
+        );
+        assert!(
+            metadata.publish_date.contains("-"),
+            "Publish date should be in date format"
+        );
+    }
+
+    /// Unit test - extract_metadata follows the shoebox page layout
+    #[test]
+    fn test_extract_metadata_from_shoebox_layout() {
+        // Given an older page layout whose JSON-LD block is identified by name
+        let html = r#"<html><head>
+            <script name="schema:podcast-episode" type="application/ld+json">
+            {"name": "Shoebox Episode", "datePublished": "2019-05-01", "partOfSeries": {"name": "Old Show"}}
+            </script>
+            <meta property="og:title" content="Wrong Title">
+            </head></html>"#;
+
+        // When we extract metadata
+        let metadata = extract_metadata(html).unwrap();
+
+        // Then the layout's JSON-LD block is used rather than the meta tag fallback
+        assert_eq!(metadata.episode_title, "Shoebox Episode");
+        assert_eq!(metadata.show_title, "Old Show");
+    }
+
+    /// Unit test - lite extraction matches full extraction on every known layout
+    #[test]
+    fn test_extract_metadata_lite_matches_full() {
+        // Given saved pages for each layout, including one with only meta tags
+        let pages = [
+            include_str!("../tests/fixtures/pages/serialized-server-data.html"),
+            include_str!("../tests/fixtures/pages/fastboot-shoebox.html"),
+            include_str!("../tests/fixtures/pages/meta-tags-only.html"),
+        ];
+
+        for html in pages {
+            // When we extract each page both ways
+            let full = extract_metadata_with(html, Extraction::Full).unwrap();
+            let lite = extract_metadata_with(html, Extraction::Lite).unwrap();
+
+            // Then the script-only path finds the same metadata, falling back where it must
+            assert_eq!(lite, full);
+        }
+    }
+
+    /// Unit test - extract_from_json_ld parses JSON-LD schema correctly
+    #[test]
+    fn test_extract_from_json_ld() {
+        // Given HTML with a JSON-LD schema
+        let html = r#"
+            <!DOCTYPE html>
+            <html>
+            <head>
+                <script id="schema:episode" type="application/ld+json">
+                {
+                    "name": "Test Episode Title",
+                    "description": "Test episode description",
+                    "datePublished": "2023-01-15",
+                    "partOfSeries": {
+                        "name": "Test Podcast Show"
+                    }
+                }
+                </script>
+            </head>
+            <body></body>
+            </html>
+        "#;
+
+        // When we parse it
+        let document = Html::parse_document(html);
+        let result = extract_from_json_ld(&document, "script[id='schema:episode']");
+
+        // Then it succeeds
+        assert!(result.is_ok(), "extract_from_json_ld should succeed");
+
+        let metadata = result.unwrap();
+
+        // And all fields are extracted correctly
+        assert_eq!(metadata.episode_title, "Test Episode Title");
+        assert_eq!(metadata.description, "Test episode description");
+        assert_eq!(metadata.show_title, "Test Podcast Show");
+        assert_eq!(metadata.publish_date, "2023-01-15");
+    }
+
+    /// Unit test - canonical_url reads the canonical link
+    #[test]
+    fn test_canonical_url_reads_link_tag() {
+        // Given a saved page with a canonical link and one without
+        let html = r#"<html><head><link rel="canonical" href="https://podcasts.apple.com/de/podcast/id1?i=2"></head></html>"#;
+
+        // When we read the canonical URL
+        // Then only the first page yields one
+        assert_eq!(
+            canonical_url(html).as_deref(),
+            Some("https://podcasts.apple.com/de/podcast/id1?i=2")
+        );
+        assert_eq!(canonical_url("<html></html>"), None);
+    }
+
+    /// Unit test - extract_metadata reads episode details from JSON-LD and serialized data
+    #[test]
+    fn test_extract_metadata_reads_episode_details() {
+        // Given a page whose schema has the duration and genre, and whose serialized data has the rest
+        let html = r#"<html><head>
+            <script id="schema:episode" type="application/ld+json">
+            {"name": "Episode 1", "datePublished": "2023-10-13", "timeRequired": "PT1H2M3S",
+             "genre": ["Technology", "Podcasts"], "partOfSeries": {"name": "Show"}}
+            </script></head><body>
+            <script type="application/json" id="serialized-server-data">[{"data":{"episode":{
+              "guid": "abc-123", "streamUrl": "https://audio.example.com/1.mp3",
+              "episodeNumber": 12, "seasonNumber": "3", "contentRating": "explicit",
+              "artwork": {"template": "https://is1.example.com/art/{w}x{h}{c}.{f}"}}}}]</script>
+            </body></html>"#;
+
+        // When we extract metadata
+        let metadata = extract_metadata(html).unwrap();
+
+        // Then every detail is populated
+        assert_eq!(metadata.duration_seconds, Some(3723));
+        assert_eq!(metadata.genres, Some(vec!["Technology".to_string()]));
+        assert_eq!(metadata.guid.as_deref(), Some("abc-123"));
+        assert_eq!(
+            metadata.audio_url.as_deref(),
+            Some("https://audio.example.com/1.mp3")
+        );
+        assert_eq!(metadata.episode_number, Some(12));
+        assert_eq!(metadata.season_number, Some(3));
+        assert_eq!(metadata.explicit, Some(true));
+        assert_eq!(
+            metadata.artwork_url.as_deref(),
+            Some("https://is1.example.com/art/3000x3000bb.jpg")
+        );
+    }
+
+    /// Unit test - missing metadata fields serialize as null
+    #[test]
+    fn test_missing_metadata_fields_serialize_as_null() {
+        // Given metadata with only a title
+        let metadata = Metadata {
+            episode_title: "Episode 1".to_string(),
+            ..Metadata::default()
+        };
+
+        // When we serialize it
+        let json = serde_json::to_value(&metadata).unwrap();
+
+        // Then absent text and details are null rather than empty
+        assert_eq!(json["episode_title"], "Episode 1");
+        assert!(json["description"].is_null());
+        assert!(json["duration_seconds"].is_null());
+        assert!(json["genres"].is_null());
+    }
+
+    /// Unit test - parse_iso8601_duration converts durations to seconds
+    #[test]
+    fn test_parse_iso8601_duration() {
+        // Given ISO 8601 durations
+        // When we parse them
+        // Then they become whole seconds, and other text is rejected
+        assert_eq!(parse_iso8601_duration("PT45M"), Some(2700));
+        assert_eq!(parse_iso8601_duration("PT1H2M3.5S"), Some(3723));
+        assert_eq!(parse_iso8601_duration("P1DT1S"), Some(86_401));
+        assert_eq!(parse_iso8601_duration("45 min"), None);
+    }
+
+    /// Unit test - fill_missing only fills fields that are empty
+    #[test]
+    fn test_fill_missing_keeps_present_fields() {
+        // Given scraped metadata with a title but no GUID, and API metadata with both
+        // and a sanitized description
+        let mut scraped = Metadata {
+            episode_title: "Scraped title".to_string(),
+            ..Metadata::default()
+        };
+        let sanitized = Sanitization {
+            trackers: vec!["https://pixel.example/t.gif".to_string()],
+            ..Sanitization::default()
+        };
+        let api = Metadata {
+            episode_title: "API title".to_string(),
+            description: "<p>Notes</p>".to_string(),
+            guid: Some("abc-123".to_string()),
+            sanitized: Some(sanitized.clone()),
+            ..Metadata::default()
+        };
+
+        // When we fill the scraped metadata from the API
+        scraped.fill_missing(api);
+
+        // Then the scraped title is kept, and the missing GUID and description are
+        // filled along with the description's sanitization record
+        assert_eq!(scraped.episode_title, "Scraped title");
+        assert_eq!(scraped.guid.as_deref(), Some("abc-123"));
+        assert_eq!(scraped.description, "<p>Notes</p>");
+        assert_eq!(scraped.sanitized, Some(sanitized));
+    }
+}
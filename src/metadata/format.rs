@@ -0,0 +1,331 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+use crate::batch::csv_field;
+use crate::error::{Error, Result};
+use crate::metadata::Metadata;
+use crate::output::{to_sorted_json, write_file};
+
+/// `metadata.json` fields written as CSV columns, after the episode URL
+///
+/// Nested fields (localized metadata, ad segments, overrides) stay in the
+/// JSON; genres are joined with `; `.
+const CSV_FIELDS: [&str; 16] = [
+    "episode_title",
+    "show_title",
+    "publish_date",
+    "publish_date_iso8601",
+    "duration_seconds",
+    "episode_number",
+    "season_number",
+    "guid",
+    "audio_url",
+    "feed_url",
+    "artwork_url",
+    "explicit",
+    "genres",
+    "description_language",
+    "transcript_language",
+    "description",
+];
+
+/// Serializes updates to the shared CSV so concurrent batch episodes do not lose each other's rows
+static CSV_LOCK: Mutex<()> = Mutex::new(());
+
+/// Formats metadata can be saved in besides `metadata.json`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataFormat {
+    /// `metadata.json`, which is always written
+    Json,
+    /// One row per episode appended to `metadata.csv` in the output directory
+    Csv,
+    Yaml,
+    Toml,
+    /// Markdown with the metadata as YAML front matter and the description as the body
+    FrontmatterMd,
+}
+
+impl MetadataFormat {
+    /// File name the format is saved as
+    pub fn file_name(self) -> &'static str {
+        match self {
+            MetadataFormat::Json => "metadata.json",
+            MetadataFormat::Csv => "metadata.csv",
+            MetadataFormat::Yaml => "metadata.yaml",
+            MetadataFormat::Toml => "metadata.toml",
+            MetadataFormat::FrontmatterMd => "metadata.md",
+        }
+    }
+}
+
+/// Parses a `--metadata-format` value
+pub fn parse_metadata_format(raw: &str) -> std::result::Result<MetadataFormat, String> {
+    match raw.trim().to_lowercase().as_str() {
+        "json" => Ok(MetadataFormat::Json),
+        "csv" => Ok(MetadataFormat::Csv),
+        "yaml" | "yml" => Ok(MetadataFormat::Yaml),
+        "toml" => Ok(MetadataFormat::Toml),
+        "frontmatter-md" | "frontmatter" | "md" => Ok(MetadataFormat::FrontmatterMd),
+        _ => Err(format!(
+            "Invalid metadata format '{}': expected json, csv, yaml, toml or frontmatter-md",
+            raw
+        )),
+    }
+}
+
+/// Saves metadata for `url` in `format`, returning the path written
+///
+/// CSV rows go to `metadata.csv` in `output_dir`, one per URL and sorted by
+/// URL, so a batch run or repeated runs build one spreadsheet that comes out
+/// the same whatever order episodes finish in. The other formats are written
+/// to `episode_dir`.
+pub fn save_metadata_as(
+    metadata: &Metadata,
+    url: &str,
+    format: MetadataFormat,
+    output_dir: &str,
+    episode_dir: &str,
+) -> Result<String> {
+    let fields = serde_json::to_value(metadata)?;
+    if format == MetadataFormat::Csv {
+        let path = format!("{}/{}", output_dir, format.file_name());
+        upsert_csv_row(&path, url, &csv_row(url, &fields))?;
+        return Ok(path);
+    }
+
+    let path = format!("{}/{}", episode_dir, format.file_name());
+    let text = match format {
+        MetadataFormat::Json => to_sorted_json(&fields)?,
+        MetadataFormat::Yaml => to_yaml(&fields),
+        MetadataFormat::Toml => toml::to_string(&without_nulls(fields))
+            .map_err(|e| Error::Parse(format!("Failed to write TOML: {}", e)))?,
+        MetadataFormat::FrontmatterMd => to_frontmatter_md(&fields),
+        MetadataFormat::Csv => unreachable!("CSV rows are written above"),
+    };
+    write_file(&path, text)?;
+    Ok(path)
+}
+
+/// The CSV header row
+fn csv_header() -> String {
+    let mut columns = vec!["url"];
+    columns.extend(CSV_FIELDS);
+    columns.join(",")
+}
+
+/// One CSV row for an episode
+fn csv_row(url: &str, fields: &Value) -> String {
+    let mut row = vec![csv_field(url)];
+    for field in CSV_FIELDS {
+        let value = match &fields[field] {
+            Value::Null => String::new(),
+            Value::String(text) => text.clone(),
+            Value::Array(items) => items
+                .iter()
+                .map(|item| {
+                    item.as_str()
+                        .map_or_else(|| item.to_string(), str::to_string)
+                })
+                .collect::<Vec<_>>()
+                .join("; "),
+            value => value.to_string(),
+        };
+        row.push(csv_field(&value));
+    }
+    row.join(",")
+}
+
+/// Adds or replaces the row for `url` in a CSV file, rewriting it under a header with rows sorted
+fn upsert_csv_row(path: &str, url: &str, row: &str) -> Result<()> {
+    let _guard = CSV_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let existing = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(Error::io("Failed to read metadata CSV")(e)),
+    };
+
+    // The URL is the first field, so a row's key is its quoted URL and the comma after it
+    let key = format!("{},", csv_field(url));
+    let mut rows: Vec<&str> = csv_records(&existing)
+        .into_iter()
+        .skip(1)
+        .filter(|record| !record.starts_with(&key))
+        .collect();
+    rows.push(row);
+    rows.sort_unstable();
+
+    let mut text = csv_header();
+    text.push('\n');
+    for row in rows {
+        text.push_str(row);
+        text.push('\n');
+    }
+    write_file(path, text)
+}
+
+/// Splits CSV text into records, keeping line breaks inside quoted fields
+fn csv_records(text: &str) -> Vec<&str> {
+    let mut records = Vec::new();
+    let (mut start, mut quoted) = (0, false);
+    for (index, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '\n' if !quoted => {
+                records.push(&text[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    records.push(&text[start..]);
+    records.retain(|record| !record.is_empty());
+    records
+}
+
+/// YAML with one top-level key per field
+///
+/// Values are written as JSON, which YAML reads as the same strings, numbers,
+/// lists and maps, so text with colons or quotes needs no special escaping.
+fn to_yaml(fields: &Value) -> String {
+    let Value::Object(fields) = fields else {
+        return String::new();
+    };
+    fields
+        .iter()
+        .map(|(key, value)| format!("{}: {}\n", key, value))
+        .collect()
+}
+
+/// Markdown with every field but the description as YAML front matter, and the description as the body
+fn to_frontmatter_md(fields: &Value) -> String {
+    let mut front = fields.clone();
+    let description = front
+        .as_object_mut()
+        .and_then(|fields| fields.remove("description"));
+
+    let mut page = format!("---\n{}---\n", to_yaml(&front));
+    if let Some(description) = description
+        .as_ref()
+        .and_then(Value::as_str)
+        .filter(|text| !text.trim().is_empty())
+    {
+        page.push('\n');
+        page.push_str(description.trim());
+        page.push('\n');
+    }
+    page
+}
+
+/// Drops `null` values, which TOML cannot represent
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, without_nulls(value)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(without_nulls).collect()),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use tempfile::TempDir;
+
+    fn sample_metadata() -> Metadata {
+        Metadata {
+            episode_title: "Q&A: \"Money\", answered".to_string(),
+            description: "Line one.\nLine two.".to_string(),
+            show_title: "Back to the Board".to_string(),
+            episode_number: Some(12),
+            genres: Some(vec!["Business".to_string(), "Careers".to_string()]),
+            overridden: Some(BTreeMap::from([("episode_title".to_string(), Value::Null)])),
+            ..Metadata::default()
+        }
+    }
+
+    /// Unit test - CSV keeps one sorted row per URL under a single header, quoted where needed
+    #[test]
+    fn test_save_metadata_as_csv_keeps_sorted_rows() {
+        // Given an output directory with no CSV yet
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+
+        // When two episodes are saved as CSV out of order, and the first is saved again
+        let metadata = sample_metadata();
+        let path =
+            save_metadata_as(&metadata, "https://b", MetadataFormat::Csv, dir, "ep2").unwrap();
+        save_metadata_as(&metadata, "https://a", MetadataFormat::Csv, dir, "ep1").unwrap();
+        save_metadata_as(&metadata, "https://b", MetadataFormat::Csv, dir, "ep2").unwrap();
+
+        // Then the file has one header and a row per episode, quoting commas, quotes and newlines
+        let csv = fs::read_to_string(&path).unwrap();
+        assert!(path.ends_with("metadata.csv"));
+        assert_eq!(csv.matches("url,episode_title,").count(), 1);
+        assert!(csv.contains(
+            "\nhttps://a,\"Q&A: \"\"Money\"\", answered\",Back to the Board,,,,12,,,,,,,Business; Careers,,,\"Line one.\nLine two.\"\n"
+        ));
+        assert_eq!(csv.matches("\nhttps://b,").count(), 1);
+
+        // And rows are sorted by URL
+        assert!(csv.find("\nhttps://a,") < csv.find("\nhttps://b,"));
+    }
+
+    /// Unit test - YAML, TOML and front-matter Markdown carry the same fields
+    #[test]
+    fn test_save_metadata_as_text_formats() {
+        // Given extracted metadata with nested overrides
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        let metadata = sample_metadata();
+        let save = |format| {
+            let path = save_metadata_as(&metadata, "https://a", format, dir, dir).unwrap();
+            fs::read_to_string(path).unwrap()
+        };
+
+        // When we save it as YAML, TOML and front-matter Markdown
+        let yaml = save(MetadataFormat::Yaml);
+        let toml_text = save(MetadataFormat::Toml);
+        let markdown = save(MetadataFormat::FrontmatterMd);
+
+        // Then YAML quotes text and keeps nulls, lists and maps
+        assert!(yaml.contains("episode_title: \"Q&A: \\\"Money\\\", answered\"\n"));
+        assert!(yaml.contains("guid: null\n"));
+        assert!(yaml.contains("genres: [\"Business\",\"Careers\"]\n"));
+
+        // And TOML parses back, leaving out the missing fields
+        let parsed: toml::Table = toml::from_str(&toml_text).unwrap();
+        assert_eq!(parsed["episode_number"].as_integer(), Some(12));
+        assert!(!parsed.contains_key("guid"));
+
+        // And the Markdown body is the description, outside the front matter
+        assert!(markdown.starts_with("---\nartwork_url: null\n"));
+        assert!(markdown.ends_with("---\n\nLine one.\nLine two.\n"));
+        assert!(!markdown.contains("description:"));
+    }
+
+    /// Unit test - parse_metadata_format accepts names and aliases
+    #[test]
+    fn test_parse_metadata_format() {
+        // Given format arguments
+        // When we parse them
+        // Then names and aliases map to formats and unknown names are rejected
+        assert_eq!(parse_metadata_format("CSV"), Ok(MetadataFormat::Csv));
+        assert_eq!(parse_metadata_format("yml"), Ok(MetadataFormat::Yaml));
+        assert_eq!(
+            parse_metadata_format("frontmatter-md"),
+            Ok(MetadataFormat::FrontmatterMd)
+        );
+        assert!(parse_metadata_format("xml").is_err());
+    }
+}
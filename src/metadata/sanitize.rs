@@ -0,0 +1,364 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use regex::{Captures, Regex};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::artwork::download_artwork;
+use crate::error::Result;
+use crate::fetch::HttpOptions;
+use crate::output::write_file;
+
+/// Directory under the output directory that archived description images are saved in
+pub const IMAGES_DIR: &str = "images";
+
+/// Hosts that serve tracking pixels and click trackers in podcast show notes
+pub const TRACKER_HOSTS: &[&str] = &[
+    "pixel.wp.com",
+    "feeds.feedburner.com",
+    "pdst.fm",
+    "podtrac.com",
+    "chtbl.com",
+    "chartable.com",
+    "op3.dev",
+    "google-analytics.com",
+    "doubleclick.net",
+    "list-manage.com",
+    "podscribe.com",
+];
+
+/// Query parameters that identify a click rather than a page, stripped from links and images
+const TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "mc_cid", "mc_eid", "igshid"];
+
+/// How much of a description's markup is cleaned before it is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SanitizeMode {
+    /// The description as the source provides it
+    #[default]
+    None,
+    /// Tracking pixels removed and tracking parameters stripped from links and images
+    Strip,
+    /// Like strip, with images rewritten to copies in the images directory
+    Archive,
+}
+
+/// Parses a `--sanitize-description` value
+pub fn parse_sanitize_mode(raw: &str) -> std::result::Result<SanitizeMode, String> {
+    match raw.trim().to_lowercase().as_str() {
+        "none" | "off" => Ok(SanitizeMode::None),
+        "strip" => Ok(SanitizeMode::Strip),
+        "archive" => Ok(SanitizeMode::Archive),
+        _ => Err(format!(
+            "Invalid sanitize policy '{}': expected none, strip or archive",
+            raw
+        )),
+    }
+}
+
+/// Which trackers are removed from descriptions and where kept images go
+///
+/// Hosts in `tracker_hosts` are matched along with [`TRACKER_HOSTS`], so
+/// trackers missing from the built-in list can be added without a release.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SanitizePolicy {
+    pub mode: SanitizeMode,
+    pub tracker_hosts: Vec<String>,
+}
+
+/// What sanitizing a description removed or rewrote, recorded in `metadata.json`
+#[derive(Debug, Serialize, Clone, PartialEq, Default)]
+pub struct Sanitization {
+    /// Image URLs of the tracking pixels removed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub trackers: Vec<String>,
+    /// Image URLs embedded again after their first use, whose repeats were removed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<String>,
+    /// Link and image URLs whose tracking parameters were stripped, as they were
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stripped_urls: Vec<String>,
+    /// Image URLs mapped to the path of their archived copy
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub images: BTreeMap<String, String>,
+}
+
+impl Sanitization {
+    /// Whether nothing was removed or rewritten
+    pub fn is_empty(&self) -> bool {
+        self.trackers.is_empty()
+            && self.duplicates.is_empty()
+            && self.stripped_urls.is_empty()
+            && self.images.is_empty()
+    }
+
+    /// Records a URL once, however often the description repeats it
+    fn note(list: &mut Vec<String>, url: &str) {
+        if !list.iter().any(|seen| seen == url) {
+            list.push(url.to_string());
+        }
+    }
+}
+
+impl SanitizePolicy {
+    /// Whether an image URL belongs to a known tracker
+    fn is_tracker_host(&self, url: &str) -> bool {
+        let Some(host) = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase))
+        else {
+            return false;
+        };
+        TRACKER_HOSTS
+            .iter()
+            .copied()
+            .chain(self.tracker_hosts.iter().map(String::as_str))
+            .any(|tracker| host == tracker || host.ends_with(&format!(".{}", tracker)))
+    }
+}
+
+/// Removes tracking pixels and repeated images from description HTML and strips tracking parameters from its URLs
+///
+/// Images are tracking pixels when they are at most 1×1, hidden, or served
+/// by a tracker host; an image repeated later in the description is kept
+/// only where it first appears. In [`SanitizeMode::Archive`], the remaining
+/// images point at `<images_dir>/<name>` instead, one file per image URL
+/// however many episodes embed it; [`archive_images`] downloads them.
+/// Plain-text descriptions come back unchanged.
+pub fn sanitize_description(
+    html: &str,
+    policy: &SanitizePolicy,
+    images_dir: &str,
+) -> (String, Sanitization) {
+    let mut sanitization = Sanitization::default();
+    if policy.mode == SanitizeMode::None {
+        return (html.to_string(), sanitization);
+    }
+    let (Ok(img), Ok(href)) = (
+        Regex::new(r#"(?is)<img\b[^>]*>"#),
+        Regex::new(r#"(?i)(\shref\s*=\s*)("[^"]*"|'[^']*')"#),
+    ) else {
+        return (html.to_string(), sanitization);
+    };
+
+    let mut seen = Vec::new();
+    let without_pixels = img.replace_all(html, |captures: &Captures| {
+        let tag = &captures[0];
+        let src = attribute(tag, "src").unwrap_or_default();
+        if is_tracking_pixel(tag) || policy.is_tracker_host(&src) {
+            Sanitization::note(&mut sanitization.trackers, &src);
+            return String::new();
+        }
+
+        let Some(cleaned) = strip_tracking_params(&src) else {
+            return tag.to_string();
+        };
+        if cleaned != src {
+            Sanitization::note(&mut sanitization.stripped_urls, &src);
+        }
+        if seen.contains(&cleaned) {
+            Sanitization::note(&mut sanitization.duplicates, &cleaned);
+            return String::new();
+        }
+        seen.push(cleaned.clone());
+        let replacement = if policy.mode == SanitizeMode::Archive {
+            let path = format!("{}/{}", images_dir, archived_image_name(&cleaned));
+            sanitization.images.insert(cleaned, path.clone());
+            path
+        } else {
+            cleaned
+        };
+        replace_attribute(tag, "src", &replacement)
+    });
+
+    let sanitized = href.replace_all(&without_pixels, |captures: &Captures| {
+        let quoted = &captures[2];
+        let url = quoted[1..quoted.len() - 1].replace("&amp;", "&");
+        match strip_tracking_params(&url) {
+            Some(cleaned) if cleaned != url => {
+                Sanitization::note(&mut sanitization.stripped_urls, &url);
+                format!("{}\"{}\"", &captures[1], cleaned.replace('&', "&amp;"))
+            }
+            _ => captures[0].to_string(),
+        }
+    });
+
+    (sanitized.into_owned(), sanitization)
+}
+
+/// File name an image URL is archived under: a hash of the URL, keeping its extension
+pub fn archived_image_name(url: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+    let extension = Url::parse(url)
+        .ok()
+        .and_then(|url| {
+            Path::new(url.path())
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+        })
+        .filter(|ext| ext.len() <= 4 && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or_else(|| "img".to_string());
+    format!("{}.{}", &hash[..16], extension)
+}
+
+/// Downloads each archived image whose copy is not on disk yet, returning the URLs that failed with why
+pub async fn archive_images(
+    images: &BTreeMap<String, String>,
+    options: &HttpOptions,
+) -> Vec<(String, String)> {
+    let mut failed = Vec::new();
+    for (url, path) in images {
+        if Path::new(path).exists() {
+            continue;
+        }
+        let saved: Result<()> = async {
+            let image = download_artwork(url, options).await?;
+            write_file(path, image)
+        }
+        .await;
+        if let Err(e) = saved {
+            failed.push((url.clone(), e.to_string()));
+        }
+    }
+    failed
+}
+
+/// Whether an `<img>` tag is sized or styled to be invisible
+fn is_tracking_pixel(tag: &str) -> bool {
+    let tiny = |name| {
+        attribute(tag, name)
+            .and_then(|value| value.trim().trim_end_matches("px").parse::<u32>().ok())
+            .is_some_and(|size| size <= 1)
+    };
+    let hidden = attribute(tag, "style").is_some_and(|style| {
+        let style: String = style.to_lowercase().split_whitespace().collect();
+        style.contains("display:none") || style.contains("visibility:hidden")
+    });
+    (tiny("width") && tiny("height")) || hidden
+}
+
+/// The URL without `utm_*` and click-ID parameters, or `None` if it is not an absolute URL
+fn strip_tracking_params(raw: &str) -> Option<String> {
+    let mut url = Url::parse(raw).ok()?;
+    let is_tracking = |name: &str| {
+        let name = name.to_lowercase();
+        name.starts_with("utm_") || TRACKING_PARAMS.contains(&name.as_str())
+    };
+    if !url.query_pairs().any(|(name, _)| is_tracking(&name)) {
+        return Some(raw.to_string());
+    }
+
+    let kept: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| !is_tracking(name))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    if kept.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(kept);
+    }
+    Some(url.to_string())
+}
+
+/// An attribute's value in an HTML tag, with `&amp;` decoded
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let pattern = format!(r#"(?i)\s{}\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#, name);
+    let captures = Regex::new(&pattern).ok()?.captures(tag)?;
+    let value = captures
+        .get(1)
+        .or_else(|| captures.get(2))
+        .or_else(|| captures.get(3))?;
+    Some(value.as_str().replace("&amp;", "&"))
+}
+
+/// The tag with one attribute's value replaced, double-quoted
+fn replace_attribute(tag: &str, name: &str, value: &str) -> String {
+    let pattern = format!(r#"(?i)(\s{}\s*=\s*)(?:"[^"]*"|'[^']*'|[^\s>]+)"#, name);
+    match Regex::new(&pattern) {
+        Ok(re) => re
+            .replace(tag, |captures: &Captures| {
+                format!("{}\"{}\"", &captures[1], value.replace('&', "&amp;"))
+            })
+            .into_owned(),
+        Err(_) => tag.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTES: &str = r#"<p>Show notes <a href="https://shop.example/?utm_source=pod&amp;id=7">shop</a></p><img src="https://cdn.example/cover.jpg?utm_medium=rss" alt="Cover"><img width="1" height="1" src="https://stats.example/p.gif"><img src="https://www.podtrac.com/pts/redirect.gif"><img src="https://cdn.example/cover.jpg?utm_medium=rss">"#;
+
+    fn policy(mode: SanitizeMode) -> SanitizePolicy {
+        SanitizePolicy {
+            mode,
+            tracker_hosts: Vec::new(),
+        }
+    }
+
+    /// Unit test - sanitize_description strips pixels, repeats and tracking parameters, recording each
+    #[test]
+    fn test_sanitize_description_strips_trackers() {
+        // Given show notes with a tagged link, a tagged image shown twice, a 1x1 pixel and a tracker-host image
+        // When we sanitize them with the strip policy
+        let (html, sanitization) =
+            sanitize_description(NOTES, &policy(SanitizeMode::Strip), "output/images");
+
+        // Then both trackers are gone and the link and images keep only their real parameters
+        assert_eq!(
+            html,
+            r#"<p>Show notes <a href="https://shop.example/?id=7">shop</a></p><img src="https://cdn.example/cover.jpg" alt="Cover">"#
+        );
+        assert_eq!(
+            sanitization.trackers,
+            vec![
+                "https://stats.example/p.gif",
+                "https://www.podtrac.com/pts/redirect.gif"
+            ]
+        );
+        assert_eq!(
+            sanitization.duplicates,
+            vec!["https://cdn.example/cover.jpg"]
+        );
+        assert_eq!(sanitization.stripped_urls.len(), 2);
+        assert!(sanitization.images.is_empty());
+
+        // And with no policy the description is untouched
+        let (unchanged, nothing) =
+            sanitize_description(NOTES, &policy(SanitizeMode::None), "output/images");
+        assert_eq!(unchanged, NOTES);
+        assert!(nothing.is_empty());
+    }
+
+    /// Unit test - sanitize_description archives each image URL once and honours extra tracker hosts
+    #[test]
+    fn test_sanitize_description_archives_images() {
+        // Given the same show notes, with the CDN added as a tracker host for a second run
+        let archive = policy(SanitizeMode::Archive);
+        let blocked = SanitizePolicy {
+            tracker_hosts: vec!["cdn.example".to_string()],
+            ..archive.clone()
+        };
+
+        // When we sanitize them with the archive policy
+        let (html, sanitization) = sanitize_description(NOTES, &archive, "output/images");
+
+        // Then the repeated image maps to one archived copy, named from its cleaned URL
+        let name = archived_image_name("https://cdn.example/cover.jpg");
+        assert!(name.ends_with(".jpg") && name.len() == 20);
+        let path = format!("output/images/{}", name);
+        assert_eq!(
+            sanitization.images,
+            BTreeMap::from([("https://cdn.example/cover.jpg".to_string(), path.clone())])
+        );
+        assert!(html.ends_with(&format!(r#"<img src="{}" alt="Cover">"#, path)));
+
+        // And an extra tracker host removes its images too
+        let (html, sanitization) = sanitize_description(NOTES, &blocked, "output/images");
+        assert!(!html.contains("<img"));
+        assert_eq!(sanitization.trackers.len(), 3);
+    }
+}
@@ -0,0 +1,582 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::ads::{build_skip_list, AdSegment};
+use crate::apple_url::{episode_id_from_url, storefront_from_url};
+use crate::compress::{write_artifact, Compression};
+use crate::error::{Error, Result};
+use crate::metadata::format::MetadataFormat;
+use crate::metadata::sanitize::SanitizePolicy;
+use crate::metadata::{DescriptionFormat, Metadata};
+use crate::overrides::DEFAULT_OVERRIDES_DIR;
+use crate::show::{EpisodeSummary, ShowMetadata};
+use crate::transcript::convert::TranscriptFormat;
+use crate::transcript::Transcript;
+
+/// Machine-readable summary of a single-episode run, printed with `--json`
+#[derive(Debug, Serialize, Clone, PartialEq, Default)]
+pub struct RunReport {
+    pub url: String,
+    pub metadata: Option<Metadata>,
+    pub transcript_url: Option<String>,
+    /// Every file written during the run, in order
+    pub outputs: Vec<String>,
+}
+
+impl RunReport {
+    /// An empty report for `url`
+    pub fn new(url: &str) -> RunReport {
+        RunReport {
+            url: url.to_string(),
+            ..RunReport::default()
+        }
+    }
+
+    /// Adds what a concurrently run step recorded in its own report
+    ///
+    /// Outputs are appended after this report's own, so merging parts in a
+    /// fixed order lists files the same way however the steps interleaved.
+    pub fn merge(&mut self, part: RunReport) {
+        if self.metadata.is_none() {
+            self.metadata = part.metadata;
+        }
+        if self.transcript_url.is_none() {
+            self.transcript_url = part.transcript_url;
+        }
+        self.outputs.extend(part.outputs);
+    }
+}
+
+/// Summary index written after crawling a show
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ShowIndex {
+    pub show: ShowMetadata,
+    pub episodes: Vec<IndexEntry>,
+}
+
+/// One processed episode in a show index
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct IndexEntry {
+    #[serde(flatten)]
+    pub episode: EpisodeSummary,
+    pub directory: String,
+    pub transcript: bool,
+    /// Already saved and unchanged, so not fetched again
+    pub skipped: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Placeholders understood by `--name-template`
+pub const TEMPLATE_PLACEHOLDERS: &[&str] = &["show", "title", "date", "id", "storefront"];
+
+/// Values substituted into a name template for one episode
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NameFields {
+    pub show: String,
+    pub title: String,
+    pub date: String,
+    pub id: String,
+    pub storefront: String,
+}
+
+impl NameFields {
+    /// Builds the fields from an episode URL and, when available, its metadata
+    pub fn new(url: &str, metadata: Option<&Metadata>) -> NameFields {
+        NameFields {
+            show: metadata.map(|m| m.show_title.clone()).unwrap_or_default(),
+            title: metadata
+                .map(|m| m.episode_title.clone())
+                .unwrap_or_default(),
+            date: metadata
+                .and_then(|m| m.publish_date_iso8601.clone())
+                .unwrap_or_else(|| "undated".to_string()),
+            id: episode_id_from_url(url).unwrap_or_default(),
+            storefront: storefront_from_url(url),
+        }
+    }
+
+    fn get(&self, placeholder: &str) -> &str {
+        match placeholder {
+            "show" => &self.show,
+            "title" => &self.title,
+            "date" => &self.date,
+            "id" => &self.id,
+            _ => &self.storefront,
+        }
+    }
+}
+
+/// Where episode artifacts are written: a base directory plus an optional name template
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputLayout {
+    pub dir: String,
+    pub template: Option<String>,
+    /// Directory of `<episode-id>.toml` metadata overrides applied when metadata is saved
+    pub overrides_dir: String,
+    /// How descriptions are written to `metadata.json`
+    pub description_format: DescriptionFormat,
+    /// Which trackers and images are cleaned from descriptions before they are formatted
+    pub sanitize: SanitizePolicy,
+    /// Formats metadata is also saved in, besides `metadata.json`
+    pub metadata_formats: Vec<MetadataFormat>,
+    /// How HTML snapshots and TTML transcripts are compressed; `None` stores them plain
+    pub compression: Option<Compression>,
+    /// The `--backup-friendly` store that saved files are also added to; `None` when off
+    pub backup_dir: Option<String>,
+}
+
+impl Default for OutputLayout {
+    fn default() -> Self {
+        OutputLayout {
+            dir: "output".to_string(),
+            template: None,
+            overrides_dir: DEFAULT_OVERRIDES_DIR.to_string(),
+            description_format: DescriptionFormat::default(),
+            sanitize: SanitizePolicy::default(),
+            metadata_formats: Vec::new(),
+            compression: None,
+            backup_dir: None,
+        }
+    }
+}
+
+impl OutputLayout {
+    /// Path of a file directly in the base directory
+    pub fn path(&self, file_name: &str) -> String {
+        format!("{}/{}", self.dir, file_name)
+    }
+
+    /// Writes an HTML snapshot or TTML transcript with the layout's compression, returning the path written
+    pub fn write_artifact(&self, path: &str, content: impl AsRef<[u8]>) -> Result<String> {
+        write_artifact(path, content, self.compression)
+    }
+
+    /// Directory for one episode's artifacts; the base directory when no template is set
+    pub fn episode_dir(&self, fields: &NameFields) -> String {
+        match &self.template {
+            Some(template) => format!("{}/{}", self.dir, render_name_template(template, fields)),
+            None => self.dir.clone(),
+        }
+    }
+}
+
+/// Validates a `--name-template` argument, rejecting unknown placeholders
+pub fn parse_name_template(raw: &str) -> std::result::Result<String, String> {
+    let mut rest = raw;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("Invalid name template '{}': unclosed '{{'", raw))?;
+        let placeholder = &rest[start + 1..start + end];
+        if !TEMPLATE_PLACEHOLDERS.contains(&placeholder) {
+            return Err(format!(
+                "Invalid name template '{}': unknown placeholder '{{{}}}', expected one of {}",
+                raw,
+                placeholder,
+                TEMPLATE_PLACEHOLDERS
+                    .iter()
+                    .map(|p| format!("{{{}}}", p))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        rest = &rest[start + end + 1..];
+    }
+
+    if raw.trim_matches('/').is_empty() {
+        return Err("Invalid name template: it is empty".to_string());
+    }
+
+    Ok(raw.to_string())
+}
+
+/// Renders a name template into a relative path
+///
+/// Values are sanitized before substitution so they can never add path
+/// segments; each `/`-separated segment of the result is sanitized again.
+pub fn render_name_template(template: &str, fields: &NameFields) -> String {
+    let mut rendered = template.to_string();
+    for placeholder in TEMPLATE_PLACEHOLDERS {
+        let value = fields.get(placeholder);
+        let value = if value.trim().is_empty() {
+            "untitled".to_string()
+        } else {
+            sanitize_file_name(value)
+        };
+        rendered = rendered.replace(&format!("{{{}}}", placeholder), &value);
+    }
+
+    rendered
+        .split('/')
+        .filter(|segment| !segment.trim().is_empty())
+        .map(sanitize_file_name)
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+/// Longest file name (in characters, before the extension) produced by `sanitize_file_name`
+const MAX_FILE_NAME_CHARS: usize = 150;
+
+/// Makes a string safe to use as a file name on Windows, macOS and Linux
+///
+/// Path separators, reserved characters and control characters become `_`,
+/// whitespace is collapsed, and trailing dots/spaces are trimmed.
+pub fn sanitize_file_name(name: &str) -> String {
+    let replaced: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_whitespace() => ' ',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    let collapsed = replaced.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let truncated: String = collapsed.chars().take(MAX_FILE_NAME_CHARS).collect();
+    let trimmed = truncated
+        .trim_end_matches(['.', ' '])
+        .trim_start_matches('.');
+
+    if trimmed.is_empty() {
+        "untitled".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Writes content to a file, creating its parent directory if needed
+pub fn write_file(output_path: &str, content: impl AsRef<[u8]>) -> Result<()> {
+    if let Some(parent) = Path::new(output_path).parent() {
+        fs::create_dir_all(parent).map_err(Error::io("Failed to create output directory"))?;
+    }
+
+    fs::write(output_path, content).map_err(Error::io("Failed to write file"))
+}
+
+/// Pretty-printed JSON with every object's keys in sorted order
+///
+/// Structs would otherwise serialize in field declaration order, which shifts
+/// whenever a field is added. With sorted keys, identical data is written as
+/// identical bytes by every version of the tool, so archives kept under git or
+/// in deduplicating backups only change when the data does.
+pub fn to_sorted_json<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&serde_json::to_value(value)?)?)
+}
+
+/// Writes a value to a file as pretty-printed JSON with sorted keys
+pub fn write_json<T: Serialize + ?Sized>(value: &T, output_path: &str) -> Result<()> {
+    write_file(output_path, to_sorted_json(value)?)
+}
+
+/// Saves metadata to a JSON file
+pub fn save_metadata_json(metadata: &Metadata, output_path: &str) -> Result<()> {
+    write_json(metadata, output_path)
+}
+
+/// Saves ad segments as a JSON skip-list keyed by the episode identifier
+pub fn save_skip_list(episode_key: &str, segments: &[AdSegment], output_path: &str) -> Result<()> {
+    let mut skip_list = serde_json::Map::new();
+    skip_list.insert(
+        episode_key.to_string(),
+        serde_json::to_value(build_skip_list(segments))?,
+    );
+
+    write_json(&skip_list, output_path)
+}
+
+/// Extension of the paragraph segments saved next to every TTML transcript
+pub const SEGMENTS_EXTENSION: &str = "segments.json";
+
+/// Saves the transcript's speaker-attributed paragraphs next to its TTML file, returning the new path
+pub fn save_transcript_segments(transcript: &Transcript, ttml_path: &str) -> Result<String> {
+    let path = Path::new(ttml_path)
+        .with_extension(SEGMENTS_EXTENSION)
+        .to_string_lossy()
+        .into_owned();
+
+    write_json(&transcript.segments()?, &path)?;
+    Ok(path)
+}
+
+/// Saves a converted copy of the transcript next to its TTML file, returning the new path
+pub fn save_converted_transcript(
+    transcript: &Transcript,
+    format: TranscriptFormat,
+    ttml_path: &str,
+) -> Result<String> {
+    let path = Path::new(ttml_path)
+        .with_extension(format.extension())
+        .to_string_lossy()
+        .into_owned();
+
+    write_file(&path, transcript.convert(format)?)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - RunReport::merge appends a part's outputs and fills what is missing
+    #[test]
+    fn test_run_report_merge() {
+        // Given a report with the page saved, and a transcript step's own report
+        let mut report = RunReport::new("https://a");
+        report.outputs.push("page.html".to_string());
+        let part = RunReport {
+            transcript_url: Some("https://t".to_string()),
+            outputs: vec!["transcript.ttml".to_string()],
+            ..RunReport::default()
+        };
+
+        // When we merge it
+        report.merge(part);
+
+        // Then its outputs follow the report's own and its transcript URL is kept
+        assert_eq!(report.outputs, vec!["page.html", "transcript.ttml"]);
+        assert_eq!(report.transcript_url.as_deref(), Some("https://t"));
+        assert_eq!(report.url, "https://a");
+    }
+
+    fn sample_fields() -> NameFields {
+        NameFields {
+            show: "Back to the Board".to_string(),
+            title: "Q&A: What's next?".to_string(),
+            date: "2023-10-13".to_string(),
+            id: "1000631244436".to_string(),
+            storefront: "us".to_string(),
+        }
+    }
+
+    /// Unit test - render_name_template substitutes sanitized values
+    #[test]
+    fn test_render_name_template_substitutes_sanitized_values() {
+        // Given a template with directory separators and placeholders
+        // When we render it for an episode with reserved characters in its title
+        let rendered = render_name_template("{show}/{date}-{title}", &sample_fields());
+
+        // Then the template's slashes are kept and the values are made safe
+        assert_eq!(rendered, "Back to the Board/2023-10-13-Q&A_ What's next_");
+    }
+
+    /// Unit test - render_name_template cannot escape the output directory
+    #[test]
+    fn test_render_name_template_blocks_traversal() {
+        // Given a show title that looks like a path and a template with ".."
+        let fields = NameFields {
+            show: "../../etc".to_string(),
+            ..sample_fields()
+        };
+
+        // When we render the template
+        let rendered = render_name_template("../{show}/{id}", &fields);
+
+        // Then no segment can climb out of the output directory
+        assert!(rendered.split('/').all(|segment| segment != ".."));
+        assert_eq!(rendered, "untitled/_.._etc/1000631244436");
+    }
+
+    /// Unit test - parse_name_template rejects unknown placeholders
+    #[test]
+    fn test_parse_name_template_rejects_unknown_placeholders() {
+        // Given templates with known, unknown and unclosed placeholders
+        // When we parse them
+        // Then only the valid template is accepted
+        assert!(parse_name_template("{show}/{date}-{title}").is_ok());
+        assert!(parse_name_template("{show}/{season}")
+            .unwrap_err()
+            .contains("{season}"));
+        assert!(parse_name_template("{show").is_err());
+    }
+
+    /// Unit test - OutputLayout keeps the flat layout without a template
+    #[test]
+    fn test_output_layout_episode_dir() {
+        // Given layouts with and without a template
+        let flat = OutputLayout {
+            dir: "archive".to_string(),
+            ..OutputLayout::default()
+        };
+        let nested = OutputLayout {
+            dir: "archive".to_string(),
+            template: Some("{show}/{id}".to_string()),
+            ..OutputLayout::default()
+        };
+
+        // When we resolve an episode directory
+        // Then the flat layout uses the base directory and the template nests under it
+        assert_eq!(flat.episode_dir(&sample_fields()), "archive");
+        assert_eq!(
+            nested.episode_dir(&sample_fields()),
+            "archive/Back to the Board/1000631244436"
+        );
+    }
+
+    /// Unit test - sanitize_file_name replaces reserved characters
+    #[test]
+    fn test_sanitize_file_name_replaces_reserved_characters() {
+        // Given names with separators, reserved characters and odd whitespace
+        // When we sanitize them
+        // Then they are safe on every platform
+        assert_eq!(sanitize_file_name("AC/DC: Live?"), "AC_DC_ Live_");
+        assert_eq!(
+            sanitize_file_name("  Tabs\tand  spaces.. "),
+            "Tabs and spaces"
+        );
+        assert_eq!(sanitize_file_name("../.."), "_");
+        assert_eq!(sanitize_file_name("..."), "untitled");
+        assert_eq!(sanitize_file_name(&"a".repeat(300)).len(), 150);
+    }
+
+    /// Unit test - write_file creates missing parent directories
+    #[test]
+    fn test_write_file_creates_directory() {
+        use tempfile::TempDir;
+
+        // Given a temporary directory with a nested path that doesn't exist
+        let temp_dir = TempDir::new().unwrap();
+        let output_path = temp_dir.path().join("nested").join("output.html");
+
+        // When we write to it
+        let result = write_file(output_path.to_str().unwrap(), "<html></html>");
+
+        // Then the write succeeds and the file exists with its content
+        assert!(
+            result.is_ok(),
+            "write_file should create nested directories"
+        );
+        assert_eq!(fs::read_to_string(&output_path).unwrap(), "<html></html>");
+    }
+
+    /// Unit test - save_metadata_json creates valid JSON file
+    #[test]
+    fn test_save_metadata_json_creates_file() {
+        use tempfile::TempDir;
+
+        // Given a metadata struct and a temporary directory
+        let metadata = Metadata {
+            episode_title: "Test Episode".to_string(),
+            description: "Test description".to_string(),
+            show_title: "Test Show".to_string(),
+            publish_date: "2023-10-13".to_string(),
+            publish_date_iso8601: Some("2023-10-13".to_string()),
+            ..Metadata::default()
+        };
+
+        let temp_dir = TempDir::new().unwrap();
+        let json_path = temp_dir.path().join("metadata.json");
+        let json_path_str = json_path.to_str().unwrap();
+
+        // When we save it
+        let result = save_metadata_json(&metadata, json_path_str);
+
+        // Then it succeeds
+        assert!(result.is_ok(), "save_metadata_json should succeed");
+
+        // And the file exists
+        assert!(json_path.exists(), "JSON file should exist");
+
+        // And the file contains valid JSON
+        let content = fs::read_to_string(&json_path).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&content).unwrap();
+
+        // And it has all the expected fields
+        assert_eq!(parsed["episode_title"], "Test Episode");
+        assert_eq!(parsed["description"], "Test description");
+        assert_eq!(parsed["show_title"], "Test Show");
+        assert_eq!(parsed["publish_date"], "2023-10-13");
+    }
+
+    /// Unit test - save_skip_list writes SponsorBlock-style entries keyed by episode
+    #[test]
+    fn test_save_skip_list_writes_sponsorblock_entries() {
+        use tempfile::TempDir;
+
+        // Given a detected ad segment
+        let segments = vec![AdSegment {
+            start_seconds: 10.0,
+            end_seconds: 40.0,
+            evidence: vec!["brought to you by".to_string()],
+            sponsor: Some("Acme".to_string()),
+            promo_codes: vec![],
+        }];
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("skip-list.json");
+
+        // When we save the skip-list
+        save_skip_list("abc-123", &segments, path.to_str().unwrap()).unwrap();
+
+        // Then it is keyed by the episode and uses SponsorBlock field names
+        let parsed: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let entry = &parsed["abc-123"][0];
+        assert_eq!(entry["segment"][0], 10.0);
+        assert_eq!(entry["segment"][1], 40.0);
+        assert_eq!(entry["category"], "sponsor");
+        assert_eq!(entry["actionType"], "skip");
+    }
+
+    /// Unit test - save_converted_transcript swaps the TTML extension
+    #[test]
+    fn test_save_converted_transcript_uses_format_extension() {
+        use tempfile::TempDir;
+
+        // Given a transcript saved as TTML
+        let transcript = Transcript {
+            url: "https://example.com/transcript.ttml".to_string(),
+            ttml: include_str!("../tests/fixtures/transcript.ttml").to_string(),
+        };
+        let temp_dir = TempDir::new().unwrap();
+        let ttml_path = temp_dir.path().join("transcript.ttml");
+
+        // When we save an SRT conversion
+        let path = save_converted_transcript(
+            &transcript,
+            TranscriptFormat::Srt,
+            ttml_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        // Then it is written next to the TTML with an .srt extension
+        assert!(path.ends_with("transcript.srt"));
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("1\n00:00:00,500"));
+    }
+
+    /// Unit test - to_sorted_json sorts keys at every level
+    #[test]
+    fn test_to_sorted_json_sorts_nested_keys() {
+        // Given nested structs whose fields are declared out of alphabetical order
+        #[derive(Serialize)]
+        struct Inner {
+            b: u32,
+            a: u32,
+        }
+        #[derive(Serialize)]
+        struct Entry {
+            zeta: Inner,
+            alpha: u32,
+        }
+        let entry = Entry {
+            zeta: Inner { b: 2, a: 1 },
+            alpha: 0,
+        };
+
+        // When we serialize it
+        let json = to_sorted_json(&entry).unwrap();
+
+        // Then keys come out sorted at both levels, unlike plain struct serialization
+        assert_eq!(
+            json.split_whitespace().collect::<String>(),
+            r#"{"alpha":0,"zeta":{"a":1,"b":2}}"#
+        );
+        assert!(serde_json::to_string(&entry)
+            .unwrap()
+            .starts_with(r#"{"zeta":{"b""#));
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::metadata::Metadata;
+
+/// Directory override files are read from unless `--overrides-dir` is given
+pub const DEFAULT_OVERRIDES_DIR: &str = "overrides";
+
+/// Hand-written corrections for one episode's metadata, read from `<dir>/<episode-id>.toml`
+///
+/// Keys are `metadata.json` field names; only the fields present are replaced.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MetadataOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub episode_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub show_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publish_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publish_date_iso8601: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub episode_number: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub season_number: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feed_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artwork_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explicit: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub genres: Option<Vec<String>>,
+}
+
+/// Path of the override file for an episode
+pub fn override_path(dir: &str, episode_id: &str) -> PathBuf {
+    Path::new(dir).join(format!("{}.toml", episode_id))
+}
+
+impl MetadataOverrides {
+    /// Parses override file contents, rejecting unknown fields and mistyped values
+    pub fn parse(text: &str) -> Result<MetadataOverrides> {
+        toml::from_str(text).map_err(|e| Error::Parse(format!("Invalid override file: {}", e)))
+    }
+
+    /// Reads the override file at `path`, or returns `None` if there is none
+    pub fn load(path: &Path) -> Result<Option<MetadataOverrides>> {
+        match fs::read_to_string(path) {
+            Ok(text) => MetadataOverrides::parse(&text).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::io("Failed to read override file")(e)),
+        }
+    }
+
+    /// Replaces the overridden fields, keeping their extracted values in `metadata.overridden`
+    ///
+    /// Applying the same overrides again leaves the recorded extracted values
+    /// alone. Returns the names of the overridden fields.
+    pub fn apply(&self, metadata: &mut Metadata) -> Result<Vec<String>> {
+        fn text(field: &mut String, value: Option<String>) {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+
+        let extracted = serde_json::to_value(&*metadata)?;
+        let fields: Vec<String> = match serde_json::to_value(self)? {
+            serde_json::Value::Object(fields) => fields.keys().cloned().collect(),
+            _ => Vec::new(),
+        };
+
+        let overrides = self.clone();
+        text(&mut metadata.episode_title, overrides.episode_title);
+        text(&mut metadata.description, overrides.description);
+        text(&mut metadata.show_title, overrides.show_title);
+        text(&mut metadata.publish_date, overrides.publish_date);
+        metadata.publish_date_iso8601 = overrides
+            .publish_date_iso8601
+            .or(metadata.publish_date_iso8601.take());
+        metadata.duration_seconds = overrides.duration_seconds.or(metadata.duration_seconds);
+        metadata.episode_number = overrides.episode_number.or(metadata.episode_number);
+        metadata.season_number = overrides.season_number.or(metadata.season_number);
+        metadata.guid = overrides.guid.or(metadata.guid.take());
+        metadata.audio_url = overrides.audio_url.or(metadata.audio_url.take());
+        metadata.feed_url = overrides.feed_url.or(metadata.feed_url.take());
+        metadata.artwork_url = overrides.artwork_url.or(metadata.artwork_url.take());
+        metadata.explicit = overrides.explicit.or(metadata.explicit);
+        metadata.genres = overrides.genres.or(metadata.genres.take());
+
+        let overridden = metadata.overridden.get_or_insert_with(BTreeMap::new);
+        for field in &fields {
+            overridden
+                .entry(field.clone())
+                .or_insert_with(|| extracted[field.as_str()].clone());
+        }
+        Ok(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - apply replaces only the listed fields and keeps the extracted values
+    #[test]
+    fn test_apply_overrides_preserves_extracted_values() {
+        // Given extracted metadata with a typo and an override file fixing it
+        let mut metadata = Metadata {
+            episode_title: "Interveiw with Ann Smiht".to_string(),
+            description: "A chat.".to_string(),
+            episode_number: Some(12),
+            ..Metadata::default()
+        };
+        let overrides = MetadataOverrides::parse(
+            "episode_title = \"Interview with Ann Smith\"\nseason_number = 2\n",
+        )
+        .unwrap();
+
+        // When we apply it twice, as a re-export would
+        let fields = overrides.apply(&mut metadata).unwrap();
+        overrides.apply(&mut metadata).unwrap();
+
+        // Then the listed fields change, the rest stay and the originals are recorded once
+        assert_eq!(fields, vec!["episode_title", "season_number"]);
+        assert_eq!(metadata.episode_title, "Interview with Ann Smith");
+        assert_eq!(metadata.season_number, Some(2));
+        assert_eq!(metadata.description, "A chat.");
+        let overridden = metadata.overridden.unwrap();
+        assert_eq!(overridden["episode_title"], "Interveiw with Ann Smiht");
+        assert_eq!(overridden["season_number"], serde_json::Value::Null);
+    }
+
+    /// Unit test - parse rejects unknown fields and mistyped values
+    #[test]
+    fn test_parse_overrides_rejects_invalid_files() {
+        // Given a misspelled field and a number given as text
+        // When we parse them
+        // Then both are parse errors
+        assert!(MetadataOverrides::parse("episode_tilte = \"x\"").is_err());
+        assert!(MetadataOverrides::parse("duration_seconds = \"long\"").is_err());
+    }
+}
@@ -0,0 +1,407 @@
+use serde::Serialize;
+
+use crate::archive::ArchivedEpisode;
+use crate::error::Result;
+use crate::metadata::{format_description, DescriptionFormat};
+use crate::tag::{chapters_from_description, Chapter};
+use crate::transcript::format_offset;
+use crate::transcript::segments::TranscriptSegment;
+
+/// Token budget a pack is sized to when none is given, leaving room for questions in a 128k context
+pub const DEFAULT_MAX_TOKENS: usize = 80_000;
+
+/// Characters per token in English prose, the usual estimate when no tokenizer is at hand
+const CHARS_PER_TOKEN: usize = 4;
+
+/// How a context pack is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackFormat {
+    Markdown,
+    Json,
+}
+
+impl PackFormat {
+    /// Extension of the file the pack is saved to
+    pub fn extension(self) -> &'static str {
+        match self {
+            PackFormat::Markdown => "md",
+            PackFormat::Json => "json",
+        }
+    }
+}
+
+/// Parses a `pack --format` value
+pub fn parse_pack_format(raw: &str) -> std::result::Result<PackFormat, String> {
+    match raw.trim().to_lowercase().as_str() {
+        "markdown" | "md" => Ok(PackFormat::Markdown),
+        "json" => Ok(PackFormat::Json),
+        _ => Err(format!(
+            "Invalid pack format '{}': expected markdown or json",
+            raw
+        )),
+    }
+}
+
+/// Parses a `--max-tokens` value: a positive count, optionally in thousands with a `k` suffix
+pub fn parse_max_tokens(raw: &str) -> std::result::Result<usize, String> {
+    let trimmed = raw.trim().to_lowercase();
+    let (number, scale) = match trimmed.strip_suffix('k') {
+        Some(number) => (number, 1000),
+        None => (trimmed.as_str(), 1),
+    };
+    number
+        .parse::<usize>()
+        .ok()
+        .and_then(|count| count.checked_mul(scale))
+        .filter(|count| *count > 0)
+        .ok_or_else(|| {
+            format!(
+                "Invalid token budget '{}': expected a positive number of tokens such as 80000 or 80k",
+                raw
+            )
+        })
+}
+
+/// Rough token count of `text`, close enough for fitting a budget with any common model
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// How a context pack is sized and laid out
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackOptions {
+    pub max_tokens: usize,
+    /// Group the transcript into chunks of this many seconds, each headed by its time range
+    pub chunk_seconds: Option<f64>,
+    pub format: PackFormat,
+}
+
+/// A stretch of the transcript: one per chunk, or a single one when it is not chunked
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct PackChunk {
+    /// Seconds from the start of the episode
+    pub start: f64,
+    pub end: f64,
+    /// Speaker-attributed paragraphs, as `Speaker: text`
+    pub paragraphs: Vec<String>,
+}
+
+/// An episode's metadata, chapters and cleaned transcript, sized to a token budget
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ContextPack {
+    pub title: String,
+    pub show_title: String,
+    pub url: String,
+    pub short_id: String,
+    pub publish_date: Option<String>,
+    pub duration_seconds: Option<u64>,
+    pub description: Option<String>,
+    pub chapters: Vec<Chapter>,
+    pub chunk_seconds: Option<f64>,
+    pub transcript: Vec<PackChunk>,
+    /// Transcript paragraphs left out because they fall in detected ad segments
+    pub ads_removed: usize,
+    /// Where the transcript was cut to fit the budget, in seconds, if it was
+    pub truncated_at: Option<f64>,
+    pub max_tokens: usize,
+    pub estimated_tokens: usize,
+}
+
+impl ContextPack {
+    /// The pack as Markdown or pretty-printed JSON
+    pub fn render(&self, format: PackFormat) -> Result<String> {
+        match format {
+            PackFormat::Markdown => Ok(self.to_markdown()),
+            PackFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+        }
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut page = format!("# {}\n\n", self.title);
+        page.push_str(&format!("- **Show:** {}\n", self.show_title));
+        if let Some(date) = &self.publish_date {
+            page.push_str(&format!("- **Published:** {}\n", date));
+        }
+        if let Some(duration) = self.duration_seconds {
+            page.push_str(&format!(
+                "- **Duration:** {}\n",
+                format_offset(duration as f64)
+            ));
+        }
+        page.push_str(&format!("- **Link:** {}\n\n", self.url));
+
+        if let Some(description) = &self.description {
+            page.push_str(&format!("## Description\n\n{}\n\n", description));
+        }
+        if !self.chapters.is_empty() {
+            page.push_str("## Chapters\n\n");
+            for chapter in &self.chapters {
+                page.push_str(&format!(
+                    "- {} {}\n",
+                    format_offset(chapter.start),
+                    chapter.title
+                ));
+            }
+            page.push('\n');
+        }
+
+        page.push_str("## Transcript\n\n");
+        for chunk in &self.transcript {
+            if self.chunk_seconds.is_some() {
+                page.push_str(&format!(
+                    "### {}–{}\n\n",
+                    format_offset(chunk.start),
+                    format_offset(chunk.end)
+                ));
+            }
+            for paragraph in &chunk.paragraphs {
+                page.push_str(paragraph);
+                page.push_str("\n\n");
+            }
+        }
+        if let Some(cut) = self.truncated_at {
+            page.push_str(&format!(
+                "_Transcript cut at {} to fit {} tokens._\n",
+                format_offset(cut),
+                self.max_tokens
+            ));
+        }
+        page
+    }
+}
+
+/// Assembles an archived episode's context pack from its transcript segments
+///
+/// Segments inside the ad segments recorded in the episode's metadata are
+/// dropped. The transcript is then cut at the last segment that keeps the
+/// rendered pack within `options.max_tokens`; metadata and chapters are always
+/// kept, so a pack may exceed a budget too small for them alone.
+pub fn build_pack(
+    episode: &ArchivedEpisode,
+    segments: &[TranscriptSegment],
+    options: &PackOptions,
+) -> Result<ContextPack> {
+    let metadata = &episode.metadata;
+    let description = metadata["description"]
+        .as_str()
+        .map(|text| format_description(text, DescriptionFormat::Plain))
+        .filter(|text| !text.is_empty());
+    let duration_seconds = metadata["duration_seconds"].as_u64();
+    let chapters = description
+        .as_deref()
+        .map(|text| chapters_from_description(text, duration_seconds))
+        .unwrap_or_default();
+
+    let kept = without_ads(segments, metadata);
+
+    let mut pack = ContextPack {
+        title: episode.title.clone(),
+        show_title: episode.show_title.clone(),
+        url: episode.url.clone(),
+        short_id: episode.short_id.clone(),
+        publish_date: episode.publish_date.clone(),
+        duration_seconds,
+        description,
+        chapters,
+        chunk_seconds: options.chunk_seconds,
+        transcript: Vec::new(),
+        ads_removed: segments.len() - kept.len(),
+        truncated_at: None,
+        max_tokens: options.max_tokens,
+        estimated_tokens: 0,
+    };
+
+    // The rendered size only grows with each segment kept, so the longest fit is found by bisection
+    let (mut fits, mut over) = (0, kept.len() + 1);
+    while over - fits > 1 {
+        let middle = (fits + over) / 2;
+        fill(&mut pack, &kept, middle, options.chunk_seconds);
+        if estimate_tokens(&pack.render(options.format)?) <= options.max_tokens {
+            fits = middle;
+        } else {
+            over = middle;
+        }
+    }
+    fill(&mut pack, &kept, fits, options.chunk_seconds);
+    pack.estimated_tokens = estimate_tokens(&pack.render(options.format)?);
+    Ok(pack)
+}
+
+/// Sets the pack's transcript to the first `count` segments, marking where it was cut
+fn fill(pack: &mut ContextPack, segments: &[&TranscriptSegment], count: usize, chunk: Option<f64>) {
+    pack.transcript = chunk_segments(&segments[..count], chunk);
+    pack.truncated_at = segments.get(count).map(|segment| segment.start);
+}
+
+/// Segments whose middle falls outside every ad segment recorded in an episode's metadata
+pub fn without_ads<'a>(
+    segments: &'a [TranscriptSegment],
+    metadata: &serde_json::Value,
+) -> Vec<&'a TranscriptSegment> {
+    let ads: Vec<(f64, f64)> = metadata["ad_segments"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|ad| Some((ad["start_seconds"].as_f64()?, ad["end_seconds"].as_f64()?)))
+        .collect();
+    segments
+        .iter()
+        .filter(|segment| {
+            let middle = (segment.start + segment.end) / 2.0;
+            !ads.iter()
+                .any(|(start, end)| (*start..*end).contains(&middle))
+        })
+        .collect()
+}
+
+/// Groups segments into chunks of `chunk` seconds each, or into a single chunk without one
+///
+/// Chunks start on multiples of `chunk`, and only stretches with speech get one.
+pub fn chunk_segments(segments: &[&TranscriptSegment], chunk: Option<f64>) -> Vec<PackChunk> {
+    let mut chunks: Vec<PackChunk> = Vec::new();
+    for segment in segments {
+        let paragraph = match &segment.speaker {
+            Some(speaker) => format!("{}: {}", speaker, segment.text),
+            None => segment.text.clone(),
+        };
+        let start = match chunk {
+            Some(seconds) => (segment.start / seconds).floor() * seconds,
+            None => 0.0,
+        };
+        match chunks.last_mut() {
+            Some(last) if last.start == start => {
+                last.end = last.end.max(segment.end);
+                last.paragraphs.push(paragraph);
+            }
+            _ => chunks.push(PackChunk {
+                start,
+                end: segment.end,
+                paragraphs: vec![paragraph],
+            }),
+        }
+    }
+    if let Some(seconds) = chunk {
+        for chunk in &mut chunks {
+            chunk.end = chunk.end.min(chunk.start + seconds);
+        }
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn episode() -> ArchivedEpisode {
+        ArchivedEpisode {
+            id: "1000631244436".to_string(),
+            short_id: "e1a2b3".to_string(),
+            show_id: Some("840986946".to_string()),
+            url: "https://podcasts.apple.com/us/podcast/id840986946?i=1000631244436".to_string(),
+            title: "Episode 12".to_string(),
+            show_title: "Back to the Board".to_string(),
+            publish_date: Some("2023-10-13".to_string()),
+            guid: None,
+            directory: "out/episode-12".to_string(),
+            transcript_path: None,
+            fetched_at: "2023-10-14T00:00:00Z".to_string(),
+            metadata: json!({
+                "description": "<p>A chat.</p><p>0:00 Intro<br>10:00 Deep dive</p>",
+                "duration_seconds": 1800,
+                "ad_segments": [{"start_seconds": 60.0, "end_seconds": 120.0}],
+            }),
+            removed_upstream: None,
+            rating: None,
+            tags: Vec::new(),
+            played_at: None,
+            progress_seconds: None,
+        }
+    }
+
+    fn segments() -> Vec<TranscriptSegment> {
+        (0..30)
+            .map(|minute| TranscriptSegment {
+                speaker: Some(if minute % 2 == 0 { "Host" } else { "Guest" }.to_string()),
+                start: minute as f64 * 60.0,
+                end: minute as f64 * 60.0 + 55.0,
+                text: format!("Minute {} of the conversation.", minute),
+            })
+            .collect()
+    }
+
+    /// Unit test - build_pack keeps everything that fits, chunked by time, without ads
+    #[test]
+    fn test_build_pack_chunks_transcript() {
+        // Given an episode with chapters in its description and an ad at minute one
+        let options = PackOptions {
+            max_tokens: DEFAULT_MAX_TOKENS,
+            chunk_seconds: Some(600.0),
+            format: PackFormat::Markdown,
+        };
+
+        // When we pack it in ten-minute chunks
+        let pack = build_pack(&episode(), &segments(), &options).unwrap();
+
+        // Then the whole transcript fits, minus the ad, in three timestamped chunks
+        assert_eq!(pack.ads_removed, 1);
+        assert_eq!(pack.truncated_at, None);
+        assert_eq!(pack.chapters.len(), 2);
+        assert_eq!(pack.transcript.len(), 3);
+        assert_eq!(
+            (pack.transcript[1].start, pack.transcript[1].end),
+            (600.0, 1195.0)
+        );
+        assert_eq!(
+            pack.transcript[0].paragraphs[1],
+            "Host: Minute 2 of the conversation."
+        );
+        let markdown = pack.render(PackFormat::Markdown).unwrap();
+        assert!(markdown.starts_with("# Episode 12\n"));
+        assert!(markdown.contains("- 10:00 Deep dive\n"));
+        assert!(markdown.contains("### 10:00–19:55\n\nHost: Minute 10"));
+        assert!(!markdown.contains("Minute 1 of"));
+        assert_eq!(pack.estimated_tokens, estimate_tokens(&markdown));
+    }
+
+    /// Unit test - build_pack cuts the transcript to fit the token budget
+    #[test]
+    fn test_build_pack_fits_budget() {
+        // Given a budget a little above what the metadata alone needs
+        let mut options = PackOptions {
+            max_tokens: DEFAULT_MAX_TOKENS,
+            chunk_seconds: None,
+            format: PackFormat::Json,
+        };
+        let empty = build_pack(&episode(), &[], &options).unwrap();
+        options.max_tokens = empty.estimated_tokens + 100;
+
+        // When we pack the episode as JSON
+        let pack = build_pack(&episode(), &segments(), &options).unwrap();
+
+        // Then the transcript is cut where the budget runs out, and the pack stays within it
+        assert!(pack.estimated_tokens <= options.max_tokens);
+        assert_eq!(pack.transcript.len(), 1);
+        let kept = pack.transcript[0].paragraphs.len();
+        assert!(kept > 0 && kept < 29);
+        assert_eq!(pack.truncated_at, Some((kept as f64 + 1.0) * 60.0));
+        let json: serde_json::Value =
+            serde_json::from_str(&pack.render(PackFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["chapters"][1]["title"], "Deep dive");
+    }
+
+    /// Unit test - parse_max_tokens accepts counts and thousands
+    #[test]
+    fn test_parse_max_tokens() {
+        // Given plain and k-suffixed budgets, and invalid ones
+        // When we parse them
+        // Then valid ones give token counts and the rest are rejected
+        assert_eq!(parse_max_tokens("80000"), Ok(80_000));
+        assert_eq!(parse_max_tokens("32K"), Ok(32_000));
+        assert!(parse_max_tokens("0").is_err());
+        assert!(parse_max_tokens("lots")
+            .unwrap_err()
+            .contains("positive number of tokens"));
+        assert_eq!(parse_pack_format("MD"), Ok(PackFormat::Markdown));
+    }
+}
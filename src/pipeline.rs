@@ -0,0 +1,144 @@
+//! The commands the `applecast-cli` binary runs
+//!
+//! Each runner does the work of one subcommand, built from the steps in
+//! [`steps`]. Instead of process-wide settings they take a [`Context`] with the
+//! run's HTTP options, output layout and flags. Progress is logged through
+//! `tracing` and results are printed on stdout, as JSON with `--json`.
+//! Runners return a [`Failure`] rather than exiting, so the binary decides the
+//! exit status.
+
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::budget::Budgets;
+use crate::error::Error;
+use crate::fetch::HttpOptions;
+use crate::metadata::Extraction;
+use crate::output::{to_sorted_json, OutputLayout};
+use crate::transform::Transforms;
+use crate::webhook::Webhooks;
+
+pub mod archived;
+pub mod batch;
+pub mod catalog;
+pub mod collection;
+pub mod devtools;
+pub mod episode;
+pub mod health;
+pub mod show;
+pub mod steps;
+pub mod watch;
+
+/// File names of each artifact within its output directory
+const HTML_FILE: &str = "episode.html";
+const METADATA_FILE: &str = "metadata.json";
+const TRANSCRIPT_FILE: &str = "transcript.ttml";
+const SKIP_LIST_FILE: &str = "skip-list.json";
+const SHOW_HTML_FILE: &str = "show.html";
+const SHOW_INDEX_FILE: &str = "index.json";
+const EPISODES_DIR: &str = "episodes";
+const BATCH_REPORT_FILE: &str = "batch-report";
+const FEEDS_DIR: &str = "feeds";
+const NOTES_AUDIO_FILE: &str = "show-notes.wav";
+const PACK_FILE: &str = "context-pack";
+
+/// Directory `list --export` writes its pages to within the output directory
+pub const SITE_DIR: &str = "site";
+
+/// How results and progress are printed, from `--json` / `--quiet`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Console {
+    /// Print each result as JSON on stdout instead of text
+    pub json: bool,
+    /// Hide progress, including download progress bars
+    pub quiet: bool,
+}
+
+/// Everything a run shares between its steps
+pub struct Context {
+    pub http: HttpOptions,
+    pub layout: OutputLayout,
+    pub console: Console,
+    /// Stage budgets and run deadline
+    pub budgets: Budgets,
+    /// How episode pages are parsed
+    pub extraction: Extraction,
+    /// Commit the output directory after each run
+    pub git_archive: bool,
+    /// The local strategy stats file; `None` when off
+    pub stats_file: Option<String>,
+    /// Per-show webhooks from the config file
+    pub webhooks: Webhooks,
+    /// Extracted-field rewrites from the config file
+    pub transforms: Transforms,
+    /// Serializes updates of the stats file between episodes processed at once
+    stats_lock: Mutex<()>,
+}
+
+impl Context {
+    /// A context for `http` and `layout`, with every other setting at its default
+    pub fn new(http: HttpOptions, layout: OutputLayout) -> Context {
+        Context {
+            http,
+            layout,
+            console: Console::default(),
+            budgets: Budgets::default(),
+            extraction: Extraction::default(),
+            git_archive: false,
+            stats_file: None,
+            webhooks: Webhooks::default(),
+            transforms: Transforms::default(),
+            stats_lock: Mutex::new(()),
+        }
+    }
+
+    /// Prints a run's result as JSON on stdout when `--json` is set
+    pub fn print_report<T: Serialize + ?Sized>(&self, report: &T) -> Outcome {
+        if self.console.json {
+            println!("{}", to_sorted_json(report)?);
+        }
+        Ok(())
+    }
+
+    /// Prints items as JSON, one per line
+    pub fn print_ndjson<T: Serialize>(&self, items: &[T]) -> Outcome {
+        for item in items {
+            println!("{}", serde_json::to_string(item).map_err(Error::from)?);
+        }
+        Ok(())
+    }
+}
+
+/// Why a command did not succeed
+#[derive(Debug)]
+pub enum Failure {
+    /// The command stopped at `error`; `context` (e.g. "Error saving metadata") introduces it
+    Error { context: &'static str, error: Error },
+    /// The command stopped for a reason that is not an [`Error`], e.g. nothing matched a query
+    Message(String),
+    /// The command finished, but what went wrong was already logged; `error` is the
+    /// last error when there was one
+    Reported(Option<Error>),
+    /// The episode has no transcript; already logged as a warning
+    NoTranscript,
+}
+
+impl Failure {
+    /// Wraps an error with a short description of the step that failed
+    pub fn context(context: &'static str) -> impl FnOnce(Error) -> Failure {
+        move |error| Failure::Error { context, error }
+    }
+}
+
+impl From<Error> for Failure {
+    fn from(error: Error) -> Failure {
+        Failure::Error {
+            context: "Error",
+            error,
+        }
+    }
+}
+
+/// Result type of the runners and the steps that can stop a run
+pub type Outcome<T = ()> = std::result::Result<T, Failure>;
@@ -0,0 +1,1167 @@
+//! Runners that read the archive: `list` and its smart lists, `link`, `refresh`, `grep`, `quote`, `ask`, `pack`,
+//! `feed new-items`, `report changes`, `report durations` and `report sponsors`, and `tag`, `rate` and `mark`, which edit its personal data
+
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::path::Path;
+
+use tracing::{error, info, warn};
+
+use super::steps::{
+    archive_id, description_images_step, description_step, index_transcript_file, override_step,
+};
+use super::{Context, Failure, Outcome, METADATA_FILE, PACK_FILE};
+use crate::ads::detect_ad_segments;
+use crate::apple_url::{show_id_from_url, validate_url};
+use crate::archive::{Archive, ArchiveFilter, ArchiveSet, ArchivedEpisode, ChangeKind};
+use crate::ask::{answer_prompt, rank_passages, run_llm, AskOptions};
+use crate::boilerplate::Boilerplate;
+use crate::chart::{write_timeline_chart, TimelinePoint};
+use crate::compress::read_artifact;
+use crate::durations::{bar, duration_report, EpisodeLength, Outlier, CHART_WIDTH};
+use crate::episode::fetch_episode;
+use crate::error::{Error, Result};
+use crate::export::SiteExport;
+use crate::feed::to_rss;
+use crate::listening::{read_listening_export, EpisodeMatcher, ProgressImport};
+use crate::manifest::Manifest;
+use crate::output::{write_file, write_json};
+use crate::pack::{build_pack, chunk_segments, without_ads, PackOptions};
+use crate::quote::{best_quotes, CitedQuote};
+use crate::refresh::{merge_fields, MergeStrategy, RefreshedEpisode};
+use crate::smartlist::{to_m3u, Condition};
+use crate::sponsors::{sponsor_report, EpisodeSponsors, SponsorRead};
+use crate::transcript::format_offset;
+use crate::transcript::segments::TranscriptSegment;
+use crate::transcript::Transcript;
+
+/// How many of a transcript's best sentences `quote` picks between
+const QUOTE_CANDIDATES: usize = 5;
+
+/// Runs `list`: prints the archived episodes `filter` selects, newest first
+pub fn run_list(
+    context: &Context,
+    archives: &ArchiveSet,
+    filter: &ArchiveFilter,
+    export: Option<&SiteExport>,
+) -> Outcome {
+    let episodes = archives
+        .episodes(filter)
+        .map_err(Failure::context("Error reading archive"))?;
+
+    if let Some(export) = export {
+        let boilerplate = if export.strip_boilerplate {
+            show_boilerplate(archives, &episodes)
+                .map_err(Failure::context("Error reading archive"))?
+        } else {
+            HashMap::new()
+        };
+        let mut failed = false;
+        for episode in &episodes {
+            let stripped = episode
+                .show_id
+                .as_ref()
+                .and_then(|show| boilerplate.get(show))
+                .map(|boilerplate| without_boilerplate(episode, boilerplate));
+            match export.write(stripped.as_ref().unwrap_or(episode)) {
+                Ok(page) => info!("📰 Exported {}", page),
+                Err(e) => {
+                    error!("{}: {}", episode.url, e);
+                    failed = true;
+                }
+            }
+        }
+        info!("✅ Exported {} pages to {}", episodes.len(), export.dir);
+        if failed {
+            return Err(Failure::Reported(None));
+        }
+    }
+
+    context.print_report(&episodes)?;
+    if !context.console.json {
+        print_episodes(&episodes);
+    }
+    Ok(())
+}
+
+/// Prints archived episodes with the user's ratings, tags and listening state
+fn print_episodes(episodes: &[ArchivedEpisode]) {
+    if episodes.is_empty() {
+        println!("No archived episodes");
+    }
+    for episode in episodes {
+        println!(
+            "{}  {} — {}{}",
+            episode.publish_date.as_deref().unwrap_or("undated   "),
+            episode.show_title,
+            episode.title,
+            if episode.transcript_path.is_some() {
+                "  📝"
+            } else {
+                ""
+            }
+        );
+        println!("    [{}] {}", episode.short_id, episode.directory);
+        if let Some(removed) = &episode.removed_upstream {
+            println!("    🚫 removed upstream {}", removed);
+        }
+        if let Some(rating) = episode.rating {
+            println!("    {}", stars(rating));
+        }
+        if !episode.tags.is_empty() {
+            println!("    🏷️ {}", episode.tags.join(", "));
+        }
+        if let Some(played) = &episode.played_at {
+            println!("    ✅ played {}", played);
+        } else if let Some(seconds) = episode.progress_seconds {
+            println!("    ⏯️ listened to {}", format_offset(seconds));
+        }
+    }
+}
+
+/// Runs `list create`: saves a named condition in the primary archive
+pub fn run_list_create(
+    context: &Context,
+    archives: &ArchiveSet,
+    name: &str,
+    condition: &str,
+) -> Outcome {
+    let primary = archives.primary();
+    primary
+        .save_smart_list(name, condition)
+        .map_err(Failure::context("Error saving smart list"))?;
+    let saved = smart_list_episodes(archives, name)?;
+    info!(
+        "✅ Saved smart list {}, matching {} episode(s) now",
+        name,
+        saved.len()
+    );
+    let list = primary
+        .smart_list(name)
+        .map_err(Failure::context("Error reading archive"))?;
+    context.print_report(&list)
+}
+
+/// Runs `list show`: prints a smart list's episodes as they match now, or every saved list
+pub fn run_list_show(context: &Context, archives: &ArchiveSet, name: Option<&str>) -> Outcome {
+    let Some(name) = name else {
+        let lists = archives
+            .primary()
+            .smart_lists()
+            .map_err(Failure::context("Error reading archive"))?;
+        context.print_report(&lists)?;
+        if context.console.json {
+            return Ok(());
+        }
+        if lists.is_empty() {
+            println!("No smart lists; save one with list create <NAME> --where <EXPR>");
+        }
+        for list in &lists {
+            println!("{}  {}", list.name, list.condition);
+        }
+        return Ok(());
+    };
+
+    let episodes = smart_list_episodes(archives, name)?;
+    context.print_report(&episodes)?;
+    if !context.console.json {
+        print_episodes(&episodes);
+    }
+    Ok(())
+}
+
+/// Runs `list export`: writes a smart list's episodes as they match now to an M3U playlist
+pub fn run_list_export(context: &Context, archives: &ArchiveSet, name: &str, out: &str) -> Outcome {
+    let episodes = smart_list_episodes(archives, name)?;
+    write_file(out, to_m3u(&episodes)).map_err(Failure::context("Error saving playlist"))?;
+    info!(
+        "✅ Playlist with {} episode(s) saved to {}",
+        episodes.len(),
+        out
+    );
+    context.print_report(&episodes)
+}
+
+/// Runs `list delete`: removes a saved smart list; its episodes are untouched
+pub fn run_list_delete(context: &Context, archives: &ArchiveSet, name: &str) -> Outcome {
+    let deleted = archives
+        .primary()
+        .delete_smart_list(name)
+        .map_err(Failure::context("Error saving smart list"))?;
+    if !deleted {
+        return Err(no_smart_list(name));
+    }
+    info!("🗑️ Deleted smart list {}", name);
+    context.print_report(&name)
+}
+
+/// The archived episodes a saved smart list's condition matches, newest first
+fn smart_list_episodes(archives: &ArchiveSet, name: &str) -> Outcome<Vec<ArchivedEpisode>> {
+    let list = archives
+        .primary()
+        .smart_list(name)
+        .map_err(Failure::context("Error reading archive"))?
+        .ok_or_else(|| no_smart_list(name))?;
+    let condition = Condition::parse(&list.condition).map_err(|e| {
+        Failure::Message(format!(
+            "Smart list {} has an invalid condition '{}': {}",
+            name, list.condition, e
+        ))
+    })?;
+    let episodes = archives
+        .episodes(&ArchiveFilter::default())
+        .map_err(Failure::context("Error reading archive"))?;
+    Ok(episodes
+        .into_iter()
+        .filter(|episode| condition.matches(episode))
+        .collect())
+}
+
+fn no_smart_list(name: &str) -> Failure {
+    Failure::Message(format!(
+        "No smart list named {}; list show prints the saved ones",
+        name
+    ))
+}
+
+/// A rating as five stars, filled up to `rating`
+fn stars(rating: u8) -> String {
+    (1..=5)
+        .map(|star| if star <= rating { '★' } else { '☆' })
+        .collect()
+}
+
+/// Runs `tag add` or `tag remove`: edits an archived episode's personal tags
+pub fn run_tag(
+    context: &Context,
+    archives: &ArchiveSet,
+    target: &str,
+    tags: &[String],
+    add: bool,
+) -> Outcome {
+    let episode = find_editable_episode(archives, target)?;
+    let archive = archives.primary();
+    if add {
+        archive.add_tags(&episode.id, tags)
+    } else {
+        archive.remove_tags(&episode.id, tags)
+    }
+    .map_err(Failure::context("Error updating archive"))?;
+
+    let episode = find_archived_episode(archives, &episode.id)?;
+    context.print_report(&episode)?;
+    if !context.console.json {
+        if episode.tags.is_empty() {
+            println!("🏷️ {}: no tags", episode.title);
+        } else {
+            println!("🏷️ {}: {}", episode.title, episode.tags.join(", "));
+        }
+    }
+    Ok(())
+}
+
+/// Runs `rate`: sets an archived episode's star rating, or clears it with `None`
+pub fn run_rate(
+    context: &Context,
+    archives: &ArchiveSet,
+    target: &str,
+    rating: Option<u8>,
+) -> Outcome {
+    let episode = find_editable_episode(archives, target)?;
+    archives
+        .primary()
+        .set_rating(&episode.id, rating)
+        .map_err(Failure::context("Error updating archive"))?;
+
+    let episode = find_archived_episode(archives, &episode.id)?;
+    context.print_report(&episode)?;
+    if !context.console.json {
+        match rating {
+            Some(rating) => println!("{} {}", stars(rating), episode.title),
+            None => println!("Cleared the rating of {}", episode.title),
+        }
+    }
+    Ok(())
+}
+
+/// Runs `mark played` or `mark unplayed` on archived episodes
+pub fn run_mark(
+    context: &Context,
+    archives: &ArchiveSet,
+    targets: &[String],
+    played: bool,
+) -> Outcome {
+    let mut marked = Vec::new();
+    for target in targets {
+        let episode = find_editable_episode(archives, target)?;
+        archives
+            .primary()
+            .set_played(&episode.id, played)
+            .map_err(Failure::context("Error updating archive"))?;
+        marked.push(find_archived_episode(archives, &episode.id)?);
+    }
+
+    context.print_report(&marked)?;
+    if !context.console.json {
+        for episode in &marked {
+            if played {
+                println!("✅ Played: {}", episode.title);
+            } else {
+                println!("⬜ Unplayed: {}", episode.title);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs `mark import`: marks archived episodes played or in progress from a podcast app's export
+///
+/// Only the primary archive's episodes are matched, since it is the only writable one.
+pub fn run_mark_import(context: &Context, archives: &ArchiveSet, path: &str) -> Outcome {
+    let records = read_listening_export(Path::new(path))
+        .map_err(Failure::context("Error reading listening export"))?;
+    let archive = archives.primary();
+    let episodes = archive
+        .episodes(&ArchiveFilter::default())
+        .map_err(Failure::context("Error reading archive"))?;
+    let matcher = EpisodeMatcher::new(&episodes);
+
+    let mut import = ProgressImport::default();
+    for record in &records {
+        let Some(id) = matcher.find(record) else {
+            import.unmatched += 1;
+            continue;
+        };
+        let updated = if record.played {
+            import.played.push(id.to_string());
+            archive.set_played(id, true)
+        } else if let Some(seconds) = record.progress_seconds {
+            import.in_progress.push(id.to_string());
+            archive.set_progress(id, seconds)
+        } else {
+            Ok(())
+        };
+        updated.map_err(Failure::context("Error updating archive"))?;
+    }
+
+    context.print_report(&import)?;
+    if !context.console.json {
+        println!(
+            "🎧 Marked {} played and {} in progress; {} listened episodes are not in the archive",
+            import.played.len(),
+            import.in_progress.len(),
+            import.unmatched
+        );
+    }
+    Ok(())
+}
+
+/// The boilerplate of each show among `episodes`, detected from all of the show's archived descriptions
+fn show_boilerplate(
+    archives: &ArchiveSet,
+    episodes: &[ArchivedEpisode],
+) -> Result<HashMap<String, Boilerplate>> {
+    let mut found = HashMap::new();
+    for show in episodes
+        .iter()
+        .filter_map(|episode| episode.show_id.as_ref())
+    {
+        if found.contains_key(show) {
+            continue;
+        }
+        let filter = ArchiveFilter {
+            show_id: Some(show.clone()),
+            limit: None,
+            since: None,
+            removed: false,
+            tag: None,
+            min_rating: None,
+            played: None,
+        };
+        let archived = archives.episodes(&filter)?;
+        let boilerplate = Boilerplate::detect(
+            archived
+                .iter()
+                .filter_map(|episode| episode.metadata["description"].as_str()),
+        );
+        found.insert(show.clone(), boilerplate);
+    }
+    Ok(found)
+}
+
+/// A copy of `episode` for export, its description without `boilerplate`; the archive keeps the original
+fn without_boilerplate(episode: &ArchivedEpisode, boilerplate: &Boilerplate) -> ArchivedEpisode {
+    let mut stripped = episode.clone();
+    if let Some(description) = episode.metadata["description"].as_str() {
+        stripped.metadata["description"] = boilerplate.strip(description).into();
+    }
+    stripped
+}
+
+/// Runs `link`: maps a short ID to its episode's URL, or an archived episode URL to its short ID
+pub fn run_link(context: &Context, archives: &ArchiveSet, target: &str) -> Outcome {
+    if validate_url(target).is_ok() {
+        let id = archive_id(target);
+        let Some(short_id) = archives
+            .short_id(&id)
+            .map_err(Failure::context("Error reading archive"))?
+        else {
+            return Err(Failure::Message(format!(
+                "{} is not in the archive",
+                target
+            )));
+        };
+        context.print_report(&serde_json::json!({ "short_id": short_id, "url": target }))?;
+        if !context.console.json {
+            println!("{}", short_id);
+        }
+        return Ok(());
+    }
+
+    let Some(episode) = archives
+        .resolve_short_id(target)
+        .map_err(Failure::context("Error reading archive"))?
+    else {
+        return Err(Failure::Message(format!(
+            "no archived episode with short ID '{}'",
+            target
+        )));
+    };
+    context.print_report(&episode)?;
+    if !context.console.json {
+        println!("{}", episode.url);
+    }
+    Ok(())
+}
+
+/// Runs `refresh`: re-fetches archived episodes and merges the selected fields into their saved metadata
+///
+/// Episodes that cannot be fetched are skipped with a warning; fails if any were.
+pub async fn run_refresh(
+    context: &Context,
+    archive: &Archive,
+    filter: &ArchiveFilter,
+    fields: &[String],
+    strategy: MergeStrategy,
+) -> Outcome {
+    let episodes = archive
+        .episodes(filter)
+        .map_err(Failure::context("Error reading archive"))?;
+    let mut refreshed = Vec::new();
+    let mut failed = 0;
+    for (index, archived) in episodes.iter().enumerate() {
+        info!("🔄 [{}/{}] {}", index + 1, episodes.len(), archived.title);
+        match refresh_episode(context, archive, archived, fields, strategy).await {
+            Ok(changed) if changed.is_empty() => {}
+            Ok(changed) => refreshed.push(RefreshedEpisode {
+                id: archived.id.clone(),
+                url: archived.url.clone(),
+                title: archived.title.clone(),
+                changed,
+            }),
+            Err(e) => {
+                warn!("Failed to refresh {}: {}", archived.url, e);
+                failed += 1;
+            }
+        }
+    }
+
+    context.print_report(&refreshed)?;
+    if !context.console.json {
+        for episode in &refreshed {
+            println!("✏️ {}: {}", episode.title, episode.changed.join(", "));
+        }
+        println!(
+            "Updated {} of {} archived episodes",
+            refreshed.len(),
+            episodes.len()
+        );
+    }
+    if failed > 0 {
+        return Err(Failure::Reported(None));
+    }
+    Ok(())
+}
+
+/// Re-fetches one archived episode and merges the selected fields into its `metadata.json` and archive record
+async fn refresh_episode(
+    context: &Context,
+    archive: &Archive,
+    archived: &ArchivedEpisode,
+    fields: &[String],
+    strategy: MergeStrategy,
+) -> Result<Vec<String>> {
+    let mut episode = fetch_episode(&archived.url, &context.http).await?;
+    // Overridden fields keep their override rather than the page's value
+    description_step(context, &mut episode.metadata);
+    description_images_step(context, &episode.metadata).await;
+    override_step(context, &archived.url, &mut episode.metadata);
+
+    // Hand edits live in metadata.json; the archived copy stands in when it is gone
+    let path = format!("{}/{}", archived.directory, METADATA_FILE);
+    let mut saved = match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text)?,
+        Err(_) => archived.metadata.clone(),
+    };
+    let changed = merge_fields(&mut saved, &episode.metadata, fields, strategy)?;
+    if !changed.is_empty() {
+        write_json(&saved, &path)?;
+        // A tool edit, so later show and batch runs should not treat it as a hand edit
+        let manifest = Manifest::update(&archived.directory, METADATA_FILE)?;
+        if let (Some(manifest), Some(store)) = (manifest, &context.layout.backup_dir) {
+            manifest.back_up(&archived.directory, store)?;
+        }
+        archive.update_metadata(&archived.id, &saved)?;
+    }
+    Ok(changed)
+}
+
+/// Runs `grep`: indexes any archived transcripts not yet searchable, then prints matching cues
+///
+/// Fails when nothing matches, so the binary exits 1 like grep.
+pub fn run_grep(
+    context: &Context,
+    archives: &ArchiveSet,
+    phrase: &str,
+    show: Option<&str>,
+    limit: usize,
+) -> Outcome {
+    let unindexed = archives
+        .unindexed_transcripts()
+        .map_err(Failure::context("Error reading archive"))?;
+    if !unindexed.is_empty() {
+        info!("🔎 Indexing {} archived transcripts...", unindexed.len());
+    }
+    for (archive, url, path) in &unindexed {
+        if let Err(e) = index_transcript_file(archive, url, path) {
+            warn!("Skipping transcript {}: {}", path, e);
+        }
+    }
+
+    let filter = ArchiveFilter {
+        show_id: show.map(|show| show_id_from_url(show).unwrap_or_else(|| show.to_string())),
+        limit: Some(limit),
+        since: None,
+        removed: false,
+        tag: None,
+        min_rating: None,
+        played: None,
+    };
+    let matches = archives
+        .search_transcripts(phrase, &filter)
+        .map_err(Failure::context("Error searching archive"))?;
+
+    context.print_report(&matches)?;
+    if !context.console.json {
+        let mut episode = None;
+        for found in &matches {
+            if episode != Some(&found.episode_id) {
+                episode = Some(&found.episode_id);
+                println!(
+                    "\n{} — {} ({})",
+                    found.show_title,
+                    found.title,
+                    found.publish_date.as_deref().unwrap_or("undated")
+                );
+                println!("{}", found.url);
+            }
+            if let Some(before) = &found.before {
+                println!("          {}", before);
+            }
+            let speaker = found
+                .speaker
+                .as_deref()
+                .map(|speaker| format!("{}: ", speaker))
+                .unwrap_or_default();
+            println!(
+                "  {:>7} {}{}",
+                format_offset(found.begin),
+                speaker,
+                found.text
+            );
+            if let Some(after) = &found.after {
+                println!("          {}", after);
+            }
+        }
+    }
+
+    if matches.is_empty() {
+        info!("No matches for \"{}\"", phrase);
+        return Err(Failure::Reported(None));
+    }
+    Ok(())
+}
+
+/// Runs `quote`: prints a high-scoring sentence from a random archived transcript
+///
+/// Transcripts are tried from a random starting episode until one has a
+/// quotable sentence; one of its best few is picked so repeated runs vary.
+pub fn run_quote(context: &Context, archives: &ArchiveSet, show: Option<&str>) -> Outcome {
+    let filter = ArchiveFilter {
+        show_id: show.map(|show| show_id_from_url(show).unwrap_or_else(|| show.to_string())),
+        limit: None,
+        since: None,
+        removed: false,
+        tag: None,
+        min_rating: None,
+        played: None,
+    };
+    let episodes: Vec<ArchivedEpisode> = archives
+        .episodes(&filter)
+        .map_err(Failure::context("Error reading archive"))?
+        .into_iter()
+        .filter(|episode| episode.transcript_path.is_some())
+        .collect();
+    if episodes.is_empty() {
+        return Err(Failure::Message(
+            "No archived transcripts to quote from".to_string(),
+        ));
+    }
+
+    let start = random_below(episodes.len());
+    let cited = episodes[start..]
+        .iter()
+        .chain(&episodes[..start])
+        .find_map(|episode| {
+            let ttml = read_artifact(episode.transcript_path.as_deref()?).ok()?;
+            let transcript = Transcript {
+                url: episode.url.clone(),
+                ttml,
+            };
+            let quotes = best_quotes(&transcript.cues().ok()?);
+            let best = &quotes[..quotes.len().min(QUOTE_CANDIDATES)];
+            let quote = best.get(random_below(best.len().max(1)))?.clone();
+            Some(CitedQuote {
+                quote,
+                episode_title: episode.title.clone(),
+                show_title: episode.show_title.clone(),
+                url: episode.url.clone(),
+                publish_date: episode.publish_date.clone(),
+            })
+        });
+    let Some(cited) = cited else {
+        return Err(Failure::Message(
+            "No quotable sentence found in the archived transcripts".to_string(),
+        ));
+    };
+
+    context.print_report(&cited)?;
+    if context.console.json {
+        return Ok(());
+    }
+
+    println!("“{}”", cited.quote.text);
+    println!("— {}", cited.citation());
+    println!("{}", cited.url);
+    Ok(())
+}
+
+/// The archived episode an Apple episode ID, short ID or episode URL names, failing if there is none
+fn find_archived_episode(archives: &ArchiveSet, target: &str) -> Outcome<ArchivedEpisode> {
+    let id = if validate_url(target).is_ok() {
+        archive_id(target)
+    } else {
+        target.trim().to_string()
+    };
+    let found = match archives
+        .episode(&id)
+        .map_err(Failure::context("Error reading archive"))?
+    {
+        Some(episode) => Some(episode),
+        None => archives
+            .resolve_short_id(target)
+            .map_err(Failure::context("Error reading archive"))?,
+    };
+    let Some(episode) = found else {
+        return Err(Failure::Message(format!(
+            "{} is not in the archive",
+            target
+        )));
+    };
+    Ok(episode)
+}
+
+/// Like [`find_archived_episode`], but the episode must be in the primary archive, the only writable one
+fn find_editable_episode(archives: &ArchiveSet, target: &str) -> Outcome<ArchivedEpisode> {
+    let episode = find_archived_episode(archives, target)?;
+    let primary = archives
+        .primary()
+        .episode(&episode.id)
+        .map_err(Failure::context("Error reading archive"))?;
+    if primary.is_none() {
+        return Err(Failure::Message(format!(
+            "{} is only in a read-only archive; pass that archive as the first --db to edit it",
+            target
+        )));
+    }
+    Ok(episode)
+}
+
+/// An archived episode's transcript as speaker paragraphs, or `None` if it was archived without one
+fn archived_segments(episode: &ArchivedEpisode) -> Outcome<Option<Vec<TranscriptSegment>>> {
+    let Some(path) = episode.transcript_path.as_deref() else {
+        return Ok(None);
+    };
+    let ttml = read_artifact(path)
+        .map_err(Error::io("Failed to read transcript"))
+        .map_err(Failure::context("Error reading transcript"))?;
+    let transcript = Transcript {
+        url: episode.url.clone(),
+        ttml,
+    };
+    let segments = transcript
+        .segments()
+        .map_err(Failure::context("Error parsing transcript"))?;
+    Ok(Some(segments))
+}
+
+/// Runs `ask`: finds the transcript passages most relevant to a question, and optionally has an LLM answer from them
+pub fn run_ask(
+    context: &Context,
+    archives: &ArchiveSet,
+    target: &str,
+    question: &str,
+    options: &AskOptions,
+    llm_command: Option<&str>,
+) -> Outcome {
+    let episode = find_archived_episode(archives, target)?;
+    let Some(segments) = archived_segments(&episode)? else {
+        return Err(Failure::Message(format!(
+            "{} was archived without a transcript",
+            episode.title
+        )));
+    };
+    let chunks = chunk_segments(
+        &without_ads(&segments, &episode.metadata),
+        Some(options.passage_seconds),
+    );
+    let passages = rank_passages(&chunks, question, options.top);
+    if passages.is_empty() {
+        return Err(Failure::Message(format!(
+            "Nothing in the transcript matches '{}'",
+            question
+        )));
+    }
+
+    let answer = match llm_command {
+        Some(command) => {
+            info!("🤖 Asking {} about {} passages", command, passages.len());
+            let prompt = answer_prompt(&episode.title, &episode.show_title, question, &passages);
+            Some(run_llm(command, &prompt)?)
+        }
+        None => None,
+    };
+    context.print_report(&serde_json::json!({
+        "question": question,
+        "title": episode.title,
+        "url": episode.url,
+        "passages": passages,
+        "answer": answer,
+    }))?;
+    if context.console.json {
+        return Ok(());
+    }
+
+    if let Some(answer) = &answer {
+        println!("{}", answer);
+        println!();
+    }
+    println!("{} — {}", episode.title, episode.url);
+    for (index, passage) in passages.iter().enumerate() {
+        println!("[{}] {}", index + 1, passage.timestamp());
+        if answer.is_none() {
+            for line in passage.text.lines() {
+                println!("    {}", line);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs `pack`: writes an archived episode's context pack, cut to fit the token budget
+pub fn run_pack(
+    context: &Context,
+    archives: &ArchiveSet,
+    target: &str,
+    options: &PackOptions,
+    output: Option<&str>,
+) -> Outcome {
+    let episode = find_archived_episode(archives, target)?;
+    let segments = archived_segments(&episode)?.unwrap_or_else(|| {
+        warn!(
+            "No transcript archived for {}; packing metadata only",
+            episode.title
+        );
+        Vec::new()
+    });
+    let pack = build_pack(&episode, &segments, options)
+        .map_err(Failure::context("Error building pack"))?;
+    let rendered = pack
+        .render(options.format)
+        .map_err(Failure::context("Error rendering pack"))?;
+    if let Some(cut) = pack.truncated_at {
+        warn!(
+            "Transcript cut at {} to fit {} tokens",
+            format_offset(cut),
+            options.max_tokens
+        );
+    }
+
+    if output == Some("-") {
+        print!("{}", rendered);
+        return Ok(());
+    }
+    let path = output.map_or_else(
+        || {
+            format!(
+                "{}/{}.{}",
+                episode.directory,
+                PACK_FILE,
+                options.format.extension()
+            )
+        },
+        str::to_string,
+    );
+    write_file(&path, &rendered).map_err(Failure::context("Error writing pack"))?;
+    context.print_report(&serde_json::json!({
+        "path": path,
+        "estimated_tokens": pack.estimated_tokens,
+        "max_tokens": options.max_tokens,
+        "truncated_at": pack.truncated_at,
+        "ads_removed": pack.ads_removed,
+    }))?;
+    if context.console.json {
+        return Ok(());
+    }
+    info!(
+        "📦 Packed {} (~{} tokens)",
+        episode.title, pack.estimated_tokens
+    );
+    println!("{}", path);
+    Ok(())
+}
+
+/// Runs `feed new-items`: writes the most recently archived episodes to `out` as an RSS feed
+pub fn run_new_items(context: &Context, archives: &ArchiveSet, out: &str, limit: usize) -> Outcome {
+    let episodes = archives
+        .recently_archived(limit)
+        .map_err(Failure::context("Error reading archive"))?;
+    write_file(out, to_rss(&episodes)).map_err(Failure::context("Error saving feed"))?;
+    info!(
+        "✅ Feed with {} episode(s) saved to {}",
+        episodes.len(),
+        out
+    );
+    context.print_report(&episodes)
+}
+
+/// Runs `report durations`: charts a show's archived episode lengths over time and flags outliers
+pub fn run_durations(
+    context: &Context,
+    archives: &ArchiveSet,
+    show_url: &str,
+    chart: Option<&str>,
+) -> Outcome {
+    let show_id = show_id_from_url(show_url).unwrap_or_else(|| show_url.to_string());
+    let filter = ArchiveFilter {
+        show_id: Some(show_id.clone()),
+        ..ArchiveFilter::default()
+    };
+    let episodes = archives
+        .episodes(&filter)
+        .map_err(Failure::context("Error reading archive"))?;
+    if episodes.is_empty() {
+        return Err(Failure::Message(format!(
+            "No archived episodes of show {}",
+            show_id
+        )));
+    }
+
+    let mut lengths = Vec::new();
+    for episode in episodes {
+        // A transcript that cannot be read counts as missing rather than failing the report
+        let transcript_words = match archived_segments(&episode) {
+            Ok(segments) => segments.map(|segments| {
+                segments
+                    .iter()
+                    .map(|segment| segment.text.split_whitespace().count())
+                    .sum()
+            }),
+            Err(_) => {
+                warn!("{}: transcript could not be read", episode.url);
+                None
+            }
+        };
+        lengths.push(EpisodeLength {
+            duration_seconds: episode.metadata["duration_seconds"].as_u64(),
+            transcript_words,
+            id: episode.id,
+            title: episode.title,
+            publish_date: episode.publish_date,
+            outliers: Vec::new(),
+        });
+    }
+    let report = duration_report(&show_id, lengths);
+
+    if let Some(chart) = chart {
+        let points: Vec<_> = report
+            .episodes
+            .iter()
+            .filter_map(|episode| {
+                Some(TimelinePoint {
+                    label: episode.publish_date.clone().unwrap_or_default(),
+                    value: episode.duration_seconds? as f64 / 60.0,
+                    flagged: episode
+                        .outliers
+                        .iter()
+                        .any(|outlier| matches!(outlier, Outlier::Short | Outlier::Long)),
+                })
+            })
+            .collect();
+        if points.is_empty() {
+            return Err(Failure::Message(format!(
+                "No archived episode of show {} has a duration to chart",
+                show_id
+            )));
+        }
+        let title = format!("Episode durations, show {}", show_id);
+        write_timeline_chart(chart, &title, "minutes", &points)
+            .map_err(Failure::context("Error saving chart"))?;
+        info!("📈 Chart saved to {}", chart);
+    }
+
+    context.print_report(&report)?;
+    if context.console.json {
+        return Ok(());
+    }
+    let longest = report
+        .episodes
+        .iter()
+        .filter_map(|episode| episode.duration_seconds)
+        .max()
+        .unwrap_or_default();
+    println!(
+        "Episode durations for show {} ({} episodes, median {}, median transcript {} words)",
+        show_id,
+        report.episodes.len(),
+        report
+            .median_duration_seconds
+            .map(format_offset)
+            .unwrap_or_else(|| "unknown".to_string()),
+        report
+            .median_transcript_words
+            .map(|words| format!("{:.0}", words))
+            .unwrap_or_else(|| "unknown".to_string())
+    );
+    for episode in &report.episodes {
+        let duration = match episode.duration_seconds {
+            Some(seconds) => format!(
+                "{} {:>8}",
+                bar(seconds, longest),
+                format_offset(seconds as f64)
+            ),
+            None => format!("{:<width$} {:>8}", "", "?", width = CHART_WIDTH),
+        };
+        let words = episode
+            .transcript_words
+            .map(|words| format!("{} words", words))
+            .unwrap_or_else(|| "no transcript".to_string());
+        println!(
+            "{}  {}  {:>13}  {}",
+            episode.publish_date.as_deref().unwrap_or("undated   "),
+            duration,
+            words,
+            episode.title
+        );
+        for outlier in &episode.outliers {
+            println!("    ⚠️ {}", outlier.label());
+        }
+    }
+    Ok(())
+}
+
+/// Runs `report sponsors`: lists the sponsors and promo codes read out in a show's archived episodes
+pub fn run_sponsors(context: &Context, archives: &ArchiveSet, show_url: &str) -> Outcome {
+    let show_id = show_id_from_url(show_url).unwrap_or_else(|| show_url.to_string());
+    let filter = ArchiveFilter {
+        show_id: Some(show_id.clone()),
+        ..ArchiveFilter::default()
+    };
+    let episodes = archives
+        .episodes(&filter)
+        .map_err(Failure::context("Error reading archive"))?;
+    if episodes.is_empty() {
+        return Err(Failure::Message(format!(
+            "No archived episodes of show {}",
+            show_id
+        )));
+    }
+
+    let mut scanned = Vec::new();
+    let mut without_transcript = 0;
+    for episode in episodes {
+        let Some(reads) = archived_sponsor_reads(&episode) else {
+            without_transcript += 1;
+            continue;
+        };
+        scanned.push(EpisodeSponsors {
+            id: episode.id,
+            title: episode.title,
+            publish_date: episode.publish_date,
+            reads,
+        });
+    }
+    let report = sponsor_report(&show_id, scanned, without_transcript);
+
+    context.print_report(&report)?;
+    if context.console.json {
+        return Ok(());
+    }
+    println!(
+        "Sponsors of show {}: {} across {} of {} scanned episodes",
+        show_id,
+        report.sponsors.len(),
+        report.episodes.len(),
+        report.episodes_scanned
+    );
+    if report.episodes_without_transcript > 0 {
+        println!(
+            "  {} episodes have no transcript and were skipped",
+            report.episodes_without_transcript
+        );
+    }
+    for sponsor in &report.sponsors {
+        println!(
+            "\n💼 {} ({} episodes, {} to {})",
+            sponsor.name,
+            sponsor.episodes,
+            sponsor.first_seen.as_deref().unwrap_or("undated"),
+            sponsor.last_seen.as_deref().unwrap_or("undated")
+        );
+        if !sponsor.promo_codes.is_empty() {
+            println!("    🎟️ {}", sponsor.promo_codes.join(", "));
+        }
+    }
+    if !report.episodes.is_empty() {
+        println!();
+    }
+    for episode in &report.episodes {
+        println!(
+            "{}  {}",
+            episode.publish_date.as_deref().unwrap_or("undated   "),
+            episode.title
+        );
+        for read in &episode.reads {
+            let codes = if read.promo_codes.is_empty() {
+                String::new()
+            } else {
+                format!("  🎟️ {}", read.promo_codes.join(", "))
+            };
+            println!(
+                "    {}  {}{}",
+                format_offset(read.start_seconds),
+                read.sponsor.as_deref().unwrap_or("unnamed sponsor"),
+                codes
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The ad reads in an archived episode, or `None` when there is nothing to look in
+///
+/// Ads are detected afresh from the archived transcript, so every episode is
+/// read with the same rules; the `ad_segments` recorded by `--detect-ads` are
+/// used for episodes archived without a transcript.
+fn archived_sponsor_reads(episode: &ArchivedEpisode) -> Option<Vec<SponsorRead>> {
+    let detected = episode.transcript_path.as_deref().and_then(|path| {
+        let segments = Transcript::from_file(path)
+            .and_then(|transcript| transcript.cues())
+            .and_then(|cues| detect_ad_segments(&cues));
+        match segments {
+            Ok(segments) => Some(segments.into_iter().map(SponsorRead::from).collect()),
+            Err(e) => {
+                warn!("{}: transcript could not be read: {}", episode.url, e);
+                None
+            }
+        }
+    });
+    detected.or_else(|| {
+        let recorded = episode.metadata["ad_segments"].as_array()?;
+        Some(recorded.iter().map(SponsorRead::from_json).collect())
+    })
+}
+
+/// Runs `report changes`: prints the upstream changes the archive recorded since `since`, by kind
+pub fn run_changes(
+    context: &Context,
+    archives: &ArchiveSet,
+    since: &str,
+    chart: Option<&str>,
+) -> Outcome {
+    let changes = archives
+        .changes(since)
+        .map_err(Failure::context("Error reading archive"))?;
+
+    if let Some(chart) = chart {
+        // Changes come latest first; the chart runs oldest first, one point per day
+        let mut per_day: Vec<TimelinePoint> = Vec::new();
+        for change in changes.iter().rev() {
+            let day = change.detected_at.get(..10).unwrap_or(&change.detected_at);
+            match per_day.last_mut() {
+                Some(point) if point.label == day => point.value += 1.0,
+                _ => per_day.push(TimelinePoint {
+                    label: day.to_string(),
+                    value: 1.0,
+                    flagged: false,
+                }),
+            }
+        }
+        let title = format!("Upstream changes since {}", since);
+        write_timeline_chart(chart, &title, "changes", &per_day)
+            .map_err(Failure::context("Error saving chart"))?;
+        info!("📈 Chart saved to {}", chart);
+    }
+
+    context.print_report(&changes)?;
+    if context.console.json {
+        return Ok(());
+    }
+    println!("Upstream changes since {}: {}", since, changes.len());
+    let sections = [
+        (ChangeKind::Removed, "🚫 Removed upstream"),
+        (ChangeKind::Restored, "↩️ Listed again"),
+        (ChangeKind::Title, "✏️ Title edited"),
+        (ChangeKind::Description, "📝 Description edited"),
+        (ChangeKind::Transcript, "📜 Transcript replaced"),
+    ];
+    for (kind, heading) in sections {
+        let found: Vec<_> = changes
+            .iter()
+            .filter(|change| change.kind == kind)
+            .collect();
+        if found.is_empty() {
+            continue;
+        }
+        println!("\n{} ({})", heading, found.len());
+        for change in found {
+            println!(
+                "  {} {} — {} [{}]",
+                change.detected_at, change.show_title, change.title, change.episode_id
+            );
+            if let (ChangeKind::Title, Some(old)) = (kind, &change.old) {
+                println!("    was: {}", old);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A random index below `len`; RandomState is seeded per process, which is enough variety here
+fn random_below(len: usize) -> usize {
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    (random % len as u64) as usize
+}
@@ -0,0 +1,230 @@
+//! The `batch` runner, which processes a list of episode and show URLs concurrently
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tracing::{error, info};
+
+use super::show::{print_plan, CrawlOptions};
+use super::steps::{fetch_episode_within, git_commit_step, repair_episode, save_episode};
+use super::{Context, Failure, Outcome, BATCH_REPORT_FILE, EPISODES_DIR};
+use crate::apple_url::{episode_id_from_url, is_show_url, validate_url};
+use crate::batch::{run_concurrently, BatchEntry, BatchReport};
+use crate::error::Result;
+use crate::manifest::{Decision, PlannedEpisode};
+use crate::output::{sanitize_file_name, write_file, write_json, NameFields};
+use crate::show::list_episodes;
+use crate::transcript::convert::TranscriptFormat;
+
+/// Runs `batch`: expands show URLs, processes every episode concurrently and saves a report
+pub async fn run_batch(
+    context: &Context,
+    urls: Vec<String>,
+    concurrency: usize,
+    report_format: &str,
+    formats: &[TranscriptFormat],
+    crawl: &CrawlOptions,
+) -> Outcome {
+    if crawl.dry_run {
+        let plan: Vec<PlannedEpisode> = urls
+            .iter()
+            .map(|url| match is_show_url(url) {
+                true => PlannedEpisode {
+                    url: url.clone(),
+                    directory: None,
+                    action: "enumerate",
+                    changed: Vec::new(),
+                    missing: Vec::new(),
+                },
+                false => crawl.plan(url, batch_episode_dir(context, url)),
+            })
+            .collect();
+        return print_plan(context, &plan);
+    }
+
+    info!("📥 {} URL(s) to process", urls.len());
+
+    // Show URLs stand for all of their episodes
+    let mut entries = Vec::new();
+    let mut episode_urls = Vec::new();
+    for url in urls {
+        if !is_show_url(&url) {
+            episode_urls.push(url);
+            continue;
+        }
+
+        let listed = match validate_url(&url) {
+            Ok(()) => list_episodes(&url, &context.http, &crawl.filter).await,
+            Err(e) => Err(e),
+        };
+        match listed {
+            Ok((_, episodes)) => {
+                info!("🎙️ {}: {} episode(s)", url, episodes.len());
+                episode_urls.extend(episodes.into_iter().map(|episode| episode.url));
+            }
+            Err(e) => {
+                error!("{}: {}", url, e);
+                entries.push(BatchEntry {
+                    url,
+                    directory: None,
+                    transcript: false,
+                    skipped: false,
+                    error: Some(e.to_string()),
+                });
+            }
+        }
+    }
+
+    let total = episode_urls.len();
+    let done = &AtomicUsize::new(0);
+    let results = run_concurrently(episode_urls, concurrency, |url| async move {
+        let result = batch_episode(context, &url, formats, crawl).await;
+        let position = done.fetch_add(1, Ordering::Relaxed) + 1;
+
+        match &result {
+            Ok(saved) if saved.skipped => {
+                info!("  [{}/{}] ⏭️ {} (unchanged, skipped)", position, total, url)
+            }
+            Ok(saved) if saved.transcript => info!("  [{}/{}] ✅ {}", position, total, url),
+            Ok(_) => info!("  [{}/{}] ✅ {} (no transcript)", position, total, url),
+            Err(e) => error!("[{}/{}] {}: {}", position, total, url, e),
+        }
+
+        match result {
+            Ok(saved) => BatchEntry {
+                url,
+                directory: Some(saved.directory),
+                transcript: saved.transcript,
+                skipped: saved.skipped,
+                error: None,
+            },
+            Err(e) => BatchEntry {
+                url,
+                directory: None,
+                transcript: false,
+                skipped: false,
+                error: Some(e.to_string()),
+            },
+        }
+    })
+    .await;
+    entries.extend(results);
+
+    let report = BatchReport::new(entries);
+    let report_path = context
+        .layout
+        .path(&format!("{}.{}", BATCH_REPORT_FILE, report_format));
+    let saved = if report_format == "csv" {
+        write_file(&report_path, report.to_csv())
+    } else {
+        write_json(&report, &report_path)
+    };
+    saved.map_err(Failure::context("Error saving batch report"))?;
+
+    info!(
+        "✅ {} succeeded, {} failed, {} skipped; report saved to {}",
+        report.succeeded, report.failed, report.skipped, report_path
+    );
+    let summary = format!(
+        "Batch: {} succeeded, {} failed, {} skipped",
+        report.succeeded, report.failed, report.skipped
+    );
+    let saved: Vec<String> = report
+        .entries
+        .iter()
+        .filter(|entry| entry.error.is_none() && !entry.skipped)
+        .map(|entry| entry.url.clone())
+        .collect();
+    git_commit_step(context, &summary, &saved);
+    context.print_report(&report)
+}
+
+/// A batch episode saved into its directory, or found already saved there
+struct SavedEpisode {
+    directory: String,
+    transcript: bool,
+    skipped: bool,
+}
+
+/// Fetches one batch episode into its own directory, unless an earlier run already saved it
+async fn batch_episode(
+    context: &Context,
+    url: &str,
+    formats: &[TranscriptFormat],
+    crawl: &CrawlOptions,
+) -> Result<SavedEpisode> {
+    // Without a name template the directory is known before the page is fetched
+    let known = batch_episode_dir(context, url);
+    if let Some(directory) = &known {
+        match crawl.existing(directory)? {
+            Decision::Skip { transcript } => {
+                return Ok(SavedEpisode {
+                    directory: directory.clone(),
+                    transcript,
+                    skipped: true,
+                })
+            }
+            Decision::Repair(missing) => {
+                let (_, transcript) =
+                    repair_episode(context, url, directory, &missing, formats, None).await?;
+                return Ok(SavedEpisode {
+                    directory: directory.clone(),
+                    transcript,
+                    skipped: false,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let checked = known.is_some();
+
+    let mut episode = fetch_episode_within(context, url).await?;
+    let directory = match (known, &context.layout.template) {
+        (Some(directory), _) => directory,
+        (None, Some(_)) => context
+            .layout
+            .episode_dir(&NameFields::new(url, Some(&episode.metadata))),
+        (None, None) => context.layout.path(&format!(
+            "{}/{}",
+            EPISODES_DIR,
+            sanitize_file_name(&episode.key())
+        )),
+    };
+    if !checked {
+        match crawl.existing(&directory)? {
+            Decision::Skip { transcript } => {
+                return Ok(SavedEpisode {
+                    directory,
+                    transcript,
+                    skipped: true,
+                })
+            }
+            Decision::Repair(missing) => {
+                let (_, transcript) =
+                    repair_episode(context, url, &directory, &missing, formats, Some(episode))
+                        .await?;
+                return Ok(SavedEpisode {
+                    directory,
+                    transcript,
+                    skipped: false,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let transcript = save_episode(context, &mut episode, &directory, formats).await?;
+    Ok(SavedEpisode {
+        directory,
+        transcript,
+        skipped: false,
+    })
+}
+
+/// A batch episode's directory when it follows from the URL alone: `episodes/<id>` without a name template
+fn batch_episode_dir(context: &Context, url: &str) -> Option<String> {
+    if context.layout.template.is_some() {
+        return None;
+    }
+    episode_id_from_url(url).map(|id| context.layout.path(&format!("{}/{}", EPISODES_DIR, id)))
+}
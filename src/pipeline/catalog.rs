@@ -0,0 +1,202 @@
+//! Runners that query Apple's catalog and RSS feeds: `feed`, `lookup`, `search` and `report gaps`
+
+use tracing::{error, info, warn};
+
+use super::{Context, Failure, Outcome, FEEDS_DIR};
+use crate::apple_url::{episode_id_from_url, show_id_from_url, storefront_from_url, validate_url};
+use crate::feed::{download_feed, resolve_feed, to_opml};
+use crate::gaps::check_gaps;
+use crate::lookup::{lookup, search_shows, LOOKUP_EPISODE_LIMIT};
+use crate::output::{sanitize_file_name, write_file};
+
+/// Runs `feed`: resolves each show to its RSS feed, optionally saving the feeds and an OPML list
+pub async fn run_feed(
+    context: &Context,
+    urls: &[String],
+    download: bool,
+    opml: Option<&str>,
+) -> Outcome {
+    let mut feeds = Vec::new();
+    let mut last_error = None;
+    for url in urls {
+        let feed = match resolve_feed(url, &context.http).await {
+            Ok(feed) => feed,
+            Err(e) => {
+                error!("{}: {}", url, e);
+                last_error = Some(e);
+                continue;
+            }
+        };
+        info!("🎙️ {}: {}", feed.title, feed.feed_url);
+
+        if download {
+            let name = if feed.title.is_empty() {
+                show_id_from_url(url).unwrap_or_else(|| "feed".to_string())
+            } else {
+                sanitize_file_name(&feed.title)
+            };
+            let path = context.layout.path(&format!("{}/{}.xml", FEEDS_DIR, name));
+            match download_feed(&feed.feed_url, &context.http).await {
+                Ok(xml) => {
+                    write_file(&path, xml).map_err(Failure::context("Error saving feed"))?;
+                    info!("✅ Feed saved to {}", path);
+                }
+                Err(e) => warn!("Feed download failed for {}: {}", feed.feed_url, e),
+            }
+        }
+        feeds.push(feed);
+    }
+
+    // Nothing resolved: fail with the last error so scripts see why
+    if feeds.is_empty() {
+        if let Some(e) = last_error {
+            return Err(Failure::Reported(Some(e)));
+        }
+    }
+
+    if let Some(path) = opml {
+        write_file(path, to_opml(&feeds)).map_err(Failure::context("Error saving OPML"))?;
+        info!("✅ OPML with {} feed(s) saved to {}", feeds.len(), path);
+    }
+    context.print_report(&feeds)
+}
+
+/// Runs `lookup`: prints the iTunes API record for a show or episode ID or URL
+pub async fn run_lookup(context: &Context, id: &str, storefront: Option<&str>) -> Outcome {
+    // URLs name both the show and, for episodes, the episode to pick out of its listing
+    let (lookup_id, episode_id, url_storefront) = if validate_url(id).is_ok() {
+        let Some(show_id) = show_id_from_url(id) else {
+            return Err(Failure::Message(format!("no Apple show ID in '{}'", id)));
+        };
+        (show_id, episode_id_from_url(id), storefront_from_url(id))
+    } else if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) {
+        (id.to_string(), None, "us".to_string())
+    } else {
+        return Err(Failure::Message(format!(
+            "'{}' is neither an Apple ID nor a URL",
+            id
+        )));
+    };
+    let storefront = storefront.unwrap_or(&url_storefront);
+
+    let mut result = lookup(&lookup_id, storefront, &context.http).await?;
+    if let Some(episode_id) = &episode_id {
+        result
+            .episodes
+            .retain(|episode| &episode.track_id.to_string() == episode_id);
+    }
+    if result.show.is_none() && result.episodes.is_empty() {
+        return Err(Failure::Message(format!("no iTunes result for '{}'", id)));
+    }
+
+    context.print_report(&result)?;
+    if context.console.json {
+        return Ok(());
+    }
+
+    if let Some(show) = &result.show {
+        println!(
+            "🎙️ {} — {} (id {})",
+            show.collection_name, show.artist_name, show.collection_id
+        );
+        if let Some(feed) = &show.feed_url {
+            println!("   Feed: {}", feed);
+        }
+    }
+    for episode in &result.episodes {
+        println!(
+            "  {}  {}  {}",
+            episode.track_id,
+            episode
+                .release_date
+                .as_deref()
+                .unwrap_or("")
+                .get(..10)
+                .unwrap_or(""),
+            episode.track_name
+        );
+    }
+    if episode_id.is_some() && result.episodes.is_empty() {
+        warn!(
+            "Episode not found among the show's latest {} episodes",
+            LOOKUP_EPISODE_LIMIT
+        );
+    }
+    Ok(())
+}
+
+/// Runs `search`: lists shows in the iTunes catalog matching a term
+pub async fn run_search(context: &Context, term: &str, storefront: &str, limit: usize) -> Outcome {
+    let shows = search_shows(term, storefront, limit, &context.http).await?;
+
+    context.print_report(&shows)?;
+    if context.console.json {
+        return Ok(());
+    }
+
+    if shows.is_empty() {
+        println!("No shows found for '{}'", term);
+    }
+    for show in &shows {
+        println!(
+            "{}  {} — {}",
+            show.collection_id, show.collection_name, show.artist_name
+        );
+        if let Some(url) = &show.collection_view_url {
+            println!("    {}", url);
+        }
+    }
+    Ok(())
+}
+
+/// Runs `report gaps`: lists episodes only one of the RSS feed and Apple's catalog has
+///
+/// Fails when there are any, so scripts can alert on them.
+pub async fn run_gaps(context: &Context, show: &str) -> Outcome {
+    info!("🔎 Comparing feed and Apple listing for {}", show);
+    let report = check_gaps(show, &context.http).await?;
+
+    context.print_report(&report)?;
+    if !context.console.json {
+        println!("🎙️ {}: {}", report.title, report.feed_url);
+        println!(
+            "   {} in feed, {} on Apple, {} matched",
+            report.feed_episodes, report.apple_episodes, report.gaps.matched
+        );
+        if !report.apple_listing_complete {
+            warn!("Apple's listing was cut off; older feed items were not checked");
+        }
+
+        let gaps = &report.gaps;
+        if !gaps.missing_from_apple.is_empty() {
+            println!("Missing from Apple ({}):", gaps.missing_from_apple.len());
+            for item in &gaps.missing_from_apple {
+                println!(
+                    "  {:<10}  {}  {}",
+                    item.publish_date.as_deref().unwrap_or(""),
+                    item.title,
+                    item.guid.as_deref().unwrap_or("")
+                );
+            }
+        }
+        if !gaps.missing_from_feed.is_empty() {
+            println!("Missing from feed ({}):", gaps.missing_from_feed.len());
+            for episode in &gaps.missing_from_feed {
+                println!(
+                    "  {:<10}  {}  {}",
+                    episode.release_date.as_deref().unwrap_or(""),
+                    episode.title,
+                    episode.url
+                );
+            }
+        }
+        if gaps.is_empty() {
+            println!("✅ Feed and Apple list the same episodes.");
+        }
+    }
+
+    if !report.gaps.is_empty() {
+        return Err(Failure::Reported(None));
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+//! Runners that move whole collections between archives: `archive merge`,
+//! `archive export` and `archive import`
+
+use std::path::Path;
+
+use tracing::info;
+
+use super::{Context, Failure, Outcome};
+use crate::archive::bundle::{export, import};
+use crate::archive::merge::{merge, MergeReport};
+use crate::archive::{archive_path, Archive, ArchiveFilter};
+
+/// Runs `archive merge`: imports another archive's episodes and files into `archive`
+pub fn run_merge(context: &Context, archive: &Archive, other: &str) -> Outcome {
+    let path = archive_path(other);
+    let other =
+        Archive::open_read_only(&path).map_err(Failure::context("Error opening archive"))?;
+    if other.path() == archive.path() {
+        return Err(Failure::Message(
+            "cannot merge an archive into itself".to_string(),
+        ));
+    }
+
+    info!("🔀 Merging {}...", path.display());
+    let report = merge(archive, &other).map_err(Failure::context("Error merging archive"))?;
+    print_merge(context, &report)
+}
+
+/// Runs `archive export`: bundles the episodes `filter` selects into `out`
+pub fn run_export(
+    context: &Context,
+    archive: &Archive,
+    filter: &ArchiveFilter,
+    out: &str,
+) -> Outcome {
+    info!("📦 Exporting to {}...", out);
+    let report = export(archive, filter, Path::new(out))
+        .map_err(Failure::context("Error exporting archive"))?;
+    context.print_report(&report)?;
+    if !context.console.json {
+        println!(
+            "Exported {} episode(s) and {} file(s) to {}",
+            report.episodes.len(),
+            report.files,
+            out
+        );
+    }
+    Ok(())
+}
+
+/// Runs `archive import`: merges an `archive export` bundle into `archive`
+pub fn run_import(context: &Context, archive: &Archive, bundle: &str) -> Outcome {
+    info!("📦 Importing {}...", bundle);
+    let report =
+        import(archive, Path::new(bundle)).map_err(Failure::context("Error importing archive"))?;
+    print_merge(context, &report)
+}
+
+/// Prints what a merge or import did, one line per imported or kept episode
+fn print_merge(context: &Context, report: &MergeReport) -> Outcome {
+    context.print_report(report)?;
+    if !context.console.json {
+        for id in &report.added {
+            println!("➕ {}", id);
+        }
+        for id in &report.updated {
+            println!("🔄 {} (newer copy imported)", id);
+        }
+        for id in &report.kept {
+            println!("⏸️ {} (newer copy kept)", id);
+        }
+        println!(
+            "Added {}, updated {}, kept {}, unchanged {}; {} file(s) copied",
+            report.added.len(),
+            report.updated.len(),
+            report.kept.len(),
+            report.unchanged,
+            report.files_copied
+        );
+    }
+    Ok(())
+}
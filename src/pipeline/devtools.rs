@@ -0,0 +1,178 @@
+//! Runners for the `devtools` commands, which help maintain the page extraction
+
+use std::fs;
+
+use super::{Context, Failure, Outcome};
+use crate::compress::{artifact_name, read_artifact};
+use crate::devtools::{coverage, diff_extractions, extract_all};
+use crate::error::{Error, Result};
+use crate::stats::{today, StrategyStats, Tally, RECENT_DAYS};
+
+/// Runs `devtools diff-extract`: field-level and selector-level differences between two snapshots
+pub fn run_diff_extract(context: &Context, old_path: &str, new_path: &str) -> Outcome {
+    let read = |path: &str| read_artifact(path).map_err(Error::io("Failed to read HTML file"));
+    let diff = diff_extractions(
+        &extract_all(&read(old_path)?),
+        &extract_all(&read(new_path)?),
+    );
+
+    context.print_report(&diff)?;
+    if context.console.json {
+        return Ok(());
+    }
+
+    let show = |value: &Option<String>| match value {
+        Some(value) => format!("{:?}", value),
+        None => "(missing)".to_string(),
+    };
+
+    println!("🔬 {} → {}", old_path, new_path);
+    println!(
+        "Variant: {} → {}",
+        diff.variant.old.unwrap_or("(unrecognized)"),
+        diff.variant.new.unwrap_or("(unrecognized)")
+    );
+
+    println!("Fields:");
+    if diff.fields.is_empty() {
+        println!("  (no changes)");
+    }
+    for change in &diff.fields {
+        println!(
+            "  ~ {}: {} → {}",
+            change.name,
+            show(&change.old),
+            show(&change.new)
+        );
+    }
+    if !diff.unchanged.is_empty() {
+        println!("  = {}", diff.unchanged.join(", "));
+    }
+
+    println!("Selectors:");
+    if diff.selectors.is_empty() {
+        println!("  (no changes)");
+    }
+    for change in &diff.selectors {
+        println!(
+            "  {} {}: {} → {} match(es)",
+            if change.new == 0 { "✗" } else { "✓" },
+            change.name,
+            change.old,
+            change.new
+        );
+    }
+    Ok(())
+}
+
+/// Runs `devtools coverage`: per-field and per-strategy success counts over a directory of pages
+pub fn run_coverage(context: &Context, dir: &str) -> Outcome {
+    let entries = fs::read_dir(dir).map_err(Error::io("Failed to read fixture directory"))?;
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            artifact_name(path)
+                .extension()
+                .is_some_and(|ext| ext == "html")
+        })
+        .collect();
+    paths.sort();
+
+    let pages = paths
+        .iter()
+        .map(|path| read_artifact(path).map_err(Error::io("Failed to read HTML file")))
+        .collect::<Result<Vec<String>>>()?;
+    let coverage = coverage(&pages);
+
+    context.print_report(&coverage)?;
+    if context.console.json {
+        return Ok(());
+    }
+
+    println!(
+        "📊 Extraction coverage over {} page(s) in {}",
+        coverage.pages, dir
+    );
+
+    println!("Fields:");
+    for (field, hits) in &coverage.fields {
+        println!("  {:<16} {}/{}", field, hits, coverage.pages);
+    }
+
+    println!("Strategies:");
+    for entry in &coverage.strategies {
+        println!(
+            "  {} {:<16} {:<48} {}/{}",
+            if entry.hits == 0 { "✗" } else { "✓" },
+            entry.field,
+            entry.strategy,
+            entry.hits,
+            coverage.pages
+        );
+    }
+    Ok(())
+}
+
+/// Runs `devtools strategy-stats`: each strategy's recent success rate against the days before
+///
+/// Reads the stats file at `path`, which the binary resolves before calling.
+pub fn run_strategy_stats(context: &Context, path: &str) -> Outcome {
+    let report = StrategyStats::load(path)
+        .map_err(Failure::context("Error reading strategy stats"))?
+        .trends(&today());
+
+    context.print_report(&report)?;
+    if context.console.json {
+        return Ok(());
+    }
+
+    let Some(since) = &report.since else {
+        println!(
+            "No strategy stats in {} yet; they are counted as episode pages are fetched.",
+            path
+        );
+        return Ok(());
+    };
+    let rate = |tally: &Tally| match tally.rate() {
+        Some(rate) => format!(
+            "{}/{} {:>3.0}%",
+            tally.succeeded,
+            tally.attempts(),
+            rate * 100.0
+        ),
+        None => "-".to_string(),
+    };
+    println!(
+        "📈 Strategy stats since {}: {} page(s) in the last {} days, {} before ({})",
+        since, report.recent_pages, RECENT_DAYS, report.earlier_pages, path
+    );
+    println!(
+        "    {:<16} {:<48} {:>16} {:>16}",
+        "field", "strategy", "recent", "before"
+    );
+    for trend in &report.strategies {
+        println!(
+            "  {} {:<16} {:<48} {:>16} {:>16}",
+            if trend.drifting() { "⚠" } else { " " },
+            trend.field,
+            trend.strategy,
+            rate(&trend.recent),
+            rate(&trend.earlier)
+        );
+    }
+
+    let drifting: Vec<String> = report
+        .drifting()
+        .iter()
+        .map(|trend| format!("{} via {}", trend.field, trend.strategy))
+        .collect();
+    if drifting.is_empty() {
+        println!("✅ No strategy is succeeding less often than before.");
+    } else {
+        println!(
+            "⚠️ Succeeding less often than before, so Apple may be changing its pages: {}",
+            drifting.join(", ")
+        );
+    }
+    Ok(())
+}
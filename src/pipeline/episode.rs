@@ -0,0 +1,510 @@
+//! Runners for a single episode: the default run, `fetch`, `metadata`, `transcript`, `audio` and `compare`
+
+use std::path::{Path, PathBuf};
+
+use tracing::{error, info, instrument, warn};
+
+use super::show::{run_show, CrawlOptions};
+use super::steps::{
+    archive_step, artwork_step, audio_step, convert_transcript_step, description_step,
+    episode_step, fetch_step, git_commit_step, metadata_formats_step, metadata_step, override_step,
+    save_html_step, speech_step, tag_step, transcript_language_step, transcript_step,
+};
+use super::{
+    Context, Failure, Outcome, EPISODES_DIR, HTML_FILE, METADATA_FILE, SKIP_LIST_FILE,
+    TRANSCRIPT_FILE,
+};
+use crate::ads::detect_ad_segments;
+use crate::apple_url::{episode_id_from_url, is_show_url, validate_url};
+use crate::archive::Archive;
+use crate::artwork::ArtworkSize;
+use crate::batch::run_concurrently;
+use crate::compress::{artifact_name, read_artifact, stored_path};
+use crate::episode::{saved_pages, Episode};
+use crate::error::{Error, Result};
+use crate::fetch::HttpOptions;
+use crate::lookup::MetadataSource;
+use crate::metadata::{canonical_url, Metadata};
+use crate::output::{
+    sanitize_file_name, save_metadata_json, save_skip_list, write_json, NameFields, RunReport,
+};
+use crate::speech::SpeechCommand;
+use crate::strip::strip_ads;
+use crate::transcript::convert::TranscriptFormat;
+use crate::transcript::{format_offset, Transcript};
+use crate::youtube::{align, fetch_captions, watch_url, YoutubeComparison, YOUTUBE_FILE};
+
+/// Share of matching phrases below which `compare` warns that the offset is unreliable
+const MIN_AGREEMENT: f64 = 0.8;
+
+/// What the default all-in-one run saves besides the page and metadata
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Detect likely ad segments in the transcript and record them in metadata
+    pub detect_ads: bool,
+    /// Export detected ad segments as a JSON skip-list
+    pub skip_list: bool,
+    /// Save a copy of the downloaded audio with the detected ad segments cut out
+    pub strip_ads: bool,
+    /// Storefronts to also capture titles and descriptions from
+    pub locales: Vec<String>,
+    pub download_audio: bool,
+    /// Write the metadata, artwork and chapters into the downloaded audio
+    pub tag_audio: bool,
+    pub no_transcript: bool,
+    /// How many of the episode's downloads run at once
+    pub concurrency: usize,
+    pub source: MetadataSource,
+    /// Artwork sizes to download; none skips the artwork
+    pub download_artwork: Vec<ArtworkSize>,
+    /// Read the show notes aloud into `show-notes.wav`
+    pub tts_notes: bool,
+    pub tts_command: SpeechCommand,
+    /// Formats to convert the transcript to
+    pub formats: Vec<TranscriptFormat>,
+    /// How a show URL is crawled
+    pub crawl: CrawlOptions,
+}
+
+/// Runs the default all-in-one pipeline: page, metadata, transcript and optional ad detection
+pub async fn run_all(
+    context: &Context,
+    url: &str,
+    run: &RunOptions,
+    archive: Option<&Archive>,
+) -> Outcome {
+    // Show URLs are crawled episode by episode instead of scraped as a single page
+    if is_show_url(url) {
+        run_show(context, url, &run.crawl, &run.formats, archive, false).await?;
+        return Ok(());
+    }
+
+    let mut report = RunReport::new(url);
+    let html = fetch_step(context, url).await?;
+    let episode = episode_step(context, url, html, run.source).await?;
+    let dir = save_html_step(context, &episode, &mut report)?;
+    let mut metadata = metadata_step(context, &episode, &run.locales, &dir, &mut report).await?;
+
+    if run.no_transcript {
+        info!("⏭️ Transcript skipped (--no-transcript)");
+    }
+    let (transcript, audio) = downloads_step(context, run, &episode, &dir, &mut report).await;
+    if let Some(transcript) = &transcript {
+        transcript_language_step(&mut metadata, transcript, &dir, &mut report)?;
+    }
+    if let Some(archive) = archive {
+        archive_step(archive, &episode.url, &metadata, &dir, transcript.is_some());
+    }
+
+    if run.tts_notes {
+        speech_step(&metadata, &run.tts_command, &dir, &mut report);
+    }
+
+    match transcript {
+        Some(transcript) if run.detect_ads => {
+            let audio = audio.as_deref();
+            run_ad_detection(
+                run,
+                &episode,
+                metadata,
+                &transcript,
+                audio,
+                &dir,
+                &mut report,
+            )?
+        }
+        None if run.detect_ads => warn!("Ad detection skipped: no transcript available."),
+        _ => {}
+    }
+
+    if let (true, Some(audio_path)) = (run.tag_audio, audio) {
+        // The saved metadata, with overrides, formatted description and detected language
+        let metadata = report
+            .metadata
+            .clone()
+            .unwrap_or_else(|| episode.metadata.clone());
+        tag_step(context, &episode, &metadata, &audio_path).await;
+    }
+
+    if let Some(metadata) = &report.metadata {
+        let paths = metadata_formats_step(context, metadata, &episode.url, &dir);
+        report.outputs.extend(paths);
+    }
+    let summary = format!("Save {}", episode.metadata.episode_title);
+    git_commit_step(context, &summary, &[]);
+    context.print_report(&report)
+}
+
+/// One of an episode's downloads, started once its page is parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Asset {
+    Transcript,
+    Audio,
+    Artwork,
+}
+
+/// What downloading one asset produced; the files it wrote are in its own report
+enum Downloaded {
+    Transcript(Option<Transcript>),
+    Audio(Option<String>),
+    Artwork,
+}
+
+/// Downloads the transcript, audio and artwork the run asked for, up to `--concurrency` at once
+///
+/// Every URL is known from the parsed page, so an episode takes about as
+/// long as its largest file. Each download records into its own report,
+/// merged back in transcript, audio, artwork order. Returns the transcript
+/// and the audio file's path.
+#[instrument(level = "debug", skip_all)]
+async fn downloads_step(
+    context: &Context,
+    run: &RunOptions,
+    episode: &Episode,
+    dir: &str,
+    report: &mut RunReport,
+) -> (Option<Transcript>, Option<String>) {
+    let assets: Vec<Asset> = [
+        (!run.no_transcript).then_some(Asset::Transcript),
+        run.download_audio.then_some(Asset::Audio),
+        (!run.download_artwork.is_empty()).then_some(Asset::Artwork),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    let results = run_concurrently(assets, run.concurrency, |asset| async move {
+        let mut part = RunReport::default();
+        let downloaded = match asset {
+            Asset::Transcript => Downloaded::Transcript(
+                transcript_step(context, episode, &run.formats, dir, &mut part).await,
+            ),
+            Asset::Audio => Downloaded::Audio(audio_step(context, episode, dir, &mut part).await),
+            Asset::Artwork => {
+                artwork_step(context, episode, &run.download_artwork, dir, &mut part).await;
+                Downloaded::Artwork
+            }
+        };
+        (downloaded, part)
+    })
+    .await;
+
+    let (mut transcript, mut audio) = (None, None);
+    for (downloaded, part) in results {
+        report.merge(part);
+        match downloaded {
+            Downloaded::Transcript(found) => transcript = found,
+            Downloaded::Audio(path) => audio = path,
+            Downloaded::Artwork => {}
+        }
+    }
+    (transcript, audio)
+}
+
+/// Detects ads in the transcript, records them in metadata and optionally exports a skip-list
+/// and cuts them from the downloaded audio
+fn run_ad_detection(
+    run: &RunOptions,
+    episode: &Episode,
+    metadata: Metadata,
+    transcript: &Transcript,
+    audio: Option<&str>,
+    dir: &str,
+    report: &mut RunReport,
+) -> Outcome {
+    let segments = match transcript.cues().and_then(|cues| detect_ad_segments(&cues)) {
+        Ok(segments) => segments,
+        Err(e) => {
+            warn!("Failed to detect ads: {}", e);
+            return Ok(());
+        }
+    };
+
+    info!("✅ Detected {} likely ad segment(s)", segments.len());
+
+    if run.skip_list {
+        let skip_list_path = format!("{}/{}", dir, SKIP_LIST_FILE);
+        match save_skip_list(&episode.key(), &segments, &skip_list_path) {
+            Ok(_) => {
+                info!("✅ Skip-list saved to {}", skip_list_path);
+                report.outputs.push(skip_list_path);
+            }
+            Err(e) => warn!("Failed to save skip-list: {}", e),
+        }
+    }
+
+    match audio {
+        Some(audio) if run.strip_ads => {
+            let ranges: Vec<_> = segments
+                .iter()
+                .map(|segment| (segment.start_seconds, segment.end_seconds))
+                .collect();
+            match strip_ads(audio, &ranges) {
+                Ok(stripped) => {
+                    info!(
+                        "✂️ Cut {} of ads; ad-free audio saved to {}",
+                        format_offset(stripped.removed_seconds),
+                        stripped.path
+                    );
+                    report.outputs.push(stripped.path);
+                }
+                Err(e) => warn!("Failed to cut ads from audio: {}", e),
+            }
+        }
+        None if run.strip_ads => warn!("Ads not cut: no audio was downloaded."),
+        _ => {}
+    }
+
+    let metadata = Metadata {
+        ad_segments: Some(segments),
+        ..metadata
+    };
+    save_metadata_json(&metadata, &format!("{}/{}", dir, METADATA_FILE))
+        .map_err(Failure::context("Error saving metadata"))?;
+    report.metadata = Some(metadata);
+    Ok(())
+}
+
+/// Runs `fetch`: saves the page, named from its metadata when the page has any
+pub async fn run_fetch(context: &Context, url: &str) -> Outcome {
+    let html = fetch_step(context, url).await?;
+    let metadata = Episode::from_html_with(url, html.clone(), &context.http, context.extraction)
+        .ok()
+        .map(|episode| episode.metadata);
+    let dir = context
+        .layout
+        .episode_dir(&NameFields::new(url, metadata.as_ref()));
+    let html_path = context
+        .layout
+        .write_artifact(&format!("{}/{}", dir, HTML_FILE), &html)?;
+
+    context.print_report(&RunReport {
+        metadata,
+        outputs: vec![html_path],
+        ..RunReport::new(url)
+    })
+}
+
+/// Runs `metadata` on a URL or a saved HTML file
+pub async fn run_metadata(
+    context: &Context,
+    source: &str,
+    locales: &[String],
+    from: MetadataSource,
+) -> Outcome {
+    let saved = stored_path(source).is_some();
+    let (url, html) = if saved {
+        let html = read_artifact(source).map_err(Error::io("Failed to read HTML file"))?;
+        info!("📄 Read HTML from {}", source);
+
+        // Saved Apple pages carry their canonical URL, which tells us the storefront
+        let url = canonical_url(&html).unwrap_or_else(|| source.to_string());
+        (url, html)
+    } else {
+        (source.to_string(), fetch_step(context, source).await?)
+    };
+
+    if !locales.is_empty() && validate_url(&url).is_err() {
+        warn!("--locales ignored: the saved page has no canonical URL");
+    }
+
+    let mut report = RunReport::new(&url);
+    let episode = episode_step(context, &url, html, from).await?;
+    let dir = if saved {
+        context
+            .layout
+            .episode_dir(&NameFields::new(&url, Some(&episode.metadata)))
+    } else {
+        save_html_step(context, &episode, &mut report)?
+    };
+    let metadata = metadata_step(context, &episode, locales, &dir, &mut report).await?;
+    let paths = metadata_formats_step(context, &metadata, &episode.url, &dir);
+    report.outputs.extend(paths);
+    context.print_report(&report)
+}
+
+/// Runs `metadata --from-html`: extracts saved pages without any network access
+///
+/// `transcript` is `None` to skip transcripts, else the TTML to use; by default
+/// each page's `transcript.ttml` sibling is read when there is one. A directory
+/// is processed page by page, and fails if any page failed; a TTML file can
+/// only go with a single page.
+pub fn run_offline_metadata(
+    context: &Context,
+    path: &str,
+    transcript: Option<Option<&str>>,
+    formats: &[TranscriptFormat],
+) -> Outcome {
+    if !Path::new(path).is_dir() {
+        let transcript = match transcript {
+            Some(Some(file)) => Some(PathBuf::from(file)),
+            Some(None) => local_transcript(Path::new(path)),
+            None => None,
+        };
+        let report = process_saved_page(
+            context,
+            Path::new(path),
+            transcript.as_deref(),
+            formats,
+            false,
+        )?;
+        return context.print_report(&report);
+    }
+
+    let pages = saved_pages(path)?;
+    info!("📂 {} saved page(s) in {}", pages.len(), path);
+
+    let mut reports = Vec::new();
+    let mut failed = 0;
+    for page in &pages {
+        let transcript = transcript.and_then(|_| local_transcript(page));
+        match process_saved_page(context, page, transcript.as_deref(), formats, true) {
+            Ok(report) => reports.push(report),
+            Err(e) => {
+                error!("{}: {}", page.display(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    context.print_report(&reports)?;
+    info!("🏁 {} succeeded, {} failed", reports.len(), failed);
+    if failed > 0 {
+        return Err(Failure::Reported(None));
+    }
+    Ok(())
+}
+
+/// The `transcript.ttml` saved beside a page, compressed or not, if there is one
+fn local_transcript(page: &Path) -> Option<PathBuf> {
+    stored_path(page.with_file_name(TRANSCRIPT_FILE))
+}
+
+/// Extracts one saved page into its episode directory, along with `transcript` if given
+///
+/// With `own_dir` and no name template, each page gets `episodes/<id>` as batch runs do.
+fn process_saved_page(
+    context: &Context,
+    page: &Path,
+    transcript: Option<&Path>,
+    formats: &[TranscriptFormat],
+    own_dir: bool,
+) -> Result<RunReport> {
+    let episode = Episode::from_file(page, &HttpOptions::default())?;
+    info!("📄 Read HTML from {}", page.display());
+
+    let layout = &context.layout;
+    let mut report = RunReport::new(&episode.url);
+    let dir = if own_dir && layout.template.is_none() {
+        let id =
+            episode_id_from_url(&episode.url).unwrap_or_else(|| sanitize_file_name(&episode.key()));
+        layout.path(&format!("{}/{}", EPISODES_DIR, id))
+    } else {
+        layout.episode_dir(&NameFields::new(&episode.url, Some(&episode.metadata)))
+    };
+    let mut metadata = episode.metadata.clone();
+    description_step(context, &mut metadata);
+    override_step(context, &episode.url, &mut metadata);
+
+    if let Some(path) = transcript {
+        let transcript = Transcript::from_file(path)?;
+        let transcript_path = format!("{}/{}", dir, TRANSCRIPT_FILE);
+        let saved = if artifact_name(path) == Path::new(&transcript_path) {
+            path.display().to_string()
+        } else {
+            layout.write_artifact(&transcript_path, &transcript.ttml)?
+        };
+        info!("✅ Transcript read from {}", path.display());
+        report.outputs.push(saved);
+        convert_transcript_step(&transcript, formats, &transcript_path, &mut report);
+        match transcript.language() {
+            Ok(language) => metadata.transcript_language = language,
+            Err(e) => warn!("Failed to detect the transcript language: {}", e),
+        }
+    }
+
+    let metadata_path = format!("{}/{}", dir, METADATA_FILE);
+    save_metadata_json(&metadata, &metadata_path)?;
+    info!("✅ Metadata extracted and saved to {}", metadata_path);
+    report.outputs.push(metadata_path);
+    let paths = metadata_formats_step(context, &metadata, &episode.url, &dir);
+    report.outputs.extend(paths);
+    report.metadata = Some(metadata);
+    Ok(report)
+}
+
+/// Runs `transcript`: saves the page and the transcript, failing when the episode has none
+pub async fn run_transcript(context: &Context, url: &str, formats: &[TranscriptFormat]) -> Outcome {
+    let mut report = RunReport::new(url);
+    let html = fetch_step(context, url).await?;
+    let episode = episode_step(context, url, html, MetadataSource::Scrape).await?;
+    let dir = save_html_step(context, &episode, &mut report)?;
+    let found = transcript_step(context, &episode, formats, &dir, &mut report).await;
+    context.print_report(&report)?;
+    match found {
+        Some(_) => Ok(()),
+        None => Err(Failure::NoTranscript),
+    }
+}
+
+/// Runs `audio`: saves the page and the audio, tagging it with `tag_audio`
+pub async fn run_audio(context: &Context, url: &str, tag_audio: bool) -> Outcome {
+    let mut report = RunReport::new(url);
+    let html = fetch_step(context, url).await?;
+    let episode = episode_step(context, url, html, MetadataSource::Scrape).await?;
+    let dir = save_html_step(context, &episode, &mut report)?;
+    let audio = audio_step(context, &episode, &dir, &mut report).await;
+    if let (true, Some(audio_path)) = (tag_audio, audio) {
+        tag_step(context, &episode, &episode.metadata, &audio_path).await;
+    }
+    context.print_report(&report)
+}
+
+/// Runs `compare`: aligns the episode's transcript against its YouTube captions and saves
+/// the offset between the two to `youtube.json`
+pub async fn run_compare(context: &Context, url: &str, video_id: &str) -> Outcome {
+    let mut report = RunReport::new(url);
+    let html = fetch_step(context, url).await?;
+    let episode = episode_step(context, url, html, MetadataSource::Scrape).await?;
+    let dir = save_html_step(context, &episode, &mut report)?;
+    let Some(transcript) = transcript_step(context, &episode, &[], &dir, &mut report).await else {
+        return Err(Failure::NoTranscript);
+    };
+    let cues = transcript
+        .cues()
+        .map_err(Failure::context("Error parsing transcript"))?;
+    let language = transcript.language().ok().flatten();
+
+    info!("🎬 Fetching YouTube captions for {}...", video_id);
+    let (track, captions) = fetch_captions(video_id, language.as_deref(), &context.http)
+        .await
+        .map_err(Failure::context("Error fetching YouTube captions"))?;
+    let Some(alignment) = align(&cues, &captions) else {
+        return Err(Failure::Message(
+            "Too few phrases match between the transcript and the YouTube captions to align them"
+                .to_string(),
+        ));
+    };
+    if alignment.agreement < MIN_AGREEMENT {
+        warn!(
+            "Only {:.0}% of matching phrases agree on the offset; the YouTube version may be cut differently",
+            alignment.agreement * 100.0
+        );
+    }
+
+    let comparison = YoutubeComparison {
+        video_id: video_id.to_string(),
+        video_url: watch_url(video_id),
+        captions_language: track.language.clone(),
+        auto_captions: track.is_auto(),
+        alignment,
+    };
+    let path = format!("{}/{}", dir, YOUTUBE_FILE);
+    write_json(&comparison, &path).map_err(Failure::context("Error saving comparison"))?;
+    info!(
+        "✅ YouTube is {:+.2}s from Apple ({} matching phrases); saved to {}",
+        comparison.alignment.offset_seconds, comparison.alignment.anchors, path
+    );
+    report.outputs.push(path);
+    context.print_report(&report)
+}
@@ -0,0 +1,89 @@
+//! Runners that check the environment: `healthcheck` and `doctor network`
+
+use std::path::PathBuf;
+
+use super::{Context, Failure, Outcome};
+use crate::archive::{archive_path, check_integrity};
+use crate::diagnostics::{
+    check_reachable, check_writable, diagnose_endpoint, DIAGNOSTIC_TIMEOUT, NETWORK_ENDPOINTS,
+};
+
+/// Runs `healthcheck`: config, output directory, archives and Apple connectivity
+///
+/// `config` is the config file that was loaded, if any; one that fails to
+/// load never gets here, as the binary reports it as unhealthy itself. Fails
+/// when any check does, after printing every result.
+pub async fn run_healthcheck(context: &Context, config: Option<PathBuf>, db: &[String]) -> Outcome {
+    let mut healthy = true;
+
+    match config {
+        Some(path) => println!("✅ config: {} is valid", path.display()),
+        None => println!("✅ config: none found, using defaults"),
+    }
+
+    match check_writable(&context.layout.dir) {
+        Ok(()) => println!("✅ output: {} is writable", context.layout.dir),
+        Err(e) => {
+            println!("❌ output: {}", e);
+            healthy = false;
+        }
+    }
+
+    for path in db {
+        let path = archive_path(path);
+        match check_integrity(&path) {
+            Ok(()) => println!("✅ archive: {} passed its integrity check", path.display()),
+            Err(e) => {
+                println!("❌ archive: {}", e);
+                healthy = false;
+            }
+        }
+    }
+
+    let endpoint = &NETWORK_ENDPOINTS[0];
+    match check_reachable(endpoint.url, &context.http).await {
+        Ok(()) => println!("✅ network: {} reachable", endpoint.url),
+        Err(e) => {
+            println!("❌ network: {} unreachable: {}", endpoint.url, e);
+            healthy = false;
+        }
+    }
+
+    if healthy {
+        Ok(())
+    } else {
+        Err(Failure::Reported(None))
+    }
+}
+
+/// Runs `doctor network` over every endpoint, failing when any is unreachable
+pub async fn run_network_doctor() -> Outcome {
+    println!("🩺 Checking network connectivity...");
+
+    let mut failures = 0;
+    for endpoint in NETWORK_ENDPOINTS {
+        println!("{} ({})", endpoint.name, endpoint.url);
+
+        let steps = diagnose_endpoint(endpoint.url, DIAGNOSTIC_TIMEOUT).await;
+        for step in &steps {
+            let icon = if step.ok { "✅" } else { "❌" };
+            println!("  {} {}: {}", icon, step.leg, step.detail);
+        }
+
+        if steps.iter().any(|step| !step.ok) {
+            failures += 1;
+        }
+    }
+
+    if failures == 0 {
+        println!("✅ All endpoints reachable.");
+        Ok(())
+    } else {
+        println!(
+            "⚠️ {} of {} endpoint(s) unreachable.",
+            failures,
+            NETWORK_ENDPOINTS.len()
+        );
+        Err(Failure::Reported(None))
+    }
+}
@@ -0,0 +1,434 @@
+//! Runners for a whole show: `show`, `sync` and their `--dry-run` plans
+
+use std::collections::HashSet;
+
+use tracing::{error, info, warn};
+
+use super::steps::{
+    archive_id, archive_step, crawl_episode, git_commit_step, repair_episode, show_episode_dir,
+};
+use super::watch::{digest_step, notify_show_webhook};
+use super::{Context, Failure, Outcome, SHOW_HTML_FILE, SHOW_INDEX_FILE};
+use crate::apple_url::{show_id_from_url, ParsedUrl};
+use crate::archive::Archive;
+use crate::compress::read_artifact;
+use crate::digest::Digest;
+use crate::error::{Error, Result};
+use crate::manifest::{decide, Decision, PlannedEpisode};
+use crate::output::{write_json, IndexEntry, ShowIndex};
+use crate::show::{
+    cursor_path, enumerate_episodes, extract_show_metadata, EpisodeFilter, EpisodeSummary,
+    ShowCursor,
+};
+use crate::transcript::convert::TranscriptFormat;
+use crate::watch::NewEpisode;
+
+/// How `show`, `sync` and `batch` crawl a show's episodes
+#[derive(Debug, Clone, Default)]
+pub struct CrawlOptions {
+    /// Which of the show's episodes to process
+    pub filter: EpisodeFilter,
+    /// How many listing pages to fetch at once when their offsets are known
+    pub page_concurrency: usize,
+    /// Skip episodes whose saved files are all unchanged since they were saved
+    pub skip_existing: bool,
+    /// Fetch every episode again, overwriting files edited since they were saved
+    pub force: bool,
+    /// Only print which episodes would be fetched, skipped or refused
+    pub dry_run: bool,
+}
+
+impl CrawlOptions {
+    /// Whether an episode saved by an earlier run is fetched, skipped or repaired
+    ///
+    /// Fails when files edited since they were saved would be overwritten
+    /// without `force`.
+    pub fn existing(&self, directory: &str) -> Result<Decision> {
+        match decide(directory, self.skip_existing, self.force)? {
+            Decision::Changed(files) => Err(Error::Changed(files)),
+            decision => Ok(decision),
+        }
+    }
+
+    /// What a run would do with an episode directory, for `--dry-run`
+    pub fn plan(&self, url: &str, directory: Option<String>) -> PlannedEpisode {
+        let decision = match &directory {
+            Some(directory) => {
+                decide(directory, self.skip_existing, self.force).unwrap_or(Decision::Fetch)
+            }
+            None => Decision::Fetch,
+        };
+        let action = decision.action();
+        let (changed, missing) = match decision {
+            Decision::Changed(files) => (files, Vec::new()),
+            Decision::Repair(files) => (Vec::new(), files),
+            _ => (Vec::new(), Vec::new()),
+        };
+        PlannedEpisode {
+            url: url.to_string(),
+            directory,
+            action,
+            changed,
+            missing,
+        }
+    }
+}
+
+/// Runs `show`: enumerates a show's episodes and processes each into its own directory
+///
+/// With `only_new`, episodes already in the archive are skipped, and once the
+/// whole show has been listed afresh, archived episodes missing from it are
+/// marked removed upstream. Returns the episodes processed successfully, for
+/// `sync --print-new`.
+pub async fn run_show(
+    context: &Context,
+    url: &str,
+    crawl: &CrawlOptions,
+    formats: &[TranscriptFormat],
+    archive: Option<&Archive>,
+    only_new: bool,
+) -> Outcome<Vec<NewEpisode>> {
+    // Reject anything that is not an Apple Podcasts link before fetching it
+    ParsedUrl::parse(url)?;
+
+    if crawl.dry_run {
+        print_plan(
+            context,
+            &show_plan(context, url, crawl, archive.filter(|_| only_new)),
+        )?;
+        return Ok(Vec::new());
+    }
+
+    // Print the received URL
+    info!("📥 Received URL: {}", url);
+
+    // Resume from the previous run's cursor so large catalogs are enumerated incrementally
+    let cursor_path = cursor_path(&context.layout.dir, url);
+    // Telling removed episodes apart needs the whole listing as Apple shows it now,
+    // so a sync lists a show it has seen in full again from the top
+    let check_removed = only_new && archive.is_some() && crawl.filter == EpisodeFilter::default();
+    let mut cursor = match ShowCursor::load_from(&context.layout.dir, url) {
+        Ok(Some(cursor)) if check_removed && cursor.next_page.is_none() => ShowCursor::new(url),
+        Ok(Some(cursor)) => {
+            info!(
+                "↩️ Resuming enumeration: {} episode(s) already listed",
+                cursor.episodes.len()
+            );
+            cursor
+        }
+        Ok(None) => ShowCursor::new(url),
+        Err(e) => {
+            warn!("Ignoring unreadable show cursor {}: {}", cursor_path, e);
+            ShowCursor::new(url)
+        }
+    };
+    let fresh = cursor.episodes.is_empty();
+    let mut save_cursor = |cursor: &ShowCursor| {
+        if let Err(e) = write_json(cursor, &cursor_path) {
+            warn!("Failed to save show cursor: {}", e);
+        }
+    };
+    let (html, mut episodes) = enumerate_episodes(
+        url,
+        &context.http,
+        &crawl.filter,
+        crawl.page_concurrency,
+        &mut cursor,
+        &mut save_cursor,
+    )
+    .await?;
+    if cursor.next_page.is_some() {
+        info!(
+            "⏸️ More episodes remain; rerun to continue from {}",
+            cursor_path
+        );
+    }
+    context
+        .layout
+        .write_artifact(&context.layout.path(SHOW_HTML_FILE), &html)?;
+    let show =
+        extract_show_metadata(&html).map_err(Failure::context("Error extracting show details"))?;
+
+    if let Some(archive) = archive {
+        if let Err(e) = archive.record_show(url, &show) {
+            warn!("Failed to record show in archive: {}", e);
+        }
+        if check_removed && fresh && cursor.next_page.is_none() {
+            removed_upstream_step(archive, url, &cursor.episodes);
+        }
+        if only_new {
+            let archived = archive
+                .episode_ids()
+                .map_err(Failure::context("Error reading archive"))?;
+            let listed = episodes.len();
+            episodes.retain(|summary| !archived.contains(&archive_id(&summary.url)));
+            info!(
+                "🗄️ {} of {} listed episode(s) already archived",
+                listed - episodes.len(),
+                listed
+            );
+        }
+    }
+
+    info!(
+        "🎙️ {}: {} episode(s) to process",
+        show.show_title,
+        episodes.len()
+    );
+
+    let total = episodes.len();
+    let mut entries = Vec::new();
+    let mut processed = Vec::new();
+    for (index, summary) in episodes.into_iter().enumerate() {
+        let directory = show_episode_dir(context, &show.show_title, &summary);
+        let result = match crawl.existing(&directory) {
+            Ok(Decision::Skip { transcript }) => {
+                info!(
+                    "  [{}/{}] ⏭️ {} (unchanged, skipped)",
+                    index + 1,
+                    total,
+                    summary.title
+                );
+                entries.push(IndexEntry {
+                    episode: summary,
+                    directory,
+                    transcript,
+                    skipped: true,
+                    error: None,
+                });
+                continue;
+            }
+            Ok(Decision::Repair(missing)) => {
+                repair_episode(context, &summary.url, &directory, &missing, formats, None).await
+            }
+            Ok(_) => crawl_episode(context, &summary, &directory, formats).await,
+            Err(e) => Err(e),
+        };
+        let result = result.map(|(metadata, transcript)| {
+            if let Some(archive) = archive {
+                archive_step(archive, &summary.url, &metadata, &directory, transcript);
+            }
+            processed.push(NewEpisode {
+                show_url: url.to_string(),
+                show_title: show.show_title.clone(),
+                episode: summary.clone(),
+                directory: directory.clone(),
+                transcript,
+                metadata,
+            });
+            transcript
+        });
+
+        match &result {
+            Ok(true) => info!("  [{}/{}] ✅ {}", index + 1, total, summary.title),
+            Ok(false) => info!(
+                "  [{}/{}] ✅ {} (no transcript)",
+                index + 1,
+                total,
+                summary.title
+            ),
+            Err(e) => error!("[{}/{}] {}: {}", index + 1, total, summary.title, e),
+        }
+
+        entries.push(IndexEntry {
+            episode: summary,
+            directory,
+            transcript: result.as_ref().is_ok_and(|found| *found),
+            skipped: false,
+            error: result.err().map(|e| e.to_string()),
+        });
+    }
+
+    let failed = entries.iter().filter(|entry| entry.error.is_some()).count();
+    let index = ShowIndex {
+        show,
+        episodes: entries,
+    };
+    let index_path = context.layout.path(SHOW_INDEX_FILE);
+    write_json(&index, &index_path).map_err(Failure::context("Error saving show index"))?;
+
+    info!("✅ Show index saved to {}", index_path);
+    let summary = format!(
+        "{} {}: {} episode(s) saved, {} failed",
+        if only_new { "Sync" } else { "Show" },
+        index.show.show_title,
+        processed.len(),
+        failed
+    );
+    let titles: Vec<String> = processed
+        .iter()
+        .map(|new| new.episode.title.clone())
+        .collect();
+    git_commit_step(context, &summary, &titles);
+    context.print_report(&index)?;
+    Ok(processed)
+}
+
+/// Runs `sync`: processes the show's episodes not yet in `archive` and notifies its webhook
+///
+/// With `print_new`, each new episode is printed as a JSON line. With a
+/// `digest`, the new episodes are also summarized in one page or email.
+pub async fn run_sync(
+    context: &Context,
+    url: &str,
+    crawl: &CrawlOptions,
+    formats: &[TranscriptFormat],
+    archive: &Archive,
+    print_new: bool,
+    digest: Option<&Digest>,
+) -> Outcome {
+    let new_episodes = run_show(context, url, crawl, formats, Some(archive), true).await?;
+    for episode in &new_episodes {
+        notify_show_webhook(context, episode).await;
+    }
+    digest_step(context, digest, &new_episodes).await;
+    if print_new {
+        context.print_ndjson(&new_episodes)?;
+    }
+    Ok(())
+}
+
+/// Marks archived episodes of the show missing from its complete listing as removed upstream
+///
+/// Failures are reported as warnings; the sync goes on.
+fn removed_upstream_step(archive: &Archive, url: &str, listed: &[EpisodeSummary]) {
+    let Some(show_id) = show_id_from_url(url) else {
+        return;
+    };
+    let listed: HashSet<String> = listed
+        .iter()
+        .map(|summary| archive_id(&summary.url))
+        .collect();
+    match archive.reconcile_listing(&show_id, &listed) {
+        Ok(changes) => {
+            for id in &changes.removed {
+                warn!(
+                    "🚫 {} is no longer listed by Apple; marked removed upstream",
+                    id
+                );
+            }
+            for id in &changes.restored {
+                info!("↩️ {} is listed again; no longer marked removed", id);
+            }
+        }
+        Err(e) => warn!("Failed to check for removed episodes: {}", e),
+    }
+}
+
+/// What `show` or `sync` would do, planned from the episodes the previous run listed
+///
+/// Without a saved listing the show has to be enumerated first, which needs
+/// the network, so the plan says only that.
+fn show_plan(
+    context: &Context,
+    url: &str,
+    crawl: &CrawlOptions,
+    archive: Option<&Archive>,
+) -> Vec<PlannedEpisode> {
+    let listed = ShowCursor::load_from(&context.layout.dir, url)
+        .ok()
+        .flatten();
+    let show = read_artifact(context.layout.path(SHOW_HTML_FILE))
+        .ok()
+        .and_then(|html| extract_show_metadata(&html).ok());
+    let (Some(cursor), Some(show)) = (listed, show) else {
+        return vec![PlannedEpisode {
+            url: url.to_string(),
+            directory: None,
+            action: "enumerate",
+            changed: Vec::new(),
+            missing: Vec::new(),
+        }];
+    };
+
+    let archived = archive
+        .and_then(|archive| archive.episode_ids().ok())
+        .unwrap_or_default();
+    crawl
+        .filter
+        .apply(&cursor.episodes)
+        .iter()
+        .filter(|summary| !archived.contains(&archive_id(&summary.url)))
+        .map(|summary| {
+            let directory = show_episode_dir(context, &show.show_title, summary);
+            crawl.plan(&summary.url, Some(directory))
+        })
+        .collect()
+}
+
+/// Prints a `--dry-run` plan, one episode per line or as JSON
+pub(crate) fn print_plan(context: &Context, plan: &[PlannedEpisode]) -> Outcome {
+    if context.console.json {
+        return context.print_report(&plan);
+    }
+    for episode in plan {
+        let directory = match (&episode.directory, episode.action) {
+            (Some(directory), _) => directory.as_str(),
+            (None, "enumerate") => "(episodes listed when run)",
+            (None, _) => "(named after fetching)",
+        };
+        match (episode.changed.as_slice(), episode.missing.as_slice()) {
+            ([], []) => println!("{:<9} {} -> {}", episode.action, episode.url, directory),
+            ([], missing) => println!(
+                "{:<9} {} -> {} ({} missing)",
+                episode.action,
+                episode.url,
+                directory,
+                missing.join(", ")
+            ),
+            (changed, _) => println!(
+                "{:<9} {} -> {} ({} changed; pass --force to overwrite)",
+                episode.action,
+                episode.url,
+                directory,
+                changed.join(", ")
+            ),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::Manifest;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Unit test - CrawlOptions skips saved episodes and refuses to overwrite edited ones
+    #[test]
+    fn test_crawl_options_existing_and_plan() {
+        // Given an episode saved with a transcript, then edited by hand
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        fs::write(temp_dir.path().join("metadata.json"), "{}").unwrap();
+        fs::write(temp_dir.path().join("transcript.ttml"), "<tt/>").unwrap();
+        Manifest::record(dir, "https://a", true).unwrap();
+        let skip = CrawlOptions {
+            skip_existing: true,
+            ..CrawlOptions::default()
+        };
+        assert_eq!(
+            skip.existing(dir).unwrap(),
+            Decision::Skip { transcript: true }
+        );
+        fs::write(temp_dir.path().join("metadata.json"), "[]").unwrap();
+
+        // When a skip-existing run checks it, with and without force
+        let refused = skip.existing(dir);
+        let forced = CrawlOptions {
+            force: true,
+            ..skip.clone()
+        };
+
+        // Then it is refused and planned as such, unless forced
+        assert!(matches!(refused, Err(Error::Changed(files)) if files == ["metadata.json"]));
+        let plan = skip.plan("https://a", Some(dir.to_string()));
+        assert_eq!(plan.changed, vec!["metadata.json"]);
+        assert_eq!(forced.existing(dir).unwrap(), Decision::Fetch);
+        assert!(forced
+            .plan("https://a", Some(dir.to_string()))
+            .changed
+            .is_empty());
+    }
+}
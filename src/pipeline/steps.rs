@@ -0,0 +1,859 @@
+//! The steps a run is built from, each saving one part of an episode
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use indicatif::{ProgressBar, ProgressStyle};
+use tracing::{debug, info, instrument, warn};
+
+use super::{
+    Context, Failure, Outcome, EPISODES_DIR, HTML_FILE, METADATA_FILE, NOTES_AUDIO_FILE,
+    TRANSCRIPT_FILE,
+};
+use crate::apple_url::{
+    episode_id_from_url, show_id_from_url, storefront_from_url, validate_url, ParsedUrl,
+};
+use crate::archive::Archive;
+use crate::artwork::{download_artwork, fill_artwork_template, find_artwork_template, ArtworkSize};
+use crate::audio::{audio_file_name, download_audio, find_audio_url};
+use crate::budget::Stage;
+use crate::compress::{artifact_name, read_artifact, stored_path};
+use crate::devtools::attempt_strategies;
+use crate::episode::Episode;
+use crate::error::{Error, Result};
+use crate::fetch::fetch_page;
+use crate::git::{commit_message, GitArchive};
+use crate::lookup::{lookup_episode, MetadataSource, LOOKUP_EPISODE_LIMIT};
+use crate::manifest::Manifest;
+use crate::metadata::format::{save_metadata_as, MetadataFormat};
+use crate::metadata::sanitize::{archive_images, sanitize_description, IMAGES_DIR};
+use crate::metadata::{fetch_localized_metadata, format_description, LocalizedMetadata, Metadata};
+use crate::output::{
+    save_converted_transcript, save_metadata_json, save_transcript_segments, write_file,
+    NameFields, RunReport,
+};
+use crate::overrides::{override_path, MetadataOverrides};
+use crate::show::EpisodeSummary;
+use crate::speech::{show_notes_text, SpeechCommand};
+use crate::stats::{today, StrategyStats};
+use crate::tag::{chapters_from_description, tag_audio, AudioTags, TAG_ARTWORK_SIZE};
+use crate::transcript::convert::TranscriptFormat;
+use crate::transcript::download_transcript_parts;
+use crate::transcript::stream::MappedTtml;
+use crate::transcript::Transcript;
+use crate::variant::{detect_variant, script_fingerprint, VARIANTS};
+
+/// Validates the URL and fetches the page
+#[instrument(level = "debug", skip_all)]
+pub async fn fetch_step(context: &Context, url: &str) -> Result<String> {
+    // Reject anything that is not an Apple Podcasts link before fetching it
+    ParsedUrl::parse(url)?;
+
+    // Print the received URL
+    info!("📥 Received URL: {}", url);
+
+    // Fetch HTML content
+    let html = context
+        .budgets
+        .run(Stage::Fetch, fetch_page(url, &context.http))
+        .await?;
+
+    info!("✅ Fetched HTML content.");
+    Ok(html)
+}
+
+/// Extracts episode metadata from fetched HTML
+#[instrument(level = "debug", skip_all)]
+pub async fn episode_step(
+    context: &Context,
+    url: &str,
+    html: String,
+    source: MetadataSource,
+) -> Outcome<Episode> {
+    let mut episode =
+        match Episode::from_html_with(url, html.clone(), &context.http, context.extraction) {
+            Ok(episode) => episode,
+            // With an API source the page only has to supply what the API lacks
+            Err(e) if source != MetadataSource::Scrape => {
+                warn!("Page extraction failed ({}); relying on the iTunes API", e);
+                Episode {
+                    url: url.to_string(),
+                    variant: detect_variant(&html),
+                    html,
+                    metadata: Metadata::default(),
+                    options: context.http.clone(),
+                }
+            }
+            Err(e) => return Err(Failure::context("Error extracting metadata")(e)),
+        };
+
+    stats_step(context, &episode.html);
+
+    // Report layouts other than the default so new variants are easy to spot and add
+    match episode.variant {
+        None => warn!(
+            "Unrecognized page layout (scripts: {}); using the default extraction profile",
+            script_fingerprint(&episode.html).join(", ")
+        ),
+        Some(variant) if variant != &VARIANTS[0] => info!("ℹ️ Page variant: {}", variant.id),
+        Some(_) => {}
+    }
+
+    if source != MetadataSource::Scrape {
+        merge_api_metadata(context, &mut episode, source).await;
+        if episode.metadata == Metadata::default() {
+            let error = Error::Parse(
+                "neither the page nor the iTunes API describes the episode".to_string(),
+            );
+            return Err(Failure::context("Error extracting metadata")(error));
+        }
+    }
+
+    Ok(episode)
+}
+
+/// Counts which extraction strategies work on a freshly fetched page in the local stats file
+///
+/// Stats are a diagnostic aid, so a file that cannot be read or written is only logged.
+pub fn stats_step(context: &Context, html: &str) {
+    let Some(path) = context.stats_file.as_deref() else {
+        return;
+    };
+    let _guard = context
+        .stats_lock
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let day = today();
+    let result = StrategyStats::load(path).and_then(|mut stats| {
+        stats.record(&day, &attempt_strategies(html));
+        stats.save(path, &day)
+    });
+    if let Err(e) = result {
+        debug!("Could not update strategy stats in {}: {}", path, e);
+    }
+}
+
+/// Merges the episode's iTunes Lookup API metadata with what was scraped, reporting problems as warnings
+pub async fn merge_api_metadata(context: &Context, episode: &mut Episode, source: MetadataSource) {
+    let (Some(show_id), Some(episode_id)) = (
+        show_id_from_url(&episode.url),
+        episode_id_from_url(&episode.url),
+    ) else {
+        warn!("iTunes lookup skipped: the URL has no show and episode ID");
+        return;
+    };
+
+    match lookup_episode(&show_id, &episode_id, &episode.storefront(), &context.http).await {
+        Ok(Some(found)) => {
+            let api = found.to_metadata();
+            if source == MetadataSource::Api {
+                let scraped = std::mem::replace(&mut episode.metadata, api);
+                episode.metadata.fill_missing(scraped);
+            } else {
+                episode.metadata.fill_missing(api);
+            }
+            info!("✅ Merged iTunes API metadata");
+        }
+        Ok(None) => warn!(
+            "Episode not found in the iTunes Lookup API (it lists a show's latest {} episodes)",
+            LOOKUP_EPISODE_LIMIT
+        ),
+        Err(e) => warn!("iTunes lookup failed: {}", e),
+    }
+}
+
+/// Saves the episode page into its output directory and returns that directory
+#[instrument(level = "debug", skip_all)]
+pub fn save_html_step(
+    context: &Context,
+    episode: &Episode,
+    report: &mut RunReport,
+) -> Result<String> {
+    let dir = context
+        .layout
+        .episode_dir(&NameFields::new(&episode.url, Some(&episode.metadata)));
+    let html_path = context
+        .layout
+        .write_artifact(&format!("{}/{}", dir, HTML_FILE), &episode.html)?;
+    report.outputs.push(html_path);
+    Ok(dir)
+}
+
+/// Captures localized metadata and saves `metadata.json` in `dir`
+#[instrument(level = "debug", skip_all)]
+pub async fn metadata_step(
+    context: &Context,
+    episode: &Episode,
+    locales: &[String],
+    dir: &str,
+    report: &mut RunReport,
+) -> Outcome<Metadata> {
+    let mut metadata = episode.metadata.clone();
+    description_step(context, &mut metadata);
+    description_images_step(context, &metadata).await;
+    override_step(context, &episode.url, &mut metadata);
+
+    // Capture the same episode as shown on other storefronts
+    if !locales.is_empty() && validate_url(&episode.url).is_ok() {
+        let mut localized =
+            capture_localized_metadata(context, &episode.url, locales, dir, report).await;
+        for entry in localized.values_mut() {
+            entry.description =
+                format_description(&entry.description, context.layout.description_format);
+        }
+        if !localized.is_empty() {
+            info!(
+                "✅ Localized metadata captured for {}",
+                localized.keys().cloned().collect::<Vec<_>>().join(", ")
+            );
+            metadata.localized = Some(localized);
+        }
+    }
+
+    // Save metadata to JSON
+    let metadata_path = format!("{}/{}", dir, METADATA_FILE);
+    save_metadata_json(&metadata, &metadata_path)
+        .map_err(Failure::context("Error saving metadata"))?;
+
+    info!("✅ Metadata extracted and saved to {}", metadata_path);
+    report.outputs.push(metadata_path);
+    report.metadata = Some(metadata.clone());
+    Ok(metadata)
+}
+
+/// Saves the final metadata in each `--metadata-format`, returning the paths written
+///
+/// Runs once per episode, after every other step has updated the metadata, so
+/// CSV rows are appended only once. Problems are reported as warnings.
+pub fn metadata_formats_step(
+    context: &Context,
+    metadata: &Metadata,
+    url: &str,
+    dir: &str,
+) -> Vec<String> {
+    let mut paths = Vec::new();
+    for format in &context.layout.metadata_formats {
+        if *format == MetadataFormat::Json {
+            continue;
+        }
+        match save_metadata_as(metadata, url, *format, &context.layout.dir, dir) {
+            Ok(path) => {
+                info!("✅ Metadata saved to {}", path);
+                paths.push(path);
+            }
+            Err(e) => warn!("Failed to save metadata as {}: {}", format.file_name(), e),
+        }
+    }
+    paths
+}
+
+/// Sanitizes the extracted description and converts it to `--description-format`, before overrides replace it
+pub fn description_step(context: &Context, metadata: &mut Metadata) {
+    let (description, sanitization) = sanitize_description(
+        &metadata.description,
+        &context.layout.sanitize,
+        &context.layout.path(IMAGES_DIR),
+    );
+    if !sanitization.is_empty() {
+        debug!(
+            trackers = sanitization.trackers.len(),
+            duplicates = sanitization.duplicates.len(),
+            images = sanitization.images.len(),
+            "Sanitized description"
+        );
+        metadata.sanitized = Some(sanitization);
+    }
+    metadata.description = format_description(&description, context.layout.description_format);
+}
+
+/// Downloads the description images `--sanitize-description archive` pointed at, reporting failures as warnings
+#[instrument(level = "debug", skip_all)]
+pub async fn description_images_step(context: &Context, metadata: &Metadata) {
+    let Some(sanitization) = &metadata.sanitized else {
+        return;
+    };
+    for (url, e) in archive_images(&sanitization.images, &context.http).await {
+        warn!("Failed to archive description image {}: {}", url, e);
+    }
+}
+
+/// Applies the config's field transforms, then the episode's override file, if it has one
+///
+/// Hand-written overrides come last so they win. A broken override file is
+/// reported and skipped.
+#[instrument(level = "debug", skip_all)]
+pub fn override_step(context: &Context, url: &str, metadata: &mut Metadata) {
+    let transformed = context.transforms.apply(url, metadata);
+    if !transformed.is_empty() {
+        info!("🧹 Transformed {}", transformed.join(", "));
+    }
+
+    let Some(id) = episode_id_from_url(url) else {
+        return;
+    };
+    let path = override_path(&context.layout.overrides_dir, &id);
+    let applied = MetadataOverrides::load(&path).and_then(|overrides| match overrides {
+        Some(overrides) => overrides.apply(metadata),
+        None => Ok(Vec::new()),
+    });
+    match applied {
+        Ok(fields) if fields.is_empty() => {}
+        Ok(fields) => info!("✏️ Overrode {} from {}", fields.join(", "), path.display()),
+        Err(e) => warn!("Ignoring {}: {}", path.display(), e),
+    }
+}
+
+/// Fetches each storefront's page, saving it to `<dir>/episode.<code>.html`
+pub async fn capture_localized_metadata(
+    context: &Context,
+    url: &str,
+    storefronts: &[String],
+    dir: &str,
+    report: &mut RunReport,
+) -> BTreeMap<String, LocalizedMetadata> {
+    let mut localized = BTreeMap::new();
+
+    for storefront in storefronts {
+        let result = fetch_localized_metadata(url, storefront, &context.http)
+            .await
+            .and_then(|(html, metadata)| {
+                let html_path = format!("{}/episode.{}.html", dir, storefront);
+                report
+                    .outputs
+                    .push(context.layout.write_artifact(&html_path, html)?);
+                Ok(metadata)
+            });
+
+        match result {
+            Ok(metadata) => {
+                localized.insert(storefront.clone(), metadata);
+            }
+            Err(e) => warn!("Failed to capture '{}' storefront: {}", storefront, e),
+        }
+    }
+
+    localized
+}
+
+/// Records the transcript's language in the saved metadata
+#[instrument(level = "debug", skip_all)]
+pub fn transcript_language_step(
+    metadata: &mut Metadata,
+    transcript: &Transcript,
+    dir: &str,
+    report: &mut RunReport,
+) -> Outcome {
+    match transcript.language() {
+        Ok(Some(language)) => {
+            metadata.transcript_language = Some(language);
+            save_metadata_json(metadata, &format!("{}/{}", dir, METADATA_FILE))
+                .map_err(Failure::context("Error saving metadata"))?;
+            report.metadata = Some(metadata.clone());
+        }
+        Ok(None) => {}
+        Err(e) => warn!("Failed to detect the transcript language: {}", e),
+    }
+    Ok(())
+}
+
+/// Finds and downloads the transcript into `dir`, reporting problems as warnings
+#[instrument(level = "debug", skip_all)]
+pub async fn transcript_step(
+    context: &Context,
+    episode: &Episode,
+    formats: &[TranscriptFormat],
+    dir: &str,
+    report: &mut RunReport,
+) -> Option<Transcript> {
+    // Search for the transcript, which long episodes may serve in parts
+    let parts = match episode.transcript_parts() {
+        Ok(parts) if !parts.is_empty() => parts,
+        Ok(_) => {
+            warn!("No transcript found for this episode.");
+            return None;
+        }
+        Err(e) => {
+            warn!("Error searching for transcript: {}", e);
+            return None;
+        }
+    };
+
+    // Transcript found, try to download it
+    let transcript_url = parts[0].url.clone();
+    report.transcript_url = Some(transcript_url.clone());
+    if parts.len() > 1 {
+        info!(
+            "🧩 Transcript is split into {} parts, downloading them together...",
+            parts.len()
+        );
+    }
+    let transcript_path = format!("{}/{}", dir, TRANSCRIPT_FILE);
+    let result = context
+        .budgets
+        .run(
+            Stage::Transcript,
+            download_transcript_parts(&parts, &context.http),
+        )
+        .await
+        .and_then(|ttml| {
+            let saved = context.layout.write_artifact(&transcript_path, &ttml)?;
+            let transcript = Transcript {
+                url: transcript_url,
+                ttml,
+            };
+            Ok((transcript, saved))
+        });
+
+    match result {
+        Ok((transcript, saved)) => {
+            info!("✅ Transcript downloaded and saved to {}", saved);
+            report.outputs.push(saved);
+            convert_transcript_step(&transcript, formats, &transcript_path, report);
+            Some(transcript)
+        }
+        Err(e) => {
+            warn!("Failed to download transcript: {}", e);
+            None
+        }
+    }
+}
+
+/// Saves the transcript's paragraph segments and each extra format next to `transcript_path`,
+/// reporting problems as warnings
+#[instrument(level = "debug", skip_all)]
+pub fn convert_transcript_step(
+    transcript: &Transcript,
+    formats: &[TranscriptFormat],
+    transcript_path: &str,
+    report: &mut RunReport,
+) {
+    match save_transcript_segments(transcript, transcript_path) {
+        Ok(path) => {
+            info!("✅ Transcript segments saved to {}", path);
+            report.outputs.push(path);
+        }
+        Err(e) => warn!("Failed to merge transcript segments: {}", e),
+    }
+    for format in formats {
+        match save_converted_transcript(transcript, *format, transcript_path) {
+            Ok(path) => {
+                info!("✅ Transcript converted and saved to {}", path);
+                report.outputs.push(path);
+            }
+            Err(e) => warn!(
+                "Failed to convert transcript to {}: {}",
+                format.extension(),
+                e
+            ),
+        }
+    }
+}
+
+/// Downloads the episode's audio into `dir` with a progress bar, reporting problems as warnings
+///
+/// Returns the audio file's path when it is on disk, downloaded now or earlier.
+#[instrument(level = "debug", skip_all)]
+pub async fn audio_step(
+    context: &Context,
+    episode: &Episode,
+    dir: &str,
+    report: &mut RunReport,
+) -> Option<String> {
+    let audio_url = match find_audio_url(&episode.html) {
+        Ok(Some(url)) => url,
+        Ok(None) => {
+            warn!("No audio found for this episode.");
+            return None;
+        }
+        Err(e) => {
+            warn!("Error searching for audio: {}", e);
+            return None;
+        }
+    };
+
+    let audio_path = format!("{}/{}", dir, audio_file_name(&episode.metadata, &audio_url));
+    if Path::new(&audio_path).exists() {
+        info!("✅ Audio already downloaded: {}", audio_path);
+        report.outputs.push(audio_path.clone());
+        return Some(audio_path);
+    }
+
+    let bar = if context.console.quiet {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new(0)
+    }
+    .with_style(
+        ProgressStyle::with_template(
+            "{bar:40.cyan/blue} {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
+        )
+        .unwrap_or_else(|_| ProgressStyle::default_bar()),
+    );
+    let mut progress = |written, total: Option<u64>| {
+        if let Some(total) = total {
+            bar.set_length(total);
+        }
+        bar.set_position(written);
+    };
+    let download = download_audio(&audio_url, &audio_path, &context.http, &mut progress);
+    let result = context.budgets.run(Stage::Audio, download).await;
+    bar.finish_and_clear();
+
+    match result {
+        Ok(download) => {
+            if download.resumed_from > 0 {
+                info!(
+                    "↩️ Resumed interrupted download at {} bytes",
+                    download.resumed_from
+                );
+            }
+            info!("✅ Audio downloaded and saved to {}", audio_path);
+            report.outputs.push(audio_path.clone());
+            Some(audio_path)
+        }
+        Err(e) => {
+            warn!("Failed to download audio: {}", e);
+            None
+        }
+    }
+}
+
+/// Embeds metadata, cover art and show-note chapters in the audio file, reporting problems as warnings
+#[instrument(level = "debug", skip_all)]
+pub async fn tag_step(context: &Context, episode: &Episode, metadata: &Metadata, audio_path: &str) {
+    let artwork = match find_artwork_template(&episode.html) {
+        Ok(Some(template)) => {
+            let url = fill_artwork_template(&template, TAG_ARTWORK_SIZE);
+            match download_artwork(&url, &context.http).await {
+                Ok(image) => Some(image),
+                Err(e) => {
+                    warn!("Tagging audio without artwork: {}", e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+    let tags = AudioTags {
+        metadata,
+        artwork,
+        chapters: chapters_from_description(&metadata.description, metadata.duration_seconds),
+    };
+
+    match tag_audio(audio_path, &tags) {
+        Ok(format) => info!(
+            "🏷️ Tagged {} ({}, {} chapter(s))",
+            audio_path,
+            format.name(),
+            tags.chapters.len()
+        ),
+        Err(e) => warn!("Failed to tag audio: {}", e),
+    }
+}
+
+/// Downloads the episode artwork at each size into `dir`, reporting problems as warnings
+#[instrument(level = "debug", skip_all)]
+pub async fn artwork_step(
+    context: &Context,
+    episode: &Episode,
+    sizes: &[ArtworkSize],
+    dir: &str,
+    report: &mut RunReport,
+) {
+    let template = match find_artwork_template(&episode.html) {
+        Ok(Some(template)) => template,
+        Ok(None) => {
+            warn!("No artwork found for this episode.");
+            return;
+        }
+        Err(e) => {
+            warn!("Error searching for artwork: {}", e);
+            return;
+        }
+    };
+
+    for size in sizes {
+        let artwork_path = format!("{}/{}", dir, size.file_name());
+        let result = download_artwork(&fill_artwork_template(&template, *size), &context.http)
+            .await
+            .and_then(|image| write_file(&artwork_path, image));
+
+        match result {
+            Ok(()) => {
+                info!("✅ Artwork saved to {}", artwork_path);
+                report.outputs.push(artwork_path);
+            }
+            Err(e) => warn!(
+                "Failed to download {}x{} artwork: {}",
+                size.width, size.height, e
+            ),
+        }
+    }
+}
+
+/// Reads the show notes aloud into `dir`, reporting failures as warnings
+#[instrument(level = "debug", skip_all)]
+pub fn speech_step(
+    metadata: &Metadata,
+    command: &SpeechCommand,
+    dir: &str,
+    report: &mut RunReport,
+) {
+    let speech_path = format!("{}/{}", dir, NOTES_AUDIO_FILE);
+    match command.render(&show_notes_text(metadata), Path::new(&speech_path)) {
+        Ok(()) => {
+            info!("✅ Show notes audio saved to {}", speech_path);
+            report.outputs.push(speech_path);
+        }
+        Err(e) => warn!("Failed to render show notes audio: {}", e),
+    }
+}
+
+/// Fetches and parses an episode page within the fetch and parse budgets
+pub async fn fetch_episode_within(context: &Context, url: &str) -> Result<Episode> {
+    ParsedUrl::parse(url)?;
+    let budgets = context.budgets;
+    let html = budgets
+        .run(Stage::Fetch, fetch_page(url, &context.http))
+        .await?;
+    let (url, http, extraction) = (url.to_string(), context.http.clone(), context.extraction);
+    budgets
+        .run_blocking(Stage::Parse, move || {
+            Episode::from_html_with(&url, html, &http, extraction)
+        })
+        .await
+}
+
+/// Saves an episode's page, metadata and transcript into `directory`, returning whether a transcript was saved
+///
+/// The transcript is fetched first so its language can be recorded in the
+/// metadata; the manifest of everything saved is written last.
+pub async fn save_episode(
+    context: &Context,
+    episode: &mut Episode,
+    directory: &str,
+    formats: &[TranscriptFormat],
+) -> Result<bool> {
+    context
+        .layout
+        .write_artifact(&format!("{}/{}", directory, HTML_FILE), &episode.html)?;
+    description_step(context, &mut episode.metadata);
+    description_images_step(context, &episode.metadata).await;
+    override_step(context, &episode.url, &mut episode.metadata);
+    let transcript = context
+        .budgets
+        .run(Stage::Transcript, episode.transcript())
+        .await?;
+    if let Some(transcript) = &transcript {
+        episode.metadata.transcript_language = transcript.language().ok().flatten();
+    }
+    save_metadata_json(
+        &episode.metadata,
+        &format!("{}/{}", directory, METADATA_FILE),
+    )?;
+    metadata_formats_step(context, &episode.metadata, &episode.url, directory);
+
+    let saved = transcript.is_some();
+    if let Some(transcript) = transcript {
+        let ttml_path = format!("{}/{}", directory, TRANSCRIPT_FILE);
+        context
+            .layout
+            .write_artifact(&ttml_path, &transcript.ttml)?;
+        save_transcript_segments(&transcript, &ttml_path)?;
+        for format in formats {
+            save_converted_transcript(&transcript, *format, &ttml_path)?;
+        }
+    }
+
+    // Recorded last, so an interrupted first save is fetched again rather than skipped
+    record_manifest(context, directory, &episode.url, saved);
+    Ok(saved)
+}
+
+/// Saves only the `missing` artifacts of an episode directory whose other files are intact
+///
+/// The saved page stands in for a fresh fetch unless it is missing itself or
+/// `fetched` is given, and a saved transcript is converted again rather than
+/// downloaded when only its other formats are gone. Returns the episode's
+/// metadata and whether it has a transcript. Any other missing file falls
+/// back to [`save_episode`].
+pub async fn repair_episode(
+    context: &Context,
+    url: &str,
+    directory: &str,
+    missing: &[String],
+    formats: &[TranscriptFormat],
+    fetched: Option<Episode>,
+) -> Result<(Metadata, bool)> {
+    let lost = |file: &str| missing.iter().any(|name| same_artifact(name, file));
+    let page_path = format!("{}/{}", directory, HTML_FILE);
+    let mut episode = match fetched {
+        Some(episode) => episode,
+        None if lost(HTML_FILE) => fetch_episode_within(context, url).await?,
+        None => {
+            let html = read_artifact(&page_path).map_err(Error::io("Failed to read saved page"))?;
+            Episode::from_html_with(url, html, &context.http, context.extraction)?
+        }
+    };
+    let known = [HTML_FILE, METADATA_FILE, TRANSCRIPT_FILE];
+    if !missing
+        .iter()
+        .all(|name| known.iter().any(|file| same_artifact(name, file)))
+    {
+        let transcript = save_episode(context, &mut episode, directory, formats).await?;
+        return Ok((episode.metadata, transcript));
+    }
+
+    if lost(HTML_FILE) {
+        context.layout.write_artifact(&page_path, &episode.html)?;
+    }
+
+    // The TTML itself is downloaded again; its other formats only need the saved copy
+    let ttml_path = format!("{}/{}", directory, TRANSCRIPT_FILE);
+    let ttml_lost = missing
+        .iter()
+        .any(|name| artifact_name(Path::new(name)) == Path::new(TRANSCRIPT_FILE));
+    let saved_transcript = !ttml_lost && stored_path(&ttml_path).is_some();
+    let transcript = if ttml_lost {
+        context
+            .budgets
+            .run(Stage::Transcript, episode.transcript())
+            .await?
+    } else if saved_transcript {
+        Some(Transcript::from_file(&ttml_path)?)
+    } else {
+        None
+    };
+    if let Some(transcript) = transcript.as_ref().filter(|_| lost(TRANSCRIPT_FILE)) {
+        if ttml_lost {
+            context
+                .layout
+                .write_artifact(&ttml_path, &transcript.ttml)?;
+        }
+        save_transcript_segments(transcript, &ttml_path)?;
+        for format in formats {
+            save_converted_transcript(transcript, *format, &ttml_path)?;
+        }
+    }
+
+    // Rebuilt from the page either way, for the archive record and hooks
+    description_step(context, &mut episode.metadata);
+    override_step(context, url, &mut episode.metadata);
+    if let Some(transcript) = &transcript {
+        episode.metadata.transcript_language = transcript.language().ok().flatten();
+    }
+    if lost(METADATA_FILE) {
+        description_images_step(context, &episode.metadata).await;
+        save_metadata_json(
+            &episode.metadata,
+            &format!("{}/{}", directory, METADATA_FILE),
+        )?;
+        metadata_formats_step(context, &episode.metadata, url, directory);
+    }
+
+    record_manifest(context, directory, url, transcript.is_some());
+    Ok((episode.metadata, transcript.is_some()))
+}
+
+/// Records what `directory` holds in its manifest, and adds it to the `--backup-friendly` store
+fn record_manifest(context: &Context, directory: &str, url: &str, transcript: bool) {
+    let manifest = match Manifest::record(directory, url, transcript) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            warn!("Failed to save manifest: {}", e);
+            return;
+        }
+    };
+    if let Some(store) = &context.layout.backup_dir {
+        if let Err(e) = manifest.back_up(directory, store) {
+            warn!("Failed to back up {}: {}", directory, e);
+        }
+    }
+}
+
+/// Whether a saved file is `file` or one of its formats: the same name up to the first dot
+fn same_artifact(name: &str, file: &str) -> bool {
+    name.split('.').next() == file.split('.').next()
+}
+
+/// Fetches one show episode into `directory`, returning its metadata and whether a transcript was saved
+pub async fn crawl_episode(
+    context: &Context,
+    summary: &EpisodeSummary,
+    directory: &str,
+    formats: &[TranscriptFormat],
+) -> Result<(Metadata, bool)> {
+    let mut episode = fetch_episode_within(context, &summary.url).await?;
+    let transcript = save_episode(context, &mut episode, directory, formats).await?;
+    Ok((episode.metadata, transcript))
+}
+
+/// Directory for one show episode: `episodes/<id>`, or the name template when one is set
+pub fn show_episode_dir(context: &Context, show_title: &str, summary: &EpisodeSummary) -> String {
+    if context.layout.template.is_none() {
+        return context
+            .layout
+            .path(&format!("{}/{}", EPISODES_DIR, summary.id));
+    }
+
+    context.layout.episode_dir(&NameFields {
+        show: show_title.to_string(),
+        title: summary.title.clone(),
+        date: summary
+            .release_date
+            .clone()
+            .unwrap_or_else(|| "undated".to_string()),
+        id: summary.id.clone(),
+        storefront: storefront_from_url(&summary.url),
+    })
+}
+
+/// The key an episode URL is archived under: its Apple episode ID, else the URL
+pub fn archive_id(url: &str) -> String {
+    episode_id_from_url(url).unwrap_or_else(|| url.to_string())
+}
+
+/// Records a processed episode in the archive, reporting failures as warnings
+#[instrument(level = "debug", skip_all)]
+pub fn archive_step(
+    archive: &Archive,
+    url: &str,
+    metadata: &Metadata,
+    dir: &str,
+    transcript: bool,
+) {
+    let transcript_path = transcript.then(|| format!("{}/{}", dir, TRANSCRIPT_FILE));
+    if let Err(e) = archive.record_episode(url, metadata, dir, transcript_path.as_deref()) {
+        warn!("Failed to record episode in archive: {}", e);
+        return;
+    }
+    if let Some(path) = transcript_path {
+        if let Err(e) = index_transcript_file(archive, url, &path) {
+            warn!("Failed to index transcript for search: {}", e);
+        }
+    }
+}
+
+/// Adds a saved TTML transcript to the archive's full-text index, streaming it from a memory map
+pub fn index_transcript_file(archive: &Archive, url: &str, path: &str) -> Result<()> {
+    let ttml = MappedTtml::open(path)?;
+    archive.index_transcript_stream(url, ttml.cues()?)
+}
+
+/// Commits the output directory with `--git-archive`, initializing the repository on first use
+#[instrument(level = "debug", skip_all)]
+pub fn git_commit_step(context: &Context, summary: &str, titles: &[String]) {
+    if !context.git_archive {
+        return;
+    }
+    let committed = GitArchive::init(&context.layout.dir)
+        .and_then(|repo| repo.commit(&commit_message(summary, titles)));
+    match committed {
+        Ok(Some(hash)) => info!(
+            "📚 Committed {} to {}: {}",
+            hash, context.layout.dir, summary
+        ),
+        Ok(None) => info!("📚 Nothing changed in {}; no commit", context.layout.dir),
+        Err(e) => warn!("Failed to commit {}: {}", context.layout.dir, e),
+    }
+}
@@ -0,0 +1,198 @@
+//! The `watch` runner, which processes a show's new episodes as they are published
+
+use std::time::Duration;
+
+use tracing::{error, info, warn};
+
+use super::steps::{archive_step, crawl_episode, git_commit_step, show_episode_dir};
+use super::{Context, Outcome};
+use crate::apple_url::ParsedUrl;
+use crate::archive::Archive;
+use crate::digest::{digest_subject, render_digest, send_digest, Digest, DIGEST_FILE};
+use crate::error::{Error, Result};
+use crate::output::{write_file, write_json};
+use crate::transcript::convert::TranscriptFormat;
+use crate::watch::{newest_episodes, notify_webhook, run_hook, state_path, NewEpisode, WatchState};
+
+/// How often `watch` checks and where it reports each new episode
+pub struct WatchOptions<'a> {
+    pub interval: Duration,
+    /// Check once and stop, failing if the check fails
+    pub once: bool,
+    /// URL each new episode is posted to as JSON
+    pub notify_url: Option<&'a str>,
+    /// Shell command run for each new episode
+    pub exec: Option<&'a str>,
+    /// Where each check's digest of new episodes goes
+    pub digest: Option<&'a Digest>,
+}
+
+/// Runs `watch`: checks a show every `interval` and processes episodes published since the last check
+///
+/// The first check of a show only records the episodes already listed, so
+/// hooks fire for new releases rather than the back catalog. Seen episodes are
+/// saved after every check, so a restarted watch picks up where it stopped.
+pub async fn run_watch(
+    context: &Context,
+    url: &str,
+    watch: &WatchOptions<'_>,
+    formats: &[TranscriptFormat],
+    archive: Option<&Archive>,
+) -> Outcome {
+    ParsedUrl::parse(url)?;
+    let state_path = state_path(&context.layout.dir, url);
+    let mut state = match WatchState::load_from(&context.layout.dir, url) {
+        Ok(state) => state,
+        Err(e) => {
+            warn!("Ignoring unreadable watch state {}: {}", state_path, e);
+            None
+        }
+    };
+    if let Some(state) = &state {
+        info!(
+            "↩️ Resuming watch: {} episode(s) already seen",
+            state.seen.len()
+        );
+    }
+
+    loop {
+        match watch_check(context, url, state.as_mut(), watch, formats, archive).await {
+            Ok(Some(baseline)) => {
+                info!(
+                    "👀 Watching {} ({} existing episode(s)); checking every {}s",
+                    url,
+                    baseline.seen.len(),
+                    watch.interval.as_secs()
+                );
+                state = Some(baseline);
+            }
+            Ok(None) => {}
+            Err(e) if watch.once => return Err(e.into()),
+            Err(e) => warn!("Check failed, retrying next interval: {}", e),
+        }
+        if let Some(state) = &state {
+            if let Err(e) = write_json(state, &state_path) {
+                warn!("Failed to save watch state: {}", e);
+            }
+        }
+
+        if watch.once {
+            return Ok(());
+        }
+        tokio::time::sleep(watch.interval).await;
+    }
+}
+
+/// Sends `episode` to its show's webhook from the config file, if one is set
+pub async fn notify_show_webhook(context: &Context, episode: &NewEpisode) {
+    let Some(webhook) = context.webhooks.for_show(&episode.show_url) else {
+        return;
+    };
+    if let Err(e) = webhook.send(episode, &context.http).await {
+        warn!("Webhook failed for {}: {}", episode.episode.title, e);
+    }
+}
+
+/// Writes or emails the digest of `episodes`; a run that found none sends nothing
+pub async fn digest_step(context: &Context, digest: Option<&Digest>, episodes: &[NewEpisode]) {
+    let Some(digest) = digest.filter(|_| !episodes.is_empty()) else {
+        return;
+    };
+    let result = match render_digest(episodes) {
+        Ok(html) => match digest {
+            Digest::Html => {
+                let path = context.layout.path(DIGEST_FILE);
+                write_file(&path, html).map(|()| format!("saved to {}", path))
+            }
+            Digest::Email(config) => {
+                let recipients = config.to.join(", ");
+                let (config, subject) = (config.clone(), digest_subject(episodes));
+                // lettre's SMTP client blocks, so it must not hold up the runtime's workers
+                tokio::task::spawn_blocking(move || send_digest(&config, &subject, html))
+                    .await
+                    .unwrap_or_else(|e| Err(Error::Email(e.to_string())))
+                    .map(|()| format!("emailed to {}", recipients))
+            }
+        },
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(done) => info!("📰 Digest of {} episode(s) {}", episodes.len(), done),
+        Err(e) => warn!("Digest failed: {}", e),
+    }
+}
+
+/// One `watch` check: processes unseen episodes, or returns the baseline state on the first check
+async fn watch_check(
+    context: &Context,
+    url: &str,
+    state: Option<&mut WatchState>,
+    watch: &WatchOptions<'_>,
+    formats: &[TranscriptFormat],
+    archive: Option<&Archive>,
+) -> Result<Option<WatchState>> {
+    let (show, episodes) = newest_episodes(url, &context.http).await?;
+    let Some(state) = state else {
+        let mut baseline = WatchState::new(url);
+        baseline.seen = episodes.into_iter().map(|summary| summary.id).collect();
+        return Ok(Some(baseline));
+    };
+
+    // Oldest first, so hooks see releases in publication order
+    let mut saved = Vec::new();
+    let mut new_episodes = Vec::new();
+    for summary in episodes.into_iter().rev() {
+        if state.seen.contains(&summary.id) {
+            continue;
+        }
+        let directory = show_episode_dir(context, &show.show_title, &summary);
+        let (metadata, transcript) =
+            match crawl_episode(context, &summary, &directory, formats).await {
+                Ok(result) => result,
+                Err(e) => {
+                    // Left unseen so the next check retries it
+                    error!("{}: {}", summary.title, e);
+                    continue;
+                }
+            };
+        if let Some(archive) = archive {
+            archive_step(archive, &summary.url, &metadata, &directory, transcript);
+        }
+        info!("🆕 {} saved to {}", summary.title, directory);
+
+        state.seen.insert(summary.id.clone());
+        saved.push(summary.title.clone());
+        let episode = NewEpisode {
+            show_url: url.to_string(),
+            show_title: show.show_title.clone(),
+            episode: summary,
+            directory,
+            transcript,
+            metadata,
+        };
+        if let Some(notify_url) = watch.notify_url {
+            if let Err(e) = notify_webhook(notify_url, &episode, &context.http).await {
+                warn!("Webhook failed for {}: {}", episode.episode.title, e);
+            }
+        }
+        notify_show_webhook(context, &episode).await;
+        if let Some(command) = watch.exec {
+            if let Err(e) = run_hook(command, &episode) {
+                warn!("Hook failed for {}: {}", episode.episode.title, e);
+            }
+        }
+        // One JSON line per episode, so a long-running watch can be piped
+        if context.console.json {
+            if let Ok(json) = serde_json::to_string(&episode) {
+                println!("{}", json);
+            }
+        }
+        new_episodes.push(episode);
+    }
+    digest_step(context, watch.digest, &new_episodes).await;
+    if !saved.is_empty() {
+        let summary = format!("Watch {}: {} new episode(s)", show.show_title, saved.len());
+        git_commit_step(context, &summary, &saved);
+    }
+    Ok(None)
+}
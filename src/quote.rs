@@ -0,0 +1,245 @@
+use serde::Serialize;
+
+use crate::transcript::{format_offset, TranscriptCue};
+
+/// Shortest and longest sentence, in characters, worth quoting on its own
+const QUOTE_LENGTH: std::ops::RangeInclusive<usize> = 60..=240;
+
+/// Fewest words a quotable sentence has
+const MIN_QUOTE_WORDS: usize = 8;
+
+/// Phrases that tend to mark a considered, standalone statement
+const SIGNAL_PHRASES: &[&str] = &[
+    "because",
+    "the key",
+    "the secret",
+    "the truth",
+    "the reason",
+    "the problem",
+    "the best",
+    "important",
+    "never",
+    "always",
+    "lesson",
+    "learned",
+    "realized",
+    "matters",
+];
+
+/// Spoken filler that makes a sentence read poorly in print
+const FILLER_PHRASES: &[&str] = &[
+    "um", "uh", "you know", "i mean", "kind of", "sort of", "i guess",
+];
+
+/// Words that show a sentence continues an earlier one
+const CONTINUATION_WORDS: &[&str] = &["and", "but", "so", "or", "because", "which"];
+
+/// A sentence from a transcript, with who said it and when
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Quote {
+    pub text: String,
+    pub speaker: Option<String>,
+    /// Seconds from the start of the episode
+    pub begin: f64,
+    pub score: i32,
+}
+
+/// A quote with the episode it came from, ready for printing or sharing
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct CitedQuote {
+    #[serde(flatten)]
+    pub quote: Quote,
+    pub episode_title: String,
+    pub show_title: String,
+    pub url: String,
+    pub publish_date: Option<String>,
+}
+
+impl CitedQuote {
+    /// The citation line, e.g. `Ann, Show: “Episode” at 12:05`
+    pub fn citation(&self) -> String {
+        let speaker = self
+            .quote
+            .speaker
+            .as_deref()
+            .map(|speaker| format!("{}, ", speaker))
+            .unwrap_or_default();
+        format!(
+            "{}{}: “{}” at {}",
+            speaker,
+            self.show_title,
+            self.episode_title,
+            format_offset(self.quote.begin)
+        )
+    }
+}
+
+/// Joins cues into whole sentences, starting each at the cue where it began
+///
+/// Works whether the transcript has one cue per sentence, several sentences
+/// per cue or sentences split across cues. A speaker change ends a sentence.
+pub fn transcript_sentences(cues: &[TranscriptCue]) -> Vec<Quote> {
+    let mut sentences = Vec::new();
+    let mut current: Option<Quote> = None;
+
+    for cue in cues {
+        if current
+            .as_ref()
+            .is_some_and(|sentence| sentence.speaker != cue.speaker)
+        {
+            sentences.extend(current.take());
+        }
+
+        for word in cue.text.split_whitespace() {
+            let sentence = current.get_or_insert_with(|| Quote {
+                text: String::new(),
+                speaker: cue.speaker.clone(),
+                begin: cue.begin,
+                score: 0,
+            });
+            if !sentence.text.is_empty() {
+                sentence.text.push(' ');
+            }
+            sentence.text.push_str(word);
+
+            if word.ends_with(['.', '!', '?']) {
+                sentences.extend(current.take());
+            }
+        }
+    }
+
+    sentences.extend(current);
+    sentences
+}
+
+/// Scores how well a sentence stands on its own as a quote; `None` when it cannot
+///
+/// Sentences must be a readable length and complete statements. Signal
+/// phrases raise the score; filler and continuation openers lower it.
+pub fn score_sentence(text: &str) -> Option<i32> {
+    let length = text.chars().count();
+    let words = text.split_whitespace().count();
+    if !QUOTE_LENGTH.contains(&length) || words < MIN_QUOTE_WORDS || text.ends_with('?') {
+        return None;
+    }
+
+    // Lowercase words separated by single spaces, so phrases match on word boundaries
+    let words: String = text
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '\'' {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect();
+    let words = format!(
+        " {} ",
+        words.split_whitespace().collect::<Vec<_>>().join(" ")
+    );
+    let count = |phrases: &[&str]| {
+        phrases
+            .iter()
+            .filter(|phrase| words.contains(&format!(" {} ", phrase)))
+            .count() as i32
+    };
+
+    let mut score = 1 + 2 * count(SIGNAL_PHRASES) - 2 * count(FILLER_PHRASES);
+    if text.starts_with(|c: char| c.is_uppercase()) && text.ends_with(['.', '!']) {
+        score += 1;
+    }
+    let first_word = words.split_whitespace().next().unwrap_or("");
+    if CONTINUATION_WORDS.contains(&first_word) {
+        score -= 3;
+    }
+    Some(score)
+}
+
+/// The transcript's quotable sentences, best first
+pub fn best_quotes(cues: &[TranscriptCue]) -> Vec<Quote> {
+    let mut quotes: Vec<Quote> = transcript_sentences(cues)
+        .into_iter()
+        .filter_map(|sentence| {
+            let score = score_sentence(&sentence.text).filter(|score| *score > 0)?;
+            Some(Quote { score, ..sentence })
+        })
+        .collect();
+    quotes.sort_by_key(|quote| std::cmp::Reverse(quote.score));
+    quotes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(begin: f64, speaker: &str, text: &str) -> TranscriptCue {
+        TranscriptCue {
+            begin,
+            end: begin + 5.0,
+            speaker: Some(speaker.to_string()),
+            text: text.to_string(),
+        }
+    }
+
+    /// Unit test - transcript_sentences joins split cues and splits combined ones
+    #[test]
+    fn test_transcript_sentences() {
+        // Given a sentence split over two cues followed by a cue with two sentences
+        let cues = vec![
+            cue(1.0, "Ann", "The thing I learned"),
+            cue(3.0, "Ann", "early on was patience. Then it clicked."),
+            cue(8.0, "Bob", "Right"),
+        ];
+
+        // When we split them into sentences
+        let sentences = transcript_sentences(&cues);
+
+        // Then each sentence starts where it began and a speaker change ends one
+        let texts: Vec<(&str, f64)> = sentences
+            .iter()
+            .map(|s| (s.text.as_str(), s.begin))
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                ("The thing I learned early on was patience.", 1.0),
+                ("Then it clicked.", 3.0),
+                ("Right", 8.0),
+            ]
+        );
+    }
+
+    /// Unit test - best_quotes prefers complete, signal-rich sentences over filler and fragments
+    #[test]
+    fn test_best_quotes_ranks_signal_over_filler() {
+        // Given a considered statement, a filler-heavy one, a question and a short remark
+        let cues = vec![
+            cue(0.0, "Ann", "The most important lesson I learned is that shipping small changes always beats waiting for perfect."),
+            cue(10.0, "Ann", "So um you know we kind of just did the thing and it sort of worked out in the end, I guess."),
+            cue(20.0, "Bob", "Why do you think that shipping small changes matters so much to the teams you work with?"),
+            cue(30.0, "Bob", "Sure."),
+        ];
+
+        // When we rank quotes
+        let quotes = best_quotes(&cues);
+
+        // Then only the considered statement qualifies
+        assert_eq!(quotes.len(), 1);
+        assert!(quotes[0].text.starts_with("The most important lesson"));
+        assert_eq!(quotes[0].speaker.as_deref(), Some("Ann"));
+
+        let cited = CitedQuote {
+            quote: Quote {
+                begin: 3725.0,
+                ..quotes[0].clone()
+            },
+            episode_title: "Small Steps".to_string(),
+            show_title: "Shipping".to_string(),
+            url: "https://podcasts.apple.com/us/podcast/id1?i=2".to_string(),
+            publish_date: None,
+        };
+        assert_eq!(cited.citation(), "Ann, Shipping: “Small Steps” at 1:02:05");
+    }
+}
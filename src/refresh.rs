@@ -0,0 +1,197 @@
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::metadata::Metadata;
+
+/// `metadata.json` fields that `refresh` can re-fetch from the episode page
+pub const REFRESH_FIELDS: &[&str] = &[
+    "episode_title",
+    "description",
+    "show_title",
+    "publish_date",
+    "publish_date_iso8601",
+    "duration_seconds",
+    "episode_number",
+    "season_number",
+    "guid",
+    "audio_url",
+    "feed_url",
+    "artwork_url",
+    "explicit",
+    "genres",
+];
+
+/// Which value wins when a refreshed field already has one, selected with `--strategy`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeStrategy {
+    /// The freshly fetched value replaces the saved one
+    #[default]
+    PreferNewer,
+    /// The saved value is kept; only empty fields are filled
+    PreferExisting,
+}
+
+/// Parses a `--strategy` value
+pub fn parse_strategy(raw: &str) -> std::result::Result<MergeStrategy, String> {
+    match raw.trim().to_lowercase().as_str() {
+        "prefer-newer" | "newer" => Ok(MergeStrategy::PreferNewer),
+        "prefer-existing" | "existing" => Ok(MergeStrategy::PreferExisting),
+        _ => Err(format!(
+            "Invalid strategy '{}': expected prefer-newer or prefer-existing",
+            raw
+        )),
+    }
+}
+
+/// Parses one `--fields` entry, accepting `kebab-case` as well as the JSON key
+pub fn parse_refresh_field(raw: &str) -> std::result::Result<String, String> {
+    let field = raw.trim().to_lowercase().replace('-', "_");
+    if REFRESH_FIELDS.contains(&field.as_str()) {
+        Ok(field)
+    } else {
+        Err(format!(
+            "Invalid field '{}': expected one of {}",
+            raw,
+            REFRESH_FIELDS.join(", ")
+        ))
+    }
+}
+
+/// An archived episode whose metadata a refresh changed
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct RefreshedEpisode {
+    pub id: String,
+    pub url: String,
+    pub title: String,
+    /// Fields whose value changed, in `--fields` order
+    pub changed: Vec<String>,
+}
+
+/// Merges the selected fields of freshly fetched metadata into saved `metadata.json` content
+///
+/// Every other key, including ones added by hand, is left untouched. A field
+/// the fresh page lacks never clears a saved value, whatever the strategy.
+/// Returns the fields that changed.
+pub fn merge_fields(
+    saved: &mut serde_json::Value,
+    fresh: &Metadata,
+    fields: &[String],
+    strategy: MergeStrategy,
+) -> Result<Vec<String>> {
+    let fresh = serde_json::to_value(fresh)?;
+    let Some(saved) = saved.as_object_mut() else {
+        return Ok(Vec::new());
+    };
+
+    let mut changed = Vec::new();
+    for field in fields {
+        let new = &fresh[field.as_str()];
+        let old = saved.get(field).unwrap_or(&serde_json::Value::Null);
+        let replace = !is_empty(new)
+            && new != old
+            && (strategy == MergeStrategy::PreferNewer || is_empty(old));
+        if replace {
+            saved.insert(field.clone(), new.clone());
+            changed.push(field.clone());
+        }
+    }
+    Ok(changed)
+}
+
+/// Whether a JSON value carries no information: null, or an empty string or list
+fn is_empty(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Null => true,
+        serde_json::Value::String(text) => text.trim().is_empty(),
+        serde_json::Value::Array(items) => items.is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Unit test - merge_fields only touches the selected fields, per strategy
+    #[test]
+    fn test_merge_fields_follows_strategy() {
+        // Given saved metadata with a hand-edited title and a custom key, and a fresh page
+        let saved = json!({
+            "episode_title": "My Own Title",
+            "description": "Old description",
+            "artwork_url": null,
+            "notes": "added by hand"
+        });
+        let fresh = Metadata {
+            episode_title: "Apple's Title".to_string(),
+            description: "Corrected description".to_string(),
+            artwork_url: Some("https://is1.example.com/a/3000x3000bb.jpg".to_string()),
+            ..Metadata::default()
+        };
+        let fields = vec!["description".to_string(), "artwork_url".to_string()];
+
+        // When we merge description and artwork with each strategy
+        let mut newer = saved.clone();
+        let newer_changed =
+            merge_fields(&mut newer, &fresh, &fields, MergeStrategy::PreferNewer).unwrap();
+        let mut existing = saved.clone();
+        let existing_changed = merge_fields(
+            &mut existing,
+            &fresh,
+            &fields,
+            MergeStrategy::PreferExisting,
+        )
+        .unwrap();
+
+        // Then prefer-newer replaces both, prefer-existing only fills the empty one,
+        // and unselected fields keep their edits
+        assert_eq!(newer_changed, vec!["description", "artwork_url"]);
+        assert_eq!(newer["description"], "Corrected description");
+        assert_eq!(existing_changed, vec!["artwork_url"]);
+        assert_eq!(existing["description"], "Old description");
+        for merged in [&newer, &existing] {
+            assert_eq!(merged["episode_title"], "My Own Title");
+            assert_eq!(merged["notes"], "added by hand");
+        }
+    }
+
+    /// Unit test - merge_fields never clears a saved value with a missing one
+    #[test]
+    fn test_merge_fields_keeps_values_the_page_lacks() {
+        // Given a saved duration and a fresh page without one
+        let mut saved = json!({"duration_seconds": 3723});
+        let fields = vec!["duration_seconds".to_string()];
+
+        // When we merge preferring newer values
+        let changed = merge_fields(
+            &mut saved,
+            &Metadata::default(),
+            &fields,
+            MergeStrategy::PreferNewer,
+        )
+        .unwrap();
+
+        // Then nothing changes
+        assert!(changed.is_empty());
+        assert_eq!(saved["duration_seconds"], 3723);
+    }
+
+    /// Unit test - parse_refresh_field and parse_strategy accept known values only
+    #[test]
+    fn test_parse_refresh_options() {
+        // Given field and strategy names in several spellings
+        // When we parse them
+        // Then known ones are normalized and unknown ones list the choices
+        assert_eq!(parse_refresh_field("artwork-url"), Ok("artwork_url".into()));
+        assert_eq!(parse_refresh_field("Description"), Ok("description".into()));
+        assert!(parse_refresh_field("chapters")
+            .unwrap_err()
+            .contains("expected one of episode_title"));
+        assert_eq!(
+            parse_strategy("prefer-existing"),
+            Ok(MergeStrategy::PreferExisting)
+        );
+        assert!(parse_strategy("merge").is_err());
+    }
+}
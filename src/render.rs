@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use headless_chrome::{Browser, LaunchOptions};
+
+use crate::error::{Error, Result};
+use crate::fetch::HttpOptions;
+
+/// How long navigation may take before rendering gives up
+pub const RENDER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Renders `url` in headless Chromium and returns the resulting DOM as HTML
+///
+/// Used for the rare page variants that only carry their data after
+/// JavaScript runs; the returned HTML goes through the normal extractors.
+pub fn render_page(url: &str, options: &HttpOptions) -> Result<String> {
+    let browser = Browser::new(LaunchOptions {
+        idle_browser_timeout: RENDER_TIMEOUT,
+        proxy_server: options.proxy.as_deref(),
+        ..LaunchOptions::default()
+    })
+    .map_err(render_error)?;
+    let tab = browser.new_tab().map_err(render_error)?;
+    tab.set_default_timeout(RENDER_TIMEOUT);
+
+    tab.set_user_agent(
+        options.user_agent(),
+        options.accept_language.as_deref(),
+        None,
+    )
+    .map_err(render_error)?;
+    if !options.headers.is_empty() {
+        let headers: HashMap<&str, &str> = options
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        tab.set_extra_http_headers(headers).map_err(render_error)?;
+    }
+
+    tab.navigate_to(url)
+        .and_then(|tab| tab.wait_until_navigated())
+        .and_then(|tab| tab.get_content())
+        .map_err(render_error)
+}
+
+/// Wraps a browser driver error
+fn render_error(e: impl std::fmt::Display) -> Error {
+    Error::Render(e.to_string())
+}
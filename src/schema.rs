@@ -0,0 +1,364 @@
+use serde_json::{json, Value};
+
+/// Major version of the output contract
+///
+/// Adding a field keeps the version; removing one or changing its type
+/// bumps it, so consumers can pin the `$id` they validate against.
+pub const SCHEMA_VERSION: u32 = 1;
+
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// JSON documents the tool writes that have a published schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaKind {
+    /// `metadata.json`
+    Metadata,
+    /// `transcript.json`, from `--transcript-format json`
+    Transcript,
+    /// `transcript.segments.json`
+    Segments,
+}
+
+impl SchemaKind {
+    /// Name used on the command line and in the schema's `$id`
+    pub fn name(self) -> &'static str {
+        match self {
+            SchemaKind::Metadata => "metadata",
+            SchemaKind::Transcript => "transcript",
+            SchemaKind::Segments => "segments",
+        }
+    }
+}
+
+/// Parses a `--print-schema` value
+pub fn parse_schema_kind(raw: &str) -> std::result::Result<SchemaKind, String> {
+    match raw.trim().to_lowercase().as_str() {
+        "metadata" => Ok(SchemaKind::Metadata),
+        "transcript" => Ok(SchemaKind::Transcript),
+        "segments" => Ok(SchemaKind::Segments),
+        _ => Err(format!(
+            "Invalid schema '{}': expected metadata, transcript or segments",
+            raw
+        )),
+    }
+}
+
+/// The JSON Schema (draft 2020-12) of one of the tool's JSON outputs
+///
+/// Objects allow additional properties, so documents written by newer
+/// versions still validate against an older schema of the same version.
+pub fn schema(kind: SchemaKind) -> Value {
+    let (title, body) = match kind {
+        SchemaKind::Metadata => ("Episode metadata", metadata_schema()),
+        SchemaKind::Transcript => (
+            "Transcript cues",
+            json!({"type": "array", "items": cue_schema()}),
+        ),
+        SchemaKind::Segments => (
+            "Transcript paragraph segments",
+            json!({"type": "array", "items": segment_schema()}),
+        ),
+    };
+    let mut schema = json!({
+        "$schema": DRAFT,
+        "$id": format!("urn:applecast-cli:{}:v{}", kind.name(), SCHEMA_VERSION),
+        "title": title,
+    });
+    if let (Some(schema), Value::Object(body)) = (schema.as_object_mut(), body) {
+        schema.extend(body);
+    }
+    schema
+}
+
+fn nullable(kind: &str) -> Value {
+    json!({"type": [kind, "null"]})
+}
+
+fn metadata_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": [
+            "episode_title", "description", "show_title", "publish_date",
+            "publish_date_iso8601", "duration_seconds", "episode_number", "season_number",
+            "guid", "audio_url", "feed_url", "artwork_url", "explicit", "genres",
+            "description_language", "transcript_language"
+        ],
+        "properties": {
+            "episode_title": nullable("string"),
+            "description": nullable("string"),
+            "show_title": nullable("string"),
+            "publish_date": nullable("string"),
+            "publish_date_iso8601": {"type": ["string", "null"], "format": "date"},
+            "duration_seconds": {"type": ["integer", "null"], "minimum": 0},
+            "episode_number": {"type": ["integer", "null"], "minimum": 0},
+            "season_number": {"type": ["integer", "null"], "minimum": 0},
+            "guid": nullable("string"),
+            "audio_url": nullable("string"),
+            "feed_url": nullable("string"),
+            "artwork_url": nullable("string"),
+            "explicit": nullable("boolean"),
+            "genres": {"type": ["array", "null"], "items": {"type": "string"}},
+            "description_language": nullable("string"),
+            "transcript_language": nullable("string"),
+            "localized": {
+                "type": "object",
+                "description": "Episode text per storefront code",
+                "additionalProperties": localized_schema()
+            },
+            "ad_segments": {"type": "array", "items": ad_segment_schema()},
+            "overridden": {
+                "type": "object",
+                "description": "Extracted values of the fields an override file replaced",
+                "additionalProperties": true
+            },
+            "sanitized": sanitized_schema()
+        }
+    })
+}
+
+fn localized_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": [
+            "episode_title", "description", "show_title", "publish_date", "publish_date_iso8601"
+        ],
+        "properties": {
+            "episode_title": nullable("string"),
+            "description": nullable("string"),
+            "show_title": nullable("string"),
+            "publish_date": nullable("string"),
+            "publish_date_iso8601": {"type": ["string", "null"], "format": "date"}
+        }
+    })
+}
+
+fn ad_segment_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["start_seconds", "end_seconds", "evidence", "promo_codes"],
+        "properties": {
+            "start_seconds": {"type": "number"},
+            "end_seconds": {"type": "number"},
+            "evidence": {"type": "array", "items": {"type": "string"}},
+            "sponsor": {"type": "string"},
+            "promo_codes": {"type": "array", "items": {"type": "string"}}
+        }
+    })
+}
+
+fn sanitized_schema() -> Value {
+    let urls = json!({"type": "array", "items": {"type": "string"}});
+    json!({
+        "type": "object",
+        "properties": {
+            "trackers": urls,
+            "duplicates": urls,
+            "stripped_urls": urls,
+            "images": {"type": "object", "additionalProperties": {"type": "string"}}
+        }
+    })
+}
+
+fn cue_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["begin", "end", "speaker", "text"],
+        "properties": {
+            "begin": {"type": "number", "minimum": 0},
+            "end": {"type": "number", "minimum": 0},
+            "speaker": nullable("string"),
+            "text": {"type": "string"}
+        }
+    })
+}
+
+fn segment_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["speaker", "start", "end", "text"],
+        "properties": {
+            "speaker": nullable("string"),
+            "start": {"type": "number", "minimum": 0},
+            "end": {"type": "number", "minimum": 0},
+            "text": {"type": "string"}
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ads::AdSegment;
+    use crate::metadata::sanitize::Sanitization;
+    use crate::metadata::{LocalizedMetadata, Metadata};
+    use crate::transcript::segments::TranscriptSegment;
+    use crate::transcript::TranscriptCue;
+    use std::collections::BTreeMap;
+
+    /// Checks `value` against the subset of JSON Schema the tool's schemas use
+    fn violations(schema: &Value, value: &Value, path: &str) -> Vec<String> {
+        let mut found = Vec::new();
+        let kind = match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(n) if n.is_u64() || n.is_i64() => "integer",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        };
+        let allowed: Vec<&str> = match &schema["type"] {
+            Value::String(kind) => vec![kind.as_str()],
+            Value::Array(kinds) => kinds.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        let integer_as_number = kind == "integer" && allowed.contains(&"number");
+        if !allowed.is_empty() && !allowed.contains(&kind) && !integer_as_number {
+            found.push(format!("{}: {} is not {:?}", path, kind, allowed));
+        }
+
+        match value {
+            Value::Object(fields) => {
+                for required in schema["required"].as_array().into_iter().flatten() {
+                    let required = required.as_str().unwrap_or_default();
+                    if !fields.contains_key(required) {
+                        found.push(format!("{}: missing {}", path, required));
+                    }
+                }
+                for (name, field) in fields {
+                    let field_path = format!("{}.{}", path, name);
+                    match schema["properties"].get(name) {
+                        Some(property) => found.extend(violations(property, field, &field_path)),
+                        None if schema["additionalProperties"] == true => {}
+                        None if schema["additionalProperties"].is_object() => found.extend(
+                            violations(&schema["additionalProperties"], field, &field_path),
+                        ),
+                        // Tests hold the schema to every field the tool writes today
+                        None => found.push(format!("{}: not in schema", field_path)),
+                    }
+                }
+            }
+            Value::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    found.extend(violations(
+                        &schema["items"],
+                        item,
+                        &format!("{}[{}]", path, index),
+                    ));
+                }
+            }
+            _ => {}
+        }
+        found
+    }
+
+    /// Unit test - the metadata schema describes every field metadata.json can hold
+    #[test]
+    fn test_metadata_schema_matches_serialized_metadata() {
+        // Given metadata with every optional field filled, and metadata with none
+        let full = Metadata {
+            episode_title: "Episode".to_string(),
+            description: "Notes".to_string(),
+            show_title: "Show".to_string(),
+            publish_date: "Oct 13, 2023".to_string(),
+            publish_date_iso8601: Some("2023-10-13".to_string()),
+            duration_seconds: Some(3600),
+            episode_number: Some(12),
+            season_number: Some(2),
+            guid: Some("guid".to_string()),
+            audio_url: Some("https://a/e.mp3".to_string()),
+            feed_url: Some("https://a/feed".to_string()),
+            artwork_url: Some("https://a/art.jpg".to_string()),
+            explicit: Some(false),
+            genres: Some(vec!["Comedy".to_string()]),
+            description_language: Some("en".to_string()),
+            transcript_language: Some("en".to_string()),
+            localized: Some(BTreeMap::from([(
+                "fr".to_string(),
+                LocalizedMetadata {
+                    episode_title: "Épisode".to_string(),
+                    description: String::new(),
+                    show_title: "Show".to_string(),
+                    publish_date: "13 oct. 2023".to_string(),
+                    publish_date_iso8601: None,
+                },
+            )])),
+            ad_segments: Some(vec![AdSegment {
+                start_seconds: 1.0,
+                end_seconds: 30.5,
+                evidence: vec!["promo code".to_string()],
+                sponsor: Some("Acme".to_string()),
+                promo_codes: vec!["SHOW".to_string()],
+            }]),
+            overridden: Some(BTreeMap::from([("show_title".to_string(), json!("Old"))])),
+            sanitized: Some(Sanitization {
+                trackers: vec!["https://t/p.gif".to_string()],
+                duplicates: vec!["https://a/b.png".to_string()],
+                stripped_urls: vec!["https://a/?utm_source=x".to_string()],
+                images: BTreeMap::from([("https://a/b.png".to_string(), "images/b.png".into())]),
+            }),
+        };
+        let schema = schema(SchemaKind::Metadata);
+
+        // When we check both against the schema
+        // Then neither has a field the schema omits or mistypes
+        for metadata in [full, Metadata::default()] {
+            let value = serde_json::to_value(&metadata).unwrap();
+            assert_eq!(violations(&schema, &value, "$"), Vec::<String>::new());
+        }
+        assert_eq!(schema["$id"], "urn:applecast-cli:metadata:v1");
+    }
+
+    /// Unit test - the transcript and segment schemas describe the transcript JSON files
+    #[test]
+    fn test_transcript_schemas_match_serialized_cues() {
+        // Given cues and segments with and without speakers
+        let cues = vec![
+            TranscriptCue {
+                begin: 0.0,
+                end: 1.5,
+                speaker: Some("Host".to_string()),
+                text: "Hello".to_string(),
+            },
+            TranscriptCue {
+                begin: 2.0,
+                end: 3.0,
+                speaker: None,
+                text: "Hi".to_string(),
+            },
+        ];
+        let segments = vec![TranscriptSegment {
+            speaker: None,
+            start: 0.0,
+            end: 3.0,
+            text: "Hello. Hi".to_string(),
+        }];
+
+        // When we check them against their schemas
+        // Then they conform, and a cue missing its text does not
+        let cues = serde_json::to_value(cues).unwrap();
+        let transcript = schema(SchemaKind::Transcript);
+        assert!(violations(&transcript, &cues, "$").is_empty());
+        let segments = serde_json::to_value(segments).unwrap();
+        assert!(violations(&schema(SchemaKind::Segments), &segments, "$").is_empty());
+        assert_eq!(
+            violations(
+                &transcript,
+                &json!([{"begin": 0, "end": 1, "speaker": null}]),
+                "$"
+            ),
+            vec!["$[0]: missing text"]
+        );
+    }
+
+    /// Unit test - parse_schema_kind accepts the three schema names
+    #[test]
+    fn test_parse_schema_kind() {
+        // Given schema names, in any case, and an unknown one
+        // When we parse them
+        // Then the known ones map to their kind
+        assert_eq!(parse_schema_kind("Metadata"), Ok(SchemaKind::Metadata));
+        assert_eq!(parse_schema_kind("segments"), Ok(SchemaKind::Segments));
+        assert!(parse_schema_kind("show").unwrap_err().contains("metadata"));
+    }
+}
@@ -0,0 +1,124 @@
+use regex::Regex;
+
+use crate::error::Result;
+use crate::variant::{variant_for, PageVariant};
+
+/// Parses the serialized data embedded in an Apple Podcasts page
+///
+/// The script holding the data depends on the page layout (see [`crate::variant`]).
+pub fn serialized_server_data(html: &str) -> Result<Option<serde_json::Value>> {
+    server_data_for(html, variant_for(html))
+}
+
+/// Parses the serialized data where `variant`'s layout keeps it
+pub fn server_data_for(html: &str, variant: &PageVariant) -> Result<Option<serde_json::Value>> {
+    // Extract the serialized data JSON
+    let attribute = format!(r#"id="{}""#, variant.server_data_id);
+    let Some(json_text) = script_content(html, &attribute)? else {
+        return Ok(None); // No serialized data found
+    };
+
+    // Invalid JSON is treated the same as missing data
+    let Ok(value) = serde_json::from_str(json_text) else {
+        return Ok(None);
+    };
+
+    Ok(Some(if variant.server_data_encoded {
+        decode_values(value)
+    } else {
+        value
+    }))
+}
+
+/// The raw content of the first `<script>` whose opening tag carries `attribute`, e.g. `id="x"`
+pub fn script_content<'a>(html: &'a str, attribute: &str) -> Result<Option<&'a str>> {
+    let re = Regex::new(&format!(
+        r#"(?s)<script[^>]*\b{}[^>]*>(.*?)</script>"#,
+        regex::escape(attribute)
+    ))?;
+    Ok(re
+        .captures(html)
+        .and_then(|captures| captures.get(1))
+        .map(|m| m.as_str()))
+}
+
+/// Parses object values that are JSON documents stored as strings
+fn decode_values(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .map(|(key, value)| match value {
+                serde_json::Value::String(text) => (
+                    key,
+                    serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text)),
+                ),
+                other => (key, other),
+            })
+            .collect(),
+        other => other,
+    }
+}
+
+/// Recursively searches a JSON structure for the first string stored under `key`
+pub fn find_string_field(value: &serde_json::Value, key: &str) -> Option<String> {
+    match value {
+        serde_json::Value::Object(map) => {
+            if let Some(found) = map.get(key).and_then(|v| v.as_str()) {
+                return Some(found.to_string());
+            }
+            map.values().find_map(|val| find_string_field(val, key))
+        }
+        serde_json::Value::Array(arr) => arr.iter().find_map(|val| find_string_field(val, key)),
+        _ => None,
+    }
+}
+
+/// Recursively searches a JSON structure for the first non-null value stored under `key`
+pub fn find_field<'a>(value: &'a serde_json::Value, key: &str) -> Option<&'a serde_json::Value> {
+    match value {
+        serde_json::Value::Object(map) => {
+            if let Some(found) = map.get(key).filter(|v| !v.is_null()) {
+                return Some(found);
+            }
+            map.values().find_map(|val| find_field(val, key))
+        }
+        serde_json::Value::Array(arr) => arr.iter().find_map(|val| find_field(val, key)),
+        _ => None,
+    }
+}
+
+/// Searches for the episode's RSS GUID in the episode HTML
+pub fn find_episode_guid(html: &str) -> Result<Option<String>> {
+    Ok(serialized_server_data(html)?.and_then(|json_value| find_string_field(&json_value, "guid")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - find_episode_guid reads the GUID from serialized data
+    #[test]
+    fn test_find_episode_guid_reads_serialized_data() {
+        // Given HTML whose serialized data carries the RSS GUID
+        let html = r#"<html><body><script type="application/json" id="serialized-server-data">[{"data":{"episode":{"guid":"abc-123","title":"Test"}}}]</script></body></html>"#;
+
+        // When we search for the GUID
+        let guid = find_episode_guid(html).unwrap();
+
+        // Then it is found
+        assert_eq!(guid.as_deref(), Some("abc-123"));
+    }
+
+    /// Unit test - serialized_server_data decodes the shoebox page layout
+    #[test]
+    fn test_serialized_server_data_reads_shoebox_layout() {
+        // Given an older layout whose cache entries are JSON-encoded strings
+        let html = r#"<html><body><script type="fastboot/shoebox" id="shoebox-media-api-cache-amp-podcasts">{"episode.1":"{\"d\":[{\"attributes\":{\"guid\":\"shoebox-guid\"}}]}"}</script></body></html>"#;
+
+        // When we search for the GUID
+        let guid = find_episode_guid(html).unwrap();
+
+        // Then the encoded entry is decoded and searched
+        assert_eq!(guid.as_deref(), Some("shoebox-guid"));
+    }
+}
@@ -0,0 +1,806 @@
+use std::fs;
+
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use url::Url;
+
+use crate::apple_url::{episode_id_from_url, show_id_from_url, storefront_from_url};
+use crate::batch::run_concurrently;
+use crate::error::{Error, Result};
+use crate::fetch::{get_text, HttpOptions};
+use crate::locale::parse_localized_date;
+use crate::metadata::clean_text;
+use crate::server_data::serialized_server_data;
+use crate::stats::{days_before, today};
+use crate::variant::variant_for;
+
+/// Upper bound on show pages followed while enumerating episodes in one run
+pub const MAX_SHOW_PAGES: usize = 50;
+
+/// Default number of show pages fetched at once when their offsets are known
+pub const DEFAULT_PAGE_CONCURRENCY: usize = 4;
+
+/// Represents podcast show details extracted from an Apple Podcasts show page
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ShowMetadata {
+    pub show_title: String,
+    pub description: String,
+    pub author: String,
+}
+
+/// Extracts show details from a show page's HTML
+pub fn extract_show_metadata(html: &str) -> Result<ShowMetadata> {
+    let document = Html::parse_document(html);
+
+    // Try to extract from JSON-LD schema first (most reliable)
+    let variant = variant_for(html);
+    match extract_show_from_json_ld(&document, variant.show_schema) {
+        Ok(show) => return Ok(show),
+        Err(e) => {
+            debug!(variant = variant.id, selector = variant.show_schema, error = %e, "Falling back to Open Graph tags")
+        }
+    }
+
+    // Fallback to Open Graph tags
+    extract_show_from_meta_tags(&document)
+}
+
+/// Extracts show details from the JSON-LD block matched by `selector`
+fn extract_show_from_json_ld(document: &Html, selector: &str) -> Result<ShowMetadata> {
+    let script_selector =
+        Selector::parse(selector).map_err(|e| Error::Parse(format!("Invalid selector: {}", e)))?;
+
+    let script = document
+        .select(&script_selector)
+        .next()
+        .ok_or_else(|| Error::Parse("JSON-LD show schema not found".to_string()))?;
+
+    let json_value: serde_json::Value = serde_json::from_str(&script.text().collect::<String>())?;
+
+    let field = |value: &serde_json::Value| value.as_str().unwrap_or("").trim().to_string();
+
+    Ok(ShowMetadata {
+        show_title: field(&json_value["name"]),
+        description: field(&json_value["description"]),
+        author: field(&json_value["author"]["name"]),
+    })
+}
+
+/// Extracts show details from Open Graph meta tags as fallback
+fn extract_show_from_meta_tags(document: &Html) -> Result<ShowMetadata> {
+    let meta_selector = Selector::parse("meta[property]")
+        .map_err(|e| Error::Parse(format!("Invalid selector: {}", e)))?;
+
+    let mut show = ShowMetadata {
+        show_title: String::new(),
+        description: String::new(),
+        author: String::new(),
+    };
+
+    for element in document.select(&meta_selector) {
+        let Some(content) = element.value().attr("content") else {
+            continue;
+        };
+
+        match element.value().attr("property") {
+            Some("og:title") if show.show_title.is_empty() => show.show_title = clean_text(content),
+            Some("og:description") if show.description.is_empty() => {
+                show.description = clean_text(content)
+            }
+            _ => {}
+        }
+    }
+
+    if show.show_title.is_empty() {
+        return Err(Error::Parse("Show title not found".to_string()));
+    }
+
+    Ok(show)
+}
+
+/// An episode listed on a show page
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EpisodeSummary {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    pub release_date: Option<String>,
+}
+
+/// Which of a show's episodes to enumerate
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EpisodeFilter {
+    /// Stop after this many episodes
+    pub limit: Option<usize>,
+    /// Only episodes released on or after this `YYYY-MM-DD` date
+    pub since: Option<String>,
+}
+
+impl EpisodeFilter {
+    /// Returns true if the episode was released on or after `since`
+    ///
+    /// Episodes without a readable release date are excluded once `since` is set.
+    fn is_recent_enough(&self, episode: &EpisodeSummary) -> bool {
+        match &self.since {
+            Some(since) => episode
+                .release_date
+                .as_deref()
+                .is_some_and(|date| date >= since.as_str()),
+            None => true,
+        }
+    }
+
+    /// The episodes that pass the filter, in order
+    pub fn apply(&self, episodes: &[EpisodeSummary]) -> Vec<EpisodeSummary> {
+        episodes
+            .iter()
+            .filter(|episode| self.is_recent_enough(episode))
+            .take(self.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Validates a `--since` argument as a `YYYY-MM-DD` date
+pub fn parse_since(raw: &str) -> std::result::Result<String, String> {
+    let trimmed = raw.trim();
+    match parse_localized_date(trimmed, "us") {
+        Some(date) if date == trimmed => Ok(date),
+        _ => Err(format!("Invalid date '{}': expected YYYY-MM-DD", raw)),
+    }
+}
+
+/// Validates a `--since` argument that may also be an age, such as `30d` for 30 days before today
+pub fn parse_since_or_age(raw: &str) -> std::result::Result<String, String> {
+    let trimmed = raw.trim();
+    match trimmed.strip_suffix('d').map(str::parse::<u64>) {
+        Some(Ok(days)) => Ok(days_before(&today(), days)),
+        _ => parse_since(raw).map_err(|_| {
+            format!(
+                "Invalid date '{}': expected YYYY-MM-DD or days, e.g. 30d",
+                raw
+            )
+        }),
+    }
+}
+
+/// Lists the episodes in a show page's serialized data, in page order
+///
+/// Any object carrying an episode URL (`?i=`) counts as an episode; its title
+/// and release date come from the nearest enclosing object that has them.
+pub fn find_episodes(html: &str, storefront: &str) -> Result<Vec<EpisodeSummary>> {
+    let Some(data) = serialized_server_data(html)? else {
+        return Ok(Vec::new());
+    };
+
+    let mut episodes = Vec::new();
+    collect_episodes(&data, None, None, storefront, &mut episodes);
+    Ok(episodes)
+}
+
+/// Walks the serialized data, carrying the nearest title and release date down the tree
+fn collect_episodes(
+    value: &serde_json::Value,
+    title: Option<&str>,
+    release_date: Option<&str>,
+    storefront: &str,
+    episodes: &mut Vec<EpisodeSummary>,
+) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let title = map
+                .get("title")
+                .or_else(|| map.get("name"))
+                .and_then(|v| v.as_str())
+                .or(title);
+            let release_date = map
+                .get("releaseDate")
+                .and_then(|v| v.as_str())
+                .or(release_date);
+
+            let episode_url = ["storeUrl", "url", "href"]
+                .iter()
+                .filter_map(|key| map.get(*key).and_then(|v| v.as_str()))
+                .find(|url| episode_id_from_url(url).is_some());
+
+            if let Some(url) = episode_url {
+                let id = episode_id_from_url(url).unwrap_or_default();
+                if !episodes.iter().any(|episode| episode.id == id) {
+                    episodes.push(EpisodeSummary {
+                        id,
+                        title: title.unwrap_or("").trim().to_string(),
+                        url: url.to_string(),
+                        release_date: release_date
+                            .and_then(|date| parse_localized_date(date, storefront)),
+                    });
+                }
+            }
+
+            for child in map.values() {
+                collect_episodes(child, title, release_date, storefront, episodes);
+            }
+        }
+        serde_json::Value::Array(arr) => {
+            for child in arr {
+                collect_episodes(child, title, release_date, storefront, episodes);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Finds the next page of episodes advertised in the page data, resolved against `base_url`
+pub fn find_next_page(html: &str, base_url: &str) -> Result<Option<String>> {
+    let Some(data) = serialized_server_data(html)? else {
+        return Ok(None);
+    };
+
+    fn find(value: &serde_json::Value) -> Option<&str> {
+        match value {
+            serde_json::Value::Object(map) => {
+                if let Some(next) = map.get("nextPage") {
+                    if let Some(href) = next.as_str().or_else(|| next["href"].as_str()) {
+                        return Some(href);
+                    }
+                }
+                map.values().find_map(find)
+            }
+            serde_json::Value::Array(arr) => arr.iter().find_map(find),
+            _ => None,
+        }
+    }
+
+    Ok(find(&data).and_then(|href| {
+        Url::parse(base_url)
+            .and_then(|base| base.join(href))
+            .ok()
+            .map(|url| url.to_string())
+    }))
+}
+
+/// Cursor file every show shared before cursors were kept per show; still read so earlier progress is kept
+const SHARED_CURSOR_FILE: &str = "show-cursor.json";
+
+/// Where the cursor for `show_url` is saved under `dir`: `show-cursor-<show id>.json`
+///
+/// One file per show, so shows crawled into the same output directory keep
+/// their own progress.
+pub fn cursor_path(dir: &str, show_url: &str) -> String {
+    match show_id_from_url(show_url) {
+        Some(id) => format!("{}/show-cursor-{}.json", dir, id),
+        None => format!("{}/{}", dir, SHARED_CURSOR_FILE),
+    }
+}
+
+/// Progress through a show's catalog, saved between runs so enumeration can resume
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ShowCursor {
+    pub show_url: String,
+    /// Next page after the deepest one fetched; `None` once the whole catalog has been seen
+    pub next_page: Option<String>,
+    /// Every episode seen so far, in show order (newest first)
+    pub episodes: Vec<EpisodeSummary>,
+}
+
+impl ShowCursor {
+    /// A cursor for a show that has not been enumerated yet
+    pub fn new(show_url: &str) -> ShowCursor {
+        ShowCursor {
+            show_url: show_url.to_string(),
+            ..ShowCursor::default()
+        }
+    }
+
+    /// Reads a saved cursor, or returns `None` if there is none for this show
+    pub fn load(path: &str, show_url: &str) -> Result<Option<ShowCursor>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::io("Failed to read show cursor")(e)),
+        };
+        let cursor: ShowCursor = serde_json::from_str(&text)?;
+        Ok(Some(cursor).filter(|cursor| cursor.show_url == show_url))
+    }
+
+    /// Reads the cursor for `show_url` saved under `dir`, falling back to the shared file of earlier versions
+    pub fn load_from(dir: &str, show_url: &str) -> Result<Option<ShowCursor>> {
+        match ShowCursor::load(&cursor_path(dir, show_url), show_url)? {
+            Some(cursor) => Ok(Some(cursor)),
+            None => ShowCursor::load(&format!("{}/{}", dir, SHARED_CURSOR_FILE), show_url),
+        }
+    }
+
+    /// Returns true once every page of the show has been fetched
+    pub fn is_complete(&self) -> bool {
+        !self.episodes.is_empty() && self.next_page.is_none()
+    }
+}
+
+/// Enumerates a show's episodes, following pagination until the filter is satisfied
+///
+/// Returns the first page's HTML (for show details) alongside the episodes.
+pub async fn list_episodes(
+    url: &str,
+    options: &HttpOptions,
+    filter: &EpisodeFilter,
+) -> Result<(String, Vec<EpisodeSummary>)> {
+    let mut cursor = ShowCursor::new(url);
+    enumerate_episodes(url, options, filter, 1, &mut cursor, &mut |_| {}).await
+}
+
+/// Enumerates a show's episodes starting from a saved cursor, updating it as pages arrive
+///
+/// The first page is always fetched so newly published episodes are picked up;
+/// once a page reaches episodes the cursor already knows, enumeration jumps to
+/// the cursor's next page. Pages whose `offset` can be predicted are fetched up
+/// to `concurrency` at a time. `checkpoint` is called with the cursor after each
+/// batch of pages so callers can persist progress; at most [`MAX_SHOW_PAGES`]
+/// pages are fetched per call, and the cursor records where to continue.
+pub async fn enumerate_episodes(
+    url: &str,
+    options: &HttpOptions,
+    filter: &EpisodeFilter,
+    concurrency: usize,
+    cursor: &mut ShowCursor,
+    checkpoint: &mut dyn FnMut(&ShowCursor),
+) -> Result<(String, Vec<EpisodeSummary>)> {
+    let storefront = storefront_from_url(url);
+
+    // With a previous run on record, walk from the top only until known episodes appear
+    let mut resume_at = (!cursor.episodes.is_empty()).then(|| cursor.next_page.clone());
+    let mut insert_at = 0;
+    let mut first_page = None;
+    let mut next = Some(url.to_string());
+    let mut page_url = url.to_string();
+    let mut fetched = 0;
+
+    'pages: while let Some(start) = next.take() {
+        // The cursor already records where the next run should continue
+        if fetched >= MAX_SHOW_PAGES {
+            break;
+        }
+
+        let window = predicted_pages(&page_url, &start, concurrency.min(MAX_SHOW_PAGES - fetched));
+        let pages = run_concurrently(window.clone(), concurrency, |page| async move {
+            get_text(&page, options).await
+        })
+        .await;
+
+        let mut expected = Some(start);
+        for (candidate, html) in window.into_iter().zip(pages) {
+            // A prefetched page only counts if it is the one the previous page linked to
+            if expected.as_deref() != Some(candidate.as_str()) {
+                break;
+            }
+            let html = match html {
+                Ok(html) => html,
+                Err(e) => {
+                    if fetched > 0 {
+                        checkpoint(cursor);
+                    }
+                    return Err(e);
+                }
+            };
+            fetched += 1;
+            page_url = candidate;
+
+            let page = find_episodes(&html, &storefront)?;
+            let page_len = page.len();
+            let any_recent = page.iter().any(|episode| filter.is_recent_enough(episode));
+            let mut added = 0;
+            for episode in page {
+                if cursor
+                    .episodes
+                    .iter()
+                    .any(|existing| existing.id == episode.id)
+                {
+                    continue;
+                }
+                if resume_at.is_some() {
+                    cursor.episodes.insert(insert_at, episode);
+                    insert_at += 1;
+                } else {
+                    cursor.episodes.push(episode);
+                }
+                added += 1;
+            }
+
+            let page_next = find_next_page(&html, &page_url)?;
+            first_page.get_or_insert(html);
+
+            // Shows list newest first, so paging stops once a page is entirely too old
+            let satisfied = filter
+                .limit
+                .is_some_and(|limit| filter.apply(&cursor.episodes).len() >= limit)
+                || (filter.since.is_some() && !any_recent);
+
+            expected = match resume_at.take() {
+                Some(resume) if added < page_len => resume,
+                Some(resume) => {
+                    resume_at = Some(resume);
+                    page_next
+                }
+                None => {
+                    // A page with nothing new means the pagination has looped back
+                    cursor.next_page = page_next.filter(|_| added > 0);
+                    cursor.next_page.clone()
+                }
+            };
+            if satisfied {
+                checkpoint(cursor);
+                break 'pages;
+            }
+        }
+
+        checkpoint(cursor);
+        next = expected;
+    }
+
+    let first_page = first_page.unwrap_or_default();
+    Ok((first_page, filter.apply(&cursor.episodes)))
+}
+
+/// The page at `next` followed by the pages after it, when their `offset` can be predicted
+///
+/// Apple pages episode lists with an `offset` query parameter; the step between
+/// `current` and `next` tells where the following pages start.
+fn predicted_pages(current: &str, next: &str, count: usize) -> Vec<String> {
+    let offset = |url: &Url| {
+        url.query_pairs()
+            .find(|(key, _)| key == "offset")
+            .and_then(|(_, value)| value.parse::<usize>().ok())
+    };
+
+    let mut pages = vec![next.to_string()];
+    let Ok(next_url) = Url::parse(next) else {
+        return pages;
+    };
+    let Some(next_offset) = offset(&next_url) else {
+        return pages;
+    };
+    let current_offset = Url::parse(current)
+        .ok()
+        .and_then(|url| offset(&url))
+        .unwrap_or(0);
+    let step = next_offset.saturating_sub(current_offset);
+    if step == 0 {
+        return pages;
+    }
+
+    for n in 1..count {
+        let mut url = next_url.clone();
+        let query: Vec<(String, String)> = next_url
+            .query_pairs()
+            .map(|(key, value)| match key.as_ref() {
+                "offset" => (key.into_owned(), (next_offset + n * step).to_string()),
+                _ => (key.into_owned(), value.into_owned()),
+            })
+            .collect();
+        url.query_pairs_mut().clear().extend_pairs(query);
+        pages.push(url.to_string());
+    }
+    pages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - extract_show_metadata reads the show JSON-LD schema
+    #[test]
+    fn test_extract_show_metadata_from_json_ld() {
+        // Given a show page with a JSON-LD schema
+        let html = r#"<html><head>
+            <script id="schema:show" type="application/ld+json">
+            {"name": "Back to the Board", "description": "Sports talk.", "author": {"name": "Board Media"}}
+            </script>
+            </head></html>"#;
+
+        // When we extract the show details
+        let show = extract_show_metadata(html).unwrap();
+
+        // Then every field is read
+        assert_eq!(show.show_title, "Back to the Board");
+        assert_eq!(show.description, "Sports talk.");
+        assert_eq!(show.author, "Board Media");
+    }
+
+    /// Unit test - extract_show_metadata falls back to Open Graph tags
+    #[test]
+    fn test_extract_show_metadata_falls_back_to_meta_tags() {
+        // Given a show page with only Open Graph tags
+        let html = r#"<html><head>
+            <meta property="og:title" content="Back to the Board">
+            <meta property="og:description" content="  Sports   talk. ">
+            </head></html>"#;
+
+        // When we extract the show details
+        let show = extract_show_metadata(html).unwrap();
+
+        // Then title and description come from the tags
+        assert_eq!(show.show_title, "Back to the Board");
+        assert_eq!(show.description, "Sports talk.");
+        assert_eq!(show.author, "");
+    }
+
+    /// Unit test - find_episodes reads episodes from nested shelves
+    #[test]
+    fn test_find_episodes_reads_nested_items() {
+        // Given show page data where each item nests its episode URL
+        let html = r#"<script type="application/json" id="serialized-server-data">[{"data":{"shelves":[{"title":"Episodes","items":[
+            {"title":"Episode Two","releaseDate":"2023-10-20T10:00:00Z","contextAction":{"episodeOffer":{"storeUrl":"https://podcasts.apple.com/us/podcast/two/id1?i=1002"}}},
+            {"title":"Episode One","releaseDate":"2023-10-13T10:00:00Z","contextAction":{"episodeOffer":{"storeUrl":"https://podcasts.apple.com/us/podcast/one/id1?i=1001"}}},
+            {"title":"Episode One","contextAction":{"episodeOffer":{"storeUrl":"https://podcasts.apple.com/us/podcast/one/id1?i=1001"}}}
+        ]}]}}]</script>"#;
+
+        // When we list the episodes
+        let episodes = find_episodes(html, "us").unwrap();
+
+        // Then each episode appears once with its own title and ISO release date
+        assert_eq!(episodes.len(), 2);
+        assert_eq!(episodes[0].id, "1002");
+        assert_eq!(episodes[0].title, "Episode Two");
+        assert_eq!(episodes[0].release_date.as_deref(), Some("2023-10-20"));
+        assert_eq!(episodes[1].title, "Episode One");
+    }
+
+    /// Unit test - find_next_page resolves relative pagination links
+    #[test]
+    fn test_find_next_page_resolves_relative_href() {
+        // Given page data advertising a relative next page
+        let html = r#"<script type="application/json" id="serialized-server-data">[{"data":{"shelves":[{"nextPage":{"href":"/us/podcast/show/id1?page=2"}}]}}]</script>"#;
+
+        // When we look for the next page
+        let next = find_next_page(html, "https://podcasts.apple.com/us/podcast/show/id1").unwrap();
+
+        // Then it is resolved against the current page
+        assert_eq!(
+            next.as_deref(),
+            Some("https://podcasts.apple.com/us/podcast/show/id1?page=2")
+        );
+    }
+
+    /// Unit test - EpisodeFilter excludes older and undated episodes when since is set
+    #[test]
+    fn test_episode_filter_applies_since() {
+        // Given a filter for episodes since 2023-10-15
+        let filter = EpisodeFilter {
+            limit: None,
+            since: Some("2023-10-15".to_string()),
+        };
+        let episode = |date: Option<&str>| EpisodeSummary {
+            id: "1".to_string(),
+            title: "Episode".to_string(),
+            url: "https://podcasts.apple.com/us/podcast/id1?i=1".to_string(),
+            release_date: date.map(str::to_string),
+        };
+
+        // When we check episodes around that date
+        // Then only those on or after it pass
+        assert!(filter.is_recent_enough(&episode(Some("2023-10-15"))));
+        assert!(!filter.is_recent_enough(&episode(Some("2023-10-13"))));
+        assert!(!filter.is_recent_enough(&episode(None)));
+    }
+
+    /// Unit test - parse_since accepts only YYYY-MM-DD
+    #[test]
+    fn test_parse_since_requires_iso_dates() {
+        // Given since arguments
+        // When we parse them
+        // Then only valid ISO dates are accepted
+        assert_eq!(parse_since("2023-10-13"), Ok("2023-10-13".to_string()));
+        assert!(parse_since("Oct 13, 2023").is_err());
+        assert!(parse_since("2023-02-30").is_err());
+    }
+
+    /// Serves show pages from a local server, recording every requested path
+    fn serve_show(
+        pages: Vec<(&'static str, Vec<u32>, Option<&'static str>)>,
+    ) -> (String, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let requested = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = requested.clone();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(&stream);
+                reader.read_line(&mut request_line).unwrap();
+                let mut header = String::new();
+                while reader.read_line(&mut header).unwrap() > 2 {
+                    header.clear();
+                }
+
+                let path = request_line.split(' ').nth(1).unwrap_or("").to_string();
+                log.lock().unwrap().push(path.clone());
+                let response = match pages.iter().find(|(page, _, _)| *page == path) {
+                    Some((_, ids, next)) => {
+                        let items: Vec<String> = ids
+                            .iter()
+                            .map(|id| {
+                                format!(
+                                    r#"{{"title":"Ep {id}","storeUrl":"https://podcasts.apple.com/us/podcast/x/id1?i={id}"}}"#
+                                )
+                            })
+                            .collect();
+                        let next = next
+                            .map(|next| format!(r#","nextPage":"{}""#, next))
+                            .unwrap_or_default();
+                        let body = format!(
+                            r#"<script type="application/json" id="serialized-server-data">[{{"data":{{"items":[{}]{}}}}}]</script>"#,
+                            items.join(","),
+                            next
+                        );
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                    }
+                    None => {
+                        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_string()
+                    }
+                };
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+
+        (base, requested)
+    }
+
+    /// Unit test - enumerate_episodes prefetches offset pages and completes the cursor
+    #[tokio::test]
+    async fn test_enumerate_episodes_prefetches_offset_pages() {
+        // Given a show paginated by offset over three pages
+        let (base, requested) = serve_show(vec![
+            ("/show", vec![1, 2], Some("/show?offset=2")),
+            ("/show?offset=2", vec![3, 4], Some("/show?offset=4")),
+            ("/show?offset=4", vec![5], None),
+        ]);
+        let url = format!("{}/show", base);
+        let mut cursor = ShowCursor::new(&url);
+        let mut checkpoints = 0;
+
+        // When we enumerate it with several page fetches in flight
+        let (_, episodes) = enumerate_episodes(
+            &url,
+            &HttpOptions::default(),
+            &EpisodeFilter::default(),
+            4,
+            &mut cursor,
+            &mut |_| checkpoints += 1,
+        )
+        .await
+        .unwrap();
+
+        // Then every episode is listed in order, the later pages were requested together,
+        // and the cursor records a finished catalog
+        let ids: Vec<&str> = episodes.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "2", "3", "4", "5"]);
+        assert!(requested
+            .lock()
+            .unwrap()
+            .contains(&"/show?offset=6".to_string()));
+        assert!(cursor.is_complete());
+        assert_eq!(checkpoints, 2);
+    }
+
+    /// Unit test - enumerate_episodes resumes from a saved cursor
+    #[tokio::test]
+    async fn test_enumerate_episodes_resumes_from_cursor() {
+        // Given a cursor from an earlier run that stopped before the last page,
+        // and a show that has since published a new episode
+        let (base, requested) = serve_show(vec![
+            ("/show", vec![0, 1], Some("/show?offset=2")),
+            ("/show?offset=2", vec![2, 3], Some("/show?offset=4")),
+            ("/show?offset=4", vec![4], None),
+        ]);
+        let url = format!("{}/show", base);
+        let known = |id: u32| EpisodeSummary {
+            id: id.to_string(),
+            title: format!("Ep {}", id),
+            url: format!("https://podcasts.apple.com/us/podcast/x/id1?i={}", id),
+            release_date: None,
+        };
+        let mut cursor = ShowCursor {
+            show_url: url.clone(),
+            next_page: Some(format!("{}/show?offset=4", base)),
+            episodes: vec![known(1), known(2), known(3)],
+        };
+
+        // When we enumerate again
+        let (_, episodes) = enumerate_episodes(
+            &url,
+            &HttpOptions::default(),
+            &EpisodeFilter::default(),
+            1,
+            &mut cursor,
+            &mut |_| {},
+        )
+        .await
+        .unwrap();
+
+        // Then the new episode is added at the top, the walk jumps straight to the saved page,
+        // and pages already seen are not fetched again
+        let ids: Vec<&str> = episodes.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["0", "1", "2", "3", "4"]);
+        assert_eq!(
+            *requested.lock().unwrap(),
+            vec!["/show".to_string(), "/show?offset=4".to_string()]
+        );
+        assert!(cursor.is_complete());
+    }
+
+    /// Unit test - predicted_pages extends the offset step
+    #[test]
+    fn test_predicted_pages_extends_offset_step() {
+        // Given a page at offset 20 linking to offset 40
+        let current = "https://amp-api.example/v1/episodes?limit=20&offset=20";
+        let next = "https://amp-api.example/v1/episodes?limit=20&offset=40";
+
+        // When we predict three pages
+        // Then the following offsets continue the step, and links without offsets are not predicted
+        assert_eq!(
+            predicted_pages(current, next, 3),
+            vec![
+                next.to_string(),
+                "https://amp-api.example/v1/episodes?limit=20&offset=60".to_string(),
+                "https://amp-api.example/v1/episodes?limit=20&offset=80".to_string(),
+            ]
+        );
+        assert_eq!(
+            predicted_pages(current, "https://amp-api.example/v1/next", 3),
+            vec!["https://amp-api.example/v1/next".to_string()]
+        );
+    }
+
+    /// Unit test - shows crawled into one output directory keep separate cursors
+    #[test]
+    fn test_show_cursors_are_kept_per_show() {
+        // Given two shows whose cursors are saved alternately into one directory,
+        // and a third show's cursor in the shared file of earlier versions
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().to_str().unwrap();
+        let daily = "https://podcasts.apple.com/us/podcast/the-daily/id1200361736";
+        let other = "https://podcasts.apple.com/us/podcast/other/id840986946";
+        let legacy = "https://podcasts.apple.com/us/podcast/legacy/id1";
+        let save = |show_url: &str, next_page: &str| {
+            let cursor = ShowCursor {
+                next_page: Some(next_page.to_string()),
+                ..ShowCursor::new(show_url)
+            };
+            fs::write(
+                cursor_path(dir, show_url),
+                serde_json::to_string(&cursor).unwrap(),
+            )
+            .unwrap();
+        };
+        save(daily, "daily?offset=10");
+        save(other, "other?offset=10");
+        save(daily, "daily?offset=20");
+        save(other, "other?offset=20");
+        fs::write(
+            format!("{}/show-cursor.json", dir),
+            serde_json::to_string(&ShowCursor::new(legacy)).unwrap(),
+        )
+        .unwrap();
+
+        // When each show's cursor is loaded
+        let load = |show_url: &str| ShowCursor::load_from(dir, show_url).unwrap().unwrap();
+
+        // Then each resumes from its own latest page, and the shared file is still read
+        assert_eq!(
+            cursor_path(dir, daily),
+            format!("{}/show-cursor-1200361736.json", dir)
+        );
+        assert_eq!(load(daily).next_page.as_deref(), Some("daily?offset=20"));
+        assert_eq!(load(other).next_page.as_deref(), Some("other?offset=20"));
+        assert_eq!(load(legacy).show_url, legacy);
+    }
+}
@@ -0,0 +1,427 @@
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::archive::ArchivedEpisode;
+use crate::audio::audio_file_name;
+use crate::metadata::Metadata;
+
+/// How a field is compared with a value in a smart list condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+    /// `~`: the text contains the value, ignoring case
+    Contains,
+}
+
+impl Comparison {
+    /// Operators longest first, so `>=` is not read as `>`
+    const OPERATORS: [(&'static str, Comparison); 7] = [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+        ("~", Comparison::Contains),
+    ];
+}
+
+/// A smart list's condition, evaluated against each archived episode
+///
+/// Conditions combine field tests with `&&`, `||`, `!` and parentheses, e.g.
+/// `duration_seconds>3600 && !played`. A bare field holds when it is set and
+/// not false, zero or empty. Fields are an episode's archive fields (`played`,
+/// `rating`, `tags`, `removed`, `transcript`, `title`, ...) or any key of its
+/// `metadata.json`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+    Field(String),
+    Compare {
+        field: String,
+        comparison: Comparison,
+        value: String,
+    },
+}
+
+impl Condition {
+    /// Parses a condition, explaining where it goes wrong
+    pub fn parse(text: &str) -> std::result::Result<Condition, String> {
+        let mut parser = Parser { text, position: 0 };
+        let condition = parser.or()?;
+        parser.skip_whitespace();
+        if parser.position < text.len() {
+            return Err(parser.error("expected && or ||"));
+        }
+        Ok(condition)
+    }
+
+    /// Whether `episode` meets the condition
+    pub fn matches(&self, episode: &ArchivedEpisode) -> bool {
+        match self {
+            Condition::And(left, right) => left.matches(episode) && right.matches(episode),
+            Condition::Or(left, right) => left.matches(episode) || right.matches(episode),
+            Condition::Not(inner) => !inner.matches(episode),
+            Condition::Field(field) => is_set(&field_value(episode, field)),
+            Condition::Compare {
+                field,
+                comparison,
+                value,
+            } => compare(&field_value(episode, field), *comparison, value),
+        }
+    }
+}
+
+/// Validates a `--where` condition, keeping its text to be saved
+pub fn parse_condition(raw: &str) -> std::result::Result<String, String> {
+    Condition::parse(raw)
+        .map(|_| raw.trim().to_string())
+        .map_err(|e| format!("Invalid condition '{}': {}", raw, e))
+}
+
+/// Validates a smart list name: letters, digits, `-` and `_`
+pub fn parse_list_name(raw: &str) -> std::result::Result<String, String> {
+    let name = raw.trim();
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "Invalid list name '{}': expected letters, digits, - or _, e.g. long-unplayed",
+            raw
+        ));
+    }
+    Ok(name.to_string())
+}
+
+/// A recursive descent parser over a condition's text
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn error(&self, expected: &str) -> String {
+        format!("{} at character {}", expected, self.position + 1)
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consumes `token` after any whitespace, returning whether it was there
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let found = self.rest().starts_with(token);
+        if found {
+            self.position += token.len();
+        }
+        found
+    }
+
+    fn or(&mut self) -> std::result::Result<Condition, String> {
+        let mut condition = self.and()?;
+        while self.eat("||") {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> std::result::Result<Condition, String> {
+        let mut condition = self.unary()?;
+        while self.eat("&&") {
+            condition = Condition::And(Box::new(condition), Box::new(self.unary()?));
+        }
+        Ok(condition)
+    }
+
+    fn unary(&mut self) -> std::result::Result<Condition, String> {
+        // `!=` only follows a field, so a leading `!` is always negation
+        if self.eat("!") {
+            return Ok(Condition::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let condition = self.or()?;
+            if !self.eat(")") {
+                return Err(self.error("expected )"));
+            }
+            return Ok(condition);
+        }
+        self.test()
+    }
+
+    /// A field, optionally compared with a value
+    fn test(&mut self) -> std::result::Result<Condition, String> {
+        self.skip_whitespace();
+        let length = self
+            .rest()
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest().len());
+        if length == 0 {
+            return Err(self.error("expected a field name"));
+        }
+        let field = self.rest()[..length].to_string();
+        self.position += length;
+
+        self.skip_whitespace();
+        let Some((operator, comparison)) = Comparison::OPERATORS
+            .into_iter()
+            .find(|(operator, _)| self.rest().starts_with(operator))
+        else {
+            return Ok(Condition::Field(field));
+        };
+        self.position += operator.len();
+        let value = self.value()?;
+        Ok(Condition::Compare {
+            field,
+            comparison,
+            value,
+        })
+    }
+
+    /// A quoted string, or a bare word such as `3600` or `2024-01-01`
+    fn value(&mut self) -> std::result::Result<String, String> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        if let Some(quote) = rest.chars().next().filter(|c| *c == '\'' || *c == '"') {
+            let Some(end) = rest[1..].find(quote) else {
+                return Err(self.error("unterminated quote"));
+            };
+            let value = rest[1..=end].to_string();
+            self.position += end + 2;
+            return Ok(value);
+        }
+        let length = rest
+            .find(|c: char| c.is_whitespace() || "()&|".contains(c))
+            .unwrap_or(rest.len());
+        if length == 0 {
+            return Err(self.error("expected a value"));
+        }
+        self.position += length;
+        Ok(rest[..length].to_string())
+    }
+}
+
+/// An episode's value for `field`: an archive field, or else the `metadata.json` key
+fn field_value(episode: &ArchivedEpisode, field: &str) -> Value {
+    let text = |text: &str| Value::from(text);
+    match field {
+        "id" => text(&episode.id),
+        "short_id" => text(&episode.short_id),
+        "show_id" => episode.show_id.as_deref().map(text).unwrap_or_default(),
+        "title" => text(&episode.title),
+        "show_title" => text(&episode.show_title),
+        "publish_date" => episode
+            .publish_date
+            .as_deref()
+            .map(text)
+            .unwrap_or_default(),
+        "fetched_at" => text(&episode.fetched_at),
+        "played" => Value::from(episode.played_at.is_some()),
+        "progress_seconds" => episode
+            .progress_seconds
+            .map(Value::from)
+            .unwrap_or_default(),
+        "rating" => episode.rating.map(Value::from).unwrap_or_default(),
+        "tags" | "tag" => Value::from(episode.tags.clone()),
+        "removed" => Value::from(episode.removed_upstream.is_some()),
+        "transcript" => Value::from(episode.transcript_path.is_some()),
+        _ => episode.metadata[field].clone(),
+    }
+}
+
+/// Whether a bare field holds: set, and not false, zero or empty
+fn is_set(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(set) => *set,
+        Value::Number(number) => number.as_f64() != Some(0.0),
+        Value::String(text) => !text.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(fields) => !fields.is_empty(),
+    }
+}
+
+/// Compares a field's value with a condition's value
+///
+/// Numbers compare as numbers when both sides are numeric; otherwise text
+/// compares ignoring case, which also orders ISO 8601 dates. A list field
+/// such as `tags` equals a value it contains. An unset field only meets `!=`.
+fn compare(field: &Value, comparison: Comparison, value: &str) -> bool {
+    let text = match field {
+        Value::Null => return comparison == Comparison::NotEqual,
+        Value::Array(items) => {
+            let found = items
+                .iter()
+                .any(|item| compare(item, Comparison::Equal, value));
+            return match comparison {
+                Comparison::Equal | Comparison::Contains => found,
+                Comparison::NotEqual => !found,
+                _ => false,
+            };
+        }
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+
+    let ordering = match (text.parse::<f64>(), value.parse::<f64>()) {
+        (Ok(left), Ok(right)) if comparison != Comparison::Contains => left.partial_cmp(&right),
+        _ => Some(text.to_lowercase().cmp(&value.to_lowercase())),
+    };
+    let Some(ordering) = ordering else {
+        return false;
+    };
+    match comparison {
+        Comparison::Less => ordering.is_lt(),
+        Comparison::LessOrEqual => ordering.is_le(),
+        Comparison::Greater => ordering.is_gt(),
+        Comparison::GreaterOrEqual => ordering.is_ge(),
+        Comparison::Equal => ordering.is_eq(),
+        Comparison::NotEqual => ordering.is_ne(),
+        Comparison::Contains => text.to_lowercase().contains(&value.to_lowercase()),
+    }
+}
+
+/// Renders episodes as an M3U playlist, pointing at downloaded audio when it was saved
+pub fn to_m3u(episodes: &[ArchivedEpisode]) -> String {
+    let mut out = String::from("#EXTM3U\n");
+    for episode in episodes {
+        let Some(audio_url) = episode.metadata["audio_url"].as_str() else {
+            continue;
+        };
+        let metadata = Metadata {
+            episode_title: episode.title.clone(),
+            publish_date_iso8601: episode.publish_date.clone(),
+            ..Metadata::default()
+        };
+        let saved = Path::new(&episode.directory).join(audio_file_name(&metadata, audio_url));
+        let location = if saved.is_file() {
+            saved.to_string_lossy().into_owned()
+        } else {
+            audio_url.to_string()
+        };
+        let duration = episode.metadata["duration_seconds"].as_i64().unwrap_or(-1);
+        out.push_str(&format!(
+            "#EXTINF:{},{} — {}\n{}\n",
+            duration, episode.show_title, episode.title, location
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn episode(title: &str, metadata: Value) -> ArchivedEpisode {
+        ArchivedEpisode {
+            id: "1".to_string(),
+            short_id: "e1a2b3".to_string(),
+            show_id: Some("840986946".to_string()),
+            url: "https://podcasts.apple.com/us/podcast/id840986946?i=1".to_string(),
+            title: title.to_string(),
+            show_title: "Back to the Board".to_string(),
+            publish_date: Some("2024-03-01".to_string()),
+            guid: None,
+            directory: "missing".to_string(),
+            transcript_path: None,
+            fetched_at: "2024-03-02T08:00:00Z".to_string(),
+            metadata,
+            removed_upstream: None,
+            rating: Some(4),
+            tags: vec!["interview".to_string()],
+            played_at: None,
+            progress_seconds: None,
+        }
+    }
+
+    /// Unit test - conditions parse with precedence and match archive and metadata fields
+    #[test]
+    fn test_condition_matches_episodes() {
+        // Given a long unplayed episode and a short one
+        let long = episode(
+            "Pricing Deep Dive",
+            json!({ "duration_seconds": 4200, "explicit": true, "audio_url": "https://cdn.example.com/1.mp3" }),
+        );
+        let short = episode("Trailer", json!({ "duration_seconds": 90 }));
+
+        // When we evaluate conditions over both
+        let matching = |text: &str| {
+            let condition = Condition::parse(text).unwrap();
+            [&long, &short]
+                .iter()
+                .filter(|episode| condition.matches(episode))
+                .map(|episode| episode.title.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        // Then comparisons, negation, lists, text and dates behave as documented
+        assert_eq!(
+            matching("duration_seconds>3600 && !played"),
+            ["Pricing Deep Dive"]
+        );
+        assert_eq!(matching("!explicit"), ["Trailer"]);
+        assert_eq!(
+            matching("title ~ 'deep dive' || duration_seconds < 100"),
+            ["Pricing Deep Dive", "Trailer"]
+        );
+        assert_eq!(
+            matching("tags == interview && rating >= 4 && publish_date >= 2024-01-01").len(),
+            2
+        );
+        assert!(matching("(played || removed) && audio_url").is_empty());
+        assert_eq!(matching("guid != x").len(), 2);
+
+        // And malformed conditions are rejected with a position
+        for bad in [
+            "",
+            "duration_seconds >",
+            "(played",
+            "played &&",
+            "title ~ 'open",
+        ] {
+            assert!(Condition::parse(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(
+            Condition::parse("played played").unwrap_err(),
+            "expected && or || at character 8"
+        );
+    }
+
+    /// Unit test - to_m3u lists episodes with audio, with their duration and title
+    #[test]
+    fn test_to_m3u() {
+        // Given one episode with an audio URL and one without
+        let episodes = [
+            episode(
+                "Launch",
+                json!({ "duration_seconds": 4200, "audio_url": "https://cdn.example.com/1.mp3" }),
+            ),
+            episode("No Audio", json!({})),
+        ];
+
+        // When we render the playlist
+        let playlist = to_m3u(&episodes);
+
+        // Then only the episode with audio is listed, pointing at its URL
+        assert_eq!(
+            playlist,
+            "#EXTM3U\n#EXTINF:4200,Back to the Board — Launch\nhttps://cdn.example.com/1.mp3\n"
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::error::{Error, Result};
+use crate::metadata::Metadata;
+
+/// Placeholder in a speech command replaced by the audio file path
+pub const OUTPUT_PLACEHOLDER: &str = "{output}";
+
+/// Speech engine used by `--tts-notes` unless `--tts-command` is given
+#[cfg(target_os = "macos")]
+pub const DEFAULT_SPEECH_COMMAND: &str = "say --data-format=LEI16@22050 -o {output}";
+
+/// Speech engine used by `--tts-notes` unless `--tts-command` is given
+#[cfg(not(target_os = "macos"))]
+pub const DEFAULT_SPEECH_COMMAND: &str = "espeak-ng --stdin -w {output}";
+
+/// An external text-to-speech command that reads text on stdin and writes WAV audio to `{output}`
+///
+/// Any engine or HTTP API wrapper works as long as it follows that contract,
+/// e.g. `espeak-ng --stdin -w {output}` or a script calling a hosted TTS API.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeechCommand {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+/// Parses a `--tts-command` value; arguments are split on whitespace
+pub fn parse_speech_command(raw: &str) -> std::result::Result<SpeechCommand, String> {
+    let mut words = raw.split_whitespace().map(str::to_string);
+    let program = words
+        .next()
+        .ok_or_else(|| "Invalid TTS command '': expected a program to run".to_string())?;
+    let args: Vec<String> = words.collect();
+    if !args.iter().any(|arg| arg.contains(OUTPUT_PLACEHOLDER)) {
+        return Err(format!(
+            "Invalid TTS command '{}': expected an {} placeholder for the audio file",
+            raw, OUTPUT_PLACEHOLDER
+        ));
+    }
+    Ok(SpeechCommand { program, args })
+}
+
+impl SpeechCommand {
+    /// Speaks `text` into the audio file at `output`
+    pub fn render(&self, text: &str, output: &Path) -> Result<()> {
+        let output = output.to_string_lossy();
+        let mut child = Command::new(&self.program)
+            .args(
+                self.args
+                    .iter()
+                    .map(|arg| arg.replace(OUTPUT_PLACEHOLDER, &output)),
+            )
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| Error::Speech(format!("could not run '{}': {}", self.program, e)))?;
+
+        // A command that fails early closes stdin; its exit status explains more than the broken pipe
+        let written = match child.stdin.take() {
+            Some(mut stdin) => stdin.write_all(text.as_bytes()),
+            None => Ok(()),
+        };
+        let result = child
+            .wait_with_output()
+            .map_err(Error::io("Failed to wait for the TTS command"))?;
+        if !result.status.success() {
+            return Err(Error::Speech(format!(
+                "'{}' exited with {}: {}",
+                self.program,
+                result.status,
+                String::from_utf8_lossy(&result.stderr).trim()
+            )));
+        }
+        written.map_err(Error::io("Failed to send text to the TTS command"))
+    }
+}
+
+/// The text read aloud for an episode's show notes: title, show and description
+pub fn show_notes_text(metadata: &Metadata) -> String {
+    [
+        metadata.episode_title.as_str(),
+        metadata.show_title.as_str(),
+        metadata.description.as_str(),
+    ]
+    .iter()
+    .map(|part| part.trim().trim_end_matches('.'))
+    .filter(|part| !part.is_empty())
+    .map(|part| format!("{}.", part))
+    .collect::<Vec<_>>()
+    .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - parse_speech_command requires a program and an output placeholder
+    #[test]
+    fn test_parse_speech_command() {
+        // Given a valid command, one without a placeholder and an empty one
+        // When we parse them
+        // Then only the valid one is accepted, split into program and arguments
+        assert_eq!(
+            parse_speech_command("espeak-ng --stdin -w {output}"),
+            Ok(SpeechCommand {
+                program: "espeak-ng".to_string(),
+                args: vec!["--stdin".into(), "-w".into(), "{output}".into()],
+            })
+        );
+        assert!(parse_speech_command("espeak-ng --stdin")
+            .unwrap_err()
+            .contains("{output}"));
+        assert!(parse_speech_command("  ").is_err());
+    }
+
+    /// Unit test - show_notes_text reads the title, show and description as sentences
+    #[test]
+    fn test_show_notes_text() {
+        // Given episode metadata with a description ending in a full stop
+        let metadata = Metadata {
+            episode_title: "Small Steps".to_string(),
+            show_title: "Shipping".to_string(),
+            description: "Why small changes win.".to_string(),
+            ..Metadata::default()
+        };
+
+        // When we build the spoken text
+        // Then each part is its own sentence
+        assert_eq!(
+            show_notes_text(&metadata),
+            "Small Steps.\n\nShipping.\n\nWhy small changes win."
+        );
+    }
+
+    /// Unit test - render pipes the text to the command and fills in the output path
+    #[cfg(unix)]
+    #[test]
+    fn test_render_pipes_text_to_command() {
+        // Given a "speech engine" that copies stdin to the output file
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("notes.wav");
+        let command = parse_speech_command("tee {output}").unwrap();
+
+        // When we render text with it, and with an engine that fails
+        command.render("Hello there.", &output).unwrap();
+        let failing = parse_speech_command("false {output}").unwrap();
+
+        // Then the file holds the text and the failure is reported
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "Hello there.");
+        assert!(matches!(
+            failing.render("Hello", &output),
+            Err(Error::Speech(_))
+        ));
+    }
+}
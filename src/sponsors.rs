@@ -0,0 +1,209 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::ads::AdSegment;
+
+/// One ad read found in an episode
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct SponsorRead {
+    pub start_seconds: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sponsor: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub promo_codes: Vec<String>,
+}
+
+impl SponsorRead {
+    /// Reads an ad segment as recorded in an episode's `metadata.json`
+    pub fn from_json(segment: &serde_json::Value) -> SponsorRead {
+        SponsorRead {
+            start_seconds: segment["start_seconds"].as_f64().unwrap_or_default(),
+            sponsor: segment["sponsor"].as_str().map(str::to_string),
+            promo_codes: segment["promo_codes"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|code| code.as_str().map(str::to_string))
+                .collect(),
+        }
+    }
+}
+
+impl From<AdSegment> for SponsorRead {
+    fn from(segment: AdSegment) -> SponsorRead {
+        SponsorRead {
+            start_seconds: segment.start_seconds,
+            sponsor: segment.sponsor,
+            promo_codes: segment.promo_codes,
+        }
+    }
+}
+
+/// The ad reads found in one episode
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct EpisodeSponsors {
+    pub id: String,
+    pub title: String,
+    pub publish_date: Option<String>,
+    pub reads: Vec<SponsorRead>,
+}
+
+/// A sponsor across a show's episodes
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct SponsorSummary {
+    /// The name as first read out
+    pub name: String,
+    /// Episodes with at least one read by this sponsor
+    pub episodes: usize,
+    pub first_seen: Option<String>,
+    pub last_seen: Option<String>,
+    /// Promo codes read out alongside the sponsor, in the order first heard
+    pub promo_codes: Vec<String>,
+}
+
+/// The result of `report sponsors` for one show
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct SponsorReport {
+    pub show_id: String,
+    /// Archived episodes whose ads were looked at
+    pub episodes_scanned: usize,
+    /// Archived episodes with neither a transcript nor recorded ad segments
+    pub episodes_without_transcript: usize,
+    /// Sponsors by the number of episodes they appear in, most first
+    pub sponsors: Vec<SponsorSummary>,
+    /// Episodes with at least one ad read, oldest first
+    pub episodes: Vec<EpisodeSponsors>,
+}
+
+/// Orders a show's ad reads by episode date and totals them per sponsor
+///
+/// Sponsors are grouped ignoring case, so "Acme Corp" and "ACME Corp" count
+/// as one. Reads with a promo code but no sponsor name stay on their episode.
+pub fn sponsor_report(
+    show_id: &str,
+    mut episodes: Vec<EpisodeSponsors>,
+    episodes_without_transcript: usize,
+) -> SponsorReport {
+    let episodes_scanned = episodes.len();
+    episodes.retain(|episode| !episode.reads.is_empty());
+    episodes.sort_by(|a, b| a.publish_date.cmp(&b.publish_date));
+
+    let mut sponsors: Vec<SponsorSummary> = Vec::new();
+    let mut by_key: HashMap<String, usize> = HashMap::new();
+    for episode in &episodes {
+        let mut counted = Vec::new();
+        for read in &episode.reads {
+            let Some(name) = &read.sponsor else {
+                continue;
+            };
+            let index = *by_key.entry(name.to_lowercase()).or_insert_with(|| {
+                sponsors.push(SponsorSummary {
+                    name: name.clone(),
+                    episodes: 0,
+                    first_seen: episode.publish_date.clone(),
+                    last_seen: None,
+                    promo_codes: Vec::new(),
+                });
+                sponsors.len() - 1
+            });
+            let sponsor = &mut sponsors[index];
+            if !counted.contains(&index) {
+                counted.push(index);
+                sponsor.episodes += 1;
+                sponsor.last_seen = episode.publish_date.clone();
+            }
+            for code in &read.promo_codes {
+                if !sponsor.promo_codes.contains(code) {
+                    sponsor.promo_codes.push(code.clone());
+                }
+            }
+        }
+    }
+    // The sort is stable, so sponsors heard in as many episodes stay in order of first appearance
+    sponsors.sort_by_key(|sponsor| Reverse(sponsor.episodes));
+
+    SponsorReport {
+        show_id: show_id.to_string(),
+        episodes_scanned,
+        episodes_without_transcript,
+        sponsors,
+        episodes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn episode(date: &str, reads: Vec<SponsorRead>) -> EpisodeSponsors {
+        EpisodeSponsors {
+            id: date.to_string(),
+            title: format!("Episode of {}", date),
+            publish_date: Some(date.to_string()),
+            reads,
+        }
+    }
+
+    fn read(sponsor: Option<&str>, codes: &[&str]) -> SponsorRead {
+        SponsorRead {
+            start_seconds: 30.0,
+            sponsor: sponsor.map(str::to_string),
+            promo_codes: codes.iter().map(|code| code.to_string()).collect(),
+        }
+    }
+
+    /// Unit test - sponsor_report totals sponsors across episodes and keeps their codes
+    #[test]
+    fn test_sponsor_report_groups_sponsors() {
+        // Given three episodes read out of order, one without ads
+        let episodes = vec![
+            episode(
+                "2024-03-01",
+                vec![
+                    read(Some("ACME Corp"), &["PODCAST20"]),
+                    read(None, &["SAVE10"]),
+                ],
+            ),
+            episode(
+                "2024-01-01",
+                vec![
+                    read(Some("Acme Corp"), &["BOARD"]),
+                    read(Some("Acme Corp"), &["BOARD"]),
+                    read(Some("Squarely"), &[]),
+                ],
+            ),
+            episode("2024-02-01", Vec::new()),
+        ];
+
+        // When we build the report
+        let report = sponsor_report("840986946", episodes, 2);
+
+        // Then sponsors are grouped ignoring case, counted once per episode, with their codes
+        assert_eq!(report.episodes_scanned, 3);
+        assert_eq!(report.episodes_without_transcript, 2);
+        assert_eq!(report.episodes.len(), 2);
+        assert_eq!(report.episodes[0].id, "2024-01-01");
+        let acme = &report.sponsors[0];
+        assert_eq!(acme.name, "Acme Corp");
+        assert_eq!(acme.episodes, 2);
+        assert_eq!(acme.first_seen.as_deref(), Some("2024-01-01"));
+        assert_eq!(acme.last_seen.as_deref(), Some("2024-03-01"));
+        assert_eq!(acme.promo_codes, ["BOARD", "PODCAST20"]);
+        assert_eq!(report.sponsors[1].name, "Squarely");
+        assert_eq!(report.sponsors.len(), 2);
+
+        // And recorded ad segments are read back from metadata.json
+        let recorded = SponsorRead::from_json(&json!({
+            "start_seconds": 12.5, "end_seconds": 70.0, "evidence": [],
+            "sponsor": "Acme Corp", "promo_codes": ["BOARD"]
+        }));
+        assert_eq!(recorded, {
+            let mut expected = read(Some("Acme Corp"), &["BOARD"]);
+            expected.start_seconds = 12.5;
+            expected
+        });
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::output::write_json;
+
+/// Directory name under the per-user state directory
+const STATE_DIR_NAME: &str = "applecast-cli";
+
+/// File the counts are kept in
+const STATS_FILE_NAME: &str = "strategy-stats.json";
+
+/// Days of counts kept; older days are dropped when the file is saved
+pub const RETENTION_DAYS: u64 = 90;
+
+/// Days that count as recent when comparing against the days before
+pub const RECENT_DAYS: u64 = 7;
+
+/// Fewest attempts on each side of the comparison before a drop is called drift
+const MIN_ATTEMPTS: u64 = 5;
+
+/// Fall in success rate, recent against earlier, that counts as drift
+const DRIFT_THRESHOLD: f64 = 0.2;
+
+/// The per-user stats file: `$XDG_STATE_HOME/applecast-cli/strategy-stats.json`,
+/// else `~/.local/state/applecast-cli/strategy-stats.json`
+pub fn default_stats_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state"))
+        })?;
+    Some(base.join(STATE_DIR_NAME).join(STATS_FILE_NAME))
+}
+
+/// Today's UTC date as `YYYY-MM-DD`
+pub fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    civil_date(seconds / 86_400)
+}
+
+/// The `YYYY-MM-DD` date `days` days after 1970-01-01
+///
+/// Howard Hinnant's `civil_from_days`, for dates from 1970 on.
+fn civil_date(days: u64) -> String {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Days from 1970-01-01 to a `YYYY-MM-DD` date, the inverse of [`civil_date`]
+pub(crate) fn day_number(date: &str) -> Option<u64> {
+    let mut parts = date.splitn(3, '-').map(|part| part.parse::<u64>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    let year = year - u64::from(month <= 2);
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    (era * 146_097 + doe).checked_sub(719_468)
+}
+
+/// The date `days` days before the `YYYY-MM-DD` date `date`
+pub(crate) fn days_before(date: &str, days: u64) -> String {
+    let number = day_number(date).unwrap_or_default();
+    civil_date(number.saturating_sub(days))
+}
+
+/// How often one strategy produced its field
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Tally {
+    pub succeeded: u64,
+    pub failed: u64,
+}
+
+impl Tally {
+    /// Every attempt counted
+    pub fn attempts(&self) -> u64 {
+        self.succeeded + self.failed
+    }
+
+    /// Share of attempts that succeeded, or `None` before any attempt
+    pub fn rate(&self) -> Option<f64> {
+        (self.attempts() > 0).then(|| self.succeeded as f64 / self.attempts() as f64)
+    }
+
+    fn add(&mut self, other: Tally) {
+        self.succeeded += other.succeeded;
+        self.failed += other.failed;
+    }
+}
+
+/// Counts for the pages fetched on one day
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct DayStats {
+    pub pages: u64,
+    /// Tallies by field, then by strategy
+    pub strategies: BTreeMap<String, BTreeMap<String, Tally>>,
+}
+
+/// Which extraction strategies worked on the pages this machine fetched, day by day
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct StrategyStats {
+    /// Counts by `YYYY-MM-DD` date
+    pub days: BTreeMap<String, DayStats>,
+}
+
+impl StrategyStats {
+    /// Reads the stats file; a missing file is empty stats
+    pub fn load(path: impl AsRef<Path>) -> Result<StrategyStats> {
+        match fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(StrategyStats::default()),
+            Err(e) => Err(Error::io("Failed to read strategy stats")(e)),
+        }
+    }
+
+    /// Writes the stats file, dropping days older than `RETENTION_DAYS` before `today`
+    pub fn save(&mut self, path: &str, today: &str) -> Result<()> {
+        let oldest = days_before(today, RETENTION_DAYS - 1);
+        self.days.retain(|day, _| day.as_str() >= oldest.as_str());
+        write_json(self, path)
+    }
+
+    /// Counts one page's `(field, strategy, succeeded)` attempts under `day`
+    pub fn record(&mut self, day: &str, attempts: &[(&str, String, bool)]) {
+        let stats = self.days.entry(day.to_string()).or_default();
+        stats.pages += 1;
+        for (field, strategy, succeeded) in attempts {
+            let tally = stats
+                .strategies
+                .entry(field.to_string())
+                .or_default()
+                .entry(strategy.clone())
+                .or_default();
+            if *succeeded {
+                tally.succeeded += 1;
+            } else {
+                tally.failed += 1;
+            }
+        }
+    }
+
+    /// Each strategy's success over the last `RECENT_DAYS` days up to `today` against the days before
+    pub fn trends(&self, today: &str) -> StrategyReport {
+        let recent_from = days_before(today, RECENT_DAYS - 1);
+        let mut report = StrategyReport {
+            since: self.days.keys().next().cloned(),
+            ..StrategyReport::default()
+        };
+        let mut trends: BTreeMap<(String, String), StrategyTrend> = BTreeMap::new();
+        for (day, stats) in &self.days {
+            let recent = day.as_str() >= recent_from.as_str();
+            if recent {
+                report.recent_pages += stats.pages;
+            } else {
+                report.earlier_pages += stats.pages;
+            }
+            for (field, strategies) in &stats.strategies {
+                for (strategy, tally) in strategies {
+                    let trend = trends
+                        .entry((field.clone(), strategy.clone()))
+                        .or_insert_with(|| StrategyTrend {
+                            field: field.clone(),
+                            strategy: strategy.clone(),
+                            ..StrategyTrend::default()
+                        });
+                    if recent {
+                        trend.recent.add(*tally);
+                    } else {
+                        trend.earlier.add(*tally);
+                    }
+                }
+            }
+        }
+        report.strategies = trends.into_values().collect();
+        report
+    }
+}
+
+/// One strategy's tallies in the recent days and in the days before
+#[derive(Debug, Serialize, Clone, PartialEq, Default)]
+pub struct StrategyTrend {
+    pub field: String,
+    pub strategy: String,
+    pub recent: Tally,
+    pub earlier: Tally,
+}
+
+impl StrategyTrend {
+    /// Whether the strategy succeeds clearly less often lately than it used to
+    pub fn drifting(&self) -> bool {
+        if self.recent.attempts() < MIN_ATTEMPTS || self.earlier.attempts() < MIN_ATTEMPTS {
+            return false;
+        }
+        match (self.recent.rate(), self.earlier.rate()) {
+            (Some(recent), Some(earlier)) => earlier - recent >= DRIFT_THRESHOLD,
+            _ => false,
+        }
+    }
+}
+
+/// What `devtools strategy-stats` prints
+#[derive(Debug, Serialize, Clone, PartialEq, Default)]
+pub struct StrategyReport {
+    /// Oldest day with counts
+    pub since: Option<String>,
+    pub recent_pages: u64,
+    pub earlier_pages: u64,
+    pub strategies: Vec<StrategyTrend>,
+}
+
+impl StrategyReport {
+    /// Strategies whose success rate has dropped
+    pub fn drifting(&self) -> Vec<&StrategyTrend> {
+        self.strategies
+            .iter()
+            .filter(|trend| trend.drifting())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - civil_date converts day numbers to ISO dates
+    #[test]
+    fn test_civil_date() {
+        // Given day numbers around epoch, a leap day and a recent date
+        // When we convert them
+        // Then they match the calendar
+        assert_eq!(civil_date(0), "1970-01-01");
+        assert_eq!(civil_date(11_016), "2000-02-29");
+        assert_eq!(civil_date(20_742), "2026-10-16");
+        assert_eq!(day_number("2026-10-16"), Some(20_742));
+        assert_eq!(days_before("2026-03-01", 1), "2026-02-28");
+    }
+
+    /// Unit test - stats flag a strategy whose recent success rate dropped
+    #[test]
+    fn test_strategy_stats_detect_drift() {
+        // Given ten earlier pages where JSON-LD always found the title and ten recent ones where it mostly failed
+        let today = today();
+        let earlier = days_before(&today, RECENT_DAYS + 3);
+        let mut stats = StrategyStats::default();
+        for page in 0..10 {
+            let attempts = |json_ld: bool| {
+                vec![
+                    ("episode_title", "json-ld".to_string(), json_ld),
+                    ("episode_title", "meta-tags".to_string(), true),
+                ]
+            };
+            stats.record(&earlier, &attempts(true));
+            stats.record(&today, &attempts(page < 3));
+        }
+
+        // When we compare recent days with earlier ones
+        let report = stats.trends(&today);
+
+        // Then only the JSON-LD strategy is drifting
+        assert_eq!((report.recent_pages, report.earlier_pages), (10, 10));
+        assert_eq!(report.since, Some(earlier));
+        let drifting: Vec<&str> = report
+            .drifting()
+            .iter()
+            .map(|trend| trend.strategy.as_str())
+            .collect();
+        assert_eq!(drifting, vec!["json-ld"]);
+        assert_eq!(
+            report.strategies[0].recent,
+            Tally {
+                succeeded: 3,
+                failed: 7
+            }
+        );
+    }
+
+    /// Unit test - saving drops days past the retention window and loading reads them back
+    #[test]
+    fn test_strategy_stats_round_trip() {
+        use tempfile::TempDir;
+
+        // Given stats with a current day and one older than the retention window
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("state/strategy-stats.json");
+        let path = path.to_str().unwrap();
+        let today = today();
+        let mut stats = StrategyStats::default();
+        stats.record(&today, &[("guid", "server-data".to_string(), true)]);
+        stats.record(
+            &days_before(&today, RETENTION_DAYS + 1),
+            &[("guid", "server-data".to_string(), false)],
+        );
+
+        // When we save and load them, and load a missing file
+        stats.save(path, &today).unwrap();
+        let loaded = StrategyStats::load(path).unwrap();
+
+        // Then only the current day survives, and a missing file is empty
+        assert_eq!(loaded.days.keys().collect::<Vec<_>>(), vec![&today]);
+        assert_eq!(
+            StrategyStats::load(temp_dir.path().join("missing.json")).unwrap(),
+            StrategyStats::default()
+        );
+    }
+}
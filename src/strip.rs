@@ -0,0 +1,268 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::output::write_file;
+
+/// Suffix added before the extension of a copy with the ads cut out
+pub const STRIPPED_SUFFIX: &str = ".no-ads";
+
+/// Bitrates in kbit/s by index, for MPEG-1 layers I, II and III
+const MPEG1_BITRATES: [[u32; 15]; 3] = [
+    [
+        0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+    ],
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+    ],
+    [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ],
+];
+
+/// Bitrates in kbit/s by index, for MPEG-2 and 2.5 layer I, then layers II and III
+const MPEG2_BITRATES: [[u32; 15]; 2] = [
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+    ],
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+];
+
+/// Sample rates by index, for MPEG-1, 2 and 2.5
+const SAMPLE_RATES: [[u32; 3]; 3] = [
+    [44100, 48000, 32000],
+    [22050, 24000, 16000],
+    [11025, 12000, 8000],
+];
+
+/// What `strip_ads` cut from an audio file
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrippedAudio {
+    /// The copy without the ads
+    pub path: String,
+    pub removed_seconds: f64,
+    pub kept_seconds: f64,
+}
+
+/// The name of the ad-free copy of `path`: `episode.mp3` becomes `episode.no-ads.mp3`
+pub fn stripped_path(path: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!(
+            "{}{}.{}",
+            stem,
+            STRIPPED_SUFFIX,
+            extension.to_string_lossy()
+        ),
+        None => format!("{}{}", stem, STRIPPED_SUFFIX),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// Writes a copy of an MP3 file without the audio in `ranges` (start and end seconds)
+///
+/// MP3 audio is a run of independent frames, each a few dozen milliseconds
+/// long, so ads are cut by dropping the frames whose middle falls in a range;
+/// nothing is re-encoded. ID3 tags are copied as they are. A Xing or Info
+/// frame is dropped, since its frame count and seek table no longer apply.
+/// Other formats are refused: M4A audio would have to be remuxed.
+pub fn strip_ads(path: &str, ranges: &[(f64, f64)]) -> Result<StrippedAudio> {
+    let extension = Path::new(path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    if extension.as_deref() != Some("mp3") {
+        return Err(Error::Parse(format!(
+            "Cannot cut ads from {}: only .mp3 audio can be cut",
+            path
+        )));
+    }
+    let audio = fs::read(path).map_err(Error::io("Failed to read audio"))?;
+    let (stripped, removed_seconds, kept_seconds) = cut_frames(&audio, ranges)?;
+    let out = stripped_path(path);
+    write_file(&out, stripped)?;
+    Ok(StrippedAudio {
+        path: out,
+        removed_seconds,
+        kept_seconds,
+    })
+}
+
+/// Drops the frames inside `ranges`, returning the audio left and the seconds removed and kept
+fn cut_frames(audio: &[u8], ranges: &[(f64, f64)]) -> Result<(Vec<u8>, f64, f64)> {
+    let mut out = Vec::with_capacity(audio.len());
+    let mut position = id3v2_len(audio);
+    out.extend_from_slice(&audio[..position]);
+
+    let (mut elapsed, mut removed, mut frames) = (0.0, 0.0, 0);
+    while position < audio.len() {
+        let rest = &audio[position..];
+        if rest.len() == 128 && rest.starts_with(b"TAG") {
+            out.extend_from_slice(rest);
+            break;
+        }
+        // Bytes that do not start a frame are junk between frames and are skipped
+        let Some(frame) = FrameHeader::parse(rest).filter(|frame| frame.length <= rest.len())
+        else {
+            position += 1;
+            continue;
+        };
+        let bytes = &rest[..frame.length];
+        position += frame.length;
+        frames += 1;
+        if frames == 1 && is_info_frame(bytes) {
+            continue;
+        }
+
+        let seconds = frame.seconds();
+        let middle = elapsed + seconds / 2.0;
+        elapsed += seconds;
+        if ranges
+            .iter()
+            .any(|(start, end)| (*start..*end).contains(&middle))
+        {
+            removed += seconds;
+        } else {
+            out.extend_from_slice(bytes);
+        }
+    }
+    if frames == 0 {
+        return Err(Error::Parse("No MP3 audio frames found".to_string()));
+    }
+    Ok((out, removed, elapsed - removed))
+}
+
+/// The length of an ID3v2 tag at the start of `audio`, or 0
+fn id3v2_len(audio: &[u8]) -> usize {
+    if audio.len() < 10 || !audio.starts_with(b"ID3") {
+        return 0;
+    }
+    // The size is "synchsafe": seven bits per byte
+    let size = audio[6..10]
+        .iter()
+        .fold(0usize, |size, byte| (size << 7) | (*byte & 0x7f) as usize);
+    let footer = if audio[5] & 0x10 != 0 { 10 } else { 0 };
+    (10 + size + footer).min(audio.len())
+}
+
+/// Whether a frame is an encoder's Xing or Info header rather than audio
+fn is_info_frame(frame: &[u8]) -> bool {
+    frame
+        .windows(4)
+        .take(64)
+        .any(|window| window == b"Xing" || window == b"Info")
+}
+
+/// The parts of an MPEG audio frame header needed to step over the frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FrameHeader {
+    length: usize,
+    samples: u32,
+    sample_rate: u32,
+}
+
+impl FrameHeader {
+    fn parse(bytes: &[u8]) -> Option<FrameHeader> {
+        let header = bytes.get(..4)?;
+        if header[0] != 0xff || header[1] & 0xe0 != 0xe0 {
+            return None;
+        }
+        // Version: 0 is MPEG-2.5, 2 is MPEG-2, 3 is MPEG-1; layer: 3 is I, 2 is II, 1 is III
+        let version = (header[1] >> 3) & 0x03;
+        let layer = (header[1] >> 1) & 0x03;
+        let bitrate_index = (header[2] >> 4) as usize;
+        let rate_index = ((header[2] >> 2) & 0x03) as usize;
+        let padding = ((header[2] >> 1) & 0x01) as usize;
+        if version == 1
+            || layer == 0
+            || bitrate_index == 0
+            || bitrate_index == 15
+            || rate_index == 3
+        {
+            return None;
+        }
+
+        let layer = 4 - layer as usize;
+        let mpeg1 = version == 3;
+        let bitrate = if mpeg1 {
+            MPEG1_BITRATES[layer - 1][bitrate_index]
+        } else {
+            MPEG2_BITRATES[usize::from(layer > 1)][bitrate_index]
+        };
+        let sample_rate = SAMPLE_RATES[match version {
+            3 => 0,
+            2 => 1,
+            _ => 2,
+        }][rate_index];
+        let samples = match (layer, mpeg1) {
+            (1, _) => 384,
+            (3, false) => 576,
+            _ => 1152,
+        };
+
+        let bytes_per_second = bitrate as usize * 1000 / 8;
+        let length = if layer == 1 {
+            (12 * bitrate as usize * 1000 / sample_rate as usize + padding) * 4
+        } else {
+            samples as usize * bytes_per_second / sample_rate as usize + padding
+        };
+        Some(FrameHeader {
+            length,
+            samples,
+            sample_rate,
+        })
+    }
+
+    fn seconds(&self) -> f64 {
+        self.samples as f64 / self.sample_rate as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 128 kbit/s 44.1 kHz MPEG-1 layer III frame: 417 bytes, 1152 samples
+    fn frame(fill: u8) -> Vec<u8> {
+        let mut frame = vec![fill; 417];
+        frame[..4].copy_from_slice(&[0xff, 0xfb, 0x90, 0x00]);
+        frame
+    }
+
+    /// Unit test - strip_ads drops the frames inside the ad ranges and keeps the tags
+    #[test]
+    fn test_strip_ads_cuts_mp3_frames() {
+        // Given an MP3 with an ID3v2 tag, an Info frame, 100 audio frames and an ID3v1 tag
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("2024-01-01 - Launch.mp3");
+        let id3v2 = [b"ID3".as_slice(), &[4, 0, 0, 0, 0, 0, 4], b"TAGS"].concat();
+        let mut info = frame(0);
+        info[36..40].copy_from_slice(b"Info");
+        let mut audio = [id3v2.clone(), info].concat();
+        for index in 0..100 {
+            audio.extend(frame(index as u8));
+        }
+        audio.extend([b"TAG".as_slice(), &[0; 125]].concat());
+        fs::write(&path, &audio).unwrap();
+
+        // When we cut out 0.5s to 1.0s
+        let stripped = strip_ads(path.to_str().unwrap(), &[(0.5, 1.0)]).unwrap();
+
+        // Then the 19 frames whose middle falls there are gone, and the tags are kept
+        assert!(stripped.path.ends_with("2024-01-01 - Launch.no-ads.mp3"));
+        let out = fs::read(&stripped.path).unwrap();
+        assert_eq!(out.len(), audio.len() - 20 * 417);
+        assert!(out.starts_with(&id3v2));
+        assert!(out[out.len() - 128..].starts_with(b"TAG"));
+        assert_eq!(out[id3v2.len() + 18 * 417 + 4], 18);
+        assert_eq!(out[id3v2.len() + 19 * 417 + 4], 38);
+        assert!((stripped.removed_seconds - 19.0 * 1152.0 / 44100.0).abs() < 1e-9);
+        assert!((stripped.kept_seconds - 81.0 * 1152.0 / 44100.0).abs() < 1e-9);
+
+        // And other formats and files without frames are refused
+        assert!(strip_ads("episode.m4a", &[]).is_err());
+        let empty = temp_dir.path().join("empty.mp3");
+        fs::write(&empty, b"not audio").unwrap();
+        assert!(strip_ads(empty.to_str().unwrap(), &[]).is_err());
+    }
+}
@@ -0,0 +1,337 @@
+use std::path::Path;
+
+use id3::frame::{Chapter as ChapterFrame, Comment, Picture, PictureType, TableOfContents};
+use id3::{Frame, Tag, TagLike, Timestamp, Version};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::artwork::ArtworkSize;
+use crate::error::{Error, Result};
+use crate::metadata::Metadata;
+
+pub mod mp4;
+
+/// Cover art size embedded in tags: Apple's minimum for podcast artwork, small enough for any player
+pub const TAG_ARTWORK_SIZE: ArtworkSize = ArtworkSize {
+    width: 1400,
+    height: 1400,
+};
+
+/// Genre written when the metadata has none
+const DEFAULT_GENRE: &str = "Podcast";
+
+/// A chapter marker, in seconds from the start of the episode
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Chapter {
+    pub start: f64,
+    pub end: f64,
+    pub title: String,
+}
+
+/// Tag container a file was written with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagFormat {
+    /// ID3v2.4, for MP3
+    Id3,
+    /// iTunes-style `ilst` atoms with Nero chapters, for MP4/M4A
+    Mp4,
+}
+
+impl TagFormat {
+    /// Name used in progress messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            TagFormat::Id3 => "ID3v2.4",
+            TagFormat::Mp4 => "MP4",
+        }
+    }
+}
+
+/// What to embed in an audio file
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioTags<'a> {
+    pub metadata: &'a Metadata,
+    /// Cover art as JPEG or PNG bytes
+    pub artwork: Option<Vec<u8>>,
+    pub chapters: Vec<Chapter>,
+}
+
+/// Reads chapter markers from timestamped lines in show notes, such as `00:00 Intro` or `(1:02:03) Q&A`
+///
+/// At least two timestamped lines, in increasing order, count as chapters;
+/// anything else is treated as prose that happens to mention a time. Each
+/// chapter ends where the next starts, and the last at `duration_seconds`
+/// when it is known.
+pub fn chapters_from_description(description: &str, duration_seconds: Option<u64>) -> Vec<Chapter> {
+    let Ok(re) =
+        Regex::new(r"^\s*[\(\[]?((?:\d{1,2}:)?\d{1,2}:\d{2})[\)\]]?\s*(?:[-–—:|]\s*)?(\S.*?)\s*$")
+    else {
+        return Vec::new();
+    };
+
+    let marks: Vec<(f64, String)> = description
+        .lines()
+        .filter_map(|line| {
+            let captures = re.captures(line)?;
+            Some((parse_timestamp(&captures[1])?, captures[2].to_string()))
+        })
+        .collect();
+    let increasing = marks.windows(2).all(|pair| pair[0].0 < pair[1].0);
+    if marks.len() < 2 || !increasing {
+        return Vec::new();
+    }
+
+    let ends: Vec<f64> = marks
+        .iter()
+        .skip(1)
+        .map(|(start, _)| *start)
+        .chain([duration_seconds.map_or(marks[marks.len() - 1].0, |d| d as f64)])
+        .collect();
+    marks
+        .into_iter()
+        .zip(ends)
+        .map(|((start, title), end)| Chapter {
+            start,
+            end: end.max(start),
+            title,
+        })
+        .collect()
+}
+
+/// Seconds in an `[h:]mm:ss` timestamp
+fn parse_timestamp(raw: &str) -> Option<f64> {
+    let parts: Vec<u64> = raw
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    let (hours, minutes, seconds) = match parts.as_slice() {
+        [m, s] => (0, *m, *s),
+        [h, m, s] => (*h, *m, *s),
+        _ => return None,
+    };
+    (seconds < 60 && (parts.len() == 2 || minutes < 60))
+        .then(|| (hours * 3600 + minutes * 60 + seconds) as f64)
+}
+
+/// Writes the episode's metadata, artwork and chapters into an audio file, choosing the format from its extension
+///
+/// Tags the file already has are kept unless these fields replace them;
+/// earlier artwork and chapters are replaced, so tagging twice does not
+/// duplicate them.
+pub fn tag_audio(path: &str, tags: &AudioTags) -> Result<TagFormat> {
+    let extension = Path::new(path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("mp3") => tag_id3(path, tags).map(|_| TagFormat::Id3),
+        Some("m4a" | "m4b" | "mp4") => mp4::tag_mp4(path, tags).map(|_| TagFormat::Mp4),
+        _ => Err(Error::Parse(format!(
+            "Cannot tag {}: expected an .mp3, .m4a, .m4b or .mp4 file",
+            path
+        ))),
+    }
+}
+
+/// Writes an ID3v2.4 tag with chapter (`CHAP`) and table of contents (`CTOC`) frames
+fn tag_id3(path: &str, tags: &AudioTags) -> Result<()> {
+    let metadata = tags.metadata;
+    let mut tag = id3::no_tag_ok(id3::partial_tag_ok(Tag::read_from_path(path)))
+        .map_err(tag_error)?
+        .unwrap_or_default();
+
+    tag.set_title(&metadata.episode_title);
+    tag.set_artist(&metadata.show_title);
+    tag.set_album(&metadata.show_title);
+    tag.set_genre(genre(metadata));
+    if let Some(released) = metadata
+        .publish_date_iso8601
+        .as_deref()
+        .and_then(|date| date.parse::<Timestamp>().ok())
+    {
+        tag.set_date_released(released);
+    }
+    if let Some(number) = metadata.episode_number {
+        tag.set_track(number);
+    }
+    if !metadata.description.is_empty() {
+        tag.remove_comment(Some(""), None);
+        tag.add_frame(Comment {
+            lang: "eng".to_string(),
+            description: String::new(),
+            text: metadata.description.clone(),
+        });
+    }
+
+    if let Some(artwork) = &tags.artwork {
+        tag.remove_picture_by_type(PictureType::CoverFront);
+        tag.add_frame(Picture {
+            mime_type: image_mime_type(artwork).to_string(),
+            picture_type: PictureType::CoverFront,
+            description: String::new(),
+            data: artwork.clone(),
+        });
+    }
+
+    if !tags.chapters.is_empty() {
+        tag.remove("CHAP");
+        tag.remove("CTOC");
+        let mut elements = Vec::new();
+        for (index, chapter) in tags.chapters.iter().enumerate() {
+            let element_id = format!("chp{}", index);
+            tag.add_frame(ChapterFrame {
+                element_id: element_id.clone(),
+                start_time: (chapter.start * 1000.0) as u32,
+                end_time: (chapter.end * 1000.0) as u32,
+                // Byte offsets are unknown, which ID3 spells as all ones
+                start_offset: u32::MAX,
+                end_offset: u32::MAX,
+                frames: vec![Frame::text("TIT2", &chapter.title)],
+            });
+            elements.push(element_id);
+        }
+        tag.add_frame(TableOfContents {
+            element_id: "toc".to_string(),
+            top_level: true,
+            ordered: true,
+            elements,
+            frames: Vec::new(),
+        });
+    }
+
+    tag.write_to_path(path, Version::Id3v24).map_err(tag_error)
+}
+
+/// The first genre, or "Podcast"
+pub(crate) fn genre(metadata: &Metadata) -> &str {
+    metadata
+        .genres
+        .as_ref()
+        .and_then(|genres| genres.first())
+        .map_or(DEFAULT_GENRE, String::as_str)
+}
+
+/// MIME type of JPEG or PNG image bytes
+pub(crate) fn image_mime_type(image: &[u8]) -> &'static str {
+    if image.starts_with(b"\x89PNG") {
+        "image/png"
+    } else {
+        "image/jpeg"
+    }
+}
+
+fn tag_error(e: id3::Error) -> Error {
+    Error::Parse(format!("Failed to tag audio: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn sample_metadata() -> Metadata {
+        Metadata {
+            episode_title: "Episode 12: Money".to_string(),
+            show_title: "Back to the Board".to_string(),
+            description: "We talk shop.".to_string(),
+            publish_date_iso8601: Some("2023-10-13".to_string()),
+            episode_number: Some(12),
+            ..Metadata::default()
+        }
+    }
+
+    /// Unit test - chapters_from_description reads timestamped show notes
+    #[test]
+    fn test_chapters_from_description() {
+        // Given show notes with timestamps in several styles, and prose mentioning one time
+        let notes = "In this episode:\n00:00 Intro\n(05:30) - Budgets\n[1:02:03] Q&A: listener mail\nThanks!";
+        let prose = "We start at 10:00 sharp.";
+
+        // When we read chapters from them
+        let chapters = chapters_from_description(notes, Some(4000));
+
+        // Then each timestamped line is a chapter ending where the next begins
+        assert_eq!(
+            chapters,
+            vec![
+                Chapter {
+                    start: 0.0,
+                    end: 330.0,
+                    title: "Intro".to_string()
+                },
+                Chapter {
+                    start: 330.0,
+                    end: 3723.0,
+                    title: "Budgets".to_string()
+                },
+                Chapter {
+                    start: 3723.0,
+                    end: 4000.0,
+                    title: "Q&A: listener mail".to_string()
+                },
+            ]
+        );
+
+        // And a lone time in prose is not a chapter list
+        assert!(chapters_from_description(prose, None).is_empty());
+    }
+
+    /// Unit test - tag_audio writes ID3 fields, artwork and chapters, and retagging replaces them
+    #[test]
+    fn test_tag_audio_writes_id3() {
+        // Given an MP3 file with no tag
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("episode.mp3");
+        std::fs::write(&path, [0xFF, 0xFB, 0x90, 0x00, 0, 0, 0, 0]).unwrap();
+        let path = path.to_str().unwrap();
+        let metadata = sample_metadata();
+        let tags = AudioTags {
+            metadata: &metadata,
+            artwork: Some(b"\xFF\xD8\xFFjpeg".to_vec()),
+            chapters: chapters_from_description("0:00 Intro\n1:00 Main", Some(120)),
+        };
+
+        // When we tag it twice
+        assert_eq!(tag_audio(path, &tags).unwrap(), TagFormat::Id3);
+        tag_audio(path, &tags).unwrap();
+
+        // Then the tag carries the metadata, one cover and one set of chapters
+        let tag = Tag::read_from_path(path).unwrap();
+        assert_eq!(tag.title(), Some("Episode 12: Money"));
+        assert_eq!(tag.album(), Some("Back to the Board"));
+        assert_eq!(tag.track(), Some(12));
+        assert_eq!(tag.date_released().map(|date| date.year), Some(2023));
+        assert_eq!(tag.comments().next().unwrap().text, "We talk shop.");
+        assert_eq!(tag.pictures().count(), 1);
+        let chapters: Vec<_> = tag.chapters().collect();
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(
+            (chapters[1].start_time, chapters[1].end_time),
+            (60_000, 120_000)
+        );
+        assert_eq!(tag.tables_of_contents().count(), 1);
+
+        // And the audio after the tag is untouched
+        assert!(std::fs::read(path)
+            .unwrap()
+            .ends_with(&[0xFF, 0xFB, 0x90, 0x00, 0, 0, 0, 0]));
+    }
+
+    /// Unit test - tag_audio rejects formats it cannot tag
+    #[test]
+    fn test_tag_audio_rejects_unknown_formats() {
+        // Given an Ogg file
+        let metadata = sample_metadata();
+        let tags = AudioTags {
+            metadata: &metadata,
+            artwork: None,
+            chapters: Vec::new(),
+        };
+
+        // When we tag it
+        // Then the error names the supported extensions
+        assert!(tag_audio("episode.ogg", &tags)
+            .unwrap_err()
+            .to_string()
+            .contains("expected an .mp3"));
+    }
+}
@@ -0,0 +1,448 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::error::{Error, Result};
+use crate::tag::{genre, image_mime_type, AudioTags};
+
+/// Boxes whose bodies are only child boxes, walked to find the chunk offset tables
+const CONTAINERS: [&[u8; 4]; 7] = [
+    b"moov", b"trak", b"mdia", b"minf", b"stbl", b"edts", b"dinf",
+];
+
+/// Largest `moov` box read into memory; real ones are kilobytes to a few megabytes
+const MAX_MOOV_BYTES: u64 = 64 * 1024 * 1024;
+
+/// `ilst` item types written from the metadata, replacing any the file already has
+const WRITTEN_ITEMS: [&[u8; 4]; 10] = [
+    b"\xA9nam", b"\xA9ART", b"\xA9alb", b"\xA9gen", b"\xA9day", b"desc", b"ldes", b"trkn", b"stik",
+    b"covr",
+];
+
+/// iTunes media kind for podcasts
+const STIK_PODCAST: u8 = 21;
+
+/// `data` box type codes
+const DATA_IMPLICIT: u32 = 0;
+const DATA_UTF8: u32 = 1;
+const DATA_JPEG: u32 = 13;
+const DATA_PNG: u32 = 14;
+const DATA_INTEGER: u32 = 21;
+
+/// A box's position in a file or buffer
+#[derive(Debug, Clone, Copy)]
+struct BoxSpan {
+    kind: [u8; 4],
+    offset: u64,
+    header: u64,
+    size: u64,
+}
+
+/// Writes iTunes-style metadata and Nero (`chpl`) chapters into an MP4/M4A file
+///
+/// Only `moov/udta` changes. When `moov` comes before the audio data, the
+/// chunk offsets in `stco`/`co64` are shifted by however much it grew, so the
+/// file still plays. The file is rewritten beside the original and renamed
+/// over it, so an interrupted tag leaves the original intact.
+pub(crate) fn tag_mp4(path: &str, tags: &AudioTags) -> Result<()> {
+    let mut file = File::open(path).map_err(Error::io("Failed to read audio"))?;
+    let length = file
+        .metadata()
+        .map_err(Error::io("Failed to read audio"))?
+        .len();
+    let boxes = top_level_boxes(&mut file, length)?;
+    let moov = boxes
+        .iter()
+        .find(|span| &span.kind == b"moov")
+        .copied()
+        .ok_or_else(|| mp4_error("no moov box"))?;
+    if moov.size > MAX_MOOV_BYTES {
+        return Err(mp4_error("moov box too large"));
+    }
+
+    let mut old = vec![0; moov.size as usize];
+    file.seek(SeekFrom::Start(moov.offset))
+        .and_then(|_| file.read_exact(&mut old))
+        .map_err(Error::io("Failed to read audio"))?;
+    let mut new = retagged_moov(&old[moov.header as usize..], tags)?;
+
+    let audio_after_moov = boxes
+        .iter()
+        .any(|span| &span.kind == b"mdat" && span.offset > moov.offset);
+    if audio_after_moov {
+        let delta = new.len() as i64 - moov.size as i64;
+        shift_chunk_offsets(&mut new[8..], delta)?;
+    }
+
+    let temporary = format!("{}.tagging", path);
+    let written = write_with_moov(&mut file, &temporary, &moov, &new, length)
+        .and_then(|_| fs::rename(&temporary, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    written.map_err(Error::io("Failed to write tagged audio"))
+}
+
+/// Copies the file to `temporary` with `moov` swapped for `new_moov`
+fn write_with_moov(
+    file: &mut File,
+    temporary: &str,
+    moov: &BoxSpan,
+    new_moov: &[u8],
+    length: u64,
+) -> io::Result<()> {
+    let mut out = File::create(temporary)?;
+    file.seek(SeekFrom::Start(0))?;
+    io::copy(&mut file.take(moov.offset), &mut out)?;
+    out.write_all(new_moov)?;
+    let after = moov.offset + moov.size;
+    file.seek(SeekFrom::Start(after))?;
+    io::copy(&mut file.take(length - after), &mut out)?;
+    out.sync_all()
+}
+
+/// The boxes at the top level of the file
+fn top_level_boxes(file: &mut File, length: u64) -> Result<Vec<BoxSpan>> {
+    let mut boxes = Vec::new();
+    let mut offset = 0;
+    while offset + 8 <= length {
+        let mut header = [0; 16];
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut header[..8]))
+            .map_err(Error::io("Failed to read audio"))?;
+        let size = match be_u32(&header[..4]) {
+            1 => {
+                file.read_exact(&mut header[8..])
+                    .map_err(Error::io("Failed to read audio"))?;
+                (be_u64(&header[8..16]), 16)
+            }
+            0 => (length - offset, 8),
+            size => (u64::from(size), 8),
+        };
+        let span = BoxSpan {
+            kind: kind(&header[4..8]),
+            offset,
+            header: size.1,
+            size: size.0,
+        };
+        if span.size < span.header || offset + span.size > length {
+            return Err(mp4_error("box size runs past the end of the file"));
+        }
+        boxes.push(span);
+        offset += span.size;
+    }
+    Ok(boxes)
+}
+
+/// The child boxes in a buffer of boxes
+fn child_boxes(bytes: &[u8]) -> Result<Vec<BoxSpan>> {
+    let mut boxes = Vec::new();
+    let mut offset = 0;
+    while offset + 8 <= bytes.len() {
+        let (size, header) = match be_u32(&bytes[offset..offset + 4]) {
+            1 if offset + 16 <= bytes.len() => (be_u64(&bytes[offset + 8..offset + 16]), 16),
+            1 => return Err(mp4_error("truncated box header")),
+            0 => ((bytes.len() - offset) as u64, 8),
+            size => (u64::from(size), 8),
+        };
+        if size < header || offset as u64 + size > bytes.len() as u64 {
+            return Err(mp4_error("box size runs past its parent"));
+        }
+        boxes.push(BoxSpan {
+            kind: kind(&bytes[offset + 4..offset + 8]),
+            offset: offset as u64,
+            header,
+            size,
+        });
+        offset += size as usize;
+    }
+    Ok(boxes)
+}
+
+/// A new `moov` box from the old one's body, with tags written into its `udta`
+fn retagged_moov(body: &[u8], tags: &AudioTags) -> Result<Vec<u8>> {
+    let mut children = Vec::new();
+    let mut udta_children = Vec::new();
+    let mut old_ilst = None;
+    for child in child_boxes(body)? {
+        let bytes = slice(body, &child);
+        if &child.kind != b"udta" {
+            children.extend_from_slice(bytes);
+            continue;
+        }
+        let udta = &bytes[child.header as usize..];
+        for entry in child_boxes(udta)? {
+            match &entry.kind {
+                b"meta" => old_ilst = ilst_body(slice(udta, &entry))?,
+                b"chpl" if !tags.chapters.is_empty() => {}
+                _ => udta_children.extend_from_slice(slice(udta, &entry)),
+            }
+        }
+    }
+
+    udta_children.extend(meta_box(tags, old_ilst.as_deref().unwrap_or_default())?);
+    if !tags.chapters.is_empty() {
+        udta_children.extend(chpl_box(tags));
+    }
+    children.extend(mp4_box(b"udta", &udta_children));
+    Ok(mp4_box(b"moov", &children))
+}
+
+/// The body of the `ilst` inside a `meta` box, if it has one
+fn ilst_body(meta: &[u8]) -> Result<Option<Vec<u8>>> {
+    // `meta` is a full box: four bytes of version and flags precede its children
+    let Some(children) = meta.get(12..) else {
+        return Ok(None);
+    };
+    Ok(child_boxes(children)?
+        .into_iter()
+        .find(|child| &child.kind == b"ilst")
+        .map(|ilst| slice(children, &ilst)[ilst.header as usize..].to_vec()))
+}
+
+/// A `meta` box with an `mdir` handler and an `ilst` of the tags, keeping old items this does not write
+fn meta_box(tags: &AudioTags, old_ilst: &[u8]) -> Result<Vec<u8>> {
+    let metadata = tags.metadata;
+    let mut items = Vec::new();
+    for item in child_boxes(old_ilst)? {
+        if !WRITTEN_ITEMS.contains(&&item.kind) {
+            items.extend_from_slice(slice(old_ilst, &item));
+        }
+    }
+
+    let mut text = |kind: &[u8; 4], value: &str| {
+        if !value.is_empty() {
+            items.extend(mp4_box(kind, &data_box(DATA_UTF8, value.as_bytes())));
+        }
+    };
+    text(b"\xA9nam", &metadata.episode_title);
+    text(b"\xA9ART", &metadata.show_title);
+    text(b"\xA9alb", &metadata.show_title);
+    text(b"\xA9gen", genre(metadata));
+    text(
+        b"\xA9day",
+        metadata.publish_date_iso8601.as_deref().unwrap_or_default(),
+    );
+    text(b"desc", &metadata.description);
+    text(b"ldes", &metadata.description);
+
+    if let Some(number) = metadata.episode_number {
+        let track = u16::try_from(number).unwrap_or(u16::MAX).to_be_bytes();
+        let value = [0, 0, track[0], track[1], 0, 0, 0, 0];
+        items.extend(mp4_box(b"trkn", &data_box(DATA_IMPLICIT, &value)));
+    }
+    items.extend(mp4_box(b"stik", &data_box(DATA_INTEGER, &[STIK_PODCAST])));
+    if let Some(artwork) = &tags.artwork {
+        let code = match image_mime_type(artwork) {
+            "image/png" => DATA_PNG,
+            _ => DATA_JPEG,
+        };
+        items.extend(mp4_box(b"covr", &data_box(code, artwork)));
+    }
+
+    let mut handler = vec![0; 8];
+    handler.extend_from_slice(b"mdirappl");
+    handler.extend_from_slice(&[0; 9]);
+
+    let mut body = vec![0; 4];
+    body.extend(mp4_box(b"hdlr", &handler));
+    body.extend(mp4_box(b"ilst", &items));
+    Ok(mp4_box(b"meta", &body))
+}
+
+/// A Nero chapter list: start times in 100 ns units and titles of up to 255 bytes
+fn chpl_box(tags: &AudioTags) -> Vec<u8> {
+    let chapters = &tags.chapters[..tags.chapters.len().min(255)];
+    // Version 1, no flags, four reserved bytes, then the chapter count
+    let mut body = vec![1, 0, 0, 0, 0, 0, 0, 0, chapters.len() as u8];
+    for chapter in chapters {
+        body.extend_from_slice(&((chapter.start * 10_000_000.0) as u64).to_be_bytes());
+        let mut end = chapter.title.len().min(255);
+        while !chapter.title.is_char_boundary(end) {
+            end -= 1;
+        }
+        body.push(end as u8);
+        body.extend_from_slice(&chapter.title.as_bytes()[..end]);
+    }
+    mp4_box(b"chpl", &body)
+}
+
+/// Adds `delta` to every chunk offset in the `stco` and `co64` boxes under `bytes`
+fn shift_chunk_offsets(bytes: &mut [u8], delta: i64) -> Result<()> {
+    for child in child_boxes(bytes)? {
+        let start = (child.offset + child.header) as usize;
+        let body = &mut bytes[start..(child.offset + child.size) as usize];
+        match &child.kind {
+            kind if CONTAINERS.contains(&kind) => shift_chunk_offsets(body, delta)?,
+            b"stco" => shift_entries(body, 4, delta)?,
+            b"co64" => shift_entries(body, 8, delta)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Shifts a chunk offset table of `width`-byte entries, after its version, flags and count
+fn shift_entries(body: &mut [u8], width: usize, delta: i64) -> Result<()> {
+    let count = body.get(4..8).map_or(0, be_u32) as usize;
+    let entries = body
+        .get_mut(8..8 + count * width)
+        .ok_or_else(|| mp4_error("truncated chunk offset table"))?;
+    for entry in entries.chunks_exact_mut(width) {
+        let shifted = if width == 4 {
+            i64::from(be_u32(entry)) + delta
+        } else {
+            be_u64(entry) as i64 + delta
+        };
+        match width {
+            4 => entry.copy_from_slice(
+                &u32::try_from(shifted)
+                    .map_err(|_| mp4_error("chunk offset out of range"))?
+                    .to_be_bytes(),
+            ),
+            _ => entry.copy_from_slice(&(shifted as u64).to_be_bytes()),
+        }
+    }
+    Ok(())
+}
+
+/// A `data` box: type code, empty locale, value
+fn data_box(code: u32, value: &[u8]) -> Vec<u8> {
+    let mut body = code.to_be_bytes().to_vec();
+    body.extend_from_slice(&[0; 4]);
+    body.extend_from_slice(value);
+    mp4_box(b"data", &body)
+}
+
+/// A box with a 32-bit size
+fn mp4_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut bytes = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(kind);
+    bytes.extend_from_slice(body);
+    bytes
+}
+
+fn slice<'a>(bytes: &'a [u8], span: &BoxSpan) -> &'a [u8] {
+    &bytes[span.offset as usize..(span.offset + span.size) as usize]
+}
+
+fn kind(bytes: &[u8]) -> [u8; 4] {
+    [bytes[0], bytes[1], bytes[2], bytes[3]]
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes(kind(bytes))
+}
+
+fn be_u64(bytes: &[u8]) -> u64 {
+    let mut value = [0; 8];
+    value.copy_from_slice(&bytes[..8]);
+    u64::from_be_bytes(value)
+}
+
+fn mp4_error(reason: &str) -> Error {
+    Error::Parse(format!(
+        "Failed to tag audio: not a usable MP4 file ({})",
+        reason
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Metadata;
+    use crate::tag::{tag_audio, Chapter, TagFormat};
+    use tempfile::TempDir;
+
+    /// A minimal M4A: `ftyp`, a `moov` with one chunk offset and an encoder tag, then `mdat`
+    fn sample_m4a() -> Vec<u8> {
+        let ftyp = mp4_box(b"ftyp", b"M4A \0\0\0\0M4A mp42");
+        let encoder = mp4_box(b"\xA9too", &data_box(DATA_UTF8, b"encoder 1.0"));
+        let mut meta = vec![0; 4];
+        meta.extend(mp4_box(b"ilst", &encoder));
+        let udta = mp4_box(b"udta", &mp4_box(b"meta", &meta));
+
+        let stco = |offset: u32| {
+            let mut body = vec![0, 0, 0, 0, 0, 0, 0, 1];
+            body.extend_from_slice(&offset.to_be_bytes());
+            let stbl = mp4_box(b"stbl", &mp4_box(b"stco", &body));
+            let trak = mp4_box(b"trak", &mp4_box(b"mdia", &mp4_box(b"minf", &stbl)));
+            mp4_box(b"moov", &[trak, udta.clone()].concat())
+        };
+        let moov_len = stco(0).len();
+        let audio_offset = (ftyp.len() + moov_len + 8) as u32;
+        [ftyp, stco(audio_offset), mp4_box(b"mdat", b"AUDIO")].concat()
+    }
+
+    /// The first chunk offset in a file's `stco`
+    fn first_chunk_offset(file: &[u8]) -> usize {
+        let at = file.windows(4).position(|w| w == b"stco").unwrap();
+        be_u32(&file[at + 12..at + 16]) as usize
+    }
+
+    /// Unit test - tag_audio writes MP4 atoms and keeps the audio playable
+    #[test]
+    fn test_tag_audio_writes_mp4_atoms() {
+        // Given an M4A whose moov precedes its audio
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("episode.m4a");
+        fs::write(&path, sample_m4a()).unwrap();
+        let path = path.to_str().unwrap();
+        let metadata = Metadata {
+            episode_title: "Episode 12: Money".to_string(),
+            show_title: "Back to the Board".to_string(),
+            episode_number: Some(12),
+            ..Metadata::default()
+        };
+        let tags = AudioTags {
+            metadata: &metadata,
+            artwork: Some(b"\x89PNGimage".to_vec()),
+            chapters: vec![Chapter {
+                start: 1.5,
+                end: 3.0,
+                title: "Intro".to_string(),
+            }],
+        };
+
+        // When we tag it twice
+        assert_eq!(tag_audio(path, &tags).unwrap(), TagFormat::Mp4);
+        tag_audio(path, &tags).unwrap();
+
+        // Then the chunk offset still points at the audio
+        let file = fs::read(path).unwrap();
+        let offset = first_chunk_offset(&file);
+        assert_eq!(&file[offset..offset + 5], b"AUDIO");
+
+        // And the tags appear once each, next to the encoder tag the file already had
+        let count = |needle: &[u8]| file.windows(needle.len()).filter(|w| *w == needle).count();
+        assert_eq!(count(b"Episode 12: Money"), 1);
+        assert_eq!(count(b"ilst"), 1);
+        assert_eq!(count(b"encoder 1.0"), 1);
+        assert_eq!(count(b"\x89PNGimage"), 1);
+        let chpl = file.windows(4).position(|w| w == b"chpl").unwrap();
+        assert_eq!(file[chpl + 12], 1);
+        assert_eq!(be_u64(&file[chpl + 13..chpl + 21]), 15_000_000);
+        assert!(!temp_dir.path().join("episode.m4a.tagging").exists());
+    }
+
+    /// Unit test - tag_audio rejects MP4 files without a moov box
+    #[test]
+    fn test_tag_audio_rejects_mp4_without_moov() {
+        // Given an M4A with only audio data
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("episode.m4a");
+        fs::write(&path, mp4_box(b"mdat", b"AUDIO")).unwrap();
+        let metadata = Metadata::default();
+        let tags = AudioTags {
+            metadata: &metadata,
+            artwork: None,
+            chapters: Vec::new(),
+        };
+
+        // When we tag it
+        let result = tag_audio(path.to_str().unwrap(), &tags);
+
+        // Then it fails and the file is untouched
+        assert!(result.unwrap_err().to_string().contains("no moov box"));
+        assert_eq!(fs::read(&path).unwrap(), mp4_box(b"mdat", b"AUDIO"));
+    }
+}
@@ -0,0 +1,540 @@
+pub mod convert;
+pub mod segments;
+pub mod stitch;
+pub mod stream;
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::batch::run_concurrently;
+use crate::compress::read_artifact;
+use crate::error::{Error, Result};
+use crate::fetch::{get_text, HttpOptions};
+use crate::locale::{detect_language, parse_language};
+use crate::server_data::serialized_server_data;
+use convert::{convert, TranscriptFormat};
+use segments::{merge_cues, TranscriptSegment};
+use stitch::{stitch_ttml, TtmlPart};
+
+/// Transcript parts downloaded at once for a segmented transcript
+const PART_CONCURRENCY: usize = 4;
+
+/// A timed cue parsed from a TTML transcript
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct TranscriptCue {
+    pub begin: f64,
+    pub end: f64,
+    pub speaker: Option<String>,
+    pub text: String,
+}
+
+/// Formats an offset into an episode as `M:SS`, or `H:MM:SS` from an hour on
+pub fn format_offset(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+/// A downloaded TTML transcript and the URL it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transcript {
+    pub url: String,
+    pub ttml: String,
+}
+
+impl Transcript {
+    /// Reads a TTML transcript saved on disk, plain or compressed; its `url` is the path
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Transcript> {
+        let path = path.as_ref();
+        let ttml = read_artifact(path).map_err(Error::io("Failed to read transcript file"))?;
+        Ok(Transcript {
+            url: path.display().to_string(),
+            ttml,
+        })
+    }
+
+    /// Parses the transcript into timed cues
+    pub fn cues(&self) -> Result<Vec<TranscriptCue>> {
+        parse_ttml(&self.ttml)
+    }
+
+    /// Converts the transcript to another format
+    pub fn convert(&self, format: TranscriptFormat) -> Result<String> {
+        convert(&self.cues()?, format)
+    }
+
+    /// Merges the transcript's cues into speaker-attributed paragraphs
+    pub fn segments(&self) -> Result<Vec<TranscriptSegment>> {
+        Ok(merge_cues(&self.cues()?))
+    }
+
+    /// The transcript's language: its declared `xml:lang`, else one detected from its text
+    pub fn language(&self) -> Result<Option<String>> {
+        let document = roxmltree::Document::parse(&self.ttml)?;
+        let declared = document
+            .root_element()
+            .attribute((roxmltree::NS_XML_URI, "lang"))
+            .and_then(|lang| parse_language(lang).ok());
+        if declared.is_some() {
+            return Ok(declared);
+        }
+
+        let text: Vec<String> = self.cues()?.into_iter().map(|cue| cue.text).collect();
+        Ok(detect_language(&text.join(" ")).map(str::to_string))
+    }
+}
+
+/// Searches for a transcript URL in the episode HTML
+pub fn find_transcript_url(html: &str) -> Result<Option<String>> {
+    Ok(serialized_server_data(html)?.and_then(|json_value| find_closed_captions_url(&json_value)))
+}
+
+/// One TTML file of a transcript the page serves in segments
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptPart {
+    pub url: String,
+    /// Where the part starts in the episode, in seconds, when the page says
+    pub offset: Option<f64>,
+}
+
+/// Searches for the transcript's parts in the episode HTML
+///
+/// A transcript served whole is a single part.
+pub fn find_transcript_parts(html: &str) -> Result<Vec<TranscriptPart>> {
+    Ok(serialized_server_data(html)?
+        .and_then(|json_value| find_closed_captions_parts(&json_value))
+        .unwrap_or_default())
+}
+
+/// The parts listed under a `closedCaptions.segments` array
+///
+/// Entries are URL strings, or objects with a `url` and an optional
+/// `startTime` or `offset` in seconds or as a TTML time expression.
+fn caption_segments(cc: &serde_json::Value) -> Vec<TranscriptPart> {
+    let segments = cc.get("segments").and_then(|segments| segments.as_array());
+    segments
+        .into_iter()
+        .flatten()
+        .filter_map(|segment| {
+            if let Some(url) = segment.as_str() {
+                return Some(TranscriptPart {
+                    url: url.to_string(),
+                    offset: None,
+                });
+            }
+            let url = segment.get("url")?.as_str()?.to_string();
+            let offset = ["startTime", "offset"]
+                .iter()
+                .filter_map(|key| segment.get(*key))
+                .find_map(|value| {
+                    value
+                        .as_f64()
+                        .or_else(|| value.as_str().and_then(parse_ttml_time))
+                });
+            Some(TranscriptPart { url, offset })
+        })
+        .collect()
+}
+
+/// Recursively searches serialized data for a `closedCaptions` URL or segment list
+fn find_closed_captions_parts(value: &serde_json::Value) -> Option<Vec<TranscriptPart>> {
+    match value {
+        serde_json::Value::Object(map) => {
+            if let Some(cc) = map.get("closedCaptions") {
+                let segments = caption_segments(cc);
+                if segments.len() > 1 {
+                    return Some(segments);
+                }
+                if let Some(url) = cc.get("url").and_then(|url| url.as_str()) {
+                    return Some(vec![TranscriptPart {
+                        url: url.to_string(),
+                        offset: None,
+                    }]);
+                }
+                if !segments.is_empty() {
+                    return Some(segments);
+                }
+            }
+            map.values().find_map(find_closed_captions_parts)
+        }
+        serde_json::Value::Array(arr) => arr.iter().find_map(find_closed_captions_parts),
+        _ => None,
+    }
+}
+
+/// Recursively searches serialized data for a `closedCaptions.url`
+///
+/// A segmented transcript without a `url` gives its first segment's.
+pub fn find_closed_captions_url(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Object(map) => {
+            // Check if this object has closedCaptions.url
+            if let Some(cc) = map.get("closedCaptions") {
+                if let Some(url) = cc.get("url") {
+                    if let Some(url_str) = url.as_str() {
+                        return Some(url_str.to_string());
+                    }
+                }
+                if let Some(part) = caption_segments(cc).into_iter().next() {
+                    return Some(part.url);
+                }
+            }
+            // Recursively search in all values
+            for val in map.values() {
+                if let Some(url) = find_closed_captions_url(val) {
+                    return Some(url);
+                }
+            }
+            None
+        }
+        serde_json::Value::Array(arr) => {
+            // Search in array elements
+            for val in arr {
+                if let Some(url) = find_closed_captions_url(val) {
+                    return Some(url);
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Downloads a transcript and returns its TTML content
+pub async fn download_transcript(url: &str, options: &HttpOptions) -> Result<String> {
+    get_text(url, options).await
+}
+
+/// Downloads a transcript's parts concurrently and stitches them into one TTML document
+///
+/// A single part is returned as downloaded.
+pub async fn download_transcript_parts(
+    parts: &[TranscriptPart],
+    options: &HttpOptions,
+) -> Result<String> {
+    if let [part] = parts {
+        return download_transcript(&part.url, options).await;
+    }
+    let downloads = run_concurrently(parts.to_vec(), PART_CONCURRENCY, |part| async move {
+        let ttml = download_transcript(&part.url, options).await?;
+        Ok::<_, Error>(TtmlPart {
+            ttml,
+            offset: part.offset,
+        })
+    })
+    .await;
+    stitch_ttml(&downloads.into_iter().collect::<Result<Vec<_>>>()?)
+}
+
+/// Parses a TTML time expression (`12.5`, `12.5s`, `1500ms`, `00:01:02.500`) into seconds
+pub fn parse_ttml_time(value: &str) -> Option<f64> {
+    let value = value.trim();
+
+    if value.contains(':') {
+        let mut seconds = 0.0;
+        for part in value.split(':') {
+            seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+        }
+        return Some(seconds);
+    }
+
+    if let Some(ms) = value.strip_suffix("ms") {
+        return ms.parse::<f64>().ok().map(|ms| ms / 1000.0);
+    }
+
+    value.strip_suffix('s').unwrap_or(value).parse().ok()
+}
+
+/// Parses TTML content into timed cues, one per `<p>` element
+pub fn parse_ttml(ttml: &str) -> Result<Vec<TranscriptCue>> {
+    let document = roxmltree::Document::parse(ttml)?;
+
+    let mut cues = Vec::new();
+    for paragraph in document.descendants().filter(|node| node.has_tag_name("p")) {
+        // Apple sometimes only times the word spans, so fall back to those
+        let timed: Vec<_> = paragraph
+            .descendants()
+            .filter(|node| node.attribute("begin").is_some())
+            .collect();
+
+        let begin = timed
+            .first()
+            .and_then(|node| node.attribute("begin"))
+            .and_then(parse_ttml_time);
+        let end = paragraph
+            .attribute("end")
+            .or_else(|| timed.last().and_then(|node| node.attribute("end")))
+            .and_then(parse_ttml_time);
+
+        let (Some(begin), Some(end)) = (begin, end) else {
+            continue;
+        };
+
+        let speaker = paragraph
+            .attributes()
+            .find(|attr| attr.name() == "agent")
+            .map(|attr| attr.value().to_string());
+
+        let text = paragraph
+            .descendants()
+            .filter(|node| node.is_text())
+            .filter_map(|node| node.text())
+            .collect::<Vec<&str>>()
+            .join(" ");
+
+        cues.push(TranscriptCue {
+            begin,
+            end,
+            speaker,
+            text: text.split_whitespace().collect::<Vec<&str>>().join(" "),
+        });
+    }
+
+    Ok(cues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - find_transcript_url returns None when transcript not available
+    #[test]
+    fn test_find_transcript_url_returns_none_when_not_available() {
+        // Given HTML without transcript data
+        let html = r#"
+            <script type="application/json" id="serialized-server-data">
+            [{"data": {"episode": {"title": "Test"}}}]
+            </script>
+        "#;
+
+        // When we search for a transcript URL
+        let result = find_transcript_url(html);
+
+        // Then it should return None
+        assert!(result.is_ok(), "Should not error when no transcript found");
+        assert!(
+            result.unwrap().is_none(),
+            "Should return None when no transcript"
+        );
+    }
+
+    /// Unit test - find_transcript_url extracts valid ttml URL
+    #[test]
+    fn test_find_transcript_url_extracts_valid_url() {
+        // Given HTML with transcript URL in serialized data (nested structure)
+        let html = r#"<html><body><script type="application/json" id="serialized-server-data">[{"data":{"shelves":[{"items":[{"contextAction":{"episodeOffer":{"closedCaptions":{"url":"https://example.com/transcript.ttml"}}}}]}]}}]</script></body></html>"#;
+
+        // When we extract the transcript URL
+        let result = find_transcript_url(html);
+
+        // Then it should return the URL
+        assert!(result.is_ok(), "Should successfully extract URL");
+        let url = result.unwrap();
+        assert!(url.is_some(), "Should find transcript URL");
+        assert_eq!(url.unwrap(), "https://example.com/transcript.ttml");
+    }
+
+    /// Unit test - download_transcript returns the content
+    #[tokio::test]
+    async fn test_download_transcript_returns_content() {
+        // Given a local server with a transcript
+        let base = serve(&[("/t.ttml", "<tt><body/></tt>")]);
+
+        // When we download it
+        let result =
+            download_transcript(&format!("{}/t.ttml", base), &HttpOptions::default()).await;
+
+        // Then it succeeds with content
+        assert!(result.is_ok(), "download_transcript should succeed");
+        assert!(
+            !result.unwrap().is_empty(),
+            "Transcript should not be empty"
+        );
+    }
+
+    /// Unit test - download_transcript handles HTTP errors
+    #[tokio::test]
+    async fn test_download_transcript_handles_http_errors() {
+        // Given a local server without the requested transcript
+        let base = serve(&[]);
+
+        // When we try to download from it
+        let result =
+            download_transcript(&format!("{}/missing.ttml", base), &HttpOptions::default()).await;
+
+        // Then it should fail with an error
+        assert!(result.is_err(), "Should fail for HTTP error codes");
+        let error = result.unwrap_err().to_string();
+        assert!(
+            error.contains("404") || error.contains("failed"),
+            "Error should mention HTTP failure"
+        );
+    }
+
+    /// Serves each `(path, body)` as a 200 and anything else as a 404
+    fn serve(routes: &'static [(&'static str, &'static str)]) -> String {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = String::new();
+                let mut line = String::new();
+                let mut reader = BufReader::new(&stream);
+                reader.read_line(&mut request).unwrap();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let path = request.split_whitespace().nth(1).unwrap_or_default();
+                let response = match routes.iter().find(|(route, _)| *route == path) {
+                    Some((_, body)) => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    ),
+                    None => {
+                        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_string()
+                    }
+                };
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        base
+    }
+
+    /// Unit test - download_transcript_parts stitches the parts it downloads in order
+    #[tokio::test]
+    async fn test_download_transcript_parts_stitches_parts() {
+        // Given a server with two transcript parts that each start at zero
+        let base = serve(&[
+            (
+                "/1.ttml",
+                r#"<tt><body><p begin="0s" end="4s">One</p></body></tt>"#,
+            ),
+            (
+                "/2.ttml",
+                r#"<tt><body><p begin="0s" end="3s">Two</p></body></tt>"#,
+            ),
+        ]);
+        let parts: Vec<TranscriptPart> = ["1", "2"]
+            .iter()
+            .map(|name| TranscriptPart {
+                url: format!("{}/{}.ttml", base, name),
+                offset: None,
+            })
+            .collect();
+
+        // When we download them
+        let ttml = download_transcript_parts(&parts, &HttpOptions::default())
+            .await
+            .unwrap();
+
+        // Then the second part follows the first
+        let cues = parse_ttml(&ttml).unwrap();
+        let times: Vec<(f64, f64)> = cues.iter().map(|cue| (cue.begin, cue.end)).collect();
+        assert_eq!(times, vec![(0.0, 4.0), (4.0, 7.0)]);
+    }
+
+    /// Unit test - find_transcript_parts reads a segmented transcript's parts
+    #[test]
+    fn test_find_transcript_parts_detects_segments() {
+        // Given pages with a segmented transcript and with a whole one
+        let segmented = r#"<script type="application/json" id="serialized-server-data">[{"data":{"episodeOffer":{"closedCaptions":{"segments":[{"url":"https://example.com/1.ttml","startTime":0},{"url":"https://example.com/2.ttml","startTime":"00:30:00"},"https://example.com/3.ttml"]}}}}]</script>"#;
+        let whole = r#"<script type="application/json" id="serialized-server-data">[{"closedCaptions":{"url":"https://example.com/t.ttml"}}]</script>"#;
+
+        // When we search for their parts
+        let parts = find_transcript_parts(segmented).unwrap();
+
+        // Then each segment is a part with its offset, and the whole transcript is one part
+        let offsets: Vec<Option<f64>> = parts.iter().map(|part| part.offset).collect();
+        assert_eq!(offsets, vec![Some(0.0), Some(1800.0), None]);
+        assert_eq!(parts[2].url, "https://example.com/3.ttml");
+        assert_eq!(
+            find_transcript_parts(whole).unwrap(),
+            vec![TranscriptPart {
+                url: "https://example.com/t.ttml".to_string(),
+                offset: None,
+            }]
+        );
+        assert_eq!(
+            find_transcript_url(segmented).unwrap().as_deref(),
+            Some("https://example.com/1.ttml")
+        );
+    }
+
+    /// Unit test - parse_ttml_time understands offset and clock formats
+    #[test]
+    fn test_parse_ttml_time_handles_offset_and_clock_formats() {
+        // Given the time expressions Apple and other TTML producers use
+        // When we parse them
+        // Then they are converted to seconds
+        assert_eq!(parse_ttml_time("12.5"), Some(12.5));
+        assert_eq!(parse_ttml_time("12.5s"), Some(12.5));
+        assert_eq!(parse_ttml_time("1500ms"), Some(1.5));
+        assert_eq!(parse_ttml_time("00:01:02.500"), Some(62.5));
+        assert_eq!(parse_ttml_time("not-a-time"), None);
+    }
+
+    /// Unit test - parse_ttml extracts timed cues with speakers
+    #[test]
+    fn test_parse_ttml_extracts_cues_with_speakers() {
+        // Given a TTML document with word-level spans
+        let ttml = r#"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttm="http://www.w3.org/ns/ttml#metadata">
+            <body><div>
+                <p begin="0.5" end="2.0" ttm:agent="SPEAKER_1"><span begin="0.5" end="1.0">Hello</span> <span begin="1.0" end="2.0">there</span></p>
+                <p ttm:agent="SPEAKER_2"><span begin="2.5" end="3.0">Hi</span></p>
+            </div></body>
+        </tt>"#;
+
+        // When we parse it
+        let cues = parse_ttml(ttml).unwrap();
+
+        // Then each paragraph becomes a cue with its timing, speaker and text
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].begin, 0.5);
+        assert_eq!(cues[0].end, 2.0);
+        assert_eq!(cues[0].speaker.as_deref(), Some("SPEAKER_1"));
+        assert_eq!(cues[0].text, "Hello there");
+
+        // And untimed paragraphs inherit timing from their word spans
+        assert_eq!(cues[1].begin, 2.5);
+        assert_eq!(cues[1].end, 3.0);
+    }
+
+    /// Unit test - Transcript::language prefers the declared language and otherwise detects it
+    #[test]
+    fn test_transcript_language_declared_or_detected() {
+        // Given one transcript declaring its language and one that does not
+        let body = r#"<body><div>
+                <p begin="0" end="4">Wir sprechen heute über die Zukunft und das ist nicht einfach.</p>
+            </div></body>"#;
+        let declared = Transcript {
+            url: String::new(),
+            ttml: format!(
+                r#"<tt xmlns="http://www.w3.org/ns/ttml" xml:lang="de-de">{}</tt>"#,
+                body
+            ),
+        };
+        let undeclared = Transcript {
+            url: String::new(),
+            ttml: format!(r#"<tt xmlns="http://www.w3.org/ns/ttml">{}</tt>"#, body),
+        };
+
+        // When we ask for their languages
+        // Then the declaration is used as-is and the text gives the other away
+        assert_eq!(declared.language().unwrap().as_deref(), Some("de-DE"));
+        assert_eq!(undeclared.language().unwrap().as_deref(), Some("de"));
+    }
+}
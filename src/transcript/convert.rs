@@ -0,0 +1,552 @@
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::output::to_sorted_json;
+use crate::transcript::TranscriptCue;
+
+/// Default longest line of the accessible transcript, the 80 characters WCAG 1.4.8 allows
+pub const DEFAULT_A11Y_LINE_LENGTH: usize = 80;
+
+/// Shortest `--a11y-line-length` accepted; narrower lines split most sentences mid-phrase
+const MIN_A11Y_LINE_LENGTH: usize = 20;
+
+/// Output formats a TTML transcript can be converted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptFormat {
+    Srt,
+    Vtt,
+    Txt,
+    Md,
+    Json,
+    /// The Podcasting 2.0 `podcast:transcript` JSON format, ready to publish in a feed
+    PodcastJson,
+    /// An accessible HTML fragment to paste into an episode web page
+    Html,
+    /// Plain text for publishing: spoken timestamps, speaker-first labels, wrapped lines
+    A11y {
+        max_line: usize,
+    },
+}
+
+impl TranscriptFormat {
+    /// File extension used when saving this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            TranscriptFormat::Srt => "srt",
+            TranscriptFormat::Vtt => "vtt",
+            TranscriptFormat::Txt => "txt",
+            TranscriptFormat::Md => "md",
+            TranscriptFormat::Json => "json",
+            TranscriptFormat::PodcastJson => "podcast.json",
+            TranscriptFormat::Html => "html",
+            TranscriptFormat::A11y { .. } => "a11y.txt",
+        }
+    }
+}
+
+/// Parses a `--transcript-format` value
+pub fn parse_format(raw: &str) -> std::result::Result<TranscriptFormat, String> {
+    match raw.trim().to_lowercase().as_str() {
+        "srt" => Ok(TranscriptFormat::Srt),
+        "vtt" | "webvtt" => Ok(TranscriptFormat::Vtt),
+        "txt" | "text" => Ok(TranscriptFormat::Txt),
+        "md" | "markdown" => Ok(TranscriptFormat::Md),
+        "json" => Ok(TranscriptFormat::Json),
+        "podcast-json" | "podcast" | "podlove" => Ok(TranscriptFormat::PodcastJson),
+        "html" | "htm" => Ok(TranscriptFormat::Html),
+        "a11y" | "accessible" => Ok(TranscriptFormat::A11y {
+            max_line: DEFAULT_A11Y_LINE_LENGTH,
+        }),
+        _ => Err(format!(
+            "Invalid transcript format '{}': expected srt, vtt, txt, md, json, podcast-json, html or a11y",
+            raw
+        )),
+    }
+}
+
+/// Parses an `--a11y-line-length` value
+pub fn parse_line_length(raw: &str) -> std::result::Result<usize, String> {
+    match raw.trim().parse::<usize>() {
+        Ok(length) if length >= MIN_A11Y_LINE_LENGTH => Ok(length),
+        _ => Err(format!(
+            "Invalid line length '{}': expected a number of characters, at least {}",
+            raw, MIN_A11Y_LINE_LENGTH
+        )),
+    }
+}
+
+/// Converts parsed cues to the requested format
+pub fn convert(cues: &[TranscriptCue], format: TranscriptFormat) -> Result<String> {
+    Ok(match format {
+        TranscriptFormat::Srt => to_srt(cues),
+        TranscriptFormat::Vtt => to_vtt(cues),
+        TranscriptFormat::Txt => to_txt(cues),
+        TranscriptFormat::Md => to_md(cues),
+        TranscriptFormat::Json => to_sorted_json(cues)?,
+        TranscriptFormat::PodcastJson => to_sorted_json(&to_podcast_json(cues))?,
+        TranscriptFormat::Html => to_html(cues),
+        TranscriptFormat::A11y { max_line } => to_a11y(cues, max_line),
+    })
+}
+
+/// Version of the Podcasting 2.0 transcript JSON format written
+const PODCAST_JSON_VERSION: &str = "1.0.0";
+
+/// A transcript in the Podcasting 2.0 JSON format
+#[derive(Debug, Serialize)]
+struct PodcastTranscript<'a> {
+    version: &'static str,
+    segments: Vec<PodcastSegment<'a>>,
+}
+
+/// One timed segment of a Podcasting 2.0 transcript
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PodcastSegment<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speaker: Option<&'a str>,
+    start_time: f64,
+    end_time: f64,
+    body: &'a str,
+}
+
+/// Formats seconds as `HH:MM:SS` followed by `separator` and milliseconds
+fn format_timestamp(seconds: f64, separator: char) -> String {
+    let total_ms = (seconds.max(0.0) * 1000.0).round() as u64;
+    let (hours, rest) = (total_ms / 3_600_000, total_ms % 3_600_000);
+    let (minutes, rest) = (rest / 60_000, rest % 60_000);
+    let (secs, ms) = (rest / 1000, rest % 1000);
+
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        hours, minutes, secs, separator, ms
+    )
+}
+
+/// Groups consecutive cues by speaker into turns of (speaker, start, text)
+fn speaker_turns(cues: &[TranscriptCue]) -> Vec<(Option<&str>, f64, String)> {
+    let mut turns: Vec<(Option<&str>, f64, String)> = Vec::new();
+
+    for cue in cues {
+        match turns.last_mut() {
+            Some((speaker, _, text)) if *speaker == cue.speaker.as_deref() => {
+                text.push(' ');
+                text.push_str(&cue.text);
+            }
+            _ => turns.push((cue.speaker.as_deref(), cue.begin, cue.text.clone())),
+        }
+    }
+
+    turns
+}
+
+/// SubRip: numbered cues with `HH:MM:SS,mmm` timestamps and a speaker prefix
+fn to_srt(cues: &[TranscriptCue]) -> String {
+    let mut out = String::new();
+
+    for (index, cue) in cues.iter().enumerate() {
+        out.push_str(&format!(
+            "{}\n{} --> {}\n",
+            index + 1,
+            format_timestamp(cue.begin, ','),
+            format_timestamp(cue.end, ',')
+        ));
+        match &cue.speaker {
+            Some(speaker) => out.push_str(&format!("{}: {}\n\n", speaker, cue.text)),
+            None => out.push_str(&format!("{}\n\n", cue.text)),
+        }
+    }
+
+    out
+}
+
+/// WebVTT: `HH:MM:SS.mmm` timestamps with `<v Speaker>` voice spans
+fn to_vtt(cues: &[TranscriptCue]) -> String {
+    let escape = |text: &str| {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    };
+
+    let mut out = String::from("WEBVTT\n\n");
+
+    for cue in cues {
+        out.push_str(&format!(
+            "{} --> {}\n",
+            format_timestamp(cue.begin, '.'),
+            format_timestamp(cue.end, '.')
+        ));
+        match &cue.speaker {
+            Some(speaker) => {
+                out.push_str(&format!("<v {}>{}\n\n", escape(speaker), escape(&cue.text)))
+            }
+            None => out.push_str(&format!("{}\n\n", escape(&cue.text))),
+        }
+    }
+
+    out
+}
+
+/// Plain text: one paragraph per speaker turn
+fn to_txt(cues: &[TranscriptCue]) -> String {
+    speaker_turns(cues)
+        .into_iter()
+        .map(|(speaker, _, text)| match speaker {
+            Some(speaker) => format!("{}: {}\n", speaker, text),
+            None => format!("{}\n", text),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Markdown: a bold speaker heading with start time above each turn
+fn to_md(cues: &[TranscriptCue]) -> String {
+    speaker_turns(cues)
+        .into_iter()
+        .map(|(speaker, begin, text)| {
+            let time = &format_timestamp(begin, '.')[..8];
+            match speaker {
+                Some(speaker) => format!("**{}** [{}]\n\n{}\n", speaker, time, text),
+                None => format!("[{}]\n\n{}\n", time, text),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Podcasting 2.0 JSON: one segment per cue with `startTime`/`endTime` in seconds
+fn to_podcast_json(cues: &[TranscriptCue]) -> PodcastTranscript<'_> {
+    PodcastTranscript {
+        version: PODCAST_JSON_VERSION,
+        segments: cues
+            .iter()
+            .map(|cue| PodcastSegment {
+                speaker: cue.speaker.as_deref(),
+                start_time: cue.begin,
+                end_time: cue.end,
+                body: &cue.text,
+            })
+            .collect(),
+    }
+}
+
+/// HTML fragment: a skip link, then one `<section>` per speaker turn with a `<time>` start
+///
+/// Each turn has a stable `id` (`t` plus its start second) for deep links,
+/// and the skip link lets keyboard and screen reader users jump past it all.
+fn to_html(cues: &[TranscriptCue]) -> String {
+    let escape = |text: &str| {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+
+    let mut out = String::from(
+        "<a class=\"transcript-skip\" href=\"#transcript-end\">Skip transcript</a>\n\
+         <article class=\"transcript\" aria-label=\"Transcript\">\n",
+    );
+    let mut ids = std::collections::HashSet::new();
+    for (speaker, begin, text) in speaker_turns(cues) {
+        // Turns starting in the same second get a suffix so ids stay unique
+        let second = begin.max(0.0) as u64;
+        let mut id = format!("t{}", second);
+        for n in 2.. {
+            if ids.insert(id.clone()) {
+                break;
+            }
+            id = format!("t{}-{}", second, n);
+        }
+        let time = &format_timestamp(begin, '.')[..8];
+        out.push_str(&format!(
+            "  <section class=\"transcript-turn\" id=\"{}\"",
+            id
+        ));
+        match speaker {
+            Some(speaker) => out.push_str(&format!(
+                " aria-labelledby=\"{id}-speaker\">\n    <h3 class=\"transcript-speaker\" id=\"{id}-speaker\">{}</h3>\n",
+                escape(speaker)
+            )),
+            None => out.push_str(">\n"),
+        }
+        out.push_str(&format!(
+            "    <p><time datetime=\"PT{:.3}S\">{}</time> {}</p>\n  </section>\n",
+            begin.max(0.0),
+            time,
+            escape(&text)
+        ));
+    }
+    out.push_str("</article>\n<span id=\"transcript-end\"></span>\n");
+    out
+}
+
+/// A time in words, as a screen reader should say it: `1 hour, 2 minutes, 3 seconds`
+fn spoken_time(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    let units = [
+        (seconds / 3600, "hour"),
+        (seconds / 60 % 60, "minute"),
+        (seconds % 60, "second"),
+    ];
+    let parts: Vec<String> = units
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, unit)| match count {
+            1 => format!("1 {}", unit),
+            _ => format!("{} {}s", count, unit),
+        })
+        .collect();
+    if parts.is_empty() {
+        "0 seconds".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+/// Drops symbols a screen reader would read aloud or skip awkwardly: music notes, emoji,
+/// bullets, markup brackets and the like. Letters, digits and ordinary punctuation stay.
+fn plain_text(text: &str) -> String {
+    let kept: String = text
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_alphanumeric() => Some(c),
+            '.' | ',' | ';' | ':' | '!' | '?' | '\'' | '"' | '(' | ')' | '-' | '&' | '%' | '/'
+            | '$' | '€' | '£' | '@' | '+' | '’' | '‘' | '“' | '”' => Some(c),
+            // Brackets hug the words they enclose, so they go without leaving a gap
+            '<' | '>' | '[' | ']' | '{' | '}' => None,
+            _ => Some(' '),
+        })
+        .collect();
+    kept.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Wraps text at word boundaries into lines of at most `max_line` characters
+///
+/// A single word longer than a line gets a line of its own rather than being split.
+fn wrap(text: &str, max_line: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= max_line => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
+/// Accessible plain text: per speaker turn, a label line naming the speaker first and the
+/// start time in words, then the turn's text wrapped to `max_line` characters
+fn to_a11y(cues: &[TranscriptCue], max_line: usize) -> String {
+    speaker_turns(cues)
+        .into_iter()
+        .map(|(speaker, begin, text)| {
+            let label = match speaker.map(plain_text).filter(|name| !name.is_empty()) {
+                Some(name) => format!("{}, at {}:", name, spoken_time(begin)),
+                None => format!("At {}:", spoken_time(begin)),
+            };
+            let mut lines = wrap(&label, max_line);
+            lines.extend(wrap(&plain_text(&text), max_line));
+            lines.join("\n") + "\n"
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::parse_ttml;
+
+    const FIXTURE: &str = include_str!("../../tests/fixtures/transcript.ttml");
+
+    fn fixture_cues() -> Vec<TranscriptCue> {
+        parse_ttml(FIXTURE).unwrap()
+    }
+
+    /// Unit test - format_timestamp handles hours and rounding
+    #[test]
+    fn test_format_timestamp_handles_hours_and_rounding() {
+        // Given times below and above an hour
+        // When we format them
+        // Then hours, minutes, seconds and milliseconds are zero-padded
+        assert_eq!(format_timestamp(0.5, ','), "00:00:00,500");
+        assert_eq!(format_timestamp(3725.25, '.'), "01:02:05.250");
+        assert_eq!(format_timestamp(7.1249, ','), "00:00:07,125");
+    }
+
+    /// Unit test - SRT output numbers cues and labels speakers
+    #[test]
+    fn test_convert_to_srt() {
+        // Given the fixture transcript
+        // When we convert it to SRT
+        let srt = convert(&fixture_cues(), TranscriptFormat::Srt).unwrap();
+
+        // Then cues are numbered with comma-separated milliseconds
+        assert!(srt.starts_with(
+            "1\n00:00:00,500 --> 00:00:02,750\nSPEAKER_1: Welcome back to the show.\n\n"
+        ));
+        assert!(
+            srt.contains("3\n00:00:05,250 --> 00:00:07,125\nSPEAKER_2: Thanks for having me.\n")
+        );
+        assert!(srt.contains("4\n01:02:03,000 --> 01:02:05,250\n"));
+    }
+
+    /// Unit test - VTT output uses voice spans and escapes markup
+    #[test]
+    fn test_convert_to_vtt() {
+        // Given the fixture transcript
+        // When we convert it to WebVTT
+        let vtt = convert(&fixture_cues(), TranscriptFormat::Vtt).unwrap();
+
+        // Then it has the WebVTT header, dotted timestamps and voice spans
+        assert!(vtt.starts_with("WEBVTT\n\n00:00:00.500 --> 00:00:02.750\n"));
+        assert!(vtt.contains("<v SPEAKER_1>Welcome back to the show.\n"));
+
+        // And text that looks like markup is escaped
+        assert!(vtt.contains("R&amp;D budgets &lt;and more&gt;."));
+    }
+
+    /// Unit test - plain text merges consecutive cues into speaker turns
+    #[test]
+    fn test_convert_to_txt() {
+        // Given the fixture transcript
+        // When we convert it to plain text
+        let txt = convert(&fixture_cues(), TranscriptFormat::Txt).unwrap();
+
+        // Then each speaker turn is a paragraph
+        assert_eq!(
+            txt,
+            "SPEAKER_1: Welcome back to the show. Today we talk about R&D budgets <and more>.\n\n\
+             SPEAKER_2: Thanks for having me.\n\n\
+             SPEAKER_1: See you next week.\n"
+        );
+    }
+
+    /// Unit test - Markdown labels each turn with speaker and start time
+    #[test]
+    fn test_convert_to_md() {
+        // Given the fixture transcript
+        // When we convert it to Markdown
+        let md = convert(&fixture_cues(), TranscriptFormat::Md).unwrap();
+
+        // Then each turn has a bold speaker heading with its start time
+        assert!(md.starts_with("**SPEAKER_1** [00:00:00]\n\nWelcome back"));
+        assert!(md.contains("**SPEAKER_2** [00:00:05]\n\nThanks for having me.\n"));
+        assert!(md.contains("**SPEAKER_1** [01:02:03]\n\nSee you next week.\n"));
+    }
+
+    /// Unit test - JSON output lists every cue
+    #[test]
+    fn test_convert_to_json() {
+        // Given the fixture transcript
+        // When we convert it to JSON
+        let json = convert(&fixture_cues(), TranscriptFormat::Json).unwrap();
+
+        // Then every cue is present with its timing and speaker
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.as_array().unwrap().len(), 4);
+        assert_eq!(parsed[2]["begin"], 5.25);
+        assert_eq!(parsed[2]["speaker"], "SPEAKER_2");
+    }
+
+    /// Unit test - Podcasting 2.0 JSON output lists versioned segments
+    #[test]
+    fn test_convert_to_podcast_json() {
+        // Given the fixture transcript
+        // When we convert it to podcast:transcript JSON
+        let json = convert(&fixture_cues(), TranscriptFormat::PodcastJson).unwrap();
+
+        // Then it has the format version and one camelCase segment per cue
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["version"], "1.0.0");
+        assert_eq!(parsed["segments"].as_array().unwrap().len(), 4);
+        assert_eq!(
+            parsed["segments"][2],
+            serde_json::json!({
+                "speaker": "SPEAKER_2",
+                "startTime": 5.25,
+                "endTime": 7.125,
+                "body": "Thanks for having me."
+            })
+        );
+    }
+
+    /// Unit test - HTML output has a skip link and a labelled section per speaker turn
+    #[test]
+    fn test_convert_to_html() {
+        // Given the fixture transcript
+        // When we convert it to an HTML fragment
+        let html = convert(&fixture_cues(), TranscriptFormat::Html).unwrap();
+
+        // Then it opens with a skip link to the end of the transcript
+        assert!(html.starts_with(
+            "<a class=\"transcript-skip\" href=\"#transcript-end\">Skip transcript</a>\n"
+        ));
+        assert!(html.ends_with("</article>\n<span id=\"transcript-end\"></span>\n"));
+
+        // And each turn is a section with a speaker heading, a machine-readable time and escaped text
+        assert_eq!(html.matches("<section ").count(), 3);
+        assert!(html.contains(
+            "  <section class=\"transcript-turn\" id=\"t5\" aria-labelledby=\"t5-speaker\">\n\
+             \x20   <h3 class=\"transcript-speaker\" id=\"t5-speaker\">SPEAKER_2</h3>\n\
+             \x20   <p><time datetime=\"PT5.250S\">00:00:05</time> Thanks for having me.</p>\n"
+        ));
+        assert!(html.contains("R&amp;D budgets &lt;and more&gt;."));
+        assert!(html.contains("<time datetime=\"PT3723.000S\">01:02:03</time>"));
+    }
+
+    /// Unit test - the accessible transcript labels turns in words and wraps long lines
+    #[test]
+    fn test_convert_to_a11y() {
+        // Given the fixture transcript and a cue with decorative characters
+        let mut cues = fixture_cues();
+        cues.push(TranscriptCue {
+            begin: 3730.0,
+            end: 3732.0,
+            speaker: None,
+            text: "♪ Theme music ♪ ★★★".to_string(),
+        });
+
+        // When we convert it with 40-character lines
+        let text = convert(&cues, TranscriptFormat::A11y { max_line: 40 }).unwrap();
+
+        // Then each turn names its speaker, without underscores, then the start time in words
+        assert!(text.starts_with(
+            "SPEAKER 1, at 0 seconds:\n\
+             Welcome back to the show. Today we talk\n\
+             about R&D budgets and more.\n\n"
+        ));
+        assert!(
+            text.contains("\nSPEAKER 1, at 1 hour, 2 minutes, 3\nseconds:\nSee you next week.\n")
+        );
+
+        // And no line is longer than the limit or keeps decorative characters
+        assert!(text.lines().all(|line| line.chars().count() <= 40));
+        assert!(text.ends_with("\nAt 1 hour, 2 minutes, 10 seconds:\nTheme music\n"));
+        assert_eq!(spoken_time(61.0), "1 minute, 1 second");
+    }
+
+    /// Unit test - parse_format accepts names and common aliases
+    #[test]
+    fn test_parse_format_accepts_aliases() {
+        // Given format arguments
+        // When we parse them
+        // Then names and aliases map to formats and unknown names are rejected
+        assert_eq!(parse_format("SRT"), Ok(TranscriptFormat::Srt));
+        assert_eq!(parse_format("markdown"), Ok(TranscriptFormat::Md));
+        assert_eq!(parse_format("podlove"), Ok(TranscriptFormat::PodcastJson));
+        assert_eq!(parse_format("HTML"), Ok(TranscriptFormat::Html));
+        assert_eq!(
+            parse_format("a11y"),
+            Ok(TranscriptFormat::A11y {
+                max_line: DEFAULT_A11Y_LINE_LENGTH
+            })
+        );
+        assert!(parse_format("docx").is_err());
+        assert_eq!(parse_line_length("60"), Ok(60));
+        assert!(parse_line_length("10").is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use serde::Serialize;
+
+use crate::transcript::TranscriptCue;
+
+/// A silence (in seconds) between cues long enough to start a new paragraph
+const PARAGRAPH_PAUSE_SECONDS: f64 = 2.0;
+
+/// Length (in seconds) after which a paragraph ends at the next sentence break
+const MAX_PARAGRAPH_SECONDS: f64 = 60.0;
+
+/// A paragraph of speech by one speaker, merged from consecutive cues
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct TranscriptSegment {
+    pub speaker: Option<String>,
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// Merges cues into speaker-attributed paragraphs
+///
+/// A new segment starts when the speaker changes, after a pause of
+/// [`PARAGRAPH_PAUSE_SECONDS`], or once a segment has run for
+/// [`MAX_PARAGRAPH_SECONDS`] and its text ends a sentence, so long monologues
+/// are split without cutting a sentence in half.
+pub fn merge_cues(cues: &[TranscriptCue]) -> Vec<TranscriptSegment> {
+    let mut segments: Vec<TranscriptSegment> = Vec::new();
+
+    for cue in cues {
+        let text = cue.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        match segments.last_mut() {
+            Some(segment) if continues(segment, cue) => {
+                segment.text.push(' ');
+                segment.text.push_str(text);
+                segment.end = segment.end.max(cue.end);
+            }
+            _ => segments.push(TranscriptSegment {
+                speaker: cue.speaker.clone(),
+                start: cue.begin,
+                end: cue.end,
+                text: text.to_string(),
+            }),
+        }
+    }
+
+    segments
+}
+
+/// Whether a cue carries on the paragraph in `segment`
+fn continues(segment: &TranscriptSegment, cue: &TranscriptCue) -> bool {
+    let full = segment.end - segment.start >= MAX_PARAGRAPH_SECONDS && ends_sentence(&segment.text);
+    segment.speaker == cue.speaker && cue.begin - segment.end < PARAGRAPH_PAUSE_SECONDS && !full
+}
+
+/// Whether text ends with sentence punctuation, allowing closing quotes and brackets
+fn ends_sentence(text: &str) -> bool {
+    text.trim_end_matches(['"', '\'', '”', '’', ')', ']'])
+        .ends_with(['.', '!', '?', '…'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::parse_ttml;
+
+    fn cue(speaker: &str, begin: f64, end: f64, text: &str) -> TranscriptCue {
+        TranscriptCue {
+            begin,
+            end,
+            speaker: Some(speaker.to_string()),
+            text: text.to_string(),
+        }
+    }
+
+    /// Unit test - merge_cues joins consecutive cues by speaker
+    #[test]
+    fn test_merge_cues_groups_by_speaker() {
+        // Given the fixture transcript
+        let cues = parse_ttml(include_str!("../../tests/fixtures/transcript.ttml")).unwrap();
+
+        // When we merge its cues
+        let segments = merge_cues(&cues);
+
+        // Then each speaker turn is one segment spanning its cues
+        assert_eq!(segments.len(), 3);
+        assert_eq!(
+            segments[0],
+            TranscriptSegment {
+                speaker: Some("SPEAKER_1".to_string()),
+                start: 0.5,
+                end: 5.0,
+                text: "Welcome back to the show. Today we talk about R&D budgets <and more>."
+                    .to_string(),
+            }
+        );
+        assert_eq!(segments[1].speaker.as_deref(), Some("SPEAKER_2"));
+        assert_eq!(segments[2].start, 3723.0);
+    }
+
+    /// Unit test - merge_cues breaks paragraphs at pauses and at sentence ends in long turns
+    #[test]
+    fn test_merge_cues_breaks_long_turns() {
+        // Given one speaker who pauses, then talks for over a minute mid-sentence
+        let cues = vec![
+            cue("A", 0.0, 2.0, "First thought."),
+            cue("A", 5.0, 40.0, "After a pause, a long"),
+            cue("A", 40.0, 70.0, "sentence that keeps"),
+            cue("A", 70.0, 72.0, "going to its end."),
+            cue("A", 72.0, 74.0, "Next paragraph."),
+        ];
+
+        // When we merge them
+        let segments = merge_cues(&cues);
+
+        // Then the pause and the first sentence end after a minute start new segments
+        let texts: Vec<&str> = segments.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "First thought.",
+                "After a pause, a long sentence that keeps going to its end.",
+                "Next paragraph."
+            ]
+        );
+        assert_eq!((segments[1].start, segments[1].end), (5.0, 72.0));
+    }
+}
@@ -0,0 +1,184 @@
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::error::{Error, Result};
+use crate::transcript::{parse_ttml, parse_ttml_time};
+
+/// How far, in seconds, a part may start before the previous part ended and still
+/// count as carrying episode-wide times rather than restarting from zero
+const OVERLAP_TOLERANCE: f64 = 1.0;
+
+/// One downloaded part of a multi-part transcript
+#[derive(Debug, Clone, PartialEq)]
+pub struct TtmlPart {
+    pub ttml: String,
+    /// Where the part starts in the episode, in seconds, when the page says
+    pub offset: Option<f64>,
+}
+
+fn timing_attribute() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r#"\b(begin|end)="([^"]*)""#).unwrap())
+}
+
+fn duration_attribute() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r#"\bdur="[^"]*""#).unwrap())
+}
+
+/// A part's `<body>` start tag, the range of its content, and whether it is self-closing
+fn body_ranges(ttml: &str) -> Result<(Range<usize>, Range<usize>, bool)> {
+    let document = roxmltree::Document::parse(ttml)?;
+    let body = document
+        .descendants()
+        .find(|node| node.has_tag_name("body"))
+        .ok_or_else(|| Error::Parse("Transcript part has no <body>".to_string()))?;
+    let range = body.range();
+    let tag_end = ttml[range.clone()]
+        .find('>')
+        .map_or(range.end, |index| range.start + index + 1);
+    if ttml[range.start..tag_end].ends_with("/>") {
+        return Ok((range.start..tag_end, tag_end..tag_end, true));
+    }
+    let content_end = ttml[..range.end].rfind("</").unwrap_or(range.end);
+    Ok((range.start..tag_end, tag_end..content_end, false))
+}
+
+/// Adds `offset` seconds to every `begin` and `end` attribute in `markup`
+fn shift_times(markup: &str, offset: f64) -> String {
+    if offset == 0.0 {
+        return markup.to_string();
+    }
+    timing_attribute()
+        .replace_all(markup, |captures: &regex::Captures| {
+            match parse_ttml_time(&captures[2]) {
+                Some(seconds) => format!("{}=\"{:.3}s\"", &captures[1], seconds + offset),
+                None => captures[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Joins the parts of a transcript into one TTML document with continuous timestamps
+///
+/// The first part's document wraps the body content of every part, in order.
+/// A part whose times restart is moved to its declared offset, or else to where
+/// the previous parts ended; a part already timed from the start of the
+/// episode is left alone. The body's `dur` becomes the whole transcript's.
+/// Parts are expected to declare the namespace prefixes the first one does.
+pub fn stitch_ttml(parts: &[TtmlPart]) -> Result<String> {
+    let Some(first) = parts.first() else {
+        return Err(Error::Parse("Transcript has no parts".to_string()));
+    };
+
+    let mut content = String::new();
+    let mut ended = 0.0_f64;
+    for part in parts {
+        let cues = parse_ttml(&part.ttml)?;
+        let starts = cues.first().map(|cue| cue.begin).unwrap_or_default();
+        let offset = match part.offset {
+            Some(offset) if starts < offset - OVERLAP_TOLERANCE => offset,
+            Some(_) => 0.0,
+            None if starts < ended - OVERLAP_TOLERANCE => ended,
+            None => 0.0,
+        };
+        let (_, body, _) = body_ranges(&part.ttml)?;
+        content.push_str(&shift_times(&part.ttml[body], offset));
+        let last_end = cues.iter().map(|cue| cue.end).fold(starts, f64::max);
+        ended = ended.max(last_end + offset);
+    }
+
+    let (tag, body, self_closing) = body_ranges(&first.ttml)?;
+    let start_tag = &first.ttml[tag.clone()];
+    let start_tag = start_tag
+        .strip_suffix("/>")
+        .map_or_else(|| start_tag.to_string(), |open| format!("{}>", open));
+    let duration = format!("dur=\"{:.3}s\"", ended);
+    let start_tag = duration_attribute().replace(&start_tag, duration.as_str());
+    let closing = if self_closing { "</body>" } else { "" };
+    Ok(format!(
+        "{}{}{}{}{}",
+        &first.ttml[..tag.start],
+        start_tag,
+        content,
+        closing,
+        &first.ttml[body.end..]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(body: &str, offset: Option<f64>) -> TtmlPart {
+        TtmlPart {
+            ttml: format!(
+                r#"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttm="http://www.w3.org/ns/ttml#metadata"><body dur="60s">{}</body></tt>"#,
+                body
+            ),
+            offset,
+        }
+    }
+
+    /// Unit test - stitch_ttml joins parts into one transcript with continuous times
+    #[test]
+    fn test_stitch_ttml_continues_timestamps() {
+        // Given a part, one that restarts at zero, one at a declared offset and one already timed from the episode start
+        let parts = vec![
+            part(
+                r#"<div><p begin="0s" end="10s" ttm:agent="A">One</p></div>"#,
+                None,
+            ),
+            part(
+                r#"<div><p begin="0s" end="5s"><span begin="0s" end="5s">Two</span></p></div>"#,
+                None,
+            ),
+            part(
+                r#"<div><p begin="1.5" end="00:00:03">Three</p></div>"#,
+                Some(100.0),
+            ),
+            part(
+                r#"<div><p begin="200s" end="210s">Four</p></div>"#,
+                Some(200.0),
+            ),
+        ];
+
+        // When we stitch them
+        let ttml = stitch_ttml(&parts).unwrap();
+
+        // Then the cues run on in order and the body covers the whole transcript
+        let cues = parse_ttml(&ttml).unwrap();
+        let times: Vec<(f64, f64)> = cues.iter().map(|cue| (cue.begin, cue.end)).collect();
+        assert_eq!(
+            times,
+            vec![(0.0, 10.0), (10.0, 15.0), (101.5, 103.0), (200.0, 210.0)]
+        );
+        assert_eq!(cues[0].speaker.as_deref(), Some("A"));
+        assert_eq!(cues[1].text, "Two");
+        assert!(ttml.contains(r#"<body dur="210.000s">"#));
+        assert!(ttml.starts_with("<tt ") && ttml.ends_with("</body></tt>"));
+    }
+
+    /// Unit test - stitch_ttml handles empty parts and rejects an empty list
+    #[test]
+    fn test_stitch_ttml_empty_parts() {
+        // Given a first part with an empty body and a second with a cue
+        let parts = vec![
+            TtmlPart {
+                ttml: r#"<tt xmlns="http://www.w3.org/ns/ttml"><body/></tt>"#.to_string(),
+                offset: None,
+            },
+            part(r#"<p begin="2s" end="4s">Hi</p>"#, None),
+        ];
+
+        // When we stitch them, and stitch nothing
+        let ttml = stitch_ttml(&parts).unwrap();
+
+        // Then the cue lands in the first part's body, and nothing is an error
+        assert_eq!(parse_ttml(&ttml).unwrap()[0].text, "Hi");
+        assert!(ttml.contains("<body>"));
+        assert!(stitch_ttml(&[]).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+use std::fs::File;
+use std::path::Path;
+
+use memmap2::Mmap;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::compress::{stored_path, Compression};
+use crate::error::{Error, Result};
+use crate::transcript::{parse_ttml_time, TranscriptCue};
+
+/// A TTML transcript file mapped into memory rather than read into it
+///
+/// The operating system pages the file in as [`MappedTtml::cues`] walks it,
+/// so indexing thousands of large transcripts needs memory for one cue at a
+/// time, not for a whole document tree. A compressed transcript cannot be
+/// mapped, so it is decompressed into memory instead.
+pub struct MappedTtml {
+    contents: Contents,
+}
+
+enum Contents {
+    Mapped(Mmap),
+    Decompressed(Vec<u8>),
+}
+
+impl MappedTtml {
+    /// Maps a TTML file saved on disk, or the compressed variant stored in its place
+    pub fn open(path: impl AsRef<Path>) -> Result<MappedTtml> {
+        let path = path.as_ref();
+        let path = stored_path(path).unwrap_or_else(|| path.to_path_buf());
+        let file = File::open(path).map_err(Error::io("Failed to read transcript"))?;
+        // Safety: transcripts are only written whole, by this tool, before they are indexed;
+        // a file truncated while mapped could fault, which is the accepted mmap trade-off
+        let map = unsafe { Mmap::map(&file) }.map_err(Error::io("Failed to map transcript"))?;
+        let contents = match Compression::detect(&map) {
+            Some(compression) => Contents::Decompressed(compression.decompress(&map)?),
+            None => Contents::Mapped(map),
+        };
+        Ok(MappedTtml { contents })
+    }
+
+    /// Streams the transcript's cues in document order
+    pub fn cues(&self) -> Result<TtmlCues<'_>> {
+        let bytes: &[u8] = match &self.contents {
+            Contents::Mapped(map) => map,
+            Contents::Decompressed(bytes) => bytes,
+        };
+        let text = std::str::from_utf8(bytes)
+            .map_err(|e| Error::Parse(format!("Failed to parse TTML: {}", e)))?;
+        Ok(TtmlCues::new(text))
+    }
+}
+
+/// Cues pulled one `<p>` at a time from TTML text
+///
+/// Yields the same cues as [`parse_ttml`](crate::transcript::parse_ttml)
+/// without building the document tree. Stops after the first error.
+pub struct TtmlCues<'a> {
+    reader: Reader<&'a [u8]>,
+    failed: bool,
+}
+
+/// The parts of a `<p>` seen so far
+#[derive(Default)]
+struct OpenCue {
+    /// Nesting depth inside the `<p>`
+    depth: usize,
+    /// The `begin` of the first timed element, the `<p>` itself included
+    first_begin: Option<Option<String>>,
+    /// The `<p>`'s own `end`
+    end: Option<String>,
+    /// The `end` of the last timed element
+    last_timed_end: Option<String>,
+    speaker: Option<String>,
+    texts: Vec<String>,
+}
+
+impl<'a> TtmlCues<'a> {
+    /// Streams cues from TTML text
+    pub fn new(ttml: &'a str) -> TtmlCues<'a> {
+        TtmlCues {
+            reader: Reader::from_str(ttml),
+            failed: false,
+        }
+    }
+
+    /// Reads up to the next complete, timed cue
+    fn next_cue(&mut self) -> Result<Option<TranscriptCue>> {
+        let mut open: Option<OpenCue> = None;
+        loop {
+            let event = self.reader.read_event().map_err(parse_error)?;
+            match (&mut open, event) {
+                (_, Event::Eof) => return Ok(None),
+                (None, Event::Start(element)) if element.local_name().as_ref() == b"p" => {
+                    open = Some(OpenCue::start(&element)?);
+                }
+                (None, Event::Empty(element)) if element.local_name().as_ref() == b"p" => {
+                    if let Some(cue) = OpenCue::start(&element)?.finish() {
+                        return Ok(Some(cue));
+                    }
+                }
+                (Some(cue), Event::Start(element)) => {
+                    cue.depth += 1;
+                    cue.timed(&element)?;
+                }
+                (Some(cue), Event::Empty(element)) => cue.timed(&element)?,
+                (Some(cue), Event::Text(text)) => {
+                    cue.texts
+                        .push(text.unescape().map_err(parse_error)?.into_owned());
+                }
+                (Some(cue), Event::CData(text)) => {
+                    cue.texts
+                        .push(String::from_utf8_lossy(&text.into_inner()).into_owned());
+                }
+                (Some(cue), Event::End(_)) if cue.depth > 0 => cue.depth -= 1,
+                (Some(_), Event::End(_)) => match open.take().and_then(OpenCue::finish) {
+                    Some(cue) => return Ok(Some(cue)),
+                    None => continue,
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Iterator for TtmlCues<'_> {
+    type Item = Result<TranscriptCue>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let next = self.next_cue();
+        self.failed = next.is_err();
+        next.transpose()
+    }
+}
+
+impl OpenCue {
+    /// Starts a cue at its `<p>`
+    fn start(paragraph: &BytesStart) -> Result<OpenCue> {
+        let mut cue = OpenCue {
+            end: attribute(paragraph, b"end")?,
+            ..OpenCue::default()
+        };
+        for attr in paragraph.attributes() {
+            let attr = attr.map_err(parse_error)?;
+            if attr.key.local_name().as_ref() == b"agent" {
+                cue.speaker = Some(attr.unescape_value().map_err(parse_error)?.into_owned());
+            }
+        }
+        cue.timed(paragraph)?;
+        Ok(cue)
+    }
+
+    /// Records the timing of an element inside the cue, if it has any
+    fn timed(&mut self, element: &BytesStart) -> Result<()> {
+        if let Some(begin) = attribute(element, b"begin")? {
+            self.first_begin.get_or_insert(Some(begin));
+            self.last_timed_end = attribute(element, b"end")?;
+        }
+        Ok(())
+    }
+
+    /// The finished cue, or `None` when it has no usable timing
+    fn finish(self) -> Option<TranscriptCue> {
+        let begin = self
+            .first_begin
+            .flatten()
+            .as_deref()
+            .and_then(parse_ttml_time)?;
+        let end = self
+            .end
+            .or(self.last_timed_end)
+            .as_deref()
+            .and_then(parse_ttml_time)?;
+        Some(TranscriptCue {
+            begin,
+            end,
+            speaker: self.speaker,
+            text: self
+                .texts
+                .join(" ")
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" "),
+        })
+    }
+}
+
+/// An unprefixed attribute's unescaped value
+fn attribute(element: &BytesStart, name: &[u8]) -> Result<Option<String>> {
+    for attr in element.attributes() {
+        let attr = attr.map_err(parse_error)?;
+        if attr.key.as_ref() == name {
+            return Ok(Some(
+                attr.unescape_value().map_err(parse_error)?.into_owned(),
+            ));
+        }
+    }
+    Ok(None)
+}
+
+fn parse_error(e: impl std::fmt::Display) -> Error {
+    Error::Parse(format!("Failed to parse TTML: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::parse_ttml;
+
+    const FIXTURE_PATH: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/transcript.ttml"
+    );
+
+    /// Unit test - streamed cues from a mapped file match the parsed document
+    #[test]
+    fn test_mapped_cues_match_parse_ttml() {
+        // Given the fixture transcript on disk
+        let expected = parse_ttml(&std::fs::read_to_string(FIXTURE_PATH).unwrap()).unwrap();
+
+        // When we map it and stream its cues
+        let mapped = MappedTtml::open(FIXTURE_PATH).unwrap();
+        let cues: Vec<TranscriptCue> = mapped.cues().unwrap().collect::<Result<_>>().unwrap();
+
+        // Then they match cue for cue, timings, speakers and unescaped text included
+        assert_eq!(cues, expected);
+    }
+
+    /// Unit test - a compressed transcript is found by its plain name and streamed like a mapped one
+    #[test]
+    fn test_mapped_ttml_reads_compressed_transcript() {
+        use crate::compress::{write_artifact, Compression};
+
+        // Given the fixture transcript stored zstd-compressed
+        let ttml = std::fs::read_to_string(FIXTURE_PATH).unwrap();
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("transcript.ttml");
+        write_artifact(path.to_str().unwrap(), &ttml, Some(Compression::Zstd)).unwrap();
+
+        // When we open it by its plain name and stream its cues
+        let mapped = MappedTtml::open(&path).unwrap();
+        let cues: Vec<TranscriptCue> = mapped.cues().unwrap().collect::<Result<_>>().unwrap();
+
+        // Then they match the uncompressed transcript
+        assert_eq!(cues, parse_ttml(&ttml).unwrap());
+    }
+
+    /// Unit test - TtmlCues skips untimed paragraphs and stops at malformed XML
+    #[test]
+    fn test_ttml_cues_skips_untimed_and_reports_errors() {
+        // Given an untimed paragraph, a timed one, then broken markup
+        let ttml = r#"<tt><body><p>No timing</p><p begin="1s" end="2s">Timed &amp; kept</p><p begin="3s"></span></p></body></tt>"#;
+
+        // When we stream its cues
+        let mut cues = TtmlCues::new(ttml);
+
+        // Then only the timed cue is yielded before the error, and nothing after it
+        assert_eq!(cues.next().unwrap().unwrap().text, "Timed & kept");
+        assert!(cues.next().unwrap().is_err());
+        assert!(cues.next().is_none());
+    }
+}
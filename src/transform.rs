@@ -0,0 +1,324 @@
+use std::collections::BTreeMap;
+
+use regex::Regex;
+
+use crate::apple_url::{parse_storefront, show_id_from_url};
+use crate::error::{Error, Result};
+use crate::locale::parse_localized_date;
+use crate::metadata::Metadata;
+
+/// Key of the `[transforms]` entry applied to every show
+const EVERY_SHOW: &str = "*";
+
+/// The `metadata.json` fields transforms can rewrite
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    EpisodeTitle,
+    ShowTitle,
+    Description,
+    PublishDate,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Field> {
+        match name {
+            "episode_title" => Some(Field::EpisodeTitle),
+            "show_title" => Some(Field::ShowTitle),
+            "description" => Some(Field::Description),
+            "publish_date" => Some(Field::PublishDate),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Field::EpisodeTitle => "episode_title",
+            Field::ShowTitle => "show_title",
+            Field::Description => "description",
+            Field::PublishDate => "publish_date",
+        }
+    }
+
+    fn value(self, metadata: &mut Metadata) -> &mut String {
+        match self {
+            Field::EpisodeTitle => &mut metadata.episode_title,
+            Field::ShowTitle => &mut metadata.show_title,
+            Field::Description => &mut metadata.description,
+            Field::PublishDate => &mut metadata.publish_date,
+        }
+    }
+}
+
+/// What a transform does to its field
+#[derive(Debug, Clone)]
+enum Action {
+    /// Replaces every match of the pattern; `$1` and `${name}` refer to its groups
+    Replace { pattern: Regex, with: String },
+    /// Removes every match of the pattern and trims what is left
+    Strip(Regex),
+    /// Reads the date text as the storefront renders it, setting `publish_date_iso8601`
+    Locale(String),
+}
+
+impl PartialEq for Action {
+    fn eq(&self, other: &Action) -> bool {
+        match (self, other) {
+            (
+                Action::Replace { pattern, with },
+                Action::Replace {
+                    pattern: other_pattern,
+                    with: other_with,
+                },
+            ) => pattern.as_str() == other_pattern.as_str() && with == other_with,
+            (Action::Strip(pattern), Action::Strip(other)) => pattern.as_str() == other.as_str(),
+            (Action::Locale(storefront), Action::Locale(other)) => storefront == other,
+            _ => false,
+        }
+    }
+}
+
+/// One rewrite of an extracted field, from a `[[transforms.<show>]]` config entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldTransform {
+    field: Field,
+    action: Action,
+}
+
+impl FieldTransform {
+    /// Reads one entry: a `field` and exactly one of `replace` (with `with`), `strip` or `locale`
+    fn parse(settings: &toml::Table) -> std::result::Result<FieldTransform, String> {
+        if let Some(key) = settings.keys().find(|key| {
+            !matches!(
+                key.as_str(),
+                "field" | "replace" | "with" | "strip" | "locale"
+            )
+        }) {
+            return Err(format!("unknown setting '{}'", key));
+        }
+        let text = |key: &str| match settings.get(key) {
+            None => Ok(None),
+            Some(toml::Value::String(text)) => Ok(Some(text.clone())),
+            Some(_) => Err(format!("'{}' must be a string", key)),
+        };
+        let pattern = |pattern: &str| {
+            Regex::new(pattern).map_err(|e| format!("invalid pattern '{}': {}", pattern, e))
+        };
+
+        let field = text("field")?.ok_or("expected a field")?;
+        let field = Field::parse(&field).ok_or_else(|| {
+            format!(
+                "unknown field '{}' (expected episode_title, show_title, description or publish_date)",
+                field
+            )
+        })?;
+        let action = match (text("replace")?, text("strip")?, text("locale")?) {
+            (Some(replace), None, None) => Action::Replace {
+                pattern: pattern(&replace)?,
+                with: text("with")?.unwrap_or_default(),
+            },
+            (None, Some(strip), None) => Action::Strip(pattern(&strip)?),
+            (None, None, Some(_)) if field != Field::PublishDate => {
+                return Err("locale only applies to publish_date".to_string())
+            }
+            (None, None, Some(locale)) => Action::Locale(parse_storefront(&locale)?),
+            _ => return Err("give exactly one of replace, strip or locale".to_string()),
+        };
+        if settings.contains_key("with") && !matches!(action, Action::Replace { .. }) {
+            return Err("'with' only goes with replace".to_string());
+        }
+        Ok(FieldTransform { field, action })
+    }
+
+    /// Rewrites the field in `metadata`, returning whether it changed
+    fn apply(&self, metadata: &mut Metadata) -> bool {
+        match &self.action {
+            Action::Replace { pattern, with } => {
+                let value = self.field.value(metadata);
+                let replaced = pattern.replace_all(value, with.as_str()).into_owned();
+                replace(value, replaced)
+            }
+            Action::Strip(pattern) => {
+                let value = self.field.value(metadata);
+                let stripped = pattern.replace_all(value, "").trim().to_string();
+                replace(value, stripped)
+            }
+            Action::Locale(storefront) => {
+                let parsed = parse_localized_date(&metadata.publish_date, storefront);
+                if parsed.is_none() || parsed == metadata.publish_date_iso8601 {
+                    return false;
+                }
+                metadata.publish_date_iso8601 = parsed;
+                true
+            }
+        }
+    }
+}
+
+/// Sets `value` to `new`, returning whether that changed it
+fn replace(value: &mut String, new: String) -> bool {
+    if *value == new {
+        return false;
+    }
+    *value = new;
+    true
+}
+
+/// Config-defined rewrites of extracted fields, for every show and per show
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Transforms {
+    every_show: Vec<FieldTransform>,
+    /// Keyed by Apple show ID
+    shows: BTreeMap<String, Vec<FieldTransform>>,
+}
+
+impl Transforms {
+    /// Reads the `[transforms]` config table: a list of transforms per show ID or URL, or `"*"`
+    ///
+    /// Patterns are compiled here, so mistakes are reported when the config is
+    /// loaded rather than when an episode is saved.
+    pub fn parse(table: &toml::Table) -> Result<Transforms> {
+        let mut transforms = Transforms::default();
+        for (show, entries) in table {
+            let invalid =
+                |message: &str| Error::Config(format!("transforms '{}': {}", show, message));
+            let toml::Value::Array(entries) = entries else {
+                return Err(invalid("expected a list of [[transforms]] tables"));
+            };
+            let parsed = entries
+                .iter()
+                .map(|entry| match entry {
+                    toml::Value::Table(settings) => FieldTransform::parse(settings),
+                    _ => Err("expected a table with a field".to_string()),
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|message| invalid(&message))?;
+
+            if show == EVERY_SHOW {
+                transforms.every_show.extend(parsed);
+                continue;
+            }
+            let id = match show_id_from_url(show) {
+                Some(id) => id,
+                None if !show.is_empty() && show.chars().all(|c| c.is_ascii_digit()) => {
+                    show.clone()
+                }
+                None => return Err(invalid("expected \"*\", an Apple show ID or show URL")),
+            };
+            transforms.shows.entry(id).or_default().extend(parsed);
+        }
+        Ok(transforms)
+    }
+
+    /// Applies the transforms for every show, then those for the show of the episode at `url`
+    ///
+    /// Returns the names of the fields that changed, each once.
+    pub fn apply(&self, url: &str, metadata: &mut Metadata) -> Vec<String> {
+        let show = show_id_from_url(url).and_then(|id| self.shows.get(&id));
+        let mut changed: Vec<String> = Vec::new();
+        for transform in self.every_show.iter().chain(show.into_iter().flatten()) {
+            let name = match transform.action {
+                Action::Locale(_) => "publish_date_iso8601",
+                _ => transform.field.name(),
+            };
+            if transform.apply(metadata) && !changed.iter().any(|field| field == name) {
+                changed.push(name.to_string());
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - transforms rewrite fields for every show and for one show only
+    #[test]
+    fn test_transforms_apply_per_show() {
+        // Given a tagline stripped everywhere, a title prefix replaced for one show and a French date fix
+        let table: toml::Table = toml::from_str(
+            r#"
+            [["*"]]
+            field = "description"
+            strip = '(?s)Support the show at .*$'
+
+            [["840986946"]]
+            field = "episode_title"
+            replace = '^Ep\. (\d+): '
+            with = '#$1 '
+
+            [["840986946"]]
+            field = "publish_date"
+            locale = "fr"
+            "#,
+        )
+        .unwrap();
+        let transforms = Transforms::parse(&table).unwrap();
+        let metadata = || Metadata {
+            episode_title: "Ep. 12: Launch".to_string(),
+            description: "About launches.\n\nSupport the show at example.com".to_string(),
+            publish_date: "13 oct. 2023".to_string(),
+            ..Metadata::default()
+        };
+
+        // When we apply them to an episode of that show and of another
+        let mut ours = metadata();
+        let changed = transforms.apply(
+            "https://podcasts.apple.com/us/podcast/show/id840986946?i=1",
+            &mut ours,
+        );
+        let mut other = metadata();
+        transforms.apply(
+            "https://podcasts.apple.com/us/podcast/other/id2?i=1",
+            &mut other,
+        );
+
+        // Then the show's transforms only touch its episodes, after the shared ones
+        assert_eq!(ours.episode_title, "#12 Launch");
+        assert_eq!(ours.description, "About launches.");
+        assert_eq!(ours.publish_date_iso8601.as_deref(), Some("2023-10-13"));
+        assert_eq!(
+            changed,
+            ["description", "episode_title", "publish_date_iso8601"]
+        );
+        assert_eq!(other.episode_title, "Ep. 12: Launch");
+        assert_eq!(other.description, "About launches.");
+    }
+
+    /// Unit test - Transforms::parse rejects entries that could never apply
+    #[test]
+    fn test_transforms_reject_invalid_entries() {
+        // Given entries with a bad pattern, an unknown field, two actions and a misplaced locale
+        let cases = [
+            (
+                "field = \"episode_title\"\nreplace = \"(\"",
+                "invalid pattern",
+            ),
+            (
+                "field = \"summary\"\nstrip = \"x\"",
+                "unknown field 'summary'",
+            ),
+            (
+                "field = \"description\"\nstrip = \"x\"\nreplace = \"y\"",
+                "exactly one of",
+            ),
+            ("field = \"episode_title\"\nlocale = \"fr\"", "only applies"),
+            (
+                "field = \"description\"\nstrip = \"x\"\nwith = \"y\"",
+                "'with'",
+            ),
+        ];
+
+        for (entry, expected) in cases {
+            // When we parse each one
+            let table: toml::Table = toml::from_str(&format!("[[\"*\"]]\n{}", entry)).unwrap();
+            let error = Transforms::parse(&table).unwrap_err().to_string();
+
+            // Then the error explains what is wrong
+            assert!(error.contains(expected), "{}: {}", entry, error);
+        }
+        let table: toml::Table =
+            toml::from_str("[[\"The Daily\"]]\nfield = \"description\"\nstrip = \"x\"").unwrap();
+        assert!(Transforms::parse(&table).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use regex::Regex;
+
+/// One of the page layouts Apple serves, and where its data lives
+///
+/// Apple A/B tests podcast page markup. Each known layout gets a profile here
+/// so the extractors look for the right script ids; adding support for a new
+/// layout means adding an entry to [`VARIANTS`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct PageVariant {
+    /// Identifier reported when this layout is detected
+    pub id: &'static str,
+    /// Substrings whose presence identifies the layout
+    markers: &'static [&'static str],
+    /// Selector for the episode JSON-LD block
+    pub episode_schema: &'static str,
+    /// Attribute marking the episode JSON-LD block, for extraction without a DOM
+    pub episode_schema_attr: &'static str,
+    /// Selector for the show JSON-LD block
+    pub show_schema: &'static str,
+    /// `id` of the script holding the page's serialized data
+    pub server_data_id: &'static str,
+    /// Whether the serialized data's values are themselves JSON-encoded strings
+    pub server_data_encoded: bool,
+}
+
+/// Known page layouts; the first one is the current default
+pub const VARIANTS: &[PageVariant] = &[
+    PageVariant {
+        id: "serialized-server-data",
+        markers: &[
+            r#"id="serialized-server-data""#,
+            r#"id="schema:episode""#,
+            r#"id="schema:show""#,
+        ],
+        episode_schema: "script[id='schema:episode']",
+        episode_schema_attr: r#"id="schema:episode""#,
+        show_schema: "script[id='schema:show']",
+        server_data_id: "serialized-server-data",
+        server_data_encoded: false,
+    },
+    PageVariant {
+        id: "fastboot-shoebox",
+        markers: &[
+            r#"type="fastboot/shoebox""#,
+            r#"name="schema:podcast-episode""#,
+            r#"name="schema:podcast-show""#,
+        ],
+        episode_schema: "script[name='schema:podcast-episode']",
+        episode_schema_attr: r#"name="schema:podcast-episode""#,
+        show_schema: "script[name='schema:podcast-show']",
+        server_data_id: "shoebox-media-api-cache-amp-podcasts",
+        server_data_encoded: true,
+    },
+];
+
+/// Identifies which known layout a page uses, if any
+pub fn detect_variant(html: &str) -> Option<&'static PageVariant> {
+    VARIANTS
+        .iter()
+        .find(|variant| variant.markers.iter().any(|marker| html.contains(marker)))
+}
+
+/// The page's layout profile, falling back to the default for unrecognized pages
+pub fn variant_for(html: &str) -> &'static PageVariant {
+    detect_variant(html).unwrap_or(&VARIANTS[0])
+}
+
+/// Lists the `id`/`name` of every script on the page, to describe an unrecognized layout
+pub fn script_fingerprint(html: &str) -> Vec<String> {
+    let Ok(re) = Regex::new(r#"<script[^>]*\s(?:id|name)="([^"]+)""#) else {
+        return Vec::new();
+    };
+
+    let mut ids: Vec<String> = re
+        .captures_iter(html)
+        .filter_map(|captures| captures.get(1).map(|m| m.as_str().to_string()))
+        .collect();
+    ids.dedup();
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - detect_variant recognizes each known layout
+    #[test]
+    fn test_detect_variant_recognizes_known_layouts() {
+        // Given pages in the current and the older shoebox layout, and an unknown one
+        let current = r#"<script type="application/json" id="serialized-server-data">[]</script>"#;
+        let shoebox = r#"<script type="fastboot/shoebox" id="shoebox-media-api-cache-amp-podcasts">{}</script>"#;
+        let unknown = r#"<script id="app-state" type="application/json">{}</script>"#;
+
+        // When we detect their variants
+        // Then known layouts get their profile and unknown ones none
+        assert_eq!(
+            detect_variant(current).map(|v| v.id),
+            Some("serialized-server-data")
+        );
+        assert_eq!(
+            detect_variant(shoebox).map(|v| v.id),
+            Some("fastboot-shoebox")
+        );
+        assert_eq!(detect_variant(unknown), None);
+
+        // And unknown pages fall back to the default profile
+        assert_eq!(variant_for(unknown).id, "serialized-server-data");
+    }
+
+    /// Unit test - script_fingerprint lists script ids and names
+    #[test]
+    fn test_script_fingerprint_lists_script_ids() {
+        // Given a page with several scripts
+        let html = r#"<script id="app-state" type="application/json">{}</script>
+            <script type="application/ld+json" name="schema:podcast-episode">{}</script>
+            <script src="/main.js"></script>"#;
+
+        // When we fingerprint it
+        // Then identified scripts are listed in page order
+        assert_eq!(
+            script_fingerprint(html),
+            vec!["app-state", "schema:podcast-episode"]
+        );
+    }
+}
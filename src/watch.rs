@@ -0,0 +1,250 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+
+use crate::apple_url::{show_id_from_url, storefront_from_url};
+use crate::budget::parse_duration;
+use crate::error::{Error, Result};
+use crate::fetch::{fetch_page, send_with_retry, HttpOptions};
+use crate::metadata::Metadata;
+use crate::show::{extract_show_metadata, find_episodes, EpisodeSummary, ShowMetadata};
+
+/// Shortest `--interval` accepted, so a watch cannot hammer Apple
+pub const MIN_WATCH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// State file every watched show shared before state was kept per show; still read so earlier progress is kept
+const SHARED_STATE_FILE: &str = "watch-state.json";
+
+/// Where the watch state for `show_url` is saved under `dir`: `watch-state-<show id>.json`
+///
+/// One file per show, so several shows can be watched into the same output directory.
+pub fn state_path(dir: &str, show_url: &str) -> String {
+    match show_id_from_url(show_url) {
+        Some(id) => format!("{}/watch-state-{}.json", dir, id),
+        None => format!("{}/{}", dir, SHARED_STATE_FILE),
+    }
+}
+
+/// Episodes of a watched show already seen, saved between checks and runs
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct WatchState {
+    pub show_url: String,
+    /// IDs of every episode listed or processed so far
+    pub seen: BTreeSet<String>,
+}
+
+impl WatchState {
+    /// State for a show that has not been checked yet
+    pub fn new(show_url: &str) -> WatchState {
+        WatchState {
+            show_url: show_url.to_string(),
+            ..WatchState::default()
+        }
+    }
+
+    /// Reads saved state, or returns `None` if there is none for this show
+    pub fn load(path: &str, show_url: &str) -> Result<Option<WatchState>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::io("Failed to read watch state")(e)),
+        };
+        let state: WatchState = serde_json::from_str(&text)?;
+        Ok(Some(state).filter(|state| state.show_url == show_url))
+    }
+
+    /// Reads the state for `show_url` saved under `dir`, falling back to the shared file of earlier versions
+    pub fn load_from(dir: &str, show_url: &str) -> Result<Option<WatchState>> {
+        match WatchState::load(&state_path(dir, show_url), show_url)? {
+            Some(state) => Ok(Some(state)),
+            None => WatchState::load(&format!("{}/{}", dir, SHARED_STATE_FILE), show_url),
+        }
+    }
+}
+
+/// A newly published episode that a watch processed, as sent to webhooks and hooks
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct NewEpisode {
+    pub show_url: String,
+    pub show_title: String,
+    #[serde(flatten)]
+    pub episode: EpisodeSummary,
+    /// Where the episode's artifacts were saved
+    pub directory: String,
+    pub transcript: bool,
+    pub metadata: Metadata,
+}
+
+/// Parses a `--interval` value: seconds, or a number with an `s`, `m`, `h` or `d` suffix
+pub fn parse_interval(raw: &str) -> std::result::Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "Invalid interval '{}': expected a duration of at least {}s such as 90s, 15m, 1h or 1d",
+            raw,
+            MIN_WATCH_INTERVAL.as_secs()
+        )
+    };
+
+    parse_duration(raw)
+        .ok()
+        .filter(|interval| *interval >= MIN_WATCH_INTERVAL)
+        .ok_or_else(invalid)
+}
+
+/// Fetches a show's first listing page: its details and newest episodes
+///
+/// New episodes always appear at the top, so one page per check is enough
+/// unless a show publishes more than a page of episodes between checks.
+pub async fn newest_episodes(
+    url: &str,
+    options: &HttpOptions,
+) -> Result<(ShowMetadata, Vec<EpisodeSummary>)> {
+    let html = fetch_page(url, options).await?;
+    let show = extract_show_metadata(&html)?;
+    let episodes = find_episodes(&html, &storefront_from_url(url))?;
+    Ok((show, episodes))
+}
+
+/// POSTs the episode as JSON to a webhook URL
+pub async fn notify_webhook(url: &str, episode: &NewEpisode, options: &HttpOptions) -> Result<()> {
+    post_json(url, serde_json::to_string(episode)?, options).await
+}
+
+/// POSTs a JSON body to a webhook URL, failing on a non-success status
+pub async fn post_json(url: &str, body: String, options: &HttpOptions) -> Result<()> {
+    let response = send_with_retry(options, |client| {
+        client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.clone())
+            .timeout(options.timeout())
+    })
+    .await?;
+
+    if !response.status().is_success() {
+        return Err(Error::Status {
+            status: response.status(),
+            retry_after: None,
+        });
+    }
+    Ok(())
+}
+
+/// Runs a shell command with the episode JSON on stdin
+pub fn run_hook(command: &str, episode: &NewEpisode) -> Result<()> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut child = Command::new(shell)
+        .args([flag, command])
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(Error::io("Failed to start hook command"))?;
+
+    // A hook may ignore its input and exit before reading it all
+    let written = match child.stdin.take() {
+        Some(mut stdin) => stdin.write_all(serde_json::to_string(episode)?.as_bytes()),
+        None => Ok(()),
+    };
+    let status = child
+        .wait()
+        .map_err(Error::io("Failed to wait for hook command"))?;
+    if !status.success() {
+        return Err(Error::Hook(format!("'{}' exited with {}", command, status)));
+    }
+    written.map_err(Error::io("Failed to send episode to hook command"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit test - parse_interval accepts unit suffixes and rejects too-short intervals
+    #[test]
+    fn test_parse_interval() {
+        // Given intervals with and without units, and invalid ones
+        // When we parse them
+        // Then units scale to seconds and anything under a minute is rejected
+        assert_eq!(parse_interval("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_interval("15m"), Ok(Duration::from_secs(900)));
+        assert_eq!(parse_interval("1H"), Ok(Duration::from_secs(3600)));
+        assert_eq!(parse_interval("2d"), Ok(Duration::from_secs(172_800)));
+        assert!(parse_interval("30s").is_err());
+        assert!(parse_interval("1w").is_err());
+        assert!(parse_interval("h").is_err());
+    }
+
+    /// Unit test - shows watched into one output directory keep separate state
+    #[test]
+    fn test_watch_state_is_kept_per_show() {
+        // Given two shows' state saved into one directory,
+        // and a third show's state in the shared file of earlier versions
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().to_str().unwrap();
+        let daily = "https://podcasts.apple.com/us/podcast/the-daily/id1200361736";
+        let other = "https://podcasts.apple.com/us/podcast/other/id840986946";
+        let legacy = "https://podcasts.apple.com/us/podcast/legacy/id1";
+        let save = |path: String, show_url: &str, seen: &str| {
+            let mut state = WatchState::new(show_url);
+            state.seen.insert(seen.to_string());
+            fs::write(path, serde_json::to_string(&state).unwrap()).unwrap();
+        };
+        save(state_path(dir, daily), daily, "10");
+        save(state_path(dir, other), other, "20");
+        save(format!("{}/watch-state.json", dir), legacy, "30");
+
+        // When each show's state is loaded
+        let load = |show_url: &str| WatchState::load_from(dir, show_url).unwrap().unwrap();
+
+        // Then each show sees only its own episodes, and the shared file is still read
+        assert_eq!(
+            state_path(dir, daily),
+            format!("{}/watch-state-1200361736.json", dir)
+        );
+        assert_eq!(load(daily).seen, BTreeSet::from(["10".to_string()]));
+        assert_eq!(load(other).seen, BTreeSet::from(["20".to_string()]));
+        assert_eq!(load(legacy).seen, BTreeSet::from(["30".to_string()]));
+        let unwatched = "https://podcasts.apple.com/us/podcast/new/id5";
+        assert_eq!(WatchState::load_from(dir, unwatched).unwrap(), None);
+    }
+
+    /// Unit test - run_hook passes the episode JSON on stdin and reports failures
+    #[cfg(unix)]
+    #[test]
+    fn test_run_hook_receives_episode_json() {
+        // Given a new episode and a hook that saves its input
+        let dir = tempfile::tempdir().unwrap();
+        let saved = dir.path().join("episode.json");
+        let episode = NewEpisode {
+            show_url: "https://podcasts.apple.com/us/podcast/show/id1".to_string(),
+            show_title: "Show".to_string(),
+            episode: EpisodeSummary {
+                id: "2".to_string(),
+                title: "New".to_string(),
+                url: "https://podcasts.apple.com/us/podcast/show/id1?i=2".to_string(),
+                release_date: None,
+            },
+            directory: "output/episodes/2".to_string(),
+            transcript: true,
+            metadata: Metadata::default(),
+        };
+
+        // When we run it, and a hook that fails
+        run_hook(&format!("cat > '{}'", saved.display()), &episode).unwrap();
+        let failed = run_hook("exit 3", &episode);
+
+        // Then the hook saw the flattened episode JSON and the failure is an error
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&saved).unwrap()).unwrap();
+        assert_eq!(json["id"], "2");
+        assert_eq!(json["directory"], "output/episodes/2");
+        assert!(matches!(failed, Err(Error::Hook(_))));
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use tera::{Context, Tera};
+
+use crate::apple_url::show_id_from_url;
+use crate::config::expand_home;
+use crate::error::{Error, Result};
+use crate::fetch::HttpOptions;
+use crate::watch::{post_json, NewEpisode};
+
+/// Name the payload template is compiled under
+const TEMPLATE_NAME: &str = "payload";
+
+/// Where one show's new episodes are announced, from a `[webhooks.<show>]` config table
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShowWebhook {
+    pub url: String,
+    /// Tera template rendering the JSON body; the episode JSON is sent without one
+    pub template: Option<String>,
+}
+
+impl ShowWebhook {
+    /// The JSON body announcing `episode`
+    ///
+    /// The template sees the same fields `watch` hooks receive. A template
+    /// that renders something other than JSON is an error, so a value that
+    /// needed `| json_encode()` fails loudly instead of reaching the receiver.
+    pub fn payload(&self, episode: &NewEpisode) -> Result<String> {
+        let Some(template) = &self.template else {
+            return Ok(serde_json::to_string(episode)?);
+        };
+        let invalid = |e: tera::Error| {
+            Error::Config(format!(
+                "webhook template for {}: {}",
+                self.url,
+                template_message(e)
+            ))
+        };
+        let context = Context::from_serialize(episode).map_err(invalid)?;
+        let body = compile(template)
+            .and_then(|tera| tera.render(TEMPLATE_NAME, &context))
+            .map_err(invalid)?;
+        serde_json::from_str::<serde_json::Value>(&body).map_err(|e| {
+            Error::Config(format!(
+                "webhook template for {} did not render JSON: {}",
+                self.url, e
+            ))
+        })?;
+        Ok(body)
+    }
+
+    /// POSTs the rendered payload for `episode` to the webhook
+    pub async fn send(&self, episode: &NewEpisode, options: &HttpOptions) -> Result<()> {
+        post_json(&self.url, self.payload(episode)?, options).await
+    }
+}
+
+/// Per-show webhooks, keyed by Apple show ID
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Webhooks {
+    shows: BTreeMap<String, ShowWebhook>,
+}
+
+impl Webhooks {
+    /// Reads the `[webhooks]` config table: one table per show ID or URL
+    ///
+    /// Each show needs a `url`, and may give its payload as a `template` string
+    /// or a `template_file` path. Templates are compiled here, so mistakes are
+    /// reported when the config is loaded rather than when an episode arrives.
+    pub fn parse(table: &toml::Table) -> Result<Webhooks> {
+        let mut shows = BTreeMap::new();
+        for (show, settings) in table {
+            let invalid = |message: &str| Error::Config(format!("webhook '{}': {}", show, message));
+            // Episodes are matched by show ID, so any other key could never fire
+            let id = match show_id_from_url(show) {
+                Some(id) => id,
+                None if !show.is_empty() && show.chars().all(|c| c.is_ascii_digit()) => {
+                    show.clone()
+                }
+                None => return Err(invalid("expected an Apple show ID or show URL")),
+            };
+            let toml::Value::Table(settings) = settings else {
+                return Err(invalid("expected a table with a url"));
+            };
+            if let Some(key) = settings
+                .keys()
+                .find(|key| !matches!(key.as_str(), "url" | "template" | "template_file"))
+            {
+                return Err(invalid(&format!("unknown setting '{}'", key)));
+            }
+
+            let url = match settings.get("url") {
+                Some(toml::Value::String(url)) => url.clone(),
+                _ => return Err(invalid("expected a url")),
+            };
+            let template = match (settings.get("template"), settings.get("template_file")) {
+                (Some(_), Some(_)) => {
+                    return Err(invalid("give either template or template_file, not both"))
+                }
+                (Some(toml::Value::String(template)), None) => Some(template.clone()),
+                (None, Some(toml::Value::String(path))) => Some(
+                    fs::read_to_string(expand_home(path))
+                        .map_err(|e| invalid(&format!("cannot read {}: {}", path, e)))?,
+                ),
+                (None, None) => None,
+                _ => return Err(invalid("template and template_file must be strings")),
+            };
+            if let Some(template) = &template {
+                compile(template).map_err(|e| invalid(&template_message(e)))?;
+            }
+
+            shows.insert(id, ShowWebhook { url, template });
+        }
+        Ok(Webhooks { shows })
+    }
+
+    /// The webhook configured for the show at `show_url`, if any
+    pub fn for_show(&self, show_url: &str) -> Option<&ShowWebhook> {
+        self.shows.get(&show_id_from_url(show_url)?)
+    }
+}
+
+/// A Tera instance holding just `template`
+fn compile(template: &str) -> tera::Result<Tera> {
+    let mut tera = Tera::default();
+    tera.add_raw_template(TEMPLATE_NAME, template)?;
+    Ok(tera)
+}
+
+/// Tera errors keep their cause in the source chain; the message alone is often just "Failed to render"
+fn template_message(e: tera::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(&e);
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Metadata;
+    use crate::show::EpisodeSummary;
+
+    fn episode() -> NewEpisode {
+        NewEpisode {
+            show_url: "https://podcasts.apple.com/us/podcast/the-daily/id1200361736".to_string(),
+            show_title: "The Daily".to_string(),
+            episode: EpisodeSummary {
+                id: "1000631244436".to_string(),
+                title: "A \"Quoted\" Title".to_string(),
+                url: "https://podcasts.apple.com/us/podcast/id1200361736?i=1000631244436"
+                    .to_string(),
+                release_date: Some("2023-10-13".to_string()),
+            },
+            directory: "output/episodes/1000631244436".to_string(),
+            transcript: true,
+            metadata: Metadata {
+                duration_seconds: Some(1800),
+                ..Metadata::default()
+            },
+        }
+    }
+
+    /// Unit test - per-show webhooks render their templates into JSON payloads
+    #[test]
+    fn test_webhook_templates_render_per_show() {
+        // Given a Discord webhook with a template for one show and a plain one for another
+        let table: toml::Table = toml::from_str(
+            r#"
+            [1200361736]
+            url = "https://discord.example.com/api/webhooks/1"
+            template = '''{% set content = "New: " ~ title %}{"content": {{ content | json_encode() }}, "minutes": {{ metadata.duration_seconds / 60 }}}'''
+
+            ["https://podcasts.apple.com/us/podcast/other/id840986946"]
+            url = "https://n8n.example.com/webhook/abc"
+            "#,
+        )
+        .unwrap();
+        let webhooks = Webhooks::parse(&table).unwrap();
+
+        // When we look up each show's webhook and render the episode
+        let discord = webhooks.for_show(&episode().show_url).unwrap();
+        let payload: serde_json::Value =
+            serde_json::from_str(&discord.payload(&episode()).unwrap()).unwrap();
+        let n8n = webhooks
+            .for_show("https://podcasts.apple.com/gb/podcast/id840986946")
+            .unwrap();
+
+        // Then the template shapes the payload, quoting safely, and the plain webhook gets the episode JSON
+        assert_eq!(payload["content"], "New: A \"Quoted\" Title");
+        assert_eq!(payload["minutes"].as_f64(), Some(30.0));
+        let plain: serde_json::Value =
+            serde_json::from_str(&n8n.payload(&episode()).unwrap()).unwrap();
+        assert_eq!(plain["id"], "1000631244436");
+        assert!(webhooks
+            .for_show("https://podcasts.apple.com/us/podcast/id1")
+            .is_none());
+    }
+
+    /// Unit test - broken webhook settings and templates are reported
+    #[test]
+    fn test_webhook_errors() {
+        // Given webhooks with a syntax error, an unknown setting, no url, a key that names
+        // no show, and output that is not JSON
+        let parse = |text: &str| Webhooks::parse(&toml::from_str(text).unwrap());
+
+        // When we parse or render them
+        // Then each is a config error naming the problem
+        let broken = parse("[1]\nurl = \"http://x\"\ntemplate = \"{{ title\"").unwrap_err();
+        assert!(broken.to_string().contains("webhook '1'"));
+        assert!(parse("[1]\nurl = \"http://x\"\nheaders = 1")
+            .unwrap_err()
+            .to_string()
+            .contains("unknown setting 'headers'"));
+        assert!(parse("[1]\ntemplate = \"{}\"")
+            .unwrap_err()
+            .to_string()
+            .contains("expected a url"));
+        assert!(parse("[\"my-show\"]\nurl = \"http://x\"")
+            .unwrap_err()
+            .to_string()
+            .contains("expected an Apple show ID or show URL"));
+        let unquoted =
+            parse("[1200361736]\nurl = \"http://x\"\ntemplate = \"{\\\"t\\\": {{ title }}}\"")
+                .unwrap();
+        let error = unquoted
+            .for_show(&episode().show_url)
+            .unwrap()
+            .payload(&episode())
+            .unwrap_err();
+        assert!(error.to_string().contains("did not render JSON"));
+    }
+}
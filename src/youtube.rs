@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::error::{Error, Result};
+use crate::fetch::{get_text, HttpOptions};
+use crate::metadata::clean_text;
+use crate::transcript::TranscriptCue;
+
+/// File the comparison is saved to in the episode directory
+pub const YOUTUBE_FILE: &str = "youtube.json";
+
+/// Consecutive words that must match for a phrase to anchor the alignment
+const ANCHOR_WORDS: usize = 4;
+
+/// Fewest anchors an alignment needs before its offset is trusted
+const MIN_ANCHORS: usize = 3;
+
+/// How far, in seconds, an anchor may sit from the offset and still agree with it
+///
+/// Word times are interpolated across cues on both sides, so anchors of a
+/// perfectly aligned pair still scatter by a second or so.
+const AGREEMENT_TOLERANCE: f64 = 2.0;
+
+/// Parses a YouTube video URL into its 11-character video ID
+///
+/// Accepts `watch?v=`, `youtu.be/`, `/live/`, `/shorts/` and `/embed/` links.
+pub fn parse_video_id(raw: &str) -> std::result::Result<String, String> {
+    let invalid = || {
+        format!(
+            "Invalid YouTube URL '{}': expected a youtube.com/watch?v=<id> or youtu.be/<id> link",
+            raw
+        )
+    };
+    let url = Url::parse(raw.trim()).map_err(|_| invalid())?;
+    let host = url
+        .host_str()
+        .unwrap_or_default()
+        .trim_start_matches("www.");
+    let mut segments = url.path_segments().into_iter().flatten();
+    let id = match host {
+        "youtu.be" => segments.next().map(str::to_string),
+        "youtube.com" | "m.youtube.com" | "music.youtube.com" => match segments.next() {
+            Some("watch") => url
+                .query_pairs()
+                .find(|(key, _)| key == "v")
+                .map(|(_, id)| id.into_owned()),
+            Some("live" | "shorts" | "embed") => segments.next().map(str::to_string),
+            _ => None,
+        },
+        _ => None,
+    };
+    id.filter(|id| {
+        id.len() == 11
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    })
+    .ok_or_else(invalid)
+}
+
+/// The canonical watch URL of a video
+pub fn watch_url(video_id: &str) -> String {
+    format!("https://www.youtube.com/watch?v={}", video_id)
+}
+
+/// A caption track listed on a video's watch page
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct CaptionTrack {
+    #[serde(rename = "baseUrl")]
+    pub url: String,
+    #[serde(rename = "languageCode", default)]
+    pub language: String,
+    /// `asr` for YouTube's automatic captions
+    #[serde(default)]
+    pub kind: Option<String>,
+}
+
+impl CaptionTrack {
+    /// Whether YouTube generated the track by speech recognition
+    pub fn is_auto(&self) -> bool {
+        self.kind.as_deref() == Some("asr")
+    }
+}
+
+/// Finds the caption tracks in a watch page's embedded player response
+pub fn find_caption_tracks(html: &str) -> Vec<CaptionTrack> {
+    const KEY: &str = "\"captionTracks\":";
+    html.find(KEY)
+        .and_then(|start| {
+            serde_json::Deserializer::from_str(&html[start + KEY.len()..])
+                .into_iter::<Vec<CaptionTrack>>()
+                .next()?
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+/// Picks the track to align against: auto-captions in `language`, then any auto-captions,
+/// then any track in `language`, then the first track
+pub fn pick_caption_track<'a>(
+    tracks: &'a [CaptionTrack],
+    language: Option<&str>,
+) -> Option<&'a CaptionTrack> {
+    let in_language = |track: &&CaptionTrack| {
+        language.is_some_and(|language| {
+            let primary = track.language.split('-').next().unwrap_or_default();
+            primary.eq_ignore_ascii_case(language)
+        })
+    };
+    tracks
+        .iter()
+        .filter(|track| track.is_auto())
+        .find(in_language)
+        .or_else(|| tracks.iter().find(|track| track.is_auto()))
+        .or_else(|| tracks.iter().find(in_language))
+        .or_else(|| tracks.first())
+}
+
+/// Parses YouTube timedtext captions into cues
+///
+/// Handles the classic format (`<text start dur>` in seconds) and srv3
+/// (`<p t d>` in milliseconds). Caption text is entity-escaped twice, so
+/// entities left after XML parsing are decoded too.
+pub fn parse_captions(xml: &str) -> Result<Vec<TranscriptCue>> {
+    let document = roxmltree::Document::parse(xml)
+        .map_err(|e| Error::Parse(format!("Failed to parse YouTube captions: {}", e)))?;
+    let number = |node: roxmltree::Node, name: &str| {
+        node.attribute(name)
+            .and_then(|value| value.parse::<f64>().ok())
+    };
+
+    let mut cues = Vec::new();
+    for node in document.descendants().filter(|node| node.is_element()) {
+        let timing = match node.tag_name().name() {
+            "text" => number(node, "start").map(|start| (start, number(node, "dur"))),
+            "p" => number(node, "t").map(|t| (t / 1000.0, number(node, "d").map(|d| d / 1000.0))),
+            _ => None,
+        };
+        let Some((begin, duration)) = timing else {
+            continue;
+        };
+        let raw: String = node
+            .descendants()
+            .filter(|node| node.is_text())
+            .filter_map(|node| node.text())
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let text = clean_text(&raw);
+        if text.is_empty() {
+            continue;
+        }
+        cues.push(TranscriptCue {
+            begin,
+            end: begin + duration.unwrap_or_default(),
+            speaker: None,
+            text,
+        });
+    }
+    Ok(cues)
+}
+
+/// Downloads a video's captions, preferring auto-captions in `language`
+///
+/// Returns the track used along with its cues.
+pub async fn fetch_captions(
+    video_id: &str,
+    language: Option<&str>,
+    options: &HttpOptions,
+) -> Result<(CaptionTrack, Vec<TranscriptCue>)> {
+    let html = get_text(&watch_url(video_id), options).await?;
+    let tracks = find_caption_tracks(&html);
+    let track = pick_caption_track(&tracks, language)
+        .cloned()
+        .ok_or_else(|| Error::Parse(format!("No captions found for video {}", video_id)))?;
+    let cues = parse_captions(&get_text(&track.url, options).await?)?;
+    Ok((track, cues))
+}
+
+/// How the YouTube version's timeline relates to the Apple one
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Alignment {
+    /// Seconds to add to an Apple time to get the YouTube time of the same moment
+    pub offset_seconds: f64,
+    /// Phrases found once in each transcript, each giving an offset estimate
+    pub anchors: usize,
+    /// Share of anchors within two seconds of the offset; low values mean the versions were edited differently
+    pub agreement: f64,
+}
+
+/// Normalized words of the cues, each with a start time spread evenly across its cue
+fn timed_words(cues: &[TranscriptCue]) -> Vec<(String, f64)> {
+    let mut words = Vec::new();
+    for cue in cues {
+        let cue_words: Vec<String> = cue
+            .text
+            .split_whitespace()
+            .map(|word| {
+                word.chars()
+                    .filter(|c| c.is_alphanumeric())
+                    .flat_map(char::to_lowercase)
+                    .collect::<String>()
+            })
+            .filter(|word| !word.is_empty())
+            .collect();
+        let step = (cue.end - cue.begin).max(0.0) / cue_words.len().max(1) as f64;
+        for (index, word) in cue_words.into_iter().enumerate() {
+            words.push((word, cue.begin + step * index as f64));
+        }
+    }
+    words
+}
+
+/// Start times of the phrases of `ANCHOR_WORDS` words that occur exactly once
+fn unique_phrases(words: &[(String, f64)]) -> HashMap<String, Option<f64>> {
+    let mut phrases: HashMap<String, Option<f64>> = HashMap::new();
+    for window in words.windows(ANCHOR_WORDS) {
+        let phrase: Vec<&str> = window.iter().map(|(word, _)| word.as_str()).collect();
+        phrases
+            .entry(phrase.join(" "))
+            .and_modify(|time| *time = None)
+            .or_insert(Some(window[0].1));
+    }
+    phrases
+}
+
+/// Aligns the Apple transcript against the YouTube captions of the same episode
+///
+/// Every phrase spoken once in both gives an offset estimate; the alignment
+/// is the average of those within `AGREEMENT_TOLERANCE` of their median.
+/// Returns `None` when fewer than `MIN_ANCHORS` phrases match.
+pub fn align(apple: &[TranscriptCue], youtube: &[TranscriptCue]) -> Option<Alignment> {
+    let youtube_phrases = unique_phrases(&timed_words(youtube));
+    let mut offsets: Vec<f64> = unique_phrases(&timed_words(apple))
+        .into_iter()
+        .filter_map(|(phrase, apple_time)| {
+            Some(youtube_phrases.get(&phrase).copied().flatten()? - apple_time?)
+        })
+        .collect();
+    if offsets.len() < MIN_ANCHORS {
+        return None;
+    }
+
+    offsets.sort_by(f64::total_cmp);
+    let median = offsets[offsets.len() / 2];
+    let agreeing: Vec<f64> = offsets
+        .iter()
+        .copied()
+        .filter(|offset| (offset - median).abs() <= AGREEMENT_TOLERANCE)
+        .collect();
+    let mean = agreeing.iter().sum::<f64>() / agreeing.len() as f64;
+    Some(Alignment {
+        offset_seconds: (mean * 100.0).round() / 100.0,
+        anchors: offsets.len(),
+        agreement: (agreeing.len() as f64 / offsets.len() as f64 * 100.0).round() / 100.0,
+    })
+}
+
+/// An episode's YouTube version and the offset between the two, as saved in `youtube.json`
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct YoutubeComparison {
+    pub video_id: String,
+    pub video_url: String,
+    pub captions_language: String,
+    /// Whether the captions were YouTube's automatic ones
+    pub auto_captions: bool,
+    #[serde(flatten)]
+    pub alignment: Alignment,
+}
+
+impl YoutubeComparison {
+    /// A YouTube link to the moment `apple_seconds` into the Apple episode
+    pub fn link_at(&self, apple_seconds: f64) -> String {
+        let seconds = (apple_seconds + self.alignment.offset_seconds).max(0.0) as u64;
+        format!("https://youtu.be/{}?t={}", self.video_id, seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(begin: f64, end: f64, text: &str) -> TranscriptCue {
+        TranscriptCue {
+            begin,
+            end,
+            speaker: None,
+            text: text.to_string(),
+        }
+    }
+
+    /// Unit test - parse_video_id accepts the common YouTube link shapes
+    #[test]
+    fn test_parse_video_id() {
+        // Given watch, short, live and non-YouTube links
+        // When we parse them
+        // Then YouTube links yield the video ID and others are rejected
+        let id = "dQw4w9WgXcQ";
+        for url in [
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42s",
+            "https://youtu.be/dQw4w9WgXcQ?si=abc",
+            "https://m.youtube.com/live/dQw4w9WgXcQ",
+            "https://youtube.com/shorts/dQw4w9WgXcQ",
+        ] {
+            assert_eq!(parse_video_id(url), Ok(id.to_string()), "{}", url);
+        }
+        assert!(parse_video_id("https://vimeo.com/dQw4w9WgXcQ").is_err());
+        assert!(parse_video_id("https://www.youtube.com/watch?v=short").is_err());
+    }
+
+    /// Unit test - caption tracks are found in the watch page and auto-captions preferred
+    #[test]
+    fn test_find_and_pick_caption_tracks() {
+        // Given a watch page with manual English and auto-generated English and German tracks
+        let html = r#"<script>var ytInitialPlayerResponse = {"captions":{"playerCaptionsTracklistRenderer":{"captionTracks":[
+            {"baseUrl":"https://www.youtube.com/api/timedtext?v=x&lang=en","languageCode":"en"},
+            {"baseUrl":"https://www.youtube.com/api/timedtext?v=x&lang=de&kind=asr","languageCode":"de","kind":"asr"},
+            {"baseUrl":"https://www.youtube.com/api/timedtext?v=x&lang=en&kind=asr","languageCode":"en","kind":"asr"}
+        ]}}};</script>"#;
+
+        // When we find the tracks and pick one for an English transcript
+        let tracks = find_caption_tracks(html);
+        let picked = pick_caption_track(&tracks, Some("en")).unwrap();
+
+        // Then the English auto-captions win, with their URL unescaped
+        assert_eq!(tracks.len(), 3);
+        assert_eq!(
+            picked.url,
+            "https://www.youtube.com/api/timedtext?v=x&lang=en&kind=asr"
+        );
+        assert_eq!(pick_caption_track(&tracks, None).unwrap().language, "de");
+        assert!(find_caption_tracks("<html></html>").is_empty());
+    }
+
+    /// Unit test - parse_captions reads classic and srv3 timedtext
+    #[test]
+    fn test_parse_captions() {
+        // Given classic captions with doubly escaped text, and srv3 captions
+        let classic = r#"<?xml version="1.0" encoding="utf-8" ?><transcript><text start="1.5" dur="2.25">it&amp;#39;s   here</text><text start="4" dur="1"></text></transcript>"#;
+        let srv3 = r#"<timedtext format="3"><body><p t="61000" d="1500"><s>hello</s><s> world</s></p></body></timedtext>"#;
+
+        // When we parse them
+        // Then each timed element is a cue with decoded, normalized text
+        assert_eq!(
+            parse_captions(classic).unwrap(),
+            vec![cue(1.5, 3.75, "it's here")]
+        );
+        assert_eq!(
+            parse_captions(srv3).unwrap(),
+            vec![cue(61.0, 62.5, "hello world")]
+        );
+        assert!(parse_captions("not xml").is_err());
+    }
+
+    /// Unit test - align finds the offset between two timelines of the same speech
+    #[test]
+    fn test_align_detects_offset() {
+        // Given an Apple transcript, and YouTube captions of it 30.5 seconds later in different cue breaks
+        let apple = vec![
+            cue(0.0, 4.0, "Welcome back to the show, everyone."),
+            cue(
+                4.0,
+                10.0,
+                "Today we are talking about how podcasts reach new listeners.",
+            ),
+            cue(10.0, 16.0, "My guest has built three shows from scratch."),
+        ];
+        let youtube = vec![
+            cue(20.0, 30.5, "intro music"),
+            cue(30.5, 34.5, "welcome back to the show everyone"),
+            cue(34.5, 37.5, "today we are talking about how"),
+            cue(37.5, 40.5, "podcasts reach new listeners"),
+            cue(40.5, 46.5, "my guest has built three shows from scratch"),
+        ];
+
+        // When we align them
+        let alignment = align(&apple, &youtube).unwrap();
+
+        // Then YouTube runs about 30.5 seconds ahead, and unrelated text does not align
+        assert!(
+            (alignment.offset_seconds - 30.5).abs() < 1.0,
+            "{:?}",
+            alignment
+        );
+        assert!(alignment.anchors >= MIN_ANCHORS);
+        assert!(alignment.agreement >= 0.9);
+        assert_eq!(
+            align(&apple, &[cue(0.0, 5.0, "something else entirely")]),
+            None
+        );
+
+        // And Apple times map to YouTube links
+        let comparison = YoutubeComparison {
+            video_id: "dQw4w9WgXcQ".to_string(),
+            video_url: watch_url("dQw4w9WgXcQ"),
+            captions_language: "en".to_string(),
+            auto_captions: true,
+            alignment: Alignment {
+                offset_seconds: 30.5,
+                anchors: 10,
+                agreement: 1.0,
+            },
+        };
+        assert_eq!(
+            comparison.link_at(60.0),
+            "https://youtu.be/dQw4w9WgXcQ?t=90"
+        );
+    }
+}
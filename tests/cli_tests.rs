@@ -1,14 +1,72 @@
+mod mock_server;
+
 use assert_cmd::Command;
+use mock_server::{fixture, MockResponse, MockServer, EPISODE_PAGE, LOOKUP, SHOW_PAGE, TRANSCRIPT};
 use predicates::prelude::*;
 
+/// Episode URL whose page the mock server serves
+const MOCK_EPISODE: &str = "https://podcasts.apple.com/us/podcast/id840986946?i=1000631244436";
+
+/// Show URL whose page, listing [`MOCK_EPISODE`], the mock server serves
+const MOCK_SHOW: &str =
+    "https://podcasts.apple.com/us/podcast/the-future-of-podcasting/id840986946";
+
+/// A mock server answering the episode page, transcript and audio from the recorded fixtures
+fn mock_episode_server() -> MockServer {
+    MockServer::builder()
+        .route(
+            "/us/podcast/id840986946",
+            vec![MockResponse::ok(fixture(EPISODE_PAGE))],
+        )
+        .route(
+            "/us/podcast/the-future-of-podcasting/id840986946",
+            vec![MockResponse::ok(fixture(SHOW_PAGE))],
+        )
+        .route(
+            "/ttd/2023-10-13.ttml",
+            vec![MockResponse::ok(fixture(TRANSCRIPT))],
+        )
+        .route(
+            "/ttd/2023-10-13.mp3",
+            vec![MockResponse::ok(vec![0u8; 2048])],
+        )
+        .start()
+}
+
+/// The CLI, run in `dir` with every request sent to `server` and no cache or stats
+fn mocked(server: &MockServer, dir: &std::path::Path) -> Command {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.current_dir(dir)
+        .env("APPLECAST_ORIGIN_OVERRIDES", server.overrides())
+        .env("APPLECAST_NO_STATS", "true")
+        .arg("--no-cache");
+    cmd
+}
+
+/// Every file under `dir`, recursively
+fn walk_files(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(walk_files(&path));
+        } else {
+            files.push(path);
+        }
+    }
+    files
+}
+
 /// Scenario - Valid URL provided
-/// Given a valid Apple Podcasts URL
+/// Given a valid Apple Podcasts URL served by the mock server
 /// When user runs `applecast-cli <url>`
 /// Then output shows "📥 Received URL: <url>"
 #[test]
 fn test_valid_url_prints_received_message() {
-    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
-    let test_url = "https://podcasts.apple.com/us/podcast/id840986946?i=1000631244436";
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+    let mut cmd = mocked(&server, temp_dir.path());
+    let test_url = MOCK_EPISODE;
 
     cmd.arg(test_url)
         .assert()
@@ -45,12 +103,14 @@ fn test_invalid_url_shows_error() {
 }
 
 /// Scenario - Valid Apple Podcasts show URL
-/// Given a valid Apple Podcasts show URL (without episode ID)
+/// Given a valid Apple Podcasts show URL (without episode ID) served by the mock server
 /// When user runs `applecast-cli <url>`
 /// Then output shows the URL was received
 #[test]
 fn test_valid_show_url_accepted() {
-    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+    let mut cmd = mocked(&server, temp_dir.path());
     let show_url = "https://podcasts.apple.com/us/podcast/id840986946";
 
     cmd.arg(show_url)
@@ -59,3 +119,2813 @@ fn test_valid_show_url_accepted() {
         .stdout(predicate::str::contains("📥 Received URL:"))
         .stdout(predicate::str::contains(show_url));
 }
+
+/// Scenario - Skip-list requested without ad detection
+/// Given a valid URL and `--skip-list` alone
+/// When user runs `applecast-cli --skip-list <url>`
+/// Then clap reports that `--detect-ads` is required
+#[test]
+fn test_skip_list_requires_detect_ads() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.arg("--skip-list")
+        .arg("https://podcasts.apple.com/us/podcast/id840986946?i=1000631244436")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--detect-ads"));
+}
+
+/// Scenario - Audio tagging requested without an audio download
+/// Given a valid URL and `--tag-audio` alone
+/// When user runs `applecast-cli --tag-audio <url>`
+/// Then clap reports that `--download-audio` is required
+#[test]
+fn test_tag_audio_requires_download_audio() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.arg("--tag-audio")
+        .arg("https://podcasts.apple.com/us/podcast/id840986946?i=1000631244436")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--download-audio"));
+}
+
+/// Scenario - Shell completions
+/// Given a bash user
+/// When user runs `applecast-cli completions bash`
+/// Then a completion script for the binary and its subcommands is printed
+#[test]
+fn test_completions_bash() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["completions", "bash"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("complete -F _applecast-cli"))
+        .stdout(predicate::str::contains("sync"));
+}
+
+/// Scenario - Printing the metadata schema for CI validation
+/// Given no URL
+/// When user runs `applecast-cli --print-schema` and `applecast-cli --print-schema=transcript`
+/// Then each prints a JSON Schema document naming the output it describes
+#[test]
+fn test_print_schema() {
+    for (args, id) in [
+        (vec!["--print-schema"], "urn:applecast-cli:metadata:v1"),
+        (
+            vec!["--print-schema=transcript"],
+            "urn:applecast-cli:transcript:v1",
+        ),
+    ] {
+        let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+        let output = cmd.args(args).output().unwrap();
+        assert!(output.status.success());
+
+        let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(schema["$id"], id);
+        assert_eq!(
+            schema["$schema"],
+            "https://json-schema.org/draft/2020-12/schema"
+        );
+    }
+}
+
+/// Scenario - Network diagnostics
+/// Given any network environment
+/// When user runs `applecast-cli doctor network`
+/// Then each Apple endpoint is reported by name, whatever the outcome
+#[test]
+fn test_doctor_network_reports_each_endpoint() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["doctor", "network"])
+        .assert()
+        .stdout(predicate::str::contains("podcasts.apple.com"))
+        .stdout(predicate::str::contains("amp-api.podcasts.apple.com"))
+        .stdout(predicate::str::contains("mzstatic.com"));
+}
+
+/// Scenario - Malformed custom header
+/// Given a `--header` value without a colon
+/// When user runs `applecast-cli --header bad <url>`
+/// Then the argument is rejected before any request is made
+#[test]
+fn test_malformed_header_is_rejected() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["--header", "bad"])
+        .arg("https://podcasts.apple.com/us/podcast/id840986946?i=1000631244436")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Name: value"));
+}
+
+/// Scenario - Invalid storefront in --locales
+/// Given a `--locales` list containing a non-storefront value
+/// When user runs `applecast-cli --locales us,france <url>`
+/// Then the argument is rejected with a hint about two-letter codes
+#[test]
+fn test_invalid_locale_is_rejected() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["--locales", "us,france"])
+        .arg("https://podcasts.apple.com/us/podcast/id840986946?i=1000631244436")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("two-letter code"));
+}
+
+/// Scenario - Metadata from an already-downloaded page
+/// Given a saved Apple Podcasts episode page with a canonical German URL
+/// When user runs `applecast-cli metadata episode.html`
+/// Then metadata is extracted offline into output/metadata.json
+#[test]
+fn test_metadata_subcommand_reads_saved_html() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        temp_dir.path().join("episode.html"),
+        r#"<html><head>
+        <link rel="canonical" href="https://podcasts.apple.com/de/podcast/id1?i=2">
+        <script id="schema:episode" type="application/ld+json">
+        {"name": "Folge 1", "datePublished": "13. Okt. 2023", "partOfSeries": {"name": "Show"}}
+        </script>
+        </head></html>"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .args(["metadata", "episode.html"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("output/metadata.json"));
+
+    let metadata = std::fs::read_to_string(temp_dir.path().join("output/metadata.json")).unwrap();
+    assert!(metadata.contains("Folge 1"));
+    assert!(metadata.contains("2023-10-13"));
+}
+
+/// Scenario - Custom output directory and name template
+/// Given a saved episode page
+/// When user runs `applecast-cli metadata episode.html --output-dir archive --name-template '{show}/{date}-{title}'`
+/// Then metadata is written under archive/Show/2023-10-13-Folge 1/
+#[test]
+fn test_output_dir_and_name_template_place_artifacts() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        temp_dir.path().join("episode.html"),
+        r#"<html><head>
+        <link rel="canonical" href="https://podcasts.apple.com/de/podcast/id1?i=2">
+        <script id="schema:episode" type="application/ld+json">
+        {"name": "Folge 1", "datePublished": "13. Okt. 2023", "partOfSeries": {"name": "Show"}}
+        </script>
+        </head></html>"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .args([
+            "metadata",
+            "episode.html",
+            "--output-dir",
+            "archive",
+            "--name-template",
+            "{show}/{date}-{title}",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "archive/Show/2023-10-13-Folge 1/metadata.json",
+        ));
+
+    assert!(temp_dir
+        .path()
+        .join("archive/Show/2023-10-13-Folge 1/metadata.json")
+        .is_file());
+    assert!(!temp_dir.path().join("output").exists());
+}
+
+/// Scenario - Unknown name template placeholder
+/// Given a `--name-template` with a placeholder the tool does not know
+/// When user runs `applecast-cli fetch <url> --name-template '{season}'`
+/// Then error message listing the valid placeholders
+#[test]
+fn test_unknown_name_template_placeholder_is_rejected() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "fetch",
+        "https://podcasts.apple.com/us/podcast/id1?i=2",
+        "--name-template",
+        "{season}",
+    ])
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("unknown placeholder '{season}'"));
+}
+
+/// Scenario - Invalid timeout
+/// Given a `--timeout` that is not a positive number of seconds
+/// When user runs `applecast-cli fetch <url> --timeout 0`
+/// Then error message explaining the expected value
+#[test]
+fn test_zero_timeout_is_rejected() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "fetch",
+        "https://podcasts.apple.com/us/podcast/id1?i=2",
+        "--timeout",
+        "0",
+    ])
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains(
+        "expected a positive number of seconds",
+    ));
+}
+
+/// Scenario - Archive commands need a database
+/// Given no `--db` option
+/// When user runs `applecast-cli list`
+/// Then error message asking for --db
+#[test]
+fn test_list_requires_db() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.arg("list")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("pass --db"));
+}
+
+/// Scenario - New episodes for a script
+/// Given `--print-new` with a format other than ndjson
+/// When user runs `applecast-cli --db <file> sync <show-url> --print-new csv`
+/// Then a usage error names the supported format before anything is fetched
+#[test]
+fn test_sync_print_new_rejects_unknown_format() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db = temp_dir.path().join("archive.sqlite");
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "--db",
+        db.to_str().unwrap(),
+        "sync",
+        "https://podcasts.apple.com/us/podcast/the-daily/id1200361736",
+        "--print-new",
+        "csv",
+    ])
+    .assert()
+    .code(2)
+    .stderr(predicate::str::contains("expected ndjson"));
+}
+
+/// Scenario - A digest of the episodes a sync archived
+/// Given a show with an episode not yet archived
+/// When user runs `applecast-cli --db <file> sync <show-url> --digest html`, twice
+/// Then output/digest.html lists the new episode, and the second sync, finding nothing new, leaves it alone
+#[test]
+fn test_sync_writes_html_digest() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+    let sync = || {
+        let mut cmd = mocked(&server, temp_dir.path());
+        cmd.args([
+            "--db",
+            "archive.sqlite",
+            "sync",
+            MOCK_SHOW,
+            "--digest",
+            "html",
+        ]);
+        cmd
+    };
+
+    sync().assert().success();
+    let digest = temp_dir.path().join("output/digest.html");
+    let html = std::fs::read_to_string(&digest).unwrap();
+    assert!(html.contains("1 new episode(s)"));
+    assert!(html.contains("?i=1000631244436"));
+
+    std::fs::remove_file(&digest).unwrap();
+    sync().assert().success();
+    assert!(!digest.exists());
+}
+
+/// Scenario - Episodes removed upstream
+/// Given a synced show whose next listing drops the archived episode, then lists it again
+/// When user runs `applecast-cli --db <file> sync <show-url>` each time, and `query --removed`
+/// Then the episode is marked removed upstream with its files kept, then unmarked
+#[test]
+fn test_sync_marks_episodes_removed_upstream() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let show = fixture(SHOW_PAGE);
+    let replaced = String::from_utf8(show.clone())
+        .unwrap()
+        .replace("1000631244436", "1000000000001");
+    let server = MockServer::builder()
+        .route(
+            "/us/podcast/id840986946",
+            vec![MockResponse::ok(fixture(EPISODE_PAGE))],
+        )
+        .route(
+            "/us/podcast/the-future-of-podcasting/id840986946",
+            vec![
+                MockResponse::ok(show.clone()),
+                MockResponse::ok(replaced),
+                MockResponse::ok(show),
+            ],
+        )
+        .route(
+            "/ttd/2023-10-13.ttml",
+            vec![MockResponse::ok(fixture(TRANSCRIPT))],
+        )
+        .start();
+    let run = |args: &[&str]| {
+        let mut cmd = mocked(&server, temp_dir.path());
+        cmd.args(["--db", "archive.sqlite"]).args(args);
+        cmd
+    };
+    let removed = || {
+        let output = run(&["--json", "query", "--removed"]).output().unwrap();
+        let episodes: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        episodes
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|episode| episode["id"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    run(&["sync", MOCK_SHOW]).assert().success();
+    assert!(removed().is_empty());
+
+    run(&["sync", MOCK_SHOW])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "1000631244436 is no longer listed by Apple",
+        ));
+    assert_eq!(removed(), ["1000631244436"]);
+    run(&["query", "--removed"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("🚫 removed upstream 20"));
+    assert!(temp_dir
+        .path()
+        .join("output/episodes/1000631244436/metadata.json")
+        .is_file());
+
+    run(&["sync", MOCK_SHOW]).assert().success();
+    assert_eq!(removed(), ["1000000000001"]);
+}
+
+/// Scenario - Report of upstream changes
+/// Given an archived episode whose title is edited on Apple's page before it is fetched again
+/// When user runs `applecast-cli --db <file> report changes --since 30d`
+/// Then the edit is listed with the old title, and the report needs an archive
+#[test]
+fn test_report_changes_lists_edited_titles() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let page = fixture(EPISODE_PAGE);
+    let edited = String::from_utf8(page.clone()).unwrap().replace(
+        "The Future of Podcasting",
+        "The Future of Podcasting (Revised)",
+    );
+    let server = MockServer::builder()
+        .route(
+            "/us/podcast/id840986946",
+            vec![MockResponse::ok(page), MockResponse::ok(edited)],
+        )
+        .route(
+            "/ttd/2023-10-13.ttml",
+            vec![MockResponse::ok(fixture(TRANSCRIPT))],
+        )
+        .start();
+    let run = |args: &[&str]| {
+        let mut cmd = mocked(&server, temp_dir.path());
+        cmd.args(["--db", "archive.sqlite"]).args(args);
+        cmd
+    };
+
+    run(&[MOCK_EPISODE]).assert().success();
+    run(&["--force", MOCK_EPISODE]).assert().success();
+
+    let output = run(&["--json", "report", "changes", "--since", "30d"])
+        .output()
+        .unwrap();
+    let changes: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let titles: Vec<_> = changes
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|change| change["kind"] == "title")
+        .collect();
+    assert_eq!(titles.len(), 1, "{}", changes);
+    assert_eq!(titles[0]["old"], "The Future of Podcasting");
+    assert_eq!(titles[0]["new"], "The Future of Podcasting (Revised)");
+    run(&["report", "changes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("✏️ Title edited (1)"))
+        .stdout(predicate::str::contains("was: The Future of Podcasting"));
+
+    mocked(&server, temp_dir.path())
+        .args(["report", "changes"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("pass --db"));
+}
+
+/// Scenario - Personal tags and ratings
+/// Given an archived episode
+/// When user runs `applecast-cli --db <file> tag add <id> Rust,interview` and `rate <id> 5`
+/// Then `query --tag` and `--min-rating` find it and its exported page carries both
+#[test]
+fn test_tag_and_rate_archived_episode() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+    let run = |args: &[&str]| {
+        let mut cmd = mocked(&server, temp_dir.path());
+        cmd.args(["--db", "archive.sqlite"]).args(args);
+        cmd
+    };
+    run(&[MOCK_EPISODE]).assert().success();
+
+    run(&["tag", "add", "1000631244436", "Rust, interview"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(": interview, rust"));
+    run(&["rate", "1000631244436", "5"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("★★★★★"));
+
+    let output = run(&["--json", "query", "--tag", "rust", "--min-rating", "4"])
+        .output()
+        .unwrap();
+    let episodes: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        episodes[0]["tags"],
+        serde_json::json!(["interview", "rust"])
+    );
+    assert_eq!(episodes[0]["rating"], 5);
+    run(&["query", "--tag", "python"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No archived episodes"));
+
+    run(&["query", "--export", "hugo"]).assert().success();
+    let pages = walk_files(&temp_dir.path().join("output/site"));
+    let page = pages
+        .iter()
+        .find(|page| page.ends_with("index.md"))
+        .unwrap();
+    let page = std::fs::read_to_string(page).unwrap();
+    assert!(page.contains("rating: 5\npersonal_tags: [\"interview\",\"rust\"]"));
+
+    run(&["rate", "1000631244436", "6"]).assert().code(2);
+    run(&["tag", "add", "999", "rust"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("999 is not in the archive"));
+}
+
+/// Scenario - Listening status
+/// Given an archived episode
+/// When user runs `mark played`, `mark unplayed` and `mark import` with an Overcast OPML export
+/// Then `query --played` and `--unplayed` follow what was marked and imported
+#[test]
+fn test_mark_played_and_import_overcast_progress() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+    let run = |args: &[&str]| {
+        let mut cmd = mocked(&server, temp_dir.path());
+        cmd.args(["--db", "archive.sqlite"]).args(args);
+        cmd
+    };
+    let listed = |args: &[&str]| {
+        let output = run(&[&["--json", "query"], args].concat())
+            .output()
+            .unwrap();
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+    };
+    run(&[MOCK_EPISODE]).assert().success();
+    let episode = listed(&[])[0].clone();
+
+    run(&["mark", "played", "1000631244436"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("✅ Played:"));
+    assert!(listed(&["--played"])[0]["played_at"].is_string());
+    assert!(listed(&["--unplayed"]).as_array().unwrap().is_empty());
+    run(&["mark", "unplayed", "1000631244436"])
+        .assert()
+        .success();
+    assert!(listed(&["--played"]).as_array().unwrap().is_empty());
+
+    // Overcast's export names the show and episode; the enclosure URL is its own redirect
+    let opml = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+        <opml version="1.0"><body><outline text="feeds">
+          <outline type="rss" title="{}" text="{}">
+            <outline type="podcast-episode" title="{}" played="0" progress="754" enclosureUrl="https://overcast.example/1.mp3"/>
+            <outline type="podcast-episode" title="Not archived" played="1"/>
+          </outline>
+        </outline></body></opml>"#,
+        episode["show_title"].as_str().unwrap(),
+        episode["show_title"].as_str().unwrap(),
+        episode["title"].as_str().unwrap()
+    );
+    std::fs::write(temp_dir.path().join("overcast.opml"), opml).unwrap();
+    run(&["mark", "import", "overcast.opml"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Marked 0 played and 1 in progress; 1 listened episodes are not in the archive",
+        ));
+    assert_eq!(listed(&["--unplayed"])[0]["progress_seconds"], 754.0);
+
+    std::fs::write(temp_dir.path().join("notes.txt"), "not an export").unwrap();
+    run(&["mark", "import", "notes.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid OPML"));
+}
+
+/// Scenario - Smart lists
+/// Given an archived episode
+/// When user saves `list create to-hear --where 'title ~ podcasting && !played'`
+/// Then `list show` and `list export` follow the episode's listening state each time they run
+#[test]
+fn test_smart_list_is_evaluated_each_time() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+    let run = |args: &[&str]| {
+        let mut cmd = mocked(&server, temp_dir.path());
+        cmd.args(["--db", "archive.sqlite"]).args(args);
+        cmd
+    };
+    let shown = || {
+        let output = run(&["--json", "list", "show", "to-hear"])
+            .output()
+            .unwrap();
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+    };
+    run(&[MOCK_EPISODE]).assert().success();
+
+    run(&[
+        "list",
+        "create",
+        "to-hear",
+        "--where",
+        "title ~ podcasting && !played",
+    ])
+    .assert()
+    .success();
+    run(&["list", "show"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "to-hear  title ~ podcasting && !played",
+        ));
+    assert_eq!(shown()[0]["title"], "The Future of Podcasting");
+
+    run(&["list", "export", "to-hear", "--out", "to-hear.m3u"])
+        .assert()
+        .success();
+    let playlist = std::fs::read_to_string(temp_dir.path().join("to-hear.m3u")).unwrap();
+    assert!(playlist.starts_with("#EXTM3U\n#EXTINF:"));
+    assert!(playlist.contains("The Future of Podcasting\nhttp"));
+
+    // The list is a query, not a snapshot: marking the episode played drops it
+    run(&["mark", "played", "1000631244436"]).assert().success();
+    assert!(shown().as_array().unwrap().is_empty());
+
+    run(&["list", "create", "broken", "--where", "title ~"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected a value at character 8"));
+    run(&["list", "delete", "to-hear"]).assert().success();
+    run(&["list", "show", "to-hear"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No smart list named to-hear"));
+}
+
+/// Scenario - Duration report
+/// Given an archived episode with a transcript
+/// When user runs `report durations --show <id>`
+/// Then each episode is charted with its transcript word count, and an unarchived show is an error
+/// And `--chart` writes an SVG chart, or explains why it cannot
+#[test]
+fn test_report_durations_counts_transcript_words() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+    let run = |args: &[&str]| {
+        let mut cmd = mocked(&server, temp_dir.path());
+        cmd.args(["--db", "archive.sqlite"]).args(args);
+        cmd
+    };
+    run(&[MOCK_EPISODE]).assert().success();
+
+    let output = run(&["--json", "report", "durations", "--show", "840986946"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let episode = &report["episodes"][0];
+    assert_eq!(episode["title"], "The Future of Podcasting");
+    assert!(episode["transcript_words"].as_u64().unwrap() > 0);
+    assert!(episode.get("outliers").is_none());
+
+    run(&["report", "durations", "--show", "840986946"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Episode durations for show 840986946",
+        ))
+        .stdout(predicate::str::contains(" words  The Future of Podcasting"));
+    run(&["report", "durations", "--show", "123"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No archived episodes of show 123"));
+
+    // The mock page gives no duration, so only the changes timeline can be drawn
+    run(&[
+        "report",
+        "durations",
+        "--show",
+        "840986946",
+        "--chart",
+        "durations.svg",
+    ])
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("has a duration to chart"));
+    run(&["report", "changes", "--chart", "charts/changes.png"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("charts are written as SVG"));
+    run(&["report", "changes", "--chart", "charts/changes.svg"])
+        .assert()
+        .success();
+    let svg = std::fs::read_to_string(temp_dir.path().join("charts/changes.svg")).unwrap();
+    assert!(svg.contains("Upstream changes since"));
+}
+
+/// A transcript with a sponsor read from 1:04 until the guest speaks at 1:30
+const SPONSOR_TRANSCRIPT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttm="http://www.w3.org/ns/ttml#metadata">
+  <body><div>
+    <p begin="0.5" end="4.0" ttm:agent="SPEAKER_1"><span begin="0.5" end="4.0">Welcome back to the show.</span></p>
+    <p begin="64.0" end="70.0" ttm:agent="SPEAKER_1"><span begin="64.0" end="70.0">This episode is brought to you by Acme Corp. Use code BOARD for a discount.</span></p>
+    <p begin="90.0" end="95.0" ttm:agent="SPEAKER_2"><span begin="90.0" end="95.0">Thanks for having me.</span></p>
+  </div></body>
+</tt>"#;
+
+/// Scenario - Sponsor report
+/// Given an archived episode whose transcript has a sponsor read with a promo code
+/// When user runs `report sponsors --show <id>`
+/// Then the sponsor is listed with its code and the episode it was read in
+#[test]
+fn test_report_sponsors_lists_promo_codes() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = MockServer::builder()
+        .route(
+            "/us/podcast/id840986946",
+            vec![MockResponse::ok(fixture(EPISODE_PAGE))],
+        )
+        .route(
+            "/ttd/2023-10-13.ttml",
+            vec![MockResponse::ok(SPONSOR_TRANSCRIPT)],
+        )
+        .start();
+    let run = |args: &[&str]| {
+        let mut cmd = mocked(&server, temp_dir.path());
+        cmd.args(["--db", "archive.sqlite"]).args(args);
+        cmd
+    };
+    run(&[MOCK_EPISODE]).assert().success();
+
+    run(&["report", "sponsors", "--show", "840986946"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("💼 Acme Corp (1 episodes"))
+        .stdout(predicate::str::contains("🎟️ BOARD"))
+        .stdout(predicate::str::contains("1:04  Acme Corp"));
+    let output = run(&["--json", "report", "sponsors", "--show", "840986946"])
+        .output()
+        .unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["episodes_scanned"], 1);
+    assert_eq!(report["episodes"][0]["title"], "The Future of Podcasting");
+    assert_eq!(report["sponsors"][0]["promo_codes"][0], "BOARD");
+}
+
+/// Scenario - Cutting ads from downloaded audio
+/// Given an episode whose transcript has a sponsor read, and MP3 audio
+/// When user runs `applecast-cli --detect-ads --download-audio --strip-ads <url>`
+/// Then an ad-free copy of the audio is saved next to the original, which is kept
+#[test]
+fn test_strip_ads_saves_ad_free_audio_copy() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    // 4000 frames of 128 kbit/s 44.1 kHz MPEG-1 layer III, 417 bytes and about 26ms each
+    let mut frame = vec![0u8; 417];
+    frame[..4].copy_from_slice(&[0xff, 0xfb, 0x90, 0x00]);
+    let mp3 = frame.repeat(4000);
+    let server = MockServer::builder()
+        .route(
+            "/us/podcast/id840986946",
+            vec![MockResponse::ok(fixture(EPISODE_PAGE))],
+        )
+        .route(
+            "/ttd/2023-10-13.ttml",
+            vec![MockResponse::ok(SPONSOR_TRANSCRIPT)],
+        )
+        .route("/ttd/2023-10-13.mp3", vec![MockResponse::ok(mp3.clone())])
+        .start();
+
+    mocked(&server, temp_dir.path())
+        .args([
+            "--detect-ads",
+            "--download-audio",
+            "--strip-ads",
+            MOCK_EPISODE,
+        ])
+        .assert()
+        .success();
+
+    let files = walk_files(&temp_dir.path().join("output"));
+    let stripped = files
+        .iter()
+        .find(|path| path.to_string_lossy().ends_with(".no-ads.mp3"))
+        .expect("ad-free copy");
+    let original = stripped.to_string_lossy().replace(".no-ads.mp3", ".mp3");
+    assert_eq!(std::fs::read(original).unwrap().len(), mp3.len());
+    let cut = std::fs::read(stripped).unwrap().len();
+    assert!(cut < mp3.len() && cut > mp3.len() / 2, "{} bytes left", cut);
+    assert_eq!(cut % 417, 0);
+
+    Command::cargo_bin("applecast-cli")
+        .unwrap()
+        .args(["--detect-ads", "--strip-ads", MOCK_EPISODE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--download-audio"));
+}
+
+/// Scenario - An email digest without an SMTP config
+/// Given `--digest email` alone
+/// When user runs `applecast-cli --db <file> sync <show-url> --digest email`
+/// Then a usage error asks for `--smtp-config` before anything is fetched
+#[test]
+fn test_email_digest_requires_smtp_config() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "--db",
+        "archive.sqlite",
+        "sync",
+        "https://podcasts.apple.com/us/podcast/id840986946",
+        "--digest",
+        "email",
+    ])
+    .assert()
+    .code(2)
+    .stderr(predicate::str::contains("--smtp-config"));
+}
+
+/// Scenario - Listing a new archive
+/// Given a `--db` path that does not exist yet
+/// When user runs `applecast-cli --db <path> list --json`
+/// Then the archive is created and an empty list is printed
+#[test]
+fn test_list_creates_empty_archive() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db = temp_dir.path().join("nested/archive.sqlite");
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["--db", db.to_str().unwrap(), "list", "--json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[]"));
+    assert!(db.exists());
+}
+
+/// Scenario - Unknown static site generator
+/// Given an archive
+/// When user runs `applecast-cli --db <file> list --export wordpress`
+/// Then a usage error names the supported formats
+#[test]
+fn test_list_export_rejects_unknown_format() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db = temp_dir.path().join("archive.sqlite");
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "--db",
+        db.to_str().unwrap(),
+        "list",
+        "--export",
+        "wordpress",
+    ])
+    .assert()
+    .code(2)
+    .stderr(predicate::str::contains("expected hugo or jekyll"));
+}
+
+/// Scenario - Boilerplate stripping outside an export
+/// Given a new, empty archive
+/// When user runs `applecast-cli --db <file> list --strip-boilerplate` without `--export`
+/// Then it is rejected as a usage error, since only exported pages are stripped
+#[test]
+fn test_strip_boilerplate_requires_export() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db = temp_dir.path().join("archive.sqlite");
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["--db", db.to_str().unwrap(), "list", "--strip-boilerplate"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("--export <FORMAT>"));
+}
+
+/// Scenario - Unknown short link
+/// Given a new, empty archive
+/// When user runs `applecast-cli --db <file> link e1a2b3`
+/// Then it fails saying no episode has that short ID
+#[test]
+fn test_link_unknown_short_id_fails() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db = temp_dir.path().join("archive.sqlite");
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["--db", db.to_str().unwrap(), "link", "e1a2b3"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(
+            "no archived episode with short ID 'e1a2b3'",
+        ));
+}
+
+/// Scenario - Quote from an archive without transcripts
+/// Given a new, empty archive
+/// When user runs `applecast-cli --db <file> quote`
+/// Then it fails explaining there are no transcripts to quote
+#[test]
+fn test_quote_without_transcripts_fails() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db = temp_dir.path().join("archive.sqlite");
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["--db", db.to_str().unwrap(), "quote"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("No archived transcripts"));
+}
+
+/// Scenario - Search an archive without matches
+/// Given a new, empty archive
+/// When user runs `applecast-cli --db <file> grep "climate change"`
+/// Then it exits 1 like grep, with no matches listed
+#[test]
+fn test_grep_without_matches_exits_one() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db = temp_dir.path().join("archive.sqlite");
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "--db",
+        db.to_str().unwrap(),
+        "--json",
+        "grep",
+        "climate change",
+    ])
+    .assert()
+    .code(1)
+    .stdout(predicate::str::contains("[]"));
+}
+
+/// Scenario - Watch with a too-short interval
+/// Given an `--interval` under a minute
+/// When user runs `applecast-cli watch <show url> --interval 10s`
+/// Then the argument is rejected before any request is made
+#[test]
+fn test_watch_rejects_short_interval() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "watch",
+        "https://podcasts.apple.com/us/podcast/the-daily/id1200361736",
+        "--interval",
+        "10s",
+    ])
+    .assert()
+    .code(2)
+    .stderr(predicate::str::contains("Invalid interval '10s'"));
+}
+
+/// Scenario - Unsupported proxy from the environment
+/// Given APPLECAST_PROXY set to an ftp:// URL
+/// When user runs any command
+/// Then the proxy is rejected as if it had been passed with --proxy
+#[test]
+fn test_unsupported_proxy_env_is_rejected() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.env("APPLECAST_PROXY", "ftp://proxy.example.com")
+        .args(["lookup", "840986946"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "expected an http, https, socks5 or socks5h URL",
+        ));
+}
+
+/// Scenario - Config profile with a command-line override
+/// Given a config file whose profile turns on --json and sets --db
+/// When user runs `applecast-cli --config <file> --profile <name> list --db <other>`
+/// Then --json comes from the profile but the command-line --db wins
+#[test]
+fn test_config_profile_defaults_yield_to_flags() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    let from_config = dir.path().join("config.sqlite");
+    let from_flag = dir.path().join("flag.sqlite");
+    std::fs::write(
+        &config,
+        format!(
+            "[profiles.scripted]\njson = true\ndb = '{}'\n",
+            from_config.display()
+        ),
+    )
+    .unwrap();
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "--config",
+        config.to_str().unwrap(),
+        "--profile",
+        "scripted",
+    ])
+    .args(["list", "--db", from_flag.to_str().unwrap()])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("[]"));
+    assert!(from_flag.exists());
+    assert!(!from_config.exists());
+}
+
+/// Scenario - Unknown config profile
+/// Given a config file without the requested profile
+/// When user runs `applecast-cli --config <file> --profile missing list`
+/// Then the profile is rejected with the ones that exist
+#[test]
+fn test_config_unknown_profile_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "[profiles.work]\nquiet = true\n").unwrap();
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "--config",
+        config.to_str().unwrap(),
+        "--profile",
+        "missing",
+        "list",
+    ])
+    .assert()
+    .code(2)
+    .stderr(predicate::str::contains(
+        "unknown profile 'missing' (available: work)",
+    ));
+}
+
+/// Scenario - First-run setup
+/// Given no config file yet
+/// When user runs `applecast-cli --config <file> init` and answers the questions, then runs it again
+/// Then a commented config file with the answers is written, later runs read it, and it is not overwritten without --force
+#[test]
+fn test_init_writes_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("applecast").join("config.toml");
+    let config_arg = config.to_str().unwrap();
+
+    Command::cargo_bin("applecast-cli")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["--config", config_arg, "init"])
+        .write_stdin("podcasts\nzz9\nde\ny\n\nsrt,md\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Invalid storefront 'zz9': expected a two-letter code",
+        ))
+        .stdout(predicate::str::contains("✅ Config written to"));
+    let text = std::fs::read_to_string(&config).unwrap();
+    assert!(
+        text.contains("# Directory all artifacts are written under\noutput-dir = \"podcasts\"\n")
+    );
+    assert!(text.contains("country = \"de\"\n"));
+    assert!(text.contains("db = \"podcasts/archive.sqlite\"\n"));
+    assert!(text.contains("transcript-format = [\"srt\", \"md\"]\n"));
+    assert!(text.contains("# notify-url = "));
+
+    Command::cargo_bin("applecast-cli")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["--config", config_arg, "--json", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[]"));
+    assert!(dir.path().join("podcasts/archive.sqlite").exists());
+
+    Command::cargo_bin("applecast-cli")
+        .unwrap()
+        .args(["--config", config_arg, "init", "--yes"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("already exists; pass --force"));
+}
+
+/// Scenario - Broken webhook template in the config file
+/// Given a config file whose `[webhooks.<show>]` template does not parse
+/// When user runs `applecast-cli --config <file> list`
+/// Then the config is rejected before anything runs, naming the show
+#[test]
+fn test_config_broken_webhook_template_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    std::fs::write(
+        &config,
+        "[webhooks.840986946]\nurl = \"https://n8n.example.com/webhook/abc\"\ntemplate = \"{{ title\"\n",
+    )
+    .unwrap();
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["--config", config.to_str().unwrap(), "list"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("webhook '840986946'"));
+}
+
+/// Scenario - Refresh of a field that is not extracted
+/// Given a `--fields` list naming a field applecast-cli does not extract
+/// When user runs `applecast-cli refresh --fields description,chapters --db <path>`
+/// Then the field is rejected with the ones that can be refreshed
+#[test]
+fn test_refresh_rejects_unknown_field() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("archive.sqlite");
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["refresh", "--fields", "description,chapters"])
+        .args(["--db", db.to_str().unwrap()])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "Invalid field 'chapters': expected one of episode_title",
+        ));
+}
+
+/// Scenario - Country rewrites the episode URL
+/// Given a US episode URL and `--country gb`
+/// When user runs `applecast-cli --country gb fetch <url>`
+/// Then the British storefront's page is requested
+#[test]
+fn test_country_rewrites_storefront() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "--country",
+        "gb",
+        "--retries",
+        "0",
+        "--timeout",
+        "5",
+        "--no-cache",
+    ])
+    .args(["fetch", "https://podcasts.apple.com/us/podcast/id1?i=2"])
+    .assert()
+    .stdout(predicate::str::contains(
+        "Received URL: https://podcasts.apple.com/gb/podcast/id1?i=2",
+    ));
+}
+
+/// Scenario - Invalid language
+/// Given a language name instead of a code
+/// When user runs `applecast-cli --lang german <url>`
+/// Then the argument is rejected
+#[test]
+fn test_invalid_lang_is_rejected() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "--lang",
+        "german",
+        "https://podcasts.apple.com/us/podcast/id1?i=2",
+    ])
+    .assert()
+    .code(2)
+    .stderr(predicate::str::contains("Invalid language 'german'"));
+}
+
+/// Scenario - Metadata override file
+/// Given a saved episode page and an overrides/<episode-id>.toml correcting its title
+/// When user runs `applecast-cli metadata episode.html`
+/// Then metadata.json has the corrected title and records the extracted one
+#[test]
+fn test_override_file_replaces_extracted_field() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        temp_dir.path().join("episode.html"),
+        r#"<html><head>
+        <link rel="canonical" href="https://podcasts.apple.com/us/podcast/id1?i=2">
+        <script id="schema:episode" type="application/ld+json">
+        {"name": "Epsiode One", "datePublished": "2023-10-13", "partOfSeries": {"name": "Show"}}
+        </script>
+        </head></html>"#,
+    )
+    .unwrap();
+    std::fs::create_dir(temp_dir.path().join("overrides")).unwrap();
+    std::fs::write(
+        temp_dir.path().join("overrides/2.toml"),
+        "episode_title = \"Episode One\"\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .args(["metadata", "episode.html"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Overrode episode_title"));
+
+    let metadata: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(temp_dir.path().join("output/metadata.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(metadata["episode_title"], "Episode One");
+    assert_eq!(metadata["overridden"]["episode_title"], "Epsiode One");
+}
+
+/// Scenario - Config transforms clean extracted fields
+/// Given a config file stripping a tagline from descriptions and a prefix from one show's titles
+/// When user runs `applecast-cli --config config.toml metadata episode.html`
+/// Then metadata.json holds the cleaned fields, and an override still wins over a transform
+#[test]
+fn test_config_transforms_rewrite_fields() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        temp_dir.path().join("episode.html"),
+        r#"<html><head>
+        <link rel="canonical" href="https://podcasts.apple.com/us/podcast/id1?i=2">
+        <script id="schema:episode" type="application/ld+json">
+        {"name": "Ep. 1: Launch", "description": "Launch day. Support us at example.com", "datePublished": "2023-10-13", "partOfSeries": {"name": "Show"}}
+        </script>
+        </head></html>"#,
+    )
+    .unwrap();
+    std::fs::write(
+        temp_dir.path().join("config.toml"),
+        r#"
+        [[transforms."*"]]
+        field = "description"
+        strip = 'Support us at \S+'
+
+        [[transforms.1]]
+        field = "episode_title"
+        replace = '^Ep\. \d+: '
+        "#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .args(["--config", "config.toml", "metadata", "episode.html"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Transformed description, episode_title",
+        ));
+
+    let metadata: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(temp_dir.path().join("output/metadata.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(metadata["episode_title"], "Launch");
+    assert_eq!(metadata["description"], "Launch day.");
+
+    // An override file replaces the transformed value
+    std::fs::create_dir(temp_dir.path().join("overrides")).unwrap();
+    std::fs::write(
+        temp_dir.path().join("overrides/2.toml"),
+        "episode_title = \"Launch Day\"\n",
+    )
+    .unwrap();
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .args(["--config", "config.toml", "metadata", "episode.html"])
+        .assert()
+        .success();
+    let metadata: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(temp_dir.path().join("output/metadata.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(metadata["episode_title"], "Launch Day");
+    assert_eq!(metadata["overridden"]["episode_title"], "Launch");
+}
+
+/// Scenario - Gap report for something that is not an Apple show
+/// Given a `--show` value that is neither a show ID nor an Apple Podcasts URL
+/// When user runs `applecast-cli report gaps --show https://example.com/feed.xml`
+/// Then it is rejected as a usage error before anything is fetched
+#[test]
+fn test_report_gaps_rejects_non_apple_show() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.args(["report", "gaps", "--show", "https://example.com/feed.xml"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("Invalid show"));
+}
+
+/// Scenario - Offline extraction from a directory of saved pages
+/// Given a directory with a saved episode page and its transcript.ttml
+/// When user runs `applecast-cli metadata --from-html saved --transcript-format txt`
+/// Then metadata, the transcript, its segments and its conversion land in episodes/<id> without any network access
+#[test]
+fn test_metadata_from_html_directory() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let saved = temp_dir.path().join("saved/episode-2");
+    std::fs::create_dir_all(&saved).unwrap();
+    std::fs::write(
+        saved.join("episode.html"),
+        r#"<html><head>
+        <link rel="canonical" href="https://podcasts.apple.com/us/podcast/id1?i=2">
+        <script id="schema:episode" type="application/ld+json">
+        {"name": "Episode Two", "datePublished": "2023-10-13", "partOfSeries": {"name": "Show"}}
+        </script>
+        </head></html>"#,
+    )
+    .unwrap();
+    std::fs::write(
+        saved.join("transcript.ttml"),
+        r#"<tt xmlns="http://www.w3.org/ns/ttml" xml:lang="en"><body><div><p begin="0.0s" end="2.0s">Hello there.</p></div></body></tt>"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .args([
+            "metadata",
+            "--from-html",
+            "saved",
+            "--transcript-format",
+            "txt",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 succeeded, 0 failed"));
+
+    let episode_dir = temp_dir.path().join("output/episodes/2");
+    let metadata: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(episode_dir.join("metadata.json")).unwrap())
+            .unwrap();
+    assert_eq!(metadata["episode_title"], "Episode Two");
+    assert_eq!(metadata["transcript_language"], "en");
+    assert!(episode_dir.join("transcript.ttml").exists());
+    let segments: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(episode_dir.join("transcript.segments.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        segments,
+        serde_json::json!([{"speaker": null, "start": 0.0, "end": 2.0, "text": "Hello there."}])
+    );
+    assert!(std::fs::read_to_string(episode_dir.join("transcript.txt"))
+        .unwrap()
+        .contains("Hello there."));
+}
+
+/// Scenario - Accessible transcript export
+/// Given a saved episode page and a transcript with a labelled speaker and a long sentence
+/// When user runs `applecast-cli metadata --from-html episode.html --transcript-file transcript.ttml --a11y --a11y-line-length 24`
+/// Then transcript.a11y.txt labels the turn with the speaker and a spoken start time, wrapped to 24 characters
+#[test]
+fn test_metadata_a11y_transcript() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        temp_dir.path().join("episode.html"),
+        r#"<html><head><script id="schema:episode" type="application/ld+json">
+        {"name": "Episode Two", "partOfSeries": {"name": "Show"}}
+        </script></head></html>"#,
+    )
+    .unwrap();
+    std::fs::write(
+        temp_dir.path().join("transcript.ttml"),
+        r#"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttm="http://www.w3.org/ns/ttml#metadata"><body><div><p begin="75.0s" end="80.0s" ttm:agent="Host">♪ Welcome back to the show about accessible podcasts. ♪</p></div></body></tt>"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .args([
+            "metadata",
+            "--from-html",
+            "episode.html",
+            "--transcript-file",
+            "transcript.ttml",
+            "--a11y",
+            "--a11y-line-length",
+            "24",
+        ])
+        .assert()
+        .success();
+
+    let text = std::fs::read_to_string(temp_dir.path().join("output/transcript.a11y.txt")).unwrap();
+    assert_eq!(
+        text,
+        "Host, at 1 minute, 15\nseconds:\nWelcome back to the show\nabout accessible\npodcasts.\n"
+    );
+}
+
+/// Scenario - Markdown descriptions
+/// Given a saved page whose description is HTML with entities and a link
+/// When user runs `applecast-cli metadata --from-html episode.html --no-transcript --description-format markdown`
+/// Then metadata.json stores the description decoded, with the link in Markdown
+#[test]
+fn test_description_format_markdown() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        temp_dir.path().join("episode.html"),
+        r#"<html><head><script id="schema:episode" type="application/ld+json">
+        {"name": "Episode Two", "partOfSeries": {"name": "Show"},
+         "description": "<p>Q&amp;A with guests</p><p>Notes: <a href=\"https://example.com/notes\">show notes</a></p>"}
+        </script></head></html>"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .args([
+            "metadata",
+            "--from-html",
+            "episode.html",
+            "--no-transcript",
+            "--description-format",
+            "markdown",
+        ])
+        .assert()
+        .success();
+
+    let metadata: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(temp_dir.path().join("output/metadata.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        metadata["description"],
+        "Q&A with guests\n\nNotes: [show notes](https://example.com/notes)"
+    );
+}
+
+/// Scenario - Stripping trackers from descriptions
+/// Given a saved page whose description embeds a tracking pixel, a tagged link and one image twice
+/// When user runs `applecast-cli metadata --from-html episode.html --no-transcript --description-format html --sanitize-description archive`
+/// Then the stored description has no pixel or tracking parameters, one local image, and metadata.json records what changed
+#[test]
+fn test_sanitize_description_archive() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        temp_dir.path().join("episode.html"),
+        r#"<html><head><script id="schema:episode" type="application/ld+json">
+        {"name": "Episode Two", "partOfSeries": {"name": "Show"},
+         "description": "<p><a href=\"https://example.com/notes?utm_source=rss\">notes</a></p><img src=\"https://example.com/a.png\"><img src=\"https://example.com/a.png\"><img src=\"https://pdst.fm/e/pixel.gif\">"}
+        </script></head></html>"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .args([
+            "metadata",
+            "--from-html",
+            "episode.html",
+            "--no-transcript",
+            "--description-format",
+            "html",
+            "--sanitize-description",
+            "archive",
+        ])
+        .assert()
+        .success();
+
+    let metadata: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(temp_dir.path().join("output/metadata.json")).unwrap(),
+    )
+    .unwrap();
+    let description = metadata["description"].as_str().unwrap();
+    assert!(description.starts_with(
+        r#"<p><a href="https://example.com/notes">notes</a></p><img src="output/images/"#
+    ));
+    assert_eq!(description.matches("<img").count(), 1);
+    assert_eq!(
+        metadata["sanitized"]["trackers"],
+        serde_json::json!(["https://pdst.fm/e/pixel.gif"])
+    );
+    assert_eq!(
+        metadata["sanitized"]["duplicates"],
+        serde_json::json!(["https://example.com/a.png"])
+    );
+    assert_eq!(
+        metadata["sanitized"]["stripped_urls"],
+        serde_json::json!(["https://example.com/notes?utm_source=rss"])
+    );
+}
+
+/// Scenario - CSV and front-matter metadata for a spreadsheet and a static site
+/// Given a saved episode page
+/// When user runs `applecast-cli metadata --from-html episode.html --no-transcript --metadata-format csv,frontmatter-md` twice
+/// Then metadata.csv keeps one row for the page under one header, byte for byte across runs, and metadata.md has the description as its body
+#[test]
+fn test_metadata_format_csv_and_frontmatter() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        temp_dir.path().join("episode.html"),
+        r#"<html><head><script id="schema:episode" type="application/ld+json">
+        {"name": "Episode Two", "partOfSeries": {"name": "Show"}, "description": "We talk shop."}
+        </script></head></html>"#,
+    )
+    .unwrap();
+
+    let mut runs = Vec::new();
+    for _ in 0..2 {
+        let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .args([
+                "metadata",
+                "--from-html",
+                "episode.html",
+                "--no-transcript",
+                "--metadata-format",
+                "csv,frontmatter-md",
+            ])
+            .assert()
+            .success();
+        runs.push(std::fs::read(temp_dir.path().join("output/metadata.csv")).unwrap());
+    }
+
+    assert_eq!(runs[0], runs[1]);
+    let csv = String::from_utf8(runs.remove(1)).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("url,episode_title,show_title,"));
+    assert!(lines[1].contains(",Episode Two,Show,"));
+    assert!(lines[1].ends_with(",We talk shop."));
+
+    let page = std::fs::read_to_string(temp_dir.path().join("output/metadata.md")).unwrap();
+    assert!(page.contains("\nepisode_title: \"Episode Two\"\n"));
+    assert!(page.ends_with("---\n\nWe talk shop.\n"));
+}
+
+/// Scenario - Low-power extraction on a Raspberry Pi
+/// Given a saved episode page with JSON-LD and serialized data
+/// When user runs `applecast-cli metadata episode.html --profile-lite`
+/// Then metadata.json has the schema details and the serialized data's audio URL and GUID
+#[test]
+fn test_metadata_profile_lite() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        temp_dir.path().join("episode.html"),
+        r#"<html><head><script id="schema:episode" type="application/ld+json">
+        {"name": "Episode Two", "datePublished": "2023-10-13", "partOfSeries": {"name": "Show"}}
+        </script></head><body>
+        <script type="application/json" id="serialized-server-data">[{"data":{"episode":{
+          "guid": "abc-123", "streamUrl": "https://audio.example.com/2.mp3"}}}]</script>
+        </body></html>"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .args(["metadata", "episode.html", "--profile-lite"])
+        .assert()
+        .success();
+
+    let metadata: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(temp_dir.path().join("output/metadata.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(metadata["episode_title"], "Episode Two");
+    assert_eq!(metadata["show_title"], "Show");
+    assert_eq!(metadata["guid"], "abc-123");
+    assert_eq!(metadata["audio_url"], "https://audio.example.com/2.mp3");
+}
+
+/// Scenario - JSON logs for a log pipeline
+/// Given a saved episode page
+/// When user runs `applecast-cli metadata --from-html episode.html --no-transcript --log-format json -v`
+/// Then every progress and debug line is a JSON object on stderr and stdout stays empty
+#[test]
+fn test_log_format_json() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        temp_dir.path().join("episode.html"),
+        r#"<html><head><script id="schema:episode" type="application/ld+json">
+        {"name": "Episode Two", "partOfSeries": {"name": "Show"}}
+        </script></head></html>"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    let output = cmd
+        .current_dir(temp_dir.path())
+        .args([
+            "metadata",
+            "--from-html",
+            "episode.html",
+            "--no-transcript",
+            "--log-format",
+            "json",
+            "-v",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    let levels: Vec<String> = String::from_utf8(output.stderr)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let event: serde_json::Value = serde_json::from_str(line).unwrap();
+            event["level"].as_str().unwrap().to_string()
+        })
+        .collect();
+    assert!(levels.iter().any(|level| level == "INFO"));
+    assert!(levels.iter().any(|level| level == "DEBUG"));
+}
+
+/// Scenario - Feed for an invalid URL
+/// Given a show argument that is not a URL
+/// When user runs `applecast-cli feed notaurl --opml <file>`
+/// Then error message for the URL and no OPML file is written
+#[test]
+fn test_feed_rejects_invalid_url() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let opml = temp_dir.path().join("shows.opml");
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["feed", "notaurl", "--opml", opml.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid URL format"));
+    assert!(!opml.exists());
+}
+
+/// Scenario - RSS feed of newly archived episodes
+/// Given an episode archived from the mock server
+/// When user runs `applecast-cli --db archive feed new-items --out new.xml`
+/// Then new.xml is an RSS feed with the episode as its item
+#[test]
+fn test_feed_new_items_writes_rss() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+    std::fs::create_dir_all(temp_dir.path().join("archive")).unwrap();
+    mocked(&server, temp_dir.path())
+        .args(["--db", "archive", MOCK_EPISODE])
+        .assert()
+        .success();
+
+    mocked(&server, temp_dir.path())
+        .args(["--db", "archive", "feed", "new-items", "--out", "new.xml"])
+        .assert()
+        .success();
+
+    let rss = std::fs::read_to_string(temp_dir.path().join("new.xml")).unwrap();
+    assert!(rss.contains("<rss version=\"2.0\">"));
+    assert!(rss.contains("<guid isPermaLink=\"false\">1000631244436</guid>"));
+    assert!(rss.contains("<pubDate>"));
+
+    // Without an archive there is nothing to list
+    mocked(&server, temp_dir.path())
+        .args(["feed", "new-items", "--out", "new.xml"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("pass --db"));
+}
+
+/// Scenario - Lookup with an invalid ID
+/// Given an ID that is neither numeric nor a URL
+/// When user runs `applecast-cli lookup abc`
+/// Then error message explaining what is expected
+#[test]
+fn test_lookup_rejects_non_numeric_id() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["lookup", "abc"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("neither an Apple ID nor a URL"));
+}
+
+/// Scenario - Unknown metadata source
+/// Given a `--source` other than scrape, api or auto
+/// When user runs `applecast-cli metadata <url> --source web`
+/// Then error message listing the valid sources
+#[test]
+fn test_unknown_source_is_rejected() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "metadata",
+        "https://podcasts.apple.com/us/podcast/id1?i=2",
+        "--source",
+        "web",
+    ])
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("expected scrape, api or auto"));
+}
+
+/// Scenario - Conflicting cache options
+/// Given both `--no-cache` and `--refresh`
+/// When user runs `applecast-cli fetch <url> --no-cache --refresh`
+/// Then error message saying the options cannot be combined
+#[test]
+fn test_no_cache_conflicts_with_refresh() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "fetch",
+        "https://podcasts.apple.com/us/podcast/id1?i=2",
+        "--no-cache",
+        "--refresh",
+    ])
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("cannot be used with"));
+}
+
+/// Scenario - Invalid artwork size
+/// Given a `--download-artwork` size that is not WIDTHxHEIGHT
+/// When user runs `applecast-cli --download-artwork=big <url>`
+/// Then error message explaining the expected format
+#[test]
+fn test_invalid_artwork_size_is_rejected() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "--download-artwork=big",
+        "https://podcasts.apple.com/us/podcast/id1?i=2",
+    ])
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("expected WIDTHxHEIGHT"));
+}
+
+/// Scenario - TTS command without an output placeholder
+/// Given a `--tts-command` that does not say where to write the audio
+/// When user runs `applecast-cli --tts-notes --tts-command say <url>`
+/// Then error message asking for the {output} placeholder
+#[test]
+fn test_tts_command_requires_output_placeholder() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "--tts-notes",
+        "--tts-command",
+        "say",
+        "https://podcasts.apple.com/us/podcast/id1?i=2",
+    ])
+    .assert()
+    .code(2)
+    .stderr(predicate::str::contains("{output} placeholder"));
+}
+
+/// Scenario - Machine-readable output
+/// Given a saved episode page
+/// When user runs `applecast-cli metadata episode.html --json`
+/// Then stdout is a single JSON document with the metadata and output paths
+#[test]
+fn test_json_flag_prints_report_on_stdout() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        temp_dir.path().join("episode.html"),
+        r#"<html><head>
+        <link rel="canonical" href="https://podcasts.apple.com/de/podcast/id1?i=2">
+        <script id="schema:episode" type="application/ld+json">
+        {"name": "Folge 1", "datePublished": "13. Okt. 2023", "partOfSeries": {"name": "Show"}}
+        </script>
+        </head></html>"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    let output = cmd
+        .current_dir(temp_dir.path())
+        .args(["metadata", "episode.html", "--json"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        report["url"],
+        "https://podcasts.apple.com/de/podcast/id1?i=2"
+    );
+    assert_eq!(report["metadata"]["episode_title"], "Folge 1");
+    assert_eq!(report["outputs"][0], "output/metadata.json");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("📄 Read HTML from episode.html"));
+}
+
+/// Scenario - Network failures have their own exit code
+/// Given an episode URL and a proxy that refuses connections
+/// When user runs `applecast-cli fetch <url> --proxy http://127.0.0.1:9 --quiet`
+/// Then it exits with status 3 and prints only the error
+#[test]
+fn test_network_failure_exit_code() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "fetch",
+        "https://podcasts.apple.com/us/podcast/id1?i=2",
+        "--proxy",
+        "http://127.0.0.1:9",
+        "--retries",
+        "0",
+        "--no-cache",
+        "--quiet",
+    ])
+    .assert()
+    .code(3)
+    .stdout(predicate::str::is_empty())
+    .stderr(predicate::str::contains("Failed to fetch URL"));
+}
+
+/// Scenario - A URL from another site
+/// Given a URL that is not an Apple Podcasts link
+/// When user runs `applecast-cli https://example.com`
+/// Then it fails before fetching, saying it is not an Apple Podcasts URL
+#[test]
+fn test_non_apple_url_rejected_early() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.arg("https://example.com")
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("Received URL").not())
+        .stderr(predicate::str::contains("Not an Apple Podcasts URL"));
+}
+
+/// Scenario - Episode download concurrency must be positive
+/// Given an episode URL
+/// When user runs `applecast-cli --download-audio --concurrency 0 <url>`
+/// Then argument parsing fails and names the invalid value
+#[test]
+fn test_episode_concurrency_must_be_positive() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "--download-audio",
+        "--concurrency",
+        "0",
+        "https://podcasts.apple.com/us/podcast/id1?i=2",
+    ])
+    .assert()
+    .code(2)
+    .stderr(predicate::str::contains("Invalid concurrency '0'"));
+}
+
+/// Scenario - Batch run continues past failing URLs
+/// Given a URL list on stdin whose entries all fail
+/// When user runs `applecast-cli batch --input - --concurrency 2 --report-format csv`
+/// Then every URL is attempted and a CSV report lists each failure
+#[test]
+fn test_batch_reports_each_failure() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .args([
+            "batch",
+            "--input",
+            "-",
+            "--concurrency",
+            "2",
+            "--report-format",
+            "csv",
+        ])
+        .write_stdin("# nightly archive\nnot-a-url\nhttps://one.invalid/\n\nhttps://two.invalid/\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("3 URL(s) to process"))
+        .stdout(predicate::str::contains("0 succeeded, 3 failed"));
+
+    let report = std::fs::read_to_string(temp_dir.path().join("output/batch-report.csv")).unwrap();
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines[0], "url,status,directory,transcript,error");
+    assert!(lines[1].starts_with("not-a-url,failed,,false,"));
+    assert!(lines[2].starts_with("https://one.invalid/,failed,"));
+    assert!(lines[3].starts_with("https://two.invalid/,failed,"));
+}
+
+/// Scenario - Committing a batch run to a git archive
+/// Given a URL list whose only entry fails
+/// When user runs `applecast-cli --git-archive batch --input -` twice
+/// Then the output directory becomes a git repository with one commit holding the report
+#[test]
+fn test_batch_git_archive_commits_report() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+
+    for expected in ["📚 Committed", "📚 Nothing changed"] {
+        let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+        cmd.current_dir(temp_dir.path())
+            .args(["--git-archive", "batch", "--input", "-"])
+            .write_stdin("not-a-url\n")
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(expected));
+    }
+
+    let log = std::process::Command::new("git")
+        .args(["-C", "output", "log", "--format=%s", "--name-only"])
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+    let log = String::from_utf8(log.stdout).unwrap();
+    assert!(log.starts_with("Batch: 0 succeeded, 1 failed, 0 skipped"));
+    assert!(log.contains("batch-report.json"));
+    assert!(log.contains(".gitignore"));
+}
+
+/// Scenario - Merging an archive from another machine
+/// Given an episode archived on a desktop, with its files under the desktop's archive root
+/// When user runs `applecast-cli --db laptop archive merge desktop`
+/// Then the episode is listed from the laptop archive, with its files copied under the laptop root
+#[test]
+fn test_archive_merge_imports_episodes() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+    for machine in ["desktop", "laptop"] {
+        std::fs::create_dir_all(temp_dir.path().join(machine)).unwrap();
+    }
+    mocked(&server, temp_dir.path())
+        .args([
+            "--db",
+            "desktop",
+            "--output-dir",
+            "desktop/output",
+            MOCK_EPISODE,
+        ])
+        .assert()
+        .success();
+
+    mocked(&server, temp_dir.path())
+        .args(["--db", "laptop", "archive", "merge", "desktop"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("➕ 1000631244436"))
+        .stdout(predicate::str::contains(
+            "Added 1, updated 0, kept 0, unchanged 0",
+        ));
+
+    let output = mocked(&server, temp_dir.path())
+        .args(["--db", "laptop", "--json", "list"])
+        .output()
+        .unwrap();
+    let episodes: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let directory = episodes[0]["directory"].as_str().unwrap();
+    assert!(directory.contains("laptop/output"));
+    assert!(std::path::Path::new(directory)
+        .join("metadata.json")
+        .is_file());
+
+    // Merging again changes nothing, and an archive cannot be merged into itself
+    mocked(&server, temp_dir.path())
+        .args(["--db", "laptop", "archive", "merge", "desktop"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("unchanged 1"));
+    mocked(&server, temp_dir.path())
+        .args([
+            "--db",
+            "laptop",
+            "archive",
+            "merge",
+            "laptop/archive.sqlite",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("into itself"));
+}
+
+/// Scenario - Moving an archive between machines as a bundle
+/// Given an archived episode
+/// When user runs `archive export --out snapshot.tar.zst` and `archive import snapshot.tar.zst` into another archive
+/// Then the episode and its files are in the other archive
+#[test]
+fn test_archive_export_and_import() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+    for machine in ["desktop", "laptop"] {
+        std::fs::create_dir_all(temp_dir.path().join(machine)).unwrap();
+    }
+    mocked(&server, temp_dir.path())
+        .args([
+            "--db",
+            "desktop",
+            "--output-dir",
+            "desktop/output",
+            MOCK_EPISODE,
+        ])
+        .assert()
+        .success();
+
+    mocked(&server, temp_dir.path())
+        .args([
+            "--db",
+            "desktop",
+            "archive",
+            "export",
+            "--out",
+            "snapshot.tar.zst",
+            "--since",
+            "2000-01-01",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Exported 1 episode(s)"));
+    assert!(temp_dir.path().join("snapshot.tar.zst").is_file());
+
+    mocked(&server, temp_dir.path())
+        .args(["--db", "laptop", "archive", "import", "snapshot.tar.zst"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("➕ 1000631244436"));
+    let output = mocked(&server, temp_dir.path())
+        .args(["--db", "laptop", "--json", "list"])
+        .output()
+        .unwrap();
+    let episodes: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let directory = episodes[0]["directory"].as_str().unwrap();
+    assert!(directory.contains("laptop/output"));
+    assert!(std::path::Path::new(directory)
+        .join("metadata.json")
+        .is_file());
+
+    // A show filter matching nothing exports an empty bundle
+    mocked(&server, temp_dir.path())
+        .args([
+            "--db",
+            "desktop",
+            "archive",
+            "export",
+            "--out",
+            "empty.tar.zst",
+            "--show",
+            "1",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Exported 0 episode(s)"));
+}
+
+/// Scenario - Planning a batch re-run without fetching anything
+/// Given one episode saved unchanged, one whose metadata was edited by hand, one never saved and a show URL
+/// When user runs `applecast-cli batch --input - --skip-existing --dry-run`
+/// Then each URL is planned as skip, refuse, fetch or enumerate and nothing is written
+#[test]
+fn test_batch_dry_run_plans_skips() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let manifest = r#"{"url":"u","transcript":false,"artifacts":{"metadata.json":{"size":2,"sha256":"44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"}}}"#;
+    for (id, metadata) in [("2", "{}"), ("3", "[]")] {
+        let dir = temp_dir.path().join("output/episodes").join(id);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("metadata.json"), metadata).unwrap();
+        std::fs::write(dir.join("manifest.json"), manifest).unwrap();
+    }
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .args(["batch", "--input", "-", "--skip-existing", "--dry-run"])
+        .write_stdin("https://podcasts.apple.com/us/podcast/id1?i=2
+https://podcasts.apple.com/us/podcast/id1?i=3
+https://podcasts.apple.com/us/podcast/id1?i=4
+https://podcasts.apple.com/us/podcast/id1
+")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "skip      https://podcasts.apple.com/us/podcast/id1?i=2 -> output/episodes/2",
+        ))
+        .stdout(predicate::str::contains(
+            "refuse    https://podcasts.apple.com/us/podcast/id1?i=3 -> output/episodes/3 (metadata.json changed; pass --force to overwrite)",
+        ))
+        .stdout(predicate::str::contains(
+            "fetch     https://podcasts.apple.com/us/podcast/id1?i=4 -> output/episodes/4",
+        ))
+        .stdout(predicate::str::contains(
+            "enumerate https://podcasts.apple.com/us/podcast/id1 -> (episodes listed when run)",
+        ))
+        .stdout(predicate::str::contains("URL(s) to process").not());
+
+    assert!(!temp_dir.path().join("output/batch-report.json").exists());
+    assert!(!temp_dir.path().join("output/episodes/4").exists());
+}
+
+/// Scenario - Re-running a batch after deleting one artifact
+/// Given an episode saved by a batch run, whose transcript was then deleted
+/// When user runs `applecast-cli batch --input - --skip-existing`, first with `--dry-run`
+/// Then it is planned as a repair, and only the transcript is downloaded again, parsed from the saved page
+#[test]
+fn test_batch_repairs_missing_artifacts() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+    let batch = |args: &[&str]| {
+        let mut cmd = mocked(&server, temp_dir.path());
+        cmd.args(["batch", "--input", "-", "--skip-existing"])
+            .args(args)
+            .write_stdin(format!("{}\n", MOCK_EPISODE));
+        cmd
+    };
+    batch(&[]).assert().success();
+    let dir = temp_dir.path().join("output/episodes/1000631244436");
+    let metadata = std::fs::read_to_string(dir.join("metadata.json")).unwrap();
+    std::fs::remove_file(dir.join("transcript.ttml")).unwrap();
+
+    batch(&["--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "repair    https://podcasts.apple.com/us/podcast/id840986946?i=1000631244436 -> output/episodes/1000631244436 (transcript.ttml missing)",
+        ));
+    batch(&[]).assert().success();
+
+    assert!(dir.join("transcript.ttml").is_file());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("metadata.json")).unwrap(),
+        metadata
+    );
+    assert_eq!(server.hits("/us/podcast/id840986946"), 1);
+    assert_eq!(server.hits("/ttd/2023-10-13.ttml"), 2);
+    batch(&["--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("skip      "));
+}
+
+/// Scenario - Keeping a backup-friendly store next to the output
+/// Given a batch run with `--backup-friendly`
+/// When the same batch is run again with `--force`
+/// Then every saved file is in output/backup/objects once, and the snapshot is not repeated
+#[test]
+fn test_batch_backup_friendly_store_is_append_only() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+    let batch = |args: &[&str]| {
+        let mut cmd = mocked(&server, temp_dir.path());
+        cmd.args(["batch", "--input", "-", "--backup-friendly"])
+            .args(args)
+            .write_stdin(format!("{}\n", MOCK_EPISODE));
+        cmd
+    };
+    batch(&[]).assert().success();
+    let store = temp_dir.path().join("output/backup");
+    let count = |dir: &str| walk_files(&store.join(dir)).len();
+    let objects = count("objects");
+    assert!(objects >= 3);
+    assert_eq!(count("manifests"), 1);
+
+    batch(&["--force"]).assert().success();
+    assert_eq!(count("objects"), objects);
+    assert_eq!(count("manifests"), 1);
+}
+
+/// Scenario - Legacy iTunes and Apple Music links in a batch
+/// Given old itunes.apple.com, music.apple.com and viewPodcast links
+/// When user runs `applecast-cli batch --input - --dry-run`
+/// Then each is planned under its current podcasts.apple.com URL
+#[test]
+fn test_batch_unwraps_legacy_urls() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .args(["batch", "--input", "-", "--dry-run"])
+        .write_stdin(
+            "https://itunes.apple.com/us/podcast/the-show/id1?mt=2&i=2
+https://music.apple.com/gb/podcast/the-show/id1?i=3
+https://itunes.apple.com/WebObjects/MZStore.woa/wa/viewPodcast?id=1
+",
+        )
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "fetch     https://podcasts.apple.com/us/podcast/the-show/id1?i=2 -> output/episodes/2",
+        ))
+        .stdout(predicate::str::contains(
+            "fetch     https://podcasts.apple.com/gb/podcast/the-show/id1?i=3 -> output/episodes/3",
+        ))
+        .stdout(predicate::str::contains(
+            "enumerate https://podcasts.apple.com/podcast/id1 -> (episodes listed when run)",
+        ));
+}
+
+/// Scenario - One stalled episode does not stall the batch
+/// Given a proxy that accepts connections but never answers
+/// When user runs `applecast-cli --proxy <stalled> --fetch-timeout 1 --deadline 2 batch --input - --concurrency 1`
+/// Then the first episode fails its fetch budget, the second hits the run deadline and the run still finishes
+#[test]
+fn test_batch_stage_timeout_and_deadline() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let held: Vec<_> = listener.incoming().collect();
+        drop(held);
+    });
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .args([
+            "--proxy",
+            &proxy,
+            "--retries",
+            "0",
+            "--fetch-timeout",
+            "1",
+            "--deadline",
+            "2",
+            "batch",
+            "--input",
+            "-",
+            "--concurrency",
+            "1",
+        ])
+        .write_stdin("https://podcasts.apple.com/us/podcast/id1?i=2\nhttps://podcasts.apple.com/us/podcast/id1?i=3\nhttps://podcasts.apple.com/us/podcast/id1?i=4\n")
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("fetch stage timed out after 1s"))
+        .stderr(predicate::str::contains("Run deadline reached"))
+        .stdout(predicate::str::contains("0 succeeded, 3 failed"));
+}
+
+/// Scenario - Comparing two page snapshots during parser maintenance
+/// Given an old page with JSON-LD and a new page with only meta tags
+/// When user runs `applecast-cli devtools diff-extract old.html new.html`
+/// Then the changed field and the selectors that stopped or started matching are shown
+#[test]
+fn test_devtools_diff_extract_shows_changes() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        temp_dir.path().join("old.html"),
+        r#"<html><head><script id="schema:episode" type="application/ld+json">{"name": "Ep 1", "partOfSeries": {"name": "Show"}}</script></head></html>"#,
+    )
+    .unwrap();
+    std::fs::write(
+        temp_dir.path().join("new.html"),
+        r#"<html><head><meta property="og:title" content="Ep 1 new"><meta property="og:site_name" content="Show"></head></html>"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.current_dir(temp_dir.path())
+        .args(["devtools", "diff-extract", "old.html", "new.html"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "~ episode_title: \"Ep 1\" → \"Ep 1 new\"",
+        ))
+        .stdout(predicate::str::contains(
+            "✗ script[id='schema:episode']: 1 → 0",
+        ));
+}
+
+/// Scenario - Measuring extractor coverage over the fixture corpus
+/// Given the saved page fixtures in the repository
+/// When user runs `applecast-cli devtools coverage --json`
+/// Then the report counts every page and credits the meta tag fallback
+#[test]
+fn test_devtools_coverage_reports_fixture_corpus() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    let output = cmd
+        .args(["devtools", "coverage", "tests/fixtures/pages", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["pages"], 3);
+    assert!(report["strategies"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|entry| entry["strategy"] == "meta-tags" && entry["hits"] == 3));
+}
+
+/// Scenario - Single-step subcommand with an invalid URL
+/// Given an invalid URL string
+/// When user runs `applecast-cli transcript not-a-url`
+/// Then error message about invalid URL
+#[test]
+fn test_transcript_subcommand_rejects_invalid_url() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["transcript", "not-a-valid-url"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid URL"));
+}
+
+/// Scenario - Invalid --since date for a show crawl
+/// Given a show URL and a `--since` value that is not YYYY-MM-DD
+/// When user runs `applecast-cli show --since yesterday <url>`
+/// Then the argument is rejected before any request is made
+#[test]
+fn test_show_since_requires_iso_date() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["show", "--since", "yesterday"])
+        .arg("https://podcasts.apple.com/us/podcast/id840986946")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("YYYY-MM-DD"));
+}
+
+/// Scenario - Unknown transcript format
+/// Given a `--transcript-format` list containing an unsupported format
+/// When user runs `applecast-cli --transcript-format srt,docx <url>`
+/// Then the argument is rejected with the list of supported formats
+#[test]
+fn test_unknown_transcript_format_is_rejected() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["--transcript-format", "srt,docx"])
+        .arg("https://podcasts.apple.com/us/podcast/id840986946?i=1000631244436")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "srt, vtt, txt, md, json, podcast-json, html or a11y",
+        ));
+}
+
+/// Scenario - Spotting extraction drift in the local strategy stats
+/// Given a stats file where JSON-LD found every title before but few recently
+/// When user runs `applecast-cli devtools strategy-stats` with APPLECAST_STATS_FILE pointing at it
+/// Then the strategy is flagged as succeeding less often, and --no-stats turns the stats off
+#[test]
+fn test_devtools_strategy_stats_flags_drift() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let stats_path = temp_dir.path().join("strategy-stats.json");
+    std::fs::write(
+        &stats_path,
+        r#"{"days": {
+            "2000-01-01": {"pages": 20, "strategies": {"episode_title": {
+                "json-ld:serialized-server-data": {"succeeded": 20, "failed": 0},
+                "meta-tags": {"succeeded": 20, "failed": 0}}}},
+            "2999-01-01": {"pages": 10, "strategies": {"episode_title": {
+                "json-ld:serialized-server-data": {"succeeded": 2, "failed": 8},
+                "meta-tags": {"succeeded": 10, "failed": 0}}}}
+        }}"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.env("APPLECAST_STATS_FILE", &stats_path)
+        .args(["devtools", "strategy-stats"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Strategy stats since 2000-01-01"))
+        .stdout(predicate::str::contains("2/10  20%"))
+        .stdout(predicate::str::contains(
+            "Apple may be changing its pages: episode_title via json-ld:serialized-server-data",
+        ));
+
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+    cmd.args(["--no-stats", "devtools", "strategy-stats"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("strategy stats are off"));
+}
+
+/// Scenario - Compare against a non-YouTube link
+/// Given an episode URL and a video link that is not on YouTube
+/// When user runs `applecast-cli compare <url> --youtube https://vimeo.com/123`
+/// Then the argument is rejected before anything is fetched
+#[test]
+fn test_compare_rejects_non_youtube_url() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args([
+        "compare",
+        "https://podcasts.apple.com/us/podcast/id840986946?i=1000631244436",
+        "--youtube",
+        "https://vimeo.com/123",
+    ])
+    .assert()
+    .code(2)
+    .stderr(predicate::str::contains("Invalid YouTube URL"));
+}
+
+/// Scenario - Container healthcheck
+/// Given any network environment
+/// When user runs `applecast-cli healthcheck` in an empty directory
+/// Then the output directory and network checks are both reported
+#[test]
+fn test_healthcheck_reports_each_check() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.current_dir(temp_dir.path())
+        .arg("healthcheck")
+        .assert()
+        .stdout(predicate::str::contains("✅ output: output is writable"))
+        .stdout(predicate::str::contains(
+            "network: https://podcasts.apple.com/",
+        ));
+}
+
+/// Scenario - Healthy container
+/// Given a valid config, a sound archive and Apple Podcasts served by the mock server
+/// When user runs `applecast-cli --config <file> healthcheck --db <archive>`
+/// Then every check passes, the connectivity probe goes through the origin overrides, and the exit status is 0
+#[test]
+fn test_healthcheck_passes_every_check() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = MockServer::builder()
+        .route("/", vec![MockResponse::ok("Apple Podcasts")])
+        .start();
+    let config = temp_dir.path().join("config.toml");
+    std::fs::write(&config, "country = \"gb\"\n").unwrap();
+    let db = temp_dir.path().join("archive.sqlite");
+    let db_arg = db.to_str().unwrap();
+    Command::cargo_bin("applecast-cli")
+        .unwrap()
+        .args(["list", "--db", db_arg])
+        .assert()
+        .success();
+
+    mocked(&server, temp_dir.path())
+        .args(["--config", config.to_str().unwrap()])
+        .args(["healthcheck", "--db", db_arg])
+        .assert()
+        .code(0)
+        .stdout(predicate::str::contains("✅ config: "))
+        .stdout(predicate::str::contains("✅ archive: "))
+        .stdout(predicate::str::contains(
+            "✅ network: https://podcasts.apple.com/ reachable",
+        ));
+    assert_eq!(server.hits("/"), 1);
+}
+
+/// Scenario - Healthcheck with a broken config
+/// Given a config file that is not valid TOML
+/// When user runs `applecast-cli --config <file> healthcheck`
+/// Then the config check fails and the exit status is 1, not a usage error
+#[test]
+fn test_healthcheck_fails_on_broken_config() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let config = temp_dir.path().join("config.toml");
+    std::fs::write(&config, "country = [unterminated\n").unwrap();
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.current_dir(temp_dir.path())
+        .args(["--config", config.to_str().unwrap(), "healthcheck"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("❌ config: Invalid config file"));
+}
+
+/// Scenario - Healthcheck with a corrupt archive
+/// Given an archive path holding a file that is not a SQLite database
+/// When user runs `applecast-cli healthcheck --db <file>`
+/// Then the archive integrity check fails and the exit status is 1
+#[test]
+fn test_healthcheck_fails_on_corrupt_archive() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db = temp_dir.path().join("archive.sqlite");
+    std::fs::write(&db, "this is not a database, only some plain text").unwrap();
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.current_dir(temp_dir.path())
+        .args(["healthcheck", "--db", db.to_str().unwrap()])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("❌ archive: "));
+}
+
+/// Scenario - Archiving an episode end to end without the network
+/// Given the mock server serving a recorded episode page, its transcript and audio
+/// When user runs `applecast-cli --download-audio --transcript-format srt <url>`
+/// Then metadata, transcript, converted transcript and audio are all written from the mock's responses
+#[test]
+fn test_pipeline_against_mock_server() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+
+    mocked(&server, temp_dir.path())
+        .args([
+            "--download-audio",
+            "--transcript-format",
+            "srt",
+            MOCK_EPISODE,
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("✅ Transcript converted"));
+
+    let output = temp_dir.path().join("output");
+    let metadata = std::fs::read_to_string(output.join("metadata.json")).unwrap();
+    assert!(metadata.contains("\"episode_title\": \"The Future of Podcasting\""));
+    let srt = std::fs::read_to_string(output.join("transcript.srt")).unwrap();
+    assert!(srt.contains("Welcome back to the show."));
+    let audio = output.join("2023-10-13 - The Future of Podcasting.mp3");
+    assert_eq!(std::fs::metadata(audio).unwrap().len(), 2048);
+    assert_eq!(
+        server.requests()[0],
+        "GET /us/podcast/id840986946?i=1000631244436"
+    );
+    assert_eq!(server.hits("/ttd/2023-10-13.ttml"), 1);
+}
+
+/// Scenario - Apple answers with server errors before the page
+/// Given the mock server failing the episode page with 503 twice
+/// When user runs `applecast-cli --retry-delay 1 <url>`
+/// Then the fetch is retried with a notice and the run succeeds on the third attempt
+#[test]
+fn test_mock_server_retries_server_errors() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = MockServer::builder()
+        .route(
+            "/us/podcast/id840986946",
+            vec![
+                MockResponse::status(503),
+                MockResponse::status(503),
+                MockResponse::ok(fixture(EPISODE_PAGE)),
+            ],
+        )
+        .start();
+
+    mocked(&server, temp_dir.path())
+        .args(["--retry-delay", "1", "--no-transcript", MOCK_EPISODE])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "503 Service Unavailable; retrying in",
+        ))
+        .stdout(predicate::str::contains("✅ Metadata extracted"));
+    assert_eq!(server.hits("/us/podcast/id840986946"), 3);
+}
+
+/// Scenario - Apple rate limits the page with a short Retry-After
+/// Given the mock server answering the episode page with a 429 and `Retry-After: 1` once
+/// When user runs `applecast-cli <url>`
+/// Then the window is waited out with a notice and the page is fetched
+#[test]
+fn test_mock_server_waits_out_rate_limit() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = MockServer::builder()
+        .route(
+            "/us/podcast/id840986946",
+            vec![
+                MockResponse::status(429).header("Retry-After", "1"),
+                MockResponse::ok(fixture(EPISODE_PAGE)),
+            ],
+        )
+        .start();
+
+    mocked(&server, temp_dir.path())
+        .args(["--no-transcript", MOCK_EPISODE])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Rate limited by 127.0.0.1; resuming in 1s",
+        ));
+    assert_eq!(server.hits("/us/podcast/id840986946"), 2);
+}
+
+/// Scenario - Rate limiting in the JSON event stream
+/// Given the mock server answering the episode page with a 429 and `Retry-After: 1` once
+/// When user runs `applecast-cli --log-format json <url>`
+/// Then a `rate_limited` event names the host and when requests resume
+#[test]
+fn test_rate_limit_is_a_json_event() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = MockServer::builder()
+        .route(
+            "/us/podcast/id840986946",
+            vec![
+                MockResponse::status(429).header("Retry-After", "1"),
+                MockResponse::ok(fixture(EPISODE_PAGE)),
+            ],
+        )
+        .start();
+
+    let output = mocked(&server, temp_dir.path())
+        .args(["--log-format", "json", "--no-transcript", MOCK_EPISODE])
+        .assert()
+        .success()
+        .get_output()
+        .stderr
+        .clone();
+    let event = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .find(|line| line["fields"]["event"] == "rate_limited")
+        .expect("a rate_limited event");
+    assert_eq!(event["level"], "WARN");
+    assert_eq!(event["fields"]["host"], "127.0.0.1");
+    assert_eq!(event["fields"]["wait_secs"], 1);
+    assert!(event["fields"]["resume_at"]
+        .as_str()
+        .unwrap()
+        .ends_with(" GMT"));
+}
+
+/// Scenario - Apple moves an episode page
+/// Given the mock server redirecting the episode page to another path
+/// When user runs `applecast-cli <url>`
+/// Then the redirect is followed and the moved page is parsed
+#[test]
+fn test_mock_server_follows_redirects() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = MockServer::builder()
+        .route(
+            "/us/podcast/id840986946",
+            vec![MockResponse::redirect(301, "/us/podcast/moved/id840986946")],
+        )
+        .route(
+            "/us/podcast/moved/id840986946",
+            vec![MockResponse::ok(fixture(EPISODE_PAGE))],
+        )
+        .start();
+
+    mocked(&server, temp_dir.path())
+        .args(["--no-transcript", MOCK_EPISODE])
+        .assert()
+        .success();
+    let metadata = std::fs::read_to_string(temp_dir.path().join("output/metadata.json")).unwrap();
+    assert!(metadata.contains("Tech Talk Daily"));
+    assert_eq!(server.hits("/us/podcast/moved/id840986946"), 1);
+}
+
+/// Scenario - Looking up a show in a recorded iTunes API response
+/// Given the mock server answering the iTunes lookup API
+/// When user runs `applecast-cli lookup <episode url>`
+/// Then the show and the episode are listed from the recorded response
+#[test]
+fn test_lookup_against_mock_api() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = MockServer::builder()
+        .route("/lookup", vec![MockResponse::ok(fixture(LOOKUP))])
+        .start();
+
+    mocked(&server, temp_dir.path())
+        .args(["lookup", MOCK_EPISODE])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "🎙️ Tech Talk Daily — Tech Talk Media (id 840986946)",
+        ))
+        .stdout(predicate::str::contains(
+            "1000631244436  2023-10-13  The Future of Podcasting",
+        ));
+    assert!(server.base().starts_with("http://127.0.0.1:"));
+}
+
+/// Scenario - Archiving with compressed snapshots and reading them back
+/// Given the mock server serving an episode
+/// When user runs `applecast-cli --compress gzip --db archive.sqlite <url>`, then `grep` and `metadata --from-html output`
+/// Then the page and transcript are stored as .gz files, and search and offline extraction read them transparently
+#[test]
+fn test_compressed_artifacts_are_read_back() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+
+    mocked(&server, temp_dir.path())
+        .args(["--compress", "gzip", "--db", "archive.sqlite", MOCK_EPISODE])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "✅ Transcript downloaded and saved to output/transcript.ttml.gz",
+        ));
+    let output = temp_dir.path().join("output");
+    assert!(output.join("episode.html.gz").is_file());
+    assert!(!output.join("episode.html").exists());
+    assert!(!output.join("transcript.ttml").exists());
+
+    mocked(&server, temp_dir.path())
+        .args(["--db", "archive.sqlite", "grep", "next week"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("See you next week."));
+    mocked(&server, temp_dir.path())
+        .args([
+            "metadata",
+            "--from-html",
+            "output",
+            "--output-dir",
+            "offline",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "✅ Transcript read from output/transcript.ttml.gz",
+        ));
+    let metadata = std::fs::read_to_string(
+        temp_dir
+            .path()
+            .join("offline/episodes/1000631244436/metadata.json"),
+    )
+    .unwrap();
+    assert!(metadata.contains("The Future of Podcasting"));
+}
+
+/// Scenario - Unknown compression
+/// Given an unsupported compression name
+/// When user runs `applecast-cli --compress brotli <url>`
+/// Then clap rejects it and lists the supported formats
+#[test]
+fn test_compress_rejects_unknown_format() {
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["--compress", "brotli", MOCK_EPISODE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected zstd or gzip"));
+}
+
+/// Scenario - Pack an archived episode for an LLM
+/// Given an episode processed into an archive from the mock server
+/// When user runs `applecast-cli --db <file> pack <episode id>`, then with `--format json -o -`
+/// Then a Markdown pack is written beside the episode, and JSON is printed with the transcript
+#[test]
+fn test_pack_archived_episode() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+    mocked(&server, temp_dir.path())
+        .args(["--db", "archive.sqlite", MOCK_EPISODE])
+        .assert()
+        .success();
+
+    mocked(&server, temp_dir.path())
+        .args([
+            "--db",
+            "archive.sqlite",
+            "pack",
+            "1000631244436",
+            "--chunk",
+            "5m",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("output/context-pack.md"));
+    let pack = std::fs::read_to_string(temp_dir.path().join("output/context-pack.md")).unwrap();
+    assert!(pack.starts_with("# The Future of Podcasting\n"));
+    assert!(pack.contains("## Transcript\n\n### 0:00–"));
+    assert!(pack.contains("Welcome back to the show."));
+
+    let output = mocked(&server, temp_dir.path())
+        .args([
+            "--db",
+            "archive.sqlite",
+            "pack",
+            MOCK_EPISODE,
+            "--format",
+            "json",
+            "--max-tokens",
+            "80k",
+            "-o",
+            "-",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let pack: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(pack["max_tokens"], 80_000);
+    assert!(pack["truncated_at"].is_null());
+    assert!(pack["transcript"][0]["paragraphs"][0]
+        .as_str()
+        .unwrap()
+        .contains("Welcome back"));
+}
+
+/// Scenario - Pack an episode missing from the archive
+/// Given a new, empty archive
+/// When user runs `applecast-cli --db <file> pack e1a2b3`
+/// Then it fails saying the episode is not archived
+#[test]
+fn test_pack_unknown_episode_fails() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db = temp_dir.path().join("archive.sqlite");
+    let mut cmd = Command::cargo_bin("applecast-cli").unwrap();
+
+    cmd.args(["--db", db.to_str().unwrap(), "pack", "e1a2b3"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("e1a2b3 is not in the archive"));
+}
+
+/// Scenario - Ask a question about an archived episode
+/// Given an episode processed into an archive from the mock server
+/// When user runs `applecast-cli --db <file> ask <episode id> "<question>"`, then with `--llm-command`
+/// Then the matching passage is printed with its timestamp, then the command's answer with citations
+#[test]
+fn test_ask_archived_episode() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+    mocked(&server, temp_dir.path())
+        .args(["--db", "archive.sqlite", MOCK_EPISODE])
+        .assert()
+        .success();
+
+    mocked(&server, temp_dir.path())
+        .args([
+            "--db",
+            "archive.sqlite",
+            "ask",
+            "1000631244436",
+            "What did they say about R&D budgets?",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[1] 0:00–0:07"))
+        .stdout(predicate::str::contains(
+            "Today we talk about R&D budgets <and more>.",
+        ))
+        .stdout(predicate::str::contains("See you next week").not());
+
+    mocked(&server, temp_dir.path())
+        .args([
+            "--db",
+            "archive.sqlite",
+            "ask",
+            MOCK_EPISODE,
+            "budgets?",
+            "--llm-command",
+            "grep -q 'Question: budgets?' && echo 'They talk about R&D budgets [1].'",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("They talk about R&D budgets [1]."))
+        .stdout(predicate::str::contains("[1] 0:00–0:07"));
+
+    mocked(&server, temp_dir.path())
+        .args(["--db", "archive.sqlite", "ask", "1000631244436", "quantum"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(
+            "Nothing in the transcript matches",
+        ));
+}
+
+/// Scenario - Query several archives as one
+/// Given an episode archived locally and an empty archive root on another disk
+/// When user runs `applecast-cli --archive <dir> --db <file> list`, then `grep`
+/// Then the episode is listed and found once, with the root's archive.sqlite created
+#[test]
+fn test_federated_archives() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let server = mock_episode_server();
+    mocked(&server, temp_dir.path())
+        .args(["--db", "local.sqlite", MOCK_EPISODE])
+        .assert()
+        .success();
+    std::fs::create_dir(temp_dir.path().join("nas")).unwrap();
+
+    let federated = |args: &[&str]| {
+        let mut cmd = mocked(&server, temp_dir.path());
+        cmd.args(["--archive", "nas", "--db", "local.sqlite"])
+            .args(args);
+        cmd
+    };
+    let output = federated(&["--json", "list"]).output().unwrap();
+    assert!(output.status.success());
+    let episodes: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(episodes.as_array().unwrap().len(), 1);
+    assert_eq!(episodes[0]["title"], "The Future of Podcasting");
+    assert!(temp_dir.path().join("nas/archive.sqlite").is_file());
+
+    federated(&["grep", "R&D budgets"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("The Future of Podcasting"))
+        .stdout(predicate::str::contains(
+            "0:02 SPEAKER_1: Today we talk about R&D budgets",
+        ));
+
+    // A secondary archive that does not exist is an error, not a new empty database
+    mocked(&server, temp_dir.path())
+        .args(["--db", "local.sqlite", "--archive", "typo.sqlite", "list"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("does not exist"));
+    assert!(!temp_dir.path().join("typo.sqlite").exists());
+}
@@ -0,0 +1,197 @@
+//! A local stand-in for Apple's servers, so CLI tests run the full pipeline offline
+//!
+//! The server answers each route from a list of recorded responses, in order,
+//! repeating the last one; unknown routes get a 404. Tests point the CLI at it
+//! through `APPLECAST_ORIGIN_OVERRIDES`, which [`MockServer::overrides`] builds.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+/// Origins the recorded fixtures link to
+pub const ORIGINS: &[&str] = &[
+    "https://podcasts.apple.com",
+    "https://itunes.apple.com",
+    "https://transcripts.example.com",
+    "https://audio.example.com",
+];
+
+/// A recorded episode page whose transcript and audio live on the example origins
+pub const EPISODE_PAGE: &str = "tests/fixtures/pages/serialized-server-data.html";
+
+/// A show page listing the episode page's episode
+pub const SHOW_PAGE: &str = "tests/fixtures/show.html";
+
+/// The transcript the episode page links to
+pub const TRANSCRIPT: &str = "tests/fixtures/transcript.ttml";
+
+/// A recorded iTunes lookup response for the episode's show
+pub const LOOKUP: &str = "tests/fixtures/api/lookup.json";
+
+/// Reads a fixture file, relative to the crate root
+pub fn fixture(path: &str) -> Vec<u8> {
+    std::fs::read(path).unwrap_or_else(|e| panic!("Missing fixture {}: {}", path, e))
+}
+
+/// One recorded response
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl MockResponse {
+    /// A 200 with `body`
+    pub fn ok(body: impl Into<Vec<u8>>) -> MockResponse {
+        MockResponse {
+            status: 200,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    /// An empty response with `status`
+    pub fn status(status: u16) -> MockResponse {
+        MockResponse {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// A redirect to `location`
+    pub fn redirect(status: u16, location: &str) -> MockResponse {
+        MockResponse::status(status).header("Location", location)
+    }
+
+    /// The response with an extra header
+    pub fn header(mut self, name: &str, value: &str) -> MockResponse {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// Routes and their remaining responses, keyed by path and query, or path alone
+type Routes = HashMap<String, Vec<MockResponse>>;
+
+/// A running mock server
+pub struct MockServer {
+    base: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+/// Collects routes before the server starts
+#[derive(Default)]
+pub struct MockServerBuilder {
+    routes: Routes,
+}
+
+impl MockServerBuilder {
+    /// Answers `route` with `responses` in turn, then keeps repeating the last one
+    ///
+    /// A route with a query only matches that exact query; one without matches any.
+    pub fn route(mut self, route: &str, responses: Vec<MockResponse>) -> MockServerBuilder {
+        assert!(!responses.is_empty(), "Route {} needs a response", route);
+        self.routes.insert(route.to_string(), responses);
+        self
+    }
+
+    /// Binds a local port and serves the routes on a background thread
+    pub fn start(self) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let routes = Arc::new(Mutex::new(self.routes));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&requests);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (routes, log) = (Arc::clone(&routes), Arc::clone(&log));
+                std::thread::spawn(move || serve(stream, &routes, &log));
+            }
+        });
+        MockServer { base, requests }
+    }
+}
+
+impl MockServer {
+    pub fn builder() -> MockServerBuilder {
+        MockServerBuilder::default()
+    }
+
+    /// The server's `http://127.0.0.1:PORT` origin
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    /// An `APPLECAST_ORIGIN_OVERRIDES` value sending every fixture origin here
+    pub fn overrides(&self) -> String {
+        ORIGINS
+            .iter()
+            .map(|origin| format!("{}={}", origin, self.base))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Requests served so far, as `METHOD /path?query`
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// How many requests asked for `path`, ignoring the query
+    pub fn hits(&self, path: &str) -> usize {
+        self.requests()
+            .iter()
+            .filter_map(|request| request.split_whitespace().nth(1))
+            .filter(|target| target.split('?').next() == Some(path))
+            .count()
+    }
+}
+
+/// Reads one request from `stream` and writes its route's next response
+fn serve(mut stream: TcpStream, routes: &Mutex<Routes>, log: &Mutex<Vec<String>>) {
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(&stream);
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    let mut line = String::new();
+    while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+        line.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+    log.lock().unwrap().push(format!("{} {}", method, target));
+
+    let response = {
+        let mut routes = routes.lock().unwrap();
+        let path = target.split('?').next().unwrap_or_default();
+        let key = if routes.contains_key(&target) {
+            target.as_str()
+        } else {
+            path
+        };
+        match routes.get_mut(key) {
+            Some(responses) if responses.len() > 1 => responses.remove(0),
+            Some(responses) => responses[0].clone(),
+            None => MockResponse::status(404),
+        }
+    };
+
+    let mut head = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    let _ = stream.write_all(head.as_bytes());
+    if method != "HEAD" {
+        let _ = stream.write_all(&response.body);
+    }
+}